@@ -1,21 +1,54 @@
+#[path = "disease/batch.rs"]
+mod batch;
+#[path = "disease/bitmap.rs"]
+mod bitmap;
+#[path = "disease/broadcast.rs"]
+mod broadcast;
+#[path = "disease/browser.rs"]
+mod browser;
+#[path = "disease/dispatch.rs"]
+mod dispatch;
+#[path = "disease/fingerprint.rs"]
+mod fingerprint;
+#[path = "disease/gas.rs"]
+mod gas;
+#[path = "disease/immutables.rs"]
+mod immutables;
 #[path = "disease/opts.rs"]
 mod opts;
+#[path = "disease/patch.rs"]
+mod patch;
 #[path = "disease/selectors.rs"]
 mod selectors;
+#[path = "disease/sstore2.rs"]
+mod sstore2;
+#[path = "disease/stats.rs"]
+mod stats;
+#[path = "disease/tui.rs"]
+mod tui;
+#[path = "disease/xref.rs"]
+mod xref;
 
-use crate::opts::Opts;
+use crate::opts::{Command, OnUnknown, Opts, Selector};
 use crate::selectors::DisplayOp;
 
 use etk_analyze::blocks::basic::Separator;
+use etk_analyze::dispatch::entries;
+use etk_analyze::immutable;
+use etk_analyze::revert::reverts;
+use etk_analyze::stack::{reachable, stacks};
 
-use etk_asm::disasm::{Disassembler, Offset};
+use etk_asm::disasm::Disassembler;
+use etk_asm::ops::{Experimental, OpcodeTableError, Specifier};
 
 use etk_cli::errors::WithSources;
+use etk_cli::io::create_output;
 
-use snafu::{Backtrace, Snafu};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
 
-use std::fs::File;
-use std::io::Write;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{Read, Write};
 
 use structopt::StructOpt;
 
@@ -26,6 +59,95 @@ enum Error {
         source: std::io::Error,
         backtrace: Backtrace,
     },
+
+    #[snafu(context(false), display("could not report on the contracts"))]
+    Batch { source: batch::Error },
+
+    #[snafu(context(false), display("could not build the bitmaps"))]
+    Bitmap { source: bitmap::Error },
+
+    #[snafu(context(false), display("could not read the deployments"))]
+    Broadcast { source: broadcast::Error },
+
+    #[snafu(context(false), display("could not list selectors"))]
+    Dispatch { source: dispatch::Error },
+
+    #[snafu(context(false), display("could not fingerprint the code"))]
+    Fingerprint { source: fingerprint::Error },
+
+    #[snafu(context(false), display("could not bound gas"))]
+    Gas { source: gas::Error },
+
+    #[snafu(context(false), display("could not list immutables"))]
+    Immutables { source: immutables::Error },
+
+    #[snafu(context(false), display("could not patch the code"))]
+    Patch { source: patch::Error },
+
+    #[snafu(
+        context(false),
+        display("could not split the code into data contracts")
+    )]
+    Sstore2 { source: sstore2::Error },
+
+    #[snafu(context(false), display("could not summarize the code"))]
+    Stats { source: stats::Error },
+
+    #[snafu(context(false), display("could not run the browser"))]
+    Tui { source: tui::Error },
+
+    #[snafu(context(false), display("could not list cross-references"))]
+    Xref { source: xref::Error },
+
+    #[snafu(display("could not read the opcode table"))]
+    Opcodes { source: OpcodeTableError },
+
+    #[snafu(display("no immutable placeholder is pushed at offset {:#x}", offset))]
+    NotImmutable { offset: usize, backtrace: Backtrace },
+
+    #[snafu(display("the dispatcher doesn't jump anywhere for selector {:#010x}", selector))]
+    NoFunction { selector: u32, backtrace: Backtrace },
+
+    #[snafu(display("unknown opcode {:#04x} at offset {:#x}", opcode, offset))]
+    UnknownOpcode {
+        opcode: u8,
+        offset: usize,
+        backtrace: Backtrace,
+    },
+}
+
+/// How each line of the disassembly is laid out.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    /// Start each line with the offset of its instruction.
+    offsets: bool,
+
+    /// Follow the offset with the bytes the instruction was encoded as.
+    bytes: bool,
+}
+
+impl Layout {
+    /// Bytes narrower than this are padded, so the mnemonics line up.
+    const BYTES_WIDTH: usize = 16;
+
+    fn line<T: fmt::Display>(&self, offset: usize, bytes: &[u8], item: T) -> String {
+        let mut line = String::new();
+
+        if self.offsets {
+            line.push_str(&format!("{: >4x}:   ", offset));
+        }
+
+        if self.bytes {
+            line.push_str(&format!(
+                "{:<width$} ",
+                hex::encode(bytes),
+                width = Self::BYTES_WIDTH
+            ));
+        }
+
+        line.push_str(&item.to_string());
+        line
+    }
 }
 
 fn main() {
@@ -43,16 +165,85 @@ fn main() {
 fn run() -> Result<(), Error> {
     let opts = Opts::from_args();
 
+    match opts.cmd {
+        Some(Command::Batch(batch)) => return Ok(batch::run(batch)?),
+        Some(Command::Bitmap(bitmap)) => return Ok(bitmap::run(bitmap)?),
+        Some(Command::Broadcast(broadcast)) => return Ok(broadcast::run(broadcast)?),
+        Some(Command::Fingerprint(fingerprint)) => return Ok(fingerprint::run(fingerprint)?),
+        Some(Command::Gas(gas)) => return Ok(gas::run(gas)?),
+        Some(Command::Immutables(immutables)) => return Ok(immutables::run(immutables)?),
+        Some(Command::Patch(patch)) => return Ok(patch::run(patch)?),
+        Some(Command::Selectors(selectors)) => return Ok(dispatch::run(selectors)?),
+        Some(Command::Sstore2(sstore2)) => return Ok(sstore2::run(sstore2)?),
+        Some(Command::Stats(stats)) => return Ok(stats::run(stats)?),
+        Some(Command::Tui(tui)) => return Ok(tui::run(tui)?),
+        Some(Command::Xref(xref)) => return Ok(xref::run(xref)?),
+        None => (),
+    }
+
     let mut input = opts.src.open()?;
-    let mut disasm = Disassembler::new();
+    let mut code = Vec::new();
+    input.read_to_end(&mut code)?;
 
-    std::io::copy(&mut input, &mut disasm)?;
+    for immutable in &opts.immutables {
+        let offset = immutable.offset;
+        ensure!(
+            immutable::substitute(&mut code, offset, immutable.value),
+            NotImmutable { offset }
+        );
+    }
+
+    let placeholders: BTreeSet<_> = immutable::references(&code).into_iter().collect();
+
+    let mut experimental = match opts.experimental {
+        true => Experimental::proposals(),
+        false => Experimental::new(),
+    };
+
+    if let Some(path) = opts.opcodes {
+        let text = std::fs::read_to_string(path)?;
+        for op in Experimental::from_toml(&text).context(Opcodes)?.iter() {
+            experimental.insert(op.clone()).context(Opcodes)?;
+        }
+    }
+
+    let mut disasm = Disassembler::new();
+    disasm.set_experimental(experimental.clone());
+    disasm.write_all(&code)?;
 
     let mut out: Box<dyn Write> = match opts.out_file {
-        Some(path) => Box::new(File::create(path)?),
+        Some(path) => create_output(path)?,
         None => Box::new(std::io::stdout()),
     };
 
+    let reasons: BTreeMap<_, _> = reverts(&code)
+        .into_iter()
+        .map(|r| (r.start, r.reason))
+        .collect();
+
+    let stacks = if opts.stack {
+        stacks(&code)
+    } else {
+        BTreeMap::new()
+    };
+
+    // Offsets of the instructions to write, when only one function is wanted.
+    let function = match opts.function {
+        Some(Selector(selector)) => {
+            let entry = entries(&code)
+                .into_iter()
+                .find(|e| e.selector == selector)
+                .context(NoFunction { selector })?;
+            Some(reachable(&code, entry.offset))
+        }
+        None => None,
+    };
+
+    let layout = Layout {
+        offsets: !opts.no_offsets,
+        bytes: opts.show_bytes,
+    };
+
     let mut separator = Separator::new();
 
     separator.push_all(disasm.ops());
@@ -62,17 +253,108 @@ fn run() -> Result<(), Error> {
         .into_iter()
         .chain(separator.finish().into_iter());
 
+    // Where the region after an unknown opcode started, while it's being
+    // skipped or written as data. The region ends at the next `jumpdest`, the
+    // first place execution could plausibly resume.
+    let mut region: Option<usize> = None;
+
     for block in basic_blocks {
         let mut offset = block.offset;
+        let mut written = false;
+
         for op in block.ops {
-            let len = op.size();
-            let off = Offset::new(offset, DisplayOp(op));
-            offset += len as usize;
+            let spec = op.specifier();
+            let extra = experimental.by_opcode(u8::from(spec));
+            let start = offset;
+            offset += match extra {
+                Some(extra) => extra.size(),
+                None => spec.size() as usize,
+            };
+
+            if let Some(ref function) = function {
+                if !function.contains(&start) {
+                    continue;
+                }
+            }
+
+            if spec == Specifier::JumpDest {
+                if let Some(from) = region.take() {
+                    if opts.on_unknown == OnUnknown::Skip {
+                        let note = format!("# skipped {} byte(s)", start - from);
+                        writeln!(out, "{}", layout.line(from, &[], note))?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+
+            if region.is_none() && !spec.is_defined() && extra.is_none() {
+                match opts.on_unknown {
+                    OnUnknown::Raw => (),
+                    OnUnknown::Abort => {
+                        return UnknownOpcode {
+                            opcode: u8::from(spec),
+                            offset: start,
+                        }
+                        .fail()
+                    }
+                    OnUnknown::Skip | OnUnknown::Data => region = Some(start),
+                }
+            }
+
+            match (region, opts.on_unknown) {
+                (Some(_), OnUnknown::Skip) => continue,
+                (Some(_), OnUnknown::Data) => {
+                    for (idx, byte) in code[start..offset].iter().enumerate() {
+                        let raw = format!("%raw({:#04x})", byte);
+                        let at = start + idx;
+                        writeln!(out, "{}", layout.line(at, &code[at..=at], raw))?;
+                    }
+                    continue;
+                }
+                _ => (),
+            }
 
-            writeln!(out, "{}", off)?;
+            if placeholders.contains(&start) {
+                let note = "# immutable placeholder";
+                writeln!(out, "{}", layout.line(start, &[], note))?;
+            }
+
+            if let Some(reason) = reasons.get(&start) {
+                let note = format!("# reverts with {}", reason);
+                writeln!(out, "{}", layout.line(start, &[], note))?;
+            }
+
+            written = true;
+
+            let bytes = &code[start..offset];
+
+            if let Some(extra) = extra {
+                let text = match extra.immediate {
+                    0 => extra.mnemonic.clone(),
+                    _ => format!("{} 0x{}", extra.mnemonic, hex::encode(&bytes[1..])),
+                };
+                let text = format!("{:<24} # experimental ({})", text, extra.origin);
+                writeln!(out, "{}", layout.line(start, bytes, text))?;
+                continue;
+            }
+
+            match stacks.get(&start) {
+                Some(stack) => {
+                    let text = format!("{:<24} # {}", DisplayOp(op).to_string(), stack);
+                    writeln!(out, "{}", layout.line(start, bytes, text))?;
+                }
+                None => writeln!(out, "{}", layout.line(start, bytes, DisplayOp(op)))?,
+            }
+        }
+
+        if region.is_none() && (written || function.is_none()) {
+            writeln!(out)?;
         }
+    }
 
-        writeln!(out)?;
+    if let (Some(from), OnUnknown::Skip) = (region, opts.on_unknown) {
+        let note = format!("# skipped {} byte(s)", code.len() - from);
+        writeln!(out, "{}", layout.line(from, &[], note))?;
     }
 
     Ok(())
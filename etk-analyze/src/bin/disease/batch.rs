@@ -0,0 +1,508 @@
+//! Reporting on many contracts at once.
+
+use crate::opts::Format;
+
+use etk_analyze::compiler::{identify, Compiler};
+use etk_analyze::dispatch::entries;
+use etk_analyze::signatures::{find, SIGNATURES};
+
+use etk_cli::io::{create_output, read_hex};
+use etk_cli::rpc::{self, Cache, Client};
+
+use serde_json::json;
+
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(context(false))]
+    Rpc { source: rpc::Error },
+
+    #[snafu(display("could not read `{}`", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("line {} isn't an address: `{}`", line, text))]
+    InvalidAddress {
+        line: usize,
+        text: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("the code of {} isn't hex", address))]
+    InvalidCode {
+        address: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("`{}` has no contracts to report on", path.display()))]
+    Empty { path: PathBuf, backtrace: Backtrace },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(
+        parse(from_os_str),
+        help = "a directory of `.hex` files, or with --rpc, a file with one address per line"
+    )]
+    path: PathBuf,
+
+    #[structopt(
+        long = "rpc",
+        help = "url of the JSON-RPC endpoint to fetch the code of each address from"
+    )]
+    rpc: Option<String>,
+
+    #[structopt(
+        long = "block",
+        default_value = "latest",
+        help = "block number or tag to fetch the code at"
+    )]
+    block: String,
+
+    #[structopt(
+        long = "cache",
+        parse(from_os_str),
+        requires = "rpc",
+        help = "directory to keep responses in, so later runs at the same block can skip the endpoint"
+    )]
+    cache: Option<PathBuf>,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the report"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+/// Which kind of proxy some code is, from the first matching fragment.
+fn proxy(code: &[u8]) -> Option<&'static str> {
+    const KINDS: &[(&str, &str)] = &[
+        ("minimal-proxy", "eip-1167"),
+        ("erc1967-implementation-slot", "erc-1967"),
+        ("openzeppelin-proxy-delegate", "delegate"),
+    ];
+
+    let found = find(code, SIGNATURES);
+
+    KINDS
+        .iter()
+        .find(|(name, _)| found.iter().any(|m| m.signature.name == *name))
+        .map(|(_, kind)| *kind)
+}
+
+/// What was found in one contract.
+#[derive(Debug)]
+struct Report {
+    /// The file name or address the code came from.
+    name: String,
+
+    size: usize,
+    selectors: Vec<u32>,
+    proxy: Option<&'static str>,
+    compiler: Option<Compiler>,
+}
+
+impl Report {
+    fn new(name: String, code: &[u8]) -> Self {
+        Self {
+            name,
+            size: code.len(),
+            selectors: entries(code).iter().map(|e| e.selector).collect(),
+            proxy: proxy(code),
+            compiler: identify(code),
+        }
+    }
+}
+
+/// Totals over every contract.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Summary {
+    contracts: usize,
+
+    /// How many contracts have no code at all.
+    empty: usize,
+
+    total_size: usize,
+
+    /// The name and size of the biggest contract.
+    largest: Option<(String, usize)>,
+
+    /// How many contracts are each kind of proxy.
+    proxies: BTreeMap<&'static str, usize>,
+
+    /// How many contracts dispatch on each selector.
+    selectors: BTreeMap<u32, usize>,
+}
+
+impl Summary {
+    fn new(reports: &[Report]) -> Self {
+        let mut summary = Self::default();
+
+        for report in reports {
+            summary.contracts += 1;
+            summary.total_size += report.size;
+
+            if report.size == 0 {
+                summary.empty += 1;
+            }
+
+            match summary.largest {
+                Some((_, size)) if size >= report.size => (),
+                _ => summary.largest = Some((report.name.clone(), report.size)),
+            }
+
+            if let Some(kind) = report.proxy {
+                *summary.proxies.entry(kind).or_default() += 1;
+            }
+
+            for selector in &report.selectors {
+                *summary.selectors.entry(*selector).or_default() += 1;
+            }
+        }
+
+        summary
+    }
+
+    /// Selectors shared by more than one contract, most common first.
+    fn shared(&self) -> Vec<(u32, usize)> {
+        let mut shared: Vec<_> = self
+            .selectors
+            .iter()
+            .filter(|(_, count)| **count > 1)
+            .map(|(s, c)| (*s, *c))
+            .collect();
+
+        shared.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        shared
+    }
+}
+
+/// The `.hex` files in `dir`, sorted by name.
+fn from_dir(dir: &Path) -> Result<Vec<Report>, Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).context(Read { path: dir })? {
+        let path = entry?.path();
+        if path.extension() == Some("hex".as_ref()) {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    let mut reports = Vec::with_capacity(paths.len());
+    for path in paths {
+        let code = read_hex(&path).context(Read { path: &path })?;
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        reports.push(Report::new(name, &code));
+    }
+
+    Ok(reports)
+}
+
+/// The addresses in `text`, one per line, ignoring blank lines and `#`
+/// comments.
+fn addresses(text: &str) -> Result<Vec<String>, Error> {
+    let mut found = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let digits = line.strip_prefix("0x").unwrap_or(line);
+        ensure!(
+            digits.len() == 40 && digits.bytes().all(|b| b.is_ascii_hexdigit()),
+            InvalidAddress {
+                line: idx + 1,
+                text: line,
+            }
+        );
+
+        found.push(format!("0x{}", digits.to_ascii_lowercase()));
+    }
+
+    Ok(found)
+}
+
+fn from_rpc(mut client: Client, list: &Path, block: &str) -> Result<Vec<Report>, Error> {
+    let text = std::fs::read_to_string(list).context(Read { path: list })?;
+
+    let mut reports = Vec::new();
+    for address in addresses(&text)? {
+        let code: String = client.request_at("eth_getCode", &[&address, block])?;
+        let code = hex::decode(code.trim_start_matches("0x"))
+            .ok()
+            .context(InvalidCode { address: &address })?;
+
+        reports.push(Report::new(address, &code));
+    }
+
+    Ok(reports)
+}
+
+fn selector_list(selectors: &[u32]) -> Vec<String> {
+    selectors.iter().map(|s| format!("{:#010x}", s)).collect()
+}
+
+fn write_text<W: Write>(out: &mut W, reports: &[Report], summary: &Summary) -> Result<(), Error> {
+    for report in reports {
+        write!(
+            out,
+            "{}: {} bytes, {} functions",
+            report.name,
+            report.size,
+            report.selectors.len()
+        )?;
+
+        if let Some(ref c) = report.compiler {
+            write!(out, ", {} {}", c.toolchain, c.versions)?;
+        }
+
+        if let Some(kind) = report.proxy {
+            write!(out, ", {} proxy", kind)?;
+        }
+
+        writeln!(out)?;
+
+        if !report.selectors.is_empty() {
+            writeln!(
+                out,
+                "    selectors: {}",
+                selector_list(&report.selectors).join(" ")
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "contracts: {} ({} empty)",
+        summary.contracts, summary.empty
+    )?;
+    writeln!(out, "total size: {} bytes", summary.total_size)?;
+
+    if let Some((ref name, size)) = summary.largest {
+        writeln!(out, "largest: {} ({} bytes)", name, size)?;
+    }
+
+    for (kind, count) in &summary.proxies {
+        writeln!(out, "{} proxies: {}", kind, count)?;
+    }
+
+    let shared = summary.shared();
+    if !shared.is_empty() {
+        writeln!(out, "shared selectors:")?;
+        for (selector, count) in shared {
+            writeln!(out, "    {:#010x} in {} contracts", selector, count)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, reports: &[Report], summary: &Summary) -> Result<(), Error> {
+    let contracts: Vec<_> = reports
+        .iter()
+        .map(|r| {
+            let compiler = r.compiler.map(|c| {
+                json!({
+                    "toolchain": c.toolchain.to_string(),
+                    "versions": c.versions.to_string(),
+                })
+            });
+
+            json!({
+                "name": r.name,
+                "size": r.size,
+                "selectors": selector_list(&r.selectors),
+                "proxy": r.proxy,
+                "compiler": compiler,
+            })
+        })
+        .collect();
+
+    let shared: Vec<_> = summary
+        .shared()
+        .into_iter()
+        .map(|(s, c)| json!({ "selector": format!("{:#010x}", s), "contracts": c }))
+        .collect();
+
+    let largest = summary
+        .largest
+        .as_ref()
+        .map(|(name, size)| json!({ "name": name, "size": size }));
+
+    let report = json!({
+        "contracts": contracts,
+        "summary": {
+            "contracts": summary.contracts,
+            "empty": summary.empty,
+            "total_size": summary.total_size,
+            "largest": largest,
+            "proxies": summary.proxies,
+            "shared_selectors": shared,
+        },
+    });
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let reports = match opts.rpc {
+        Some(ref url) => {
+            let mut client = Client::new(url);
+            if let Some(dir) = opts.cache {
+                client = client.with_cache(Cache::new(dir));
+            }
+
+            from_rpc(client, &opts.path, &opts.block)?
+        }
+        None => from_dir(&opts.path)?,
+    };
+
+    ensure!(!reports.is_empty(), Empty { path: opts.path });
+
+    let summary = Summary::new(&reports);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &reports, &summary),
+        Format::Json => write_json(&mut out, &reports, &summary),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    const MINIMAL_PROXY: [u8; 45] = hex!(
+        "363d3d373d3d3d363d73bebebebebebebebebebebebebebebebebebebebe5af43d82803e903d91602b57fd5bf3"
+    );
+
+    /// A dispatcher for `0xa9059cbb` and `0x70a08231`.
+    const TOKEN: [u8; 31] = hex!(
+        "
+        6000 35 60e0 1c
+        80 63 a9059cbb 14 601b 57
+        80 63 70a08231 14 601d 57
+        00
+        5b 00
+        5b 00
+        "
+    );
+
+    #[test]
+    fn reports() {
+        let report = Report::new("proxy.hex".into(), &MINIMAL_PROXY);
+        assert_eq!(report.size, 45);
+        assert_eq!(report.proxy, Some("eip-1167"));
+        assert!(report.selectors.is_empty());
+
+        let report = Report::new("token.hex".into(), &TOKEN);
+        assert_eq!(report.selectors, [0xa9059cbb, 0x70a08231]);
+        assert_eq!(report.proxy, None);
+    }
+
+    #[test]
+    fn summary() {
+        let reports = [
+            Report::new("a".into(), &TOKEN),
+            Report::new("b".into(), &MINIMAL_PROXY),
+            Report::new("c".into(), &TOKEN),
+            Report::new("d".into(), &[]),
+        ];
+
+        let summary = Summary::new(&reports);
+        assert_eq!(summary.contracts, 4);
+        assert_eq!(summary.empty, 1);
+        assert_eq!(summary.total_size, 31 + 45 + 31);
+        assert_eq!(summary.largest, Some(("b".into(), 45)));
+        assert_eq!(summary.proxies.get("eip-1167"), Some(&1));
+        assert_eq!(summary.shared(), [(0x70a08231, 2), (0xa9059cbb, 2)]);
+    }
+
+    #[test]
+    fn directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.hex"), hex::encode(MINIMAL_PROXY)).unwrap();
+        std::fs::write(dir.path().join("a.hex"), hex::encode(TOKEN)).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let reports = from_dir(dir.path()).unwrap();
+        let names: Vec<_> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["a.hex", "b.hex"]);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &reports, &Summary::new(&reports)).unwrap();
+
+        let expected = "\
+a.hex: 31 bytes, 2 functions
+    selectors: 0xa9059cbb 0x70a08231
+b.hex: 45 bytes, 0 functions, minimal-proxy unknown version, eip-1167 proxy
+
+contracts: 2 (0 empty)
+total size: 76 bytes
+largest: b.hex (45 bytes)
+eip-1167 proxies: 1
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn address_list() {
+        let text = "
+            # Tokens
+            0x5FbDB2315678afecb367f032d93F642f64180aa3
+            e7f1725e7734ce288f8367e1bb143e90bb3f0512 # no prefix
+        ";
+
+        assert_eq!(
+            addresses(text).unwrap(),
+            [
+                "0x5fbdb2315678afecb367f032d93f642f64180aa3",
+                "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512",
+            ]
+        );
+
+        assert_matches!(
+            addresses("0x5fbdb2315678afecb367f032d93f642f64180aa3\n0x1234"),
+            Err(Error::InvalidAddress { line: 2, .. })
+        );
+    }
+}
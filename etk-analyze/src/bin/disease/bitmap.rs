@@ -0,0 +1,122 @@
+//! Writing the jump destination and push data bitmaps of a contract.
+
+use crate::opts::Format;
+
+use etk_analyze::bitmap::{bitmaps, Bitmaps};
+
+use etk_cli::io::{create_output, InputSource};
+
+use serde_json::json;
+
+use snafu::{Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the bitmaps"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+fn write_text<W: Write>(out: &mut W, maps: &Bitmaps) -> Result<(), Error> {
+    writeln!(
+        out,
+        "jumpdests 0x{}",
+        hex::encode(maps.jumpdests.as_bytes())
+    )?;
+    writeln!(out, "data      0x{}", hex::encode(maps.data.as_bytes()))?;
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, maps: &Bitmaps) -> Result<(), Error> {
+    let json = json!({
+        "length": maps.jumpdests.len(),
+        "jumpdests": format!("0x{}", hex::encode(maps.jumpdests.as_bytes())),
+        "data": format!("0x{}", hex::encode(maps.data.as_bytes())),
+        "jumpdest_offsets": maps.jumpdests.ones().collect::<Vec<_>>(),
+    });
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&json).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let maps = bitmaps(&code);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &maps),
+        Format::Json => write_json(&mut out, &maps),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    // 0: jumpdest; 1: push2 0x5b5b; 4: jumpdest; 5: push1 0x5b
+    const CODE: [u8; 7] = hex!("5b 615b5b 5b 605b");
+
+    #[test]
+    fn text_bitmaps() {
+        let mut out = Vec::new();
+        write_text(&mut out, &bitmaps(&CODE)).unwrap();
+
+        let expected = "jumpdests 0x11\ndata      0x4c\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn json_bitmaps() {
+        let mut out = Vec::new();
+        write_json(&mut out, &bitmaps(&CODE)).unwrap();
+
+        let actual: Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!({
+            "length": 7,
+            "jumpdests": "0x11",
+            "data": "0x4c",
+            "jumpdest_offsets": [0, 4],
+        });
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,490 @@
+//! Reading the code a deployment sent from a Foundry broadcast file, or from a
+//! raw signed transaction.
+
+use etk_cli::io::{create_output, open_input, read_hex};
+use etk_cli::parse::Hex;
+
+use serde_json::Value;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("the broadcast file isn't valid JSON"))]
+    Json {
+        source: serde_json::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("the broadcast file has no `transactions` list"))]
+    NoTransactions { backtrace: Backtrace },
+
+    #[snafu(display("transaction {} has no input, or it isn't hex", index))]
+    InvalidInput { index: usize, backtrace: Backtrace },
+
+    #[snafu(display("the raw transaction isn't valid: {}", reason))]
+    InvalidTransaction {
+        reason: &'static str,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("the raw transaction calls a contract instead of creating one"))]
+    NotCreation { backtrace: Backtrace },
+
+    #[snafu(display("no deployment matches the filters"))]
+    NoDeployment { backtrace: Backtrace },
+
+    #[snafu(display(
+        "{} deployments match, but only one can be extracted (use --contract or --index)",
+        count
+    ))]
+    Ambiguous { count: usize, backtrace: Backtrace },
+
+    #[snafu(display("{} deployment(s) don't match the expected code", count))]
+    Mismatch { count: usize, backtrace: Backtrace },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(
+        parse(from_os_str),
+        required_unless = "raw-tx",
+        conflicts_with = "raw-tx",
+        help = "path to a Foundry broadcast file, like `broadcast/Deploy.s.sol/1/run-latest.json` (`-` for standard input)"
+    )]
+    file: Option<PathBuf>,
+
+    #[structopt(
+        long = "raw-tx",
+        help = "a signed contract creation transaction, encoded in hexadecimal (with 0x prefix)"
+    )]
+    raw_tx: Option<Hex<Vec<u8>>>,
+
+    #[structopt(
+        long = "contract",
+        help = "only include deployments of the contract NAME"
+    )]
+    contract: Option<String>,
+
+    #[structopt(
+        long = "index",
+        help = "only include the deployment in transaction INDEX"
+    )]
+    index: Option<usize>,
+
+    #[structopt(
+        long = "expected",
+        parse(from_os_str),
+        help = "hex file of the init code each deployment is expected to send"
+    )]
+    expected: Option<PathBuf>,
+
+    #[structopt(
+        long = "extract",
+        help = "write the input of the one matching deployment as hex, instead of a summary"
+    )]
+    extract: bool,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+/// A contract creation found in a broadcast file or transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Deployment {
+    /// Position of the transaction in the broadcast file.
+    index: usize,
+
+    /// The name Foundry gave the contract, if known.
+    contract: Option<String>,
+
+    /// The address Foundry expected the contract to be deployed at.
+    address: Option<String>,
+
+    /// The init code, followed by any constructor arguments.
+    input: Vec<u8>,
+}
+
+/// Decode a `0x` prefixed hex string.
+fn decode(text: &str) -> Option<Vec<u8>> {
+    hex::decode(text.strip_prefix("0x").unwrap_or(text)).ok()
+}
+
+/// The contract creations in the broadcast file `json`.
+///
+/// Deployments through the `CREATE2` factory send a 32 byte salt before the
+/// init code, which is left out.
+fn deployments(json: &str) -> Result<Vec<Deployment>, Error> {
+    let json: Value = serde_json::from_str(json).context(Json)?;
+    let transactions = json["transactions"].as_array().context(NoTransactions)?;
+
+    let mut found = Vec::new();
+
+    for (index, tx) in transactions.iter().enumerate() {
+        let salted = match tx["transactionType"].as_str() {
+            Some("CREATE") => false,
+            Some("CREATE2") => true,
+            _ => continue,
+        };
+
+        // Older versions of Foundry called the input `data`.
+        let inner = &tx["transaction"];
+        let input = inner["input"]
+            .as_str()
+            .or_else(|| inner["data"].as_str())
+            .and_then(decode)
+            .context(InvalidInput { index })?;
+
+        let input = match salted {
+            true => input.get(32..).context(InvalidInput { index })?.to_vec(),
+            false => input,
+        };
+
+        found.push(Deployment {
+            index,
+            contract: tx["contractName"].as_str().map(str::to_owned),
+            address: tx["contractAddress"].as_str().map(str::to_owned),
+            input,
+        });
+    }
+
+    Ok(found)
+}
+
+/// An RLP item: either a string of bytes, or a list of items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rlp<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<Rlp<'a>>),
+}
+
+/// Split the first RLP item off of `data`, returning it and what follows.
+fn rlp(data: &[u8]) -> Option<(Rlp<'_>, &[u8])> {
+    let (&prefix, rest) = data.split_first()?;
+
+    let (is_list, len, rest) = match prefix {
+        0x00..=0x7f => return Some((Rlp::Bytes(&data[..1]), rest)),
+        0x80..=0xb7 => (false, usize::from(prefix - 0x80), rest),
+        0xc0..=0xf7 => (true, usize::from(prefix - 0xc0), rest),
+        _ => {
+            let is_list = prefix >= 0xf8;
+            let size = usize::from(prefix - if is_list { 0xf7 } else { 0xb7 });
+            if size > std::mem::size_of::<usize>() || rest.len() < size {
+                return None;
+            }
+
+            let len = rest[..size]
+                .iter()
+                .fold(0usize, |acc, b| (acc << 8) | usize::from(*b));
+            (is_list, len, &rest[size..])
+        }
+    };
+
+    if rest.len() < len {
+        return None;
+    }
+
+    let (payload, rest) = rest.split_at(len);
+    if !is_list {
+        return Some((Rlp::Bytes(payload), rest));
+    }
+
+    let mut items = Vec::new();
+    let mut remaining = payload;
+    while !remaining.is_empty() {
+        let (item, after) = rlp(remaining)?;
+        items.push(item);
+        remaining = after;
+    }
+
+    Some((Rlp::List(items), rest))
+}
+
+/// The deployment sent by the signed transaction `raw`, either a legacy
+/// transaction, or a typed one from EIP-2930 or EIP-1559.
+fn transaction(raw: &[u8]) -> Result<Deployment, Error> {
+    // Positions of the recipient and the input in the transaction's fields.
+    let (body, to, input) = match raw.first() {
+        Some(0xc0..=0xff) => (raw, 3, 5),
+        Some(0x01) => (&raw[1..], 4, 6),
+        Some(0x02) => (&raw[1..], 5, 7),
+        _ => {
+            return InvalidTransaction {
+                reason: "unsupported transaction type",
+            }
+            .fail()
+        }
+    };
+
+    let fields = match rlp(body) {
+        Some((Rlp::List(fields), [])) => fields,
+        _ => {
+            return InvalidTransaction {
+                reason: "malformed RLP",
+            }
+            .fail()
+        }
+    };
+
+    let (to, input) = match (fields.get(to), fields.get(input)) {
+        (Some(Rlp::Bytes(to)), Some(Rlp::Bytes(input))) => (to, input),
+        _ => {
+            return InvalidTransaction {
+                reason: "missing fields",
+            }
+            .fail()
+        }
+    };
+
+    ensure!(to.is_empty(), NotCreation);
+
+    Ok(Deployment {
+        index: 0,
+        contract: None,
+        address: None,
+        input: input.to_vec(),
+    })
+}
+
+/// How a deployment's input compares to the expected init code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    /// The input is the expected code, followed by this many bytes of
+    /// constructor arguments.
+    Matches(usize),
+
+    /// The input first differs from the expected code at this offset.
+    Differs(usize),
+}
+
+fn compare(input: &[u8], expected: &[u8]) -> Comparison {
+    let same = input
+        .iter()
+        .zip(expected)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if same == expected.len() {
+        Comparison::Matches(input.len() - same)
+    } else {
+        Comparison::Differs(same)
+    }
+}
+
+fn write_summary<W: Write>(
+    out: &mut W,
+    deployments: &[Deployment],
+    expected: Option<&[u8]>,
+) -> Result<usize, Error> {
+    let mut mismatched = 0;
+
+    for deployment in deployments {
+        let name = deployment.contract.as_deref().unwrap_or("<unknown>");
+        write!(out, "{}: {}", deployment.index, name)?;
+        if let Some(ref address) = deployment.address {
+            write!(out, " at {}", address)?;
+        }
+        writeln!(out)?;
+
+        let hash = Keccak256::digest(&deployment.input);
+        writeln!(
+            out,
+            "    input: {} bytes, keccak 0x{}",
+            deployment.input.len(),
+            hex::encode(hash)
+        )?;
+
+        let expected = match expected {
+            Some(e) => e,
+            None => continue,
+        };
+
+        match compare(&deployment.input, expected) {
+            Comparison::Matches(0) => writeln!(out, "    matches the expected code")?,
+            Comparison::Matches(args) => writeln!(
+                out,
+                "    matches the expected code, followed by {} bytes of constructor arguments",
+                args
+            )?,
+            Comparison::Differs(offset) => {
+                mismatched += 1;
+                writeln!(
+                    out,
+                    "    differs from the expected code at offset {:#x}",
+                    offset
+                )?;
+            }
+        }
+    }
+
+    Ok(mismatched)
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let found = match (&opts.raw_tx, &opts.file) {
+        (Some(Hex(raw)), _) => vec![transaction(raw)?],
+        (None, Some(path)) => {
+            let mut json = String::new();
+            open_input(path)?.read_to_string(&mut json)?;
+            deployments(&json)?
+        }
+        (None, None) => unreachable!(),
+    };
+
+    let selected: Vec<_> = found
+        .into_iter()
+        .filter(|d| opts.index.is_none() || opts.index == Some(d.index))
+        .filter(|d| opts.contract.is_none() || d.contract == opts.contract)
+        .collect();
+
+    ensure!(!selected.is_empty(), NoDeployment);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    if opts.extract {
+        ensure!(
+            selected.len() == 1,
+            Ambiguous {
+                count: selected.len()
+            }
+        );
+        writeln!(out, "{}", hex::encode(&selected[0].input))?;
+        return Ok(());
+    }
+
+    let expected = match opts.expected {
+        Some(path) => Some(read_hex(path)?),
+        None => None,
+    };
+
+    let count = write_summary(&mut out, &selected, expected.as_deref())?;
+    ensure!(count == 0, Mismatch { count });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    const BROADCAST: &str = r#"{
+        "transactions": [
+            {
+                "transactionType": "CREATE",
+                "contractName": "Counter",
+                "contractAddress": "0x5fbdb2315678afecb367f032d93f642f64180aa3",
+                "transaction": { "input": "0x6080604052" }
+            },
+            {
+                "transactionType": "CALL",
+                "contractName": "Counter",
+                "transaction": { "input": "0xd09de08a" }
+            },
+            {
+                "transactionType": "CREATE2",
+                "contractName": "Token",
+                "transaction": {
+                    "data": "0x00000000000000000000000000000000000000000000000000000000000000016001"
+                }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn broadcast_deployments() {
+        let found = deployments(BROADCAST).unwrap();
+
+        assert_eq!(
+            found,
+            [
+                Deployment {
+                    index: 0,
+                    contract: Some("Counter".into()),
+                    address: Some("0x5fbdb2315678afecb367f032d93f642f64180aa3".into()),
+                    input: hex!("6080604052").to_vec(),
+                },
+                Deployment {
+                    index: 2,
+                    contract: Some("Token".into()),
+                    address: None,
+                    input: hex!("6001").to_vec(),
+                },
+            ]
+        );
+
+        assert_matches!(deployments("[]"), Err(Error::NoTransactions { .. }));
+        assert_matches!(
+            deployments(r#"{"transactions": [{"transactionType": "CREATE"}]}"#),
+            Err(Error::InvalidInput { index: 0, .. })
+        );
+    }
+
+    #[test]
+    fn raw_transactions() {
+        // nonce 0, gas price 1, gas 0x5208, no recipient, no value, then the
+        // input, and a signature.
+        let legacy = hex!("ce 80 01 825208 80 80 83600100 1b 01 01");
+        assert_eq!(transaction(&legacy).unwrap().input, hex!("600100"));
+
+        // chain 1, nonce 0, fees 1 and 2, gas 0x5208, no recipient, no value,
+        // the input, an empty access list, and a signature.
+        let eip1559 = hex!("02 d1 01 80 01 02 825208 80 80 83600100 c0 01 01 01");
+        assert_eq!(transaction(&eip1559).unwrap().input, hex!("600100"));
+
+        let call = hex!("d2 80 01 825208 84deadbeef 80 83600100 1b 01 01");
+        assert_matches!(transaction(&call), Err(Error::NotCreation { .. }));
+
+        assert_matches!(
+            transaction(&hex!("cd 80 01")),
+            Err(Error::InvalidTransaction { .. })
+        );
+        assert_matches!(
+            transaction(&hex!("03 c0")),
+            Err(Error::InvalidTransaction { .. })
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let found = deployments(BROADCAST).unwrap();
+
+        let mut out = Vec::new();
+        let mismatched = write_summary(&mut out, &found, Some(&hex!("608060"))).unwrap();
+        assert_eq!(mismatched, 1);
+
+        let expected = "\
+0: Counter at 0x5fbdb2315678afecb367f032d93f642f64180aa3
+    input: 5 bytes, keccak 0x1c3374235d773b2189aed115aa13143020fcdbbe86e38f358cf3e4771b2f0244
+    matches the expected code, followed by 2 bytes of constructor arguments
+2: Token
+    input: 2 bytes, keccak 0x309c67890bde4c575dc23d2cc3b5c3a3d599e312e980e9b61b5bc8f3cd87c8bb
+    differs from the expected code at offset 0x1
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,370 @@
+//! State behind `disease tui`, kept separate from the terminal so it can be
+//! tested.
+
+use etk_asm::disasm::Offset;
+use etk_asm::ops::{ConcreteOp, Specifier};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+/// Labels given to offsets, read from and written to a hints file.
+///
+/// Each line of a hints file is an offset (`0x` prefixed hex, or decimal)
+/// followed by a label. Blank lines and anything after a `#` are ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Hints(pub BTreeMap<usize, String>);
+
+impl Hints {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut labels = BTreeMap::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(hash) => &line[..hash],
+                None => line,
+            };
+
+            let words: Vec<_> = line.split_whitespace().collect();
+
+            let (offset, label) = match words.as_slice() {
+                [] => continue,
+                [offset, label] => (offset, label),
+                _ => return Err(format!("line {}: expected `OFFSET LABEL`", idx + 1)),
+            };
+
+            let offset = match offset.strip_prefix("0x") {
+                Some(digits) => usize::from_str_radix(digits, 16),
+                None => offset.parse(),
+            }
+            .map_err(|_| format!("line {}: `{}` isn't an offset", idx + 1, offset))?;
+
+            labels.insert(offset, (*label).to_owned());
+        }
+
+        Ok(Self(labels))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        for (offset, label) in &self.0 {
+            writeln!(text, "{:#06x} {}", offset, label).unwrap();
+        }
+
+        text
+    }
+}
+
+/// One disassembled instruction.
+#[derive(Debug)]
+struct Line {
+    offset: usize,
+
+    /// The instruction, or `None` if the code ends part way through it.
+    op: Option<ConcreteOp>,
+
+    /// The value this instruction pushes, if it's a push.
+    value: Option<usize>,
+
+    /// Where this instruction jumps, if it's a jump (or the push before a
+    /// jump) with a known target.
+    target: Option<usize>,
+}
+
+/// Scrollable, navigable disassembly.
+#[derive(Debug)]
+pub struct Browser {
+    lines: Vec<Line>,
+
+    /// Offsets of the static jumps to each `jumpdest`.
+    xrefs: BTreeMap<usize, Vec<usize>>,
+
+    hints: Hints,
+
+    /// Index into `lines` of the selected instruction.
+    cursor: usize,
+
+    /// Previous values of `cursor`, for going back after following a jump.
+    history: Vec<usize>,
+}
+
+impl Browser {
+    pub fn new(code: &[u8], hints: Hints) -> Self {
+        let mut lines = Vec::new();
+        let mut offset = 0;
+
+        while offset < code.len() {
+            let spec = Specifier::from(code[offset]);
+            let end = offset + spec.size() as usize;
+            let bytes = code.get(offset..end);
+
+            lines.push(Line {
+                offset,
+                op: bytes.map(ConcreteOp::from_slice),
+                value: bytes
+                    .filter(|b| b.len() > 1)
+                    .and_then(|b| to_usize(&b[1..])),
+                target: None,
+            });
+
+            offset = end;
+        }
+
+        let jumpdests: BTreeSet<_> = lines
+            .iter()
+            .filter(|l| l.op.as_ref().map(ConcreteOp::specifier) == Some(Specifier::JumpDest))
+            .map(|l| l.offset)
+            .collect();
+
+        let mut xrefs: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+        for idx in 1..lines.len() {
+            let is_jump = matches!(
+                lines[idx].op.as_ref().map(ConcreteOp::specifier),
+                Some(Specifier::Jump) | Some(Specifier::JumpI)
+            );
+
+            if !is_jump {
+                continue;
+            }
+
+            let target = match lines[idx - 1].value {
+                Some(t) if jumpdests.contains(&t) => t,
+                _ => continue,
+            };
+
+            lines[idx - 1].target = Some(target);
+            lines[idx].target = Some(target);
+            xrefs.entry(target).or_default().push(lines[idx].offset);
+        }
+
+        Self {
+            lines,
+            xrefs,
+            hints,
+            cursor: 0,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn hints(&self) -> &Hints {
+        &self.hints
+    }
+
+    /// Offset of the selected instruction.
+    pub fn offset(&self) -> Option<usize> {
+        self.lines.get(self.cursor).map(|l| l.offset)
+    }
+
+    /// Move the cursor by `delta` lines, stopping at either end.
+    pub fn scroll(&mut self, delta: isize) {
+        let last = self.lines.len().saturating_sub(1) as isize;
+        let cursor = (self.cursor as isize + delta).max(0).min(last);
+        self.cursor = cursor as usize;
+    }
+
+    /// Move the cursor to the instruction at `offset`, remembering where it
+    /// was. Returns false if no instruction starts at `offset`.
+    pub fn goto(&mut self, offset: usize) -> bool {
+        match self.lines.binary_search_by_key(&offset, |l| l.offset) {
+            Ok(idx) => {
+                self.history.push(self.cursor);
+                self.cursor = idx;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Follow the jump under the cursor. Returns false if it isn't a jump with
+    /// a known target.
+    pub fn follow(&mut self) -> bool {
+        match self.lines.get(self.cursor).and_then(|l| l.target) {
+            Some(target) => self.goto(target),
+            None => false,
+        }
+    }
+
+    /// Return to where the cursor was before the last jump.
+    pub fn back(&mut self) -> bool {
+        match self.history.pop() {
+            Some(cursor) => {
+                self.cursor = cursor;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Offsets of the jumps to the `jumpdest` under the cursor.
+    pub fn xrefs(&self) -> &[usize] {
+        self.offset()
+            .and_then(|o| self.xrefs.get(&o))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// The label of the instruction under the cursor, if it has one.
+    pub fn label(&self) -> Option<&str> {
+        self.offset()
+            .and_then(|o| self.hints.0.get(&o))
+            .map(String::as_str)
+    }
+
+    /// Label the instruction under the cursor, or remove its label if `label`
+    /// is empty. Whitespace inside the label becomes `_`.
+    pub fn rename(&mut self, label: &str) {
+        let offset = match self.offset() {
+            Some(o) => o,
+            None => return,
+        };
+
+        let label: Vec<_> = label.split_whitespace().collect();
+        if label.is_empty() {
+            self.hints.0.remove(&offset);
+        } else {
+            self.hints.0.insert(offset, label.join("_"));
+        }
+    }
+
+    fn name(&self, offset: usize) -> String {
+        match self.hints.0.get(&offset) {
+            Some(label) => label.clone(),
+            None => format!("{:#x}", offset),
+        }
+    }
+
+    /// Text of the line at `idx`, with labels and jump targets.
+    pub fn text(&self, idx: usize) -> String {
+        let line = &self.lines[idx];
+
+        let mut text = match line.op {
+            Some(ref op) => Offset::new(line.offset, op).to_string(),
+            None => Offset::new(line.offset, "(truncated)").to_string(),
+        };
+
+        if let Some(label) = self.hints.0.get(&line.offset) {
+            write!(text, "  # {}:", label).unwrap();
+        }
+
+        if let Some(target) = line.target {
+            write!(text, "  # -> {}", self.name(target)).unwrap();
+        }
+
+        text
+    }
+
+    /// Text describing the jumps to the `jumpdest` under the cursor.
+    pub fn xref_text(&self) -> Vec<String> {
+        self.xrefs()
+            .iter()
+            .map(|o| match self.hints.0.get(o) {
+                Some(label) => format!("{:#x} ({})", o, label),
+                None => format!("{:#x}", o),
+            })
+            .collect()
+    }
+}
+
+fn to_usize(imm: &[u8]) -> Option<usize> {
+    let mut value: usize = 0;
+
+    for byte in imm {
+        value = value.checked_mul(256)?.checked_add(usize::from(*byte))?;
+    }
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    // 0: push1 0x07; 2: jumpi; 3: push1 0x07; 5: jump; 6: stop; 7: jumpdest
+    const CODE: [u8; 9] = hex!("6007 57 6007 56 00 5b 00");
+
+    #[test]
+    fn hints_round_trip() {
+        let hints = Hints::parse("# comment\n0x1b transfer\n\n29 balanceOf # trailing\n").unwrap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert(0x1b, "transfer".to_owned());
+        expected.insert(29, "balanceOf".to_owned());
+        assert_eq!(hints.0, expected);
+
+        assert_eq!(Hints::parse(&hints.to_text()).unwrap(), hints);
+
+        Hints::parse("0x1b").unwrap_err();
+        Hints::parse("zz transfer").unwrap_err();
+    }
+
+    #[test]
+    fn follow_and_back() {
+        let mut browser = Browser::new(&CODE, Hints::default());
+
+        assert!(browser.follow());
+        assert_eq!(browser.offset(), Some(7));
+
+        assert!(!browser.follow());
+
+        assert!(browser.back());
+        assert_eq!(browser.offset(), Some(0));
+        assert!(!browser.back());
+
+        browser.scroll(3);
+        assert_eq!(browser.offset(), Some(5));
+        assert!(browser.follow());
+        assert!(browser.back());
+        assert_eq!(browser.offset(), Some(5));
+    }
+
+    #[test]
+    fn scroll_stops_at_ends() {
+        let mut browser = Browser::new(&CODE, Hints::default());
+
+        browser.scroll(-3);
+        assert_eq!(browser.cursor(), 0);
+
+        browser.scroll(100);
+        assert_eq!(browser.cursor(), browser.len() - 1);
+    }
+
+    #[test]
+    fn xrefs_and_rename() {
+        let mut browser = Browser::new(&CODE, Hints::default());
+        assert!(browser.goto(7));
+
+        assert_eq!(browser.xrefs(), &[2, 5]);
+
+        browser.rename("target");
+        assert_eq!(browser.label(), Some("target"));
+        assert_eq!(browser.hints().0[&7], "target");
+
+        assert!(browser.back());
+        assert_eq!(browser.text(0), "   0:   push1 0x07  # -> target");
+
+        assert!(browser.goto(7));
+        browser.rename(" pay out ");
+        assert_eq!(browser.label(), Some("pay_out"));
+
+        browser.rename(" ");
+        assert_eq!(browser.label(), None);
+    }
+
+    #[test]
+    fn ignores_jumps_to_non_jumpdests() {
+        let code = hex!("6000 56");
+        let mut browser = Browser::new(&code, Hints::default());
+        assert!(!browser.follow());
+    }
+}
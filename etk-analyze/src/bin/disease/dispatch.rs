@@ -0,0 +1,235 @@
+//! Listing the functions a contract's dispatcher jumps to.
+
+use crate::opts::Format;
+
+use etk_analyze::dispatch::{entries, Entry};
+
+use etk_cli::io::{create_output, InputSource};
+
+use serde_json::json;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("could not read signatures from `{}`", path.display()))]
+    ReadSignatures {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the table"
+    )]
+    format: Format,
+
+    #[structopt(
+        long = "signatures",
+        parse(from_os_str),
+        help = "path to a file of function signatures, one per line, to name the selectors with"
+    )]
+    signatures: Option<PathBuf>,
+
+    #[structopt(
+        long = "4byte",
+        help = "also name the selectors with the built-in 4byte.directory database"
+    )]
+    four_byte: bool,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+fn selector(signature: &str) -> u32 {
+    let hash = Keccak256::digest(signature.as_bytes());
+    u32::from_be_bytes(hash[..4].try_into().unwrap())
+}
+
+/// Map selectors to the signatures in `text`, one per line. Blank lines and
+/// lines starting with `#` are ignored.
+fn parse_signatures(text: &str) -> HashMap<u32, Vec<String>> {
+    let mut names: HashMap<u32, Vec<String>> = HashMap::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        names
+            .entry(selector(line))
+            .or_default()
+            .push(line.to_owned());
+    }
+
+    names
+}
+
+/// Everything known about one entry, ready to be written out.
+struct Row {
+    entry: Entry,
+    signatures: Vec<String>,
+}
+
+fn write_text<W: Write>(out: &mut W, rows: &[Row]) -> Result<(), Error> {
+    for row in rows {
+        write!(
+            out,
+            "{:#010x} {:#06x}",
+            row.entry.selector, row.entry.offset
+        )?;
+
+        if !row.signatures.is_empty() {
+            write!(out, " {}", row.signatures.join(" "))?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, rows: &[Row]) -> Result<(), Error> {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            json!({
+                "selector": format!("{:#010x}", row.entry.selector),
+                "offset": row.entry.offset,
+                "signatures": row.signatures,
+            })
+        })
+        .collect();
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&rows).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let names = match opts.signatures {
+        Some(ref path) => {
+            let text = std::fs::read_to_string(path).context(ReadSignatures { path })?;
+            parse_signatures(&text)
+        }
+        None => HashMap::new(),
+    };
+
+    let four_byte = opts.four_byte;
+
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let rows: Vec<_> = entries(&code)
+        .into_iter()
+        .map(|entry| {
+            let mut signatures = names.get(&entry.selector).cloned().unwrap_or_default();
+
+            if four_byte {
+                for sig in etk_4byte::reverse_selector(entry.selector) {
+                    if !signatures.iter().any(|s| s == sig) {
+                        signatures.push((*sig).to_owned());
+                    }
+                }
+            }
+
+            Row { entry, signatures }
+        })
+        .collect();
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &rows),
+        Format::Json => write_json(&mut out, &rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_by_selector() {
+        let names = parse_signatures(
+            "
+            # ERC-20
+            transfer(address,uint256)
+
+            balanceOf(address)
+            ",
+        );
+
+        assert_eq!(names[&0xa9059cbb], ["transfer(address,uint256)"]);
+        assert_eq!(names[&0x70a08231], ["balanceOf(address)"]);
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn json_table() {
+        let rows = [
+            Row {
+                entry: Entry {
+                    selector: 0xa9059cbb,
+                    offset: 0x1b,
+                },
+                signatures: vec!["transfer(address,uint256)".into()],
+            },
+            Row {
+                entry: Entry {
+                    selector: 0x00000001,
+                    offset: 0x1d,
+                },
+                signatures: vec![],
+            },
+        ];
+
+        let mut out = Vec::new();
+        write_json(&mut out, &rows).unwrap();
+
+        let actual: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!([
+            {
+                "selector": "0xa9059cbb",
+                "offset": 27,
+                "signatures": ["transfer(address,uint256)"],
+            },
+            {
+                "selector": "0x00000001",
+                "offset": 29,
+                "signatures": [],
+            },
+        ]);
+
+        assert_eq!(actual, expected);
+    }
+}
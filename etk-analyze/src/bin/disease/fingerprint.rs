@@ -0,0 +1,141 @@
+//! Guessing what produced a contract from the code fragments it contains.
+
+use crate::opts::Format;
+
+use etk_analyze::signatures::{find, toolchain, Match, SIGNATURES};
+
+use etk_cli::io::{create_output, InputSource};
+
+use serde_json::json;
+
+use snafu::{Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the report"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+fn write_text<W: Write>(out: &mut W, found: &[Match]) -> Result<(), Error> {
+    for m in found {
+        writeln!(
+            out,
+            "{:#06x} {} ({})",
+            m.offset, m.signature.name, m.signature.toolchain
+        )?;
+    }
+
+    if !found.is_empty() {
+        writeln!(out)?;
+    }
+
+    match toolchain(found) {
+        Some(t) => writeln!(out, "likely toolchain: {}", t)?,
+        None => writeln!(out, "likely toolchain: unknown")?,
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, found: &[Match]) -> Result<(), Error> {
+    let matches: Vec<_> = found
+        .iter()
+        .map(|m| {
+            json!({
+                "offset": m.offset,
+                "name": m.signature.name,
+                "toolchain": m.signature.toolchain.to_string(),
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "matches": matches,
+        "toolchain": toolchain(found).map(|t| t.to_string()),
+    });
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let found = find(&code, SIGNATURES);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &found),
+        Format::Json => write_json(&mut out, &found),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    // Free memory pointer, then an old Vyper selector load.
+    const CODE: [u8; 11] = hex!("6080604052 600035601c52");
+
+    #[test]
+    fn text_report() {
+        let mut out = Vec::new();
+        write_text(&mut out, &find(&CODE, SIGNATURES)).unwrap();
+
+        let expected = "\
+0x0000 solidity-free-memory-pointer (solidity)
+0x0005 vyper-selector (vyper)
+
+likely toolchain: solidity
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn json_report() {
+        let mut out = Vec::new();
+        write_json(&mut out, &[]).unwrap();
+
+        let actual: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!({ "matches": [], "toolchain": null });
+
+        assert_eq!(actual, expected);
+    }
+}
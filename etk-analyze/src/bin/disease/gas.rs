@@ -0,0 +1,211 @@
+//! Bounding the gas used by each function a contract's dispatcher jumps to.
+
+use crate::opts::Format;
+
+use etk_analyze::dispatch::entries;
+use etk_analyze::gas::{bounds, Bounds, Upper};
+
+use etk_cli::io::{create_output, InputSource};
+
+use serde_json::{json, Value};
+
+use snafu::{Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the table"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+/// The bounds for one function, or for the whole contract when `selector` is
+/// `None`.
+struct Row {
+    selector: Option<u32>,
+    offset: usize,
+    bounds: Bounds,
+}
+
+fn selector_text(selector: Option<u32>) -> String {
+    match selector {
+        Some(s) => format!("{:#010x}", s),
+        None => format!("{:10}", "-"),
+    }
+}
+
+fn write_text<W: Write>(out: &mut W, rows: &[Row]) -> Result<(), Error> {
+    for row in rows {
+        write!(
+            out,
+            "{} {:#06x} min ",
+            selector_text(row.selector),
+            row.offset
+        )?;
+
+        match row.bounds.min {
+            Some(min) => write!(out, "{}", min)?,
+            None => write!(out, "unbounded")?,
+        }
+
+        match row.bounds.max {
+            Upper::Bounded(max) => write!(out, " max {}", max)?,
+            Upper::Loop(at) => write!(out, " max unbounded (loop at {:#06x})", at)?,
+            Upper::DynamicJump(at) => write!(out, " max unbounded (dynamic jump at {:#06x})", at)?,
+        }
+
+        if row.bounds.dynamic {
+            write!(out, " + dynamic")?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, rows: &[Row]) -> Result<(), Error> {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            let (max, unbounded) = match row.bounds.max {
+                Upper::Bounded(max) => (Some(max), Value::Null),
+                Upper::Loop(at) => (None, json!({ "reason": "loop", "offset": at })),
+                Upper::DynamicJump(at) => (None, json!({ "reason": "dynamic_jump", "offset": at })),
+            };
+
+            json!({
+                "selector": row.selector.map(|s| format!("{:#010x}", s)),
+                "offset": row.offset,
+                "min": row.bounds.min,
+                "max": max,
+                "unbounded": unbounded,
+                "dynamic": row.bounds.dynamic,
+            })
+        })
+        .collect();
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&rows).unwrap())?;
+    Ok(())
+}
+
+fn rows(code: &[u8]) -> Vec<Row> {
+    let functions = entries(code);
+
+    if functions.is_empty() {
+        return bounds(code, 0)
+            .map(|bounds| Row {
+                selector: None,
+                offset: 0,
+                bounds,
+            })
+            .into_iter()
+            .collect();
+    }
+
+    functions
+        .into_iter()
+        .filter_map(|entry| {
+            let bounds = bounds(code, entry.offset)?;
+            Some(Row {
+                selector: Some(entry.selector),
+                offset: entry.offset,
+                bounds,
+            })
+        })
+        .collect()
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let rows = rows(&code);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &rows),
+        Format::Json => write_json(&mut out, &rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    // Dispatches 0x00000001 to 0x11, which stops, and 0x00000002 to 0x14,
+    // which loops forever.
+    //
+    //  0: push1 0x01; 2: dup2; 3: eq; 4: push1 0x11; 6: jumpi
+    //  7: push1 0x02; 9: eq; 10: push1 0x14; 12: jumpi; 13..16: stop
+    // 17: jumpdest; 18: stop; 19: stop
+    // 20: jumpdest; 21: push1 0x14; 23: jump
+    const CODE: [u8; 24] = hex!("6001 81 14 6011 57 6002 14 6014 57 00000000 5b 00 00 5b 6014 56");
+
+    #[test]
+    fn text_table() {
+        let mut out = Vec::new();
+        write_text(&mut out, &rows(&CODE)).unwrap();
+
+        let expected = "\
+0x00000001 0x0011 min 1 max 1
+0x00000002 0x0014 min unbounded max unbounded (loop at 0x0014)
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn whole_contract() {
+        // push1 0x00; sload; stop
+        let code = hex!("6000 54 00");
+
+        let mut out = Vec::new();
+        write_json(&mut out, &rows(&code)).unwrap();
+
+        let actual: Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!([{
+            "selector": null,
+            "offset": 0,
+            "min": 103,
+            "max": 103,
+            "unbounded": null,
+            "dynamic": true,
+        }]);
+
+        assert_eq!(actual, expected);
+    }
+}
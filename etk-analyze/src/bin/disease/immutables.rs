@@ -0,0 +1,86 @@
+//! Listing the placeholders for immutable variables.
+
+use crate::opts::Format;
+
+use etk_analyze::immutable::references;
+
+use etk_cli::io::{create_output, InputSource};
+
+use snafu::{Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the list"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+fn write_text<W: Write>(out: &mut W, offsets: &[usize]) -> Result<(), Error> {
+    for offset in offsets {
+        writeln!(out, "{:#06x}", offset)?;
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, offsets: &[usize]) -> Result<(), Error> {
+    writeln!(out, "{}", serde_json::to_string_pretty(offsets).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let offsets = references(&code);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &offsets),
+        Format::Json => write_json(&mut out, &offsets),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_list() {
+        let mut out = Vec::new();
+        write_text(&mut out, &[0, 0x4a]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0x0000\n0x004a\n");
+    }
+}
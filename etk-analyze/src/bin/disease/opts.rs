@@ -1,18 +1,278 @@
+use crate::{
+    batch, bitmap, broadcast, dispatch, fingerprint, gas, immutables, patch, sstore2, stats, tui,
+    xref,
+};
+
 use etk_cli::io::InputSource;
 
 use std::path::PathBuf;
+use std::str::FromStr;
 
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
+#[structopt(setting = AppSettings::SubcommandsNegateReqs)]
 pub struct Opts {
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+
     #[structopt(flatten)]
     pub src: InputSource,
 
     #[structopt(
         short = "o",
         long = "out-file",
-        help = "path to output file (defaults to stdout)"
+        help = "path to output file (`-` or omitted for stdout)"
     )]
     pub out_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "on-unknown",
+        default_value = "raw",
+        possible_values(&["raw", "abort", "skip", "data"]),
+        help = "what to do with unassigned opcodes"
+    )]
+    pub on_unknown: OnUnknown,
+
+    #[structopt(
+        long = "immutable",
+        number_of_values = 1,
+        help = "fill in the immutable placeholder pushed at OFFSET with VALUE (as OFFSET=VALUE)"
+    )]
+    pub immutables: Vec<Immutable>,
+
+    #[structopt(
+        long = "stack",
+        help = "write the values on the stack after each instruction as a comment"
+    )]
+    pub stack: bool,
+
+    #[structopt(
+        long = "show-bytes",
+        help = "write the bytes each instruction is encoded as, after its offset"
+    )]
+    pub show_bytes: bool,
+
+    #[structopt(
+        long = "no-offsets",
+        help = "leave out the offsets, so the output can be assembled again"
+    )]
+    pub no_offsets: bool,
+
+    #[structopt(
+        long = "experimental",
+        help = "write instructions from proposals no fork has adopted, like EIP-2315's jumpsub"
+    )]
+    pub experimental: bool,
+
+    #[structopt(
+        long = "opcodes",
+        value_name = "TABLE",
+        parse(from_os_str),
+        help = "write the instructions in this TOML file, for chains with instructions of their own"
+    )]
+    pub opcodes: Option<PathBuf>,
+
+    #[structopt(
+        long = "function",
+        help = "only write the code the dispatcher can run for SELECTOR (ex. 0xa9059cbb)"
+    )]
+    pub function: Option<Selector>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    #[structopt(about = "report on every contract in a directory, or at a list of addresses")]
+    Batch(batch::Opts),
+
+    #[structopt(about = "write the jumpdest and push data bitmaps clients build for the code")]
+    Bitmap(bitmap::Opts),
+
+    #[structopt(
+        about = "list or check the contracts a Foundry broadcast or signed transaction deploys"
+    )]
+    Broadcast(broadcast::Opts),
+
+    #[structopt(about = "guess which compiler or library produced the code")]
+    Fingerprint(fingerprint::Opts),
+
+    #[structopt(about = "bound the gas used by each function the dispatcher jumps to")]
+    Gas(gas::Opts),
+
+    #[structopt(about = "list the placeholders for immutable variables")]
+    Immutables(immutables::Opts),
+
+    #[structopt(about = "apply a patch spec to existing code, and check its jumps still work")]
+    Patch(patch::Opts),
+
+    #[structopt(about = "list the function selectors a contract dispatches on")]
+    Selectors(dispatch::Opts),
+
+    #[structopt(about = "show how the code would be split across SSTORE2 data contracts")]
+    Sstore2(sstore2::Opts),
+
+    #[structopt(about = "summarize the code, and guess which compiler version produced it")]
+    Stats(stats::Opts),
+
+    #[structopt(about = "browse the disassembly interactively")]
+    Tui(tui::Opts),
+
+    #[structopt(about = "list the references to each jumpdest and storage slot")]
+    Xref(xref::Opts),
+}
+
+/// How to write a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown format `{}`", text)),
+        }
+    }
+}
+
+/// What to do when the code contains an unassigned opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnUnknown {
+    /// Write the opcode as `%raw`, and carry on.
+    Raw,
+
+    /// Stop with an error.
+    Abort,
+
+    /// Leave out everything up to the next `jumpdest`.
+    Skip,
+
+    /// Write everything up to the next `jumpdest` as `%raw` bytes.
+    Data,
+}
+
+impl FromStr for OnUnknown {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "raw" => Ok(Self::Raw),
+            "abort" => Ok(Self::Abort),
+            "skip" => Ok(Self::Skip),
+            "data" => Ok(Self::Data),
+            _ => Err(format!("unknown behavior `{}`", text)),
+        }
+    }
+}
+
+/// A value to fill in an immutable placeholder with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Immutable {
+    /// Offset of the `push32` instruction.
+    pub offset: usize,
+    pub value: [u8; 32],
+}
+
+impl FromStr for Immutable {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let eq = text
+            .find('=')
+            .ok_or_else(|| format!("expected `OFFSET=VALUE`, got `{}`", text))?;
+        let (offset, value) = (&text[..eq], &text[eq + 1..]);
+
+        let offset = match offset.strip_prefix("0x") {
+            Some(digits) => usize::from_str_radix(digits, 16),
+            None => offset.parse(),
+        }
+        .map_err(|_| format!("`{}` isn't an offset", offset))?;
+
+        let digits = value.strip_prefix("0x").unwrap_or(value);
+        let digits = if digits.len() % 2 == 1 {
+            format!("0{}", digits)
+        } else {
+            digits.to_owned()
+        };
+
+        let bytes = hex::decode(&digits).map_err(|_| format!("`{}` isn't a hex value", value))?;
+
+        if bytes.len() > 32 {
+            return Err(format!("`{}` is longer than 32 bytes", value));
+        }
+
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+
+        Ok(Self {
+            offset,
+            value: word,
+        })
+    }
+}
+
+/// A function selector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selector(pub u32);
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let digits = text.strip_prefix("0x").unwrap_or(text);
+
+        if digits.len() != 8 {
+            return Err(format!("`{}` isn't a four byte selector", text));
+        }
+
+        u32::from_str_radix(digits, 16)
+            .map(Self)
+            .map_err(|_| format!("`{}` isn't a four byte selector", text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_immutable() {
+        let parsed: Immutable = "0x1c=0xabc".parse().unwrap();
+
+        let mut value = [0u8; 32];
+        value[30..].copy_from_slice(&[0x0a, 0xbc]);
+
+        assert_eq!(
+            parsed,
+            Immutable {
+                offset: 0x1c,
+                value
+            }
+        );
+        assert_eq!("28=0".parse::<Immutable>().unwrap().offset, 28);
+
+        "0x1c".parse::<Immutable>().unwrap_err();
+        "0x1c=zz".parse::<Immutable>().unwrap_err();
+        format!("0=0x{}", "00".repeat(33))
+            .parse::<Immutable>()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn parse_selector() {
+        assert_eq!(
+            "0xa9059cbb".parse::<Selector>().unwrap(),
+            Selector(0xa9059cbb)
+        );
+        assert_eq!("000000ff".parse::<Selector>().unwrap(), Selector(0xff));
+
+        "0xff".parse::<Selector>().unwrap_err();
+        "0xa9059cbz".parse::<Selector>().unwrap_err();
+    }
 }
@@ -0,0 +1,596 @@
+//! Applying small, in-place patches to existing code.
+//!
+//! A patch spec is a text file with one patch per line. Offsets and values
+//! are either `0x` prefixed hex, or decimal:
+//!
+//! ```text
+//! # Overwrite the instructions at 0x12 with `push1 0x05; add`.
+//! replace 0x12 600501
+//!
+//! # Change the immediate of the push at 0x40.
+//! push 0x40 0xdeadbeef
+//!
+//! # Fill 0x80 up to (but not including) 0x90 with `jumpdest`.
+//! nop 0x80 0x90
+//! ```
+//!
+//! Patches never change the length of the code, so jump targets elsewhere stay
+//! where they were.
+
+use etk_asm::disasm::Offset;
+use etk_asm::ops::{ConcreteOp, Specifier};
+
+use etk_cli::io::{create_output, InputSource};
+
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("could not read patch spec `{}`", path.display()))]
+    ReadSpec {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("line {} of the patch spec: {}", line, message))]
+    Spec {
+        line: usize,
+        message: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("line {}: {:#x} isn't the start of an instruction", line, offset))]
+    Boundary {
+        line: usize,
+        offset: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("line {}: the patch runs past the end of the code", line))]
+    OutOfBounds { line: usize, backtrace: Backtrace },
+
+    #[snafu(display("line {}: the instruction at {:#x} isn't a push", line, offset))]
+    NotPush {
+        line: usize,
+        offset: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("line {}: the value doesn't fit in the push at {:#x}", line, offset))]
+    ImmediateTooLarge {
+        line: usize,
+        offset: usize,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display(
+        "{} jump(s) no longer land on a `jumpdest` (use --force to write the code anyway)",
+        count
+    ))]
+    BrokenJumps { count: usize, backtrace: Backtrace },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "spec",
+        short = "s",
+        parse(from_os_str),
+        help = "path to the patch spec"
+    )]
+    spec: PathBuf,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to write the patched code, in hexadecimal (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "force",
+        help = "write the patched code even if jumps no longer land on a `jumpdest`"
+    )]
+    force: bool,
+}
+
+/// One change to make to the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Patch {
+    /// Overwrite the bytes starting at `offset`.
+    Replace { offset: usize, bytes: Vec<u8> },
+
+    /// Replace the immediate of the push at `offset`.
+    Push { offset: usize, value: Vec<u8> },
+
+    /// Fill `start..end` with `jumpdest`, the closest thing the EVM has to a
+    /// no-op.
+    Nop { start: usize, end: usize },
+}
+
+impl Patch {
+    fn name(&self) -> &'static str {
+        match self {
+            Patch::Replace { .. } => "replace",
+            Patch::Push { .. } => "push",
+            Patch::Nop { .. } => "nop",
+        }
+    }
+
+    fn start(&self) -> usize {
+        match self {
+            Patch::Replace { offset, .. } | Patch::Push { offset, .. } => *offset,
+            Patch::Nop { start, .. } => *start,
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<u128, String> {
+    match text.strip_prefix("0x") {
+        Some(digits) => u128::from_str_radix(digits, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("`{}` isn't a number", text))
+}
+
+fn parse_offset(text: &str) -> Result<usize, String> {
+    let n = parse_number(text)?;
+
+    if n > usize::MAX as u128 {
+        Err(format!("`{}` is too large for an offset", text))
+    } else {
+        Ok(n as usize)
+    }
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    hex::decode(digits).map_err(|_| format!("`{}` isn't hexadecimal", text))
+}
+
+fn parse_value(text: &str) -> Result<Vec<u8>, String> {
+    let bytes = match text.strip_prefix("0x") {
+        Some(digits) if digits.len() % 2 == 1 => parse_hex(&format!("0{}", digits))?,
+        Some(_) => parse_hex(text)?,
+        None => parse_number(text)?.to_be_bytes().to_vec(),
+    };
+
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    Ok(bytes[zeros..].to_vec())
+}
+
+fn parse_line(text: &str) -> Result<Option<Patch>, String> {
+    let text = match text.find('#') {
+        Some(idx) => &text[..idx],
+        None => text,
+    };
+
+    let words: Vec<_> = text.split_whitespace().collect();
+
+    let patch = match words.as_slice() {
+        [] => return Ok(None),
+        ["replace", offset, bytes] => Patch::Replace {
+            offset: parse_offset(offset)?,
+            bytes: parse_hex(bytes)?,
+        },
+        ["push", offset, value] => Patch::Push {
+            offset: parse_offset(offset)?,
+            value: parse_value(value)?,
+        },
+        ["nop", start, end] => Patch::Nop {
+            start: parse_offset(start)?,
+            end: parse_offset(end)?,
+        },
+        [kind, ..] if ["replace", "push", "nop"].contains(kind) => {
+            return Err(format!("wrong number of arguments for `{}`", kind))
+        }
+        [kind, ..] => return Err(format!("unknown patch `{}`", kind)),
+    };
+
+    Ok(Some(patch))
+}
+
+fn parse_spec(text: &str) -> Result<Vec<(usize, Patch)>, Error> {
+    let mut patches = Vec::new();
+
+    for (idx, text) in text.lines().enumerate() {
+        let line = idx + 1;
+
+        match parse_line(text) {
+            Ok(Some(patch)) => patches.push((line, patch)),
+            Ok(None) => (),
+            Err(message) => return Spec { line, message }.fail(),
+        }
+    }
+
+    Ok(patches)
+}
+
+/// Offsets of every instruction in `code`.
+fn boundaries(code: &[u8]) -> BTreeSet<usize> {
+    let mut offsets = BTreeSet::new();
+    let mut offset = 0;
+
+    while offset < code.len() {
+        offsets.insert(offset);
+        offset += Specifier::from(code[offset]).size() as usize;
+    }
+
+    offsets
+}
+
+/// Offsets of every `jumpdest` that is actually an instruction.
+fn jumpdests(code: &[u8]) -> BTreeSet<usize> {
+    boundaries(code)
+        .into_iter()
+        .filter(|o| Specifier::from(code[*o]) == Specifier::JumpDest)
+        .collect()
+}
+
+/// Jumps with a target pushed by the instruction immediately before them, as
+/// `(offset of the push, target)`.
+fn static_jumps(code: &[u8]) -> BTreeSet<(usize, u128)> {
+    let offsets: Vec<_> = boundaries(code).into_iter().collect();
+    let mut jumps = BTreeSet::new();
+
+    for pair in offsets.windows(2) {
+        let (push, jump) = (pair[0], pair[1]);
+        let spec = Specifier::from(code[push]);
+
+        if spec.size() == 1 {
+            continue;
+        }
+
+        if !matches!(
+            Specifier::from(code[jump]),
+            Specifier::Jump | Specifier::JumpI
+        ) {
+            continue;
+        }
+
+        let imm = &code[push + 1..jump];
+        if imm.len() > 16 && imm[..imm.len() - 16].iter().any(|b| *b != 0) {
+            jumps.insert((push, u128::MAX));
+            continue;
+        }
+
+        let target = imm.iter().fold(0u128, |acc, b| (acc << 8) | u128::from(*b));
+        jumps.insert((push, target));
+    }
+
+    jumps
+}
+
+/// Apply `patch` to `code`, returning the range of bytes it changed.
+fn apply(code: &mut [u8], line: usize, patch: &Patch) -> Result<(usize, usize), Error> {
+    let starts = boundaries(code);
+    let is_boundary = |offset: usize| offset == code.len() || starts.contains(&offset);
+
+    let (start, end) = match patch {
+        Patch::Replace { offset, bytes } => (*offset, offset.saturating_add(bytes.len())),
+        Patch::Push { offset, .. } => {
+            ensure!(*offset < code.len(), OutOfBounds { line });
+            let size = Specifier::from(code[*offset]).size() as usize;
+            (*offset, offset + size)
+        }
+        Patch::Nop { start, end } => (*start, *end),
+    };
+
+    ensure!(start <= end && end <= code.len(), OutOfBounds { line });
+    ensure!(
+        is_boundary(start),
+        Boundary {
+            line,
+            offset: start
+        }
+    );
+
+    // A push at the end of the code can be truncated, so its end isn't a
+    // boundary, but there's nothing after it to misalign.
+    if !matches!(patch, Patch::Push { .. }) {
+        ensure!(is_boundary(end), Boundary { line, offset: end });
+    }
+
+    match patch {
+        Patch::Replace { bytes, .. } => code[start..end].copy_from_slice(bytes),
+        Patch::Push { value, .. } => {
+            ensure!(
+                end - start > 1,
+                NotPush {
+                    line,
+                    offset: start
+                }
+            );
+
+            let imm = &mut code[start + 1..end];
+            ensure!(
+                value.len() <= imm.len(),
+                ImmediateTooLarge {
+                    line,
+                    offset: start
+                }
+            );
+
+            let pad = imm.len() - value.len();
+            imm[..pad].iter_mut().for_each(|b| *b = 0);
+            imm[pad..].copy_from_slice(value);
+        }
+        Patch::Nop { .. } => code[start..end]
+            .iter_mut()
+            .for_each(|b| *b = Specifier::JumpDest.into()),
+    }
+
+    Ok((start, end))
+}
+
+/// Disassemble the instructions of `code` that start in `start..end`.
+fn listing(code: &[u8], start: usize, end: usize) -> Vec<String> {
+    boundaries(code)
+        .range(start..end)
+        .map(|offset| {
+            let size = Specifier::from(code[*offset]).size() as usize;
+
+            match code.get(*offset..offset + size) {
+                Some(bytes) => Offset::new(*offset, ConcreteOp::from_slice(bytes)).to_string(),
+                None => {
+                    let raw = format!("%raw({:#04x}) # truncated", code[*offset]);
+                    Offset::new(*offset, raw).to_string()
+                }
+            }
+        })
+        .collect()
+}
+
+/// Write a report of the differences between `before` and `after` to `out`,
+/// returning the number of jumps the patches broke.
+fn report<W: Write>(
+    out: &mut W,
+    before: &[u8],
+    after: &[u8],
+    changes: &[(&Patch, usize, usize)],
+) -> Result<usize, Error> {
+    for (patch, start, end) in changes {
+        writeln!(out, "{} at {:#x}:", patch.name(), patch.start())?;
+
+        for line in listing(before, *start, *end) {
+            writeln!(out, "-{}", line)?;
+        }
+
+        for line in listing(after, *start, *end) {
+            writeln!(out, "+{}", line)?;
+        }
+
+        writeln!(out)?;
+    }
+
+    let old_dests = jumpdests(before);
+    let new_dests = jumpdests(after);
+
+    for removed in old_dests.difference(&new_dests) {
+        writeln!(out, "warning: removed jumpdest at {:#x}", removed)?;
+    }
+
+    for added in new_dests.difference(&old_dests) {
+        writeln!(out, "note: added jumpdest at {:#x}", added)?;
+    }
+
+    let lands = |dests: &BTreeSet<usize>, target: u128| {
+        target <= usize::MAX as u128 && dests.contains(&(target as usize))
+    };
+
+    let already_broken: BTreeSet<_> = static_jumps(before)
+        .into_iter()
+        .filter(|(_, target)| !lands(&old_dests, *target))
+        .collect();
+
+    let mut broken = 0;
+    for (push, target) in static_jumps(after) {
+        if lands(&new_dests, target) || already_broken.contains(&(push, target)) {
+            continue;
+        }
+
+        broken += 1;
+        writeln!(
+            out,
+            "error: jump pushed at {:#x} targets {:#x}, which isn't a jumpdest",
+            push, target
+        )?;
+    }
+
+    Ok(broken)
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let text = std::fs::read_to_string(&opts.spec).context(ReadSpec { path: &opts.spec })?;
+    let patches = parse_spec(&text)?;
+
+    let mut before = Vec::new();
+    opts.src.open()?.read_to_end(&mut before)?;
+
+    let mut after = before.clone();
+    let mut changes = Vec::with_capacity(patches.len());
+
+    for (line, patch) in &patches {
+        let (start, end) = apply(&mut after, *line, patch)?;
+        changes.push((patch, start, end));
+    }
+
+    let stderr = std::io::stderr();
+    let broken = report(&mut stderr.lock(), &before, &after, &changes)?;
+
+    ensure!(broken == 0 || opts.force, BrokenJumps { count: broken });
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    writeln!(out, "0x{}", hex::encode(&after))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn parse_spec_lines() {
+        let text = "
+            # comment
+            replace 0x12 600501
+            push 64 0x0dead # trailing comment
+            nop 0x80 0x90
+        ";
+
+        let patches = parse_spec(text).unwrap();
+        let expected = vec![
+            (
+                3,
+                Patch::Replace {
+                    offset: 0x12,
+                    bytes: vec![0x60, 0x05, 0x01],
+                },
+            ),
+            (
+                4,
+                Patch::Push {
+                    offset: 64,
+                    value: vec![0xde, 0xad],
+                },
+            ),
+            (
+                5,
+                Patch::Nop {
+                    start: 0x80,
+                    end: 0x90,
+                },
+            ),
+        ];
+
+        assert_eq!(patches, expected);
+
+        assert_matches!(parse_spec("jump 1 2"), Err(Error::Spec { line: 1, .. }));
+        assert_matches!(parse_spec("\nnop 1"), Err(Error::Spec { line: 2, .. }));
+        assert_matches!(parse_spec("replace 0 0xzz"), Err(Error::Spec { .. }));
+    }
+
+    #[test]
+    fn apply_push() {
+        // push2 0x0004; jump; jumpdest
+        let mut code = hex!("6100045601").to_vec();
+        let patch = Patch::Push {
+            offset: 0,
+            value: vec![0x05],
+        };
+
+        assert_eq!(apply(&mut code, 1, &patch).unwrap(), (0, 3));
+        assert_eq!(code, hex!("6100055601"));
+
+        let patch = Patch::Push {
+            offset: 3,
+            value: vec![0x05],
+        };
+        assert_matches!(apply(&mut code, 1, &patch), Err(Error::NotPush { .. }));
+
+        let patch = Patch::Push {
+            offset: 0,
+            value: vec![0x01, 0x02, 0x03],
+        };
+        assert_matches!(
+            apply(&mut code, 1, &patch),
+            Err(Error::ImmediateTooLarge { .. })
+        );
+    }
+
+    #[test]
+    fn apply_checks_boundaries() {
+        // push2 0x0102; stop
+        let mut code = hex!("61010200").to_vec();
+
+        let patch = Patch::Replace {
+            offset: 1,
+            bytes: vec![0x00],
+        };
+        assert_matches!(
+            apply(&mut code, 1, &patch),
+            Err(Error::Boundary { offset: 1, .. })
+        );
+
+        let patch = Patch::Replace {
+            offset: 0,
+            bytes: vec![0x00, 0x00],
+        };
+        assert_matches!(
+            apply(&mut code, 1, &patch),
+            Err(Error::Boundary { offset: 2, .. })
+        );
+
+        let patch = Patch::Nop { start: 3, end: 5 };
+        assert_matches!(apply(&mut code, 1, &patch), Err(Error::OutOfBounds { .. }));
+
+        let patch = Patch::Nop { start: 0, end: 3 };
+        assert_eq!(apply(&mut code, 1, &patch).unwrap(), (0, 3));
+        assert_eq!(code, hex!("5b5b5b00"));
+    }
+
+    #[test]
+    fn report_broken_jumps() {
+        // push1 0x03; jump; jumpdest
+        let before = hex!("6003565b");
+        let mut after = before.to_vec();
+
+        let patch = Patch::Replace {
+            offset: 3,
+            bytes: vec![0x00],
+        };
+        let (start, end) = apply(&mut after, 1, &patch).unwrap();
+
+        let mut out = Vec::new();
+        let broken = report(&mut out, &before, &after, &[(&patch, start, end)]).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(broken, 1);
+        assert!(
+            out.contains("-   3:   jumpdest\n+   3:   stop\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("removed jumpdest at 0x3"), "{}", out);
+        assert!(out.contains("jump pushed at 0x0 targets 0x3"), "{}", out);
+    }
+
+    #[test]
+    fn report_ignores_already_broken_jumps() {
+        // push1 0x09; jump; stop
+        let before = hex!("60095600");
+        let mut after = before.to_vec();
+
+        let patch = Patch::Nop { start: 3, end: 4 };
+        let (start, end) = apply(&mut after, 1, &patch).unwrap();
+
+        let mut out = Vec::new();
+        let broken = report(&mut out, &before, &after, &[(&patch, start, end)]).unwrap();
+
+        assert_eq!(broken, 0);
+    }
+}
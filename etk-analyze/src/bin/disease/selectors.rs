@@ -66,6 +66,13 @@ impl DisplayOp {
 
 impl fmt::Display for DisplayOp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let spec = self.0.specifier();
+        if !spec.is_defined() {
+            // Unassigned opcodes can't be written as instructions, but the
+            // assembler will still emit the byte.
+            return write!(f, "%raw({:#04x})", u8::from(spec));
+        }
+
         let selectors = self.reverse_selector();
 
         if selectors.is_empty() {
@@ -100,6 +107,15 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn format_unknown_opcode() {
+        let op = ConcreteOp::from_slice(&hex!("0c"));
+        assert_eq!(DisplayOp(op).to_string(), "%raw(0x0c)");
+
+        let op = ConcreteOp::from_slice(&hex!("fe"));
+        assert_eq!(DisplayOp(op).to_string(), "invalid");
+    }
+
     #[test]
     fn format_selector_push1() {
         let bin = hex!("b6");
@@ -0,0 +1,282 @@
+//! Reporting how code or data would be split across SSTORE2 data contracts.
+
+use crate::opts::Format;
+
+use etk_analyze::sstore2::{init_code, split, Chunk, MAX_CHUNK};
+
+use etk_cli::io::{create_output, InputSource};
+use etk_cli::parse::Hex;
+
+use serde_json::json;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{ensure, Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("chunk size must be between 1 and {} bytes", MAX_CHUNK))]
+    ChunkSize { backtrace: Backtrace },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "chunk-size",
+        default_value = "24575",
+        help = "most bytes of data to put in each contract"
+    )]
+    chunk_size: usize,
+
+    #[structopt(
+        long = "deployer",
+        help = "address that deploys the chunks, to work out their pointers (with 0x prefix)"
+    )]
+    deployer: Option<Hex<[u8; 20]>>,
+
+    #[structopt(
+        long = "nonce",
+        default_value = "0",
+        help = "nonce of the deployer when it deploys the first chunk"
+    )]
+    nonce: u64,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the report"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+/// Append `bytes` to `out`, encoded as an RLP string.
+fn rlp_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    match bytes {
+        [b] if *b < 0x80 => out.push(*b),
+        _ => {
+            out.push(0x80 + bytes.len() as u8);
+            out.extend_from_slice(bytes);
+        }
+    }
+}
+
+/// The address of the contract `deployer` creates with `create` when its
+/// nonce is `nonce`.
+fn create_address(deployer: &[u8; 20], nonce: u64) -> [u8; 20] {
+    let nonce = nonce.to_be_bytes();
+    let first = nonce.iter().position(|b| *b != 0).unwrap_or(nonce.len());
+
+    let mut payload = Vec::with_capacity(30);
+    rlp_bytes(&mut payload, deployer);
+    rlp_bytes(&mut payload, &nonce[first..]);
+
+    let mut list = vec![0xc0 + payload.len() as u8];
+    list.extend_from_slice(&payload);
+
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&Keccak256::digest(&list)[12..]);
+    address
+}
+
+/// One chunk, and where it ends up if the deployer is known.
+struct Row {
+    chunk: Chunk,
+    pointer: Option<[u8; 20]>,
+}
+
+fn rows(len: usize, max: usize, deployer: Option<&[u8; 20]>, nonce: u64) -> Vec<Row> {
+    split(len, max)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk)| Row {
+            chunk,
+            pointer: deployer.map(|d| create_address(d, nonce + idx as u64)),
+        })
+        .collect()
+}
+
+fn write_text<W: Write>(out: &mut W, data: &[u8], max: usize, rows: &[Row]) -> Result<(), Error> {
+    writeln!(
+        out,
+        "{} bytes in {} chunks of at most {} bytes",
+        data.len(),
+        rows.len(),
+        max
+    )?;
+
+    for (idx, row) in rows.iter().enumerate() {
+        write!(
+            out,
+            "{:>4} {:#08x} {:>5} bytes {:>8} gas",
+            idx,
+            row.chunk.offset,
+            row.chunk.len,
+            row.chunk.deploy_gas()
+        )?;
+
+        if let Some(pointer) = row.pointer {
+            write!(out, " 0x{}", hex::encode(pointer))?;
+        }
+
+        writeln!(out)?;
+    }
+
+    let code: usize = rows.iter().map(|r| r.chunk.code_size()).sum();
+    let gas: u64 = rows.iter().map(|r| r.chunk.deploy_gas()).sum();
+    writeln!(out, "total {} bytes of code, {} gas to deploy", code, gas)?;
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, data: &[u8], max: usize, rows: &[Row]) -> Result<(), Error> {
+    let chunks: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            json!({
+                "offset": row.chunk.offset,
+                "len": row.chunk.len,
+                "code_size": row.chunk.code_size(),
+                "deploy_gas": row.chunk.deploy_gas(),
+                "pointer": row.pointer.map(|p| format!("0x{}", hex::encode(p))),
+                "init_code": format!("0x{}", hex::encode(init_code(row.chunk.data(data)))),
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "size": data.len(),
+        "chunk_size": max,
+        "deploy_gas": rows.iter().map(|r| r.chunk.deploy_gas()).sum::<u64>(),
+        "chunks": chunks,
+    });
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    ensure!(
+        opts.chunk_size > 0 && opts.chunk_size <= MAX_CHUNK,
+        ChunkSize
+    );
+
+    let mut data = Vec::new();
+    opts.src.open()?.read_to_end(&mut data)?;
+
+    let deployer = opts.deployer.as_ref().map(|d| &d.0);
+    let rows = rows(data.len(), opts.chunk_size, deployer, opts.nonce);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &data, opts.chunk_size, &rows),
+        Format::Json => write_json(&mut out, &data, opts.chunk_size, &rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    const DEPLOYER: [u8; 20] = hex!("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+
+    #[test]
+    fn create_addresses() {
+        assert_eq!(
+            create_address(&DEPLOYER, 0),
+            hex!("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d")
+        );
+        assert_eq!(
+            create_address(&DEPLOYER, 1),
+            hex!("343c43a37d37dff08ae8c4a11544c718abb4fcf8")
+        );
+        assert_eq!(
+            create_address(&DEPLOYER, 2),
+            hex!("f778b86fa74e846c4f0a1fbd1335fe81c00a0c91")
+        );
+    }
+
+    #[test]
+    fn text_report() {
+        let data = [0xaa; 5];
+        let rows = rows(data.len(), 2, Some(&DEPLOYER), 1);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &data, 2, &rows).unwrap();
+
+        let expected = "\
+5 bytes in 3 chunks of at most 2 bytes
+   0 0x000000     2 bytes    32600 gas 0x343c43a37d37dff08ae8c4a11544c718abb4fcf8
+   1 0x000002     2 bytes    32600 gas 0xf778b86fa74e846c4f0a1fbd1335fe81c00a0c91
+   2 0x000004     1 bytes    32400 gas 0xfffd933a0bc612844eaf0c6fe3e5b8e9b6c1d19c
+total 8 bytes of code, 97600 gas to deploy
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn json_report() {
+        let data = [0xaa, 0xbb, 0xcc];
+        let rows = rows(data.len(), 2, None, 0);
+
+        let mut out = Vec::new();
+        write_json(&mut out, &data, 2, &rows).unwrap();
+
+        let actual: Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!({
+            "size": 3,
+            "chunk_size": 2,
+            "deploy_gas": 65_000,
+            "chunks": [
+                {
+                    "offset": 0,
+                    "len": 2,
+                    "code_size": 3,
+                    "deploy_gas": 32_600,
+                    "pointer": null,
+                    "init_code": "0x61000380600c6000396000f300aabb",
+                },
+                {
+                    "offset": 2,
+                    "len": 1,
+                    "code_size": 2,
+                    "deploy_gas": 32_400,
+                    "pointer": null,
+                    "init_code": "0x61000280600c6000396000f300cc",
+                },
+            ],
+        });
+
+        assert_eq!(actual, expected);
+    }
+}
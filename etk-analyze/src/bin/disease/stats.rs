@@ -0,0 +1,200 @@
+//! Summarizing a contract.
+
+use crate::opts::Format;
+
+use etk_analyze::compiler::{identify, Compiler, Versions};
+use etk_analyze::dispatch::entries;
+
+use etk_asm::ops::Specifier;
+
+use etk_cli::io::{create_output, InputSource};
+
+use serde_json::json;
+
+use snafu::{Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the report"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Stats {
+    size: usize,
+    instructions: usize,
+    jumpdests: usize,
+    functions: usize,
+    compiler: Option<Compiler>,
+}
+
+impl Stats {
+    fn new(code: &[u8]) -> Self {
+        let mut instructions = 0;
+        let mut jumpdests = 0;
+        let mut offset = 0;
+
+        while offset < code.len() {
+            let spec = Specifier::from(code[offset]);
+
+            instructions += 1;
+            if spec == Specifier::JumpDest {
+                jumpdests += 1;
+            }
+
+            offset += spec.size() as usize;
+        }
+
+        Self {
+            size: code.len(),
+            instructions,
+            jumpdests,
+            functions: entries(code).len(),
+            compiler: identify(code),
+        }
+    }
+}
+
+/// Where the compiler version came from.
+fn source(versions: &Versions) -> &'static str {
+    match versions {
+        Versions::Exact(_) => "metadata",
+        Versions::Range { .. } => "idioms",
+    }
+}
+
+fn write_text<W: Write>(out: &mut W, stats: &Stats) -> Result<(), Error> {
+    writeln!(out, "size: {} bytes", stats.size)?;
+    writeln!(out, "instructions: {}", stats.instructions)?;
+    writeln!(out, "jumpdests: {}", stats.jumpdests)?;
+    writeln!(out, "functions: {}", stats.functions)?;
+
+    match stats.compiler {
+        Some(ref c) => writeln!(
+            out,
+            "compiler: {} {} (from {})",
+            c.toolchain,
+            c.versions,
+            source(&c.versions)
+        )?,
+        None => writeln!(out, "compiler: unknown")?,
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, stats: &Stats) -> Result<(), Error> {
+    let compiler = stats.compiler.map(|c| {
+        json!({
+            "toolchain": c.toolchain.to_string(),
+            "versions": c.versions.to_string(),
+            "source": source(&c.versions),
+        })
+    });
+
+    let report = json!({
+        "size": stats.size,
+        "instructions": stats.instructions,
+        "jumpdests": stats.jumpdests,
+        "functions": stats.functions,
+        "compiler": compiler,
+    });
+
+    writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let stats = Stats::new(&code);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &stats),
+        Format::Json => write_json(&mut out, &stats),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn text_report() {
+        // Free memory pointer, then `push0` and a jumpdest.
+        let code = hex!("6080604052 5f 5b 00");
+
+        let mut out = Vec::new();
+        write_text(&mut out, &Stats::new(&code)).unwrap();
+
+        let expected = "\
+size: 8 bytes
+instructions: 6
+jumpdests: 1
+functions: 0
+compiler: solidity >=0.8.20 (from idioms)
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn json_report() {
+        let code = hex!("00 a164736f6c6343000813 000a");
+
+        let mut out = Vec::new();
+        write_json(&mut out, &Stats::new(&code)).unwrap();
+
+        let actual: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!({
+            "size": 13,
+            "instructions": 8,
+            "jumpdests": 0,
+            "functions": 0,
+            "compiler": {
+                "toolchain": "solidity",
+                "versions": "0.8.19",
+                "source": "metadata",
+            },
+        });
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,262 @@
+//! Interactive disassembly browser.
+
+use crate::browser::{Browser, Hints};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+
+use etk_cli::io::InputSource;
+
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use std::io::{Read, Stdout};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::Terminal;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(context(false))]
+    Terminal {
+        source: crossterm::ErrorKind,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("could not read hints from `{}`", path.display()))]
+    ReadHints {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("`{}` isn't a hints file: {}", path.display(), message))]
+    ParseHints {
+        path: PathBuf,
+        message: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("could not write hints to `{}`", path.display()))]
+    WriteHints {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "hints",
+        parse(from_os_str),
+        help = "path to a file of labels, which is updated when labels are renamed"
+    )]
+    hints: Option<PathBuf>,
+}
+
+const HELP: &str = "↑/↓ move  enter follow  ⌫ back  n rename  q quit";
+
+/// What the keyboard is being used for.
+enum Mode {
+    Browse,
+
+    /// Typing a new label for the selected instruction.
+    Rename(String),
+}
+
+struct App {
+    browser: Browser,
+    hints_path: Option<PathBuf>,
+    mode: Mode,
+
+    /// Shown in the status line until the next key press.
+    message: Option<String>,
+}
+
+impl App {
+    fn save(&mut self) -> Result<(), Error> {
+        let path = match self.hints_path {
+            Some(ref p) => p,
+            None => {
+                self.message = Some("not saved (no --hints file)".into());
+                return Ok(());
+            }
+        };
+
+        std::fs::write(path, self.browser.hints().to_text()).context(WriteHints { path })
+    }
+
+    /// Handle a key press, returning false to quit.
+    fn key(&mut self, key: KeyEvent, page: isize) -> Result<bool, Error> {
+        self.message = None;
+
+        let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+
+        let mut label = match mode {
+            Mode::Browse => return Ok(self.browse(key, page)),
+            Mode::Rename(label) => label,
+        };
+
+        match key.code {
+            KeyCode::Enter => {
+                self.browser.rename(&label);
+                self.save()?;
+                return Ok(true);
+            }
+            KeyCode::Esc => return Ok(true),
+            KeyCode::Backspace => {
+                label.pop();
+            }
+            KeyCode::Char(c) => label.push(c),
+            _ => (),
+        }
+
+        self.mode = Mode::Rename(label);
+        Ok(true)
+    }
+
+    fn browse(&mut self, key: KeyEvent, page: isize) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if ctrl => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.browser.scroll(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.browser.scroll(1),
+            KeyCode::PageUp => self.browser.scroll(-page),
+            KeyCode::PageDown => self.browser.scroll(page),
+            KeyCode::Home | KeyCode::Char('g') => self.browser.scroll(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.browser.scroll(isize::MAX / 2),
+            KeyCode::Enter => {
+                let followed = self.browser.follow();
+                if !followed {
+                    self.message = Some("not a jump with a known target".into());
+                }
+            }
+            KeyCode::Backspace | KeyCode::Esc | KeyCode::Char('b') => {
+                self.browser.back();
+            }
+            KeyCode::Char('n') => {
+                let current = self.browser.label().unwrap_or_default().to_owned();
+                self.mode = Mode::Rename(current);
+            }
+            _ => (),
+        }
+
+        true
+    }
+
+    fn draw(&self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<isize, Error> {
+        let mut page = 1;
+
+        terminal.draw(|f| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+                .split(f.size());
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
+                .split(rows[0]);
+
+            page = columns[0].height.saturating_sub(2).max(1) as isize;
+
+            let items: Vec<_> = (0..self.browser.len())
+                .map(|idx| ListItem::new(self.browser.text(idx)))
+                .collect();
+
+            let listing = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title("disassembly"))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+            let mut state = ListState::default();
+            state.select(Some(self.browser.cursor()));
+            f.render_stateful_widget(listing, columns[0], &mut state);
+
+            let xrefs: Vec<_> = self
+                .browser
+                .xref_text()
+                .into_iter()
+                .map(ListItem::new)
+                .collect();
+            let xrefs =
+                List::new(xrefs).block(Block::default().borders(Borders::ALL).title("xrefs"));
+            f.render_widget(xrefs, columns[1]);
+
+            let status = match (&self.mode, &self.message) {
+                (Mode::Rename(label), _) => format!("rename: {}█", label),
+                (Mode::Browse, Some(message)) => message.clone(),
+                (Mode::Browse, None) => HELP.to_owned(),
+            };
+            f.render_widget(Paragraph::new(status), rows[1]);
+        })?;
+
+        Ok(page)
+    }
+}
+
+fn event_loop(app: &mut App) -> Result<(), Error> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    terminal.clear()?;
+
+    loop {
+        let page = app.draw(&mut terminal)?;
+
+        if let Event::Key(key) = event::read()? {
+            if !app.key(key, page)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let hints = match opts.hints {
+        Some(ref path) if path.exists() => {
+            let text = std::fs::read_to_string(path).context(ReadHints { path })?;
+            match Hints::parse(&text) {
+                Ok(hints) => hints,
+                Err(message) => return ParseHints { path, message }.fail(),
+            }
+        }
+        _ => Hints::default(),
+    };
+
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let mut app = App {
+        browser: Browser::new(&code, hints),
+        hints_path: opts.hints,
+        mode: Mode::Browse,
+        message: None,
+    };
+
+    enable_raw_mode()?;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+
+    let result = event_loop(&mut app);
+
+    // Put the terminal back, even if something went wrong.
+    execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+
+    result
+}
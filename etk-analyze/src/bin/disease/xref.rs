@@ -0,0 +1,175 @@
+//! Listing the references to each jumpdest and storage slot.
+
+use crate::opts::Format;
+
+use etk_analyze::xref::{xrefs, Xrefs};
+
+use etk_cli::io::{create_output, InputSource};
+
+use serde_json::json;
+
+use snafu::{Backtrace, Snafu};
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(context(false))]
+    Io {
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    #[structopt(flatten)]
+    src: InputSource,
+
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values(&["text", "json"]),
+        help = "format of the report"
+    )]
+    format: Format,
+
+    #[structopt(
+        short = "o",
+        long = "out-file",
+        help = "path to output file (`-` or omitted for stdout)"
+    )]
+    out_file: Option<PathBuf>,
+}
+
+/// Format a storage slot as a number, without leading zeros.
+fn slot_hex(slot: &[u8; 32]) -> String {
+    let digits = hex::encode(slot);
+    let digits = digits.trim_start_matches('0');
+
+    if digits.is_empty() {
+        "0x0".to_owned()
+    } else {
+        format!("0x{}", digits)
+    }
+}
+
+fn offsets(offsets: &[usize]) -> String {
+    let text: Vec<_> = offsets.iter().map(|o| format!("{:#06x}", o)).collect();
+    text.join(" ")
+}
+
+fn write_text<W: Write>(out: &mut W, found: &Xrefs) -> Result<(), Error> {
+    writeln!(out, "jumpdests:")?;
+
+    for (dest, pushes) in &found.jumpdests {
+        if pushes.is_empty() {
+            writeln!(out, "  {:#06x} (not pushed)", dest)?;
+        } else {
+            writeln!(out, "  {:#06x} pushed at {}", dest, offsets(pushes))?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "storage:")?;
+
+    for (slot, refs) in &found.slots {
+        write!(out, "  {}", slot_hex(slot))?;
+
+        if !refs.loads.is_empty() {
+            write!(out, " sload at {}", offsets(&refs.loads))?;
+        }
+
+        if !refs.stores.is_empty() {
+            write!(out, " sstore at {}", offsets(&refs.stores))?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, found: &Xrefs) -> Result<(), Error> {
+    let jumpdests: Vec<_> = found
+        .jumpdests
+        .iter()
+        .map(|(offset, pushes)| json!({ "offset": offset, "pushes": pushes }))
+        .collect();
+
+    let storage: Vec<_> = found
+        .slots
+        .iter()
+        .map(|(slot, refs)| {
+            json!({
+                "slot": slot_hex(slot),
+                "sloads": refs.loads,
+                "sstores": refs.stores,
+            })
+        })
+        .collect();
+
+    let report = json!({ "jumpdests": jumpdests, "storage": storage });
+    writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())?;
+    Ok(())
+}
+
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut code = Vec::new();
+    opts.src.open()?.read_to_end(&mut code)?;
+
+    let found = xrefs(&code);
+
+    let mut out: Box<dyn Write> = match opts.out_file {
+        Some(path) => create_output(path)?,
+        None => Box::new(std::io::stdout()),
+    };
+
+    match opts.format {
+        Format::Text => write_text(&mut out, &found),
+        Format::Json => write_json(&mut out, &found),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    // 0: push1 0x07; 2: jump; 3: push1 0x01; 5: sload; 6: stop; 7: jumpdest
+    const CODE: [u8; 8] = hex!("6007 56 6001 54 00 5b");
+
+    #[test]
+    fn text_report() {
+        let mut out = Vec::new();
+        write_text(&mut out, &xrefs(&CODE)).unwrap();
+
+        let expected = "\
+jumpdests:
+  0x0007 pushed at 0x0000
+
+storage:
+  0x1 sload at 0x0005
+";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn json_report() {
+        let mut out = Vec::new();
+        write_json(&mut out, &xrefs(&CODE)).unwrap();
+
+        let actual: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let expected = json!({
+            "jumpdests": [{ "offset": 7, "pushes": [0] }],
+            "storage": [{ "slot": "0x1", "sloads": [5], "sstores": [] }],
+        });
+
+        assert_eq!(actual, expected);
+    }
+}
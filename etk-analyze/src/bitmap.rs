@@ -0,0 +1,122 @@
+//! Bitmaps marking which bytes of a contract are valid jump destinations, and
+//! which are push data.
+//!
+//! These are the tables clients build before running code: a `jumpdest` byte
+//! is only a valid destination if it isn't part of a push's immediate.
+//!
+//! Bit `i` of each bitmap describes the byte at offset `i`, and is stored in
+//! byte `i / 8` at bit `i % 8`, least significant bit first.
+
+use etk_asm::ops::Specifier;
+
+/// One bit for each byte of a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitmap {
+    fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    fn set(&mut self, offset: usize) {
+        self.bytes[offset / 8] |= 1 << (offset % 8);
+    }
+
+    /// Whether the bit for the byte at `offset` is set. Offsets past the end
+    /// of the code aren't.
+    pub fn get(&self, offset: usize) -> bool {
+        offset < self.len && self.bytes[offset / 8] & (1 << (offset % 8)) != 0
+    }
+
+    /// Number of bits, which is the length of the code.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the code was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The packed bits, least significant bit first.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Offsets of the bytes whose bit is set, in order.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(move |o| self.get(*o))
+    }
+}
+
+/// The bitmaps for a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmaps {
+    /// Set for each `jumpdest` that can be jumped to.
+    pub jumpdests: Bitmap,
+
+    /// Set for each byte that's the immediate of a push, rather than an
+    /// instruction. Pushes cut short by the end of the code only mark the
+    /// bytes that are there.
+    pub data: Bitmap,
+}
+
+/// Build the jump destination and push data bitmaps for `code`.
+pub fn bitmaps(code: &[u8]) -> Bitmaps {
+    let mut jumpdests = Bitmap::new(code.len());
+    let mut data = Bitmap::new(code.len());
+
+    let mut offset = 0;
+    while offset < code.len() {
+        let spec = Specifier::from(code[offset]);
+
+        if spec == Specifier::JumpDest {
+            jumpdests.set(offset);
+        }
+
+        let end = code.len().min(offset + spec.size() as usize);
+        for idx in offset + 1..end {
+            data.set(idx);
+        }
+
+        offset = end;
+    }
+
+    Bitmaps { jumpdests, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn jumpdest_in_push() {
+        // 0: jumpdest; 1: push2 0x5b5b; 4: jumpdest; 5: push1 0x5b
+        let maps = bitmaps(&hex!("5b 615b5b 5b 605b"));
+
+        assert_eq!(maps.jumpdests.ones().collect::<Vec<_>>(), vec![0, 4]);
+        assert_eq!(maps.jumpdests.as_bytes(), &[0b0001_0001]);
+
+        assert_eq!(maps.data.ones().collect::<Vec<_>>(), vec![2, 3, 6]);
+        assert_eq!(maps.data.as_bytes(), &[0b0100_1100]);
+        assert!(!maps.data.get(7));
+    }
+
+    #[test]
+    fn truncated_push() {
+        // 0..8: jumpdest * 8; 8: push32, with only two bytes of its immediate.
+        let maps = bitmaps(&hex!("5b5b5b5b5b5b5b5b 7f 5b5b"));
+
+        assert_eq!(maps.jumpdests.len(), 11);
+        assert_eq!(maps.jumpdests.as_bytes(), &[0xff, 0x00]);
+        assert_eq!(maps.data.as_bytes(), &[0x00, 0b0000_0110]);
+    }
+}
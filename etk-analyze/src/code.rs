@@ -0,0 +1,47 @@
+//! Walking the instructions of a contract without building basic blocks.
+
+use etk_asm::ops::Specifier;
+
+/// An instruction, with its offset and immediate.
+pub(crate) struct Instruction<'a> {
+    pub(crate) offset: usize,
+    pub(crate) spec: Specifier,
+    pub(crate) imm: &'a [u8],
+}
+
+/// Every complete instruction in `code`, stopping at one that's truncated.
+pub(crate) fn instructions(code: &[u8]) -> Vec<Instruction<'_>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+
+    while offset < code.len() {
+        let spec = Specifier::from(code[offset]);
+        let end = offset + spec.size() as usize;
+
+        let imm = match code.get(offset + 1..end) {
+            Some(imm) => imm,
+            None => break,
+        };
+
+        out.push(Instruction { offset, spec, imm });
+        offset = end;
+    }
+
+    out
+}
+
+/// Interpret the immediate of a push as a number, if it fits in `N` bytes.
+pub(crate) fn immediate<const N: usize>(inst: &Instruction) -> Option<[u8; N]> {
+    if inst.spec.size() == 1 {
+        return None;
+    }
+
+    let skip = inst.imm.len().saturating_sub(N);
+    if inst.imm[..skip].iter().any(|b| *b != 0) {
+        return None;
+    }
+
+    let mut out = [0u8; N];
+    out[N - (inst.imm.len() - skip)..].copy_from_slice(&inst.imm[skip..]);
+    Some(out)
+}
@@ -0,0 +1,224 @@
+//! Working out which compiler version produced a contract.
+//!
+//! Solidity and Vyper append CBOR encoded metadata to the code, which includes
+//! the exact compiler version. When it's been stripped, the version is
+//! narrowed down from idioms that changed between releases, like how the free
+//! memory pointer is initialized, how the dispatcher extracts the selector,
+//! and whether failed checks revert with `Panic(uint256)`.
+
+use crate::code::{immediate, instructions};
+use crate::signatures::{find, toolchain, Toolchain, SIGNATURES};
+
+use std::convert::TryInto;
+use std::fmt;
+
+/// A compiler version, as major, minor, and patch numbers.
+pub type Version = [u8; 3];
+
+/// The compiler versions that could have produced some code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Versions {
+    /// Exactly this version, read from the metadata.
+    Exact(Version),
+
+    /// At least `min` (if set), and older than `below` (if set).
+    Range {
+        min: Option<Version>,
+        below: Option<Version>,
+    },
+}
+
+impl fmt::Display for Versions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = |v: &Version| format!("{}.{}.{}", v[0], v[1], v[2]);
+
+        match self {
+            Versions::Exact(v) => write!(f, "{}", text(v)),
+            Versions::Range {
+                min: None,
+                below: None,
+            } => write!(f, "unknown version"),
+            Versions::Range {
+                min: Some(min),
+                below: None,
+            } => write!(f, ">={}", text(min)),
+            Versions::Range {
+                min: None,
+                below: Some(below),
+            } => write!(f, "<{}", text(below)),
+            Versions::Range {
+                min: Some(min),
+                below: Some(below),
+            } => write!(f, ">={}, <{}", text(min), text(below)),
+        }
+    }
+}
+
+/// What most likely produced some code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compiler {
+    pub toolchain: Toolchain,
+    pub versions: Versions,
+}
+
+/// Read the compiler version from the metadata at the end of `code`.
+pub fn metadata(code: &[u8]) -> Option<Compiler> {
+    const SOLC_KEY: &[u8] = &[0x64, b's', b'o', b'l', b'c', 0x43];
+    const VYPER_KEY: &[u8] = &[0x65, b'v', b'y', b'p', b'e', b'r', 0x83];
+
+    let len_at = code.len().checked_sub(2)?;
+    let len = u16::from_be_bytes(code[len_at..].try_into().unwrap());
+    let map = &code[len_at.checked_sub(usize::from(len))?..len_at];
+
+    for at in 0..map.len() {
+        let rest = &map[at..];
+
+        if rest.starts_with(SOLC_KEY) {
+            let version = rest.get(SOLC_KEY.len()..SOLC_KEY.len() + 3)?;
+            return Some(Compiler {
+                toolchain: Toolchain::Solidity,
+                versions: Versions::Exact(version.try_into().unwrap()),
+            });
+        }
+
+        if rest.starts_with(VYPER_KEY) {
+            // An array of three small integers, each encoded in one byte.
+            let version = rest.get(VYPER_KEY.len()..VYPER_KEY.len() + 3)?;
+            if version.iter().any(|v| *v >= 0x18) {
+                return None;
+            }
+
+            return Some(Compiler {
+                toolchain: Toolchain::Vyper,
+                versions: Versions::Exact(version.try_into().unwrap()),
+            });
+        }
+    }
+
+    None
+}
+
+/// Narrow `min` and `below` down using the Solidity idioms in `code`.
+fn solidity_range(code: &[u8], names: &[&str]) -> (Option<Version>, Option<Version>) {
+    let mut min: Option<Version> = None;
+    let mut below: Option<Version> = None;
+
+    let mut at_least = |v: Version| min = Some(min.map_or(v, |m| m.max(v)));
+    let mut older_than = |v: Version| below = Some(below.map_or(v, |b| b.min(v)));
+
+    for name in names {
+        match *name {
+            "solidity-free-memory-pointer" => at_least([0, 4, 22]),
+            "solidity-legacy-free-memory-pointer" => older_than([0, 4, 22]),
+            "solidity-dispatcher" => at_least([0, 5, 0]),
+            "solidity-legacy-dispatcher" => older_than([0, 5, 0]),
+            _ => (),
+        }
+    }
+
+    for inst in instructions(code) {
+        // `push0`, which the Shanghai upgrade added.
+        if code[inst.offset] == 0x5f {
+            at_least([0, 8, 20]);
+        }
+
+        // Checked arithmetic and friends revert with `Panic(uint256)`.
+        if inst.imm.len() == 4 && immediate::<4>(&inst) == Some([0x4e, 0x48, 0x7b, 0x71]) {
+            at_least([0, 8, 0]);
+        }
+    }
+
+    (min, below)
+}
+
+/// Work out what most likely produced `code`, preferring its metadata when it
+/// has any.
+pub fn identify(code: &[u8]) -> Option<Compiler> {
+    if let Some(compiler) = metadata(code) {
+        return Some(compiler);
+    }
+
+    let matches = find(code, SIGNATURES);
+    let toolchain = toolchain(&matches)?;
+    let names: Vec<_> = matches.iter().map(|m| m.signature.name).collect();
+
+    let (min, below) = match toolchain {
+        Toolchain::Solidity => solidity_range(code, &names),
+        Toolchain::Vyper if names.contains(&"vyper-selector") => (None, Some([0, 3, 0])),
+        _ => (None, None),
+    };
+
+    Some(Compiler {
+        toolchain,
+        versions: Versions::Range { min, below },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn solc_metadata() {
+        // Only the compiler version from a real trailer; the rest is elided.
+        let code = hex!("00 a164736f6c6343000813 000a");
+
+        let expected = Compiler {
+            toolchain: Toolchain::Solidity,
+            versions: Versions::Exact([0, 8, 19]),
+        };
+
+        assert_eq!(metadata(&code), Some(expected));
+        assert_eq!(identify(&code), Some(expected));
+        assert_eq!(expected.versions.to_string(), "0.8.19");
+    }
+
+    #[test]
+    fn vyper_metadata() {
+        let code = hex!("00 a165767970657283000307 000b");
+
+        let expected = Compiler {
+            toolchain: Toolchain::Vyper,
+            versions: Versions::Exact([0, 3, 7]),
+        };
+
+        assert_eq!(metadata(&code), Some(expected));
+    }
+
+    #[test]
+    fn no_metadata() {
+        assert_eq!(metadata(&hex!("6000 ff00")), None);
+        assert_eq!(metadata(&hex!("00")), None);
+    }
+
+    #[test]
+    fn solidity_idioms() {
+        // Free memory pointer, dispatcher, then `push4 0x4e487b71`.
+        let code = hex!("6080604052 600436106100285760003560e01c 634e487b71 00");
+        let found = identify(&code).unwrap();
+
+        assert_eq!(found.toolchain, Toolchain::Solidity);
+        assert_eq!(found.versions.to_string(), ">=0.8.0");
+    }
+
+    #[test]
+    fn legacy_solidity() {
+        let code = hex!(
+            "
+            6080604052
+            600035 7c0100000000000000000000000000000000000000000000000000000000
+            900463ffffffff16
+            "
+        );
+
+        let found = identify(&code).unwrap();
+        assert_eq!(found.versions.to_string(), ">=0.4.22, <0.5.0");
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(identify(&hex!("6000 6000 f3")), None);
+    }
+}
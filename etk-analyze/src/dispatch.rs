@@ -0,0 +1,145 @@
+//! Recovering the function dispatcher of a contract.
+//!
+//! Compilers route calls to the right function by comparing the selector
+//! (first four bytes of the call data) against each function's selector in
+//! turn, and jumping to the function's entry point when one matches. This
+//! module looks for those comparisons.
+
+use crate::code::{immediate, instructions};
+
+use etk_asm::ops::Specifier;
+
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+/// A function the dispatcher can jump to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// The function selector.
+    pub selector: u32,
+
+    /// Offset of the `jumpdest` the dispatcher jumps to for `selector`.
+    pub offset: usize,
+}
+
+/// Find the entry points of the functions in `code`, in the order the
+/// dispatcher checks them.
+///
+/// Recognizes comparisons of the form `push4 SELECTOR; eq; push2 DEST; jumpi`,
+/// optionally with a `dup` or `swap` between the push and the `eq`, which
+/// covers the dispatchers Solidity generates. Selectors are only
+/// reported once, and only when the destination is a `jumpdest`.
+pub fn entries(code: &[u8]) -> Vec<Entry> {
+    let insts = instructions(code);
+
+    let jumpdests: BTreeSet<_> = insts
+        .iter()
+        .filter(|i| i.spec == Specifier::JumpDest)
+        .map(|i| i.offset)
+        .collect();
+
+    let mut seen = BTreeSet::new();
+    let mut found = Vec::new();
+
+    for (idx, inst) in insts.iter().enumerate() {
+        let selector = match immediate::<4>(inst) {
+            Some(s) => u32::from_be_bytes(s),
+            None => continue,
+        };
+
+        let mut rest = insts[idx + 1..].iter().peekable();
+
+        if let Some(next) = rest.peek() {
+            if matches!(
+                next.spec,
+                Specifier::Dup1 | Specifier::Dup2 | Specifier::Swap1
+            ) {
+                rest.next();
+            }
+        }
+
+        if rest.next().map(|i| i.spec) != Some(Specifier::Eq) {
+            continue;
+        }
+
+        let dest = match rest.next().and_then(immediate::<8>) {
+            Some(d) => u64::from_be_bytes(d),
+            None => continue,
+        };
+
+        if rest.next().map(|i| i.spec) != Some(Specifier::JumpI) {
+            continue;
+        }
+
+        let offset: usize = match dest.try_into() {
+            Ok(o) => o,
+            Err(_) => continue,
+        };
+
+        if jumpdests.contains(&offset) && seen.insert(selector) {
+            found.push(Entry { selector, offset });
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn solidity_dispatcher() {
+        let code = hex!(
+            "
+            60003560e01c
+            80 63a9059cbb 14 601b 57
+            80 6370a08231 14 601d 57
+            00
+            5b 00
+            5b 00
+            "
+        );
+
+        let expected = vec![
+            Entry {
+                selector: 0xa9059cbb,
+                offset: 0x1b,
+            },
+            Entry {
+                selector: 0x70a08231,
+                offset: 0x1d,
+            },
+        ];
+
+        assert_eq!(entries(&code), expected);
+    }
+
+    #[test]
+    fn swapped_comparison_and_short_push() {
+        // push3 0x00ff00; dup2; eq; push1 0x0a; jumpi; stop; jumpdest
+        let code = hex!("6200ff00 81 14 600a 57 00 5b");
+
+        assert_eq!(
+            entries(&code),
+            vec![Entry {
+                selector: 0xff00,
+                offset: 0x0a,
+            }]
+        );
+    }
+
+    #[test]
+    fn ignores_jumps_to_non_jumpdests() {
+        let code = hex!("80 63a9059cbb 14 600b 57 00 00");
+        assert!(entries(&code).is_empty());
+    }
+
+    #[test]
+    fn ignores_truncated_code() {
+        let code = hex!("80 63a9059cbb 14 61");
+        assert!(entries(&code).is_empty());
+    }
+}
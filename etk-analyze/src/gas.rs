@@ -0,0 +1,342 @@
+//! Static bounds on the gas used by a contract, starting from an offset.
+//!
+//! Only [`base_gas`](etk_asm::ops::Specifier::base_gas) is counted, so the
+//! bounds don't include dynamic costs like memory expansion or storage access.
+//! [`Bounds::dynamic`] says whether any reachable instruction has such costs.
+//!
+//! Jumps are followed only when their target is pushed immediately before
+//! them. Loops are never bounded, since the number of iterations isn't known.
+
+use crate::code::{immediate, instructions, Instruction};
+
+use etk_asm::ops::{Metadata, Specifier};
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryInto;
+
+/// Why, or how far, the gas used is bounded from above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upper {
+    /// At most this much gas is used.
+    Bounded(u64),
+
+    /// The code can loop back to the `jumpdest` at this offset.
+    Loop(usize),
+
+    /// The jump at this offset has a target that isn't known statically.
+    DynamicJump(usize),
+}
+
+/// Bounds on the gas used from an offset until execution halts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    /// At least this much gas is used, or `None` if execution can't halt
+    /// without running out of gas.
+    pub min: Option<u64>,
+
+    /// At most this much gas is used, if known.
+    pub max: Upper,
+
+    /// Whether a reachable instruction can cost more than its base gas.
+    pub dynamic: bool,
+}
+
+/// A run of instructions that are always executed together.
+#[derive(Debug)]
+struct Block {
+    /// Offset of the first instruction.
+    offset: usize,
+
+    cost: u64,
+
+    /// Indexes of the blocks that can run next.
+    next: Vec<usize>,
+
+    /// Execution can halt at the end of this block, or continue somewhere
+    /// unknown.
+    halts: bool,
+
+    /// Offset of a jump with an unknown target, ending this block.
+    dynamic_jump: Option<usize>,
+
+    dynamic: bool,
+}
+
+fn ends_block(inst: &Instruction) -> bool {
+    inst.spec.is_jump() || inst.spec.is_exit()
+}
+
+fn blocks(code: &[u8], entry: usize) -> Vec<Block> {
+    let insts = instructions(code);
+
+    let mut groups: Vec<&[Instruction]> = Vec::new();
+    let mut start = 0;
+
+    for idx in 0..insts.len() {
+        let inst = &insts[idx];
+        let starts = inst.spec == Specifier::JumpDest || inst.offset == entry;
+
+        if starts && start < idx {
+            groups.push(&insts[start..idx]);
+            start = idx;
+        }
+
+        if ends_block(inst) {
+            groups.push(&insts[start..=idx]);
+            start = idx + 1;
+        }
+    }
+
+    if start < insts.len() {
+        groups.push(&insts[start..]);
+    }
+
+    let jumpdests: HashMap<usize, usize> = groups
+        .iter()
+        .enumerate()
+        .filter(|(_, g)| g[0].spec == Specifier::JumpDest)
+        .map(|(idx, g)| (g[0].offset, idx))
+        .collect();
+
+    let mut out = Vec::with_capacity(groups.len());
+
+    for (idx, group) in groups.iter().enumerate() {
+        let last = &group[group.len() - 1];
+
+        let mut block = Block {
+            offset: group[0].offset,
+            cost: group.iter().map(|i| i.spec.base_gas()).sum(),
+            next: Vec::new(),
+            halts: false,
+            dynamic_jump: None,
+            dynamic: group.iter().any(|i| i.spec.has_dynamic_gas()),
+        };
+
+        let falls_through = last.spec == Specifier::JumpI || !ends_block(last);
+
+        if falls_through {
+            if idx + 1 < groups.len() {
+                block.next.push(idx + 1);
+            } else {
+                // Running off the end of the code is a `stop`.
+                block.halts = true;
+            }
+        }
+
+        if last.spec.is_exit() {
+            block.halts = true;
+        } else if last.spec.is_jump() {
+            let pushed = group
+                .len()
+                .checked_sub(2)
+                .and_then(|i| immediate::<8>(&group[i]))
+                .and_then(|v| u64::from_be_bytes(v).try_into().ok());
+
+            match pushed {
+                Some(target) => match jumpdests.get(&target) {
+                    Some(dest) => block.next.push(*dest),
+
+                    // Jumping somewhere other than a `jumpdest` halts.
+                    None => block.halts = true,
+                },
+                None => {
+                    block.halts = true;
+                    block.dynamic_jump = Some(last.offset);
+                }
+            }
+        }
+
+        out.push(block);
+    }
+
+    out
+}
+
+/// Cheapest path from each block to a halt, found by relaxing every block
+/// until nothing changes.
+fn minimums(blocks: &[Block]) -> Vec<Option<u64>> {
+    let mut min: Vec<Option<u64>> = vec![None; blocks.len()];
+
+    loop {
+        let mut changed = false;
+
+        for (idx, block) in blocks.iter().enumerate() {
+            let rest = block
+                .next
+                .iter()
+                .filter_map(|n| min[*n])
+                .chain(if block.halts { Some(0) } else { None })
+                .min();
+
+            let cost = match rest {
+                Some(r) => r.saturating_add(block.cost),
+                None => continue,
+            };
+
+            match min[idx] {
+                Some(m) if m <= cost => (),
+                _ => {
+                    min[idx] = Some(cost);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            return min;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Active,
+    Done(u64),
+}
+
+/// Most expensive path from `idx` to a halt, or why there isn't one.
+fn maximum(blocks: &[Block], idx: usize, seen: &mut HashMap<usize, Visit>) -> Upper {
+    match seen.get(&idx) {
+        Some(Visit::Done(max)) => return Upper::Bounded(*max),
+        Some(Visit::Active) => return Upper::Loop(blocks[idx].offset),
+        None => (),
+    }
+
+    let block = &blocks[idx];
+
+    if let Some(jump) = block.dynamic_jump {
+        return Upper::DynamicJump(jump);
+    }
+
+    seen.insert(idx, Visit::Active);
+
+    let mut rest = 0;
+
+    for next in &block.next {
+        match maximum(blocks, *next, seen) {
+            Upper::Bounded(max) => rest = rest.max(max),
+            unbounded => return unbounded,
+        }
+    }
+
+    let max = block.cost.saturating_add(rest);
+    seen.insert(idx, Visit::Done(max));
+    Upper::Bounded(max)
+}
+
+fn reachable(blocks: &[Block], start: usize) -> BTreeSet<usize> {
+    let mut found = BTreeSet::new();
+    let mut todo = vec![start];
+
+    while let Some(idx) = todo.pop() {
+        if found.insert(idx) {
+            todo.extend(&blocks[idx].next);
+        }
+    }
+
+    found
+}
+
+/// Bound the gas used by `code` when execution starts at `entry`.
+///
+/// Returns `None` if no instruction starts at `entry`.
+pub fn bounds(code: &[u8], entry: usize) -> Option<Bounds> {
+    let blocks = blocks(code, entry);
+    let start = blocks.iter().position(|b| b.offset == entry)?;
+
+    let dynamic = reachable(&blocks, start)
+        .into_iter()
+        .any(|b| blocks[b].dynamic);
+
+    Some(Bounds {
+        min: minimums(&blocks)[start],
+        max: maximum(&blocks, start, &mut HashMap::new()),
+        dynamic,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn straight_line() {
+        // push1 0x01; push1 0x02; add; stop
+        let code = hex!("6001 6002 01 00");
+
+        let expected = Bounds {
+            min: Some(9),
+            max: Upper::Bounded(9),
+            dynamic: false,
+        };
+
+        assert_eq!(bounds(&code, 0), Some(expected));
+    }
+
+    #[test]
+    fn branches() {
+        // 0: calldatasize; 1: push1 0x07; 3: jumpi; 4: push1 0x00; 6: stop
+        // 7: jumpdest; 8: push1 0x00; 10: push1 0x00; 12: sstore; 13: stop
+        let code = hex!("36 6007 57 6000 00 5b 6000 6000 55 00");
+
+        let expected = Bounds {
+            min: Some(2 + 3 + 10 + 3),
+            max: Upper::Bounded(2 + 3 + 10 + 1 + 3 + 3 + 100),
+            dynamic: true,
+        };
+
+        assert_eq!(bounds(&code, 0), Some(expected));
+
+        // Starting part way through.
+        let expected = Bounds {
+            min: Some(1 + 3 + 3 + 100),
+            max: Upper::Bounded(1 + 3 + 3 + 100),
+            dynamic: true,
+        };
+
+        assert_eq!(bounds(&code, 7), Some(expected));
+        assert_eq!(bounds(&code, 2), None);
+    }
+
+    #[test]
+    fn loops() {
+        // 0: jumpdest; 1: calldatasize; 2: push1 0x00; 4: jumpi; 5: stop
+        let code = hex!("5b 36 6000 57 00");
+        let found = bounds(&code, 0).unwrap();
+
+        assert_eq!(found.min, Some(1 + 2 + 3 + 10));
+        assert_eq!(found.max, Upper::Loop(0));
+    }
+
+    #[test]
+    fn never_halts() {
+        // 0: jumpdest; 1: push1 0x00; 3: jump
+        let code = hex!("5b 6000 56");
+        let found = bounds(&code, 0).unwrap();
+
+        assert_eq!(found.min, None);
+        assert_eq!(found.max, Upper::Loop(0));
+    }
+
+    #[test]
+    fn dynamic_jumps() {
+        // 0: callvalue; 1: jump
+        let code = hex!("34 56");
+        let found = bounds(&code, 0).unwrap();
+
+        assert_eq!(found.min, Some(2 + 8));
+        assert_eq!(found.max, Upper::DynamicJump(1));
+    }
+
+    #[test]
+    fn runs_off_the_end() {
+        // push1 0x00; pop
+        let code = hex!("6000 50");
+        let found = bounds(&code, 0).unwrap();
+
+        assert_eq!(found.min, Some(5));
+        assert_eq!(found.max, Upper::Bounded(5));
+    }
+}
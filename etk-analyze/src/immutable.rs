@@ -0,0 +1,67 @@
+//! Finding the placeholders Solidity leaves for immutable variables.
+//!
+//! Before deployment, every read of an immutable variable is a `push32` of
+//! zero, which the constructor overwrites with the real value. Compilers
+//! otherwise push zero with a shorter instruction, so a zero-filled `push32`
+//! is almost certainly one of these placeholders.
+
+use crate::code::instructions;
+
+use etk_asm::ops::Specifier;
+
+/// Offsets of the `push32` instructions that are immutable placeholders.
+pub fn references(code: &[u8]) -> Vec<usize> {
+    instructions(code)
+        .into_iter()
+        .filter(|i| i.spec == Specifier::Push32(()) && i.imm.iter().all(|b| *b == 0))
+        .map(|i| i.offset)
+        .collect()
+}
+
+/// Fill in the placeholder pushed by the `push32` at `offset` with `value`.
+///
+/// Returns false, leaving `code` unchanged, if there's no placeholder at
+/// `offset`.
+pub fn substitute(code: &mut [u8], offset: usize, value: [u8; 32]) -> bool {
+    if !references(code).contains(&offset) {
+        return false;
+    }
+
+    code[offset + 1..offset + 33].copy_from_slice(&value);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    const CODE: [u8; 69] = hex!(
+        "
+        7f 0000000000000000000000000000000000000000000000000000000000000000
+        6000
+        7f 0000000000000000000000000000000000000000000000000000000000000001
+        00
+        "
+    );
+
+    #[test]
+    fn finds_zero_push32() {
+        assert_eq!(references(&CODE), [0]);
+        assert!(references(&hex!("6000 00")).is_empty());
+    }
+
+    #[test]
+    fn substitutes() {
+        let mut code = CODE;
+        let value = [0xaa; 32];
+
+        assert!(!substitute(&mut code, 35, value));
+        assert_eq!(code, CODE);
+
+        assert!(substitute(&mut code, 0, value));
+        assert_eq!(code[1..33], value);
+        assert!(references(&code).is_empty());
+    }
+}
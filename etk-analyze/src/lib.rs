@@ -7,7 +7,18 @@
 // TODO: #![deny(unreachable_pub)]
 #![deny(missing_debug_implementations)]
 
+pub mod bitmap;
 pub mod blocks;
 #[cfg(feature = "cfg")]
 pub mod cfg;
+mod code;
+pub mod compiler;
+pub mod dispatch;
+pub mod gas;
+pub mod immutable;
+pub mod revert;
+pub mod signatures;
+pub mod sstore2;
+pub mod stack;
 mod sym;
+pub mod xref;
@@ -0,0 +1,390 @@
+//! Decoding the `Error(string)` and `Panic(uint256)` payloads that code builds
+//! before reverting.
+//!
+//! Each basic block is run on its own, tracking only constants and offsets
+//! from the free memory pointer. When a block leaves a complete payload in
+//! memory, it's decoded. Payloads built across several blocks, or by helper
+//! functions, aren't found.
+
+use crate::code::{instructions, Instruction};
+
+use etk_asm::ops::{Metadata, Specifier};
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt;
+
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Longest message that will be decoded, to keep garbage out of the output.
+const MAX_MESSAGE: u64 = 1024;
+
+/// Why code reverts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// `Error(string)`, as produced by `require` and `revert` with a message.
+    Error(String),
+
+    /// `Panic(uint256)`, as produced by failed assertions and checks.
+    Panic(u64),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Error(message) => write!(f, "Error({:?})", message),
+            Reason::Panic(code) => {
+                write!(f, "Panic({:#04x})", code)?;
+
+                if let Some(text) = panic_description(*code) {
+                    write!(f, " {}", text)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What a Solidity panic code means.
+pub fn panic_description(code: u64) -> Option<&'static str> {
+    let text = match code {
+        0x00 => "generic compiler panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop from empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to zero-initialized function",
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// A block of code that builds a revert payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revert {
+    /// Offset of the first instruction in the block.
+    pub start: usize,
+
+    /// Offset of the last instruction in the block.
+    pub end: usize,
+
+    pub reason: Reason,
+}
+
+/// A value on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Const([u8; 32]),
+
+    /// The free memory pointer, plus an offset.
+    Free(u64),
+
+    Unknown,
+}
+
+impl Value {
+    fn small(self) -> Option<u64> {
+        match self {
+            Value::Const(word) if word[..24].iter().all(|b| *b == 0) => {
+                Some(u64::from_be_bytes(word[24..].try_into().unwrap()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where a word was stored in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Addr {
+    Fixed(u64),
+    Free(u64),
+}
+
+impl Addr {
+    fn plus(self, n: u64) -> Self {
+        match self {
+            Addr::Fixed(a) => Addr::Fixed(a.saturating_add(n)),
+            Addr::Free(a) => Addr::Free(a.saturating_add(n)),
+        }
+    }
+}
+
+fn add(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut carry = 0u16;
+
+    for idx in (0..32).rev() {
+        let sum = u16::from(a[idx]) + u16::from(b[idx]) + carry;
+        out[idx] = sum as u8;
+        carry = sum >> 8;
+    }
+
+    out
+}
+
+/// Shift left by a whole number of bytes.
+fn shl(word: [u8; 32], bytes: usize) -> [u8; 32] {
+    let mut out = [0u8; 32];
+
+    if bytes < 32 {
+        out[..32 - bytes].copy_from_slice(&word[bytes..]);
+    }
+
+    out
+}
+
+#[derive(Debug, Default)]
+struct Machine {
+    stack: Vec<Value>,
+    memory: BTreeMap<Addr, [u8; 32]>,
+}
+
+impl Machine {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Unknown)
+    }
+
+    /// Make sure there are at least `n` values on the stack, adding unknown
+    /// values below the ones already there.
+    fn reserve(&mut self, n: usize) {
+        if self.stack.len() < n {
+            let mut padded = vec![Value::Unknown; n - self.stack.len()];
+            padded.append(&mut self.stack);
+            self.stack = padded;
+        }
+    }
+
+    fn step(&mut self, code: &[u8], inst: &Instruction) {
+        let byte = code[inst.offset];
+
+        let value = match inst.spec {
+            _ if inst.spec.size() > 1 => {
+                let mut word = [0u8; 32];
+                word[32 - inst.imm.len()..].copy_from_slice(inst.imm);
+                Value::Const(word)
+            }
+
+            _ if (0x80..=0x8f).contains(&byte) => {
+                let n = usize::from(byte - 0x80) + 1;
+                self.reserve(n);
+                self.stack[self.stack.len() - n]
+            }
+
+            _ if (0x90..=0x9f).contains(&byte) => {
+                let n = usize::from(byte - 0x90) + 1;
+                self.reserve(n + 1);
+                let top = self.stack.len() - 1;
+                self.stack.swap(top, top - n);
+                return;
+            }
+
+            Specifier::Add => match (self.pop(), self.pop()) {
+                (Value::Const(a), Value::Const(b)) => Value::Const(add(a, b)),
+                (Value::Free(a), n) | (n, Value::Free(a)) => match n.small() {
+                    Some(n) if n < MAX_MESSAGE => Value::Free(a.saturating_add(n)),
+                    _ => Value::Unknown,
+                },
+                _ => Value::Unknown,
+            },
+
+            Specifier::Shl => match (self.pop().small(), self.pop()) {
+                (Some(shift), Value::Const(word)) if shift % 8 == 0 => {
+                    Value::Const(shl(word, (shift / 8).min(32) as usize))
+                }
+                _ => Value::Unknown,
+            },
+
+            Specifier::MLoad => match self.pop().small() {
+                Some(0x40) => Value::Free(0),
+                _ => Value::Unknown,
+            },
+
+            Specifier::MStore => {
+                let addr = match self.pop() {
+                    Value::Free(a) => Some(Addr::Free(a)),
+                    v => v.small().filter(|a| *a < MAX_MESSAGE).map(Addr::Fixed),
+                };
+
+                let value = self.pop();
+
+                if let Some(addr) = addr {
+                    match value {
+                        Value::Const(word) => {
+                            self.memory.insert(addr, word);
+                        }
+                        _ => {
+                            self.memory.remove(&addr);
+                        }
+                    }
+                }
+
+                return;
+            }
+
+            _ => {
+                for _ in 0..inst.spec.pops() {
+                    self.pop();
+                }
+
+                for _ in 0..inst.spec.pushes() {
+                    self.stack.push(Value::Unknown);
+                }
+
+                return;
+            }
+        };
+
+        self.stack.push(value);
+    }
+
+    fn word(&self, addr: Addr) -> Option<&[u8; 32]> {
+        self.memory.get(&addr)
+    }
+
+    /// Decode a payload stored in memory at `base`.
+    fn payload(&self, base: Addr) -> Option<Reason> {
+        let selector = &self.word(base)?[..4];
+
+        if selector == PANIC_SELECTOR {
+            let code = Value::Const(*self.word(base.plus(4))?).small()?;
+            return Some(Reason::Panic(code));
+        }
+
+        if selector != ERROR_SELECTOR {
+            return None;
+        }
+
+        let offset = Value::Const(*self.word(base.plus(4))?).small()?;
+        let len = Value::Const(*self.word(base.plus(0x24))?).small()?;
+
+        if offset != 0x20 || len > MAX_MESSAGE {
+            return None;
+        }
+
+        let mut message = Vec::new();
+        let mut at = 0x44;
+
+        while (message.len() as u64) < len {
+            message.extend_from_slice(self.word(base.plus(at))?);
+            at += 32;
+        }
+
+        message.truncate(len as usize);
+        Some(Reason::Error(
+            String::from_utf8_lossy(&message).into_owned(),
+        ))
+    }
+}
+
+fn ends_block(inst: &Instruction) -> bool {
+    inst.spec.is_jump() || inst.spec.is_exit()
+}
+
+/// Find the blocks of `code` that build a revert payload, in order.
+pub fn reverts(code: &[u8]) -> Vec<Revert> {
+    let insts = instructions(code);
+    let mut found = Vec::new();
+
+    let mut machine = Machine::default();
+    let mut start = 0;
+
+    for (idx, inst) in insts.iter().enumerate() {
+        if inst.spec == Specifier::JumpDest {
+            machine = Machine::default();
+            start = idx;
+        }
+
+        machine.step(code, inst);
+
+        let last = match insts.get(idx + 1) {
+            None => true,
+            Some(next) => next.spec == Specifier::JumpDest || ends_block(inst),
+        };
+
+        if !last {
+            continue;
+        }
+
+        let reason = machine
+            .payload(Addr::Free(0))
+            .or_else(|| machine.payload(Addr::Fixed(0)));
+
+        if let Some(reason) = reason {
+            found.push(Revert {
+                start: insts[start].offset,
+                end: inst.offset,
+                reason,
+            });
+        }
+
+        machine = Machine::default();
+        start = idx + 1;
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn panic() {
+        // push4 0x4e487b71; push1 0xe0; shl; push1 0x00; mstore
+        // push1 0x11; push1 0x04; mstore; push1 0x24; push1 0x00; revert
+        let code = hex!("634e487b71 60e0 1b 6000 52 6011 6004 52 6024 6000 fd");
+
+        let expected = Revert {
+            start: 0,
+            end: 20,
+            reason: Reason::Panic(0x11),
+        };
+
+        assert_eq!(reverts(&code), [expected]);
+        assert_eq!(
+            Reason::Panic(0x11).to_string(),
+            "Panic(0x11) arithmetic overflow or underflow"
+        );
+    }
+
+    #[test]
+    fn error_string() {
+        let code = hex!(
+            "
+            6000 35 6006 57
+            5b
+            6040 51
+            7f 08c379a000000000000000000000000000000000000000000000000000000000
+            81 52
+            6020 6004 82 01 52
+            6009 6024 82 01 52
+            7f 6e6f74206f776e65720000000000000000000000000000000000000000000000
+            6044 82 01 52
+            6064 01 6000 fd
+            "
+        );
+
+        let found = reverts(&code);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].start, 6);
+        assert_eq!(found[0].reason, Reason::Error("not owner".into()));
+        assert_eq!(found[0].reason.to_string(), "Error(\"not owner\")");
+    }
+
+    #[test]
+    fn incomplete_payload() {
+        // The selector, but no panic code.
+        let code = hex!("634e487b71 60e0 1b 6000 52 6024 6000 fd");
+        assert!(reverts(&code).is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! Recognizing code fragments produced by well-known compilers and libraries.
+//!
+//! Each [`Signature`] is a short run of bytecode, with wildcards for the
+//! bytes (like jump destinations and addresses) that change from contract to
+//! contract. Signatures only match where an instruction starts.
+
+use crate::code::instructions;
+
+use std::fmt;
+
+/// What produced a fragment of code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Toolchain {
+    /// The Solidity compiler, including libraries written in Solidity.
+    Solidity,
+
+    /// The Vyper compiler.
+    Vyper,
+
+    /// Hand-written minimal proxies, as described in EIP-1167.
+    MinimalProxy,
+}
+
+impl fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Toolchain::Solidity => "solidity",
+            Toolchain::Vyper => "vyper",
+            Toolchain::MinimalProxy => "minimal-proxy",
+        };
+
+        write!(f, "{}", text)
+    }
+}
+
+/// A known fragment of code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    /// Short name for the fragment.
+    pub name: &'static str,
+
+    pub toolchain: Toolchain,
+
+    /// The fragment, in hex. Each `__` matches any byte. Spaces are ignored.
+    pub pattern: &'static str,
+}
+
+impl Signature {
+    fn bytes(&self) -> Vec<Option<u8>> {
+        let digits: Vec<_> = self.pattern.bytes().filter(|b| *b != b' ').collect();
+
+        digits
+            .chunks(2)
+            .map(|pair| match pair {
+                b"__" => None,
+                _ => {
+                    let text = std::str::from_utf8(pair).unwrap();
+                    Some(u8::from_str_radix(text, 16).unwrap())
+                }
+            })
+            .collect()
+    }
+}
+
+/// The built-in signature database.
+pub static SIGNATURES: &[Signature] = &[
+    Signature {
+        name: "solidity-free-memory-pointer",
+        toolchain: Toolchain::Solidity,
+        pattern: "6080 6040 52",
+    },
+    Signature {
+        name: "solidity-legacy-free-memory-pointer",
+        toolchain: Toolchain::Solidity,
+        pattern: "6060 6040 52",
+    },
+    Signature {
+        name: "solidity-dispatcher",
+        toolchain: Toolchain::Solidity,
+        pattern: "6004 36 10 61____ 57 6000 35 60e0 1c",
+    },
+    Signature {
+        name: "solidity-legacy-dispatcher",
+        toolchain: Toolchain::Solidity,
+        pattern: "6000 35 7c 0100000000000000000000000000000000000000000000000000000000 90 04 63 ffffffff 16",
+    },
+    Signature {
+        name: "solidity-nonpayable-check",
+        toolchain: Toolchain::Solidity,
+        pattern: "34 80 15 61____ 57 6000 80 fd",
+    },
+    Signature {
+        name: "openzeppelin-proxy-delegate",
+        toolchain: Toolchain::Solidity,
+        pattern: "36 6000 80 37 6000 80 36 6000 84 5a f4 3d 6000 80 3e",
+    },
+    Signature {
+        name: "erc1967-implementation-slot",
+        toolchain: Toolchain::Solidity,
+        pattern: "7f 360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc",
+    },
+    Signature {
+        name: "vyper-selector",
+        toolchain: Toolchain::Vyper,
+        pattern: "6000 35 601c 52",
+    },
+    Signature {
+        name: "vyper-address-bound",
+        toolchain: Toolchain::Vyper,
+        pattern: "74 0100000000000000000000000000000000000000 6020 52",
+    },
+    Signature {
+        name: "minimal-proxy",
+        toolchain: Toolchain::MinimalProxy,
+        pattern: "363d3d373d3d3d363d73 ________________________________________ 5af43d82803e903d91602b57fd5bf3",
+    },
+];
+
+/// Where a signature was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    /// Offset of the first instruction of the fragment.
+    pub offset: usize,
+
+    pub signature: &'static Signature,
+}
+
+/// Find every occurrence of `signatures` in `code`, in order of offset.
+pub fn find(code: &[u8], signatures: &'static [Signature]) -> Vec<Match> {
+    let patterns: Vec<_> = signatures.iter().map(Signature::bytes).collect();
+    let mut found = Vec::new();
+
+    for inst in instructions(code) {
+        let rest = &code[inst.offset..];
+
+        for (signature, pattern) in signatures.iter().zip(&patterns) {
+            if pattern.len() > rest.len() {
+                continue;
+            }
+
+            let matched = pattern
+                .iter()
+                .zip(rest)
+                .all(|(p, b)| p.is_none() || *p == Some(*b));
+
+            if matched {
+                found.push(Match {
+                    offset: inst.offset,
+                    signature,
+                });
+            }
+        }
+    }
+
+    found
+}
+
+/// The toolchain with the most matches, which most likely produced the code.
+/// Ties go to the toolchain matched first.
+pub fn toolchain(matches: &[Match]) -> Option<Toolchain> {
+    let mut counts: Vec<(Toolchain, usize)> = Vec::new();
+
+    for m in matches {
+        let toolchain = m.signature.toolchain;
+
+        match counts.iter_mut().find(|(t, _)| *t == toolchain) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((toolchain, 1)),
+        }
+    }
+
+    let most = counts.iter().map(|(_, c)| *c).max()?;
+    counts.into_iter().find(|(_, c)| *c == most).map(|(t, _)| t)
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn names(matches: &[Match]) -> Vec<(usize, &'static str)> {
+        matches
+            .iter()
+            .map(|m| (m.offset, m.signature.name))
+            .collect()
+    }
+
+    #[test]
+    fn patterns_are_valid() {
+        for signature in SIGNATURES {
+            let digits = signature.pattern.replace(' ', "");
+            assert_eq!(digits.len() % 2, 0, "{}", signature.name);
+            assert!(!signature.bytes().is_empty());
+        }
+    }
+
+    #[test]
+    fn solidity() {
+        let code = hex!("6080604052 34801561000f57600080fd5b50 600436106100285760003560e01c 00");
+
+        let found = find(&code, SIGNATURES);
+
+        assert_eq!(
+            names(&found),
+            [
+                (0, "solidity-free-memory-pointer"),
+                (5, "solidity-nonpayable-check"),
+                (18, "solidity-dispatcher"),
+            ]
+        );
+
+        assert_eq!(toolchain(&found), Some(Toolchain::Solidity));
+    }
+
+    #[test]
+    fn minimal_proxy() {
+        let code = hex!(
+            "363d3d373d3d3d363d73bebebebebebebebebebebebebebebebebebebebe5af43d82803e903d91602b57fd5bf3"
+        );
+
+        let found = find(&code, SIGNATURES);
+
+        assert_eq!(names(&found), [(0, "minimal-proxy")]);
+        assert_eq!(toolchain(&found), Some(Toolchain::MinimalProxy));
+    }
+
+    #[test]
+    fn only_at_instruction_boundaries() {
+        // push5 0x6080604052
+        let code = hex!("646080604052");
+        assert!(find(&code, SIGNATURES).is_empty());
+        assert_eq!(toolchain(&[]), None);
+    }
+}
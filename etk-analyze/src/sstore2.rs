@@ -0,0 +1,146 @@
+//! Splitting data across SSTORE2-style data contracts.
+//!
+//! [SSTORE2] stores data as the code of a contract, which is much cheaper to
+//! read back than storage. The contract's code is a `stop`, so it can't be
+//! called, followed by the data. A contract can only be so large, so a bigger
+//! blob has to be split into chunks, each in a contract of its own, with the
+//! address of each one (its pointer) kept somewhere to find it again.
+//!
+//! [SSTORE2]: https://github.com/0xsequence/sstore2
+
+use std::convert::TryFrom;
+
+/// The most data one contract can hold: the largest contract allowed by
+/// [EIP-170](https://eips.ethereum.org/EIPS/eip-170), less the `stop`.
+pub const MAX_CHUNK: usize = 24_575;
+
+/// The init code that deploys a chunk, up to the `stop` and data it returns.
+/// The size of the contract goes in the zeros after `0x61`.
+const HEADER: [u8; 12] = [
+    0x61, 0x00, 0x00, 0x80, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3,
+];
+
+/// Gas charged for each byte of code a contract is deployed with.
+const DEPOSIT_GAS: u64 = 200;
+
+/// Gas charged for a `create`.
+const CREATE_GAS: u64 = 32_000;
+
+/// Part of the data, stored in one contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// Where the chunk starts in the data.
+    pub offset: usize,
+
+    /// How many bytes of data are in the chunk.
+    pub len: usize,
+}
+
+impl Chunk {
+    /// The bytes of `data` in this chunk.
+    pub fn data<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.offset..self.offset + self.len]
+    }
+
+    /// The size of the contract holding this chunk, including the `stop`.
+    pub fn code_size(&self) -> usize {
+        self.len + 1
+    }
+
+    /// The gas a `create` deploying this chunk costs, including the code
+    /// deposit, but not the transaction carrying the init code or running it.
+    pub fn deploy_gas(&self) -> u64 {
+        CREATE_GAS + DEPOSIT_GAS * self.code_size() as u64
+    }
+}
+
+/// Split `len` bytes of data into chunks of at most `max` bytes each.
+///
+/// ## Panics
+///
+/// If `max` is zero or larger than [`MAX_CHUNK`].
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_analyze::sstore2::{split, Chunk, MAX_CHUNK};
+///
+/// let chunks = split(50_000, MAX_CHUNK);
+///
+/// assert_eq!(chunks.len(), 3);
+/// assert_eq!(chunks[2], Chunk { offset: 49_150, len: 850 });
+/// ```
+pub fn split(len: usize, max: usize) -> Vec<Chunk> {
+    assert!(max > 0 && max <= MAX_CHUNK, "chunk size out of range");
+
+    (0..len)
+        .step_by(max)
+        .map(|offset| Chunk {
+            offset,
+            len: std::cmp::min(max, len - offset),
+        })
+        .collect()
+}
+
+/// The init code that deploys `data` as a data contract.
+///
+/// ## Panics
+///
+/// If `data` is longer than [`MAX_CHUNK`].
+pub fn init_code(data: &[u8]) -> Vec<u8> {
+    assert!(data.len() <= MAX_CHUNK, "too much data for one contract");
+
+    let size = u16::try_from(data.len() + 1).unwrap();
+
+    let mut code = HEADER.to_vec();
+    code[1..3].copy_from_slice(&size.to_be_bytes());
+    code.push(0x00);
+    code.extend_from_slice(data);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hex_literal::hex;
+
+    #[test]
+    fn split_exact() {
+        assert_eq!(split(0, 10), []);
+        assert_eq!(
+            split(20, 10),
+            [
+                Chunk { offset: 0, len: 10 },
+                Chunk {
+                    offset: 10,
+                    len: 10
+                }
+            ]
+        );
+        assert_eq!(split(5, MAX_CHUNK), [Chunk { offset: 0, len: 5 }]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_too_large() {
+        split(1, MAX_CHUNK + 1);
+    }
+
+    #[test]
+    fn chunk_costs() {
+        let chunk = Chunk { offset: 3, len: 2 };
+
+        assert_eq!(chunk.data(b"abcdefg"), b"de");
+        assert_eq!(chunk.code_size(), 3);
+        assert_eq!(chunk.deploy_gas(), 32_600);
+    }
+
+    #[test]
+    fn init_code_layout() {
+        assert_eq!(
+            init_code(&[0xaa, 0xbb]),
+            hex!("610003 80 600c 6000 39 6000 f3 00 aabb")
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Naming the values on the stack after each instruction.
+//!
+//! Values are named after where they came from: constants by their value, the
+//! first four bytes of the calldata as `selector`, and loads as
+//! `calldata[..]`, `storage[..]`, or `memory[..]`. Values computed from others
+//! are written as calls, like `add(0x04, calldata[0x24])`, unless that would be
+//! too long to read.
+//!
+//! [`reachable`] uses the same analysis to find the code a function can run.
+//!
+//! Jumps are followed only when their target is a constant. Where control can
+//! arrive from more than one place, values that differ are unknown (`?`), and
+//! where the bottom of the stack can't be known, like after a dynamic jump, it
+//! is written as `..`.
+
+use crate::code::{instructions, Instruction};
+
+use etk_asm::ops::{Metadata, Specifier};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Names longer than this are shortened to `op(..)`.
+const MAX_NAME: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    /// A constant, as big-endian bytes without leading zeros.
+    Const(Vec<u8>),
+    Name(String),
+    Unknown,
+}
+
+impl Value {
+    fn constant(bytes: &[u8]) -> Self {
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        Value::Const(bytes[start..].to_vec())
+    }
+
+    fn is_const(&self, bytes: &[u8]) -> bool {
+        matches!(self, Value::Const(c) if c == bytes)
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        matches!(self, Value::Name(n) if n == name)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Const(c) if c.is_empty() => write!(f, "0x00"),
+            Value::Const(c) => write!(f, "0x{}", hex::encode(c)),
+            Value::Name(n) => write!(f, "{}", n),
+            Value::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// The values on the stack at some point in the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stack {
+    /// Bottom first.
+    items: Vec<Value>,
+
+    /// Whether `items` is everything on the stack.
+    complete: bool,
+}
+
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+
+        for (idx, item) in self.items.iter().rev().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+
+        if !self.complete {
+            if !self.items.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "..")?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+impl Stack {
+    fn empty() -> Self {
+        Self {
+            items: Vec::new(),
+            complete: true,
+        }
+    }
+
+    fn unknown() -> Self {
+        Self {
+            items: Vec::new(),
+            complete: false,
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.items.pop().unwrap_or(Value::Unknown)
+    }
+
+    /// Make sure the top `depth` items are known to be there, even if they're
+    /// unknown.
+    fn reach(&mut self, depth: usize) {
+        if !self.complete && self.items.len() < depth {
+            let missing = depth - self.items.len();
+            self.items.splice(0..0, vec![Value::Unknown; missing]);
+        }
+    }
+
+    /// Keep only what `self` and `other` agree on. Returns whether `self`
+    /// changed.
+    fn merge(&mut self, other: &Self) -> bool {
+        let before = self.clone();
+
+        let len = self.items.len().min(other.items.len());
+        if len < self.items.len() || len < other.items.len() || !other.complete {
+            self.complete = false;
+        }
+
+        self.items.drain(..self.items.len() - len);
+        let skip = other.items.len() - len;
+
+        for (mine, theirs) in self.items.iter_mut().zip(&other.items[skip..]) {
+            if mine != theirs {
+                *mine = Value::Unknown;
+            }
+        }
+
+        *self != before
+    }
+
+    /// The constant on top of the stack, as a jump target.
+    fn target(&self) -> Option<usize> {
+        match self.items.last()? {
+            Value::Const(c) if c.len() <= 8 => {
+                let mut bytes = [0u8; 8];
+                bytes[8 - c.len()..].copy_from_slice(c);
+                usize::try_from(u64::from_be_bytes(bytes)).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn step(&mut self, inst: &Instruction) {
+        let spec = inst.spec;
+        let byte = u8::from(spec);
+
+        if spec.size() > 1 {
+            self.items.push(Value::constant(inst.imm));
+            return;
+        }
+
+        if (0x80..=0x8f).contains(&byte) {
+            let depth = usize::from(byte - 0x80) + 1;
+            self.reach(depth);
+            let value = match self.items.len().checked_sub(depth) {
+                Some(idx) => self.items[idx].clone(),
+                None => Value::Unknown,
+            };
+            self.items.push(value);
+            return;
+        }
+
+        if (0x90..=0x9f).contains(&byte) {
+            let depth = usize::from(byte - 0x90) + 2;
+            self.reach(depth);
+            let len = self.items.len();
+            if len >= depth {
+                self.items.swap(len - 1, len - depth);
+            }
+            return;
+        }
+
+        if !spec.is_defined() {
+            return;
+        }
+
+        let args: Vec<_> = (0..spec.pops()).map(|_| self.pop()).collect();
+
+        if spec.pushes() == 1 {
+            self.items.push(name(spec, &args));
+        }
+    }
+}
+
+/// Name the value that `spec` computes from `args` (top of the stack first.)
+fn name(spec: Specifier, args: &[Value]) -> Value {
+    let selector = Value::Name("selector".into());
+    let mut shift = vec![1];
+    shift.extend_from_slice(&[0; 28]);
+
+    match (spec, args) {
+        (Specifier::Shr, [s, v]) if s.is_const(&[0xe0]) && v.is_name("calldata[0x00]") => {
+            return selector
+        }
+        (Specifier::Div, [v, d]) if v.is_name("calldata[0x00]") && d.is_const(&shift) => {
+            return selector
+        }
+        (Specifier::And, [a, b])
+            if (a.is_const(&[0xff; 4]) && *b == selector)
+                || (*a == selector && b.is_const(&[0xff; 4])) =>
+        {
+            return selector
+        }
+        (_, []) => return Value::Name(spec.mnemonic().into()),
+        _ => (),
+    }
+
+    let (open, close, what) = match spec {
+        Specifier::CallDataLoad => ("[", "]", "calldata"),
+        Specifier::SLoad => ("[", "]", "storage"),
+        Specifier::MLoad => ("[", "]", "memory"),
+        _ => ("(", ")", spec.mnemonic()),
+    };
+
+    let args: Vec<_> = args.iter().map(Value::to_string).collect();
+    let full = format!("{}{}{}{}", what, open, args.join(", "), close);
+
+    if full.len() > MAX_NAME {
+        Value::Name(format!("{}{}..{}", what, open, close))
+    } else {
+        Value::Name(full)
+    }
+}
+
+/// Instructions that are always executed together, by index.
+struct Block {
+    start: usize,
+    end: usize,
+}
+
+/// What's on the stack at the start of each block, as far as it's known.
+struct Flow<'a> {
+    insts: Vec<Instruction<'a>>,
+    blocks: Vec<Block>,
+
+    /// The stack at the start of each block, or `None` if the block can't be
+    /// reached.
+    entries: Vec<Option<Stack>>,
+}
+
+/// Follow the stack from the instruction at offset `entry`, which starts with
+/// `stack` on it.
+fn flow(code: &[u8], entry: usize, stack: Stack) -> Flow<'_> {
+    let insts = instructions(code);
+
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    for (idx, inst) in insts.iter().enumerate() {
+        let starts = inst.spec == Specifier::JumpDest || inst.offset == entry;
+        if starts && start < idx {
+            blocks.push(Block { start, end: idx });
+            start = idx;
+        }
+
+        if inst.spec.is_jump() || inst.spec.is_exit() || !inst.spec.is_defined() {
+            blocks.push(Block {
+                start,
+                end: idx + 1,
+            });
+            start = idx + 1;
+        }
+    }
+
+    if start < insts.len() {
+        blocks.push(Block {
+            start,
+            end: insts.len(),
+        });
+    }
+
+    let jumpdests: HashMap<usize, usize> = blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| insts[b.start].spec == Specifier::JumpDest)
+        .map(|(idx, b)| (insts[b.start].offset, idx))
+        .collect();
+
+    let mut entries: Vec<Option<Stack>> = vec![None; blocks.len()];
+    let mut queue = VecDeque::new();
+
+    if let Some(first) = blocks.iter().position(|b| insts[b.start].offset == entry) {
+        entries[first] = Some(stack);
+        queue.push_back(first);
+    }
+
+    while let Some(idx) = queue.pop_front() {
+        let block = &blocks[idx];
+        let mut stack = entries[idx].clone().unwrap();
+        let mut next = Vec::new();
+
+        for inst in &insts[block.start..block.end] {
+            if inst.spec.is_jump() {
+                if let Some(dest) = stack.target().and_then(|t| jumpdests.get(&t)) {
+                    next.push(*dest);
+                }
+            }
+
+            stack.step(inst);
+        }
+
+        let last = insts[block.end - 1].spec;
+        let falls_through =
+            last == Specifier::JumpI || !(last.is_jump() || last.is_exit() || !last.is_defined());
+
+        if falls_through && idx + 1 < blocks.len() {
+            next.push(idx + 1);
+        }
+
+        for dest in next {
+            let changed = match &mut entries[dest] {
+                Some(entry) => entry.merge(&stack),
+                entry @ None => {
+                    *entry = Some(stack.clone());
+                    true
+                }
+            };
+
+            if changed {
+                queue.push_back(dest);
+            }
+        }
+    }
+
+    Flow {
+        insts,
+        blocks,
+        entries,
+    }
+}
+
+/// Name the values on the stack after each instruction in `code`, keyed on the
+/// instruction's offset.
+pub fn stacks(code: &[u8]) -> BTreeMap<usize, Stack> {
+    let flow = flow(code, 0, Stack::empty());
+    let mut out = BTreeMap::new();
+
+    for (block, entry) in flow.blocks.iter().zip(flow.entries) {
+        let mut stack = entry.unwrap_or_else(Stack::unknown);
+        for inst in &flow.insts[block.start..block.end] {
+            stack.step(inst);
+            out.insert(inst.offset, stack.clone());
+        }
+    }
+
+    out
+}
+
+/// Offsets of the instructions that can run once execution reaches the
+/// instruction at `entry`, like a function's entry point.
+///
+/// Jumps are followed when their target is a constant, even if it was pushed
+/// long before the jump, so internal functions that return to a pushed
+/// address are followed back to their caller.
+pub fn reachable(code: &[u8], entry: usize) -> BTreeSet<usize> {
+    let flow = flow(code, entry, Stack::unknown());
+    let mut out = BTreeSet::new();
+
+    for (block, entry) in flow.blocks.iter().zip(&flow.entries) {
+        if entry.is_some() {
+            out.extend(flow.insts[block.start..block.end].iter().map(|i| i.offset));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn texts(code: &[u8]) -> Vec<(usize, String)> {
+        stacks(code)
+            .into_iter()
+            .map(|(offset, stack)| (offset, stack.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dispatcher() {
+        //  0: push1 0x00; 2: calldataload; 3: push1 0xe0; 5: shr; 6: dup1
+        //  7: push4 0xa9059cbb; 12: eq; 13: push1 0x12; 15: jumpi; 16: pop; 17: stop
+        // 18: jumpdest; 19: callvalue; 20: stop
+        let code = hex!("6000 35 60e0 1c 80 63a9059cbb 14 6012 57 50 00 5b 34 00");
+
+        let expected = vec![
+            (0, "[0x00]"),
+            (2, "[calldata[0x00]]"),
+            (3, "[0xe0, calldata[0x00]]"),
+            (5, "[selector]"),
+            (6, "[selector, selector]"),
+            (7, "[0xa9059cbb, selector, selector]"),
+            (12, "[eq(0xa9059cbb, selector), selector]"),
+            (13, "[0x12, eq(0xa9059cbb, selector), selector]"),
+            (15, "[selector]"),
+            (16, "[]"),
+            (17, "[]"),
+            (18, "[selector]"),
+            (19, "[callvalue, selector]"),
+            (20, "[callvalue, selector]"),
+        ];
+
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(o, s)| (o, s.to_owned()))
+            .collect();
+
+        assert_eq!(texts(&code), expected);
+    }
+
+    #[test]
+    fn merge_and_unknown() {
+        //  0: calldatasize; 1: push1 0x08; 3: jumpi; 4: caller; 5: push1 0x09; 7: jump
+        //  8: jumpdest; 9: jumpdest; 10: swap1; 11: stop
+        // 12: jumpdest; 13: pop; 14: stop
+        let code = hex!("36 6008 57 33 6009 56 5b 5b 90 00 5b 50 00");
+        let stacks = stacks(&code);
+
+        // Reached by jumping with `[]`, and from 0x04 with `[caller]`.
+        assert_eq!(stacks[&8].to_string(), "[]");
+        assert_eq!(stacks[&9].to_string(), "[..]");
+        assert_eq!(stacks[&10].to_string(), "[?, ?, ..]");
+
+        // Never reached.
+        assert_eq!(stacks[&13].to_string(), "[..]");
+    }
+
+    #[test]
+    fn long_names() {
+        // push1 0x24; calldataload; push1 0x04; calldataload; add; sload
+        let code = hex!("6024 35 6004 35 01 54");
+        let stacks = stacks(&code);
+
+        assert_eq!(
+            stacks[&6].to_string(),
+            "[add(calldata[0x04], calldata[0x24])]"
+        );
+        assert_eq!(stacks[&7].to_string(), "[storage[..]]");
+    }
+
+    #[test]
+    fn reachable_function() {
+        //  0: push1 0x00; 2: calldataload; 3: push1 0xe0; 5: shr
+        //  6: dup1; 7: push4 0x00000001; 12: eq; 13: push1 0x1b; 15: jumpi
+        // 16: dup1; 17: push4 0x00000002; 22: eq; 23: push1 0x21; 25: jumpi; 26: stop
+        // 27: jumpdest; 28: push1 0x24; 30: push1 0x26; 32: jump
+        // 33: jumpdest; 34: callvalue; 35: stop
+        // 36: jumpdest; 37: stop
+        // 38: jumpdest; 39: jump
+        let code = hex!(
+            "
+            6000 35 60e0 1c
+            80 6300000001 14 601b 57
+            80 6300000002 14 6021 57 00
+            5b 6024 6026 56
+            5b 34 00
+            5b 00
+            5b 56
+            "
+        );
+
+        // Pushes the return address 0x24, then calls 0x26, which returns.
+        let expected: BTreeSet<usize> = vec![27, 28, 30, 32, 36, 37, 38, 39].into_iter().collect();
+        assert_eq!(reachable(&code, 0x1b), expected);
+
+        let expected: BTreeSet<usize> = vec![33, 34, 35].into_iter().collect();
+        assert_eq!(reachable(&code, 0x21), expected);
+    }
+}
@@ -0,0 +1,138 @@
+//! Cross-references to jump destinations and storage slots.
+//!
+//! Only constants are followed: a `jumpdest` is referenced by any push of its
+//! offset, and a storage slot by an `sload` or `sstore` immediately after a
+//! push of the slot.
+
+use crate::code::{immediate, instructions};
+
+use etk_asm::ops::Specifier;
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+/// The places that use a storage slot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlotRefs {
+    /// Offsets of the `sload` instructions that read the slot.
+    pub loads: Vec<usize>,
+
+    /// Offsets of the `sstore` instructions that write the slot.
+    pub stores: Vec<usize>,
+}
+
+/// Cross-references found in a contract.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Xrefs {
+    /// For each `jumpdest`, the offsets of the pushes of its offset. Every
+    /// `jumpdest` is included, even if nothing refers to it.
+    pub jumpdests: BTreeMap<usize, Vec<usize>>,
+
+    /// For each constant storage slot, the instructions that access it.
+    pub slots: BTreeMap<[u8; 32], SlotRefs>,
+}
+
+/// Find the cross-references in `code`.
+pub fn xrefs(code: &[u8]) -> Xrefs {
+    let insts = instructions(code);
+    let mut found = Xrefs::default();
+
+    for inst in &insts {
+        if inst.spec == Specifier::JumpDest {
+            found.jumpdests.insert(inst.offset, Vec::new());
+        }
+    }
+
+    for (idx, inst) in insts.iter().enumerate() {
+        if let Some(value) = immediate::<8>(inst) {
+            let target: Option<usize> = u64::from_be_bytes(value).try_into().ok();
+
+            if let Some(pushes) = target.and_then(|t| found.jumpdests.get_mut(&t)) {
+                pushes.push(inst.offset);
+            }
+        }
+
+        let access = match insts.get(idx + 1) {
+            Some(next) if next.spec == Specifier::SLoad || next.spec == Specifier::SStore => next,
+            _ => continue,
+        };
+
+        let slot = match immediate::<32>(inst) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let refs = found.slots.entry(slot).or_default();
+
+        if access.spec == Specifier::SLoad {
+            refs.loads.push(access.offset);
+        } else {
+            refs.stores.push(access.offset);
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn slot(n: u8) -> [u8; 32] {
+        let mut slot = [0u8; 32];
+        slot[31] = n;
+        slot
+    }
+
+    #[test]
+    fn jumpdest_pushes() {
+        // 0: push1 0x08; 2: push1 0x08; 4: jumpi; 5: push1 0x08; 7: jump
+        // 8: jumpdest; 9: jumpdest
+        let code = hex!("6008 6008 57 6008 56 5b 5b");
+        let found = xrefs(&code);
+
+        let mut expected = BTreeMap::new();
+        expected.insert(8, vec![0, 2, 5]);
+        expected.insert(9, vec![]);
+
+        assert_eq!(found.jumpdests, expected);
+    }
+
+    #[test]
+    fn storage_slots() {
+        // 0: push1 0x01; 2: sload; 3: push1 0x2a; 5: push1 0x01; 7: sstore
+        // 8: push1 0x00; 10: sload; 11: dup1; 12: sload
+        let code = hex!("6001 54 602a 6001 55 6000 54 80 54");
+        let found = xrefs(&code);
+
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            slot(1),
+            SlotRefs {
+                loads: vec![2],
+                stores: vec![7],
+            },
+        );
+        expected.insert(
+            slot(0),
+            SlotRefs {
+                loads: vec![10],
+                stores: vec![],
+            },
+        );
+
+        assert_eq!(found.slots, expected);
+    }
+
+    #[test]
+    fn wide_slots() {
+        let code = hex!("7f 0000000000000000000000000000000000000000000000000000000000000100 54");
+        let found = xrefs(&code);
+
+        let mut key = [0u8; 32];
+        key[30] = 1;
+        assert_eq!(found.slots[&key].loads, vec![33]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Compares disassembly against a reference implementation.
+//!
+//! This test only runs when `ETK_REFERENCE_DISASM` is set to a command that
+//! disassembles a file of hex encoded bytecode, printing one instruction per
+//! line like geth's `evm disasm`:
+//!
+//! ```text
+//! 00000: PUSH1 0x60
+//! 00002: opcode 0xc not defined
+//! ```
+//!
+//! Offsets are in hex. Other tools (like evmone) can be compared by wrapping
+//! them in a script that prints the same format.
+//!
+//! ```bash
+//! ETK_REFERENCE_DISASM="evm disasm" cargo test -p etk-analyze --test differential
+//! ```
+//!
+//! Every `*.hex` file in `tests/differential` is disassembled by both, and any
+//! difference in instruction boundaries, push immediates, or which opcodes are
+//! defined is reported.
+
+use etk_asm::disasm::Disassembler;
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable holding the reference disassembler command.
+const REFERENCE: &str = "ETK_REFERENCE_DISASM";
+
+/// Differences to report per file, before giving up on it.
+const MAX_DIFFERENCES: usize = 10;
+
+/// The parts of an instruction both disassemblers agree on the meaning of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instruction {
+    offset: usize,
+    defined: bool,
+    immediate: Vec<u8>,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:05x}: ", self.offset)?;
+
+        if !self.defined {
+            write!(f, "undefined")
+        } else if self.immediate.is_empty() {
+            write!(f, "defined")
+        } else {
+            write!(f, "defined 0x{}", hex::encode(&self.immediate))
+        }
+    }
+}
+
+/// Disassembly of one file.
+#[derive(Debug)]
+struct Listing {
+    instructions: Vec<Instruction>,
+
+    /// Whether the code ended part way through an instruction.
+    truncated: bool,
+}
+
+fn etk(code: &[u8]) -> Listing {
+    let mut dasm = Disassembler::new();
+    dasm.write_all(code).unwrap();
+
+    let instructions = dasm
+        .ops()
+        .map(|off| {
+            let spec = off.item.specifier();
+            let end = off.offset + spec.size() as usize;
+
+            Instruction {
+                offset: off.offset,
+                defined: spec.is_defined(),
+                immediate: code[off.offset + 1..end].to_vec(),
+            }
+        })
+        .collect();
+
+    Listing {
+        instructions,
+        truncated: dasm.finish().is_err(),
+    }
+}
+
+/// Parse one line of the reference's output, returning `None` for lines that
+/// aren't instructions (like the copy of the input geth prints first.)
+fn parse_line(line: &str) -> Option<Instruction> {
+    let line = line.trim();
+    let colon = line.find(": ")?;
+    let text = &line[colon + 2..];
+
+    let offset = usize::from_str_radix(&line[..colon], 16).ok()?;
+
+    if text.ends_with("not defined") {
+        return Some(Instruction {
+            offset,
+            defined: false,
+            immediate: Vec::new(),
+        });
+    }
+
+    let immediate = match text.find(' ') {
+        Some(space) => {
+            let arg = text[space..].trim();
+            let arg = arg.strip_prefix("0x").unwrap_or(arg);
+            hex::decode(arg).unwrap_or_else(|e| panic!("bad immediate in `{}`: {}", line, e))
+        }
+        None => Vec::new(),
+    };
+
+    Some(Instruction {
+        offset,
+        defined: true,
+        immediate,
+    })
+}
+
+fn reference(command: &str, path: &Path) -> Listing {
+    let mut words = command.split_whitespace();
+    let program = words.next().expect("empty reference command");
+
+    let output = Command::new(program)
+        .args(words)
+        .arg(path)
+        .output()
+        .unwrap_or_else(|e| panic!("couldn't run `{}`: {}", command, e));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let instructions = stdout.lines().filter_map(parse_line).collect();
+
+    // geth exits with an error when the last push is incomplete, after
+    // printing everything before it.
+    Listing {
+        instructions,
+        truncated: !output.status.success(),
+    }
+}
+
+/// Describe how `actual` differs from `expected`, if at all.
+fn compare(expected: &Listing, actual: &Listing) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    let mut expected_iter = expected.instructions.iter().peekable();
+    let mut actual_iter = actual.instructions.iter().peekable();
+
+    loop {
+        let (e, a) = match (expected_iter.peek(), actual_iter.peek()) {
+            (None, None) => break,
+            (Some(e), None) => {
+                differences.push(format!("only reference: {}", e));
+                expected_iter.next();
+                continue;
+            }
+            (None, Some(a)) => {
+                differences.push(format!("only etk:       {}", a));
+                actual_iter.next();
+                continue;
+            }
+            (Some(e), Some(a)) => (*e, *a),
+        };
+
+        // Step through whichever side is behind, so a single disagreement
+        // about an instruction's length doesn't misalign the rest.
+        if e.offset < a.offset {
+            differences.push(format!("only reference: {}", e));
+            expected_iter.next();
+        } else if a.offset < e.offset {
+            differences.push(format!("only etk:       {}", a));
+            actual_iter.next();
+        } else {
+            if e != a {
+                differences.push(format!("reference: {}\n    etk:       {}", e, a));
+            }
+            expected_iter.next();
+            actual_iter.next();
+        }
+    }
+
+    if expected.truncated != actual.truncated {
+        differences.push(format!(
+            "reference truncated: {}, etk truncated: {}",
+            expected.truncated, actual.truncated,
+        ));
+    }
+
+    differences
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("differential");
+
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some(OsStr::new("hex")))
+        .collect();
+
+    paths.sort();
+    paths
+}
+
+#[test]
+fn disassembly_matches_reference() {
+    let command = match env::var(REFERENCE) {
+        Ok(c) => c,
+        Err(_) => {
+            eprintln!("{} isn't set, skipping", REFERENCE);
+            return;
+        }
+    };
+
+    let mut report = String::new();
+
+    for path in corpus() {
+        let text = fs::read_to_string(&path).unwrap();
+        let code = hex::decode(text.trim()).unwrap();
+
+        let differences = compare(&reference(&command, &path), &etk(&code));
+        if differences.is_empty() {
+            continue;
+        }
+
+        report.push_str(&format!("{}:\n", path.display()));
+        for difference in differences.iter().take(MAX_DIFFERENCES) {
+            report.push_str(&format!("    {}\n", difference));
+        }
+        if differences.len() > MAX_DIFFERENCES {
+            let more = differences.len() - MAX_DIFFERENCES;
+            report.push_str(&format!("    ...and {} more\n", more));
+        }
+    }
+
+    assert!(
+        report.is_empty(),
+        "disassembly differs from `{}`:\n{}",
+        command,
+        report
+    );
+}
+
+#[test]
+fn parse_geth_lines() {
+    let expected = Instruction {
+        offset: 0x12,
+        defined: true,
+        immediate: vec![0x00, 0x60],
+    };
+    assert_eq!(parse_line("00012: PUSH2 0x0060"), Some(expected));
+
+    let expected = Instruction {
+        offset: 2,
+        defined: false,
+        immediate: Vec::new(),
+    };
+    assert_eq!(parse_line("00002: opcode 0xc not defined"), Some(expected));
+
+    assert_eq!(parse_line("6060604052"), None);
+}
@@ -0,0 +1,245 @@
+//! Benchmarks for parsing, macro expansion, label resolution, and
+//! disassembly.
+//!
+//! To measure a change, save a baseline before making it, then compare:
+//!
+//! ```text
+//! cargo bench -p etk-asm --features cli,mmap -- --save-baseline before
+//! cargo bench -p etk-asm --features cli,mmap -- --baseline before
+//! ```
+//!
+//! CI does the same for pull requests labelled `performance`, comparing
+//! against the base branch.
+//!
+//! Deployed bytecode (as hex) in `benches/corpus`, which comes with a few
+//! contracts and can hold more, is disassembled too. See
+//! `benches/corpus/README.md`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use etk_asm::asm::Assembler;
+use etk_asm::disasm::Disassembler;
+use etk_asm::ingest::{Cache, Ingest};
+use etk_asm::ops::{AbstractOp, Imm, Op};
+
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Number of functions in each generated contract.
+const SIZES: &[usize] = &[100, 1_000, 10_000];
+
+/// Number of files generated contracts are split into for the expansion
+/// benchmarks.
+const PARTS: usize = 50;
+
+/// A contract with `functions` external functions, shaped like the output of
+/// a simple compiler: a dispatcher comparing the selector against each
+/// function, followed by the body of every function.
+///
+/// Labels start with `prefix`, so several contracts can share a scope.
+fn dispatcher(prefix: &str, functions: usize) -> String {
+    let mut src = String::new();
+
+    writeln!(src, "push1 0\ncalldataload\npush1 0xe0\nshr").unwrap();
+
+    for ii in 0..functions {
+        writeln!(
+            src,
+            "dup1\npush4 selector(\"{0}f{1}(uint256)\")\neq\n%push({0}f{1})\njumpi",
+            prefix, ii,
+        )
+        .unwrap();
+    }
+
+    writeln!(src, "push1 0\ndup1\nrevert").unwrap();
+
+    for ii in 0..functions {
+        writeln!(
+            src,
+            "{}f{}:\njumpdest\npush1 0x04\ncalldataload\npush2 {}\nadd\n\
+            push1 0\nmstore\npush1 0x20\npush1 0\nreturn",
+            prefix, ii, ii,
+        )
+        .unwrap();
+    }
+
+    src
+}
+
+/// Assemble `src`, reusing (and returning) whatever `cache` holds.
+fn assemble(src: &str, cache: Cache) -> (Vec<u8>, Cache) {
+    let mut output = Vec::new();
+    let mut ingest = Ingest::with_cache(&mut output, cache);
+    ingest.ingest("./bench.etk", src).unwrap();
+    let cache = ingest.into_cache();
+    (output, cache)
+}
+
+fn ingest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ingest");
+
+    for &size in SIZES {
+        let src = dispatcher("a", size);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("cold", size), &src, |b, src| {
+            b.iter(|| assemble(src, Cache::new()))
+        });
+
+        // Parsed files are cached, so the difference between `cold` and
+        // `cached` is the time spent parsing.
+        group.bench_with_input(BenchmarkId::new("cached", size), &src, |b, src| {
+            b.iter_custom(|iters| {
+                let (_, mut cache) = assemble(src, Cache::new());
+
+                let start = Instant::now();
+                for _ in 0..iters {
+                    cache = assemble(src, cache).1;
+                }
+                start.elapsed()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Write a contract with `functions` functions, split into `PARTS` files,
+/// to `dir`. The root file pulls each part in with `directive`.
+fn split(dir: &Path, directive: &str, functions: usize) -> PathBuf {
+    let mut root = String::new();
+
+    for part in 0..PARTS {
+        let name = format!("part{}.etk", part);
+        let prefix = format!("p{}_", part);
+        fs::write(dir.join(&name), dispatcher(&prefix, functions / PARTS)).unwrap();
+        writeln!(root, "%{}(\"{}\")", directive, name).unwrap();
+    }
+
+    let path = dir.join("main.etk");
+    fs::write(&path, root).unwrap();
+    path
+}
+
+fn expand(c: &mut Criterion) {
+    let mut group = c.benchmark_group("expand");
+
+    for directive in &["import", "include"] {
+        for &size in SIZES {
+            let dir = tempfile::tempdir().unwrap();
+            let root = split(dir.path(), directive, size);
+
+            group.bench_with_input(BenchmarkId::new(*directive, size), &root, |b, root| {
+                b.iter(|| {
+                    let mut output = Vec::new();
+                    let mut ingest = Ingest::new(&mut output);
+                    ingest.ingest_file(root).unwrap();
+                    output
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// Jumps to `labels` labels, each referred to (with an unsized push) before
+/// it's declared, so the assembler has to hold everything until the end.
+fn forward_jumps(labels: usize) -> Vec<AbstractOp> {
+    let mut ops = vec![AbstractOp::Push(Imm::from("end"))];
+
+    for ii in 0..labels {
+        // Jump somewhere else in the program, like a loop or branch would.
+        let target = format!("l{}", (ii * 7919) % labels);
+
+        ops.push(AbstractOp::Label(format!("l{}", ii).into()));
+        ops.push(AbstractOp::Op(Op::JumpDest));
+        ops.push(AbstractOp::Push(Imm::from(target)));
+        ops.push(AbstractOp::Op(Op::Jump));
+    }
+
+    ops.push(AbstractOp::Label("end".into()));
+    ops
+}
+
+fn labels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("labels");
+
+    for &size in SIZES {
+        let ops = forward_jumps(size);
+        group.throughput(Throughput::Elements(ops.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("forward", size), &ops, |b, ops| {
+            b.iter(|| {
+                let mut asm = Assembler::new();
+                asm.push_all(ops.iter().cloned()).unwrap();
+                let output = asm.take();
+                asm.finish().unwrap();
+                output
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Bytecode to disassemble: generated contracts, plus anything in the corpus
+/// directory.
+fn bytecode() -> Vec<(String, Vec<u8>)> {
+    let mut programs: Vec<_> = SIZES
+        .iter()
+        .map(|size| {
+            let (code, _) = assemble(&dispatcher("a", *size), Cache::new());
+            (format!("generated-{}", size), code)
+        })
+        .collect();
+
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("benches")
+        .join("corpus");
+
+    let mut paths: Vec<_> = fs::read_dir(corpus)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some(OsStr::new("hex")))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let text = fs::read_to_string(&path).unwrap();
+        let text = text.trim();
+        let text = text.strip_prefix("0x").unwrap_or(text);
+
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        programs.push((name, hex::decode(text).unwrap()));
+    }
+
+    programs
+}
+
+fn disasm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("disasm");
+
+    for (name, code) in bytecode() {
+        group.throughput(Throughput::Bytes(code.len() as u64));
+
+        group.bench_with_input(BenchmarkId::from_parameter(name), &code, |b, code| {
+            b.iter(|| {
+                let mut dasm = Disassembler::new();
+                dasm.write_all(code).unwrap();
+                // Deployed code often ends in metadata that doesn't decode,
+                // so don't call `finish`.
+                dasm.ops().count()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, ingest, expand, labels, disasm);
+criterion_main!(benches);
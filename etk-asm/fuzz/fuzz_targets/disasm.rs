@@ -0,0 +1,33 @@
+//! Every byte sequence should disassemble, except for a truncated push at the
+//! very end.
+
+#![no_main]
+
+use etk_asm::disasm::{Disassembler, Error};
+use etk_asm::ops::Specifier;
+
+use libfuzzer_sys::fuzz_target;
+
+use std::io::Write;
+
+fuzz_target!(|code: &[u8]| {
+    let mut dasm = Disassembler::new();
+    dasm.write_all(code).unwrap();
+
+    let mut len = 0;
+    for op in dasm.ops() {
+        assert_eq!(op.offset, len);
+        len += op.item.size() as usize;
+    }
+
+    match dasm.finish() {
+        Ok(()) => assert_eq!(len, code.len()),
+        Err(Error::Truncated { remaining, .. }) => {
+            assert_eq!(remaining.offset, len);
+            assert_eq!(remaining.item, &code[len..]);
+            let size = Specifier::from(remaining.item[0]).size() as usize;
+            assert!(remaining.item.len() < size);
+        }
+        Err(e) => panic!("{}", e),
+    }
+});
@@ -0,0 +1,9 @@
+//! Parsing arbitrary text should only ever fail with a `ParseError`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let _ = etk_asm::fuzzing::parse_asm(text);
+});
@@ -0,0 +1,98 @@
+//! Extracting bytecode from the JSON artifacts written by Solidity toolchains.
+
+use serde_json::Value;
+
+use std::error::Error;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Find the hex encoded bytecode in `artifact`. Understands:
+///
+///  - Foundry: `{"bytecode": {"object": "0x..."}}`;
+///  - Hardhat and Truffle: `{"bytecode": "0x..."}`; and
+///  - solc's standard JSON: `{"evm": {"bytecode": {"object": "..."}}}`.
+fn find<'a>(artifact: &'a Value, key: &str) -> Option<&'a str> {
+    let field = artifact
+        .get(key)
+        .or_else(|| artifact.get("evm")?.get(key))?;
+
+    match field {
+        Value::String(s) => Some(s),
+        Value::Object(o) => o.get("object")?.as_str(),
+        _ => None,
+    }
+}
+
+/// The creation bytecode (or, if `deployed`, the runtime bytecode) held in the
+/// JSON `artifact`.
+pub(crate) fn bytecode(artifact: &[u8], deployed: bool) -> Result<Vec<u8>, BoxError> {
+    let key = if deployed {
+        "deployedBytecode"
+    } else {
+        "bytecode"
+    };
+
+    let value: Value = serde_json::from_slice(artifact)?;
+    let text = find(&value, key).ok_or_else(|| format!("no `{}` field", key))?;
+    let text = text.strip_prefix("0x").unwrap_or(text);
+
+    if let Some(idx) = text.find("__") {
+        return Err(format!("unlinked library reference at byte {}", idx / 2).into());
+    }
+
+    if text.is_empty() {
+        return Err(format!("`{}` is empty (is the contract abstract?)", key).into());
+    }
+
+    Ok(hex::decode(text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn foundry() {
+        let json = br#"{
+            "abi": [],
+            "bytecode": {"object": "0x6001", "linkReferences": {}},
+            "deployedBytecode": {"object": "0x6002", "linkReferences": {}}
+        }"#;
+
+        assert_eq!(bytecode(json, false).unwrap(), [0x60, 0x01]);
+        assert_eq!(bytecode(json, true).unwrap(), [0x60, 0x02]);
+    }
+
+    #[test]
+    fn hardhat() {
+        let json = br#"{"bytecode": "0x6001", "deployedBytecode": "0x6002"}"#;
+
+        assert_eq!(bytecode(json, false).unwrap(), [0x60, 0x01]);
+        assert_eq!(bytecode(json, true).unwrap(), [0x60, 0x02]);
+    }
+
+    #[test]
+    fn standard_json() {
+        let json = br#"{"evm": {"deployedBytecode": {"object": "6002"}}}"#;
+        assert_eq!(bytecode(json, true).unwrap(), [0x60, 0x02]);
+    }
+
+    #[test]
+    fn unusable() {
+        let err = bytecode(br#"{"bytecode": "0x"}"#, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`bytecode` is empty (is the contract abstract?)"
+        );
+
+        let err = bytecode(br#"{"bytecode": "0x"}"#, true).unwrap_err();
+        assert_eq!(err.to_string(), "no `deployedBytecode` field");
+
+        let json = br#"{"bytecode": "0x6001__$1234567890abcdef1234567890abcdef12$__"}"#;
+        let err = bytecode(json, false).unwrap_err();
+        assert_eq!(err.to_string(), "unlinked library reference at byte 2");
+
+        bytecode(b"{", false).unwrap_err();
+        bytecode(br#"{"bytecode": "0x6"}"#, false).unwrap_err();
+    }
+}
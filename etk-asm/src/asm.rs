@@ -45,6 +45,24 @@ mod error {
             backtrace: Backtrace,
         },
 
+        /// An `%org` asked for an offset the output had already passed.
+        #[snafu(display(
+            "can't move to offset {:#x}, the output is already at {:#x}",
+            target,
+            offset
+        ))]
+        #[non_exhaustive]
+        OffsetPassed {
+            /// The offset that was asked for.
+            target: u32,
+
+            /// The offset the output had already reached.
+            offset: u32,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
         /// A label was used without being defined.
         #[snafu(display("label `{}` was never defined", label))]
         #[non_exhaustive]
@@ -68,14 +86,32 @@ mod error {
     }
 }
 
+use crate::codes::{self, Code};
 use crate::ops::{AbstractOp, Imm, Specifier};
+use crate::symbol::{Interner, Symbol, SymbolMap, SymbolSet};
 
 pub use self::error::Error;
 
-use snafu::{OptionExt, ResultExt};
+impl Error {
+    /// The stable code identifying this kind of error.
+    pub fn code(&self) -> Code {
+        match self {
+            Error::DuplicateLabel { .. } => codes::DUPLICATE_LABEL,
+            Error::LabelTooLarge { .. } => codes::LABEL_TOO_LARGE,
+            Error::UnsizedPushTooLarge { .. } => codes::PUSH_TOO_LARGE,
+            Error::UndeclaredLabel { .. } => codes::UNDECLARED_LABEL,
+            Error::OffsetPassed { .. } => codes::OFFSET_PASSED,
+            Error::ParseInclude { source } => source.code(),
+        }
+    }
+}
+
+use snafu::{ensure, OptionExt, ResultExt};
 
-use std::collections::{hash_map, HashMap, HashSet, VecDeque};
+use std::collections::{hash_map, BTreeMap, VecDeque};
 use std::convert::TryInto;
+use std::io::{self, Write};
+use std::sync::Arc;
 
 /// An item to be assembled, which can be either an [`AbstractOp`] or a raw byte
 /// sequence.
@@ -87,6 +123,16 @@ pub enum RawOp {
     /// Raw bytes, for example from `%include_hex`, to be included verbatim in
     /// the output.
     Raw(Vec<u8>),
+
+    /// Copies of `fill`, enough that the next op starts at `offset`, as
+    /// written with `%org`.
+    Org {
+        /// The offset of the next op.
+        offset: u32,
+
+        /// The byte to pad with.
+        fill: u8,
+    },
 }
 
 impl RawOp {
@@ -94,17 +140,26 @@ impl RawOp {
         match self {
             Self::Op(op) => op.size(),
             Self::Raw(raw) => Some(raw.len().try_into().expect("raw too big")),
+            Self::Org { .. } => None,
         }
     }
 
-    fn immediate_label(&self) -> Option<&str> {
+    /// The label declared or used by this op, if any.
+    fn label(&self) -> Option<&Arc<str>> {
         match self {
-            Self::Op(op) => op.immediate_label(),
-            Self::Raw(_) => None,
+            Self::Op(AbstractOp::Label(label)) => Some(label),
+            Self::Op(op) => op.shared_label(),
+            Self::Raw(_) | Self::Org { .. } => None,
         }
     }
 }
 
+/// The padding that `%org(target)` adds at `offset`.
+fn padding(target: u32, fill: u8, offset: u32) -> Result<Vec<u8>, Error> {
+    ensure!(offset <= target, error::OffsetPassed { target, offset });
+    Ok(vec![fill; (target - offset) as usize])
+}
+
 impl From<AbstractOp> for RawOp {
     fn from(op: AbstractOp) -> Self {
         Self::Op(op)
@@ -117,6 +172,61 @@ impl From<Vec<u8>> for RawOp {
     }
 }
 
+/// The output of [`assemble`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Assembled {
+    /// The assembled bytes.
+    pub code: Vec<u8>,
+
+    /// The address of every declared label, by name.
+    pub labels: BTreeMap<String, u32>,
+}
+
+/// Assemble a complete sequence of [`RawOp`] (or anything that converts into
+/// one, like [`AbstractOp`]), as produced by a code generator, without going
+/// through the text front-end.
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_asm::asm::assemble;
+/// use etk_asm::ops::{AbstractOp, Imm, Op};
+/// # use etk_asm::asm::Error;
+/// #
+/// # use hex_literal::hex;
+///
+/// let assembled = assemble(vec![
+///     AbstractOp::Push(Imm::from("end")),
+///     AbstractOp::Op(Op::Jump),
+///     AbstractOp::Label("end".into()),
+///     AbstractOp::Op(Op::JumpDest),
+/// ])?;
+///
+/// assert_eq!(assembled.code, hex!("6003565b"));
+/// assert_eq!(assembled.labels["end"], 3);
+/// # Result::<(), Error>::Ok(())
+/// ```
+pub fn assemble<I, O>(ops: I) -> Result<Assembled, Error>
+where
+    I: IntoIterator<Item = O>,
+    O: Into<RawOp>,
+{
+    let mut asm = Assembler::new();
+    asm.push_all(ops)?;
+
+    let code = asm.take();
+    let labels = asm
+        .labels()
+        .into_iter()
+        .map(|(name, address)| (name.to_owned(), address))
+        .collect();
+
+    asm.finish()?;
+
+    Ok(Assembled { code, labels })
+}
+
 /// Assembles a series of [`RawOp`] into raw bytes, tracking and resolving labels
 /// and handling dynamic pushes.
 ///
@@ -142,8 +252,9 @@ pub struct Assembler {
     /// Assembled ops, ready to be taken.
     ready: Vec<u8>,
 
-    /// Ops that cannot be encoded yet.
-    pending: VecDeque<RawOp>,
+    /// Ops that cannot be encoded yet, along with the symbol for the label
+    /// each declares or uses.
+    pending: VecDeque<(RawOp, Option<Symbol>)>,
 
     /// Sum of the size of all the ops in `pending`, or `None` if `pending` contains
     /// an unsized op.
@@ -153,11 +264,14 @@ pub struct Assembler {
     concrete_len: u32,
 
     /// Labels, in `pending`, associated with an `AbstractOp::Label`.
-    declared_labels: HashMap<String, Option<u32>>,
+    declared_labels: SymbolMap<Option<u32>>,
 
     /// Labels, in `pending`, that have been referred to (ex. with push) but
     /// have not been declared with an `AbstractOp::Label`.
-    undeclared_labels: HashSet<String>,
+    undeclared_labels: SymbolSet,
+
+    /// Symbols for labels pushed with [`Assembler::push`].
+    symbols: Interner,
 }
 
 impl Default for Assembler {
@@ -169,6 +283,7 @@ impl Default for Assembler {
             concrete_len: 0,
             declared_labels: Default::default(),
             undeclared_labels: Default::default(),
+            symbols: Default::default(),
         }
     }
 }
@@ -182,7 +297,7 @@ impl Assembler {
     /// Indicate that the input sequence is complete. Returns any errors that
     /// may remain.
     pub fn finish(self) -> Result<(), Error> {
-        if let Some(undef) = self.pending.front() {
+        if let Some((undef, _)) = self.pending.front() {
             return match undef {
                 RawOp::Op(op) => {
                     let label = op.immediate_label().unwrap();
@@ -199,11 +314,40 @@ impl Assembler {
         Ok(())
     }
 
+    /// The address of `label`, if it has been declared and its address is
+    /// known.
+    pub(crate) fn address(&self, symbols: &Interner, label: &str) -> Option<u32> {
+        let symbol = symbols.get(label)?;
+        self.declared_labels.get(&symbol).copied().flatten()
+    }
+
+    /// The address of every label declared so far whose address is known, by
+    /// name.
+    pub fn labels(&self) -> BTreeMap<&str, u32> {
+        self.declared_labels
+            .iter()
+            .filter_map(|(symbol, address)| Some((self.symbols.name(*symbol), (*address)?)))
+            .collect()
+    }
+
     /// Collect any assembled instructions that are ready to be output.
     pub fn take(&mut self) -> Vec<u8> {
         std::mem::take(&mut self.ready)
     }
 
+    /// Write any assembled instructions that are ready to `output`, instead of
+    /// collecting them with [`Assembler::take`].
+    ///
+    /// Calling this after each [`Assembler::push`] keeps only the instructions
+    /// waiting on a label in memory, rather than the whole program.
+    ///
+    /// Returns the number of bytes written.
+    pub fn write_ready<W: Write>(&mut self, mut output: W) -> io::Result<usize> {
+        let ready = self.take();
+        output.write_all(&ready)?;
+        Ok(ready.len())
+    }
+
     /// Feed instructions into the `Assembler`.
     ///
     /// Returns the number of bytes that can be collected with [`Assembler::take`].
@@ -228,58 +372,81 @@ impl Assembler {
     where
         O: Into<RawOp>,
     {
-        let rop = rop.into();
+        let mut symbols = std::mem::take(&mut self.symbols);
+        let result = self.push_with(&mut symbols, rop.into());
+        self.symbols = symbols;
+        result
+    }
+
+    /// Like [`Assembler::push`], but interns labels with `symbols`, so one
+    /// interner can be shared by several assemblers.
+    ///
+    /// Every push to an assembler must use the same interner.
+    pub(crate) fn push_with(&mut self, symbols: &mut Interner, rop: RawOp) -> Result<usize, Error> {
+        let symbol = rop.label().map(|label| symbols.intern(label));
 
         if let RawOp::Op(AbstractOp::Label(ref label)) = rop {
-            match self.declared_labels.entry(label.to_owned()) {
+            let symbol = symbol.unwrap();
+            match self.declared_labels.entry(symbol) {
                 hash_map::Entry::Occupied(_) => {
-                    return error::DuplicateLabel { label }.fail();
+                    return error::DuplicateLabel {
+                        label: label.as_ref(),
+                    }
+                    .fail();
                 }
                 hash_map::Entry::Vacant(v) => {
                     v.insert(None);
-                    self.undeclared_labels.remove(label);
+                    self.undeclared_labels.remove(&symbol);
                 }
             }
-        }
-
-        if let Some(label) = rop.immediate_label() {
-            if !self.declared_labels.contains_key(label) {
-                self.undeclared_labels.insert(label.to_owned());
+        } else if let Some(symbol) = symbol {
+            if !self.declared_labels.contains_key(&symbol) {
+                self.undeclared_labels.insert(symbol);
             }
         }
 
-        self.push_unchecked(rop)?;
+        self.push_unchecked(rop, symbol)?;
         Ok(self.ready.len())
     }
 
-    fn push_unchecked(&mut self, rop: RawOp) -> Result<(), Error> {
+    fn push_unchecked(&mut self, rop: RawOp, symbol: Option<Symbol>) -> Result<(), Error> {
+        // Padding becomes raw bytes as soon as its offset is known.
+        let rop = match (rop, self.pending_len) {
+            (RawOp::Org { offset, fill }, Some(pending_len)) => {
+                RawOp::Raw(padding(offset, fill, self.concrete_len + pending_len)?)
+            }
+            (rop, _) => rop,
+        };
+
         if self.pending.is_empty() {
-            self.push_ready(rop)
+            self.push_ready(rop, symbol)
         } else {
-            self.push_pending(rop)
+            self.push_pending(rop, symbol)
         }
     }
 
-    fn push_ready(&mut self, rop: RawOp) -> Result<(), Error> {
+    fn push_ready(&mut self, rop: RawOp, symbol: Option<Symbol>) -> Result<(), Error> {
         match rop {
-            RawOp::Op(AbstractOp::Label(label)) => {
+            RawOp::Op(AbstractOp::Label(_)) => {
                 let old = self
                     .declared_labels
-                    .insert(label, Some(self.concrete_len))
+                    .insert(symbol.unwrap(), Some(self.concrete_len))
                     .expect("label should exist");
                 assert_eq!(old, None, "label should have been undefined");
                 Ok(())
             }
             RawOp::Op(mut op) => {
-                if let Some(label) = op.immediate_label() {
-                    match self.declared_labels.get(label) {
+                if let Some(symbol) = symbol {
+                    match self.declared_labels.get(&symbol) {
                         Some(Some(addr)) => {
-                            op = op.realize(*addr).context(error::LabelTooLarge { label })?;
+                            op = op.realize(*addr).with_context(|| error::LabelTooLarge {
+                                label: op.immediate_label().unwrap(),
+                            })?;
                         }
                         _ => {
                             assert_eq!(self.pending_len, Some(0));
                             self.pending_len = op.size();
-                            self.pending.push_back(RawOp::Op(op));
+                            self.pending.push_back((RawOp::Op(op), Some(symbol)));
                             return Ok(());
                         }
                     }
@@ -292,17 +459,25 @@ impl Assembler {
 
                 Ok(())
             }
+            RawOp::Org { .. } => unreachable!("padding is raw once its offset is known"),
             RawOp::Raw(raw) => {
                 let len: u32 = raw.len().try_into().expect("raw too long");
                 self.concrete_len += len;
-                self.ready.extend(raw);
+
+                // Avoid copying large blobs (ex. from `%include_hex`.)
+                if self.ready.is_empty() {
+                    self.ready = raw;
+                } else {
+                    self.ready.extend(raw);
+                }
+
                 Ok(())
             }
         }
     }
 
     fn pop_pending(&mut self) -> Result<(), Error> {
-        let popped = self.pending.pop_front().unwrap();
+        let (popped, _) = self.pending.pop_front().unwrap();
 
         let size;
 
@@ -311,6 +486,11 @@ impl Assembler {
                 size = raw.len() as u32;
                 self.ready.extend(raw);
             }
+            RawOp::Org { offset, fill } => {
+                let raw = padding(offset, fill, self.concrete_len)?;
+                size = raw.len() as u32;
+                self.ready.extend(raw);
+            }
             RawOp::Op(aop) => {
                 let cop = aop.concretize().context(error::UnsizedPushTooLarge {})?;
                 size = cop.size();
@@ -329,7 +509,7 @@ impl Assembler {
         Ok(())
     }
 
-    fn push_pending(&mut self, rop: RawOp) -> Result<(), Error> {
+    fn push_pending(&mut self, rop: RawOp, symbol: Option<Symbol>) -> Result<(), Error> {
         // Update total size of pending ops.
         match (&mut self.pending_len, rop.size()) {
             (Some(p), Some(me)) => {
@@ -343,26 +523,26 @@ impl Assembler {
 
         // Handle labels.
         match (self.pending_len, rop) {
-            (Some(pending_len), RawOp::Op(AbstractOp::Label(lbl))) => {
+            (Some(pending_len), RawOp::Op(AbstractOp::Label(_))) => {
                 // The label has a defined address.
                 let address = self.concrete_len + pending_len;
-                let item = self.declared_labels.get_mut(&*lbl).unwrap();
+                let item = self.declared_labels.get_mut(&symbol.unwrap()).unwrap();
                 *item = Some(address);
             }
             (None, rop @ RawOp::Op(AbstractOp::Label(_))) => {
-                self.pending.push_back(rop);
+                self.pending.push_back((rop, symbol));
                 if self.undeclared_labels.is_empty() {
                     self.choose_sizes()?;
                 }
             }
             (_, rop) => {
                 // Not a label.
-                self.pending.push_back(rop);
+                self.pending.push_back((rop, symbol));
             }
         }
 
         // Repeatedly check if the front of the pending list is ready.
-        while let Some(next) = self.pending.front() {
+        while let Some((next, symbol)) = self.pending.front() {
             let op = match next {
                 RawOp::Op(AbstractOp::Push(Imm::Label(_))) => {
                     if self.undeclared_labels.is_empty() {
@@ -375,22 +555,20 @@ impl Assembler {
                 RawOp::Op(AbstractOp::Push(Imm::Constant(_))) => unreachable!(),
                 RawOp::Op(AbstractOp::Label(_)) => unreachable!(),
                 RawOp::Op(op) => op,
-                RawOp::Raw(_) => {
+                RawOp::Raw(_) | RawOp::Org { .. } => {
                     self.pop_pending()?;
                     continue;
                 }
             };
 
             let mut address = None;
-            let mut label = None;
 
-            if let Some(lbl) = op.immediate_label() {
+            if let Some(symbol) = symbol {
                 // If the op has a label, try to resolve it.
-                match self.declared_labels.get(lbl) {
+                match self.declared_labels.get(symbol) {
                     Some(Some(addr)) => {
                         // The label resolved! Move the op to ready.
                         address = Some(*addr);
-                        label = Some(lbl);
                     }
                     _ => {
                         // Otherwise, the address isn't known yet, so break!
@@ -402,10 +580,10 @@ impl Assembler {
             if let Some(s) = address {
                 // Don't modify `self.pending` if realize returns an error.
                 let realized = op.realize(s).with_context(|| error::LabelTooLarge {
-                    label: label.unwrap(),
+                    label: op.immediate_label().unwrap(),
                 })?;
                 let front = self.pending.front_mut().unwrap();
-                *front = RawOp::Op(realized);
+                front.0 = RawOp::Op(realized);
             }
 
             self.pop_pending()?;
@@ -414,74 +592,149 @@ impl Assembler {
         Ok(())
     }
 
+    /// Size of `op`, starting at `offset` in the output, once the unsized
+    /// pushes to each label in `sizes` use the given specifier.
+    fn laid_out_size(
+        &self,
+        op: &RawOp,
+        offset: u32,
+        symbol: Option<Symbol>,
+        sizes: &SymbolMap<Specifier>,
+    ) -> Result<u32, Error> {
+        let size = match op {
+            RawOp::Raw(raw) => raw.len() as u32,
+            RawOp::Org { offset: target, .. } => {
+                ensure!(
+                    offset <= *target,
+                    error::OffsetPassed {
+                        target: *target,
+                        offset
+                    }
+                );
+                target - offset
+            }
+            RawOp::Op(AbstractOp::Push(Imm::Label(_))) => {
+                let symbol = symbol.unwrap();
+                match sizes.get(&symbol) {
+                    Some(spec) => spec.size(),
+                    None => {
+                        // The label was declared before the pending ops, so its
+                        // address is already known.
+                        let address = self.declared_labels[&symbol].unwrap();
+                        Specifier::push_for(address)
+                            .context(error::UnsizedPushTooLarge)?
+                            .size()
+                    }
+                }
+            }
+            RawOp::Op(aop @ AbstractOp::Push(Imm::Constant(_))) => aop
+                .clone()
+                .concretize()
+                .context(error::UnsizedPushTooLarge)?
+                .size(),
+            RawOp::Op(aop) => aop.size().unwrap(),
+        };
+
+        Ok(size)
+    }
+
+    /// Pick a size for every unsized push in `pending`, then assemble them.
+    ///
+    /// Every label declared in `pending` starts out with the smallest push that
+    /// could hold its address. Each pass lays out the pending ops once, and
+    /// grows the push for any label whose address no longer fits. Addresses
+    /// only ever grow, so this settles after a handful of passes, and the
+    /// pending ops are only encoded once, at the end.
     fn choose_sizes(&mut self) -> Result<(), Error> {
         let minimum = Specifier::push_for(self.concrete_len).unwrap();
 
-        let mut undefined_labels: HashMap<String, Specifier> = self
-            .declared_labels
+        let mut sizes: SymbolMap<Specifier> = self
+            .pending
             .iter()
-            .filter(|(_, v)| v.is_none())
-            .map(|(k, _)| (k.clone(), minimum))
+            .filter_map(|(op, symbol)| match op {
+                RawOp::Op(AbstractOp::Label(_)) => Some((symbol.unwrap(), minimum)),
+                _ => None,
+            })
             .collect();
 
-        let mut subasm;
+        let mut addresses: SymbolMap<u32> =
+            SymbolMap::with_capacity_and_hasher(sizes.len(), Default::default());
 
         loop {
-            // Create a sub-assembler to try assembling with the sizes in
-            // `undefined_labels`.
-            subasm = Self::default();
-            subasm.concrete_len = self.concrete_len;
-            subasm.declared_labels = self.declared_labels.clone();
-
-            let result: Result<Vec<_>, Error> = self
-                .pending
-                .iter()
-                .map(|op| {
-                    let new = match op {
-                        RawOp::Op(AbstractOp::Push(Imm::Label(lbl))) => {
-                            // Replace unsized push with our current guess.
-                            let spec = undefined_labels[lbl];
-                            let aop = AbstractOp::with_label(spec, lbl);
-                            RawOp::Op(aop)
-                        }
-                        RawOp::Op(aop @ AbstractOp::Push(Imm::Constant(_))) => {
-                            let cop = aop
-                                .clone()
-                                .concretize()
-                                .context(error::UnsizedPushTooLarge)?;
-                            RawOp::Op(cop.into())
-                        }
+            addresses.clear();
 
-                        op => op.clone(),
-                    };
+            let mut offset = self.concrete_len;
+            for (op, symbol) in &self.pending {
+                if let RawOp::Op(AbstractOp::Label(_)) = op {
+                    addresses.insert(symbol.unwrap(), offset);
+                }
 
-                    subasm.push_pending(new)
-                })
-                .collect();
+                offset += self.laid_out_size(op, offset, *symbol, &sizes)?;
+            }
 
-            match result {
-                Ok(_) => {
-                    assert!(subasm.pending.is_empty());
-                    break;
-                }
-                Err(Error::LabelTooLarge { label, .. }) => {
-                    // If a label is too large for an op, increase the width of
-                    // that label.
-                    let item = undefined_labels.get_mut(&label).unwrap();
+            let mut grew = false;
+            for (symbol, spec) in sizes.iter_mut() {
+                let needed =
+                    Specifier::push_for(addresses[symbol]).context(error::UnsizedPushTooLarge)?;
 
-                    let new_size = item.upsize().context(error::UnsizedPushTooLarge)?;
-                    *item = new_size;
+                if needed.size() > spec.size() {
+                    *spec = needed;
+                    grew = true;
                 }
-                Err(e) => return Err(e),
             }
+
+            if !grew {
+                break;
+            }
+        }
+
+        // Patch every label reference now that the offsets are final.
+        let mut raw = Vec::new();
+        for (op, symbol) in &self.pending {
+            let aop = match op {
+                RawOp::Raw(bytes) => {
+                    raw.extend_from_slice(bytes);
+                    continue;
+                }
+                RawOp::Org { offset, fill } => {
+                    let at = self.concrete_len + raw.len() as u32;
+                    raw.extend(padding(*offset, *fill, at)?);
+                    continue;
+                }
+                RawOp::Op(AbstractOp::Label(_)) => continue,
+                RawOp::Op(AbstractOp::Push(Imm::Label(lbl)))
+                    if sizes.contains_key(&symbol.unwrap()) =>
+                {
+                    AbstractOp::with_label(sizes[&symbol.unwrap()], lbl.clone())
+                }
+                RawOp::Op(aop) => aop.clone(),
+            };
+
+            let aop = match symbol {
+                Some(symbol) => {
+                    let address = match addresses.get(symbol) {
+                        Some(a) => *a,
+                        None => self.declared_labels[symbol].unwrap(),
+                    };
+                    aop.realize(address).with_context(|| error::LabelTooLarge {
+                        label: aop.immediate_label().unwrap(),
+                    })?
+                }
+                None => aop,
+            };
+
+            aop.concretize()
+                .context(error::UnsizedPushTooLarge)?
+                .assemble(&mut raw);
+        }
+
+        for (symbol, address) in addresses {
+            self.declared_labels.insert(symbol, Some(address));
         }
 
-        // Insert the results of the sub-assembler into self.
-        let raw = subasm.take();
         self.pending_len = Some(raw.len().try_into().unwrap());
         self.pending.clear();
-        self.pending.push_back(RawOp::Raw(raw));
-        self.declared_labels = subasm.declared_labels;
+        self.pending.push_back((RawOp::Raw(raw), None));
 
         Ok(())
     }
@@ -662,7 +915,8 @@ mod tests {
         let sz = asm.push_all(ops)?;
         assert_eq!(1, sz);
         assert_eq!(asm.declared_labels.len(), 1);
-        assert_eq!(asm.declared_labels.get("lbl"), Some(&Some(0)));
+        let lbl = asm.symbols.intern(&"lbl".into());
+        assert_eq!(asm.declared_labels.get(&lbl), Some(&Some(0)));
         assert_eq!(asm.take(), hex!("5b"));
         Ok(())
     }
@@ -753,4 +1007,187 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn assemble_variable_push_declared_while_pending() -> Result<(), Error> {
+        let mut asm = Assembler::new();
+        let sz = asm.push_all(vec![
+            AbstractOp::Label("a".into()),
+            AbstractOp::Op(Op::JumpDest),
+            AbstractOp::Push("b".into()),
+            AbstractOp::Push("a".into()),
+            AbstractOp::Label("b".into()),
+            AbstractOp::Op(Op::JumpDest),
+        ])?;
+        assert_eq!(sz, 6);
+        assert_eq!(asm.take(), hex!("5b600560005b"));
+        asm.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn assemble_variable_pushes_grow_together() -> Result<(), Error> {
+        // Every push has to grow to push2 once the jumpdests pass 255.
+        let count = 100;
+
+        let mut ops = Vec::new();
+        for ii in 0..count {
+            ops.push(AbstractOp::Push(format!("l{}", ii).as_str().into()));
+        }
+        ops.extend(vec![AbstractOp::Op(Op::GetPc); 200]);
+        for ii in 0..count {
+            ops.push(AbstractOp::Label(format!("l{}", ii).into()));
+            ops.push(AbstractOp::Op(Op::JumpDest));
+        }
+
+        let mut asm = Assembler::new();
+        asm.push_all(ops)?;
+        let assembled = asm.take();
+        asm.finish()?;
+
+        let start = 3 * count + 200;
+        assert_eq!(assembled.len(), start + count);
+
+        for ii in 0..count {
+            let address = ((start + ii) as u16).to_be_bytes();
+            assert_eq!(
+                assembled[3 * ii..3 * ii + 3],
+                [0x61, address[0], address[1]]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn assemble_org() -> Result<(), Error> {
+        let mut asm = Assembler::new();
+        asm.push(AbstractOp::Op(Op::GetPc))?;
+        asm.push(RawOp::Org {
+            offset: 4,
+            fill: 0xfe,
+        })?;
+        asm.push(AbstractOp::Op(Op::JumpDest))?;
+        assert_eq!(asm.take(), hex!("58fefefe5b"));
+
+        asm.push(RawOp::Org { offset: 2, fill: 0 })
+            .expect_err("offset should already be passed");
+        Ok(())
+    }
+
+    #[test]
+    fn assemble_org_while_pending() -> Result<(), Error> {
+        let ops = vec![
+            RawOp::Op(AbstractOp::Push("lbl".into())),
+            RawOp::Org { offset: 4, fill: 0 },
+            RawOp::Op(AbstractOp::Label("lbl".into())),
+            RawOp::Op(AbstractOp::Op(Op::JumpDest)),
+        ];
+
+        let mut asm = Assembler::new();
+        asm.push_all(ops)?;
+        assert_eq!(asm.take(), hex!("6004 0000 5b"));
+        asm.finish()?;
+
+        let ops = vec![
+            RawOp::Op(AbstractOp::Push("lbl".into())),
+            RawOp::Org { offset: 1, fill: 0 },
+            RawOp::Op(AbstractOp::Label("lbl".into())),
+        ];
+
+        let mut asm = Assembler::new();
+        let err = asm.push_all(ops).unwrap_err();
+        assert_matches!(
+            err,
+            Error::OffsetPassed {
+                target: 1,
+                offset: 2,
+                ..
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn assemble_sized_push_too_small_while_pending() {
+        let mut ops = vec![
+            AbstractOp::Push("b".into()),
+            AbstractOp::Op(Op::Push1(Imm::from("a"))),
+        ];
+        ops.extend(vec![AbstractOp::Op(Op::GetPc); 300]);
+        ops.push(AbstractOp::Label("a".into()));
+        ops.push(AbstractOp::Label("b".into()));
+
+        let mut asm = Assembler::new();
+        let err = asm.push_all(ops).unwrap_err();
+        assert_matches!(err, Error::LabelTooLarge { label, .. } if label == "a");
+    }
+
+    #[test]
+    fn assemble_stream() -> Result<(), Error> {
+        let ops = vec![
+            RawOp::Op(AbstractOp::Push("b".into())),
+            RawOp::Op(AbstractOp::Label("a".into())),
+            RawOp::Raw(vec![0xaa, 0xbb]),
+            RawOp::Org { offset: 6, fill: 0 },
+            RawOp::Op(AbstractOp::Label("b".into())),
+            RawOp::Op(AbstractOp::Op(Op::JumpDest)),
+        ];
+
+        let assembled = assemble(ops)?;
+        assert_eq!(assembled.code, hex!("6006aabb00005b"));
+
+        let labels: Vec<_> = assembled
+            .labels
+            .iter()
+            .map(|(l, a)| (l.as_str(), *a))
+            .collect();
+        assert_eq!(labels, [("a", 2), ("b", 6)]);
+
+        let err = assemble(vec![AbstractOp::Push("c".into())]).unwrap_err();
+        assert_matches!(err, Error::UndeclaredLabel { label, .. } if label == "c");
+
+        Ok(())
+    }
+
+    #[test]
+    fn assemble_labels_while_pending() -> Result<(), Error> {
+        let mut asm = Assembler::new();
+        asm.push_all(vec![
+            AbstractOp::Label("a".into()),
+            AbstractOp::Push("c".into()),
+            AbstractOp::Label("b".into()),
+        ])?;
+
+        // `b` comes after a push whose size isn't known yet.
+        assert_eq!(asm.labels().into_iter().collect::<Vec<_>>(), [("a", 0)]);
+
+        asm.push(AbstractOp::Label("c".into()))?;
+        assert_eq!(asm.labels()["b"], 2);
+        assert_eq!(asm.labels()["c"], 2);
+
+        asm.take();
+        asm.finish()
+    }
+
+    #[test]
+    fn assemble_write_ready() -> Result<(), Error> {
+        let mut asm = Assembler::new();
+        let mut output = Vec::new();
+
+        asm.push(AbstractOp::Op(Op::GetPc))?;
+        assert_eq!(asm.write_ready(&mut output).unwrap(), 1);
+
+        asm.push(AbstractOp::Op(Op::Push1(Imm::from("a"))))?;
+        assert_eq!(asm.write_ready(&mut output).unwrap(), 0);
+
+        asm.push(AbstractOp::Label("a".into()))?;
+        asm.push(AbstractOp::Op(Op::JumpDest))?;
+        assert_eq!(asm.write_ready(&mut output).unwrap(), 3);
+
+        asm.finish()?;
+        assert_eq!(output, hex!("5860035b"));
+
+        Ok(())
+    }
 }
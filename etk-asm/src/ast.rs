@@ -1,13 +1,100 @@
-use crate::ops::{AbstractOp, Op};
+use crate::ops::{AbstractOp, Expression, Op, Specifier};
+use crate::Span;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Node {
     Op(AbstractOp),
     Raw(Vec<u8>),
-    Import(PathBuf),
+    Import {
+        path: PathBuf,
+        alias: Option<String>,
+    },
     Include(PathBuf),
     IncludeHex(PathBuf),
+    IncludeArtifact {
+        path: PathBuf,
+        deployed: bool,
+    },
+
+    /// Runs the script at the path, and splices in what it returns.
+    Script(PathBuf),
+    BuildInfo,
+    PragmaOnce,
+
+    /// Opts the rest of the program into checking that it keeps to the
+    /// memory layout Solidity uses.
+    PragmaSolidityMemory,
+    Export(Vec<String>),
+    Org {
+        offset: u32,
+        fill: u8,
+    },
+
+    /// A push of the value of `expression`, which is only known once every
+    /// label in the scope has an address.
+    ComputedPush {
+        spec: Specifier,
+        expression: Expression,
+    },
+
+    /// A push of the value of `expression`, as wide as the largest value it
+    /// could have. That depends on the defines, so it's sized when it's
+    /// assembled.
+    PushExpression(Expression),
+
+    /// Fails assembly with `message` unless `expression` is non-zero, once
+    /// every label in the scope has an address.
+    Assert {
+        expression: Expression,
+        message: String,
+    },
+
+    /// Where in the source the next node came from.
+    Location(Span),
+
+    /// Where in the source the next node came from, when it was written in
+    /// the body of an alias. `span` is the alias's declaration, and `from`
+    /// lists each alias the node was expanded from, innermost first, with
+    /// where that alias was used.
+    Expanded {
+        span: Span,
+        from: Vec<(String, Span)>,
+    },
+}
+
+impl Node {
+    /// The expression in this node, if it has one.
+    pub(crate) fn expression_mut(&mut self) -> Option<&mut Expression> {
+        match self {
+            Node::ComputedPush { expression, .. }
+            | Node::PushExpression(expression)
+            | Node::Assert { expression, .. } => Some(expression),
+            _ => None,
+        }
+    }
+
+    /// Whether this node computes a value from the address of a label.
+    pub(crate) fn has_label_math(&self) -> bool {
+        match self {
+            Node::ComputedPush { expression, .. }
+            | Node::PushExpression(expression)
+            | Node::Assert { expression, .. } => !expression.labels().is_empty(),
+            _ => false,
+        }
+    }
+
+    /// This node, as it appears after expanding the alias `name` used at
+    /// `used`.
+    pub(crate) fn expanded(self, name: &str, used: Span) -> Self {
+        match self {
+            Node::Expanded { span, mut from } => {
+                from.push((name.to_owned(), used));
+                Node::Expanded { span, from }
+            }
+            other => other,
+        }
+    }
 }
 
 impl From<Op> for Node {
@@ -1,32 +1,354 @@
+#[path = "eas/alloc.rs"]
+mod alloc;
+#[path = "eas/attest.rs"]
+mod attest;
+#[path = "eas/build.rs"]
+mod build;
+#[path = "eas/ci_check.rs"]
+mod ci_check;
+#[path = "eas/deploy.rs"]
+mod deploy;
+#[path = "eas/diagnostic.rs"]
+mod diagnostic;
+#[path = "eas/import_solc.rs"]
+mod import_solc;
+#[path = "eas/init.rs"]
+mod init;
+#[path = "eas/lints.rs"]
+mod lints;
+#[path = "eas/manifest.rs"]
+mod manifest;
+#[path = "eas/map.rs"]
+mod map;
+#[path = "eas/tx.rs"]
+mod tx;
+#[path = "eas/watch.rs"]
+mod watch;
+
 use etk_cli::errors::WithSources;
-use etk_cli::io::HexWrite;
+use etk_cli::io::{create_output, is_stdio, Format, HexWrite};
+use etk_cli::log::LogOptions;
+
+use crate::diagnostic::{Diagnostic, Failure, Kind, MessageFormat};
+use crate::lints::{Flags, Override};
+use crate::map::MapFormat;
 
-use etk_asm::ingest::{Error, Ingest};
+use etk_asm::codes::{self, Code};
+use etk_asm::ingest::{AssemblerContext, Dialect, Symbol};
+use etk_asm::lint::Levels;
+use etk_asm::ops::{Experimental, Profile};
+
+use snafu::ErrorCompat;
 
-use std::fs::File;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use structopt::clap::{self, AppSettings, ErrorKind};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "eas")]
+#[structopt(name = "eas", setting = AppSettings::SubcommandsNegateReqs)]
 struct Opt {
-    #[structopt(parse(from_os_str))]
-    input: PathBuf,
-    #[structopt(parse(from_os_str))]
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    #[structopt(
+        long = "watch",
+        short = "w",
+        help = "reassemble whenever the input, or any file it includes or imports, changes"
+    )]
+    watch: bool,
+
+    #[structopt(
+        long = "check",
+        conflicts_with_all(&["watch", "out"]),
+        help = "assemble without writing any output, only reporting errors"
+    )]
+    check: bool,
+
+    #[structopt(
+        long = "message-format",
+        default_value = "human",
+        possible_values(&["human", "json"]),
+        help = "how to print errors"
+    )]
+    message_format: MessageFormat,
+
+    #[structopt(
+        long = "sarif",
+        parse(from_os_str),
+        conflicts_with = "watch",
+        help = "also write errors and warnings to this file, as a SARIF log"
+    )]
+    sarif: Option<PathBuf>,
+
+    #[structopt(
+        long = "map",
+        parse(from_os_str),
+        conflicts_with_all(&["watch", "check"]),
+        help = "also write the offset of every label to this file"
+    )]
+    map: Option<PathBuf>,
+
+    #[structopt(
+        long = "attest",
+        parse(from_os_str),
+        conflicts_with_all(&["watch", "check"]),
+        help = "also write the hashes of the output and the sources it was assembled from to this file"
+    )]
+    attest: Option<PathBuf>,
+
+    #[structopt(
+        long = "map-format",
+        default_value = "nm",
+        possible_values(&["nm", "json", "tree"]),
+        help = "how to write the offsets of labels"
+    )]
+    map_format: MapFormat,
+
+    #[structopt(
+        long = "output-format",
+        default_value = "hex",
+        possible_values(&["hex", "binary"]),
+        help = "encoding of the assembled code"
+    )]
+    output_format: Format,
+
+    #[structopt(
+        long = "bin",
+        help = "write all of the assembled code, like the constructor of a contract (the default)"
+    )]
+    bin: bool,
+
+    #[structopt(
+        long = "bin-runtime",
+        conflicts_with = "bin",
+        help = "write only the code of the last file the input includes, like the runtime of a contract"
+    )]
+    bin_runtime: bool,
+
+    #[structopt(
+        long = "stamp",
+        help = "append the assembler version and a hash of the sources to the output"
+    )]
+    stamp: bool,
+
+    #[structopt(
+        long = "dialect",
+        default_value = "etk",
+        possible_values(&["etk", "huff"]),
+        help = "language of the assembly source"
+    )]
+    dialect: Dialect,
+
+    #[structopt(
+        long = "profile",
+        default_value = "ethereum",
+        possible_values(&["ethereum", "zksync-era", "polygon-zkevm", "scroll", "linea"]),
+        help = "chain the code is deployed to, to warn about instructions that don't work the same way there"
+    )]
+    profile: Profile,
+
+    #[structopt(
+        long = "experimental",
+        help = "accept instructions from proposals no fork has adopted, like EIP-2315's jumpsub"
+    )]
+    experimental: bool,
+
+    #[structopt(
+        long = "opcodes",
+        value_name = "TABLE",
+        parse(from_os_str),
+        help = "accept the instructions in this TOML file, for chains with instructions of their own"
+    )]
+    opcodes: Option<PathBuf>,
+
+    #[structopt(
+        long = "template",
+        value_name = "VARS",
+        parse(from_os_str),
+        conflicts_with = "watch",
+        help = "render the input as a template, with the variables in this JSON file, before assembling"
+    )]
+    template: Option<PathBuf>,
+
+    #[structopt(
+        long = "macro-depth",
+        value_name = "DEPTH",
+        default_value = "255",
+        help = "how deeply macros may invoke each other"
+    )]
+    macro_depth: usize,
+
+    #[structopt(
+        long = "macro-size",
+        value_name = "COUNT",
+        default_value = "1048576",
+        help = "how many instructions a macro or alias may expand to"
+    )]
+    macro_size: usize,
+
+    #[structopt(
+        long = "opt-level",
+        short = "O",
+        default_value = "0",
+        possible_values(&["0", "s"]),
+        help = "`s` narrows pushes of constants, like `push32 1`, to save space"
+    )]
+    opt_level: OptLevel,
+
+    #[structopt(flatten)]
+    lints: Flags,
+
+    #[structopt(
+        long = "explain",
+        value_name = "CODE",
+        help = "describe the error with the given code (ex. `E0016`) and exit"
+    )]
+    explain: Option<Code>,
+
+    #[structopt(flatten)]
+    log: LogOptions,
+
+    #[structopt(
+        parse(from_os_str),
+        help = "path to the assembly source (`-` for standard input)"
+    )]
+    input: Option<PathBuf>,
+    #[structopt(
+        parse(from_os_str),
+        help = "path to write the assembled code to (`-` or omitted for standard output)"
+    )]
     out: Option<PathBuf>,
 }
 
-fn create(path: PathBuf) -> File {
-    match File::create(&path) {
+/// How much `eas` rewrites instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptLevel {
+    /// Assemble instructions as written.
+    None,
+
+    /// Narrow pushes of constants.
+    Size,
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "0" => Ok(OptLevel::None),
+            "s" => Ok(OptLevel::Size),
+            _ => Err(format!("unknown optimization level `{}`", text)),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    #[structopt(about = "assemble every target declared in etk.toml")]
+    Build(build::Opts),
+
+    #[structopt(about = "create a new project")]
+    Init(init::Opts),
+
+    #[structopt(about = "assemble a contract and deploy it over JSON-RPC")]
+    Deploy(deploy::Opts),
+
+    #[structopt(about = "compare code size and gas against a stored baseline")]
+    CiCheck(ci_check::Opts),
+
+    #[structopt(about = "assemble a contract into a genesis alloc or state test pre-state entry")]
+    Alloc(alloc::Opts),
+
+    #[structopt(about = "translate the output of `solc --asm` into etk sources")]
+    ImportSolc(import_solc::Opts),
+}
+
+fn create(path: PathBuf) -> Box<dyn Write> {
+    match create_output(&path) {
         Err(why) => panic!("couldn't create `{}`: {}", path.display(), why),
         Ok(file) => file,
     }
 }
 
-fn main() {
-    let err = match run() {
+/// Read the variables for `--template` from the JSON file at `path`.
+fn read_vars(path: &Path) -> serde_json::Value {
+    let text = match std::fs::read_to_string(path) {
+        Err(why) => panic!("couldn't read `{}`: {}", path.display(), why),
+        Ok(t) => t,
+    };
+
+    match serde_json::from_str(&text) {
+        Ok(vars) => vars,
+        Err(why) => clap::Error::with_description(
+            &format!("`{}` isn't valid JSON: {}", path.display(), why),
+            ErrorKind::InvalidValue,
+        )
+        .exit(),
+    }
+}
+
+/// The experimental instructions to accept: the proposals, if `proposals`
+/// is set, and the instructions in the table at `path`, if there is one.
+fn read_opcodes(path: Option<&Path>, proposals: bool) -> Experimental {
+    let mut experimental = match proposals {
+        true => Experimental::proposals(),
+        false => Experimental::new(),
+    };
+
+    let path = match path {
+        Some(p) => p,
+        None => return experimental,
+    };
+
+    let text = match std::fs::read_to_string(path) {
+        Err(why) => panic!("couldn't read `{}`: {}", path.display(), why),
+        Ok(t) => t,
+    };
+
+    let table = Experimental::from_toml(&text).and_then(|table| {
+        table
+            .iter()
+            .try_for_each(|op| experimental.insert(op.clone()))
+    });
+
+    match table {
+        Ok(()) => experimental,
+        Err(why) => clap::Error::with_description(
+            &format!(
+                "`{}` isn't a valid opcode table: {}",
+                path.display(),
+                WithSources(why)
+            ),
+            ErrorKind::InvalidValue,
+        )
+        .exit(),
+    }
+}
+
+/// The source to assemble, and the variables to render it with, if it's a
+/// template.
+#[derive(Debug)]
+struct Input<'a> {
+    path: PathBuf,
+    vars: Option<&'a serde_json::Value>,
+}
+
+fn read_stdin() -> String {
+    let mut text = String::new();
+    match std::io::stdin().read_to_string(&mut text) {
+        Err(why) => panic!("couldn't read standard input: {}", why),
+        Ok(_) => text,
+    }
+}
+
+fn exit_on_error<E>(result: Result<(), E>)
+where
+    E: ErrorCompat + std::error::Error,
+{
+    let err = match result {
         Ok(_) => return,
         Err(e) => e,
     };
@@ -35,20 +357,284 @@ fn main() {
     std::process::exit(1);
 }
 
-fn run() -> Result<(), Error> {
-    let opt = Opt::from_args();
+/// The lint levels for assembling `input`, from the project it's in and the
+/// command line.
+fn lint_levels(input: Option<&Path>, overrides: &[Override]) -> Levels {
+    let cwd = match std::env::current_dir() {
+        Err(why) => panic!("couldn't get the current directory: {}", why),
+        Ok(d) => d,
+    };
+
+    // Standard input is treated as if it were in the current directory.
+    let dir = match input {
+        Some(path) if !is_stdio(path) => cwd.join(path.parent().unwrap_or(&cwd)),
+        _ => cwd,
+    };
+
+    match lints::find(&dir) {
+        Ok(levels) => lints::with_overrides(levels, overrides),
+        Err(e) => {
+            eprintln!("{}", WithSources(e));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// What `eas --help` says `eas` is for.
+///
+/// It's set on the app after deriving it, because structopt gives the app the
+/// doc comment of the last struct flattened into it ([`LogOptions`]), for both
+/// `-h` and `--help`, over an `about` attribute on `Opt`.
+const ABOUT: &str = "assemble EVM code, written in etk assembly or Huff, into bytecode";
+
+fn main() {
+    let matches = Opt::clap().about(ABOUT).long_about(ABOUT).get_matches();
+    let mut opt = Opt::from_clap(&matches);
+    opt.lints = std::mem::take(&mut opt.lints).ordered(&matches);
+    opt.log.init();
+
+    if let Some(code) = opt.explain {
+        return explain(code);
+    }
+
+    let map_format = opt.map_format;
+    let map = opt.map.map(|m| (m, map_format));
+
+    // `--bin` is the default, and conflicts with `--bin-runtime`.
+    let runtime = opt.bin_runtime && !opt.bin;
+
+    let mut context = AssemblerContext::new()
+        .stamp(opt.stamp)
+        .runtime(runtime)
+        .dialect(opt.dialect)
+        .macro_limits(opt.macro_depth, opt.macro_size)
+        .optimize_size(opt.opt_level == OptLevel::Size)
+        .profile(opt.profile);
+
+    if opt.experimental || opt.opcodes.is_some() {
+        context = context.experimental(read_opcodes(opt.opcodes.as_deref(), opt.experimental));
+    }
+
+    // Subcommands find their own lint levels, if they need them.
+    if opt.command.is_none() {
+        context = context.lints(lint_levels(opt.input.as_deref(), &opt.lints.overrides()));
+    }
+
+    let vars = opt.template.as_deref().map(read_vars);
+    let input = opt.input.map(|path| Input {
+        path,
+        vars: vars.as_ref(),
+    });
+
+    match opt.command {
+        Some(Command::Build(build)) => {
+            let matches = matches.subcommand_matches("build").unwrap();
+            exit_on_error(build::run(build.ordered(matches)))
+        }
+        Some(Command::Init(init)) => exit_on_error(init::run(init)),
+        Some(Command::Deploy(deploy)) => exit_on_error(deploy::run(deploy)),
+        Some(Command::CiCheck(check)) => exit_on_error(ci_check::run(check)),
+        Some(Command::Alloc(alloc)) => exit_on_error(alloc::run(alloc)),
+        Some(Command::ImportSolc(import)) => exit_on_error(import_solc::run(import)),
+        None => match input {
+            Some(input) if opt.watch && is_stdio(&input.path) => clap::Error::with_description(
+                "standard input cannot be watched for changes",
+                ErrorKind::ArgumentConflict,
+            )
+            .exit(),
+            Some(input) if opt.attest.is_some() && is_stdio(&input.path) => {
+                clap::Error::with_description(
+                    "standard input cannot be attested, since it can't be hashed again",
+                    ErrorKind::ArgumentConflict,
+                )
+                .exit()
+            }
+            Some(input) if opt.watch => exit_on_error(watch::run(
+                input.path,
+                opt.out,
+                opt.output_format,
+                opt.message_format,
+                context,
+            )),
+            Some(input) if opt.check => {
+                report(check(input, &context), opt.message_format, opt.sarif)
+            }
+            Some(input) => report(
+                run(input, opt.out, opt.output_format, &context, map, opt.attest),
+                opt.message_format,
+                opt.sarif,
+            ),
+            None => clap::Error::with_description(
+                "the following required arguments were not provided:\n    <input>",
+                ErrorKind::MissingRequiredArgument,
+            )
+            .exit(),
+        },
+    }
+}
+
+fn explain(code: Code) {
+    let explanation = match codes::explain(code) {
+        Some(e) => e,
+        None => clap::Error::with_description(
+            &format!("there is no error with the code `{}`", code),
+            ErrorKind::InvalidValue,
+        )
+        .exit(),
+    };
+
+    println!("{} ({})\n", explanation.code, explanation.name);
+    println!("{}\n", explanation.text);
+    println!("help: {}", explanation.help);
+}
+
+/// The warnings from assembling, and the error that stopped it, if any.
+type Outcome = (Vec<Diagnostic>, Result<(), Failure>);
+
+fn write_sarif(path: &Path, diagnostics: &[Diagnostic]) {
+    let mut file = create(path.to_owned());
+    let log = diagnostic::sarif(diagnostics);
+
+    if let Err(why) = serde_json::to_writer_pretty(&mut file, &log) {
+        panic!("couldn't write `{}`: {}", path.display(), why);
+    }
+}
+
+/// Print warnings and errors, and exit with a code describing the error, if
+/// there was one.
+fn report(outcome: Outcome, format: MessageFormat, sarif: Option<PathBuf>) {
+    let (mut diagnostics, result) = outcome;
+
+    let failure = result.err();
+    if let Some(ref f) = failure {
+        diagnostics.push(f.diagnostic());
+    }
+
+    for diagnostic in &diagnostics {
+        diagnostic.report(format);
+    }
+
+    if let Some(path) = sarif {
+        write_sarif(&path, &diagnostics);
+    }
+
+    if let Some(f) = failure {
+        std::process::exit(f.kind().exit_code());
+    }
+
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        std::process::exit(Kind::Denied.exit_code());
+    }
+}
+
+fn write_map(path: PathBuf, input: &Path, symbols: &[Symbol], format: MapFormat) {
+    let text = map::render(input, symbols, format);
+    let mut file = create(path.clone());
+
+    if let Err(why) = file.write_all(text.as_bytes()) {
+        panic!("couldn't write `{}`: {}", path.display(), why);
+    }
+}
+
+/// Where to write the attestation for the assembled code, and where the code
+/// itself was written.
+#[derive(Debug)]
+struct Attest {
+    path: PathBuf,
+    output: Option<PathBuf>,
+}
+
+fn write_attestation(attest: Attest, code: &[u8], files: &[PathBuf]) {
+    let path = attest.path;
+    let written = attest::attestation(code, attest.output.as_deref(), files, Path::new(""))
+        .and_then(|a| attest::write(&path, &a));
 
-    let mut out: Box<dyn Write> = match opt.out {
-        Some(o) => Box::new(create(o)),
-        None => Box::new(std::io::stdout()),
+    if let Err(why) = written {
+        panic!("couldn't write `{}`: {}", path.display(), why);
+    }
+}
+
+/// Assemble `input`, then hand the code to `write`, and write the map and the
+/// attestation.
+///
+/// Like `eas build`, nothing at all is written if assembling fails or a lint
+/// is denied.
+fn assemble<F>(
+    input: Input,
+    context: &AssemblerContext,
+    map: Option<(PathBuf, MapFormat)>,
+    attest: Option<Attest>,
+    write: F,
+) -> Outcome
+where
+    F: FnOnce(&[u8]),
+{
+    let mut code = Vec::new();
+    let mut ingest = context.ingest(&mut code);
+    let path = input.path;
+
+    // Includes and imports from standard input are resolved relative to the
+    // current directory.
+    let result = match (is_stdio(&path), input.vars) {
+        (true, None) => ingest.ingest(path.clone(), &read_stdin()),
+        (false, None) => ingest.ingest_file(path.clone()),
+        (true, Some(vars)) => ingest.ingest_template(path.clone(), &read_stdin(), vars),
+        (false, Some(vars)) => ingest.ingest_template_file(path.clone(), vars),
     };
 
-    let hex_out = HexWrite::new(&mut out);
+    let warnings: Vec<Diagnostic> = ingest.warnings().iter().map(Diagnostic::from).collect();
+    let result = result.map_err(|e| Failure::new(e, ingest.failed_file()));
+    let accepted = result.is_ok() && !warnings.iter().any(Diagnostic::is_error);
+
+    if let (true, Some((map, format))) = (accepted, map) {
+        write_map(map, &path, ingest.symbols(), format);
+    }
+
+    let files = ingest.files().to_vec();
+    drop(ingest);
+
+    if accepted {
+        write(&code);
+
+        if let Some(attest) = attest {
+            write_attestation(attest, &code, &files);
+        }
+    }
+
+    (warnings, result)
+}
+
+fn check(input: Input, context: &AssemblerContext) -> Outcome {
+    assemble(input, &context.clone().stamp(false), None, None, |_| ())
+}
+
+fn run(
+    input: Input,
+    out: Option<PathBuf>,
+    format: Format,
+    context: &AssemblerContext,
+    map: Option<(PathBuf, MapFormat)>,
+    attest: Option<PathBuf>,
+) -> Outcome {
+    let attest = attest.map(|path| Attest {
+        path,
+        output: out.clone().filter(|o| !is_stdio(o)),
+    });
 
-    let mut ingest = Ingest::new(hex_out);
-    ingest.ingest_file(opt.input)?;
+    assemble(input, context, map, attest, |code| {
+        let mut out: Box<dyn Write> = match out {
+            Some(o) => create(o),
+            None => Box::new(std::io::stdout()),
+        };
 
-    out.write_all(b"\n").unwrap();
+        match format {
+            Format::Hex => {
+                HexWrite::new(&mut out).write_all(code).unwrap();
+                out.write_all(b"\n").unwrap();
+            }
+            Format::Binary => out.write_all(code).unwrap(),
+        }
 
-    Ok(())
+        out.flush().unwrap();
+    })
 }
@@ -0,0 +1,340 @@
+//! Placing an assembled contract into a genesis file or a state test.
+
+use etk_asm::ingest::Ingest;
+
+use etk_cli::parse::Hex;
+
+use serde_json::{json, Map, Value};
+
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("could not assemble `{}`", path.display()))]
+    Assemble {
+        path: PathBuf,
+        #[snafu(source(from(etk_asm::ingest::Error, Box::new)))]
+        source: Box<etk_asm::ingest::Error>,
+    },
+
+    #[snafu(display("could not read `{}`", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("`{}` isn't valid JSON", path.display()))]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("`{}` isn't a genesis file (no `alloc` object)", path.display()))]
+    NotGenesis { path: PathBuf, backtrace: Backtrace },
+
+    #[snafu(display("`--genesis` only works with `--format genesis`"))]
+    GenesisFormat { backtrace: Backtrace },
+
+    #[snafu(display("could not write `{}`", path.display()))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Which kind of JSON to produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// An entry in the `alloc` of a geth genesis file.
+    Genesis,
+
+    /// An entry in the `pre` of an ethereum/tests state test filler.
+    StateTest,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "genesis" => Ok(Format::Genesis),
+            "state-test" => Ok(Format::StateTest),
+            _ => Err(format!("unknown alloc format `{}`", text)),
+        }
+    }
+}
+
+/// A 256-bit word, written as `0x` prefixed hex or as a decimal number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Word([u8; 32]);
+
+impl FromStr for Word {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut word = [0u8; 32];
+
+        match text.strip_prefix("0x") {
+            Some(digits) => {
+                let padded = format!("{:0>64}", digits);
+                hex::decode_to_slice(&padded, &mut word)
+                    .map_err(|_| format!("`{}` isn't a 32 byte hex number", text))?;
+            }
+            None => {
+                let n: u128 = text
+                    .parse()
+                    .map_err(|_| format!("`{}` isn't a number", text))?;
+                word[16..].copy_from_slice(&n.to_be_bytes());
+            }
+        }
+
+        Ok(Word(word))
+    }
+}
+
+impl Word {
+    fn to_hex(self) -> String {
+        format!("0x{}", hex::encode(self.0))
+    }
+}
+
+/// A storage slot and its value, written as `KEY=VALUE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    key: Word,
+    value: Word,
+}
+
+impl FromStr for Slot {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let eq = text
+            .find('=')
+            .ok_or_else(|| format!("expected `KEY=VALUE`, got `{}`", text))?;
+
+        Ok(Slot {
+            key: text[..eq].parse()?,
+            value: text[eq + 1..].parse()?,
+        })
+    }
+}
+
+/// Parse an amount of wei, in decimal or `0x` prefixed hex.
+fn parse_wei(text: &str) -> Result<u128, String> {
+    match text.strip_prefix("0x") {
+        Some(digits) => u128::from_str_radix(digits, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("`{}` isn't an amount of wei", text))
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opts {
+    #[structopt(
+        long = "format",
+        default_value = "genesis",
+        possible_values(&["genesis", "state-test"]),
+        help = "whether to write a geth genesis alloc entry, or a state test filler pre-state entry"
+    )]
+    format: Format,
+
+    #[structopt(long = "address", help = "address of the account holding the code")]
+    address: Hex<[u8; 20]>,
+
+    #[structopt(
+        long = "balance",
+        default_value = "0",
+        parse(try_from_str = parse_wei),
+        help = "balance of the account, in wei"
+    )]
+    balance: u128,
+
+    #[structopt(long = "nonce", default_value = "0", help = "nonce of the account")]
+    nonce: u64,
+
+    #[structopt(
+        long = "storage",
+        value_name = "KEY=VALUE",
+        number_of_values = 1,
+        help = "set a storage slot (may be repeated)"
+    )]
+    storage: Vec<Slot>,
+
+    #[structopt(
+        long = "genesis",
+        parse(from_os_str),
+        help = "add the account to this genesis file, instead of printing it"
+    )]
+    genesis: Option<PathBuf>,
+
+    #[structopt(
+        parse(from_os_str),
+        help = "path to the assembly source of the account's (runtime) code"
+    )]
+    input: PathBuf,
+}
+
+/// The JSON describing the account, keyed by its address.
+fn account(opts: &Opts, code: &[u8]) -> (String, Value) {
+    let address = format!("0x{}", hex::encode(opts.address.0));
+
+    let storage: Map<String, Value> = opts
+        .storage
+        .iter()
+        .map(|s| (s.key.to_hex(), Value::String(s.value.to_hex())))
+        .collect();
+
+    let account = match opts.format {
+        Format::Genesis => json!({
+            "balance": format!("{:#x}", opts.balance),
+            "nonce": format!("{:#x}", opts.nonce),
+            "code": format!("0x{}", hex::encode(code)),
+            "storage": storage,
+        }),
+        Format::StateTest => json!({
+            "balance": opts.balance.to_string(),
+            "nonce": opts.nonce.to_string(),
+            "code": format!(":raw 0x{}", hex::encode(code)),
+            "storage": storage,
+        }),
+    };
+
+    (address, account)
+}
+
+pub(crate) fn run(opts: Opts) -> Result<(), Error> {
+    ensure!(
+        opts.genesis.is_none() || opts.format == Format::Genesis,
+        GenesisFormat
+    );
+
+    let mut code = Vec::new();
+    Ingest::new(&mut code)
+        .ingest_file(&opts.input)
+        .context(Assemble { path: &opts.input })?;
+
+    let (address, account) = account(&opts, &code);
+
+    let path = match opts.genesis {
+        Some(ref p) => p,
+        None => {
+            let mut entry = Map::new();
+            entry.insert(address, account);
+            println!("{}", serde_json::to_string_pretty(&entry).unwrap());
+            return Ok(());
+        }
+    };
+
+    let text = std::fs::read_to_string(path).context(Read { path })?;
+    let mut genesis: Value = serde_json::from_str(&text).context(Json { path })?;
+
+    genesis
+        .get_mut("alloc")
+        .and_then(Value::as_object_mut)
+        .context(NotGenesis { path })?
+        .insert(address, account);
+
+    let mut text = serde_json::to_string_pretty(&genesis).unwrap();
+    text.push('\n');
+    std::fs::write(path, text).context(Write { path })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn opts(format: Format, genesis: Option<PathBuf>) -> Opts {
+        Opts {
+            format,
+            address: Hex(hex!("00000000000000000000000000000000000000aa")),
+            balance: 1_000_000_000_000_000_000,
+            nonce: 1,
+            storage: vec!["0x01=42".parse().unwrap()],
+            genesis,
+            input: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn parse_slot() {
+        let slot: Slot = "0x1=0xff".parse().unwrap();
+        assert_eq!(slot.key.0[31], 0x01);
+        assert_eq!(slot.value.0[31], 0xff);
+
+        let slot: Slot = "256=1".parse().unwrap();
+        assert_eq!(slot.key.0[30..], [0x01, 0x00]);
+
+        "1".parse::<Slot>().unwrap_err();
+        "0xzz=1".parse::<Slot>().unwrap_err();
+        format!("0x{}=1", "1".repeat(65))
+            .parse::<Slot>()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn genesis_account() {
+        let (address, account) = account(&opts(Format::Genesis, None), &[0x60, 0x2a]);
+
+        assert_eq!(address, "0x00000000000000000000000000000000000000aa");
+        assert_eq!(
+            account,
+            json!({
+                "balance": "0xde0b6b3a7640000",
+                "nonce": "0x1",
+                "code": "0x602a",
+                "storage": {
+                    "0x0000000000000000000000000000000000000000000000000000000000000001":
+                        "0x000000000000000000000000000000000000000000000000000000000000002a",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn state_test_account() {
+        let (_, account) = account(&opts(Format::StateTest, None), &[0x60, 0x2a]);
+
+        assert_eq!(account["balance"], "1000000000000000000");
+        assert_eq!(account["nonce"], "1");
+        assert_eq!(account["code"], ":raw 0x602a");
+    }
+
+    #[test]
+    fn into_genesis_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let input = dir.path().join("main.etk");
+        std::fs::write(&input, "push1 42").unwrap();
+
+        let genesis = dir.path().join("genesis.json");
+        std::fs::write(&genesis, r#"{"config": {"chainId": 1337}, "alloc": {}}"#).unwrap();
+
+        let mut o = opts(Format::Genesis, Some(genesis.clone()));
+        o.input = input.clone();
+        run(o).unwrap();
+
+        let written: Value =
+            serde_json::from_str(&std::fs::read_to_string(&genesis).unwrap()).unwrap();
+        assert_eq!(written["config"]["chainId"], 1337);
+        assert_eq!(
+            written["alloc"]["0x00000000000000000000000000000000000000aa"]["code"],
+            "0x602a"
+        );
+
+        let mut o = opts(Format::StateTest, Some(genesis));
+        o.input = input;
+        assert_matches!(run(o), Err(Error::GenesisFormat { .. }));
+    }
+}
@@ -0,0 +1,150 @@
+//! Attestation files, recording hashes of assembled code and the sources it
+//! was assembled from, so anyone can check a build is reproducible.
+
+use serde_json::{json, Value};
+
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Returns `bytes` hashed with keccak256 and sha256, as hex.
+fn digests(bytes: &[u8]) -> Value {
+    json!({
+        "keccak256": format!("0x{}", hex::encode(Keccak256::digest(bytes))),
+        "sha256": format!("0x{}", hex::encode(Sha256::digest(bytes))),
+    })
+}
+
+/// `path` relative to `base` if it's inside it, with `/` separating
+/// components on every platform.
+fn display(path: &Path, base: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| match c {
+            Component::RootDir => String::new(),
+            c => c.as_os_str().to_string_lossy().into_owned(),
+        })
+        .collect();
+
+    parts.join("/")
+}
+
+/// Describe `code`, written to `output`, and the `inputs` it was assembled
+/// from.
+///
+/// The output and inputs are listed relative to `base`, and inputs are
+/// sorted, with each file only listed once, so the attestation doesn't depend
+/// on where the project is checked out or what order files were read in.
+pub(crate) fn attestation(
+    code: &[u8],
+    output: Option<&Path>,
+    inputs: &[PathBuf],
+    base: &Path,
+) -> Result<Value, io::Error> {
+    let artifact_path = output.map(|o| display(o, base));
+
+    // A manifest in the current directory has an empty parent.
+    let base = match base.as_os_str().is_empty() {
+        true => Path::new(".").canonicalize()?,
+        false => base.canonicalize()?,
+    };
+
+    let mut files = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let path = input.canonicalize()?;
+        files.push((display(&path, &base), std::fs::read(&path)?));
+    }
+
+    files.sort();
+    files.dedup_by(|a, b| a.0 == b.0);
+
+    let inputs: Vec<_> = files
+        .iter()
+        .map(|(path, bytes)| {
+            let mut entry = digests(bytes);
+            entry["path"] = json!(path);
+            entry
+        })
+        .collect();
+
+    let mut artifact = digests(code);
+    artifact["size"] = json!(code.len());
+    artifact["path"] = json!(artifact_path);
+
+    Ok(json!({
+        "assembler": concat!("etk-asm ", env!("CARGO_PKG_VERSION")),
+        "artifact": artifact,
+        "inputs": inputs,
+    }))
+}
+
+/// Write `attestation` to `path`.
+pub(crate) fn write(path: &Path, attestation: &Value) -> Result<(), io::Error> {
+    let mut text = serde_json::to_string_pretty(attestation).unwrap();
+    text.push('\n');
+    std::fs::write(path, text)
+}
+
+/// The path of the attestation written next to `output`.
+pub(crate) fn sidecar(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_owned();
+    name.push(".attestation.json");
+    output.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attestation_of_project() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.etk"), "%include(\"lib.etk\")").unwrap();
+        std::fs::write(dir.path().join("src/lib.etk"), "stop").unwrap();
+
+        let inputs = [
+            dir.path().join("src/main.etk"),
+            dir.path().join("src/./lib.etk"),
+            dir.path().join("src/lib.etk"),
+        ];
+
+        let output = dir.path().join("out/main.hex");
+        let value = attestation(&[0x00], Some(&output), &inputs, dir.path()).unwrap();
+
+        assert_eq!(value["artifact"]["size"], 1);
+        assert_eq!(value["artifact"]["path"], "out/main.hex");
+        assert_eq!(
+            value["artifact"]["keccak256"],
+            "0xbc36789e7a1e281436464229828f817d6612f7b477d66591ff96a9e064bcc98a"
+        );
+        assert_eq!(
+            value["artifact"]["sha256"],
+            "0x6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+
+        let paths: Vec<_> = value["inputs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| i["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["src/lib.etk", "src/main.etk"]);
+        assert_eq!(
+            value["inputs"][0]["keccak256"],
+            format!("0x{}", hex::encode(Keccak256::digest(b"stop")))
+        );
+    }
+
+    #[test]
+    fn sidecar_path() {
+        assert_eq!(
+            sidecar(Path::new("out/main.hex")),
+            Path::new("out/main.hex.attestation.json")
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use crate::attest;
+use crate::diagnostic::{Diagnostic, MessageFormat};
+use crate::lints::{self, Flags};
+use crate::manifest::{self, Format, Manifest, Target};
+
+use etk_asm::ingest::AssemblerContext;
+use etk_asm::lint::{Level, Levels, Warning};
+
+use rayon::prelude::*;
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::path::{Path, PathBuf};
+
+use structopt::clap::ArgMatches;
+use structopt::StructOpt;
+
+use tracing::info;
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display(
+        "could not find `{}` in `{}` or any parent directory",
+        manifest::FILE_NAME,
+        dir.display()
+    ))]
+    NoManifest { dir: PathBuf, backtrace: Backtrace },
+
+    #[snafu(display("could not get the current directory"))]
+    CurrentDir { source: std::io::Error },
+
+    #[snafu(context(false))]
+    Manifest { source: manifest::Error },
+
+    #[snafu(display("no target named `{}`", name))]
+    UnknownTarget { name: String, backtrace: Backtrace },
+
+    #[snafu(display("could not assemble target `{}`", target))]
+    Assemble {
+        target: String,
+        #[snafu(source(from(etk_asm::ingest::Error, Box::new)))]
+        source: Box<etk_asm::ingest::Error>,
+    },
+
+    #[snafu(display("target `{}` has warnings that are denied", target))]
+    Denied {
+        target: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("could not write `{}`", path.display()))]
+    Output {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("could not start the thread pool"))]
+    ThreadPool { source: rayon::ThreadPoolBuildError },
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opts {
+    #[structopt(
+        long = "manifest-path",
+        parse(from_os_str),
+        help = "path to etk.toml (defaults to searching the current directory and its parents)"
+    )]
+    manifest_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "jobs",
+        short = "j",
+        help = "number of targets to assemble at once (defaults to the number of CPUs)"
+    )]
+    jobs: Option<usize>,
+
+    #[structopt(flatten)]
+    lints: Flags,
+
+    #[structopt(
+        long = "attest",
+        help = "also write the hashes of each output and its sources next to it, as OUTPUT.attestation.json"
+    )]
+    attest: bool,
+
+    #[structopt(help = "names of the targets to build (defaults to all of them)")]
+    targets: Vec<String>,
+}
+
+impl Opts {
+    /// Put the lint levels in the order they were given. See
+    /// [`Flags::ordered`].
+    pub(crate) fn ordered(self, matches: &ArgMatches) -> Self {
+        Self {
+            lints: self.lints.ordered(matches),
+            ..self
+        }
+    }
+}
+
+/// The code assembled for a target, and the files it was assembled from.
+#[derive(Debug)]
+pub(crate) struct Built {
+    pub(crate) code: Vec<u8>,
+    pub(crate) files: Vec<PathBuf>,
+}
+
+fn assemble(target: &Target, lints: &Levels) -> Result<(Built, Vec<Warning>), Error> {
+    let mut context = AssemblerContext::new()
+        .fork(target.fork)
+        .profile(target.profile)
+        .optimize_size(target.optimize)
+        .lints(lints.clone());
+    for (name, value) in &target.defines {
+        context = context.define(name.as_str(), value.as_slice());
+    }
+
+    let mut code = Vec::new();
+    let mut ingest = context.ingest(&mut code);
+
+    ingest.ingest_file(&target.entry).context(Assemble {
+        target: &target.name,
+    })?;
+
+    let warnings = ingest.warnings().to_vec();
+    let files = ingest.files().to_vec();
+    Ok((Built { code, files }, warnings))
+}
+
+/// Assemble `targets` on rayon's thread pool, returning what was built for
+/// each in the same order.
+///
+/// Warnings are reported in the order the targets are listed, and the first
+/// target in that order to fail, or to have a denied warning, is the one
+/// reported as the error.
+pub(crate) fn assemble_all(targets: &[&Target], lints: &Levels) -> Result<Vec<Built>, Error> {
+    let results: Vec<_> = targets.par_iter().map(|t| assemble(t, lints)).collect();
+
+    let mut built = Vec::with_capacity(results.len());
+    for (target, result) in targets.iter().zip(results) {
+        let (output, warnings) = result?;
+
+        for warning in &warnings {
+            Diagnostic::from(warning).report(MessageFormat::Human);
+        }
+
+        if warnings.iter().any(|w| w.level == Level::Deny) {
+            return Denied {
+                target: &target.name,
+            }
+            .fail();
+        }
+
+        built.push(output);
+    }
+
+    Ok(built)
+}
+
+fn write(path: &Path, format: Format, code: &[u8]) -> Result<(), std::io::Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    match format {
+        Format::Hex => std::fs::write(path, format!("{}\n", hex::encode(code))),
+        Format::Binary => std::fs::write(path, code),
+    }
+}
+
+/// Read the manifest at `path`, or search for one starting from the current
+/// directory.
+pub(crate) fn load(path: Option<&Path>) -> Result<Manifest, Error> {
+    let path = match path {
+        Some(p) => p.to_owned(),
+        None => {
+            let dir = std::env::current_dir().context(CurrentDir)?;
+            Manifest::find(&dir).context(NoManifest { dir })?
+        }
+    };
+
+    Ok(Manifest::read(&path)?)
+}
+
+/// The targets listed in `names`, or every target if `names` is empty.
+///
+/// Naming a target that has variants selects all of them.
+pub(crate) fn select<'a>(
+    manifest: &'a Manifest,
+    names: &[String],
+) -> Result<Vec<&'a Target>, Error> {
+    for name in names {
+        if !manifest.targets.iter().any(|t| t.is_named(name)) {
+            return UnknownTarget { name }.fail();
+        }
+    }
+
+    let targets = manifest
+        .targets
+        .iter()
+        .filter(|t| names.is_empty() || names.iter().any(|n| t.is_named(n)))
+        .collect();
+
+    Ok(targets)
+}
+
+pub(crate) fn run(opts: Opts) -> Result<(), Error> {
+    let manifest = load(opts.manifest_path.as_deref())?;
+    let targets = select(&manifest, &opts.targets)?;
+    let lints = lints::with_overrides(manifest.lints.clone(), &opts.lints.overrides());
+
+    let built = match opts.jobs {
+        Some(jobs) => rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .context(ThreadPool)?
+            .install(|| assemble_all(&targets, &lints))?,
+        None => assemble_all(&targets, &lints)?,
+    };
+
+    for (target, Built { code, files }) in targets.into_iter().zip(built) {
+        write(&target.output, target.format, &code).context(Output {
+            path: &target.output,
+        })?;
+
+        if opts.attest {
+            let path = attest::sidecar(&target.output);
+            attest::attestation(&code, Some(&target.output), &files, &manifest.dir)
+                .and_then(|a| attest::write(&path, &a))
+                .context(Output { path })?;
+        }
+
+        info!(
+            "{}: {} bytes -> {}",
+            target.name,
+            code.len(),
+            target.output.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/a.etk"), "push2 VALUE\nchainid").unwrap();
+        std::fs::write(
+            dir.path().join(manifest::FILE_NAME),
+            r#"
+                [[target]]
+                name = "a"
+                entry = "src/a.etk"
+                defines = { VALUE = 258 }
+
+                [[target]]
+                name = "a-binary"
+                entry = "src/a.etk"
+                format = "binary"
+                defines = { VALUE = 1 }
+
+                [[target]]
+                name = "a-old"
+                entry = "src/a.etk"
+                fork = "petersburg"
+                defines = { VALUE = 1 }
+            "#,
+        )
+        .unwrap();
+
+        dir
+    }
+
+    fn opts(dir: &Path, targets: &[&str]) -> Opts {
+        Opts {
+            manifest_path: Some(dir.join(manifest::FILE_NAME)),
+            jobs: None,
+            lints: Flags::default(),
+            attest: false,
+            targets: targets.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn build_selected() {
+        let dir = project();
+
+        run(opts(dir.path(), &["a", "a-binary"])).unwrap();
+
+        let hex = std::fs::read_to_string(dir.path().join("out/a.hex")).unwrap();
+        assert_eq!(hex, "61010246\n");
+
+        let bin = std::fs::read(dir.path().join("out/a-binary.bin")).unwrap();
+        assert_eq!(bin, [0x61, 0x00, 0x01, 0x46]);
+    }
+
+    #[test]
+    fn build_attest() {
+        let dir = project();
+
+        let mut opts = opts(dir.path(), &["a"]);
+        opts.attest = true;
+        run(opts).unwrap();
+
+        let path = dir.path().join("out/a.hex.attestation.json");
+        let text = std::fs::read_to_string(path).unwrap();
+        let attestation: serde_json::Value = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(attestation["artifact"]["path"], "out/a.hex");
+        assert_eq!(attestation["artifact"]["size"], 4);
+        assert_eq!(attestation["inputs"][0]["path"], "src/a.etk");
+    }
+
+    #[test]
+    fn build_unsupported_fork() {
+        let dir = project();
+
+        let err = run(opts(dir.path(), &[])).unwrap_err();
+        assert_matches!(err, Error::Assemble { target, .. } if target == "a-old");
+
+        // Nothing is written unless every target assembles.
+        assert!(!dir.path().join("out").exists());
+    }
+
+    #[test]
+    fn build_with_jobs() {
+        let dir = project();
+
+        let opts = Opts {
+            jobs: Some(2),
+            ..opts(dir.path(), &["a-binary", "a"])
+        };
+        run(opts).unwrap();
+
+        let hex = std::fs::read_to_string(dir.path().join("out/a.hex")).unwrap();
+        assert_eq!(hex, "61010246\n");
+
+        let bin = std::fs::read(dir.path().join("out/a-binary.bin")).unwrap();
+        assert_eq!(bin, [0x61, 0x00, 0x01, 0x46]);
+    }
+
+    #[test]
+    fn build_denied() {
+        let dir = project();
+        std::fs::write(
+            dir.path().join("src/a.etk"),
+            "push2 VALUE\nchainid\nunused:",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join(lints::FILE_NAME),
+            "unused-label = \"deny\"\n",
+        )
+        .unwrap();
+
+        let err = run(opts(dir.path(), &["a"])).unwrap_err();
+        assert_matches!(err, Error::Denied { target, .. } if target == "a");
+        assert!(!dir.path().join("out").exists());
+
+        let opts = Opts {
+            lints: Flags::new(vec!["unused-label=allow".parse().unwrap()]),
+            ..opts(dir.path(), &["a"])
+        };
+        run(opts).unwrap();
+    }
+
+    #[test]
+    fn build_variants() {
+        let dir = project();
+        std::fs::write(
+            dir.path().join(manifest::FILE_NAME),
+            r#"
+                [[target]]
+                name = "a"
+                entry = "src/a.etk"
+                defines = { VALUE = 1 }
+
+                [target.variants.one]
+
+                [target.variants.two]
+                defines = { VALUE = 2 }
+            "#,
+        )
+        .unwrap();
+
+        run(opts(dir.path(), &["a"])).unwrap();
+
+        let one = std::fs::read_to_string(dir.path().join("out/a-one.hex")).unwrap();
+        assert_eq!(one, "61000146\n");
+
+        let two = std::fs::read_to_string(dir.path().join("out/a-two.hex")).unwrap();
+        assert_eq!(two, "61000246\n");
+
+        assert!(!dir.path().join("out/a.hex").exists());
+    }
+
+    #[test]
+    fn build_optimize() {
+        let dir = project();
+        std::fs::write(dir.path().join("src/b.etk"), "push32 1").unwrap();
+        std::fs::write(
+            dir.path().join(manifest::FILE_NAME),
+            r#"
+                [[target]]
+                name = "b"
+                entry = "src/b.etk"
+                optimize = true
+            "#,
+        )
+        .unwrap();
+
+        run(opts(dir.path(), &["b"])).unwrap();
+
+        let hex = std::fs::read_to_string(dir.path().join("out/b.hex")).unwrap();
+        assert_eq!(hex, "6001\n");
+    }
+
+    #[test]
+    fn build_unknown_target() {
+        let dir = project();
+
+        let err = run(opts(dir.path(), &["b"])).unwrap_err();
+        assert_matches!(err, Error::UnknownTarget { name, .. } if name == "b");
+    }
+}
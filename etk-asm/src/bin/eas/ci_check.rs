@@ -0,0 +1,365 @@
+use crate::build;
+
+use etk_asm::disasm::Disassembler;
+use etk_asm::ops::Profile;
+
+use serde::{Deserialize, Serialize};
+
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use tracing::info;
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(context(false))]
+    Build { source: build::Error },
+
+    #[snafu(display("could not read baseline `{}`", path.display()))]
+    ReadBaseline {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("could not parse baseline `{}`", path.display()))]
+    ParseBaseline {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("could not write baseline `{}`", path.display()))]
+    WriteBaseline {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("{} metric(s) grew more than allowed", count))]
+    Regressed { count: usize, backtrace: Backtrace },
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opts {
+    #[structopt(
+        long = "manifest-path",
+        parse(from_os_str),
+        help = "path to etk.toml (defaults to searching the current directory and its parents)"
+    )]
+    manifest_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "baseline",
+        parse(from_os_str),
+        help = "path to the JSON file holding the metrics to compare against"
+    )]
+    baseline: PathBuf,
+
+    #[structopt(
+        long = "size-threshold",
+        default_value = "0",
+        help = "percentage the code size may grow by before failing"
+    )]
+    size_threshold: f64,
+
+    #[structopt(
+        long = "gas-threshold",
+        default_value = "0",
+        help = "percentage the static gas may grow by before failing"
+    )]
+    gas_threshold: f64,
+
+    #[structopt(
+        long = "update",
+        help = "record the current metrics in the baseline instead of comparing"
+    )]
+    update: bool,
+
+    #[structopt(help = "names of the targets to check (defaults to all of them)")]
+    targets: Vec<String>,
+}
+
+/// Numbers describing a target's assembled code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Metrics {
+    /// Length of the code, in bytes.
+    size: u64,
+
+    /// Sum of the base gas of every instruction, as if each ran once.
+    static_gas: u64,
+}
+
+impl Metrics {
+    fn measure(code: &[u8], profile: Profile) -> Self {
+        let mut disasm = Disassembler::new();
+        disasm.write_all(code).unwrap();
+
+        // A push truncated by the end of the code is left in the disassembler,
+        // and doesn't count towards the gas. Neither do instructions the
+        // profile can't run, which are warned about while assembling.
+        let static_gas = disasm
+            .ops()
+            .filter_map(|op| profile.base_gas(op.item.specifier()))
+            .sum();
+
+        Self {
+            size: code.len() as u64,
+            static_gas,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Baseline {
+    targets: BTreeMap<String, Metrics>,
+}
+
+/// Whether growing from `old` to `new` is more than `threshold` percent.
+fn exceeds(old: u64, new: u64, threshold: f64) -> bool {
+    if new <= old {
+        return false;
+    }
+
+    ((new - old) as f64) * 100.0 > threshold * (old as f64)
+}
+
+fn delta(old: u64, new: u64) -> String {
+    let diff = new as i128 - old as i128;
+
+    if old == 0 {
+        format!("{:+}", diff)
+    } else {
+        let percent = (diff as f64) * 100.0 / (old as f64);
+        format!("{:+} ({:+.2}%)", diff, percent)
+    }
+}
+
+/// Compare `current` against `baseline`, printing a report to `out`.
+///
+/// Returns the number of metrics that exceeded their thresholds.
+fn compare<W: Write>(
+    out: &mut W,
+    baseline: &Baseline,
+    current: &BTreeMap<String, Metrics>,
+    size_threshold: f64,
+    gas_threshold: f64,
+) -> std::io::Result<usize> {
+    let mut exceeded = 0;
+
+    writeln!(
+        out,
+        "{:<20} {:<10} {:>10} {:>10}  delta",
+        "target", "metric", "baseline", "current"
+    )?;
+
+    for (name, now) in current {
+        let before = match baseline.targets.get(name) {
+            Some(b) => b,
+            None => {
+                writeln!(out, "{:<20} (not in baseline)", name)?;
+                continue;
+            }
+        };
+
+        let rows = [
+            ("size", before.size, now.size, size_threshold),
+            (
+                "static_gas",
+                before.static_gas,
+                now.static_gas,
+                gas_threshold,
+            ),
+        ];
+
+        for &(metric, old, new, threshold) in rows.iter() {
+            let verdict = if exceeds(old, new, threshold) {
+                exceeded += 1;
+                format!("  exceeds {}%", threshold)
+            } else {
+                String::new()
+            };
+
+            writeln!(
+                out,
+                "{:<20} {:<10} {:>10} {:>10}  {}{}",
+                name,
+                metric,
+                old,
+                new,
+                delta(old, new),
+                verdict,
+            )?;
+        }
+    }
+
+    Ok(exceeded)
+}
+
+pub(crate) fn run(opts: Opts) -> Result<(), Error> {
+    let manifest = build::load(opts.manifest_path.as_deref())?;
+
+    let targets = build::select(&manifest, &opts.targets)?;
+    let built = build::assemble_all(&targets, &manifest.lints)?;
+
+    let current: BTreeMap<_, _> = targets
+        .iter()
+        .zip(built)
+        .map(|(target, b)| {
+            (
+                target.name.clone(),
+                Metrics::measure(&b.code, target.profile),
+            )
+        })
+        .collect();
+
+    let path = &opts.baseline;
+
+    let mut baseline = if opts.update && !path.exists() {
+        Baseline::default()
+    } else {
+        let text = std::fs::read_to_string(path).context(ReadBaseline { path })?;
+        serde_json::from_str(&text).context(ParseBaseline { path })?
+    };
+
+    if opts.update {
+        baseline.targets.extend(current);
+
+        let mut text = serde_json::to_string_pretty(&baseline).unwrap();
+        text.push('\n');
+        std::fs::write(path, text).context(WriteBaseline { path })?;
+
+        info!("updated {}", path.display());
+        return Ok(());
+    }
+
+    let stdout = std::io::stdout();
+    let count = compare(
+        &mut stdout.lock(),
+        &baseline,
+        &current,
+        opts.size_threshold,
+        opts.gas_threshold,
+    )
+    .expect("couldn't write report");
+
+    ensure!(count == 0, Regressed { count });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use crate::manifest;
+
+    use super::*;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::write(dir.path().join("a.etk"), "push1 1\npush1 2\nadd").unwrap();
+        std::fs::write(
+            dir.path().join(manifest::FILE_NAME),
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+            "#,
+        )
+        .unwrap();
+
+        dir
+    }
+
+    fn opts(dir: &std::path::Path, update: bool) -> Opts {
+        Opts {
+            manifest_path: Some(dir.join(manifest::FILE_NAME)),
+            baseline: dir.join("baseline.json"),
+            size_threshold: 0.0,
+            gas_threshold: 0.0,
+            update,
+            targets: vec![],
+        }
+    }
+
+    #[test]
+    fn measure() {
+        let metrics = Metrics::measure(&[0x60, 0x01, 0x60, 0x02, 0x01], Profile::Ethereum);
+        assert_eq!(metrics.size, 5);
+        assert_eq!(metrics.static_gas, 9);
+
+        let metrics = Metrics::measure(&[0x58, 0x58], Profile::ZkSyncEra);
+        assert_eq!(metrics.static_gas, 0);
+    }
+
+    #[test]
+    fn exceeds_threshold() {
+        assert!(!exceeds(100, 100, 0.0));
+        assert!(!exceeds(100, 90, 0.0));
+        assert!(exceeds(100, 101, 0.0));
+        assert!(!exceeds(100, 105, 5.0));
+        assert!(exceeds(100, 106, 5.0));
+        assert!(exceeds(0, 1, 50.0));
+    }
+
+    #[test]
+    fn report() {
+        let mut baseline = Baseline::default();
+        baseline.targets.insert(
+            "a".into(),
+            Metrics {
+                size: 100,
+                static_gas: 10,
+            },
+        );
+
+        let mut current = BTreeMap::new();
+        current.insert(
+            "a".into(),
+            Metrics {
+                size: 110,
+                static_gas: 10,
+            },
+        );
+        current.insert(
+            "b".into(),
+            Metrics {
+                size: 1,
+                static_gas: 1,
+            },
+        );
+
+        let mut out = Vec::new();
+        let count = compare(&mut out, &baseline, &current, 5.0, 0.0).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert_eq!(count, 1);
+        assert!(text.contains("+10 (+10.00%)  exceeds 5%"));
+        assert!(text.contains("b                    (not in baseline)"));
+    }
+
+    #[test]
+    fn update_then_check() {
+        let dir = project();
+
+        run(opts(dir.path(), true)).unwrap();
+        run(opts(dir.path(), false)).unwrap();
+
+        std::fs::write(dir.path().join("a.etk"), "push1 1\npush1 2\nadd\npop").unwrap();
+
+        let err = run(opts(dir.path(), false)).unwrap_err();
+        assert_matches!(err, Error::Regressed { count: 2, .. });
+    }
+
+    #[test]
+    fn missing_baseline() {
+        let dir = project();
+
+        let err = run(opts(dir.path(), false)).unwrap_err();
+        assert_matches!(err, Error::ReadBaseline { .. });
+    }
+}
@@ -0,0 +1,316 @@
+use crate::tx::{self, Create};
+
+use etk_asm::asm::{Assembler, RawOp};
+use etk_asm::ingest::AssemblerContext;
+use etk_asm::ops::{AbstractOp, Fork, Imm, Op};
+
+use etk_cli::rpc::{self, Client};
+
+use k256::ecdsa::SigningKey;
+
+use serde_json::{json, Value};
+
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use structopt::StructOpt;
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("could not assemble `{}`", path.display()))]
+    Assemble {
+        path: PathBuf,
+        #[snafu(source(from(etk_asm::ingest::Error, Box::new)))]
+        source: Box<etk_asm::ingest::Error>,
+    },
+
+    #[snafu(display("runtime code is {} bytes, which is too large to wrap", len))]
+    RuntimeTooLarge { len: usize, backtrace: Backtrace },
+
+    #[snafu(display("environment variable `{}` is not set", name))]
+    MissingKey {
+        name: String,
+        source: std::env::VarError,
+    },
+
+    #[snafu(display("environment variable `{}` is not a valid private key", name))]
+    InvalidKey { name: String, backtrace: Backtrace },
+
+    #[snafu(context(false))]
+    Rpc { source: rpc::Error },
+
+    #[snafu(display("the node's response didn't include `{}`", field))]
+    MissingField {
+        field: &'static str,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("no receipt for `{}` after {} seconds", hash, seconds))]
+    Timeout {
+        hash: String,
+        seconds: u64,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("deployment transaction `{}` reverted", hash))]
+    Reverted { hash: String, backtrace: Backtrace },
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opts {
+    #[structopt(long = "rpc", help = "url of the JSON-RPC endpoint to deploy through")]
+    rpc: String,
+
+    #[structopt(
+        long = "private-key-env",
+        value_name = "VAR",
+        help = "name of the environment variable holding the deployer's private key"
+    )]
+    private_key_env: String,
+
+    #[structopt(
+        long = "fork",
+        help = "hardfork to assemble for (defaults to the fork of the endpoint's chain at its latest block)"
+    )]
+    fork: Option<Fork>,
+
+    #[structopt(
+        long = "runtime",
+        help = "treat the assembled code as runtime code, and wrap it in a minimal constructor"
+    )]
+    runtime: bool,
+
+    #[structopt(long = "gas-limit", help = "gas limit (defaults to eth_estimateGas)")]
+    gas_limit: Option<u64>,
+
+    #[structopt(
+        long = "max-fee-per-gas",
+        help = "maximum total fee per gas, in wei (defaults to twice the base fee plus the priority fee)"
+    )]
+    max_fee_per_gas: Option<u128>,
+
+    #[structopt(
+        long = "max-priority-fee-per-gas",
+        help = "maximum priority fee per gas, in wei (defaults to eth_maxPriorityFeePerGas)"
+    )]
+    max_priority_fee_per_gas: Option<u128>,
+
+    #[structopt(
+        long = "timeout",
+        default_value = "120",
+        help = "seconds to wait for the transaction receipt"
+    )]
+    timeout: u64,
+
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+}
+
+/// Prepend a constructor to `runtime` that copies it into memory and returns it.
+fn wrap(runtime: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let len = u16::try_from(runtime.len())
+        .ok()
+        .context(RuntimeTooLarge { len: runtime.len() })?;
+
+    let ops: Vec<RawOp> = vec![
+        AbstractOp::Op(Op::Push2(Imm::from(len))).into(),
+        AbstractOp::Op(Op::Dup1).into(),
+        AbstractOp::Op(Op::Push1(Imm::from("runtime"))).into(),
+        AbstractOp::Op(Op::Push1(Imm::from(0u8))).into(),
+        AbstractOp::Op(Op::CodeCopy).into(),
+        AbstractOp::Op(Op::Push1(Imm::from(0u8))).into(),
+        AbstractOp::Op(Op::Return).into(),
+        AbstractOp::Label("runtime".into()).into(),
+        runtime.into(),
+    ];
+
+    let mut asm = Assembler::new();
+    asm.push_all(ops).expect("constructor should assemble");
+    let init = asm.take();
+    asm.finish().expect("constructor should assemble");
+
+    Ok(init)
+}
+
+fn read_key(name: &str) -> Result<SigningKey, Error> {
+    let text = std::env::var(name).context(MissingKey { name })?;
+    let text = text.trim();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+
+    hex::decode(text)
+        .ok()
+        .and_then(|bytes| SigningKey::from_bytes(&bytes).ok())
+        .context(InvalidKey { name })
+}
+
+fn field<'a>(value: &'a Value, field: &'static str) -> Result<&'a str, Error> {
+    value[field].as_str().context(MissingField { field })
+}
+
+/// The fork to assemble for, and why it was picked.
+fn fork(client: &mut Client, chain_id: u64, fork: Option<Fork>) -> Result<(Fork, String), Error> {
+    if let Some(fork) = fork {
+        return Ok((fork, "from --fork".into()));
+    }
+
+    let block = client.request_quantity("eth_blockNumber", ())? as u64;
+
+    Ok(match Fork::at_block(chain_id, block) {
+        Some(fork) => (fork, format!("chain {} at block {}", chain_id, block)),
+        None => (
+            Fork::latest(),
+            format!("chain {} has no known schedule", chain_id),
+        ),
+    })
+}
+
+pub(crate) fn run(opts: Opts) -> Result<(), Error> {
+    let mut client = Client::new(opts.rpc);
+    let chain_id = client.chain_id()? as u64;
+
+    let (fork, reason) = fork(&mut client, chain_id, opts.fork)?;
+    println!("fork: {} ({})", fork, reason);
+
+    let mut code = Vec::new();
+    let mut ingest = AssemblerContext::new().fork(fork).ingest(&mut code);
+    ingest
+        .ingest_file(&opts.input)
+        .context(Assemble { path: &opts.input })?;
+
+    if opts.runtime {
+        code = wrap(code)?;
+    }
+
+    let key = read_key(&opts.private_key_env)?;
+    let from = format!("0x{}", hex::encode(tx::address(&key.verifying_key())));
+    let data = format!("0x{}", hex::encode(&code));
+
+    let nonce = client.request_quantity("eth_getTransactionCount", (&from, "pending"))? as u64;
+
+    let max_priority_fee_per_gas = match opts.max_priority_fee_per_gas {
+        Some(fee) => fee,
+        None => client.request_quantity("eth_maxPriorityFeePerGas", ())?,
+    };
+
+    let max_fee_per_gas = match opts.max_fee_per_gas {
+        Some(fee) => fee,
+        None => {
+            let block: Value = client.request("eth_getBlockByNumber", ("latest", false))?;
+            let base_fee = rpc::parse_quantity(field(&block, "baseFeePerGas")?)?;
+            2 * base_fee + max_priority_fee_per_gas
+        }
+    };
+
+    let gas_limit = match opts.gas_limit {
+        Some(limit) => limit,
+        None => {
+            let call = json!({ "from": from, "data": data });
+            client.request_quantity("eth_estimateGas", [call])? as u64
+        }
+    };
+
+    let create = Create {
+        chain_id,
+        nonce,
+        max_priority_fee_per_gas,
+        max_fee_per_gas,
+        gas_limit,
+        init_code: code,
+    };
+
+    let raw = format!("0x{}", hex::encode(create.sign(&key)));
+    let hash: String = client.request("eth_sendRawTransaction", [raw])?;
+
+    println!("transaction: {}", hash);
+
+    let started = Instant::now();
+    let receipt = loop {
+        let receipt: Value = client.request("eth_getTransactionReceipt", [&hash])?;
+        if !receipt.is_null() {
+            break receipt;
+        }
+
+        ensure!(
+            started.elapsed() < Duration::from_secs(opts.timeout),
+            Timeout {
+                hash,
+                seconds: opts.timeout,
+            }
+        );
+
+        std::thread::sleep(Duration::from_secs(1));
+    };
+
+    let block = rpc::parse_quantity(field(&receipt, "blockNumber")?)?;
+    let gas_used = rpc::parse_quantity(field(&receipt, "gasUsed")?)?;
+
+    println!("block: {}", block);
+    println!("gas used: {}", gas_used);
+
+    ensure!(field(&receipt, "status")? == "0x1", Reverted { hash });
+
+    println!("contract address: {}", field(&receipt, "contractAddress")?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn wrap_runtime() {
+        let init = wrap(hex!("602a60005260206000f3").to_vec()).unwrap();
+        let expected = hex!("61000a80600c6000396000f3602a60005260206000f3");
+        assert_eq!(init, expected);
+    }
+
+    #[test]
+    fn wrap_too_large() {
+        let err = wrap(vec![0u8; 0x10000]).unwrap_err();
+        assert_matches!(err, Error::RuntimeTooLarge { len: 0x10000, .. });
+    }
+
+    #[test]
+    fn read_key_with_prefix() {
+        let name = "EAS_TEST_READ_KEY_WITH_PREFIX";
+        std::env::set_var(
+            name,
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318\n",
+        );
+
+        let key = read_key(name).unwrap();
+        let expected = hex!("2c7536e3605d9c16a7a3d7b1898e529396a65c23");
+        assert_eq!(tx::address(&key.verifying_key()), expected);
+    }
+
+    #[test]
+    fn read_key_invalid() {
+        let name = "EAS_TEST_READ_KEY_INVALID";
+        std::env::set_var(name, "0xnothex");
+        assert_matches!(read_key(name), Err(Error::InvalidKey { .. }));
+    }
+
+    #[test]
+    fn read_key_missing() {
+        let name = "EAS_TEST_READ_KEY_MISSING";
+        std::env::remove_var(name);
+        assert_matches!(read_key(name), Err(Error::MissingKey { .. }));
+    }
+
+    #[test]
+    fn fork_override() {
+        // Nothing listens here, so the fork can't come from the endpoint.
+        let mut client = Client::new("http://127.0.0.1:9");
+        let (fork, reason) = fork(&mut client, 1, Some(Fork::Berlin)).unwrap();
+        assert_eq!(fork, Fork::Berlin);
+        assert_eq!(reason, "from --fork");
+    }
+}
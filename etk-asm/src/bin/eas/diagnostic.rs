@@ -0,0 +1,813 @@
+//! Reporting assembly errors to people and to tools.
+
+use etk_asm::asm::Error as AsmError;
+use etk_asm::codes::{explain, Code, EXPLANATIONS};
+use etk_asm::ingest::{Error, Site};
+use etk_asm::lint::{Level, Lint, Warning};
+use etk_asm::{ParseError, Span};
+
+use etk_cli::snippet::{render, ColorChoice, Label, Style};
+
+use serde_json::json;
+
+use snafu::{Backtrace, ErrorCompat};
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MessageFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!("unknown message format `{}`", text)),
+        }
+    }
+}
+
+/// Broad categories of failure, each with its own exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// Reading or writing files failed.
+    Io,
+
+    /// The source code isn't valid syntax.
+    Parse,
+
+    /// The source code parsed, but couldn't be assembled.
+    Semantic,
+
+    /// The source code assembled, but with a warning that the lint
+    /// configuration denies.
+    Denied,
+}
+
+impl Kind {
+    pub(crate) fn exit_code(self) -> i32 {
+        match self {
+            Kind::Io => 1,
+            Kind::Parse => 2,
+            Kind::Semantic => 3,
+            Kind::Denied => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// An error or warning, in a form that can be printed for people or tools.
+#[derive(Debug)]
+pub(crate) struct Diagnostic {
+    severity: Severity,
+    code: &'static str,
+
+    /// The stable code of an error, like `E0016`.
+    error_code: Option<Code>,
+
+    message: String,
+    file: Option<PathBuf>,
+    span: Option<Span>,
+
+    /// Regions of the source to show, underlined, and the files they're in.
+    labels: Vec<(PathBuf, Label)>,
+
+    /// Extra lines of advice, printed after everything else.
+    notes: Vec<String>,
+
+    /// Text printed at the very end, like a backtrace.
+    trailer: String,
+}
+
+impl Diagnostic {
+    /// Whether this is an error, including a warning that was denied.
+    pub(crate) fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Print this diagnostic to standard error.
+    pub(crate) fn report(&self, format: MessageFormat) {
+        match format {
+            MessageFormat::Human => {
+                eprint!("{}", self.render(Style::new(ColorChoice::Auto.enabled())))
+            }
+            MessageFormat::Json => eprintln!("{}", self.to_json()),
+        }
+    }
+
+    /// Format this diagnostic for people, with an excerpt of the source if
+    /// the location is known and the file can still be read.
+    fn render(&self, style: Style) -> String {
+        let (title, code) = match (self.severity, self.error_code) {
+            (Severity::Error, Some(code)) => (style.error("Error"), code.to_string()),
+            (Severity::Error, None) => (style.error("Error"), self.code.to_owned()),
+            (Severity::Warning, _) => (style.warning("Warning"), self.code.to_owned()),
+        };
+
+        let mut out = format!("{}[{}]: {}\n", title, code, style.bold(&self.message));
+
+        // Labels are shown grouped by file, in the order the files first
+        // appear.
+        let mut files: Vec<&Path> = Vec::new();
+        for (file, _) in &self.labels {
+            if !files.contains(&file.as_path()) {
+                files.push(file);
+            }
+        }
+
+        for file in files {
+            let labels: Vec<Label> = self
+                .labels
+                .iter()
+                .filter(|(f, _)| f == file)
+                .map(|(_, l)| l.clone())
+                .collect();
+
+            match std::fs::read_to_string(file) {
+                Ok(source) => {
+                    let origin = file.display().to_string();
+                    out.push_str(&render(&origin, &source, &labels, style));
+                }
+                Err(_) => out.push_str(&location(style, file, Some(labels[0].start))),
+            }
+        }
+
+        if let (Some(file), true) = (&self.file, self.labels.is_empty()) {
+            out.push_str(&location(style, file, self.span.map(|s| s.start)));
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("  {} {}\n", style.note("="), note));
+        }
+
+        out.push_str(&self.trailer);
+        out
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let span = self.span.map(|s| {
+            json!({
+                "line_start": s.start.0,
+                "column_start": s.start.1,
+                "line_end": s.end.0,
+                "column_end": s.end.1,
+            })
+        });
+
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|(file, label)| {
+                json!({
+                    "file": file.display().to_string(),
+                    "line_start": label.start.0,
+                    "column_start": label.start.1,
+                    "line_end": label.end.0,
+                    "column_end": label.end.1,
+                    "message": label.message,
+                    "primary": label.primary,
+                })
+            })
+            .collect();
+
+        json!({
+            "file": self.file.as_ref().map(|f| f.display().to_string()),
+            "span": span,
+            "labels": labels,
+            "severity": self.severity.name(),
+            "code": self.code,
+            "error_code": self.error_code.map(|c| c.to_string()),
+            "message": self.message,
+            "rendered": self.render(Style::new(false)),
+        })
+    }
+
+    fn to_sarif(&self) -> serde_json::Value {
+        let mut result = json!({
+            "ruleId": self.code,
+            "level": self.severity.name(),
+            "message": { "text": self.message },
+        });
+
+        if let Some(ref file) = self.file {
+            let mut location = json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": uri(file) },
+                },
+            });
+
+            if let Some(span) = self.span {
+                location["physicalLocation"]["region"] = json!({
+                    "startLine": span.start.0,
+                    "startColumn": span.start.1,
+                    "endLine": span.end.0,
+                    "endColumn": span.end.1,
+                });
+            }
+
+            result["locations"] = json!([location]);
+        }
+
+        let related: Vec<_> = self
+            .labels
+            .iter()
+            .filter(|(_, label)| !label.primary)
+            .map(|(file, label)| {
+                json!({
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri(file) },
+                        "region": {
+                            "startLine": label.start.0,
+                            "startColumn": label.start.1,
+                            "endLine": label.end.0,
+                            "endColumn": label.end.1,
+                        },
+                    },
+                    "message": { "text": label.message },
+                })
+            })
+            .collect();
+
+        if !related.is_empty() {
+            result["relatedLocations"] = json!(related);
+        }
+
+        result
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        let (severity, mut notes) = match warning.level {
+            Level::Deny => (
+                Severity::Error,
+                vec![format!(
+                    "note: `{}` is denied by the lint configuration",
+                    warning.lint
+                )],
+            ),
+            _ => (Severity::Warning, Vec::new()),
+        };
+
+        if let Some(ref suggestion) = warning.suggestion {
+            notes.push(format!("help: replace it with `{}`", suggestion));
+        }
+
+        let mut labels = Vec::new();
+        if let (Some(file), Some(s)) = (&warning.file, warning.span) {
+            labels.push((file.clone(), Label::primary(s.start, s.end, "")));
+        }
+
+        Self {
+            severity,
+            code: warning.lint.name(),
+            error_code: None,
+            message: warning.message.clone(),
+            file: warning.file.clone(),
+            span: warning.span,
+            labels,
+            notes,
+            trailer: String::new(),
+        }
+    }
+}
+
+/// A `--> file:line:column` line, for when the source can't be shown.
+fn location(style: Style, file: &Path, at: Option<(usize, usize)>) -> String {
+    let at = match at {
+        Some((line, column)) => format!(":{}:{}", line, column),
+        None => String::new(),
+    };
+
+    format!("  {} {}{}\n", style.note("-->"), file.display(), at)
+}
+
+/// SARIF wants forward slashes, even on Windows.
+fn uri(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Build a SARIF 2.1.0 log containing `diagnostics`, for code scanning tools.
+pub(crate) fn sarif(diagnostics: &[Diagnostic]) -> serde_json::Value {
+    let lints = Lint::ALL.iter().map(|lint| {
+        json!({
+            "id": lint.name(),
+            "shortDescription": { "text": lint.description() },
+            "defaultConfiguration": { "level": "warning" },
+        })
+    });
+
+    let errors = EXPLANATIONS.iter().map(|e| {
+        json!({
+            "id": e.name,
+            "shortDescription": { "text": e.text },
+            "help": { "text": e.help },
+            "defaultConfiguration": { "level": "error" },
+            "properties": { "code": e.code.to_string() },
+        })
+    });
+
+    let rules: Vec<_> = lints.chain(errors).collect();
+
+    let results: Vec<_> = diagnostics.iter().map(Diagnostic::to_sarif).collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "eas",
+                    "informationUri": "https://quilt.github.io/etk/",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// An assembly error, and the file that was being assembled when it happened.
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) error: Error,
+    pub(crate) file: Option<PathBuf>,
+}
+
+impl Failure {
+    pub(crate) fn new(error: Error, file: Option<&Path>) -> Self {
+        Self {
+            error,
+            file: file.map(Path::to_owned),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> Kind {
+        classify(&self.error)
+    }
+
+    pub(crate) fn diagnostic(&self) -> Diagnostic {
+        let code = self.error.code();
+        let explanation = explain(code).unwrap();
+        let span = self.error.span();
+
+        let mut labels = Vec::new();
+        let mut notes = Vec::new();
+
+        match self.error {
+            Error::DuplicateLabel {
+                ref first,
+                ref second,
+                ..
+            } => {
+                labels.extend(site_label(second, true, "declared again here"));
+                labels.extend(site_label(first, false, "first declared here"));
+                notes.extend(chain_note(second, "declared again"));
+                notes.extend(chain_note(first, "first declared"));
+            }
+            Error::UndeclaredLabels {
+                labels: ref undeclared,
+                ..
+            } => {
+                for unresolved in undeclared {
+                    let message = format!("`{}` is never declared", unresolved.label);
+
+                    for site in &unresolved.sites {
+                        labels.extend(site_label(site, true, &message));
+                        notes.extend(chain_note(site, "used"));
+                    }
+
+                    if !unresolved.suggestions.is_empty() {
+                        let names: Vec<_> = unresolved
+                            .suggestions
+                            .iter()
+                            .map(|s| format!("`{}`", s))
+                            .collect();
+
+                        notes.push(format!(
+                            "help: did you mean {} instead of `{}`?",
+                            names.join(" or "),
+                            unresolved.label
+                        ));
+                    }
+                }
+            }
+            _ => match self.error.site() {
+                Some(site) => {
+                    labels.extend(site_label(site, true, ""));
+                    notes.extend(chain_note(site, "failed"));
+                }
+                None => {
+                    if let (Some(file), Some(s)) = (&self.file, span) {
+                        labels.push((file.clone(), Label::primary(s.start, s.end, "")));
+                    }
+                }
+            },
+        }
+
+        notes.extend(locals_note(&self.error));
+
+        notes.push(format!("help: {}", explanation.help));
+        notes.push(format!(
+            "for more information, run `eas --explain {}`",
+            code
+        ));
+
+        Diagnostic {
+            severity: Severity::Error,
+            code: explanation.name,
+            error_code: Some(code),
+            message: Chain(&self.error).to_string(),
+            file: self.file.clone(),
+            span,
+            labels,
+            notes,
+            trailer: backtrace(&self.error),
+        }
+    }
+}
+
+/// A note listing what's on the stack above a local that's out of reach, so
+/// it's clear which values to move out of the way.
+fn locals_note(err: &Error) -> Option<String> {
+    let mut parse = match err {
+        Error::Parse { source, .. } => source,
+        _ => return None,
+    };
+
+    while let ParseError::MacroExpansion { error, .. } = parse {
+        parse = error;
+    }
+
+    let (name, depth, above) = match parse {
+        ParseError::LocalOutOfReach {
+            name, depth, above, ..
+        } => (name, *depth, above),
+        _ => return None,
+    };
+
+    let mut values: Vec<_> = above.iter().map(|n| format!("`{}`", n)).collect();
+    match depth - above.len() {
+        0 => (),
+        1 if above.is_empty() => values.push("1 unnamed value".into()),
+        n if above.is_empty() => values.push(format!("{} unnamed values", n)),
+        1 => values.push("1 other value".into()),
+        n => values.push(format!("{} other values", n)),
+    }
+
+    let values = match values.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{}, and {}", rest.join(", "), last),
+        None => return None,
+    };
+
+    Some(format!("note: `{}` is below {}", name, values))
+}
+
+/// A label underlining `site`, if its location is known.
+fn site_label(site: &Site, primary: bool, message: &str) -> Option<(PathBuf, Label)> {
+    let span = site.span?;
+
+    let label = if primary {
+        Label::primary(span.start, span.end, message)
+    } else {
+        Label::secondary(span.start, span.end, message)
+    };
+
+    Some((site.file.clone(), label))
+}
+
+/// A note listing the aliases and directives that were expanded to get to
+/// `site`, innermost first, if there are any.
+fn chain_note(site: &Site, what: &str) -> Option<String> {
+    if site.expanded_from.is_empty() {
+        return None;
+    }
+
+    let mut note = format!("note: {} in `{}`", what, site.file.display());
+
+    for expansion in &site.expanded_from {
+        note.push_str(&format!(", expanded from {}", expansion));
+    }
+
+    Some(note)
+}
+
+/// The backtrace of `err`, if backtraces are enabled.
+fn backtrace(err: &Error) -> String {
+    match ErrorCompat::backtrace(err) {
+        // XXX: hack to determine if snafu's backtraces are enabled.
+        Some(bt) if std::mem::size_of::<Backtrace>() > 0 => format!("Backtrace:\n{}\n", bt),
+        _ => String::new(),
+    }
+}
+
+/// Displays an error and its sources on one line, separated by colons.
+struct Chain<'a>(&'a (dyn std::error::Error + 'static));
+
+impl fmt::Display for Chain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+
+        let mut current = self.0.source();
+        while let Some(e) = current {
+            // Lexer errors have an excerpt of the source code as their source.
+            if let Some(parse) = e.downcast_ref::<ParseError>() {
+                return write!(f, ": {}", parse.summary());
+            }
+
+            write!(f, ": {}", e)?;
+            current = e.source();
+        }
+
+        Ok(())
+    }
+}
+
+/// Sort `err` into a [`Kind`].
+fn classify(err: &Error) -> Kind {
+    match err {
+        Error::Parse { .. } => Kind::Parse,
+        Error::Assemble {
+            source: AsmError::ParseInclude { .. },
+            ..
+        } => Kind::Parse,
+        Error::Io { .. } => Kind::Io,
+        Error::InvalidHex { .. }
+        | Error::InvalidArtifact { .. }
+        | Error::Template { .. }
+        | Error::Script { .. } => Kind::Parse,
+        _ => Kind::Semantic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use etk_asm::ingest::{AssemblerContext, Ingest};
+    use etk_asm::lint::Levels;
+
+    use std::io::Write;
+
+    use super::*;
+
+    fn fail(src: &str) -> Failure {
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", src).unwrap_err();
+        Failure::new(err, ingest.failed_file())
+    }
+
+    #[test]
+    fn parse_error_json() {
+        let failure = fail("stop\n  pusj1 1\n");
+        assert_eq!(failure.kind(), Kind::Parse);
+
+        let json = failure.diagnostic().to_json();
+        assert_eq!(json["file"], "./main.etk");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["code"], "syntax");
+        assert_eq!(json["error_code"], "E0001");
+        assert_eq!(json["span"]["line_start"], 2);
+        assert_eq!(json["span"]["column_start"], 3);
+
+        let rendered = json["rendered"].as_str().unwrap();
+        assert!(rendered.starts_with("Error[E0001]: parsing failed: lexing failed: expected "));
+        assert!(rendered.ends_with("run `eas --explain E0001`\n"));
+    }
+
+    #[test]
+    fn semantic_error_json() {
+        let failure = fail("push1 nowhere");
+        assert_eq!(failure.kind(), Kind::Semantic);
+
+        let json = failure.diagnostic().to_json();
+        assert_eq!(json["code"], "undeclared-label");
+        assert_eq!(json["error_code"], "E0016");
+        assert_eq!(json["span"]["line_start"], 1);
+        assert_eq!(json["message"], "label `nowhere` was never defined");
+    }
+
+    #[test]
+    fn unhashable_code_json() {
+        let json = fail("a:\npush32 keccak(a, b)\nb:").diagnostic().to_json();
+        assert_eq!(json["code"], "unhashable-code");
+        assert_eq!(
+            json["message"],
+            "`keccak(a, b)` couldn't be evaluated: the code from 0x0 to 0x21 can't be hashed"
+        );
+        assert!(json["rendered"]
+            .as_str()
+            .unwrap()
+            .contains("help: move the push of the hash"));
+    }
+
+    #[test]
+    fn local_out_of_reach_note() {
+        let src = format!("%locals(a, b, c)\n{}dup c", "push1 0\n".repeat(15));
+        let notes = fail(&src).diagnostic().notes;
+        assert_eq!(notes[0], "note: `c` is below `a`, `b`, and 15 other values");
+
+        let src = format!("%locals(a)\n{}dup a", "push1 0\n".repeat(16));
+        let notes = fail(&src).diagnostic().notes;
+        assert_eq!(notes[0], "note: `a` is below 16 unnamed values");
+    }
+
+    #[test]
+    fn render_undeclared_labels() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "start:\npush1 strat\npush1 end\n").unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest_file(file.path()).unwrap_err();
+        let failure = Failure::new(err, ingest.failed_file());
+
+        let rendered = failure.diagnostic().render(Style::new(false));
+        let expected = format!(
+            "Error[E0016]: 2 labels were never defined: `end`, `strat`
+  --> {}:2:1
+  |
+2 | push1 strat
+  | ^^^^^^^^^^^ `strat` is never declared
+3 | push1 end
+  | ^^^^^^^^^ `end` is never declared
+  |
+  = help: did you mean `start` instead of `strat`?
+  = help: ",
+            file.path().display()
+        );
+
+        assert!(rendered.starts_with(&expected), "{}", rendered);
+    }
+
+    #[test]
+    fn render_excerpt() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "stop\n  pusj1 1\n").unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest_file(file.path()).unwrap_err();
+        let failure = Failure::new(err, ingest.failed_file());
+
+        let rendered = failure.diagnostic().render(Style::new(false));
+        let excerpt = format!(
+            "  --> {}:2:3\n  |\n2 |   pusj1 1\n  |   ^\n  |\n  = help: ",
+            file.path().display()
+        );
+
+        assert!(rendered.contains(&excerpt), "{}", rendered);
+    }
+
+    #[test]
+    fn render_duplicate_label() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.etk");
+        let lib = dir.path().join("lib.etk");
+
+        std::fs::write(&main, "a:\njumpdest\n%import(\"lib.etk\")\n").unwrap();
+        std::fs::write(&lib, "stop\n  a:\n").unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest_file(&main).unwrap_err();
+        let failure = Failure::new(err, ingest.failed_file());
+
+        let rendered = failure.diagnostic().render(Style::new(false));
+        let expected = format!(
+            "Error[E0013]: label `a` declared multiple times
+  --> {lib}:2:3
+  |
+2 |   a:
+  |   ^ declared again here
+  |
+  --> {main}:1:1
+  |
+1 | a:
+  | - first declared here
+  |
+  = note: declared again in `{lib}`, expanded from `%import` at {main}:3:1
+  = help: ",
+            lib = lib.display(),
+            main = main.display(),
+        );
+
+        assert!(rendered.starts_with(&expected), "{}", rendered);
+
+        let json = failure.diagnostic().to_json();
+        assert_eq!(json["labels"][1]["message"], "first declared here");
+        assert_eq!(json["labels"][1]["primary"], false);
+
+        let sarif = failure.diagnostic().to_sarif();
+        assert_eq!(
+            sarif["relatedLocations"][0]["physicalLocation"]["region"]["startLine"],
+            1
+        );
+    }
+
+    #[test]
+    fn render_expansion() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "%alias(exit, \"push2 done; jump\")\nexit\n").unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest_file(file.path()).unwrap_err();
+        let failure = Failure::new(err, ingest.failed_file());
+
+        let rendered = failure.diagnostic().render(Style::new(false));
+        let note = format!(
+            "  = note: used in `{0}`, expanded from `exit` at {0}:2:1\n",
+            file.path().display()
+        );
+
+        assert!(rendered.contains(&note), "{}", rendered);
+        assert!(rendered.contains("\n1 | %alias(exit"), "{}", rendered);
+    }
+
+    #[test]
+    fn denied_warning() {
+        let mut levels = Levels::new();
+        levels.set(Lint::DeprecatedInstruction, Level::Deny);
+
+        let mut ingest = AssemblerContext::new().lints(levels).ingest(Vec::new());
+        ingest.ingest("./main.etk", "callcode").unwrap();
+
+        let diagnostics: Vec<Diagnostic> = ingest.warnings().iter().map(Into::into).collect();
+        assert!(diagnostics[0].is_error());
+        assert!(!diagnostics[1].is_error());
+
+        let rendered = diagnostics[0].render(Style::new(false));
+        assert!(
+            rendered.starts_with("Error[deprecated-instruction]: `callcode` is deprecated"),
+            "{}",
+            rendered
+        );
+        assert!(rendered
+            .ends_with("= note: `deprecated-instruction` is denied by the lint configuration\n"));
+    }
+
+    #[test]
+    fn exit_codes_differ() {
+        assert_ne!(Kind::Parse.exit_code(), Kind::Semantic.exit_code());
+        assert_ne!(Kind::Parse.exit_code(), 0);
+        assert_ne!(Kind::Semantic.exit_code(), 0);
+    }
+
+    #[test]
+    fn sarif_log() {
+        let failure = fail("stop\n  pusj1 1\n");
+
+        let mut ingest = Ingest::new(Vec::new());
+        ingest.ingest("./main.etk", "callcode").unwrap();
+
+        let mut diagnostics: Vec<Diagnostic> = ingest.warnings().iter().map(Into::into).collect();
+        diagnostics.push(failure.diagnostic());
+
+        let log = sarif(&diagnostics);
+        assert_eq!(log["version"], "2.1.0");
+
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0]["ruleId"], "deprecated-instruction");
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "./main.etk"
+        );
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            1
+        );
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"]["startColumn"],
+            1
+        );
+
+        let error = &results[2];
+        assert_eq!(error["level"], "error");
+        assert_eq!(
+            error["locations"][0]["physicalLocation"]["region"]["startLine"],
+            2
+        );
+    }
+}
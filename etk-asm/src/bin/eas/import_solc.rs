@@ -0,0 +1,117 @@
+use etk_asm::ingest::Ingest;
+use etk_asm::solc::{self, Module};
+
+use etk_cli::io::is_stdio;
+
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use tracing::info;
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("could not read `{}`", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("could not translate `{}`", path.display()))]
+    Translate { path: PathBuf, source: solc::Error },
+
+    #[snafu(display("`{}` already exists", path.display()))]
+    Exists { path: PathBuf, backtrace: Backtrace },
+
+    #[snafu(display("could not write `{}`", path.display()))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("the translated code failed to assemble"))]
+    Assemble { source: etk_asm::ingest::Error },
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opts {
+    #[structopt(
+        parse(from_os_str),
+        help = "path to the output of `solc --asm` for one contract (`-` for standard input)"
+    )]
+    input: PathBuf,
+
+    #[structopt(
+        parse(from_os_str),
+        default_value = ".",
+        help = "directory to write the etk sources to"
+    )]
+    out: PathBuf,
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    let mut text = String::new();
+
+    if is_stdio(path) {
+        std::io::stdin().read_to_string(&mut text)
+    } else {
+        std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut text))
+    }
+    .context(Read { path })?;
+
+    Ok(text)
+}
+
+pub(crate) fn run(opts: Opts) -> Result<(), Error> {
+    let text = read(&opts.input)?;
+    let modules = solc::translate(&text).context(Translate { path: &opts.input })?;
+
+    // Check everything first, so nothing is left half written.
+    for Module { path, .. } in &modules {
+        let path = opts.out.join(path);
+        ensure!(!path.exists(), Exists { path });
+    }
+
+    std::fs::create_dir_all(&opts.out).context(Write { path: &opts.out })?;
+
+    for module in &modules {
+        let path = opts.out.join(&module.path);
+        std::fs::write(&path, &module.contents).context(Write { path: &path })?;
+        info!("created {}", module.path.display());
+    }
+
+    Ingest::new(std::io::sink())
+        .ingest_file(opts.out.join(&modules[0].path))
+        .context(Assemble)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("c.asm");
+        std::fs::write(&input, "EVM assembly:\n  0x01\n  stop\n").unwrap();
+
+        let opts = || Opts {
+            input: input.clone(),
+            out: dir.path().join("out"),
+        };
+
+        run(opts()).unwrap();
+
+        let main = std::fs::read_to_string(dir.path().join("out").join("main.etk")).unwrap();
+        assert!(main.ends_with("push1 0x01\nstop\n"));
+
+        assert_matches!(run(opts()), Err(Error::Exists { .. }));
+    }
+}
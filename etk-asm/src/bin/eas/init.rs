@@ -0,0 +1,162 @@
+use crate::manifest;
+
+use etk_asm::ingest::Ingest;
+
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use tracing::info;
+
+const MANIFEST: &str = r#"[[target]]
+name = "main"
+entry = "src/main.etk"
+"#;
+
+const GITIGNORE: &str = "/out/\n";
+
+const CONSTRUCTOR: &str = r#"# Constructor: copies the runtime code (everything after `start`) into memory
+# and returns it, so it becomes the code of the deployed contract.
+
+# -- Instructions --            -- Current stack layout --
+
+pc                              # [0]
+pc                              # [1, 0]
+push1 start                     # [start, 1, 0]
+add                             # [start+1, 0]
+dup1                            # [start+1, start+1, 0]
+codesize                        # [codesize, start+1, start+1, 0]
+sub                             # [length, start+1, 0]
+swap2                           # [0, start+1, length]
+swap1                           # [start+1, 0, length]
+dup3                            # [length, start+1, 0, length]
+swap1                           # [start+1, length, 0, length]
+dup3                            # [0, start+1, length, 0, length]
+codecopy                        # [0, length]
+return                          # []
+
+start:
+%include("runtime.etk")
+"#;
+
+const RUNTIME: &str = r#"# Runtime: the code that runs when the deployed contract is called.
+#
+# Labels here are independent of the constructor, so jumps work as expected.
+
+# Return the 32-byte word 42.
+push1 42
+push1 0
+mstore
+push1 32
+push1 0
+return
+"#;
+
+const EXPECTED: &str = "tests/expected/main.hex";
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("`{}` already exists", path.display()))]
+    Exists { path: PathBuf, backtrace: Backtrace },
+
+    #[snafu(display("could not write `{}`", path.display()))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("the new project failed to assemble"))]
+    Assemble { source: etk_asm::ingest::Error },
+}
+
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opts {
+    #[structopt(
+        parse(from_os_str),
+        default_value = ".",
+        help = "directory to create the project in"
+    )]
+    path: PathBuf,
+}
+
+fn write(dir: &Path, file: &str, contents: &str) -> Result<(), Error> {
+    let path = dir.join(file);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context(Write { path: parent })?;
+    }
+
+    std::fs::write(&path, contents).context(Write { path: &path })?;
+    info!("created {}", file);
+
+    Ok(())
+}
+
+pub(crate) fn run(opts: Opts) -> Result<(), Error> {
+    let dir = opts.path;
+
+    let manifest = dir.join(manifest::FILE_NAME);
+    ensure!(!manifest.exists(), Exists { path: manifest });
+
+    write(&dir, manifest::FILE_NAME, MANIFEST)?;
+    write(&dir, ".gitignore", GITIGNORE)?;
+    write(&dir, "src/main.etk", CONSTRUCTOR)?;
+    write(&dir, "src/runtime.etk", RUNTIME)?;
+
+    // Record what the skeleton assembles to, so changes to the output are
+    // easy to spot with `diff out/main.hex tests/expected/main.hex`.
+    let mut code = Vec::new();
+    Ingest::new(&mut code)
+        .ingest_file(dir.join("src/main.etk"))
+        .context(Assemble)?;
+    write(&dir, EXPECTED, &format!("{}\n", hex::encode(code)))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn init_then_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("project");
+
+        run(Opts { path: path.clone() }).unwrap();
+
+        let manifest = manifest::Manifest::read(&path.join(manifest::FILE_NAME)).unwrap();
+        assert_eq!(manifest.targets.len(), 1);
+
+        let mut code = Vec::new();
+        Ingest::new(&mut code)
+            .ingest_file(&manifest.targets[0].entry)
+            .unwrap();
+
+        let expected = std::fs::read_to_string(path.join(EXPECTED)).unwrap();
+        assert_eq!(expected.trim(), hex::encode(&code));
+
+        // The runtime should follow the 15 byte constructor.
+        assert_eq!(
+            code[15..],
+            [0x60, 42, 0x60, 0, 0x52, 0x60, 32, 0x60, 0, 0xf3]
+        );
+    }
+
+    #[test]
+    fn init_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(manifest::FILE_NAME), "").unwrap();
+
+        let err = run(Opts {
+            path: dir.path().to_owned(),
+        })
+        .unwrap_err();
+
+        assert_matches!(err, Error::Exists { .. });
+    }
+}
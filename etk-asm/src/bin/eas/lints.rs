@@ -0,0 +1,379 @@
+//! Choosing which warnings are allowed, reported, or denied.
+
+use crate::manifest::{self, Manifest};
+
+use etk_asm::lint::{Level, Levels, Lint, UnknownLevelError, UnknownLintError};
+
+use snafu::{ResultExt, Snafu};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use structopt::clap::ArgMatches;
+use structopt::StructOpt;
+
+/// The file `eas` looks for lint levels in, before falling back to the
+/// `[lints]` table of an `etk.toml`.
+pub(crate) const FILE_NAME: &str = "etk-lints.toml";
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("could not read `{}`", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("could not parse `{}`", path.display()))]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[snafu(display("`{}` names a lint that doesn't exist", path.display()))]
+    UnknownLint {
+        path: PathBuf,
+        source: UnknownLintError,
+    },
+
+    #[snafu(display("`{}` gives lint `{}` an invalid level", path.display(), name))]
+    UnknownLevel {
+        path: PathBuf,
+        name: Name,
+        source: UnknownLevelError,
+    },
+
+    #[snafu(context(false))]
+    Manifest { source: manifest::Error },
+}
+
+/// A lint, or `warnings` for every lint at once, like rustc's `-D warnings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Name {
+    Lint(Lint),
+    Warnings,
+}
+
+impl Name {
+    /// Treat the lints with this name according to `level`.
+    fn set(self, levels: &mut Levels, level: Level) {
+        match self {
+            Name::Lint(lint) => {
+                levels.set(lint, level);
+            }
+            Name::Warnings => {
+                for lint in Lint::ALL.iter() {
+                    levels.set(*lint, level);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Name::Lint(lint) => write!(f, "{}", lint),
+            Name::Warnings => write!(f, "warnings"),
+        }
+    }
+}
+
+impl FromStr for Name {
+    type Err = UnknownLintError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "warnings" => Ok(Name::Warnings),
+            _ => text.parse().map(Name::Lint),
+        }
+    }
+}
+
+/// Convert a table of lint names to level names, read from `path`.
+///
+/// A level for `warnings` applies to every lint that isn't listed by name.
+pub(crate) fn levels(path: &Path, table: &BTreeMap<String, String>) -> Result<Levels, Error> {
+    let mut named = Vec::with_capacity(table.len());
+
+    for (name, level) in table {
+        let name: Name = name.parse().context(UnknownLint { path })?;
+        let level = level.parse().context(UnknownLevel { path, name })?;
+        named.push((name, level));
+    }
+
+    // Set the group first, so the lints listed by name take precedence.
+    named.sort_by_key(|(name, _)| *name != Name::Warnings);
+
+    let mut levels = Levels::new();
+    for (name, level) in named {
+        name.set(&mut levels, level);
+    }
+
+    Ok(levels)
+}
+
+/// Read an `etk-lints.toml`.
+pub(crate) fn read(path: &Path) -> Result<Levels, Error> {
+    let text = std::fs::read_to_string(path).context(Read { path })?;
+    let table = toml::from_str(&text).context(Parse { path })?;
+    levels(path, &table)
+}
+
+/// The lint levels for sources in `dir`, from the closest `etk-lints.toml` or
+/// `etk.toml` in it or any of its parents.
+///
+/// When a directory has both, `etk-lints.toml` is used.
+pub(crate) fn find(dir: &Path) -> Result<Levels, Error> {
+    for dir in dir.ancestors() {
+        let path = dir.join(FILE_NAME);
+        if path.is_file() {
+            return read(&path);
+        }
+
+        let path = dir.join(manifest::FILE_NAME);
+        if path.is_file() {
+            return Ok(Manifest::read(&path)?.lints);
+        }
+    }
+
+    Ok(Levels::new())
+}
+
+/// A level given on the command line, like `--lint unused-label=allow` or
+/// `-D warnings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Override {
+    name: Name,
+    level: Level,
+}
+
+impl FromStr for Override {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let eq = text
+            .find('=')
+            .ok_or_else(|| format!("expected `NAME=LEVEL`, got `{}`", text))?;
+
+        Ok(Override {
+            name: text[..eq]
+                .parse()
+                .map_err(|e: UnknownLintError| e.to_string())?,
+            level: text[eq + 1..]
+                .parse()
+                .map_err(|e: UnknownLevelError| e.to_string())?,
+        })
+    }
+}
+
+/// Apply `overrides`, in order, on top of `levels`.
+pub(crate) fn with_overrides(mut levels: Levels, overrides: &[Override]) -> Levels {
+    for o in overrides {
+        o.name.set(&mut levels, o.level);
+    }
+
+    levels
+}
+
+/// The options for choosing lint levels on the command line.
+#[derive(Debug, Default, StructOpt)]
+pub(crate) struct Flags {
+    #[structopt(
+        long = "allow",
+        short = "A",
+        value_name = "LINT",
+        number_of_values = 1,
+        help = "allow a lint, or every lint with `warnings` (may be repeated)"
+    )]
+    allow: Vec<Name>,
+
+    #[structopt(
+        long = "warn",
+        short = "W",
+        value_name = "LINT",
+        number_of_values = 1,
+        help = "warn about a lint, or every lint with `warnings` (may be repeated)"
+    )]
+    warn: Vec<Name>,
+
+    #[structopt(
+        long = "deny",
+        short = "D",
+        value_name = "LINT",
+        number_of_values = 1,
+        help = "deny a lint, or every lint with `warnings` (may be repeated)"
+    )]
+    deny: Vec<Name>,
+
+    #[structopt(
+        long = "lint",
+        value_name = "NAME=LEVEL",
+        number_of_values = 1,
+        help = "allow, warn, or deny a lint, overriding etk-lints.toml and etk.toml (may be repeated)"
+    )]
+    lint: Vec<Override>,
+}
+
+impl Flags {
+    /// Flags giving each level in `overrides`, in order.
+    pub(crate) fn new(overrides: Vec<Override>) -> Self {
+        Self {
+            lint: overrides,
+            ..Default::default()
+        }
+    }
+
+    /// Put the levels in the order they were given on the command line,
+    /// according to `matches`, so later flags take precedence over earlier
+    /// ones, like `-D warnings -A unused-label`.
+    pub(crate) fn ordered(self, matches: &ArgMatches) -> Self {
+        let indices = |arg| matches.indices_of(arg).into_iter().flatten();
+
+        let with =
+            |names: Vec<Name>, level| names.into_iter().map(move |name| Override { name, level });
+
+        let mut given: Vec<_> = indices("allow")
+            .zip(with(self.allow, Level::Allow))
+            .chain(indices("warn").zip(with(self.warn, Level::Warn)))
+            .chain(indices("deny").zip(with(self.deny, Level::Deny)))
+            .chain(indices("lint").zip(self.lint))
+            .collect();
+
+        given.sort_by_key(|(index, _)| *index);
+
+        Self::new(given.into_iter().map(|(_, o)| o).collect())
+    }
+
+    /// Every level given by these flags, with later ones taking precedence.
+    pub(crate) fn overrides(&self) -> Vec<Override> {
+        let with = |names: &[Name], level| {
+            names
+                .iter()
+                .map(move |name| Override { name: *name, level })
+                .collect::<Vec<_>>()
+        };
+
+        let mut overrides = with(&self.allow, Level::Allow);
+        overrides.extend(with(&self.warn, Level::Warn));
+        overrides.extend(with(&self.deny, Level::Deny));
+        overrides.extend_from_slice(&self.lint);
+        overrides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn read_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        std::fs::write(&path, "unused-label = \"allow\"\ncode-size = \"deny\"\n").unwrap();
+
+        let levels = read(&path).unwrap();
+        assert_eq!(levels.get(Lint::UnusedLabel), Level::Allow);
+        assert_eq!(levels.get(Lint::CodeSize), Level::Deny);
+        assert_eq!(levels.get(Lint::StackUnderflow), Level::Warn);
+    }
+
+    #[test]
+    fn read_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+
+        std::fs::write(&path, "unused_label = \"allow\"\n").unwrap();
+        assert_matches!(read(&path), Err(Error::UnknownLint { .. }));
+
+        std::fs::write(&path, "unused-label = \"forbid\"\n").unwrap();
+        assert_matches!(
+            read(&path),
+            Err(Error::UnknownLevel {
+                name: Name::Lint(Lint::UnusedLabel),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn read_warnings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        std::fs::write(&path, "unused-label = \"allow\"\nwarnings = \"deny\"\n").unwrap();
+
+        let levels = read(&path).unwrap();
+        assert_eq!(levels.get(Lint::UnusedLabel), Level::Allow);
+        assert_eq!(levels.get(Lint::CodeSize), Level::Deny);
+        assert_eq!(levels.get(Lint::MemoryLayout), Level::Deny);
+    }
+
+    #[test]
+    fn find_closest() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+
+        assert_eq!(find(&src).unwrap(), Levels::new());
+
+        std::fs::write(
+            dir.path().join(manifest::FILE_NAME),
+            "[lints]\nunused-label = \"deny\"\n",
+        )
+        .unwrap();
+        assert_eq!(find(&src).unwrap().get(Lint::UnusedLabel), Level::Deny);
+
+        std::fs::write(dir.path().join(FILE_NAME), "unused-label = \"allow\"\n").unwrap();
+        assert_eq!(find(&src).unwrap().get(Lint::UnusedLabel), Level::Allow);
+    }
+
+    #[test]
+    fn overrides() {
+        let overrides: Vec<Override> = vec![
+            "code-size=deny".parse().unwrap(),
+            "unused-label=warn".parse().unwrap(),
+        ];
+
+        let mut levels = Levels::new();
+        levels.set(Lint::UnusedLabel, Level::Allow);
+
+        let levels = with_overrides(levels, &overrides);
+        assert_eq!(levels.get(Lint::CodeSize), Level::Deny);
+        assert_eq!(levels.get(Lint::UnusedLabel), Level::Warn);
+
+        assert!("code-size".parse::<Override>().is_err());
+        assert!("code-size=forbid".parse::<Override>().is_err());
+    }
+
+    #[test]
+    fn flags_in_order() {
+        let parse = |args: &[&str]| {
+            let matches = Flags::clap().get_matches_from(args);
+            let flags = Flags::from_clap(&matches).ordered(&matches);
+            with_overrides(Levels::new(), &flags.overrides())
+        };
+
+        let levels = parse(&["eas", "-D", "warnings", "-A", "unused-label"]);
+        assert_eq!(levels.get(Lint::UnusedLabel), Level::Allow);
+        assert_eq!(levels.get(Lint::CodeSize), Level::Deny);
+
+        let levels = parse(&["eas", "-A", "unused-label", "--deny", "warnings"]);
+        assert_eq!(levels.get(Lint::UnusedLabel), Level::Deny);
+
+        let levels = parse(&[
+            "eas",
+            "--lint",
+            "code-size=allow",
+            "-W",
+            "code-size",
+            "-A",
+            "stack-underflow",
+        ]);
+        assert_eq!(levels.get(Lint::CodeSize), Level::Warn);
+        assert_eq!(levels.get(Lint::StackUnderflow), Level::Allow);
+    }
+}
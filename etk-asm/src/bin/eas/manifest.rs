@@ -0,0 +1,587 @@
+//! The `etk.toml` project manifest.
+
+use crate::lints;
+
+use etk_asm::lint::Levels;
+use etk_asm::ops::{Fork, Profile, UnknownForkError, UnknownProfileError};
+
+use serde::Deserialize;
+
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// The file name `eas build` looks for.
+pub(crate) const FILE_NAME: &str = "etk.toml";
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("could not read `{}`", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("could not parse `{}`", path.display()))]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[snafu(display("target `{}` is declared more than once", name))]
+    DuplicateTarget { name: String, backtrace: Backtrace },
+
+    #[snafu(display("target `{}` has an invalid fork", target))]
+    InvalidFork {
+        target: String,
+        source: UnknownForkError,
+    },
+
+    #[snafu(display("target `{}` has an invalid profile", target))]
+    InvalidProfile {
+        target: String,
+        source: UnknownProfileError,
+    },
+
+    #[snafu(display(
+        "define `{}` in target `{}` must be an integer or a `0x` prefixed hex string",
+        name,
+        target
+    ))]
+    InvalidDefine {
+        target: String,
+        name: String,
+        backtrace: Backtrace,
+    },
+
+    #[snafu(display("invalid lint levels"))]
+    Lints {
+        #[snafu(source(from(lints::Error, Box::new)))]
+        source: Box<lints::Error>,
+    },
+}
+
+/// How assembled code is written to a target's output file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    #[default]
+    Hex,
+    Binary,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Hex => "hex",
+            Format::Binary => "bin",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Define {
+    Integer(u64),
+    Text(String),
+}
+
+impl Define {
+    fn to_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Define::Integer(n) => Some(n.to_be_bytes().to_vec()),
+            Define::Text(text) => {
+                let digits = text.strip_prefix("0x")?;
+                if digits.len() % 2 == 0 {
+                    hex::decode(digits).ok()
+                } else {
+                    hex::decode(format!("0{}", digits)).ok()
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTarget {
+    name: String,
+    entry: PathBuf,
+    output: Option<PathBuf>,
+    #[serde(default)]
+    format: Format,
+    fork: Option<String>,
+    profile: Option<String>,
+    optimize: Option<bool>,
+    #[serde(default)]
+    defines: BTreeMap<String, Define>,
+    #[serde(default)]
+    variants: BTreeMap<String, RawVariant>,
+}
+
+/// A variant of a target, like one for each fork the code has to run on.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawVariant {
+    output: Option<PathBuf>,
+    fork: Option<String>,
+    profile: Option<String>,
+    optimize: Option<bool>,
+    #[serde(default)]
+    defines: BTreeMap<String, Define>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    #[serde(default, rename = "target")]
+    targets: Vec<RawTarget>,
+    #[serde(default)]
+    lints: BTreeMap<String, String>,
+}
+
+/// A single contract to assemble.
+#[derive(Debug)]
+pub(crate) struct Target {
+    pub(crate) name: String,
+
+    /// Path to the root assembly file.
+    pub(crate) entry: PathBuf,
+
+    /// Path the assembled code is written to.
+    pub(crate) output: PathBuf,
+
+    pub(crate) format: Format,
+    pub(crate) fork: Fork,
+
+    /// The chain the code is deployed to.
+    pub(crate) profile: Profile,
+
+    /// Whether pushes of constants are narrowed to the smallest width.
+    pub(crate) optimize: bool,
+
+    pub(crate) defines: BTreeMap<String, Vec<u8>>,
+
+    /// The name of the target this is a variant of, if it is one.
+    pub(crate) variant_of: Option<String>,
+}
+
+impl Target {
+    /// Whether `name` selects this target, either by its own name or by the
+    /// name of the target it's a variant of.
+    pub(crate) fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.variant_of.as_deref() == Some(name)
+    }
+}
+
+/// A parsed `etk.toml`, with paths resolved relative to its directory.
+#[derive(Debug)]
+pub(crate) struct Manifest {
+    /// The directory holding `etk.toml`, which paths in it are relative to.
+    pub(crate) dir: PathBuf,
+
+    pub(crate) targets: Vec<Target>,
+
+    /// Lint levels from the `[lints]` table, or from an `etk-lints.toml` next
+    /// to the manifest, if there is one.
+    pub(crate) lints: Levels,
+}
+
+impl Manifest {
+    /// Search `dir` and its ancestors for an `etk.toml`.
+    pub(crate) fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|d| d.join(FILE_NAME))
+            .find(|p| p.is_file())
+    }
+
+    pub(crate) fn read(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).context(Read { path })?;
+        let dir = match path.parent() {
+            Some(p) => p,
+            None => Path::new("."),
+        };
+
+        let mut manifest = Self::parse(path, dir, &text)?;
+
+        let lints = dir.join(lints::FILE_NAME);
+        if lints.is_file() {
+            manifest.lints = lints::read(&lints).context(Lints)?;
+        }
+
+        Ok(manifest)
+    }
+
+    fn parse(path: &Path, dir: &Path, text: &str) -> Result<Self, Error> {
+        let raw: RawManifest = toml::from_str(text).context(Parse { path })?;
+
+        let mut names = HashSet::new();
+        let mut targets = Vec::with_capacity(raw.targets.len());
+
+        for mut target in raw.targets {
+            ensure!(
+                names.insert(target.name.clone()),
+                DuplicateTarget { name: target.name }
+            );
+
+            let variants = std::mem::take(&mut target.variants);
+            if variants.is_empty() {
+                targets.push(Self::target(dir, &target, None, RawVariant::default())?);
+                continue;
+            }
+
+            for (suffix, variant) in variants {
+                let variant = Self::target(dir, &target, Some(&suffix), variant)?;
+                ensure!(
+                    names.insert(variant.name.clone()),
+                    DuplicateTarget { name: variant.name }
+                );
+                targets.push(variant);
+            }
+        }
+
+        let lints = lints::levels(path, &raw.lints).context(Lints)?;
+
+        Ok(Manifest {
+            dir: dir.to_owned(),
+            targets,
+            lints,
+        })
+    }
+
+    /// Resolve `target`, or its variant named `suffix` with the settings in
+    /// `variant` taking precedence over the target's.
+    fn target(
+        dir: &Path,
+        target: &RawTarget,
+        suffix: Option<&str>,
+        variant: RawVariant,
+    ) -> Result<Target, Error> {
+        let name = match suffix {
+            Some(s) => format!("{}-{}", target.name, s),
+            None => target.name.clone(),
+        };
+
+        let fork = match variant.fork.as_ref().or(target.fork.as_ref()) {
+            Some(f) => f.parse().context(InvalidFork { target: &name })?,
+            None => Fork::latest(),
+        };
+
+        let profile = match variant.profile.as_ref().or(target.profile.as_ref()) {
+            Some(p) => p.parse().context(InvalidProfile { target: &name })?,
+            None => Profile::default(),
+        };
+
+        let optimize = variant.optimize.or(target.optimize).unwrap_or(false);
+
+        let mut defines = BTreeMap::new();
+        for (define, value) in target.defines.iter().chain(&variant.defines) {
+            let bytes = value.to_bytes().context(InvalidDefine {
+                target: &name,
+                name: define,
+            })?;
+            defines.insert(define.clone(), bytes);
+        }
+
+        let output = match (variant.output, &target.output, suffix) {
+            (Some(o), _, _) => dir.join(o),
+            (None, Some(o), None) => dir.join(o),
+            (None, Some(o), Some(s)) => dir.join(suffixed(o, s)),
+            (None, None, _) => {
+                let file = format!("{}.{}", name, target.format.extension());
+                dir.join("out").join(file)
+            }
+        };
+
+        Ok(Target {
+            entry: dir.join(&target.entry),
+            output,
+            format: target.format,
+            fork,
+            profile,
+            optimize,
+            defines,
+            variant_of: suffix.map(|_| target.name.clone()),
+            name,
+        })
+    }
+}
+
+/// `path` with `-suffix` added to the end of its file stem, like
+/// `build/token-london.bin` for `build/token.bin`.
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file = format!("{}-{}", stem, suffix);
+
+    if let Some(extension) = path.extension() {
+        file.push('.');
+        file.push_str(&extension.to_string_lossy());
+    }
+
+    path.with_file_name(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use etk_asm::lint::{Level, Lint};
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<Manifest, Error> {
+        Manifest::parse(Path::new("etk.toml"), Path::new("proj"), text)
+    }
+
+    #[test]
+    fn defaults() {
+        let manifest = parse(
+            r#"
+                [[target]]
+                name = "token"
+                entry = "src/token.etk"
+            "#,
+        )
+        .unwrap();
+
+        let target = &manifest.targets[0];
+        assert_eq!(target.entry, Path::new("proj/src/token.etk"));
+        assert_eq!(target.output, Path::new("proj/out/token.hex"));
+        assert_eq!(target.format, Format::Hex);
+        assert_eq!(target.fork, Fork::latest());
+        assert!(!target.optimize);
+        assert!(target.defines.is_empty());
+    }
+
+    #[test]
+    fn all_fields() {
+        let manifest = parse(
+            r#"
+                [[target]]
+                name = "token"
+                entry = "src/token.etk"
+                output = "build/token.bin"
+                format = "binary"
+                fork = "istanbul"
+                profile = "scroll"
+                optimize = true
+
+                [target.defines]
+                FEE = 1000
+                OWNER = "0xabc"
+            "#,
+        )
+        .unwrap();
+
+        let target = &manifest.targets[0];
+        assert_eq!(target.output, Path::new("proj/build/token.bin"));
+        assert_eq!(target.format, Format::Binary);
+        assert_eq!(target.fork, Fork::Istanbul);
+        assert_eq!(target.profile, Profile::Scroll);
+        assert!(target.optimize);
+        assert_eq!(target.defines["FEE"], 1000u64.to_be_bytes());
+        assert_eq!(target.defines["OWNER"], [0x0a, 0xbc]);
+    }
+
+    #[test]
+    fn variants() {
+        let manifest = parse(
+            r#"
+                [[target]]
+                name = "token"
+                entry = "src/token.etk"
+                fork = "berlin"
+                optimize = true
+                defines = { FEE = 1, BASEFEE = 0 }
+
+                [target.variants.berlin]
+
+                [target.variants.london]
+                fork = "london"
+                optimize = false
+                defines = { BASEFEE = 1 }
+
+                [[target]]
+                name = "pool"
+                entry = "src/pool.etk"
+                output = "build/pool.bin"
+                format = "binary"
+
+                [target.variants.old]
+                fork = "istanbul"
+
+                [target.variants.new]
+                output = "build/new/pool.bin"
+            "#,
+        )
+        .unwrap();
+
+        let names: Vec<_> = manifest.targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["token-berlin", "token-london", "pool-new", "pool-old"]
+        );
+
+        let berlin = &manifest.targets[0];
+        assert_eq!(berlin.output, Path::new("proj/out/token-berlin.hex"));
+        assert_eq!(berlin.fork, Fork::Berlin);
+        assert!(berlin.optimize);
+        assert_eq!(berlin.defines["FEE"], 1u64.to_be_bytes());
+        assert_eq!(berlin.defines["BASEFEE"], 0u64.to_be_bytes());
+        assert!(berlin.is_named("token"));
+        assert!(berlin.is_named("token-berlin"));
+        assert!(!berlin.is_named("token-london"));
+
+        let london = &manifest.targets[1];
+        assert_eq!(london.fork, Fork::London);
+        assert!(!london.optimize);
+        assert_eq!(london.defines["FEE"], 1u64.to_be_bytes());
+        assert_eq!(london.defines["BASEFEE"], 1u64.to_be_bytes());
+
+        let new = &manifest.targets[2];
+        assert_eq!(new.output, Path::new("proj/build/new/pool.bin"));
+        assert_eq!(new.fork, Fork::latest());
+
+        let old = &manifest.targets[3];
+        assert_eq!(old.output, Path::new("proj/build/pool-old.bin"));
+        assert_eq!(old.format, Format::Binary);
+        assert_eq!(old.fork, Fork::Istanbul);
+    }
+
+    #[test]
+    fn variant_errors() {
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+
+                [target.variants.b]
+                fork = "nope"
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::InvalidFork { target, .. } if target == "a-b");
+
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a-b"
+                entry = "a.etk"
+
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+
+                [target.variants.b]
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::DuplicateTarget { name, .. } if name == "a-b");
+    }
+
+    #[test]
+    fn duplicate_target() {
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+
+                [[target]]
+                name = "a"
+                entry = "b.etk"
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::DuplicateTarget { name, .. } if name == "a");
+    }
+
+    #[test]
+    fn invalid_fork() {
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+                fork = "nope"
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::InvalidFork { target, .. } if target == "a");
+    }
+
+    #[test]
+    fn invalid_profile() {
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+                profile = "nope"
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::InvalidProfile { target, .. } if target == "a");
+    }
+
+    #[test]
+    fn invalid_define() {
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+                defines = { FEE = "1000" }
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::InvalidDefine { name, .. } if name == "FEE");
+    }
+
+    #[test]
+    fn lints() {
+        let manifest = parse(
+            r#"
+                [lints]
+                unused-label = "allow"
+            "#,
+        )
+        .unwrap();
+
+        assert!(manifest.targets.is_empty());
+        assert_eq!(manifest.lints.get(Lint::UnusedLabel), Level::Allow);
+
+        let err = parse(
+            r#"
+                [lints]
+                unused-label = "ignore"
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::Lints { .. });
+    }
+
+    #[test]
+    fn unknown_field() {
+        let err = parse(
+            r#"
+                [[target]]
+                name = "a"
+                entry = "a.etk"
+                optimise = true
+            "#,
+        )
+        .unwrap_err();
+
+        assert_matches!(err, Error::Parse { .. });
+    }
+}
@@ -0,0 +1,233 @@
+//! Listing where each label ended up, for debuggers and other tools.
+
+use etk_asm::ingest::Symbol;
+
+use serde_json::json;
+
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How the label map is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MapFormat {
+    /// One `offset name kind` line per label, like `nm` prints.
+    Nm,
+
+    /// A JSON object for the input, with the modules it imports nested
+    /// inside.
+    Json,
+
+    /// An indented outline of the input and the modules it imports, like
+    /// `dwarfdump` prints.
+    Tree,
+}
+
+impl FromStr for MapFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "nm" => Ok(MapFormat::Nm),
+            "json" => Ok(MapFormat::Json),
+            "tree" => Ok(MapFormat::Tree),
+            _ => Err(format!("unknown map format `{}`", text)),
+        }
+    }
+}
+
+/// The labels declared in one file, and in the modules it imports.
+#[derive(Debug)]
+struct Module<'a> {
+    file: &'a Path,
+    symbols: Vec<&'a Symbol>,
+    modules: Vec<Module<'a>>,
+}
+
+impl<'a> Module<'a> {
+    fn new(file: &'a Path) -> Self {
+        Self {
+            file,
+            symbols: Vec::new(),
+            modules: Vec::new(),
+        }
+    }
+
+    /// Group `symbols` by the file they were declared in, under `root`.
+    fn tree(root: &'a Path, symbols: &'a [Symbol]) -> Self {
+        let mut module = Module::new(root);
+
+        for symbol in symbols {
+            // Every file that imported the declaring file, outermost first,
+            // except for the root itself.
+            let mut files: Vec<&Path> = symbol
+                .site
+                .included_from
+                .iter()
+                .rev()
+                .skip(1)
+                .map(AsRef::as_ref)
+                .collect();
+
+            if !symbol.site.included_from.is_empty() {
+                files.push(&symbol.site.file);
+            }
+
+            module.insert(&files, symbol);
+        }
+
+        module
+    }
+
+    fn insert(&mut self, files: &[&'a Path], symbol: &'a Symbol) {
+        let (first, rest) = match files.split_first() {
+            Some(f) => f,
+            None => {
+                self.symbols.push(symbol);
+                return;
+            }
+        };
+
+        let idx = match self.modules.iter().position(|m| m.file == *first) {
+            Some(idx) => idx,
+            None => {
+                self.modules.push(Module::new(first));
+                self.modules.len() - 1
+            }
+        };
+
+        self.modules[idx].insert(rest, symbol);
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let symbols: Vec<_> = self
+            .symbols
+            .iter()
+            .map(|s| {
+                json!({
+                    "name": s.name,
+                    "offset": s.offset,
+                    "kind": s.kind.name(),
+                    "line": s.site.span.map(|span| span.start.0),
+                })
+            })
+            .collect();
+
+        let modules: Vec<_> = self.modules.iter().map(Module::to_json).collect();
+
+        json!({
+            "file": self.file.display().to_string(),
+            "symbols": symbols,
+            "modules": modules,
+        })
+    }
+
+    fn outline(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        writeln!(out, "{}{}", indent, self.file.display()).unwrap();
+
+        for symbol in &self.symbols {
+            writeln!(
+                out,
+                "{}  0x{:08x}  {:<9}  {}",
+                indent, symbol.offset, symbol.kind, symbol.name
+            )
+            .unwrap();
+        }
+
+        for module in &self.modules {
+            module.outline(out, depth + 1);
+        }
+    }
+}
+
+/// Write `symbols`, assembled from `input`, in the given format.
+pub(crate) fn render(input: &Path, symbols: &[Symbol], format: MapFormat) -> String {
+    let mut out = String::new();
+
+    match format {
+        MapFormat::Nm => {
+            for symbol in symbols {
+                writeln!(out, "{:08x} {} {}", symbol.offset, symbol.name, symbol.kind).unwrap();
+            }
+        }
+        MapFormat::Json => {
+            let json = Module::tree(input, symbols).to_json();
+            out = serde_json::to_string_pretty(&json).unwrap();
+            out.push('\n');
+        }
+        MapFormat::Tree => Module::tree(input, symbols).outline(&mut out, 0),
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use etk_asm::ingest::Ingest;
+
+    use std::io::Write as _;
+
+    use tempfile::TempDir;
+
+    fn assemble(dir: &TempDir) -> Vec<Symbol> {
+        let mut math = std::fs::File::create(dir.path().join("math.etk")).unwrap();
+        write!(math, "%export(add)\nadd:\njumpdest\nhelper:\nstop\n").unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        ingest
+            .ingest(
+                dir.path().join("main.etk"),
+                "start:\n%import(\"math.etk\") as math\nend:\n",
+            )
+            .unwrap();
+
+        ingest.symbols().to_vec()
+    }
+
+    #[test]
+    fn nm() {
+        let dir = TempDir::new().unwrap();
+        let symbols = assemble(&dir);
+
+        let expected = "\
+00000000 math.add label
+00000000 start label
+00000001 helper private
+00000002 end label
+";
+
+        let main = dir.path().join("main.etk");
+        assert_eq!(render(&main, &symbols, MapFormat::Nm), expected);
+    }
+
+    #[test]
+    fn nested() {
+        let dir = TempDir::new().unwrap();
+        let symbols = assemble(&dir);
+        let main = dir.path().join("main.etk");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&main, &symbols, MapFormat::Json)).unwrap();
+
+        assert_eq!(json["file"], main.display().to_string());
+        assert_eq!(json["symbols"][0]["name"], "start");
+        assert_eq!(json["symbols"][1]["line"], 3);
+        assert_eq!(json["modules"][0]["symbols"][0]["name"], "math.add");
+        assert_eq!(json["modules"][0]["symbols"][1]["kind"], "private");
+
+        let tree = render(&main, &symbols, MapFormat::Tree);
+        let lines: Vec<_> = tree.lines().skip(1).collect();
+        assert_eq!(
+            lines[..2],
+            [
+                "  0x00000000  label      start",
+                "  0x00000002  label      end"
+            ]
+        );
+        assert!(lines[2].ends_with("math.etk"));
+        assert_eq!(lines[3], "    0x00000000  label      math.add");
+    }
+}
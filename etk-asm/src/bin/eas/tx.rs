@@ -0,0 +1,280 @@
+//! Just enough RLP and EIP-1559 to sign a contract creation transaction.
+
+use k256::ecdsa::signature::Signer;
+use k256::ecdsa::{recoverable, SigningKey, VerifyingKey};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+use sha3::{Digest, Keccak256};
+
+/// EIP-2718 type byte for EIP-1559 transactions.
+const EIP1559_TYPE: u8 = 0x02;
+
+fn rlp_header(out: &mut Vec<u8>, offset: u8, len: usize) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let bytes = trim(&len.to_be_bytes()).to_vec();
+        out.push(offset + 55 + bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+}
+
+fn rlp_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        out.push(bytes[0]);
+    } else {
+        rlp_header(out, 0x80, bytes.len());
+        out.extend_from_slice(bytes);
+    }
+}
+
+fn rlp_uint(out: &mut Vec<u8>, n: u128) {
+    rlp_bytes(out, trim(&n.to_be_bytes()));
+}
+
+fn rlp_list(out: &mut Vec<u8>, payload: &[u8]) {
+    rlp_header(out, 0xc0, payload.len());
+    out.extend_from_slice(payload);
+}
+
+/// Append the `v` (the recovery id plus `v_offset`), `r`, and `s` of
+/// `signature` to `fields`.
+fn append_signature(fields: &mut Vec<u8>, signature: &recoverable::Signature, v_offset: u128) {
+    let bytes = signature.as_ref();
+
+    rlp_uint(fields, v_offset + u128::from(bytes[64]));
+    rlp_bytes(fields, trim(&bytes[..32]));
+    rlp_bytes(fields, trim(&bytes[32..64]));
+}
+
+fn trim(mut bytes: &[u8]) -> &[u8] {
+    while !bytes.is_empty() && bytes[0] == 0 {
+        bytes = &bytes[1..];
+    }
+    bytes
+}
+
+/// Compute the address controlled by `key`.
+pub(crate) fn address(key: &VerifyingKey) -> [u8; 20] {
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// An unsigned EIP-1559 transaction that creates a contract.
+#[derive(Debug, Clone)]
+pub(crate) struct Create {
+    pub(crate) chain_id: u64,
+    pub(crate) nonce: u64,
+    pub(crate) max_priority_fee_per_gas: u128,
+    pub(crate) max_fee_per_gas: u128,
+    pub(crate) gas_limit: u64,
+    pub(crate) init_code: Vec<u8>,
+}
+
+impl Create {
+    fn fields(&self) -> Vec<u8> {
+        let mut fields = Vec::new();
+        rlp_uint(&mut fields, self.chain_id.into());
+        rlp_uint(&mut fields, self.nonce.into());
+        rlp_uint(&mut fields, self.max_priority_fee_per_gas);
+        rlp_uint(&mut fields, self.max_fee_per_gas);
+        rlp_uint(&mut fields, self.gas_limit.into());
+        rlp_bytes(&mut fields, &[]); // Empty `to` means contract creation.
+        rlp_uint(&mut fields, 0); // Value.
+        rlp_bytes(&mut fields, &self.init_code);
+        rlp_list(&mut fields, &[]); // Access list.
+        fields
+    }
+
+    /// The bytes that are hashed and signed.
+    fn signing_payload(&self) -> Vec<u8> {
+        let mut payload = vec![EIP1559_TYPE];
+        rlp_list(&mut payload, &self.fields());
+        payload
+    }
+
+    /// Sign the transaction, returning the raw bytes suitable for
+    /// `eth_sendRawTransaction`.
+    pub(crate) fn sign(&self, key: &SigningKey) -> Vec<u8> {
+        let signature: recoverable::Signature = key.sign(&self.signing_payload());
+
+        let mut fields = self.fields();
+        append_signature(&mut fields, &signature, 0);
+
+        let mut raw = vec![EIP1559_TYPE];
+        rlp_list(&mut raw, &fields);
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    fn key() -> SigningKey {
+        let bytes = hex!("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        SigningKey::from_bytes(&bytes).unwrap()
+    }
+
+    fn create() -> Create {
+        Create {
+            chain_id: 1337,
+            nonce: 7,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 100_000_000_000,
+            gas_limit: 100_000,
+            init_code: hex!("600a600c600039600a6000f3602a60005260206000f3").to_vec(),
+        }
+    }
+
+    #[test]
+    fn rlp_string() {
+        let mut out = Vec::new();
+        rlp_bytes(&mut out, b"dog");
+        assert_eq!(out, hex!("83646f67"));
+    }
+
+    #[test]
+    fn rlp_single_byte() {
+        let mut out = Vec::new();
+        rlp_bytes(&mut out, &[0x0f]);
+        assert_eq!(out, hex!("0f"));
+    }
+
+    #[test]
+    fn rlp_integers() {
+        let mut out = Vec::new();
+        rlp_uint(&mut out, 0);
+        rlp_uint(&mut out, 1024);
+        assert_eq!(out, hex!("80820400"));
+    }
+
+    #[test]
+    fn rlp_nested_list() {
+        let mut payload = Vec::new();
+        rlp_bytes(&mut payload, b"cat");
+        rlp_bytes(&mut payload, b"dog");
+
+        let mut out = Vec::new();
+        rlp_list(&mut out, &payload);
+        assert_eq!(out, hex!("c88363617483646f67"));
+    }
+
+    #[test]
+    fn rlp_long_string() {
+        let text = [b'a'; 56];
+
+        let mut out = Vec::new();
+        rlp_bytes(&mut out, &text);
+
+        assert_eq!(out[..2], hex!("b838"));
+        assert_eq!(out[2..], text[..]);
+    }
+
+    #[test]
+    fn address_from_key() {
+        let expected = hex!("2c7536e3605d9c16a7a3d7b1898e529396a65c23");
+        assert_eq!(address(&key().verifying_key()), expected);
+    }
+
+    #[test]
+    fn signature_recovers_sender() {
+        let tx = create();
+        let payload = tx.signing_payload();
+
+        let signature: recoverable::Signature = key().sign(&payload);
+        let recovered = signature.recover_verify_key(&payload).unwrap();
+
+        assert_eq!(address(&recovered), address(&key().verifying_key()));
+    }
+
+    #[test]
+    fn signed_encoding() {
+        let tx = create();
+        let raw = tx.sign(&key());
+        let unsigned = tx.fields();
+
+        // Type byte, then a list header with a one byte length.
+        assert_eq!(raw[..3], [EIP1559_TYPE, 0xf8, raw.len() as u8 - 3]);
+        assert_eq!(raw[3..3 + unsigned.len()], unsigned[..]);
+
+        // Then `v`, which is only the recovery id (zero is the empty string),
+        // and `r` and `s`, each at most 32 bytes.
+        let rest = &raw[3 + unsigned.len()..];
+        assert!(rest[0] == 0x80 || rest[0] == 0x01);
+        assert!(rest[1] <= 0xa0);
+    }
+
+    /// The example transaction from [EIP-155], checked against the signing
+    /// hash, sender, and raw bytes published there.
+    ///
+    /// k256 derives its deterministic nonces with the message's hash function
+    /// instead of SHA-256, so it signs with a different (but equally valid)
+    /// `r` and `s`. Both signatures have to recover the same sender, and the
+    /// published one has to encode to the published bytes.
+    ///
+    /// [EIP-155]: https://eips.ethereum.org/EIPS/eip-155
+    #[test]
+    fn eip155_known_answer() {
+        let chain_id = 1;
+        let key = SigningKey::from_bytes(&[0x46; 32]).unwrap();
+        let sender = hex!("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+
+        let mut fields = Vec::new();
+        rlp_uint(&mut fields, 9); // Nonce.
+        rlp_uint(&mut fields, 20_000_000_000); // Gas price.
+        rlp_uint(&mut fields, 21_000); // Gas limit.
+        rlp_bytes(&mut fields, &[0x35; 20]); // To.
+        rlp_uint(&mut fields, 1_000_000_000_000_000_000); // Value.
+        rlp_bytes(&mut fields, &[]); // Data.
+
+        let mut unsigned = fields.clone();
+        rlp_uint(&mut unsigned, chain_id);
+        rlp_uint(&mut unsigned, 0);
+        rlp_uint(&mut unsigned, 0);
+
+        let mut payload = Vec::new();
+        rlp_list(&mut payload, &unsigned);
+        assert_eq!(
+            payload,
+            hex!(
+                "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080"
+            )
+        );
+        assert_eq!(
+            Keccak256::digest(&payload)[..],
+            hex!("daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53")
+        );
+        assert_eq!(address(&key.verifying_key()), sender);
+
+        let published = recoverable::Signature::try_from(
+            &hex!("28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276 67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83 00")[..],
+        )
+        .unwrap();
+        let recovered = published.recover_verify_key(&payload).unwrap();
+        assert_eq!(address(&recovered), sender);
+
+        let ours: recoverable::Signature = key.sign(&payload);
+        let recovered = ours.recover_verify_key(&payload).unwrap();
+        assert_eq!(address(&recovered), sender);
+
+        append_signature(&mut fields, &published, 35 + 2 * chain_id);
+
+        let mut raw = Vec::new();
+        rlp_list(&mut raw, &fields);
+        assert_eq!(
+            raw,
+            hex!(
+                "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+            )
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::diagnostic::{Diagnostic, Failure, MessageFormat};
+
+use etk_asm::ingest::{AssemblerContext, Cache};
+
+use etk_cli::io::{is_stdio, Format};
+
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use tracing::info;
+
+/// How long to wait for a burst of file system events to settle before
+/// reassembling.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("could not start the file watcher"))]
+    Start { source: notify::Error },
+
+    #[snafu(display("could not watch `{}`", path.display()))]
+    Watch {
+        path: PathBuf,
+        source: notify::Error,
+    },
+
+    #[snafu(display("could not write `{}`", path.display()))]
+    Output {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("the file watcher stopped unexpectedly"))]
+    Disconnected { backtrace: Backtrace },
+}
+
+/// Assemble `input`, report the result, and return the files that were read.
+fn build(
+    input: &Path,
+    out: Option<&Path>,
+    format: Format,
+    messages: MessageFormat,
+    context: &AssemblerContext,
+    cache: &mut Cache,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut code = Vec::new();
+    let mut ingest = context.ingest_with_cache(&mut code, std::mem::take(cache));
+    let result = ingest.ingest_file(input);
+    let files = ingest.files().to_vec();
+    let warnings: Vec<Diagnostic> = ingest.warnings().iter().map(Into::into).collect();
+    let result = result.map_err(|e| Failure::new(e, ingest.failed_file()));
+    *cache = ingest.into_cache();
+
+    for warning in &warnings {
+        warning.report(messages);
+    }
+
+    if let Err(failure) = result {
+        failure.diagnostic().report(messages);
+        return Ok(files);
+    }
+
+    if warnings.iter().any(Diagnostic::is_error) {
+        return Ok(files);
+    }
+
+    let bytes = match format {
+        Format::Hex => format!("{}\n", hex::encode(&code)).into_bytes(),
+        Format::Binary => code.clone(),
+    };
+
+    match out {
+        Some(path) if !is_stdio(path) => std::fs::write(path, bytes).context(Output { path })?,
+        _ => {
+            let mut stdout = std::io::stdout();
+            stdout
+                .write_all(&bytes)
+                .and_then(|_| stdout.flush())
+                .context(Output { path: "-" })?;
+        }
+    }
+
+    info!(
+        "assembled {} bytes (keccak256: 0x{})",
+        code.len(),
+        hex::encode(Keccak256::digest(&code)),
+    );
+
+    Ok(files)
+}
+
+/// Resolve `files` against their canonicalized parent directories, so they can
+/// be compared against the paths reported by the watcher.
+fn locate(files: &[PathBuf]) -> Result<(BTreeSet<PathBuf>, BTreeSet<PathBuf>), Error> {
+    let mut dirs = BTreeSet::new();
+    let mut located = BTreeSet::new();
+
+    for file in files {
+        let name = match file.file_name() {
+            Some(n) => n,
+            None => continue,
+        };
+
+        let parent = match file.parent() {
+            Some(p) if p != Path::new("") => p,
+            _ => Path::new("."),
+        };
+
+        let dir = parent
+            .canonicalize()
+            .map_err(notify::Error::Io)
+            .context(Watch { path: parent })?;
+
+        located.insert(dir.join(name));
+        dirs.insert(dir);
+    }
+
+    Ok((dirs, located))
+}
+
+/// Whether `event` means any of `files` might have changed.
+fn touches(event: &DebouncedEvent, files: &BTreeSet<PathBuf>) -> bool {
+    match event {
+        DebouncedEvent::Create(p)
+        | DebouncedEvent::Write(p)
+        | DebouncedEvent::Remove(p)
+        | DebouncedEvent::Chmod(p) => files.contains(p),
+        DebouncedEvent::Rename(from, to) => files.contains(from) || files.contains(to),
+        DebouncedEvent::Rescan => true,
+        DebouncedEvent::NoticeWrite(_)
+        | DebouncedEvent::NoticeRemove(_)
+        | DebouncedEvent::Error(_, _) => false,
+    }
+}
+
+pub(crate) fn run(
+    input: PathBuf,
+    out: Option<PathBuf>,
+    format: Format,
+    messages: MessageFormat,
+    context: AssemblerContext,
+) -> Result<(), Error> {
+    let mut cache = Cache::new();
+
+    loop {
+        let files = build(
+            &input,
+            out.as_deref(),
+            format,
+            messages,
+            &context,
+            &mut cache,
+        )?;
+
+        // The set of files can change between builds, so start from scratch
+        // every time. Watching directories instead of files keeps working when
+        // editors save by replacing the file.
+        let (dirs, files) = locate(&files)?;
+
+        let (tx, rx) = channel();
+        let mut watcher = notify::watcher(tx, DEBOUNCE).context(Start)?;
+
+        for dir in dirs {
+            watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .context(Watch { path: dir })?;
+        }
+
+        loop {
+            let event = rx.recv().ok().context(Disconnected)?;
+            if touches(&event, &files) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use etk_asm::lint::{Level, Levels, Lint};
+
+    #[test]
+    fn locate_relative() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let root = dir.path().canonicalize().unwrap();
+
+        let (dirs, files) = locate(&[dir.path().join("src/../main.etk")]).unwrap();
+        assert_eq!(
+            dirs.into_iter().collect::<Vec<_>>(),
+            std::slice::from_ref(&root)
+        );
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            [root.join("main.etk")]
+        );
+
+        // A bare file name is in the current directory.
+        let (dirs, files) = locate(&[PathBuf::from("main.etk")]).unwrap();
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(
+            dirs.into_iter().collect::<Vec<_>>(),
+            std::slice::from_ref(&cwd)
+        );
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            [cwd.join("main.etk")]
+        );
+    }
+
+    #[test]
+    fn denied_lint_skips_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("main.etk");
+        let out = dir.path().join("main.hex");
+        std::fs::write(&input, "callcode").unwrap();
+
+        let mut levels = Levels::new();
+        levels.set(Lint::DeprecatedInstruction, Level::Deny);
+        let context = AssemblerContext::new().lints(levels);
+
+        let mut cache = Cache::default();
+        let files = build(
+            &input,
+            Some(&out),
+            Format::Hex,
+            MessageFormat::Json,
+            &context,
+            &mut cache,
+        )
+        .unwrap();
+
+        assert_eq!(files, [input]);
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn touches_watched_file() {
+        let watched = PathBuf::from("/src/main.etk");
+        let other = PathBuf::from("/src/other.etk");
+
+        let mut files = BTreeSet::new();
+        files.insert(watched.clone());
+
+        assert!(touches(&DebouncedEvent::Write(watched.clone()), &files));
+        assert!(touches(&DebouncedEvent::Create(watched.clone()), &files));
+        assert!(touches(
+            &DebouncedEvent::Rename(other.clone(), watched.clone()),
+            &files
+        ));
+        assert!(touches(&DebouncedEvent::Rescan, &files));
+
+        assert!(!touches(&DebouncedEvent::Write(other), &files));
+        assert!(!touches(&DebouncedEvent::NoticeWrite(watched), &files));
+    }
+}
@@ -0,0 +1,153 @@
+//! Build information appended to assembled code.
+//!
+//! When requested, with the `%buildinfo()` directive or
+//! [`AssemblerContext::stamp`](crate::ingest::AssemblerContext::stamp), a trailer
+//! identifying the assembler version and the source is appended to the end of
+//! the output. It's laid out like the metadata `solc` appends, so tools that
+//! look for that can find it:
+//!
+//! ```text
+//! a2                  CBOR map with two entries
+//!   63 65746b         "etk"
+//!   43 MMmmpp         three bytes: major, minor, and patch version
+//!   63 737263         "src"
+//!   5820 <32 bytes>   the source hash
+//! 002f                length of the map (47 bytes), big-endian
+//! ```
+//!
+//! The source hash is the keccak256 of the keccak256 hashes of the root source,
+//! followed by every file it reads (through `%import`, `%include`, and
+//! `%include_hex`), in the order they're first read. Paths don't contribute,
+//! so the same sources produce the same trailer wherever they're assembled.
+
+use std::convert::TryInto;
+
+/// Length of the CBOR encoded map.
+const MAP_LEN: usize = 47;
+
+/// Length of the whole trailer, including the length suffix.
+pub const TRAILER_LEN: usize = MAP_LEN + 2;
+
+const ETK_KEY: [u8; 4] = [0x63, b'e', b't', b'k'];
+const SRC_KEY: [u8; 4] = [0x63, b's', b'r', b'c'];
+
+/// The contents of a build information trailer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Major, minor, and patch version of the assembler.
+    pub version: [u8; 3],
+
+    /// Hash of every source file that went into the code.
+    pub source_hash: [u8; 32],
+}
+
+impl BuildInfo {
+    /// Build information for this version of the assembler, and the given
+    /// source hash.
+    pub fn new(source_hash: [u8; 32]) -> Self {
+        Self {
+            version: version(env!("CARGO_PKG_VERSION")),
+            source_hash,
+        }
+    }
+
+    /// Encode as a trailer, to be appended to assembled code.
+    pub fn to_trailer(&self) -> [u8; TRAILER_LEN] {
+        let mut out = [0u8; TRAILER_LEN];
+
+        out[0] = 0xa2;
+        out[1..5].copy_from_slice(&ETK_KEY);
+        out[5] = 0x43;
+        out[6..9].copy_from_slice(&self.version);
+        out[9..13].copy_from_slice(&SRC_KEY);
+        out[13] = 0x58;
+        out[14] = 0x20;
+        out[15..47].copy_from_slice(&self.source_hash);
+        out[47..].copy_from_slice(&(MAP_LEN as u16).to_be_bytes());
+
+        out
+    }
+
+    /// Find the build information at the end of `code`, if it has any.
+    pub fn from_code(code: &[u8]) -> Option<Self> {
+        if code.len() < TRAILER_LEN {
+            return None;
+        }
+
+        let trailer = &code[code.len() - TRAILER_LEN..];
+
+        let well_formed = trailer[0] == 0xa2
+            && trailer[1..5] == ETK_KEY
+            && trailer[5] == 0x43
+            && trailer[9..13] == SRC_KEY
+            && trailer[13..15] == [0x58, 0x20]
+            && trailer[47..] == (MAP_LEN as u16).to_be_bytes();
+
+        if !well_formed {
+            return None;
+        }
+
+        Some(Self {
+            version: trailer[6..9].try_into().unwrap(),
+            source_hash: trailer[15..47].try_into().unwrap(),
+        })
+    }
+}
+
+/// Parse the numeric parts of a semver version, ignoring any pre-release or
+/// build metadata.
+fn version(text: &str) -> [u8; 3] {
+    let core = text.split(&['-', '+'][..]).next().unwrap();
+
+    let mut out = [0u8; 3];
+    for (part, slot) in core.split('.').zip(out.iter_mut()) {
+        *slot = part.parse().unwrap_or(u8::MAX);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn version_ignores_pre_release() {
+        assert_eq!(version("0.2.0-dev"), [0, 2, 0]);
+        assert_eq!(version("1.12.3+build.4"), [1, 12, 3]);
+    }
+
+    #[test]
+    fn trailer_layout() {
+        let info = BuildInfo {
+            version: [0, 2, 0],
+            source_hash: [0x11; 32],
+        };
+
+        // Changing this breaks tools that read the trailer, so don't.
+        let expected = hex!(
+            "
+            a26365746b4300020063737263582011111111111111111111111111111111
+            11111111111111111111111111111111002f
+            "
+        );
+
+        assert_eq!(info.to_trailer(), expected);
+    }
+
+    #[test]
+    fn trailer_round_trip() {
+        let info = BuildInfo {
+            version: [1, 2, 3],
+            source_hash: [0xab; 32],
+        };
+
+        let mut code = vec![0x60, 0x00];
+        code.extend_from_slice(&info.to_trailer());
+
+        assert_eq!(BuildInfo::from_code(&code), Some(info));
+        assert_eq!(BuildInfo::from_code(&code[..code.len() - 1]), None);
+    }
+}
@@ -0,0 +1,457 @@
+//! Stable codes identifying each kind of error, like `E0016`.
+//!
+//! A code is never renumbered or reused for a different kind of error, so
+//! tools can match on codes across versions, even as error messages change.
+//! Find the code of an error with [`Error::code`], and describe it with
+//! [`explain`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! use etk_asm::codes::{explain, Code};
+//! use etk_asm::ingest::Ingest;
+//!
+//! let mut ingest = Ingest::new(Vec::new());
+//! let err = ingest.ingest("./example.etk", "push1 nowhere").unwrap_err();
+//!
+//! assert_eq!(err.code().to_string(), "E0016");
+//! assert_eq!(explain(err.code()).unwrap().name, "undeclared-label");
+//! # assert_eq!("E0016".parse::<Code>().unwrap(), err.code());
+//! ```
+//!
+//! [`Error::code`]: crate::ingest::Error::code
+
+mod error {
+    use snafu::{Backtrace, Snafu};
+
+    /// The error that can arise while parsing a [`super::Code`] from a string.
+    #[derive(Debug, Snafu)]
+    #[snafu(display("`{}` isn't an error code (expected something like `E0007`)", text))]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub struct InvalidCodeError {
+        text: String,
+        backtrace: Backtrace,
+    }
+}
+
+pub use self::error::InvalidCodeError;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A stable identifier for a kind of error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Code(u16);
+
+impl Code {
+    /// The number of this code, without the `E` prefix.
+    pub fn number(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "E{:04}", self.0)
+    }
+}
+
+impl FromStr for Code {
+    type Err = InvalidCodeError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let digits = text
+            .strip_prefix('E')
+            .or_else(|| text.strip_prefix('e'))
+            .filter(|d| d.len() == 4 && d.bytes().all(|b| b.is_ascii_digit()));
+
+        match digits {
+            Some(d) => Ok(Code(d.parse().unwrap())),
+            None => error::InvalidCodeContext { text }.fail(),
+        }
+    }
+}
+
+pub(crate) const SYNTAX: Code = Code(1);
+pub(crate) const IMMEDIATE_TOO_LARGE: Code = Code(2);
+pub(crate) const MISSING_ARGUMENT: Code = Code(3);
+pub(crate) const EXTRA_ARGUMENT: Code = Code(4);
+pub(crate) const ARGUMENT_TYPE: Code = Code(5);
+pub(crate) const UNKNOWN_PRAGMA: Code = Code(6);
+pub(crate) const UNDEFINED_MACRO: Code = Code(7);
+pub(crate) const UNDEFINED_CONSTANT: Code = Code(8);
+pub(crate) const UNDEFINED_ARGUMENT: Code = Code(9);
+pub(crate) const UNDEFINED_SIGNATURE: Code = Code(10);
+pub(crate) const DUPLICATE_DEFINITION: Code = Code(11);
+pub(crate) const MACRO_RECURSION_LIMIT: Code = Code(12);
+pub(crate) const DUPLICATE_LABEL: Code = Code(13);
+pub(crate) const LABEL_TOO_LARGE: Code = Code(14);
+pub(crate) const PUSH_TOO_LARGE: Code = Code(15);
+pub(crate) const UNDECLARED_LABEL: Code = Code(16);
+pub(crate) const IO: Code = Code(17);
+pub(crate) const DIRECTORY_TRAVERSAL: Code = Code(18);
+pub(crate) const INVALID_HEX: Code = Code(19);
+pub(crate) const INVALID_ARTIFACT: Code = Code(20);
+pub(crate) const RECURSION_LIMIT: Code = Code(21);
+pub(crate) const UNSUPPORTED_INSTRUCTION: Code = Code(22);
+pub(crate) const DEFINE_TOO_LARGE: Code = Code(23);
+pub(crate) const DEFINE_CONFLICT: Code = Code(24);
+pub(crate) const UNDECLARED_EXPORT: Code = Code(25);
+pub(crate) const UNMATCHED_ANONYMOUS_LABEL: Code = Code(26);
+pub(crate) const UNKNOWN_INSTRUCTION: Code = Code(27);
+pub(crate) const ASSERTION_FAILED: Code = Code(28);
+pub(crate) const INVALID_EXPRESSION: Code = Code(29);
+pub(crate) const OFFSET_PASSED: Code = Code(30);
+pub(crate) const RECURSIVE_MACRO: Code = Code(31);
+pub(crate) const EXPANSION_LIMIT: Code = Code(32);
+pub(crate) const TEMPLATE: Code = Code(33);
+pub(crate) const SCRIPT: Code = Code(34);
+pub(crate) const UNKNOWN_LOCAL: Code = Code(35);
+pub(crate) const LOCAL_OUT_OF_REACH: Code = Code(36);
+pub(crate) const UNKNOWN_MODULE: Code = Code(37);
+pub(crate) const INVALID_EVENT: Code = Code(38);
+pub(crate) const UNDEFINED_ARRAY: Code = Code(39);
+pub(crate) const ARRAY_INDEX: Code = Code(40);
+pub(crate) const INVALID_REPETITION: Code = Code(41);
+pub(crate) const STRING_RANGE: Code = Code(42);
+pub(crate) const INVALID_SELECTOR: Code = Code(43);
+pub(crate) const NO_RUNTIME: Code = Code(44);
+pub(crate) const STACK_TOO_DEEP: Code = Code(45);
+pub(crate) const EXPERIMENTAL_DISABLED: Code = Code(46);
+pub(crate) const NEGATIVE_VALUE: Code = Code(47);
+pub(crate) const UNHASHABLE_CODE: Code = Code(48);
+pub(crate) const ARGUMENT_TOO_LARGE: Code = Code(49);
+
+/// A description of a kind of error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Explanation {
+    /// The code identifying this kind of error.
+    pub code: Code,
+
+    /// A short, human readable name, like `undeclared-label`.
+    pub name: &'static str,
+
+    /// A one line suggestion for fixing the error.
+    pub help: &'static str,
+
+    /// A longer explanation of what causes the error.
+    pub text: &'static str,
+}
+
+macro_rules! explanations {
+    ($($code:ident $name:literal $help:literal $text:literal)*) => {
+        /// Every kind of error, in order of their codes.
+        pub static EXPLANATIONS: &[Explanation] = &[
+            $(
+                Explanation {
+                    code: $code,
+                    name: $name,
+                    help: $help,
+                    text: $text,
+                },
+            )*
+        ];
+    };
+}
+
+explanations! {
+    SYNTAX "syntax"
+        "check the source near the reported line and column for typos"
+        "The source isn't valid syntax. Usually an instruction is misspelled, \
+        or a macro is missing its `%` or parentheses."
+
+    IMMEDIATE_TOO_LARGE "immediate-too-large"
+        "use a larger push, or a smaller value"
+        "A literal doesn't fit in the instruction or macro it was given to. \
+        For example, `push1 256` needs two bytes, but `push1` only has room \
+        for one."
+
+    MISSING_ARGUMENT "missing-argument"
+        "add the missing arguments to the macro"
+        "A macro was given fewer arguments than it requires."
+
+    EXTRA_ARGUMENT "extra-argument"
+        "remove the extra arguments from the macro"
+        "A macro was given more arguments than it accepts."
+
+    ARGUMENT_TYPE "argument-type"
+        "check whether the macro expects a string, a number, or a label"
+        "An argument to a macro was the wrong kind of value. Paths are \
+        strings, like `%import(\"lib.etk\")`, while labels are written \
+        without quotes."
+
+    UNKNOWN_PRAGMA "unknown-pragma"
+        "the only pragma is `%pragma(once)`"
+        "A `%pragma` directive named a pragma that doesn't exist."
+
+    UNDEFINED_MACRO "undefined-macro"
+        "define the macro with `%macro` (or `#define macro` in Huff), or fix its name"
+        "A macro was invoked, but never defined. In Huff, macros can be \
+        defined in the file or anything it includes. A `%macro` has to be \
+        defined earlier in the same file."
+
+    UNDEFINED_CONSTANT "undefined-constant"
+        "define the constant with `%def` (or `#define constant` in Huff), or fix its name"
+        "A constant was used, but never defined. In Huff, constants can be \
+        defined in the file or anything it includes. A string used in \
+        `concat`, `substr`, `len`, or `selector` has to be defined with \
+        `%def` earlier in the same file."
+
+    UNDEFINED_ARGUMENT "undefined-argument"
+        "add the argument to the macro's definition"
+        "A macro used an argument, like `$name` in a `%macro` or `<name>` in \
+        Huff, but has no argument with that name."
+
+    UNDEFINED_SIGNATURE "undefined-signature"
+        "declare the function, event, or error"
+        "A Huff builtin like `__FUNC_SIG` named a function, event, or error \
+        that wasn't declared with `#define`."
+
+    DUPLICATE_DEFINITION "duplicate-definition"
+        "rename or remove one of the definitions"
+        "A Huff macro or constant was defined more than once."
+
+    MACRO_RECURSION_LIMIT "macro-recursion-limit"
+        "flatten the macros, or raise the limit with `--macro-depth`"
+        "Macros were nested more deeply than the limit allows."
+
+    DUPLICATE_LABEL "duplicate-label"
+        "rename one of the labels, or use `%include` to give a file its own labels"
+        "A label was declared more than once in the same scope. Imported files \
+        share labels with the file importing them, so importing a file twice \
+        declares its labels twice."
+
+    LABEL_TOO_LARGE "label-too-large"
+        "use a larger push, or `%push(label)` to pick the size automatically"
+        "The address of a label doesn't fit in the push that refers to it. For \
+        example, `push1 label` only works if the label is within the first \
+        256 bytes."
+
+    PUSH_TOO_LARGE "push-too-large"
+        "split the value into several pushes"
+        "The value given to `%push` doesn't fit in any push instruction, which \
+        can hold at most 32 bytes."
+
+    UNDECLARED_LABEL "undeclared-label"
+        "declare the label, or check that it's exported by the file that declares it"
+        "A label was used, but never declared. Labels in included files aren't \
+        visible to the file including them, and imported files only share the \
+        labels they `%export` (when they export any.)"
+
+    IO "io"
+        "check that the file exists and can be read"
+        "Reading a source file, or writing the output, failed."
+
+    DIRECTORY_TRAVERSAL "directory-traversal"
+        "move the file into the directory of the file being assembled"
+        "An imported or included file is outside the directory containing the \
+        file being assembled, which isn't allowed."
+
+    INVALID_HEX "invalid-hex"
+        "check for non-hex characters or an odd number of digits"
+        "A file included with `%include_hex`, or a string given to `%raw`, \
+        isn't valid hexadecimal."
+
+    INVALID_ARTIFACT "invalid-artifact"
+        "check that the artifact was produced by a successful compile"
+        "A file included with `%include_artifact` isn't a compiler artifact, \
+        or doesn't contain the bytecode that was asked for."
+
+    RECURSION_LIMIT "recursion-limit"
+        "check for a file that imports or includes itself"
+        "Files were imported or included too deeply, which usually means a \
+        file imports or includes itself, directly or through other files. \
+        `%pragma(once)` stops a file from being expanded more than once."
+
+    UNSUPPORTED_INSTRUCTION "unsupported-instruction"
+        "target a later fork, or avoid the instruction"
+        "An instruction was used that the target fork doesn't have yet."
+
+    DEFINE_TOO_LARGE "define-too-large"
+        "use a larger push, or `%push(name)` to pick the size automatically"
+        "The value of a define doesn't fit in the push it was used in."
+
+    DEFINE_CONFLICT "define-conflict"
+        "rename the label or the define"
+        "A label was declared with the same name as a define, so it would be \
+        ambiguous which one a push refers to."
+
+    UNDECLARED_EXPORT "undeclared-export"
+        "declare the label, or remove it from `%export`"
+        "An imported file listed a label in `%export` without declaring it."
+
+    UNMATCHED_ANONYMOUS_LABEL "unmatched-anonymous-label"
+        "declare the anonymous label, or point the reference the other way"
+        "A reference like `1b` or `1f` needs an anonymous label with the same \
+        number (`1:`) before (`b`) or after (`f`) it in the same file."
+
+    UNKNOWN_INSTRUCTION "unknown-instruction"
+        "check the spelling, or declare the alias with `%alias` before using it"
+        "A word on a line of its own isn't an instruction, and no alias by that \
+        name was declared earlier in the file."
+
+    ASSERTION_FAILED "assertion-failed"
+        "change the code so the condition holds, or relax the `%assert`"
+        "The condition of an `%assert` was false once the labels in its scope \
+        had addresses."
+
+    INVALID_EXPRESSION "invalid-expression"
+        "check the expression for a division by zero"
+        "An expression, like the condition of an `%assert`, couldn't be \
+        evaluated."
+
+    OFFSET_PASSED "offset-passed"
+        "move the `%org` later, or shrink the code before it"
+        "An `%org` asked for the next instruction to start at an offset, but \
+        the code before it already reaches past that offset."
+
+    RECURSIVE_MACRO "recursive-macro"
+        "break the cycle of invocations shown in the error"
+        "A macro invoked itself, directly or through other macros, which \
+        would never finish expanding."
+
+    EXPANSION_LIMIT "expansion-limit"
+        "expand fewer macros or aliases, or raise the limit with `--macro-size`"
+        "Expanding a `%macro`, a Huff macro, or an `%alias` built from other \
+        aliases, produced more instructions than the limit allows."
+
+    TEMPLATE "template"
+        "fix the template, or the variables passed to it"
+        "A template couldn't be rendered into assembly, because of a mistake in \
+        its syntax, or because it used a variable that wasn't passed to it."
+
+    SCRIPT "script"
+        "fix the script, or make it return assembly text or bytes"
+        "A `%script` couldn't be run, because of a mistake in the script, \
+        because it ran for too long, or because it returned something other \
+        than a string of assembly or an array of bytes."
+
+    UNKNOWN_LOCAL "unknown-local"
+        "name the slot in `%locals` first, or check it wasn't popped"
+        "A `dup` or `swap` named a stack slot that no `%locals` declared, or \
+        that an instruction since then has popped off the stack."
+
+    LOCAL_OUT_OF_REACH "local-out-of-reach"
+        "pop or reorder values so the slot is within 16 of the top"
+        "A `dup` or `swap` named a stack slot deeper than 16 elements, which \
+        no instruction can reach. A `swap` also can't name the slot already \
+        on top of the stack."
+
+    UNKNOWN_MODULE "unknown-module"
+        "check the spelling of the module, like `%import(std, \"math\")`"
+        "An `%import(std, ...)` named a module that isn't part of the standard \
+        library shipped with the assembler."
+
+    INVALID_EVENT "invalid-event"
+        "write the event like `Transfer(address indexed,address indexed,uint256)`"
+        "The first argument of `%emit` must be an event signature: a name, then \
+        the types of its parameters in parentheses, with `indexed` after the \
+        ones that are topics. A log has room for at most three indexed \
+        parameters, after the topic identifying the event."
+
+    UNDEFINED_ARRAY "undefined-array"
+        "define the array with `%def NAME = [...]` earlier in the file"
+        "An array was indexed, like `FEES[1]`, but no `%def` earlier in the \
+        same file defined an array by that name."
+
+    ARRAY_INDEX "array-index"
+        "index with a constant from zero up to one less than the array's length"
+        "An array defined with `%def` was indexed with something that isn't a \
+        constant, like a label, or with a constant past its last element. \
+        Indexes start at zero."
+
+    INVALID_REPETITION "invalid-repetition"
+        "close the group with `)*`, and give the macro an argument like `args...`"
+        "The body of a `%macro` has a `$(...)*` group, which is repeated once \
+        for each variadic argument. The group has to be closed with `)*`, \
+        can't be inside another group, and can only be used in a macro whose \
+        last argument is variadic."
+
+    STRING_RANGE "string-range"
+        "check the offset and length given to `substr`"
+        "A `substr(text, start, len)` asked for bytes past the end of the \
+        string, or would have split a character that takes more than one \
+        byte."
+
+    INVALID_SELECTOR "invalid-selector"
+        "build a signature like `transfer(address,uint256)`, without spaces"
+        "The string given to `selector(...)` wasn't a function signature: a \
+        name, then the types of its parameters in parentheses, separated by \
+        commas without spaces."
+
+    NO_RUNTIME "no-runtime"
+        "end the constructor with `%include` of the runtime, or assemble all of it"
+        "Only the runtime was asked for (with `--bin-runtime`), which is the \
+        code of the last file the input includes with `%include`, but the \
+        input doesn't include any."
+
+    STACK_TOO_DEEP "stack-too-deep"
+        "keep fewer values on the stack, by storing some in memory or splitting up the code"
+        "A `dup` or `swap` with a number above 16, like `dup17`, was written \
+        or generated by a macro. No such instructions exist: `dup16` and \
+        `swap16` reach the deepest, 16 values below the top of the stack. \
+        Code that needs a value deeper than that has to be restructured."
+
+    EXPERIMENTAL_DISABLED "experimental-disabled"
+        "pass `--experimental` to assemble instructions from proposals"
+        "An instruction from a proposal that no fork has adopted, like \
+        `jumpsub` from EIP-2315, was used. These instructions are only \
+        assembled when asked for, since no network will run them."
+
+    NEGATIVE_VALUE "negative-value"
+        "check the order of the operands, like `len(start, end)` instead of `len(end, start)`"
+        "An expression given to a push evaluated to a number below zero, which \
+        a push can't hold. Often the operands of a subtraction, or the labels \
+        given to `len`, are the wrong way around."
+
+    UNHASHABLE_CODE "unhashable-code"
+        "move the push of the hash, and any other push of an expression, out of the hashed range"
+        "A `keccak` hashed a range of code that wasn't final yet. The range \
+        held the immediate of a push of an expression that hadn't been filled \
+        in, often the push of the hash itself, or code that was already \
+        streamed out."
+
+    ARGUMENT_TOO_LARGE "argument-too-large"
+        "give `%raw` and the fill of `%org` at most `0xff`, and other numbers at most `0xffffffff`"
+        "A number given to a directive was too large for its argument. A byte \
+        given to `%raw`, or as the fill of `%org`, is at most `0xff`, while \
+        the offset of `%org` and the index of `%calldata_word` are at most \
+        `0xffffffff`."
+}
+
+/// Describe the kind of error identified by `code`, or `None` if there's no
+/// such kind of error.
+pub fn explain(code: Code) -> Option<&'static Explanation> {
+    EXPLANATIONS.iter().find(|e| e.code == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_in_order() {
+        for (idx, explanation) in EXPLANATIONS.iter().enumerate() {
+            assert_eq!(usize::from(explanation.code.number()), idx + 1);
+        }
+    }
+
+    #[test]
+    fn parse_code() {
+        assert_eq!("E0016".parse::<Code>().unwrap(), UNDECLARED_LABEL);
+        assert_eq!("e0001".parse::<Code>().unwrap(), SYNTAX);
+        assert_eq!(UNDECLARED_LABEL.to_string(), "E0016");
+
+        assert!("E16".parse::<Code>().is_err());
+        assert!("0016".parse::<Code>().is_err());
+        assert!("E00a6".parse::<Code>().is_err());
+    }
+
+    #[test]
+    fn explain_code() {
+        assert_eq!(explain(DUPLICATE_LABEL).unwrap().name, "duplicate-label");
+        assert_eq!(explain(Code(9999)), None);
+    }
+}
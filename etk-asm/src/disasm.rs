@@ -25,7 +25,7 @@ mod error {
     }
 }
 
-use crate::ops::{ConcreteOp, Op, Specifier};
+use crate::ops::{ConcreteOp, Experimental, Op, Specifier};
 
 pub use self::error::Error;
 
@@ -75,16 +75,17 @@ impl<'a> Iterator for Iter<'a> {
         let buffer = &mut self.disassembler.buffer;
         let front = *buffer.front()?;
         let specifier = Specifier::from(front);
-        let len = specifier.size() as usize;
+        let len = match self.disassembler.experimental.by_opcode(front) {
+            Some(experimental) => experimental.size(),
+            None => specifier.size() as usize,
+        };
         if buffer.len() < len {
             return None;
         }
 
-        let remaining = buffer.split_off(len);
-        let mut instruction = std::mem::replace(&mut self.disassembler.buffer, remaining);
-        let instruction = instruction.make_contiguous();
+        let instruction: Vec<u8> = buffer.drain(..len).collect();
 
-        let item = Op::from_slice(instruction);
+        let item = Op::from_slice(&instruction[..specifier.size() as usize]);
         let offset = self.disassembler.offset;
         self.disassembler.offset += len;
         Some(Offset::new(offset, item))
@@ -118,6 +119,7 @@ impl<'a> Iterator for Iter<'a> {
 pub struct Disassembler {
     buffer: VecDeque<u8>,
     offset: usize,
+    experimental: Experimental,
 }
 
 impl Write for Disassembler {
@@ -138,6 +140,36 @@ impl Disassembler {
         Default::default()
     }
 
+    /// Treat the opcodes of the instructions in `experimental` as
+    /// instructions, so their immediate arguments are skipped over instead of
+    /// being disassembled.
+    ///
+    /// An experimental instruction is produced as the unassigned [`ConcreteOp`]
+    /// of its opcode, and its immediate argument is the bytes between its
+    /// offset and the next one.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::disasm::Disassembler;
+    /// use etk_asm::ops::{Experimental, ExperimentalOp};
+    ///
+    /// use std::io::Write;
+    ///
+    /// let mut experimental = Experimental::new();
+    /// experimental.insert(ExperimentalOp::new("l1block", 0xe0).immediate(2)).unwrap();
+    ///
+    /// let mut dasm = Disassembler::new();
+    /// dasm.set_experimental(experimental);
+    /// dasm.write_all(&[0xe0, 0x60, 0x01, 0x00]).unwrap();
+    ///
+    /// let offsets: Vec<_> = dasm.ops().map(|op| op.offset).collect();
+    /// assert_eq!(offsets, [0, 3]);
+    /// ```
+    pub fn set_experimental(&mut self, experimental: Experimental) {
+        self.experimental = experimental;
+    }
+
     /// Get an iterator over the disassembled [`ConcreteOp`].
     pub fn ops(&mut self) -> Iter {
         Iter { disassembler: self }
@@ -160,6 +192,8 @@ impl Disassembler {
 mod tests {
     use hex_literal::hex;
 
+    use crate::ops::ExperimentalOp;
+
     use super::*;
 
     #[test]
@@ -213,4 +247,24 @@ mod tests {
         assert_eq!(expected, actual.as_slice());
         dasm.finish().unwrap();
     }
+
+    #[test]
+    fn partial_experimental() {
+        let mut experimental = Experimental::new();
+        experimental
+            .insert(ExperimentalOp::new("l1block", 0xe0).immediate(2))
+            .unwrap();
+
+        let mut dasm = Disassembler::new();
+        dasm.set_experimental(experimental);
+        dasm.write_all(&hex!("e060")).unwrap();
+        assert!(dasm.ops().next().is_none());
+
+        dasm.write_all(&hex!("0100")).unwrap();
+
+        let actual: Vec<_> = dasm.ops().map(|op| op.offset).collect();
+
+        assert_eq!(actual, [0, 3]);
+        dasm.finish().unwrap();
+    }
 }
@@ -0,0 +1,34 @@
+//! Entry points for the fuzz targets in `etk-asm/fuzz`, and the property tests
+//! in `etk-testing`.
+//!
+//! Only available with the `fuzzing` feature, and not part of the stable API.
+
+use crate::ast::Node;
+use crate::ops::AbstractOp;
+use crate::ParseError;
+
+/// Parse `text` as assembly, throwing away the result.
+pub fn parse_asm(text: &str) -> Result<(), ParseError> {
+    crate::parse::parse_asm(text, Default::default(), &Default::default()).map(|_| ())
+}
+
+/// Parse `text` as assembly, keeping the instructions.
+///
+/// ## Panics
+///
+/// Panics if `text` contains a directive that isn't an instruction, like
+/// `%import`.
+pub fn parse_ops(text: &str) -> Result<Vec<AbstractOp>, ParseError> {
+    let nodes = crate::parse::parse_asm(text, Default::default(), &Default::default())?;
+
+    let ops = nodes
+        .into_iter()
+        .filter_map(|node| match node {
+            Node::Op(op) => Some(op),
+            Node::Location(_) | Node::Expanded { .. } => None,
+            other => panic!("not an instruction: {:?}", other),
+        })
+        .collect();
+
+    Ok(ops)
+}
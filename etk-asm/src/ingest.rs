@@ -3,10 +3,17 @@
 //! See the [`Ingest`] documentation for examples and more information.
 mod error {
     use crate::asm::Error as AssembleError;
+    use crate::ops::{ExpressionError, Fork, Specifier};
     use crate::ParseError;
 
+    use num_bigint::BigInt;
+
     use snafu::{Backtrace, Snafu};
 
+    use super::{Site, Unresolved};
+
+    use crate::Span;
+
     use std::path::PathBuf;
 
     /// Errors that may arise during the assembly process.
@@ -78,7 +85,21 @@ mod error {
             path: PathBuf,
 
             /// The underlying source of this error.
-            source: Box<dyn std::error::Error>,
+            source: Box<dyn std::error::Error + Send + Sync>,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// An included artifact didn't hold usable bytecode.
+        #[snafu(display("artifact `{}` is unusable: {}", path.to_string_lossy(), source))]
+        #[non_exhaustive]
+        InvalidArtifact {
+            /// Path to the offending file.
+            path: PathBuf,
+
+            /// The underlying source of this error.
+            source: Box<dyn std::error::Error + Send + Sync>,
 
             /// The location of the error.
             backtrace: Backtrace,
@@ -91,498 +112,5206 @@ mod error {
             /// The location of the error.
             backtrace: Backtrace,
         },
-    }
-}
 
-use crate::asm::{Assembler, RawOp};
-use crate::ast::Node;
-use crate::parse::parse_asm;
+        /// An instruction isn't available in the target fork.
+        #[snafu(display(
+            "`{}` requires {} or later, but the target is {}",
+            op,
+            op.introduced_in(),
+            fork
+        ))]
+        #[non_exhaustive]
+        UnsupportedInstruction {
+            /// The unavailable instruction.
+            op: Specifier,
 
-pub use self::error::Error;
+            /// The target fork.
+            fork: Fork,
 
-use snafu::{ensure, ResultExt};
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-use std::fs::{read_to_string, File};
-use std::io::{self, Read, Write};
-use std::path::{Path, PathBuf};
+        /// The value of a define was too large for the instruction it was used in.
+        #[snafu(display("value of `{}` is too large for `{}`", name, op))]
+        #[non_exhaustive]
+        DefineTooLarge {
+            /// The name of the define.
+            name: String,
 
-fn parse_file<P: AsRef<Path>>(path: P) -> Result<Vec<Node>, Error> {
-    let asm = read_to_string(path.as_ref()).with_context(|| error::Io {
-        message: "reading file before parsing",
-        path: path.as_ref().to_owned(),
-    })?;
-    let nodes = parse_asm(&asm)?;
-    Ok(nodes)
-}
+            /// The instruction the define was used in.
+            op: Specifier,
 
-#[derive(Debug)]
-enum Scope {
-    Same,
-    Independent(Assembler),
-}
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-impl Scope {
-    fn same() -> Self {
-        Self::Same
-    }
+        /// An imported module exported a label it doesn't declare.
+        #[snafu(display("label `{}` is exported but never declared", name))]
+        #[non_exhaustive]
+        UndeclaredExport {
+            /// The name of the label.
+            name: String,
 
-    fn independent() -> Self {
-        Self::Independent(Assembler::new())
-    }
-}
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-#[derive(Debug)]
-struct Source {
-    path: PathBuf,
-    nodes: std::vec::IntoIter<Node>,
-    scope: Scope,
-}
+        /// A label was declared more than once in the same scope.
+        #[snafu(display("label `{}` declared multiple times", label))]
+        #[non_exhaustive]
+        DuplicateLabel {
+            /// The name of the conflicting label.
+            label: String,
 
-#[derive(Debug)]
-struct Root {
-    original: PathBuf,
-    canonicalized: PathBuf,
-}
+            /// Where the label was first declared.
+            first: Box<Site>,
 
-impl Root {
-    fn new(mut file: PathBuf) -> Result<Self, Error> {
-        // Pop the filename.
-        if !file.pop() {
-            return Err(io::Error::from(io::ErrorKind::NotFound)).context(error::Io {
-                message: "no parent",
-                path: Some(file),
-            });
-        }
+            /// Where the label was declared again.
+            second: Box<Site>,
 
-        let file = std::env::current_dir()
-            .context(error::Io {
-                message: "getting cwd",
-                path: None,
-            })?
-            .join(file);
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-        let metadata = file.metadata().with_context(|| error::Io {
-            message: "getting metadata",
-            path: file.clone(),
-        })?;
+        /// Labels were used without being declared.
+        #[snafu(display("{}", super::undeclared(labels)))]
+        #[non_exhaustive]
+        UndeclaredLabels {
+            /// Every label that was used without being declared, in
+            /// alphabetical order.
+            labels: Vec<Unresolved>,
 
-        // Root must be a directory.
-        if !metadata.is_dir() {
-            let err = io::Error::from(io::ErrorKind::NotFound);
-            return Err(err).context(error::Io {
-                message: "root is not directory",
-                path: file,
-            });
-        }
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-        let canonicalized = std::fs::canonicalize(&file).with_context(|| error::Io {
-            message: "canonicalizing root",
-            path: file.clone(),
-        })?;
+        /// A label was declared with the same name as a define.
+        #[snafu(display("label `{}` has the same name as a define", name))]
+        #[non_exhaustive]
+        DefineConflict {
+            /// The name of the label.
+            name: String,
 
-        Ok(Self {
-            original: file,
-            canonicalized,
-        })
-    }
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-    fn check<P>(&self, path: P) -> Result<(), Error>
-    where
-        P: AsRef<Path>,
-    {
-        let path = path.as_ref();
+        /// The condition of an `%assert` was false.
+        #[snafu(display("assertion failed: {}", message))]
+        #[non_exhaustive]
+        AssertionFailed {
+            /// The message given to `%assert`, or the condition if there
+            /// wasn't one.
+            message: String,
 
-        let canonicalized = std::fs::canonicalize(path).with_context(|| error::Io {
-            message: "canonicalizing include/import",
-            path: path.to_owned(),
-        })?;
+            /// Where the assertion is.
+            site: Box<Site>,
 
-        // Don't allow directory traversals above the first file.
-        if canonicalized.starts_with(&self.canonicalized) {
-            Ok(())
-        } else {
-            error::DirectoryTraversal {
-                root: self.original.clone(),
-                file: path.to_owned(),
-            }
-            .fail()
-        }
-    }
-}
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-#[must_use]
-struct PartialSource<'a, W> {
-    stack: &'a mut SourceStack<W>,
-    path: PathBuf,
-    scope: Scope,
-}
+        /// The value of an expression was too large for the push it was used
+        /// in.
+        #[snafu(display("value of `{}` is too large for `{}`", expression, op))]
+        #[non_exhaustive]
+        ExpressionTooLarge {
+            /// The expression.
+            expression: String,
 
-impl<'a, W> PartialSource<'a, W> {
-    fn path(&self) -> &Path {
-        &self.path
-    }
+            /// The push the expression was used in.
+            op: Specifier,
 
-    fn push(self, nodes: Vec<Node>) -> &'a mut Source {
-        self.stack.sources.push(Source {
-            path: self.path,
-            nodes: nodes.into_iter(),
-            scope: self.scope,
-        });
+            /// Where the push is.
+            site: Box<Site>,
 
-        self.stack.sources.last_mut().unwrap()
-    }
-}
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-#[derive(Debug)]
-struct SourceStack<W> {
-    output: W,
-    sources: Vec<Source>,
-    root: Option<Root>,
-}
+        /// The value of an expression given to a push was negative.
+        #[snafu(display("value of `{}` is negative ({})", expression, value))]
+        #[non_exhaustive]
+        NegativeValue {
+            /// The expression.
+            expression: String,
 
-impl<W> SourceStack<W> {
-    fn new(output: W) -> Self {
-        Self {
-            output,
-            sources: Default::default(),
-            root: Default::default(),
-        }
-    }
+            /// What the expression evaluated to.
+            value: BigInt,
 
-    fn resolve(&mut self, path: PathBuf, scope: Scope) -> Result<PartialSource<W>, Error> {
-        ensure!(self.sources.len() <= 255, error::RecursionLimit);
+            /// Where the push is.
+            site: Box<Site>,
 
-        let path = if let Some(ref root) = self.root {
-            let last = self.sources.last().unwrap();
-            let dir = match last.path.parent() {
-                Some(s) => s,
-                None => Path::new("./"),
-            };
-            let candidate = dir.join(path);
-            root.check(&candidate)?;
-            candidate
-        } else {
-            assert!(self.sources.is_empty());
-            self.root = Some(Root::new(path.clone())?);
-            path
-        };
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-        Ok(PartialSource {
-            stack: self,
-            path,
-            scope,
-        })
-    }
+        /// An expression couldn't be evaluated.
+        #[snafu(display("`{}` couldn't be evaluated", expression))]
+        #[non_exhaustive]
+        InvalidExpression {
+            /// The underlying source of this error.
+            #[snafu(backtrace)]
+            source: ExpressionError,
 
-    fn peek(&mut self) -> Option<&mut Source> {
-        self.sources.last_mut()
-    }
-}
+            /// The expression, as written.
+            expression: String,
 
-impl<W> SourceStack<W>
-where
-    W: Write,
-{
-    fn pop(&mut self) -> Result<(), Error> {
-        let popped = self.sources.pop().unwrap();
+            /// Where the expression is.
+            site: Box<Site>,
+        },
 
-        if self.sources.is_empty() {
-            self.root = None;
-        }
+        /// A template couldn't be rendered into assembly.
+        #[snafu(display("template `{}` couldn't be rendered: {}", path.display(), message))]
+        #[non_exhaustive]
+        Template {
+            /// Path to the template.
+            path: PathBuf,
 
-        let mut asm = match popped.scope {
-            Scope::Independent(a) => a,
-            Scope::Same => return Ok(()),
-        };
+            /// What went wrong.
+            message: String,
 
-        let raw = asm.take();
-        asm.finish()?;
+            /// Where in the template it went wrong, if known.
+            span: Option<Span>,
 
-        if raw.is_empty() {
-            return Ok(());
-        }
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-        if self.sources.is_empty() {
-            self.output.write_all(&raw).context(error::Io {
-                message: "writing output",
-                path: None,
-            })?;
-            Ok(())
-        } else {
-            self.write(RawOp::Raw(raw))
-        }
-    }
+        /// A script run by `%script` failed, or returned something that isn't
+        /// code.
+        #[snafu(display("script `{}` failed: {}", path.display(), message))]
+        #[non_exhaustive]
+        Script {
+            /// Path to the script.
+            path: PathBuf,
 
-    fn write(&mut self, mut op: RawOp) -> Result<(), Error> {
-        if self.sources.is_empty() {
-            panic!("no sources!");
-        }
+            /// What went wrong.
+            message: String,
 
-        for frame in self.sources[1..].iter_mut().rev() {
-            let asm = match frame.scope {
-                Scope::Same => continue,
-                Scope::Independent(ref mut a) => a,
-            };
+            /// Where in the script it went wrong, if known.
+            span: Option<Span>,
 
-            if 0 == asm.push(op)? {
-                return Ok(());
-            } else {
-                op = RawOp::Raw(asm.take());
-            }
-        }
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
 
-        let first_asm = match self.sources[0].scope {
-            Scope::Independent(ref mut a) => a,
-            Scope::Same => panic!("sources[0] must be independent"),
-        };
+        /// Only the runtime was asked for, but the input doesn't include
+        /// anything to be the runtime.
+        #[snafu(display("`{}` doesn't include a runtime", path.display()))]
+        #[non_exhaustive]
+        NoRuntime {
+            /// Path to the input.
+            path: PathBuf,
 
-        first_asm.push(op)?;
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+    }
 
-        Ok(())
+    /// The error that can arise while parsing a [`super::Dialect`] from a
+    /// string.
+    #[derive(Debug, Snafu)]
+    #[snafu(display("unknown dialect: {}", text))]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub struct UnknownDialectError {
+        text: String,
+        backtrace: Backtrace,
     }
 }
 
-/// A high-level interface for assembling files into EVM bytecode.
+use crate::artifact;
+use crate::asm::{Assembler, Error as AssembleError, RawOp};
+use crate::ast::Node;
+use crate::buildinfo::BuildInfo;
+use crate::codes::{self, Code};
+use crate::lint::{Level, Levels, Linter, Mark, Replay, Sink, Warning};
+use crate::ops::{
+    AbstractOp, Experimental, Expression, ExpressionError, Fork, Lookup, Profile, Specifier,
+};
+use crate::parse::huff::{parse_huff, Definitions};
+use crate::parse::{is_anonymous, parse_located, Limits, Parsed};
+use crate::script::{self, Output};
+use crate::symbol::Interner;
+use crate::visit::Fold;
+use crate::Span;
+
+pub use self::error::{Error, UnknownDialectError};
+
+impl Error {
+    /// The stable code identifying this kind of error.
+    ///
+    /// See [`crate::codes`] for more information.
+    pub fn code(&self) -> Code {
+        match self {
+            Error::DirectoryTraversal { .. } => codes::DIRECTORY_TRAVERSAL,
+            Error::Io { .. } => codes::IO,
+            Error::Parse { source } => source.code(),
+            Error::Assemble { source } => source.code(),
+            Error::InvalidHex { .. } => codes::INVALID_HEX,
+            Error::InvalidArtifact { .. } => codes::INVALID_ARTIFACT,
+            Error::RecursionLimit { .. } => codes::RECURSION_LIMIT,
+            Error::UnsupportedInstruction { .. } => codes::UNSUPPORTED_INSTRUCTION,
+            Error::DefineTooLarge { .. } => codes::DEFINE_TOO_LARGE,
+            Error::UndeclaredExport { .. } => codes::UNDECLARED_EXPORT,
+            Error::DuplicateLabel { .. } => codes::DUPLICATE_LABEL,
+            Error::UndeclaredLabels { .. } => codes::UNDECLARED_LABEL,
+            Error::DefineConflict { .. } => codes::DEFINE_CONFLICT,
+            Error::AssertionFailed { .. } => codes::ASSERTION_FAILED,
+            Error::InvalidExpression {
+                source: ExpressionError::CodeUnavailable { .. },
+                ..
+            } => codes::UNHASHABLE_CODE,
+            Error::InvalidExpression { .. } => codes::INVALID_EXPRESSION,
+            Error::ExpressionTooLarge { .. } => codes::IMMEDIATE_TOO_LARGE,
+            Error::NegativeValue { .. } => codes::NEGATIVE_VALUE,
+            Error::Template { .. } => codes::TEMPLATE,
+            Error::Script { .. } => codes::SCRIPT,
+            Error::NoRuntime { .. } => codes::NO_RUNTIME,
+        }
+    }
+
+    /// A one line suggestion for fixing this error.
+    pub fn help(&self) -> &'static str {
+        codes::explain(self.code()).unwrap().help
+    }
+
+    /// The location of the error in the source, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Parse { source } => source.span(),
+            Error::Assemble {
+                source: AssembleError::ParseInclude { source },
+            } => source.span(),
+            Error::DuplicateLabel { second, .. } => second.span,
+            Error::UndeclaredLabels { labels, .. } => labels[0].sites[0].span,
+            Error::AssertionFailed { site, .. }
+            | Error::InvalidExpression { site, .. }
+            | Error::ExpressionTooLarge { site, .. }
+            | Error::NegativeValue { site, .. } => site.span,
+            Error::Template { span, .. } | Error::Script { span, .. } => *span,
+            _ => None,
+        }
+    }
+
+    /// Where in the source the error happened, if it's tied to one place.
+    ///
+    /// Unlike [`Error::span`], the site says which file the location is in,
+    /// and how the code there came to be assembled.
+    pub fn site(&self) -> Option<&Site> {
+        match self {
+            Error::DuplicateLabel { second, .. } => Some(second),
+            Error::UndeclaredLabels { labels, .. } => Some(&labels[0].sites[0]),
+            Error::AssertionFailed { site, .. }
+            | Error::InvalidExpression { site, .. }
+            | Error::ExpressionTooLarge { site, .. }
+            | Error::NegativeValue { site, .. } => Some(site),
+            _ => None,
+        }
+    }
+}
+
+/// Where a label was declared or used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Site {
+    /// The file containing the declaration or use.
+    pub file: PathBuf,
+
+    /// The location of the declaration or use in `file`, if known.
+    pub span: Option<Span>,
+
+    /// The files that imported or included `file`, starting with the one
+    /// that imported or included it directly.
+    pub included_from: Vec<PathBuf>,
+
+    /// The aliases and directives that were expanded to get to `span`,
+    /// innermost first.
+    ///
+    /// An alias is reported at its declaration, and expanded from wherever
+    /// it was used. A file is expanded from the directive, like `%include`,
+    /// that brought it in.
+    pub expanded_from: Vec<Expansion>,
+}
+
+/// One step in the expansion that produced the code at a [`Site`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Expansion {
+    /// The name of the alias, or of the directive (like `%include`), that was
+    /// expanded.
+    pub name: String,
+
+    /// The file the alias or directive was used in.
+    pub file: PathBuf,
+
+    /// The location of the alias or directive in `file`, if known.
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Expansion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` at {}", self.name, self.file.display())?;
+
+        if let Some(span) = self.span {
+            write!(f, ":{}:{}", span.start.0, span.start.1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A label that was used, but never declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Unresolved {
+    /// The name of the label.
+    pub label: String,
+
+    /// Everywhere the label was used, in order.
+    pub sites: Vec<Site>,
+
+    /// Declared labels with similar names, closest first.
+    pub suggestions: Vec<String>,
+}
+
+/// The size of the code a source assembles to, and where its labels are, as
+/// worked out by [`Ingest::estimate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Estimate {
+    /// The number of bytes the output would have.
+    pub size: usize,
+
+    /// Labels declared by the input and the modules it imports, like
+    /// [`Ingest::symbols`].
+    pub symbols: Vec<Symbol>,
+}
+
+/// What sort of label a [`Symbol`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SymbolKind {
+    /// A named label, including one exported by an imported module.
+    Label,
+
+    /// A label that an imported module didn't export.
+    Private,
+
+    /// A numeric label, like `1:`.
+    Anonymous,
+}
+
+impl SymbolKind {
+    /// A short lowercase name for the kind.
+    pub fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Label => "label",
+            SymbolKind::Private => "private",
+            SymbolKind::Anonymous => "anonymous",
+        }
+    }
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// A label in the assembled code, as listed by [`Ingest::symbols`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Symbol {
+    /// The name the label is used by. Private and anonymous labels have the
+    /// name they were declared with, which may not be unique.
+    pub name: String,
+
+    /// Where the label is, in bytes from the start of the output.
+    pub offset: usize,
+
+    /// What sort of label it is.
+    pub kind: SymbolKind,
+
+    /// Where the label was declared.
+    pub site: Site,
+}
+
+impl Symbol {
+    fn new(label: &str, offset: usize, site: Site) -> Self {
+        // Private labels are renamed to `import.<n>.<label>`.
+        let (private, label) = match label.strip_prefix("import.") {
+            Some(rest) => match rest.find('.') {
+                Some(dot) => (true, &rest[dot + 1..]),
+                None => (false, label),
+            },
+            None => (false, label),
+        };
+
+        // Anonymous labels are renamed to `anon.<number>.<index>`.
+        let (kind, name) = if is_anonymous(label) {
+            let number = label.split('.').nth(1).unwrap();
+            (SymbolKind::Anonymous, number)
+        } else if private {
+            (SymbolKind::Private, label)
+        } else {
+            (SymbolKind::Label, label)
+        };
+
+        Self {
+            name: name.to_owned(),
+            offset,
+            kind,
+            site,
+        }
+    }
+}
+
+fn undeclared(labels: &[Unresolved]) -> String {
+    if let [only] = labels {
+        return format!("label `{}` was never defined", only.label);
+    }
+
+    let names: Vec<_> = labels.iter().map(|l| format!("`{}`", l.label)).collect();
+    format!(
+        "{} labels were never defined: {}",
+        labels.len(),
+        names.join(", ")
+    )
+}
+
+/// The number of single character insertions, deletions, substitutions, and
+/// swaps of adjacent characters needed to turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // `d[i][j]` is the distance between the first `i` characters of `a` and
+    // the first `j` characters of `b`.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+
+            let mut best = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[i - 2][j - 2] + 1);
+            }
+
+            d[i][j] = best;
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Names from `candidates` close enough to `name` to be a typo, closest
+/// first.
+fn suggest<'a, I>(name: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    const MAX_SUGGESTIONS: usize = 3;
+
+    // Short names are too close to too many others to guess at.
+    let limit = name.chars().count() / 3;
+
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        // Private labels of imported modules can't be used anyway.
+        .filter(|c| !c.starts_with("import.") && !is_anonymous(c))
+        .map(|c| (distance(name, c), c))
+        .filter(|(d, _)| 0 < *d && *d <= limit)
+        .collect();
+
+    close.sort_unstable();
+    close.dedup();
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_owned())
+        .collect()
+}
+
+use num_bigint::{BigInt, Sign};
+
+use num_traits::Zero;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{ensure, OptionExt, ResultExt};
+
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use tracing::{debug, debug_span, info_span, trace};
+
+type Hash = [u8; 32];
+
+fn hash<T: AsRef<[u8]>>(data: T) -> Hash {
+    Keccak256::digest(data.as_ref()).into()
+}
+
+/// Number of hex digits decoded by each task in `decode_hex`.
+const HEX_CHUNK: usize = 1 << 16;
+
+/// `text` without a `0x` prefix, or any whitespace.
+fn normalize_hex(text: &[u8]) -> Cow<'_, [u8]> {
+    let start = text
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let mut text = &text[start..];
+
+    if text.len() >= 2 && text[..2].eq_ignore_ascii_case(b"0x") {
+        text = &text[2..];
+    }
+
+    if text.iter().any(u8::is_ascii_whitespace) {
+        Cow::Owned(
+            text.iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Decode `text` as hexadecimal, a chunk at a time.
+///
+/// A `0x` prefix and whitespace are ignored, since hex copied from elsewhere
+/// rarely arrives as one clean line.
+///
+/// With the `parallel` feature, chunks are decoded on rayon's thread pool.
+fn decode_hex(text: &[u8]) -> Result<Vec<u8>, hex::FromHexError> {
+    let text = &*normalize_hex(text);
+    let mut out = vec![0u8; text.len() / 2];
+
+    let decode = |(src, dst): (&[u8], &mut [u8])| hex::decode_to_slice(src, dst);
+
+    #[cfg(feature = "parallel")]
+    let result = {
+        use rayon::prelude::*;
+
+        text.par_chunks(HEX_CHUNK)
+            .zip(out.par_chunks_mut(HEX_CHUNK / 2))
+            .try_for_each(decode)
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    let result = text
+        .chunks(HEX_CHUNK)
+        .zip(out.chunks_mut(HEX_CHUNK / 2))
+        .try_for_each(decode);
+
+    // Chunks don't know their offset, and may fail in any order, so decode
+    // everything at once to report the first problem (including an odd
+    // length.)
+    if result.is_err() {
+        hex::decode_to_slice(text, &mut out)?;
+    }
+
+    Ok(out)
+}
+
+/// The contents of a file, either read into memory or, with the `mmap`
+/// feature, mapped from disk.
+#[derive(Debug)]
+enum Contents {
+    Read(Vec<u8>),
+
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Read(data) => data,
+
+            #[cfg(feature = "mmap")]
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+impl Contents {
+    /// The contents as text, or an `InvalidData` error if they aren't UTF-8.
+    fn text(&self) -> io::Result<&str> {
+        std::str::from_utf8(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Files smaller than this are copied into memory, since mapping them costs
+/// more than reading them.
+#[cfg(feature = "mmap")]
+const MMAP_THRESHOLD: u64 = 1 << 16;
+
+#[cfg(feature = "mmap")]
+#[allow(unsafe_code)]
+fn read_file(path: &Path) -> io::Result<Contents> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    if len < MMAP_THRESHOLD {
+        let mut data = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut data)?;
+        return Ok(Contents::Read(data));
+    }
+
+    // SAFETY: Changing the file while it's mapped is undefined behaviour. The
+    //         map only lives while the file is hashed and parsed (or decoded),
+    //         and source files aren't expected to change during a build.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(Contents::Mapped(map))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn read_file(path: &Path) -> io::Result<Contents> {
+    std::fs::read(path).map(Contents::Read)
+}
+
+/// The canonical form of `path`, or `path` itself if it can't be
+/// canonicalized (usually because it doesn't exist.)
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Rename the labels declared by an imported module, so the importing source
+/// can only use the ones the module exports (or all of them, if it has no
+/// `%export` directives), prefixed with `alias` if there is one.
+///
+/// Labels that aren't exported are renamed to `import.n.label`, where `n`
+/// counts imports, which can't be written in source. Anonymous labels are
+/// never exported.
+fn interface(nodes: Vec<Node>, alias: Option<&str>, n: usize) -> Result<Vec<Node>, Error> {
+    let mut exports: Option<BTreeSet<&str>> = None;
+    let mut declared: HashSet<&Arc<str>> = HashSet::new();
+
+    for node in &nodes {
+        match node {
+            Node::Export(names) => exports
+                .get_or_insert_with(BTreeSet::new)
+                .extend(names.iter().map(String::as_str)),
+            Node::Op(AbstractOp::Label(lbl)) => {
+                declared.insert(lbl);
+            }
+            _ => (),
+        }
+    }
+
+    if exports.is_none() && alias.is_none() && !declared.iter().any(|l| is_anonymous(l)) {
+        return Ok(nodes);
+    }
+
+    if let Some(ref exports) = exports {
+        for name in exports {
+            ensure!(
+                declared.iter().any(|d| d.as_ref() == *name),
+                error::UndeclaredExport { name: *name }
+            );
+        }
+    }
+
+    let renames: HashMap<Arc<str>, Arc<str>> = declared
+        .into_iter()
+        .map(|lbl| {
+            let public = match exports {
+                _ if is_anonymous(lbl) => false,
+                Some(ref e) => e.contains(lbl.as_ref()),
+                None => true,
+            };
+
+            let renamed = match (public, alias) {
+                (true, Some(a)) => format!("{}.{}", a, lbl),
+                (true, None) => lbl.to_string(),
+                (false, _) => format!("import.{}.{}", n, lbl),
+            };
+
+            (lbl.clone(), renamed.into())
+        })
+        .collect();
+
+    let mut rename = Rename(&renames);
+
+    let renamed = nodes
+        .iter()
+        .map(|node| match node {
+            Node::Op(op) => Node::Op(rename.fold_abstract_op(op.clone())),
+            _ => {
+                let mut node = node.clone();
+                if let Some(expression) = node.expression_mut() {
+                    expression.relabel(&|l| renames.get(l).cloned());
+                }
+                node
+            }
+        })
+        .collect();
+
+    Ok(renamed)
+}
+
+/// Renames the labels of an imported module, declarations and uses alike.
+struct Rename<'a>(&'a HashMap<Arc<str>, Arc<str>>);
+
+impl Rename<'_> {
+    fn get(&self, label: Arc<str>) -> Arc<str> {
+        match self.0.get(&label) {
+            Some(new) => new.clone(),
+            None => label,
+        }
+    }
+}
+
+impl Fold for Rename<'_> {
+    fn fold_label(&mut self, label: Arc<str>) -> Arc<str> {
+        self.get(label)
+    }
+
+    fn fold_label_use(&mut self, label: Arc<str>) -> Arc<str> {
+        self.get(label)
+    }
+}
+
+fn record(files: &mut Vec<PathBuf>, path: &Path) {
+    if !files.iter().any(|p| p == path) {
+        files.push(path.to_owned());
+    }
+}
+
+/// Decides which file an `%import` or `%include` refers to.
+///
+/// Whatever file is chosen must still be inside the directory of the file
+/// being assembled.
+pub trait Resolver: fmt::Debug + Send + Sync {
+    /// The file that `path`, as written in the source file `from`, refers to,
+    /// or `None` to look for it relative to the directory containing `from`.
+    fn resolve(&self, from: &Path, path: &Path) -> Option<PathBuf>;
+}
+
+/// The language of the source being assembled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// The assembly language described in The ETK Book.
+    Etk,
+
+    /// The core of [Huff](https://huff.sh/): `#define macro`, `#define
+    /// constant`, `#include`, and jump labels.
+    ///
+    /// The `MAIN` macro is expanded to produce the code. Labels inside other
+    /// macros are renamed to `MACRO.n.label`, where `n` counts invocations, so
+    /// they don't clash when a macro is used more than once.
+    Huff,
+}
+
+impl Dialect {
+    fn name(self) -> &'static str {
+        match self {
+            Dialect::Etk => "etk",
+            Dialect::Huff => "huff",
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = UnknownDialectError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "etk" => Ok(Dialect::Etk),
+            "huff" => Ok(Dialect::Huff),
+            _ => error::UnknownDialectContext { text }.fail(),
+        }
+    }
+}
+
+/// What parsing a source depends on: its text, and the options that change
+/// what it parses to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ParseKey {
+    text: Hash,
+    limits: Limits,
+    experimental: Experimental,
+}
+
+/// The largest address a label is assumed to have when sizing `%push` of an
+/// expression: the size of the largest init code allowed (see EIP-3860.)
+const MAX_LABEL: u32 = 0xc000;
+
+/// Settings that change how instructions are assembled.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Options {
+    fork: Fork,
+    defines: BTreeMap<String, Vec<u8>>,
+    limits: Limits,
+    optimize_size: bool,
+    experimental: Experimental,
+    profile: Profile,
+}
+
+impl Options {
+    /// The key that `text`, parsed with these options, is cached under.
+    fn parse_key(&self, text: &str) -> ParseKey {
+        ParseKey {
+            text: hash(text),
+            limits: self.limits,
+            experimental: self.experimental.clone(),
+        }
+    }
+
+    /// The narrowest push that holds every value `expression` could have,
+    /// with defines at their values and labels up to [`MAX_LABEL`].
+    fn push_for(&self, expression: &Expression) -> Specifier {
+        let bound = expression.bound(&|label: &str| match self.defines.get(label) {
+            Some(value) => BigInt::from_bytes_be(Sign::Plus, value),
+            None => MAX_LABEL.into(),
+        });
+
+        let width = std::cmp::min(bound.to_bytes_be().1.len(), 32);
+        Specifier::push(width as u32).unwrap()
+    }
+
+    /// Check `op` against the target fork, and replace any define used as its
+    /// immediate argument.
+    fn apply(&self, op: AbstractOp) -> Result<AbstractOp, Error> {
+        if let AbstractOp::Label(ref name) = op {
+            ensure!(
+                !self.defines.contains_key(name.as_ref()),
+                error::DefineConflict {
+                    name: name.as_ref()
+                }
+            );
+        }
+
+        if let Some(spec) = op.specifier() {
+            ensure!(
+                spec.is_available_in(self.fork),
+                error::UnsupportedInstruction {
+                    op: spec,
+                    fork: self.fork,
+                }
+            );
+        }
+
+        let (name, value) = match op.immediate_label() {
+            Some(label) => match self.defines.get_key_value(label) {
+                Some(d) => d,
+                None => return Ok(op),
+            },
+            None => return Ok(op),
+        };
+
+        let mut trimmed = value.as_slice();
+        while trimmed.len() > 1 && trimmed[0] == 0 {
+            trimmed = &trimmed[1..];
+        }
+
+        let spec = match op.specifier() {
+            Some(s) => s,
+            None => Specifier::push(trimmed.len() as u32).context(error::DefineTooLarge {
+                name,
+                op: Specifier::Push32(()),
+            })?,
+        };
+
+        let width = spec.size() as usize - 1;
+        ensure!(
+            trimmed.len() <= width,
+            error::DefineTooLarge { name, op: spec }
+        );
+
+        let mut imm = vec![0u8; width - trimmed.len()];
+        imm.extend_from_slice(trimmed);
+
+        Ok(AbstractOp::with_immediate(spec, &imm).unwrap())
+    }
+}
+
+/// The assembled output of an included file, and the contents of every file
+/// that went into it.
+#[derive(Debug, Clone)]
+struct Included {
+    root: PathBuf,
+    options: Options,
+    deps: Vec<(PathBuf, Hash)>,
+    raw: Vec<u8>,
+
+    /// The labels it declared, for the symbol map.
+    symbols: Vec<Symbol>,
+
+    /// What the linter found in it, reported again whenever it's reused.
+    lints: Replay,
+
+    /// Whether it was linted at all, which it isn't while estimating.
+    linted: bool,
+
+    /// Whether a `%buildinfo` directive was expanded while assembling it.
+    buildinfo: bool,
+
+    /// Whether it contains a `%pragma(once)` directive.
+    once: bool,
+}
+
+impl Included {
+    fn is_fresh(&self, root: &Path, options: &Options) -> bool {
+        self.root == root
+            && self.options == *options
+            && self
+                .deps
+                .iter()
+                .all(|(path, expected)| match read_file(path) {
+                    Ok(data) => hash(&*data) == *expected,
+                    Err(_) => false,
+                })
+    }
+}
+
+/// Parsed and assembled files that can be reused by later builds.
+///
+/// Parsed files and hex includes are keyed on the hash of their contents.
+/// Included files are keyed on their path, and are only reused if none of the
+/// files they read, directly or through their own includes and imports, have
+/// changed.
+///
+/// Only entries used by the most recent successful build are kept.
 ///
 /// ## Example
 ///
 /// ```rust
-/// use etk_asm::ingest::Ingest;
+/// use etk_asm::ingest::{Cache, Ingest};
 /// #
 /// # use etk_asm::ingest::Error;
-/// #
-/// # use hex_literal::hex;
-///
-/// let text = r#"
-///     push2 lbl
-///     lbl:
-///     jumpdest
-/// "#;
 ///
-/// let mut output = Vec::new();
-/// let mut ingest = Ingest::new(&mut output);
-/// ingest.ingest("./example.etk", &text)?;
+/// let mut cache = Cache::new();
 ///
-/// # let expected = hex!("6100035b");
-/// # assert_eq!(output, expected);
+/// for _ in 0..2 {
+///     let mut output = Vec::new();
+///     let mut ingest = Ingest::with_cache(&mut output, cache);
+///     ingest.ingest("./example.etk", "push1 42")?;
+///     cache = ingest.into_cache();
+/// #   assert_eq!(output, [0x60, 0x2a]);
+/// }
 /// # Result::<(), Error>::Ok(())
 /// ```
-#[derive(Debug)]
-pub struct Ingest<W> {
-    sources: SourceStack<W>,
+#[derive(Debug, Default)]
+pub struct Cache {
+    parsed: HashMap<ParseKey, Parsed>,
+    hex: HashMap<Hash, Vec<u8>>,
+    included: HashMap<PathBuf, Included>,
+}
+
+impl Cache {
+    /// Make a new, empty `Cache`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn merge(&mut self, older: Self) {
+        for (key, nodes) in older.parsed {
+            self.parsed.entry(key).or_insert(nodes);
+        }
+
+        for (key, raw) in older.hex {
+            self.hex.entry(key).or_insert(raw);
+        }
+
+        for (key, included) in older.included {
+            self.included.entry(key).or_insert(included);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Scope {
+    Same,
+    Independent(Box<Assembler>),
+}
+
+impl Scope {
+    fn same() -> Self {
+        Self::Same
+    }
+
+    fn independent() -> Self {
+        Self::Independent(Box::new(Assembler::new()))
+    }
+}
+
+/// The directive that brought a source in, and where it's written in the
+/// enclosing source.
+#[derive(Debug, Clone, Copy)]
+struct Directive {
+    name: &'static str,
+    span: Option<Span>,
+}
+
+impl Directive {
+    fn new(name: &'static str, span: Option<Span>) -> Self {
+        Self { name, span }
+    }
+}
+
+#[derive(Debug)]
+struct Source {
+    path: PathBuf,
+    nodes: std::vec::IntoIter<Node>,
+
+    /// How this source was brought in, or `None` for the root.
+    directive: Option<Directive>,
+    scope: Scope,
+    first_read: usize,
+    first_buildinfo: usize,
+    first_lint: Mark,
+
+    /// The labels of this source, when its code was reused from the cache
+    /// instead of assembled.
+    symbols: Option<Vec<Symbol>>,
+
+    /// Bytes already passed on to the enclosing source, kept for the cache.
+    taken: Vec<u8>,
+
+    /// Bytes already written to the output while streaming, which aren't
+    /// kept anywhere.
+    streamed: usize,
+
+    /// Whether to keep the assembled bytes until this source is popped,
+    /// because some of them still have to be patched.
+    held: bool,
+
+    /// Identifies the label namespace this source belongs to.
+    unit: usize,
+}
+
+/// A push of an expression's value, waiting for the labels in its scope to
+/// have addresses.
+#[derive(Debug)]
+struct Patch {
+    unit: usize,
+
+    /// The hidden label declared just before the push, which gives its
+    /// address.
+    label: Arc<str>,
+
+    spec: Specifier,
+    expression: Expression,
+    site: Site,
+}
+
+/// Looks up labels, and the code, of a scope that has finished assembling.
+struct Assembled<'a> {
+    defines: &'a BTreeMap<String, Vec<u8>>,
+    symbols: &'a Interner,
+    asm: &'a Assembler,
+
+    /// Bytes written to the output before `code`, which weren't kept.
+    streamed: usize,
+
+    code: &'a [u8],
+
+    /// The immediates of pushes that haven't been patched yet, which can't be
+    /// hashed.
+    unpatched: &'a [(usize, usize)],
+}
+
+impl Lookup for Assembled<'_> {
+    fn label(&self, label: &str) -> Option<BigInt> {
+        match self.defines.get(label) {
+            Some(value) => Some(BigInt::from_bytes_be(Sign::Plus, value)),
+            None => self.asm.address(self.symbols, label).map(BigInt::from),
+        }
+    }
+
+    fn code(&self, start: usize, end: usize) -> Option<&[u8]> {
+        if self.unpatched.iter().any(|(s, e)| start < *e && *s < end) {
+            return None;
+        }
+
+        let start = start.checked_sub(self.streamed)?;
+        let end = end.checked_sub(self.streamed)?;
+        self.code.get(start..end)
+    }
+}
+
+/// An `%assert`, waiting for the labels in its scope to have addresses.
+#[derive(Debug)]
+struct Assertion {
+    unit: usize,
+    expression: Expression,
+    message: String,
+    site: Site,
+}
+
+#[derive(Debug)]
+struct Root {
+    original: PathBuf,
+    canonicalized: PathBuf,
+}
+
+impl Root {
+    fn new(mut file: PathBuf) -> Result<Self, Error> {
+        // Pop the filename.
+        if !file.pop() {
+            return Err(io::Error::from(io::ErrorKind::NotFound)).context(error::Io {
+                message: "no parent",
+                path: Some(file),
+            });
+        }
+
+        let file = std::env::current_dir()
+            .context(error::Io {
+                message: "getting cwd",
+                path: None,
+            })?
+            .join(file);
+
+        let metadata = file.metadata().with_context(|| error::Io {
+            message: "getting metadata",
+            path: file.clone(),
+        })?;
+
+        // Root must be a directory.
+        if !metadata.is_dir() {
+            let err = io::Error::from(io::ErrorKind::NotFound);
+            return Err(err).context(error::Io {
+                message: "root is not directory",
+                path: file,
+            });
+        }
+
+        let canonicalized = std::fs::canonicalize(&file).with_context(|| error::Io {
+            message: "canonicalizing root",
+            path: file.clone(),
+        })?;
+
+        Ok(Self {
+            original: file,
+            canonicalized,
+        })
+    }
+
+    fn check<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        let canonicalized = std::fs::canonicalize(path).with_context(|| error::Io {
+            message: "canonicalizing include/import",
+            path: path.to_owned(),
+        })?;
+
+        // Don't allow directory traversals above the first file.
+        if canonicalized.starts_with(&self.canonicalized) {
+            Ok(())
+        } else {
+            error::DirectoryTraversal {
+                root: self.original.clone(),
+                file: path.to_owned(),
+            }
+            .fail()
+        }
+    }
+}
+
+#[must_use]
+struct PartialSource<'a, W> {
+    stack: &'a mut SourceStack<W>,
+    path: PathBuf,
+    scope: Scope,
+    directive: Option<Directive>,
+    first_read: usize,
+    first_buildinfo: usize,
+    first_lint: Mark,
+}
+
+impl<'a, W> PartialSource<'a, W> {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record this source as the one that failed if `result` is an error.
+    fn blame<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        if result.is_err() {
+            self.stack.failed = Some(self.path.clone());
+        }
+        result
+    }
+
+    /// Read the file, and record its hash.
+    fn read(&mut self, message: &'static str) -> Result<(Contents, Hash), Error> {
+        let result = read_file(&self.path).with_context(|| error::Io {
+            message,
+            path: self.path.clone(),
+        });
+        let contents = self.blame(result)?;
+
+        let key = hash(&*contents);
+        self.stack.reads.push((self.path.clone(), key));
+        Ok((contents, key))
+    }
+
+    /// Read the file as hexadecimal, and decode it.
+    fn read_hex(&mut self) -> Result<Vec<u8>, Error> {
+        let (text, key) = self.read("reading hex include")?;
+
+        let result = self
+            .stack
+            .decode_hex(key, &text)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+            .context(error::InvalidHex {
+                path: self.path.clone(),
+            });
+
+        self.blame(result)
+    }
+
+    /// Read the file as a JSON artifact, and extract its bytecode.
+    fn read_artifact(&mut self, deployed: bool) -> Result<Vec<u8>, Error> {
+        let (json, _) = self.read("reading artifact")?;
+
+        let result = artifact::bytecode(&json, deployed).context(error::InvalidArtifact {
+            path: self.path.clone(),
+        });
+
+        self.blame(result)
+    }
+
+    /// Run the file as a script, and parse or wrap up what it returns.
+    fn run_script(&mut self) -> Result<Vec<Node>, Error> {
+        let message = "reading script";
+        let (contents, _) = self.read(message)?;
+        let result = contents.text().with_context(|| error::Io {
+            message,
+            path: self.path.clone(),
+        });
+        let text = self.blame(result)?;
+
+        let result = match script::run(text) {
+            Ok(Output::Text(src)) => self.stack.parse(&self.path, &src),
+            Ok(Output::Bytes(raw)) => Ok(vec![Node::Raw(raw)]),
+            Err(failure) => error::Script {
+                path: self.path.clone(),
+                message: failure.message,
+                span: failure.span,
+            }
+            .fail(),
+        };
+
+        self.blame(result)
+    }
+
+    fn parse(&mut self) -> Result<Vec<Node>, Error> {
+        let message = "reading file before parsing";
+        let (contents, _) = self.read(message)?;
+        let result = contents.text().with_context(|| error::Io {
+            message,
+            path: self.path.clone(),
+        });
+        let text = self.blame(result)?;
+
+        let result = self.stack.parse(&self.path, text);
+        let nodes = self.blame(result)?;
+
+        #[cfg(feature = "parallel")]
+        self.stack.prefetch(&self.path, &nodes);
+
+        Ok(nodes)
+    }
+
+    /// Parse the file as an imported module, renaming its labels to match its
+    /// `%export` directives and `alias`.
+    fn parse_module(&mut self, alias: Option<&str>) -> Result<Vec<Node>, Error> {
+        let nodes = self.parse()?;
+
+        self.stack.imports += 1;
+        let result = interface(nodes, alias, self.stack.imports);
+        self.blame(result)
+    }
+
+    /// Look for the assembled output of this source from an earlier build.
+    fn cached(&mut self) -> Option<Included> {
+        let included = match self.stack.cache.included.get(&self.path) {
+            Some(i) => i.clone(),
+            None => self.stack.previous.included.remove(&self.path)?,
+        };
+
+        let root = &self.stack.root.as_ref()?.canonicalized;
+        if !included.is_fresh(root, &self.stack.options) {
+            return None;
+        }
+
+        // Only an estimate can reuse code assembled without linting it.
+        if !included.linted && !self.stack.estimating {
+            return None;
+        }
+
+        // Anything enclosing this source depends on the same files.
+        self.stack.reads.extend(included.deps.iter().cloned());
+
+        if included.buildinfo {
+            self.stack.buildinfo += 1;
+        }
+
+        if included.once {
+            self.stack.once.insert(canonical(&self.path));
+        }
+
+        Some(included)
+    }
+
+    /// Whether this source has already been expanded, and asked not to be
+    /// expanded again with `%pragma(once)`.
+    fn is_done(&self) -> bool {
+        self.stack.once.contains(&canonical(&self.path))
+    }
+
+    fn push(self, mut nodes: Vec<Node>) -> &'a mut Source {
+        // Math on labels might count on the width of the pushes between
+        // them, so files that do any are left alone.
+        if self.stack.options.optimize_size && !nodes.iter().any(Node::has_label_math) {
+            for node in &mut nodes {
+                if let Node::Op(op) = node {
+                    if let Some(shrunk) = op.shrunk() {
+                        *op = shrunk;
+                    }
+                }
+            }
+        }
+
+        let unit = match self.scope {
+            Scope::Independent(_) => {
+                self.stack.units += 1;
+                self.stack.units
+            }
+            Scope::Same => self.stack.sources.last().map(|s| s.unit).unwrap_or(0),
+        };
+
+        self.stack.sources.push(Source {
+            unit,
+            path: self.path,
+            nodes: nodes.into_iter(),
+            scope: self.scope,
+            directive: self.directive,
+            first_read: self.first_read,
+            first_buildinfo: self.first_buildinfo,
+            first_lint: self.first_lint,
+            symbols: None,
+            taken: Vec::new(),
+            streamed: 0,
+            held: false,
+        });
+
+        self.stack.sources.last_mut().unwrap()
+    }
+
+    /// Push `included`, the assembled output of this source from an earlier
+    /// build, reporting what the linter found in it again.
+    fn push_cached(self, included: Included) {
+        self.stack.linter.replay(&included.lints);
+
+        let source = self.push(vec![Node::Raw(included.raw)]);
+        source.symbols = Some(included.symbols);
+    }
+}
+
+#[derive(Debug)]
+struct SourceStack<W> {
+    output: W,
+    sources: Vec<Source>,
+    root: Option<Root>,
+    reads: Vec<(PathBuf, Hash)>,
+    buildinfo: usize,
+    previous: Cache,
+    cache: Cache,
+    options: Options,
+    failed: Option<PathBuf>,
+    units: usize,
+    linter: Linter,
+    symbols: Interner,
+    written: usize,
+    streaming: bool,
+
+    /// Only work out the size and labels, for [`Ingest::estimate`]: nothing
+    /// is written to the output, lints aren't run, and the expressions in
+    /// the outermost source aren't evaluated.
+    estimating: bool,
+
+    /// Canonical paths of the sources that contain `%pragma(once)`.
+    once: HashSet<PathBuf>,
+
+    /// Number of modules imported, used to keep their private labels apart.
+    imports: usize,
+
+    /// Where each label was declared, keyed on its unit and name.
+    labels: HashMap<(usize, Arc<str>), Site>,
+
+    /// Where each label that hasn't been declared yet was used, keyed on its
+    /// unit and name.
+    unresolved: BTreeMap<(usize, Arc<str>), Vec<Site>>,
+
+    /// Assertions that haven't been checked yet, in order.
+    assertions: Vec<Assertion>,
+
+    /// Pushes that haven't been patched yet, in order.
+    patches: Vec<Patch>,
+
+    /// How many pushes have been given a hidden label, used to name the next.
+    patched: usize,
+
+    /// Labels declared by the root source and its imports, once it's popped.
+    map: Vec<Symbol>,
+
+    /// Write only the code of the last source included directly by the
+    /// root, instead of the root's own code.
+    runtime: bool,
+
+    /// The code and labels of the last source included directly by the root,
+    /// once it's popped.
+    last_include: Option<(Vec<u8>, Vec<Symbol>)>,
+
+    /// The aliases the next located node was expanded from, innermost first,
+    /// with where each was used.
+    expanded: Vec<(String, Span)>,
+
+    resolver: Option<Arc<dyn Resolver>>,
 }
 
-impl<W> Ingest<W> {
-    /// Make a new `Ingest` that writes assembled bytes to `output`.
-    pub fn new(output: W) -> Self {
-        Self {
-            sources: SourceStack::new(output),
-        }
+impl<W> SourceStack<W> {
+    fn new(output: W, previous: Cache) -> Self {
+        Self {
+            output,
+            sources: Default::default(),
+            root: Default::default(),
+            reads: Default::default(),
+            buildinfo: Default::default(),
+            previous,
+            cache: Default::default(),
+            options: Default::default(),
+            failed: Default::default(),
+            units: Default::default(),
+            linter: Default::default(),
+            symbols: Default::default(),
+            written: Default::default(),
+            streaming: Default::default(),
+            estimating: Default::default(),
+            once: Default::default(),
+            imports: Default::default(),
+            labels: Default::default(),
+            unresolved: Default::default(),
+            assertions: Default::default(),
+            patches: Default::default(),
+            patched: Default::default(),
+            map: Default::default(),
+            runtime: Default::default(),
+            last_include: Default::default(),
+            expanded: Default::default(),
+            resolver: Default::default(),
+        }
+    }
+
+    /// Parse `src`, the contents of the file at `path`.
+    fn parse(&mut self, path: &Path, src: &str) -> Result<Vec<Node>, Error> {
+        let _span = debug_span!("parse", bytes = src.len()).entered();
+
+        let key = self.options.parse_key(src);
+
+        let parsed = match self.cache.parsed.get(&key) {
+            Some(p) => {
+                trace!("already parsed");
+                p.clone()
+            }
+            None => {
+                let parsed = match self.previous.parsed.remove(&key) {
+                    Some(p) => {
+                        trace!("parsed in a previous build");
+                        p
+                    }
+                    None => parse_located(src, self.options.limits, &self.options.experimental)?,
+                };
+
+                self.cache.parsed.insert(key, parsed.clone());
+                parsed
+            }
+        };
+
+        if !self.estimating {
+            for (signature, span) in &parsed.signatures {
+                self.linter.signature(signature, path, Some(*span));
+            }
+        }
+
+        Ok(parsed.nodes)
+    }
+
+    /// Parse the files imported and included by `nodes` on rayon's thread
+    /// pool, so they're ready by the time assembly reaches them.
+    ///
+    /// Only parsing is done in parallel. The files are still assembled one
+    /// after another, since where each one's labels end up depends on the
+    /// size of everything before it.
+    ///
+    /// Parsed files go into the previous build's cache, so they're only kept
+    /// if they're actually used. Failures are ignored here, and reported when
+    /// the file is reached.
+    #[cfg(feature = "parallel")]
+    fn prefetch(&mut self, parent: &Path, nodes: &[Node]) {
+        use rayon::prelude::*;
+
+        let root = match self.root {
+            Some(ref r) => r,
+            None => return,
+        };
+
+        let paths: Vec<_> = nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Import { path: p, .. } | Node::Include(p) => Some(self.locate(parent, p)),
+                _ => None,
+            })
+            .filter(|p| {
+                !self.cache.included.contains_key(p) && !self.previous.included.contains_key(p)
+            })
+            .collect();
+
+        // Nothing to gain from the thread pool for a single file.
+        if paths.len() < 2 {
+            return;
+        }
+
+        let _span = debug_span!("prefetch", files = paths.len()).entered();
+
+        let cache = &self.cache;
+        let previous = &self.previous;
+        let options = &self.options;
+
+        let parsed: Vec<_> = paths
+            .par_iter()
+            .filter_map(|path| {
+                root.check(path).ok()?;
+
+                let contents = read_file(path).ok()?;
+                let text = contents.text().ok()?;
+                let key = options.parse_key(text);
+
+                if cache.parsed.contains_key(&key) || previous.parsed.contains_key(&key) {
+                    return None;
+                }
+
+                let parsed = parse_located(text, options.limits, &options.experimental).ok()?;
+                Some((key, parsed))
+            })
+            .collect();
+
+        for (key, parsed) in parsed {
+            self.previous.parsed.entry(key).or_insert(parsed);
+        }
+    }
+
+    /// Decode the contents of a hex include, keyed on `key`, the hash of
+    /// `text`.
+    fn decode_hex(&mut self, key: Hash, text: &[u8]) -> Result<Vec<u8>, hex::FromHexError> {
+        let _span = debug_span!("decode_hex", bytes = text.len()).entered();
+
+        if let Some(raw) = self.cache.hex.get(&key) {
+            trace!("already decoded");
+            return Ok(raw.clone());
+        }
+
+        let raw = match self.previous.hex.remove(&key) {
+            Some(r) => {
+                trace!("decoded in a previous build");
+                r
+            }
+            None => decode_hex(text)?,
+        };
+
+        self.cache.hex.insert(key, raw.clone());
+        Ok(raw)
+    }
+
+    /// The file that `path`, as written in the source file `from`, refers to.
+    fn locate(&self, from: &Path, path: &Path) -> PathBuf {
+        if let Some(found) = self.resolver.as_ref().and_then(|r| r.resolve(from, path)) {
+            return found;
+        }
+
+        let dir = match from.parent() {
+            Some(s) => s,
+            None => Path::new("./"),
+        };
+
+        dir.join(path)
+    }
+
+    fn resolve(
+        &mut self,
+        path: PathBuf,
+        scope: Scope,
+        directive: Option<Directive>,
+    ) -> Result<PartialSource<W>, Error> {
+        ensure!(self.sources.len() <= 255, error::RecursionLimit);
+
+        let path = if let Some(ref root) = self.root {
+            let last = self.sources.last().unwrap();
+            let candidate = self.locate(&last.path, &path);
+            root.check(&candidate)?;
+            candidate
+        } else {
+            assert!(self.sources.is_empty());
+            self.root = Some(Root::new(path.clone())?);
+            path
+        };
+
+        Ok(PartialSource {
+            first_read: self.reads.len(),
+            first_buildinfo: self.buildinfo,
+            first_lint: self.linter.mark(),
+            stack: self,
+            path,
+            scope,
+            directive,
+        })
+    }
+
+    fn peek(&mut self) -> Option<&mut Source> {
+        self.sources.last_mut()
+    }
+
+    /// The location `span` in the innermost source.
+    fn site(&self, span: Option<Span>) -> Site {
+        let (source, enclosing) = self.sources.split_last().unwrap();
+
+        // The aliases only apply to the node that came with `span`.
+        let aliases = match span {
+            Some(_) => self.expanded.as_slice(),
+            None => &[],
+        };
+
+        let mut expanded_from: Vec<_> = aliases
+            .iter()
+            .map(|(name, used)| Expansion {
+                name: name.clone(),
+                file: source.path.clone(),
+                span: Some(*used),
+            })
+            .collect();
+
+        for (idx, inner) in self.sources.iter().enumerate().skip(1).rev() {
+            if let Some(directive) = inner.directive {
+                expanded_from.push(Expansion {
+                    name: directive.name.to_owned(),
+                    file: self.sources[idx - 1].path.clone(),
+                    span: directive.span,
+                });
+            }
+        }
+
+        Site {
+            file: source.path.clone(),
+            span,
+            included_from: enclosing.iter().rev().map(|s| s.path.clone()).collect(),
+            expanded_from,
+        }
+    }
+
+    /// Record the declaration of `label` by the innermost source, failing if
+    /// it was already declared in the same scope.
+    fn declare(&mut self, label: &Arc<str>, span: Option<Span>) -> Result<(), Error> {
+        let site = self.site(span);
+        let key = (self.sources.last().unwrap().unit, label.clone());
+
+        self.unresolved.remove(&key);
+
+        match self.labels.entry(key) {
+            Entry::Occupied(first) => error::DuplicateLabel {
+                label: label.as_ref(),
+                first: Box::new(first.get().clone()),
+                second: Box::new(site),
+            }
+            .fail(),
+            Entry::Vacant(v) => {
+                v.insert(site);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record a use of `label` by the innermost source, if it hasn't been
+    /// declared yet.
+    fn refer(&mut self, label: &Arc<str>, span: Option<Span>) {
+        let unit = self.sources.last().unwrap().unit;
+        self.linter.refer(&mut self.symbols, unit, label);
+
+        let key = (unit, label.clone());
+
+        if !self.labels.contains_key(&key) {
+            let site = self.site(span);
+            self.unresolved.entry(key).or_default().push(site);
+        }
+    }
+
+    /// Record an `%assert` in the innermost source, to be checked once the
+    /// labels in its scope have addresses.
+    fn assert(&mut self, expression: Expression, message: String, span: Option<Span>) {
+        self.refer_all(&expression, span);
+
+        self.assertions.push(Assertion {
+            unit: self.sources.last().unwrap().unit,
+            site: self.site(span),
+            expression,
+            message,
+        });
+    }
+
+    /// Record a use of each label in `expression`, other than defines.
+    fn refer_all(&mut self, expression: &Expression, span: Option<Span>) {
+        for label in expression.labels() {
+            if !self.options.defines.contains_key(label.as_ref()) {
+                self.refer(label, span);
+            }
+        }
+    }
+
+    /// Check the assertions in `unit`, now that `asm` knows the address of
+    /// every label in it. `code` is everything the unit assembled to, after
+    /// the first `streamed` bytes.
+    fn check_assertions(
+        &mut self,
+        unit: usize,
+        asm: &Assembler,
+        streamed: usize,
+        code: &[u8],
+    ) -> Result<(), Error> {
+        let (checked, rest) = std::mem::take(&mut self.assertions)
+            .into_iter()
+            .partition(|a: &Assertion| a.unit == unit);
+
+        self.assertions = rest;
+
+        let lookup = Assembled {
+            defines: &self.options.defines,
+            symbols: &self.symbols,
+            asm,
+            streamed,
+            code,
+            unpatched: &[],
+        };
+
+        for assertion in checked {
+            let value = assertion.expression.eval(&lookup);
+
+            let result = match value {
+                Ok(v) if !v.is_zero() => continue,
+                Ok(_) => error::AssertionFailed {
+                    message: assertion.message,
+                    site: assertion.site.clone(),
+                }
+                .fail(),
+                Err(source) => Err(source).context(error::InvalidExpression {
+                    expression: assertion.expression.to_string(),
+                    site: assertion.site.clone(),
+                }),
+            };
+
+            self.failed = Some(assertion.site.file);
+            return result;
+        }
+
+        Ok(())
+    }
+
+    /// Fill in the value of each push of an expression in `unit`, now that
+    /// `asm` knows the address of every label in it. `code` is everything the
+    /// unit assembled to, after the first `streamed` bytes.
+    fn patch(
+        &mut self,
+        unit: usize,
+        asm: &Assembler,
+        streamed: usize,
+        code: &mut [u8],
+    ) -> Result<(), Error> {
+        let (patches, rest): (Vec<_>, _) = std::mem::take(&mut self.patches)
+            .into_iter()
+            .partition(|p: &Patch| p.unit == unit);
+
+        self.patches = rest;
+
+        // Where the immediate of each push is, skipping the opcode.
+        let ranges: Vec<(usize, usize)> = patches
+            .iter()
+            .map(|p| {
+                let start = asm.address(&self.symbols, &p.label).unwrap() as usize + 1;
+                (start, start + p.spec.size() as usize - 1)
+            })
+            .collect();
+
+        for (idx, patch) in patches.into_iter().enumerate() {
+            let lookup = Assembled {
+                defines: &self.options.defines,
+                symbols: &self.symbols,
+                asm,
+                streamed,
+                code,
+                unpatched: &ranges[idx..],
+            };
+
+            let (start, end) = ranges[idx];
+            let width = end - start;
+
+            let result = match patch.expression.eval(&lookup) {
+                Ok(value) if value.sign() == Sign::Minus => Some(
+                    error::NegativeValue {
+                        expression: patch.expression.to_string(),
+                        value,
+                        site: patch.site.clone(),
+                    }
+                    .fail(),
+                ),
+                Ok(value) => match value.to_bytes_be() {
+                    (_, bytes) if bytes.len() > width => None,
+                    (_, bytes) => Some(Ok(bytes)),
+                },
+                Err(source) => Some(Err(source).context(error::InvalidExpression {
+                    expression: patch.expression.to_string(),
+                    site: patch.site.clone(),
+                })),
+            };
+
+            let result = result.unwrap_or_else(|| {
+                error::ExpressionTooLarge {
+                    expression: patch.expression.to_string(),
+                    op: patch.spec,
+                    site: patch.site.clone(),
+                }
+                .fail()
+            });
+
+            let bytes = match result {
+                Ok(b) => b,
+                Err(err) => {
+                    self.failed = Some(patch.site.file);
+                    return Err(err);
+                }
+            };
+
+            // Held sources keep everything after the push, so the immediate
+            // is always in `code`.
+            let end = end - streamed;
+            code[end - bytes.len()..end].copy_from_slice(&bytes);
+        }
+
+        Ok(())
+    }
+
+    /// The labels declared in `unit`, by offset, now that `asm` knows the
+    /// address of every one.
+    fn map(&self, unit: usize, asm: &Assembler) -> Vec<Symbol> {
+        let mut map: Vec<_> = self
+            .labels
+            .iter()
+            .filter(|((u, _), _)| *u == unit)
+            .filter_map(|((_, label), site)| {
+                let offset = asm.address(&self.symbols, label)?;
+                Some(Symbol::new(label, offset as usize, site.clone()))
+            })
+            .collect();
+
+        map.sort_by(|a, b| (a.offset, &a.name).cmp(&(b.offset, &b.name)));
+        map
+    }
+
+    /// Fail if any label used in `unit` was never declared.
+    fn check_unresolved(&mut self, unit: usize) -> Result<(), Error> {
+        let keys: Vec<_> = self
+            .unresolved
+            .keys()
+            .filter(|(u, _)| *u == unit)
+            .cloned()
+            .collect();
+
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let declared: Vec<&str> = self
+            .labels
+            .keys()
+            .filter(|(u, _)| *u == unit)
+            .map(|(_, l)| l.as_ref())
+            .collect();
+
+        let labels: Vec<_> = keys
+            .iter()
+            .map(|key| Unresolved {
+                label: key.1.to_string(),
+                sites: self.unresolved[key].clone(),
+                suggestions: suggest(&key.1, declared.iter().copied()),
+            })
+            .collect();
+
+        self.failed = Some(labels[0].sites[0].file.clone());
+        error::UndeclaredLabels { labels }.fail()
+    }
+}
+
+impl<W> SourceStack<W>
+where
+    W: Write,
+{
+    fn pop(&mut self) -> Result<(), Error> {
+        let popped = self.sources.pop().unwrap();
+
+        if self.sources.is_empty() {
+            self.root = None;
+        }
+
+        let mut asm = match popped.scope {
+            Scope::Independent(a) => a,
+            Scope::Same => return Ok(()),
+        };
+
+        let resolve = debug_span!("resolve", path = %popped.path.display()).entered();
+
+        self.check_unresolved(popped.unit)?;
+
+        let mut code = popped.taken;
+        let forwarded = code.len();
+        code.extend_from_slice(&asm.take());
+
+        // Evaluating an expression can't change the size of the code, and the
+        // outermost source is never cached, so an estimate can leave its
+        // placeholders alone.
+        if self.estimating && self.sources.is_empty() {
+            let unit = popped.unit;
+            self.patches.retain(|p| p.unit != unit);
+            self.assertions.retain(|a| a.unit != unit);
+        } else {
+            self.patch(popped.unit, &asm, popped.streamed, &mut code)?;
+            self.check_assertions(popped.unit, &asm, popped.streamed, &code)?;
+        }
+
+        // Labels can't be used from outside the scope that declares them.
+        self.linter.close(popped.unit);
+
+        let symbols = match popped.symbols {
+            Some(symbols) => symbols,
+            None => self.map(popped.unit, &asm),
+        };
+
+        if self.sources.is_empty() {
+            self.map = symbols.clone();
+        }
+
+        if let Err(err) = asm.finish() {
+            self.failed = Some(popped.path);
+            return Err(err.into());
+        }
+
+        resolve.exit();
+
+        let mut raw = code[forwarded..].to_vec();
+
+        if self.sources.len() == 1 {
+            self.last_include = Some((code.clone(), symbols.clone()));
+        }
+
+        if self.sources.is_empty() && self.runtime {
+            let (code, symbols) = match self.last_include.take() {
+                Some(included) => included,
+                None => {
+                    let path = popped.path;
+                    self.failed = Some(path.clone());
+                    return error::NoRuntime { path }.fail();
+                }
+            };
+
+            self.map = symbols;
+            raw = code;
+        }
+
+        if let Some(ref root) = self.root {
+            let included = Included {
+                root: root.canonicalized.clone(),
+                options: self.options.clone(),
+                deps: self.reads[popped.first_read..].to_vec(),
+                raw: code,
+                symbols,
+                lints: self.linter.since(popped.first_lint),
+                linted: !self.estimating,
+                buildinfo: self.buildinfo > popped.first_buildinfo,
+                once: self.once.contains(&canonical(&popped.path)),
+            };
+
+            self.cache.included.insert(popped.path, included);
+        }
+
+        if raw.is_empty() {
+            return Ok(());
+        }
+
+        if self.sources.is_empty() {
+            let _span = debug_span!("emit", bytes = raw.len()).entered();
+            self.emit(&raw)
+        } else {
+            self.write(RawOp::Raw(raw))
+        }
+    }
+
+    fn emit(&mut self, raw: &[u8]) -> Result<(), Error> {
+        if !self.estimating {
+            self.output.write_all(raw).context(error::Io {
+                message: "writing output",
+                path: None,
+            })?;
+        }
+
+        self.written += raw.len();
+        Ok(())
+    }
+
+    /// Write a placeholder for a push of `expression`, to be patched with its
+    /// value once the labels in the innermost scope have addresses.
+    fn computed_push(
+        &mut self,
+        spec: Specifier,
+        expression: Expression,
+        span: Option<Span>,
+    ) -> Result<(), Error> {
+        self.refer_all(&expression, span);
+
+        self.patched += 1;
+        let label: Arc<str> = format!("patch.{}", self.patched).into();
+
+        let source = self
+            .sources
+            .iter_mut()
+            .rev()
+            .find(|s| matches!(s.scope, Scope::Independent(_)))
+            .unwrap();
+        source.held = true;
+
+        let source = self.sources.last().unwrap();
+        let unit = source.unit;
+
+        // The value isn't known yet, so lint it like a push of a label rather
+        // than of the zeros standing in for it.
+        if !self.estimating {
+            let linted = AbstractOp::with_label(spec, label.clone());
+            self.linter
+                .op(&mut self.symbols, unit, &source.path, span, &linted);
+        }
+
+        let placeholder = vec![0; spec.size() as usize - 1];
+        let op = AbstractOp::with_immediate(spec, &placeholder).unwrap();
+        let op = self.options.apply(op)?;
+
+        self.write(RawOp::Op(AbstractOp::Label(label.clone())))?;
+        self.write(RawOp::Op(op))?;
+
+        self.patches.push(Patch {
+            unit,
+            label,
+            spec,
+            expression,
+            site: self.site(span),
+        });
+
+        Ok(())
+    }
+
+    fn write(&mut self, mut op: RawOp) -> Result<(), Error> {
+        if self.sources.is_empty() {
+            panic!("no sources!");
+        }
+
+        for frame in self.sources[1..].iter_mut().rev() {
+            let asm = match frame.scope {
+                Scope::Same => continue,
+                Scope::Independent(ref mut a) => a,
+            };
+
+            if 0 == asm.push_with(&mut self.symbols, op)? || frame.held {
+                return Ok(());
+            } else {
+                let raw = asm.take();
+                frame.taken.extend_from_slice(&raw);
+                op = RawOp::Raw(raw);
+            }
+        }
+
+        let first = &mut self.sources[0];
+        let first_asm = match first.scope {
+            Scope::Independent(ref mut a) => a,
+            Scope::Same => panic!("sources[0] must be independent"),
+        };
+
+        if 0 < first_asm.push_with(&mut self.symbols, op)?
+            && self.streaming
+            && !self.runtime
+            && !first.held
+        {
+            let raw = first_asm.take();
+            first.streamed += raw.len();
+            self.emit(&raw)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings for assembling, which can be reused to make any number of
+/// [`Ingest`]s.
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_asm::ingest::AssemblerContext;
+/// use etk_asm::ops::Fork;
+/// #
+/// # use etk_asm::ingest::Error;
+/// #
+/// # use hex_literal::hex;
+///
+/// let context = AssemblerContext::new()
+///     .fork(Fork::London)
+///     .define("OWNER", vec![0x01, 0x02]);
+///
+/// let mut output = Vec::new();
+/// let mut ingest = context.ingest(&mut output);
+/// ingest.ingest("./example.etk", "push2 OWNER")?;
+///
+/// # let expected = hex!("610102");
+/// # assert_eq!(output, expected);
+/// # Result::<(), Error>::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AssemblerContext {
+    dialect: Dialect,
+    options: Options,
+    stamp: bool,
+    streaming: bool,
+    runtime: bool,
+    resolver: Option<Arc<dyn Resolver>>,
+    sink: Option<Arc<dyn Sink>>,
+    lints: Levels,
+}
+
+impl Default for AssemblerContext {
+    fn default() -> Self {
+        Self {
+            dialect: Dialect::Etk,
+            options: Default::default(),
+            stamp: false,
+            streaming: false,
+            runtime: false,
+            resolver: None,
+            sink: None,
+            lints: Levels::default(),
+        }
+    }
+}
+
+impl AssemblerContext {
+    /// Make a new `AssemblerContext` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read sources written in `dialect`.
+    ///
+    /// Only the file passed to [`Ingest::ingest`] (and, for Huff, the files it
+    /// includes) is affected.
+    ///
+    /// Defaults to [`Dialect::Etk`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::{AssemblerContext, Dialect};
+    /// #
+    /// # use etk_asm::ingest::Error;
+    /// #
+    /// # use hex_literal::hex;
+    ///
+    /// let text = r#"
+    ///     #define macro MAIN() = takes(0) returns(0) {
+    ///         0x01 0x02 add
+    ///     }
+    /// "#;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = AssemblerContext::new()
+    ///     .dialect(Dialect::Huff)
+    ///     .ingest(&mut output);
+    /// ingest.ingest("./example.huff", text)?;
+    ///
+    /// # let expected = hex!("6001600201");
+    /// # assert_eq!(output, expected);
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Reject instructions that aren't available in `fork`.
+    ///
+    /// Defaults to [`Fork::latest`].
+    pub fn fork(mut self, fork: Fork) -> Self {
+        self.options.fork = fork;
+        self
+    }
+
+    /// Replace `name`, wherever it's used as an immediate argument, with the
+    /// big-endian integer `value`.
+    ///
+    /// With a fixed size push (ex. `push2 name`), `value` must fit in the
+    /// immediate. With `%push(name)`, the smallest push that fits is used.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::AssemblerContext;
+    /// #
+    /// # use etk_asm::ingest::Error;
+    /// #
+    /// # use hex_literal::hex;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = AssemblerContext::new()
+    ///     .define("FEE", vec![0x03, 0xe8])
+    ///     .ingest(&mut output);
+    /// ingest.ingest("./example.etk", "push4 FEE")?;
+    ///
+    /// # let expected = hex!("63000003e8");
+    /// # assert_eq!(output, expected);
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn define<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<Vec<u8>>,
+    {
+        self.options.defines.insert(name.into(), value.into());
+        self
+    }
+
+    /// Limit how deeply macros may invoke each other, and how many
+    /// instructions a macro or an alias may expand to.
+    ///
+    /// The limits apply alike to `%macro`s, `%alias`es, and Huff macros. A
+    /// macro that invokes itself, directly or through others, is always an
+    /// error, whatever the limits.
+    ///
+    /// Defaults to a depth of 255 and a size of 1,048,576 instructions.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::AssemblerContext;
+    ///
+    /// let text = r#"
+    ///     %alias(two, "push1 2; push1 2")
+    ///     %alias(four, "two two")
+    /// "#;
+    ///
+    /// let mut ingest = AssemblerContext::new()
+    ///     .macro_limits(255, 3)
+    ///     .ingest(Vec::new());
+    /// ingest.ingest("./example.etk", text).unwrap_err();
+    /// ```
+    pub fn macro_limits(mut self, depth: usize, size: usize) -> Self {
+        self.options.limits = Limits { depth, size };
+        self
+    }
+
+    /// Warn, with the `unsupported-instruction` lint, about each instruction
+    /// that doesn't work, or doesn't work the same way, on `profile`.
+    ///
+    /// Defaults to [`Profile::Ethereum`], which runs every instruction.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::AssemblerContext;
+    /// use etk_asm::ops::Profile;
+    ///
+    /// let mut ingest = AssemblerContext::new()
+    ///     .profile(Profile::Scroll)
+    ///     .ingest(Vec::new());
+    /// ingest.ingest("./example.etk", "blockhash").unwrap();
+    ///
+    /// assert_eq!(
+    ///     ingest.warnings()[0].message,
+    ///     "`blockhash` behaves differently on Scroll: it returns a hash of \
+    ///     the chain id and block number",
+    /// );
+    /// ```
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.options.profile = profile;
+        self
+    }
+
+    /// Accept the instructions in `experimental`, even though no fork has
+    /// them. Each use is reported with the `experimental-instruction` lint.
+    ///
+    /// Using one of [`Experimental::proposals`] without accepting it is an
+    /// error.
+    ///
+    /// Defaults to no instructions.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::AssemblerContext;
+    /// use etk_asm::ops::Experimental;
+    /// #
+    /// # use etk_asm::ingest::Error;
+    /// #
+    /// # use hex_literal::hex;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = AssemblerContext::new()
+    ///     .experimental(Experimental::proposals())
+    ///     .ingest(&mut output);
+    /// ingest.ingest("./example.etk", "sub:\nbeginsub\nreturnsub")?;
+    ///
+    /// assert_eq!(ingest.warnings()[0].lint.name(), "experimental-instruction");
+    /// # drop(ingest);
+    /// # assert_eq!(output, hex!("5c5d"));
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn experimental(mut self, experimental: Experimental) -> Self {
+        self.options.experimental = experimental;
+        self
+    }
+
+    /// Rewrite each push of a constant to the narrowest push that holds it,
+    /// like `push32 1` to `push1 1`.
+    ///
+    /// Files containing math on labels, like `push1 end - start` or
+    /// `%assert(...)`, aren't rewritten, since the math might count on the
+    /// width of the pushes.
+    pub fn optimize_size(mut self, optimize: bool) -> Self {
+        self.options.optimize_size = optimize;
+        self
+    }
+
+    /// Append a trailer describing the assembler version and the source files
+    /// to the output, as if the source contained a `%buildinfo()` directive.
+    ///
+    /// See [`crate::buildinfo`] for the format.
+    ///
+    /// Defaults to `false`.
+    pub fn stamp(mut self, stamp: bool) -> Self {
+        self.stamp = stamp;
+        self
+    }
+
+    /// Write assembled bytes to the output as soon as they're ready, instead of
+    /// holding the whole program in memory until it has been assembled.
+    ///
+    /// Only instructions that refer to labels that haven't been declared yet,
+    /// and the instructions after them, are held back. If assembling fails,
+    /// anything assembled before the error will already have been written.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::AssemblerContext;
+    /// #
+    /// # use etk_asm::ingest::Error;
+    /// #
+    /// # use hex_literal::hex;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = AssemblerContext::new()
+    ///     .streaming(true)
+    ///     .ingest(&mut output);
+    /// ingest.ingest("./example.etk", "push1 1\npush1 nowhere").unwrap_err();
+    ///
+    /// # drop(ingest);
+    /// # let expected = hex!("6001");
+    /// # assert_eq!(output, expected);
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Write only the code of the last file the input includes directly,
+    /// instead of all of it.
+    ///
+    /// A constructor ends by including the runtime it deploys, so this
+    /// assembles the runtime of a contract from the same source as the
+    /// constructor. [`Ingest::symbols`] then lists the labels declared by the
+    /// runtime, with offsets from its start. Fails if the input doesn't
+    /// include anything.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::AssemblerContext;
+    /// #
+    /// # use etk_asm::ingest::Error;
+    /// #
+    /// # use hex_literal::hex;
+    /// #
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # std::fs::write(dir.path().join("runtime.etk"), "start:\njumpdest").unwrap();
+    /// # let main = dir.path().join("main.etk");
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = AssemblerContext::new()
+    ///     .runtime(true)
+    ///     .ingest(&mut output);
+    /// ingest.ingest(main, "push1 0\n%include(\"runtime.etk\")")?;
+    ///
+    /// assert_eq!(ingest.symbols()[0].name, "start");
+    /// assert_eq!(ingest.symbols()[0].offset, 0);
+    /// # drop(ingest);
+    /// # assert_eq!(output, hex!("5b"));
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn runtime(mut self, runtime: bool) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Find imported and included files with `resolver`, instead of only
+    /// relative to the file that mentions them.
+    pub fn resolver<R>(mut self, resolver: R) -> Self
+    where
+        R: 'static + Resolver,
+    {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Pass the warnings from each successful build to `sink`, in addition to
+    /// keeping them for [`Ingest::warnings`].
+    pub fn sink<S>(mut self, sink: S) -> Self
+    where
+        S: 'static + Sink,
+    {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Drop allowed warnings, and mark denied ones, according to `lints`.
+    pub fn lints(mut self, lints: Levels) -> Self {
+        self.lints = lints;
+        self
+    }
+
+    /// Make a new `Ingest` with these settings that writes assembled bytes to
+    /// `output`.
+    pub fn ingest<W>(&self, output: W) -> Ingest<W> {
+        self.ingest_with_cache(output, Cache::new())
+    }
+
+    /// Make a new `Ingest` with these settings that writes assembled bytes to
+    /// `output`, reusing unchanged files from the build that produced `cache`.
+    pub fn ingest_with_cache<W>(&self, output: W, cache: Cache) -> Ingest<W> {
+        let mut sources = SourceStack::new(output, cache);
+        sources.options = self.options.clone();
+        sources.streaming = self.streaming;
+        sources.runtime = self.runtime;
+        sources.resolver = self.resolver.clone();
+
+        Ingest {
+            sources,
+            files: Vec::new(),
+            complete: true,
+            warnings: Vec::new(),
+            stamp: self.stamp,
+            dialect: self.dialect,
+            sink: self.sink.clone(),
+            lints: self.lints.clone(),
+        }
+    }
+}
+
+/// A high-level interface for assembling files into EVM bytecode.
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_asm::ingest::Ingest;
+/// #
+/// # use etk_asm::ingest::Error;
+/// #
+/// # use hex_literal::hex;
+///
+/// let text = r#"
+///     push2 lbl
+///     lbl:
+///     jumpdest
+/// "#;
+///
+/// let mut output = Vec::new();
+/// let mut ingest = Ingest::new(&mut output);
+/// ingest.ingest("./example.etk", &text)?;
+///
+/// # let expected = hex!("6100035b");
+/// # assert_eq!(output, expected);
+/// # Result::<(), Error>::Ok(())
+/// ```
+#[derive(Debug)]
+pub struct Ingest<W> {
+    sources: SourceStack<W>,
+    files: Vec<PathBuf>,
+    complete: bool,
+    warnings: Vec<Warning>,
+    stamp: bool,
+    dialect: Dialect,
+    sink: Option<Arc<dyn Sink>>,
+    lints: Levels,
+}
+
+impl<W> Ingest<W> {
+    /// Make a new `Ingest` that writes assembled bytes to `output`.
+    pub fn new(output: W) -> Self {
+        Self::with_cache(output, Cache::new())
+    }
+
+    /// Make a new `Ingest` that writes assembled bytes to `output`, reusing
+    /// unchanged files from the build that produced `cache`.
+    ///
+    /// To assemble with other settings, use [`AssemblerContext`].
+    pub fn with_cache(output: W, cache: Cache) -> Self {
+        AssemblerContext::new().ingest_with_cache(output, cache)
+    }
+
+    /// Consume this `Ingest`, returning a [`Cache`] for the next build.
+    ///
+    /// If the last call to [`Ingest::ingest`] failed, entries from the
+    /// previous cache are kept as well, so fixing the error doesn't mean
+    /// starting over.
+    pub fn into_cache(self) -> Cache {
+        let mut cache = self.sources.cache;
+
+        if !self.complete {
+            cache.merge(self.sources.previous);
+        }
+
+        cache
+    }
+
+    /// Paths of every file ingested so far, including files that were
+    /// imported or included, in the order they were first encountered.
+    ///
+    /// Files are recorded before they are read, so after a failure this still
+    /// lists everything that contributed to the error.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Path of the file being assembled when the last call to
+    /// [`Ingest::ingest`] failed, or `None` if it succeeded.
+    pub fn failed_file(&self) -> Option<&Path> {
+        self.sources.failed.as_deref()
+    }
+
+    /// Warnings about the code assembled by the last successful call to
+    /// [`Ingest::ingest`].
+    ///
+    /// Included files reused from a [`Cache`] aren't inspected again, but the
+    /// warnings found when they were first assembled are reported again.
+    /// Lints allowed with [`AssemblerContext::lints`] are left out.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Labels declared by the input and the modules it imports, in order of
+    /// offset. Empty if the last call to [`Ingest::ingest`] failed.
+    ///
+    /// Labels in included files have scopes (and offsets) of their own, so
+    /// they aren't listed.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::{Ingest, SymbolKind};
+    /// #
+    /// # use etk_asm::ingest::Error;
+    ///
+    /// let mut ingest = Ingest::new(Vec::new());
+    /// ingest.ingest("./example.etk", "push1 0\nstart:\njumpdest")?;
+    ///
+    /// let symbols = ingest.symbols();
+    /// assert_eq!(symbols[0].name, "start");
+    /// assert_eq!(symbols[0].offset, 2);
+    /// assert_eq!(symbols[0].kind, SymbolKind::Label);
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.sources.map
+    }
+}
+
+impl<W> Ingest<W>
+where
+    W: Write,
+{
+    /// Assemble instructions from the file located at `path`.
+    pub fn ingest_file<P>(&mut self, path: P) -> Result<(), Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        record(&mut self.files, &path);
+
+        let read = read_file(&path);
+        let text = match read {
+            Ok(ref contents) => contents.text(),
+            Err(err) => Err(err),
+        };
+        let text = text.with_context(|| error::Io {
+            message: "reading source",
+            path: path.clone(),
+        });
+
+        match text {
+            Ok(text) => self.ingest(path, text),
+            Err(err) => {
+                self.sources.failed = Some(path);
+                Err(err)
+            }
+        }
+    }
+
+    /// Render the template in the file located at `path`, then assemble the
+    /// result. See [`Ingest::ingest_template`].
+    #[cfg(feature = "template")]
+    pub fn ingest_template_file<P>(
+        &mut self,
+        path: P,
+        vars: &serde_json::Value,
+    ) -> Result<(), Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        let text = std::fs::read_to_string(&path).with_context(|| error::Io {
+            message: "reading template",
+            path: path.clone(),
+        });
+
+        match text {
+            Ok(text) => self.ingest_template(path, &text, vars),
+            Err(err) => {
+                record(&mut self.files, &path);
+                self.sources.failed = Some(path);
+                Err(err)
+            }
+        }
+    }
+
+    /// Render `template`, a [MiniJinja](https://docs.rs/minijinja) template,
+    /// with the fields of `vars`, then assemble the result as if it were read
+    /// from a file located at `path`.
+    ///
+    /// Errors in the template itself are reported at their location in
+    /// `template`. Errors in the rendered assembly are reported at their
+    /// location in the rendered text.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::Ingest;
+    /// #
+    /// # use etk_asm::ingest::Error;
+    /// #
+    /// # use hex_literal::hex;
+    ///
+    /// let template = "{% for fee in fees %}push2 {{ fee }}\n{% endfor %}";
+    /// let vars = serde_json::json!({ "fees": [100, 300] });
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = Ingest::new(&mut output);
+    /// ingest.ingest_template("./example.etk.tmpl", template, &vars)?;
+    ///
+    /// # let expected = hex!("610064 61012c");
+    /// # assert_eq!(output, expected);
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    #[cfg(feature = "template")]
+    pub fn ingest_template<P>(
+        &mut self,
+        path: P,
+        template: &str,
+        vars: &serde_json::Value,
+    ) -> Result<(), Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        let failure = match crate::template::render(template, vars) {
+            Ok(text) => return self.ingest(path, &text),
+            Err(f) => f,
+        };
+
+        self.complete = false;
+        self.warnings.clear();
+        self.sources.map.clear();
+        record(&mut self.files, &path);
+        self.sources.failed = Some(path.clone());
+
+        error::Template {
+            path,
+            message: failure.message,
+            span: failure.span,
+        }
+        .fail()
+    }
+
+    /// Assemble `src` as if it were read from a file located at `path`, like
+    /// [`Ingest::ingest`], but only work out how large the output would be and
+    /// where its labels are, without writing anything to the output.
+    ///
+    /// Meant for editors showing the size of the code as it's typed, so it
+    /// skips what can't change the size: lints aren't run (leaving
+    /// [`Ingest::warnings`] empty), and the pushes of expressions and the
+    /// `%assert`s in `src` itself aren't evaluated, so errors in them aren't
+    /// reported. Files `src` includes or imports are still assembled in full,
+    /// and reusing the same `Ingest` (or a [`Cache`] from it) for each
+    /// estimate means the ones that haven't changed aren't assembled again.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ingest::Ingest;
+    /// use etk_asm::lint::MAX_CODE_SIZE;
+    /// #
+    /// # use etk_asm::ingest::Error;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut ingest = Ingest::new(&mut output);
+    /// let estimate = ingest.estimate("./example.etk", "push1 0\nstart:\njumpdest")?;
+    ///
+    /// assert_eq!(estimate.size, 3);
+    /// assert_eq!(estimate.symbols[0].offset, 2);
+    /// assert_eq!(
+    ///     format!("current size: {}/{} bytes", estimate.size, MAX_CODE_SIZE),
+    ///     "current size: 3/24576 bytes"
+    /// );
+    ///
+    /// # drop(ingest);
+    /// assert!(output.is_empty());
+    /// # Result::<(), Error>::Ok(())
+    /// ```
+    pub fn estimate<P>(&mut self, path: P, src: &str) -> Result<Estimate, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        self.sources.estimating = true;
+        let result = self.ingest(path, src);
+        self.sources.estimating = false;
+        result?;
+
+        Ok(Estimate {
+            size: self.sources.written,
+            symbols: self.sources.map.clone(),
+        })
+    }
+
+    /// Assemble instructions from `src` as if they were read from a file located
+    /// at `path`.
+    pub fn ingest<P>(&mut self, path: P, src: &str) -> Result<(), Error>
+    where
+        P: Into<PathBuf>,
+    {
+        self.complete = false;
+        self.sources.failed = None;
+        self.sources.linter = Linter::default();
+        self.sources
+            .linter
+            .experimental(&self.sources.options.experimental);
+        self.sources.linter.profile(self.sources.options.profile);
+        self.sources.written = 0;
+        self.sources.buildinfo = 0;
+        self.sources.once.clear();
+        self.sources.labels.clear();
+        self.sources.expanded.clear();
+        self.sources.unresolved.clear();
+        self.sources.map.clear();
+        self.sources.last_include = None;
+        self.warnings.clear();
+
+        let path = path.into();
+        record(&mut self.files, &path);
+
+        let _span = info_span!("ingest", path = %path.display()).entered();
+
+        let result = self.assemble(path.clone(), src);
+
+        if result.is_err() && self.sources.failed.is_none() {
+            // Anything not blamed on a specific file happened while working
+            // through the innermost source.
+            let innermost = self.sources.sources.last().map(|s| s.path.clone());
+            self.sources.failed = Some(innermost.unwrap_or(path));
+        }
+
+        result
+    }
+
+    fn assemble(&mut self, path: PathBuf, src: &str) -> Result<(), Error> {
+        let first_read = self.sources.reads.len();
+
+        let nodes = match self.dialect {
+            Dialect::Etk => self.sources.parse(&path, src)?,
+            Dialect::Huff => self.huff(&path, src)?,
+        };
+
+        let partial = self.sources.resolve(path, Scope::independent(), None)?;
+        partial.push(nodes);
+
+        // Where the next node came from, if the parser recorded it.
+        let mut location = None;
+
+        while let Some(source) = self.sources.peek() {
+            let node = match source.nodes.next() {
+                Some(n) => n,
+                None => {
+                    self.sources.pop()?;
+                    continue;
+                }
+            };
+
+            match node {
+                Node::Location(span) => {
+                    location = Some(span);
+                    self.sources.expanded.clear();
+                }
+                Node::Expanded { span, from } => {
+                    location = Some(span);
+                    self.sources.expanded = from;
+                }
+                Node::Op(op) => {
+                    let span = location.take();
+
+                    if let AbstractOp::Label(ref label) = op {
+                        self.sources.declare(label, span)?;
+                    }
+
+                    if !self.sources.estimating {
+                        let source = self.sources.sources.last().unwrap();
+                        self.sources.linter.op(
+                            &mut self.sources.symbols,
+                            source.unit,
+                            &source.path,
+                            span,
+                            &op,
+                        );
+                    }
+
+                    let op = self.sources.options.apply(op)?;
+
+                    if let Some(label) = op.shared_label() {
+                        self.sources.refer(label, span);
+                    }
+
+                    self.sources.write(RawOp::Op(op))?;
+                }
+                Node::Raw(raw) => {
+                    self.sources.linter.raw();
+                    self.sources.write(RawOp::Raw(raw))?;
+                }
+                Node::Import { path, alias } => {
+                    let _span = debug_span!("expand", import = %path.display()).entered();
+
+                    let directive = Some(Directive::new("%import", location.take()));
+                    let mut partial = self.sources.resolve(path, Scope::same(), directive)?;
+                    record(&mut self.files, partial.path());
+
+                    if partial.is_done() {
+                        debug!("skipping import included once");
+                        continue;
+                    }
+
+                    let parsed = partial.parse_module(alias.as_deref())?;
+                    partial.push(parsed);
+                }
+                Node::Include(path) => {
+                    let _span = debug_span!("expand", include = %path.display()).entered();
+
+                    let directive = Some(Directive::new("%include", location.take()));
+                    let mut partial =
+                        self.sources
+                            .resolve(path, Scope::independent(), directive)?;
+                    record(&mut self.files, partial.path());
+
+                    if partial.is_done() {
+                        debug!("skipping include included once");
+                        continue;
+                    }
+
+                    match partial.cached() {
+                        Some(included) => {
+                            debug!("reusing assembled include");
+                            for (dep, _) in &included.deps {
+                                record(&mut self.files, dep);
+                            }
+                            partial.push_cached(included);
+                        }
+                        None => {
+                            let nodes = partial.parse()?;
+                            partial.push(nodes);
+                        }
+                    }
+                }
+                Node::IncludeHex(path) => {
+                    let _span = debug_span!("expand", include_hex = %path.display()).entered();
+
+                    let directive = Some(Directive::new("%include_hex", location.take()));
+                    let mut partial = self.sources.resolve(path, Scope::same(), directive)?;
+                    record(&mut self.files, partial.path());
+
+                    let raw = partial.read_hex()?;
+                    partial.push(vec![Node::Raw(raw)]);
+                }
+                Node::IncludeArtifact { path, deployed } => {
+                    let _span = debug_span!("expand", include_artifact = %path.display()).entered();
+
+                    let directive = Some(Directive::new("%include_artifact", location.take()));
+                    let mut partial = self.sources.resolve(path, Scope::same(), directive)?;
+                    record(&mut self.files, partial.path());
+
+                    let raw = partial.read_artifact(deployed)?;
+                    partial.push(vec![Node::Raw(raw)]);
+                }
+                Node::Script(path) => {
+                    let _span = debug_span!("expand", script = %path.display()).entered();
+
+                    let directive = Some(Directive::new("%script", location.take()));
+                    let mut partial = self.sources.resolve(path, Scope::same(), directive)?;
+                    record(&mut self.files, partial.path());
+
+                    let nodes = partial.run_script()?;
+                    partial.push(nodes);
+                }
+                Node::BuildInfo => self.sources.buildinfo += 1,
+                Node::PragmaOnce => {
+                    let path = canonical(&self.sources.sources.last().unwrap().path);
+                    self.sources.once.insert(path);
+                }
+                Node::PragmaSolidityMemory => self.sources.linter.solidity_memory(),
+                Node::Org { offset, fill } => {
+                    self.sources.linter.raw();
+                    self.sources.write(RawOp::Org { offset, fill })?;
+                }
+                Node::Assert {
+                    expression,
+                    message,
+                } => {
+                    let span = location.take();
+                    self.sources.assert(expression, message, span);
+                }
+                Node::ComputedPush { spec, expression } => {
+                    let span = location.take();
+                    self.sources.computed_push(spec, expression, span)?;
+                }
+                Node::PushExpression(expression) => {
+                    let span = location.take();
+                    let spec = self.sources.options.push_for(&expression);
+                    self.sources.computed_push(spec, expression, span)?;
+                }
+                // Only has an effect in imported modules, which are handled
+                // when they're parsed.
+                Node::Export(_) => (),
+            }
+        }
+
+        if !self.sources.sources.is_empty() {
+            panic!("extra sources?");
+        }
+
+        if (self.stamp || self.sources.buildinfo > 0) && self.sources.estimating {
+            self.sources.written += crate::buildinfo::TRAILER_LEN;
+        } else if self.stamp || self.sources.buildinfo > 0 {
+            let mut hasher = Keccak256::new();
+            hasher.update(hash(src));
+            for (_, key) in &self.sources.reads[first_read..] {
+                hasher.update(key);
+            }
+
+            let info = BuildInfo::new(hasher.finalize().into());
+            self.sources.emit(&info.to_trailer())?;
+        }
+
+        let linter = std::mem::take(&mut self.sources.linter);
+        let lints = &self.lints;
+
+        if self.sources.estimating {
+            self.warnings.clear();
+            self.complete = true;
+            return Ok(());
+        }
+
+        self.warnings = linter
+            .finish(self.sources.written)
+            .into_iter()
+            .map(|w| Warning {
+                level: lints.get(w.lint),
+                ..w
+            })
+            .filter(|w| w.level != Level::Allow)
+            .collect();
+
+        if let Some(ref sink) = self.sink {
+            for warning in &self.warnings {
+                sink.warning(warning);
+            }
+        }
+
+        self.complete = true;
+        Ok(())
+    }
+
+    /// Parse the Huff source `src`, and every file it includes, then expand
+    /// its `MAIN` macro.
+    fn huff(&mut self, path: &Path, src: &str) -> Result<Vec<Node>, Error> {
+        let _span = debug_span!("huff").entered();
+
+        let root = Root::new(path.to_owned())?;
+        let mut defs = Definitions::default();
+        let mut seen = HashSet::new();
+
+        seen.insert(canonical(path));
+
+        self.huff_file(&root, &mut defs, &mut seen, path, src)?;
+        Ok(defs.expand(self.sources.options.limits)?)
+    }
+
+    /// Merge the definitions from `src` into `defs`, after the definitions
+    /// from the files it includes. Each file is only included once.
+    fn huff_file(
+        &mut self,
+        root: &Root,
+        defs: &mut Definitions,
+        seen: &mut HashSet<PathBuf>,
+        path: &Path,
+        src: &str,
+    ) -> Result<(), Error> {
+        let result = self.huff_includes(root, defs, seen, path, src);
+
+        if result.is_err() && self.sources.failed.is_none() {
+            self.sources.failed = Some(path.to_owned());
+        }
+
+        result
+    }
+
+    fn huff_includes(
+        &mut self,
+        root: &Root,
+        defs: &mut Definitions,
+        seen: &mut HashSet<PathBuf>,
+        path: &Path,
+        src: &str,
+    ) -> Result<(), Error> {
+        let file = parse_huff(src)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("./"));
+
+        for include in &file.includes {
+            let candidate = dir.join(include);
+            record(&mut self.files, &candidate);
+
+            let message = "reading huff include";
+            let result = root.check(&candidate).and_then(|_| {
+                read_file(&candidate).with_context(|| error::Io {
+                    message,
+                    path: candidate.clone(),
+                })
+            });
+
+            let contents = match result {
+                Ok(c) => c,
+                Err(e) => {
+                    self.sources.failed = Some(candidate);
+                    return Err(e);
+                }
+            };
+
+            if !seen.insert(canonical(&candidate)) {
+                continue;
+            }
+
+            self.sources
+                .reads
+                .push((candidate.clone(), hash(&*contents)));
+
+            let text = match contents.text() {
+                Ok(t) => t,
+                Err(e) => {
+                    self.sources.failed = Some(candidate.clone());
+                    return Err(e).context(error::Io {
+                        message,
+                        path: candidate,
+                    });
+                }
+            };
+
+            self.huff_file(root, defs, seen, &candidate, text)?;
+        }
+
+        Ok(defs.merge(file)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use crate::lint::Lint;
+    use crate::ops::ExperimentalOp;
+    use crate::ParseError;
+
+    use hex_literal::hex;
+
+    use std::fmt::Display;
+    use std::io::Write;
+
+    use super::*;
+
+    use tempfile::NamedTempFile;
+
+    fn new_file<S: Display>(s: S) -> (NamedTempFile, PathBuf) {
+        let mut f = NamedTempFile::new().unwrap();
+        let root = f.path().parent().unwrap().join("root.asm");
+
+        write!(f, "{}", s).unwrap();
+        (f, root)
+    }
+
+    #[test]
+    fn ingest_import() -> Result<(), Error> {
+        let (f, root) = new_file("push1 42");
+
+        let text = format!(
+            r#"
+            push1 1
+            %import("{}")
+            push1 2
+        "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("6001602a6002"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_pragma_once() -> Result<(), Error> {
+        let (once, root) = new_file("%pragma(once)\npush1 42");
+        let (twice, _) = new_file("push1 43");
+
+        let text = format!(
+            r#"
+            %import("{0}")
+            %import("{0}")
+            %include("{0}")
+            %import("{1}")
+            %import("{1}")
+        "#,
+            once.path().display(),
+            twice.path().display(),
+        );
+
+        let mut cache = Cache::new();
+
+        // The second build reuses the include from the cache.
+        for _ in 0..2 {
+            let mut output = Vec::new();
+            let mut ingest = Ingest::with_cache(&mut output, cache);
+            ingest.ingest(&root, &text)?;
+            cache = ingest.into_cache();
+            assert_eq!(output, hex!("602a602b602b"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_pragma_once_include_first() -> Result<(), Error> {
+        let (once, root) = new_file("%pragma(once)\npush1 42");
+
+        let text = format!(
+            r#"
+            %include("{0}")
+            %include("{0}")
+            %import("{0}")
+        "#,
+            once.path().display(),
+        );
+
+        let mut cache = Cache::new();
+
+        for _ in 0..2 {
+            let mut output = Vec::new();
+            let mut ingest = Ingest::with_cache(&mut output, cache);
+            ingest.ingest(&root, &text)?;
+            cache = ingest.into_cache();
+            assert_eq!(output, hex!("602a"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_include() -> Result<(), Error> {
+        let (f, root) = new_file(
+            r#"
+                a:
+                jumpdest
+                pc
+                push1 a
+                jump
+            "#,
+        );
+
+        let text = format!(
+            r#"
+            push1 1
+            %include("{}")
+            push1 2
+        "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("60015b586000566002"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_include_same_label() -> Result<(), Error> {
+        let (f, root) = new_file(
+            r#"
+                a:
+                jumpdest
+                push1 a
+            "#,
+        );
+
+        let text = format!(
+            r#"
+            push1 a
+            %include("{}")
+            a:
+            jumpdest
+        "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("60055b60005b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_import_twice() {
+        let (f, root) = new_file(
+            r#"
+                a:
+                jumpdest
+                push1 a
+            "#,
+        );
+
+        let text = format!(
+            r#"
+                push1 1
+                %import("{0}")
+                %import("{0}")
+                push1 2
+            "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let err = ingest.ingest(root, &text).unwrap_err();
+
+        assert_matches!(
+            err,
+            Error::DuplicateLabel { label, .. } if label == "a"
+        );
+    }
+
+    #[test]
+    fn ingest_duplicate_label_sites() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.etk");
+        let lib = dir.path().join("lib.etk");
+        let util = dir.path().join("util.etk");
+
+        std::fs::write(&main, "a:\njumpdest\n%import(\"lib.etk\")\n").unwrap();
+        std::fs::write(&lib, "%import(\"util.etk\")\n").unwrap();
+        std::fs::write(&util, "stop\n  a:\n").unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest_file(&main).unwrap_err();
+
+        let (first, second) = match err {
+            Error::DuplicateLabel {
+                ref label,
+                ref first,
+                ref second,
+                ..
+            } if label == "a" => (first, second),
+            _ => panic!("unexpected error: {:?}", err),
+        };
+
+        assert_eq!(first.file, main);
+        assert_eq!(first.span.unwrap().start, (1, 1));
+        assert!(first.included_from.is_empty());
+
+        assert_eq!(second.file, util);
+        assert_eq!(second.span.unwrap().start, (2, 3));
+        assert_eq!(second.included_from, [lib, main]);
+
+        assert_eq!(err.code(), codes::DUPLICATE_LABEL);
+        assert_eq!(err.span(), second.span);
+        assert_eq!(ingest.failed_file(), Some(util.as_path()));
+    }
+
+    #[test]
+    fn ingest_expansion_sites() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.etk");
+        let lib = dir.path().join("lib.etk");
+
+        std::fs::write(&main, "push1 0\n%include(\"lib.etk\")\n").unwrap();
+        std::fs::write(
+            &lib,
+            "%alias(exit, \"push2 done; jump\")\n%alias(transfer, \"pop; exit\")\ntransfer\n",
+        )
+        .unwrap();
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest_file(&main).unwrap_err();
+        let site = err.site().unwrap();
+
+        assert_eq!(site.file, lib);
+        assert_eq!(site.span.unwrap().start, (1, 1));
+
+        let expanded: Vec<_> = site
+            .expanded_from
+            .iter()
+            .map(|e| (e.name.as_str(), e.file.clone(), e.span.unwrap().start))
+            .collect();
+
+        assert_eq!(
+            expanded,
+            [
+                ("exit", lib.clone(), (2, 1)),
+                ("transfer", lib.clone(), (3, 1)),
+                ("%include", main.clone(), (2, 1)),
+            ]
+        );
+
+        assert_eq!(
+            site.expanded_from[2].to_string(),
+            format!("`%include` at {}:2:1", main.display())
+        );
+    }
+
+    #[test]
+    fn ingest_import_anonymous_labels() -> Result<(), Error> {
+        let (f, root) = new_file("1:\njumpdest\npush1 1b\n");
+
+        let text = format!(
+            r#"
+                %import("{0}")
+                %import("{0}")
+                1:
+                push1 1b
+            "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("5b6000 5b6003 6006"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_symbols() -> Result<(), Error> {
+        let (module, root) = new_file("%export(add)\nadd:\njumpdest\nhelper:\n1:\nstop");
+        let (included, _) = new_file("inner:\njumpdest");
+
+        let text = format!(
+            r#"
+                %import("{0}") as math
+                start:
+                %include("{1}")
+                1:
+                push1 1b
+            "#,
+            module.path().display(),
+            included.path().display(),
+        );
+
+        let mut ingest = Ingest::new(Vec::new());
+        ingest.ingest(&root, &text)?;
+
+        let listed: Vec<_> = ingest
+            .symbols()
+            .iter()
+            .map(|s| (s.offset, s.name.as_str(), s.kind))
+            .collect();
+
+        assert_eq!(
+            listed,
+            [
+                (0, "math.add", SymbolKind::Label),
+                (1, "1", SymbolKind::Anonymous),
+                (1, "helper", SymbolKind::Private),
+                (2, "start", SymbolKind::Label),
+                (3, "1", SymbolKind::Anonymous),
+            ]
+        );
+
+        assert_eq!(ingest.symbols()[0].site.file, module.path());
+        assert_eq!(
+            ingest.symbols()[0].site.included_from,
+            std::slice::from_ref(&root)
+        );
+
+        ingest.ingest(&root, "push1 nowhere").unwrap_err();
+        assert!(ingest.symbols().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_runtime() -> Result<(), Error> {
+        let (first, root) = new_file("stop");
+        let (runtime, _) = new_file("push1 top\ntop:\njumpdest");
+
+        let text = format!(
+            r#"
+                push1 1
+                start:
+                %include("{0}")
+                %include("{1}")
+            "#,
+            first.path().display(),
+            runtime.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .streaming(true)
+            .runtime(true)
+            .ingest(&mut output);
+        ingest.ingest(&root, &text)?;
+
+        let listed: Vec<_> = ingest
+            .symbols()
+            .iter()
+            .map(|s| (s.offset, s.name.as_str()))
+            .collect();
+        assert_eq!(listed, [(2, "top")]);
+        assert_eq!(ingest.symbols()[0].site.file, runtime.path());
+
+        let err = ingest.ingest(&root, "push1 1").unwrap_err();
+        assert_matches!(err, Error::NoRuntime { ref path, .. } if path == &root);
+        assert_eq!(err.code(), codes::NO_RUNTIME);
+        assert!(ingest.symbols().is_empty());
+
+        drop(ingest);
+        assert_eq!(output, hex!("6002 5b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_experimental() -> Result<(), Error> {
+        let text = "push1 sub\njumpsub\nstop\nsub:\nbeginsub\nreturnsub";
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+
+        let err = ingest.ingest("./example.etk", text).unwrap_err();
+        assert_eq!(err.code(), codes::EXPERIMENTAL_DISABLED);
+        drop(ingest);
+
+        output.clear();
+        let mut ingest = AssemblerContext::new()
+            .experimental(Experimental::proposals())
+            .ingest(&mut output);
+        ingest.ingest("./example.etk", text)?;
+
+        let warned: Vec<_> = ingest.warnings().iter().map(|w| w.lint).collect();
+        assert_eq!(warned, [Lint::ExperimentalInstruction; 3]);
+        assert_eq!(
+            ingest.warnings()[0].message,
+            "`jumpsub` is an experimental instruction from EIP-2315, which no fork has adopted"
+        );
+
+        drop(ingest);
+        assert_eq!(output, hex!("6004 5e 00 5c 5d"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_experimental_stack() -> Result<(), Error> {
+        let mut experimental = Experimental::new();
+        experimental
+            .insert(
+                ExperimentalOp::new("l1block", 0xe0)
+                    .immediate(2)
+                    .stack(1, 1),
+            )
+            .unwrap();
+        experimental
+            .insert(ExperimentalOp::new("burn", 0xe1).stack(2, 0))
+            .unwrap();
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .experimental(experimental)
+            .ingest(&mut output);
+        ingest.ingest("./example.etk", "push1 1\nl1block 2\nburn")?;
+
+        let warning = ingest
+            .warnings()
+            .iter()
+            .find(|w| w.lint == Lint::StackUnderflow)
+            .unwrap();
+        assert_eq!(
+            warning.message,
+            "`burn` pops 2 stack item(s), but only 1 are available when execution reaches it"
+        );
+
+        drop(ingest);
+        assert_eq!(output, hex!("6001 e00002 e1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_import_exports() -> Result<(), Error> {
+        let (f, root) = new_file(
+            r#"
+                %export(start)
+                start:
+                jumpdest
+                push1 helper
+                helper:
+                jumpdest
+            "#,
+        );
+
+        let text = format!(
+            r#"
+                %import("{0}") as m
+                %import("{0}") as n
+                push1 m.start
+                push1 n.start
+            "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("5b60035b 5b60075b 6000 6004"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_import_private_label() {
+        let (f, root) = new_file("%export(a)\na:\nb:");
+
+        let text = format!(
+            r#"
+                %import("{}")
+                push1 a
+                push1 b
+            "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let err = ingest.ingest(root, &text).unwrap_err();
+
+        assert_matches!(
+            err,
+            Error::UndeclaredLabels { labels, .. }
+                if labels.len() == 1 && labels[0].label == "b" && labels[0].suggestions.is_empty()
+        );
+    }
+
+    #[test]
+    fn ingest_undeclared_labels() {
+        let text = "push1 strat\njumpdest\nstart:\npush2 ending\n%push(strat)\n";
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", text).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "2 labels were never defined: `ending`, `strat`"
+        );
+        assert_eq!(err.code(), codes::UNDECLARED_LABEL);
+
+        let labels = match err {
+            Error::UndeclaredLabels { labels, .. } => labels,
+            _ => panic!("unexpected error: {:?}", err),
+        };
+
+        assert_eq!(labels[0].label, "ending");
+        assert!(labels[0].suggestions.is_empty());
+
+        assert_eq!(labels[1].label, "strat");
+        assert_eq!(labels[1].suggestions, ["start"]);
+
+        let starts: Vec<_> = labels[1]
+            .sites
+            .iter()
+            .map(|s| s.span.unwrap().start)
+            .collect();
+        assert_eq!(starts, [(1, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn ingest_minimal_proxy() -> Result<(), Error> {
+        let text = r#"
+            %minimal_proxy(0xbebebebebebebebebebebebebebebebebebebebe)
+            push1 implementation
+            %minimal_proxy(implementation)
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(
+            output,
+            hex!(
+                "
+                3d602d80600a3d3981f3363d3d373d3d3d363d73
+                bebebebebebebebebebebebebebebebebebebebe
+                5af43d82803e903d91602b57fd5bf3
+                604d
+                3d602d80600a3d3981f3363d3d373d3d3d363d73
+                0000000000000000000000000000000000000000
+                5af43d82803e903d91602b57fd5bf3
+                "
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_create() -> Result<(), Error> {
+        let text = r#"
+            %create2(runtime, runtime_end, 0x42)
+            %create(runtime, runtime_end)
+            stop
+            runtime:
+                ret0
+            runtime_end:
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(
+            output,
+            hex!(
+                "
+                6042 610004 80 601d 6000 39 6000 80 f5
+                610004 80 601d 6000 39 6000 80 f0
+                00
+                600080f3
+                "
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_sstore2() -> Result<(), Error> {
+        let text = r#"
+            %sstore2_write(data, data_end)
+            %sstore2_read(0x40)
+            stop
+            data:
+                %raw("0x0102")
+            data_end:
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(
+            output,
+            hex!(
+                "
+                610003 60e8 1b
+                7f 61000080600c6000396000f3 0000000000000000000000000000000000000000
+                17 6000 52
+                610002 604b 600d 39
+                61000f 6000 6000 f0
+                80 3b 6001 90 03 90 81 6001 6040 83 3c 50
+                00
+                0102
+                "
+            )
+        );
+
+        let text = r#"
+            %sstore2_write(data, data_end)
+            data:
+                %raw(0x01)
+            %org(0x603b)        # 24576 bytes after `data`.
+            data_end:
+        "#;
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", text).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "assertion failed: an SSTORE2 data contract can't hold more than 24575 bytes"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_std_call() -> Result<(), Error> {
+        let text = r#"
+            %import(std, "call")
+            push1 0x00
+            bubble_revert
+            stop
+            push1 0x01
+            bubble_result
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(
+            output,
+            hex!(
+                "
+                6000 600e57 3d6000803e 3d6000fd 5b
+                00
+                6001 3d6000803e 601e57 3d6000fd 5b 3d6000f3
+                "
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_std_math() -> Result<(), Error> {
+        let text = r#"
+            %import(std, "math")
+            push1 2
+            push1 1
+            safe_add
+            push1 3
+            safe_sub
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(
+            output,
+            hex!(
+                "
+                6002 6001
+                810180911115 601157 600080fd 5b
+                6003
+                81811015 601f57 600080fd 5b 03
+                "
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_org() -> Result<(), Error> {
+        let text = r#"
+            %push(target)
+            jump
+            %org(0x08)
+            target:
+            jumpdest
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("600856 0000000000 5b"));
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest
+            .ingest("./main.etk", "push32 0\n%org(0x10)")
+            .unwrap_err();
+        assert_eq!(err.code(), codes::OFFSET_PASSED);
+        assert_matches!(
+            err,
+            Error::Assemble {
+                source: AssembleError::OffsetPassed {
+                    target: 0x10,
+                    offset: 0x21,
+                    ..
+                }
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_push_expression() -> Result<(), Error> {
+        let text = r#"
+            %push(table + 1)
+            %push(end - table)
+            stop
+            table:
+            %raw("aabbcc")
+            end:
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("610008 610003 00 aabbcc"));
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest
+            .ingest("./main.etk", "%push(a - b)\na:\nstop\nb:")
+            .unwrap_err();
+        assert_eq!(err.code(), codes::NEGATIVE_VALUE);
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", "%push(1 - 2)").unwrap_err();
+        assert_matches!(err, Error::NegativeValue { .. });
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", "%push(keccak(a, b))\na:\nb:")?;
+        assert_eq!(output.len(), 33);
+        assert_eq!(output[0], 0x7f);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_array() -> Result<(), Error> {
+        let text = r#"
+            %def TARGETS = [first, second + 1]
+            push1 TARGETS[1]
+            jump
+            first:
+            jumpdest
+            second:
+            stop
+            jumpdest
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("600556 5b 00 5b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_macro() -> Result<(), Error> {
+        let text = r#"
+            %macro route(sel, name)
+                dup1
+                push4 $sel
+                eq
+                push1 handler_$name
+                jumpi
+            %end
+
+            %macro handler(name)
+                handler_$name:
+                jumpdest
+                stop
+            %end
+
+            push1 0
+            %route(0x01020304, mint)
+            %handler(mint)
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("6000 80 6301020304 14 600c 57 5b 00"));
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest
+            .ingest("./main.etk", "%macro m(a)\npush1 $a\n%end\n%m(missing)")
+            .unwrap_err();
+        assert_eq!(err.code(), codes::UNDECLARED_LABEL);
+        assert_eq!(
+            err.site().unwrap().expanded_from[0].to_string(),
+            "`m` at ./main.etk:4:1"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_assert() -> Result<(), Error> {
+        let text = r#"
+            start:
+            %push(end)
+            jump
+            end:
+            %assert(end - start == 3)
+            %assert(end < SIZE, "too large")
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("SIZE", vec![0x04])
+            .ingest(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("600356"));
+
+        let mut ingest = AssemblerContext::new()
+            .define("SIZE", vec![0x03])
+            .ingest(Vec::new());
+        let err = ingest.ingest("./main.etk", text).unwrap_err();
+
+        assert_eq!(err.to_string(), "assertion failed: too large");
+        assert_eq!(err.code(), codes::ASSERTION_FAILED);
+        assert_eq!(err.span().unwrap().start, (7, 13));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_assert_errors() {
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", "%assert(nowhere)").unwrap_err();
+        assert_matches!(err, Error::UndeclaredLabels { .. });
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest
+            .ingest("./main.etk", "a:\n%assert(1 / a)")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "`1 / a` couldn't be evaluated");
+        assert_eq!(err.code(), codes::INVALID_EXPRESSION);
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", "%assert(1 > 2)").unwrap_err();
+        assert_eq!(err.to_string(), "assertion failed: `1 > 2`");
+    }
+
+    #[test]
+    fn ingest_builtins() -> Result<(), Error> {
+        let text = r#"
+            push1 len(data, end)
+            push32 keccak(data, end)
+            stop
+            data:
+            %raw("c0ffee")
+            end:
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+
+        let mut expected = hex!("6003 7f").to_vec();
+        expected.extend_from_slice(&Keccak256::digest(&hex!("c0ffee")));
+        expected.extend_from_slice(&hex!("00 c0ffee"));
+        assert_eq!(output, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_pc_of() -> Result<(), Error> {
+        let text = r#"
+            %push(pc_of(table) + 2 * INDEX)
+            pop
+            push1 pc_of(table)
+            stop
+            table:
+            %raw("0x00010002")
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("INDEX", vec![0x01])
+            .ingest(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("610009 50 6007 00 00010002"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_push_operand_expression() -> Result<(), Error> {
+        let text = r#"
+            push1 pc_of(table) + 32*2
+            stop
+            table:
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest("./main.etk", text)?;
+        assert_eq!(output, hex!("6043 00"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_builtins_included() -> Result<(), Error> {
+        let (f, root) = new_file("push1 len(a, b)\na:\npc\nb:");
+
+        // Earlier pushes are patched first, so later ones can hash them.
+        let text = format!(
+            r#"
+            jumpdest
+            %include("{}")
+            start:
+            push1 len(start, end)
+            end:
+            push32 keccak(start, end)
+        "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+
+        let mut expected = hex!("5b 6001 58 6002 7f").to_vec();
+        expected.extend_from_slice(&Keccak256::digest(&hex!("6002")));
+        assert_eq!(output, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_parse_errors_located() {
+        let cases = [
+            ("stop\n  %raw(0x100)", codes::ARGUMENT_TOO_LARGE, (2, 3)),
+            ("%org(1, 0x100)", codes::ARGUMENT_TOO_LARGE, (1, 1)),
+            ("\n%calldata_word(a)", codes::ARGUMENT_TYPE, (2, 1)),
+            (
+                "%macro p(a)\npop\n%end\n%p()",
+                codes::MISSING_ARGUMENT,
+                (4, 1),
+            ),
+            (
+                r#"push1 len(substr("abc", 2, 2))"#,
+                codes::STRING_RANGE,
+                (1, 1),
+            ),
+        ];
+
+        for (text, code, start) in cases {
+            let mut ingest = Ingest::new(Vec::new());
+            let err = ingest.ingest("./main.etk", text).unwrap_err();
+            assert_eq!(err.code(), code, "{}", text);
+            assert_eq!(err.span().map(|s| s.start), Some(start), "{}", text);
+        }
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", "%raw(0x100)").unwrap_err();
+        assert_eq!(err.to_string(), "parsing failed");
+        assert_matches!(
+            err,
+            Error::Parse { source, .. } if source.summary() == "`0x100` doesn't fit in 1 byte(s)"
+        );
+    }
+
+    #[test]
+    fn ingest_builtins_errors() {
+        let text = "push1 len(a, b)\na:\n%raw(0x00)\n%org(0x200)\nb:";
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest("./main.etk", text).unwrap_err();
+        assert_eq!(err.to_string(), "value of `b - a` is too large for `push1`");
+        assert_eq!(err.code(), codes::IMMEDIATE_TOO_LARGE);
+        assert_eq!(err.span().unwrap().start, (1, 1));
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest
+            .ingest("./main.etk", "a:\npush1 len(b, a)\nb:")
+            .unwrap_err();
+        assert_matches!(err, Error::NegativeValue { .. });
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest
+            .ingest("./main.etk", "a:\npush32 keccak(a, b)\nb:")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "`keccak(a, b)` couldn't be evaluated");
+        assert_eq!(err.code(), codes::UNHASHABLE_CODE);
+    }
+
+    #[test]
+    fn ingest_import_assert() -> Result<(), Error> {
+        let (f, root) = new_file("%export(a)\na:\nb:\npc\n%assert(b - a == 0)");
+
+        let text = format!(
+            r#"
+                pc
+                %import("{0}") as m
+                %assert(m.a == 1)
+            "#,
+            f.path().display()
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("5858"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn suggest_similar() {
+        assert_eq!(distance("start", "strat"), 1);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+
+        let candidates = ["loop", "lop", "import.1.loop", "exit"];
+        assert_eq!(suggest("loopp", candidates.iter().copied()), ["loop"]);
+        assert_eq!(suggest("lopp", candidates.iter().copied()), ["loop", "lop"]);
+        assert!(suggest("x", candidates.iter().copied()).is_empty());
+    }
+
+    #[test]
+    fn ingest_import_undeclared_export() {
+        let (f, root) = new_file("%export(a, b)\na:");
+
+        let text = format!(r#"%import("{}")"#, f.path().display());
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let err = ingest.ingest(root, &text).unwrap_err();
+
+        assert_matches!(err, Error::UndeclaredExport { name, .. } if name == "b");
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn ingest_include_hex() -> Result<(), Error> {
+        let (f, root) = new_file("deadbeef0102f6");
+
+        let text = format!(
+            r#"
+                push1 1
+                %include_hex("{}")
+                push1 2
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("6001deadbeef0102f66002"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_include_artifact() -> Result<(), Error> {
+        let (f, root) = new_file(
+            r#"{"bytecode": {"object": "0xc0de"}, "deployedBytecode": {"object": "0xbeef"}}"#,
+        );
+
+        let text = format!(
+            r#"
+                %include_artifact("{0}")
+                %include_artifact("{0}", deployed)
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("c0debeef"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "script")]
+    fn ingest_script() -> Result<(), Error> {
+        let (text, root) =
+            new_file(r#"let out = ""; for i in 1..3 { out += `push1 ${i}` + "\n"; } out"#);
+        let (bytes, _) = new_file("[0xde, 0xad]");
+
+        let text = format!(
+            r#"
+                %script("{}")
+                %script("{}")
+            "#,
+            text.path().display(),
+            bytes.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("60016002dead"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "script")]
+    fn ingest_script_failed() {
+        let (f, root) = new_file("let x = 1;\nnope(x)");
+        let text = format!(r#"%script("{}")"#, f.path().display());
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest(root, &text).unwrap_err();
+
+        assert_matches!(err, Error::Script { .. });
+        assert_eq!(err.span().unwrap().start, (2, 1));
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn ingest_include_artifact_unlinked() {
+        let (f, root) = new_file(r#"{"bytecode": "0x73__$abcd$__"}"#);
+        let text = format!(r#"%include_artifact("{}")"#, f.path().display());
+
+        let mut ingest = Ingest::new(Vec::new());
+        let err = ingest.ingest(root, &text).unwrap_err();
+
+        assert_matches!(err, Error::InvalidArtifact { .. });
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn ingest_large_include_hex() -> Result<(), Error> {
+        // Large enough to be mapped with the `mmap` feature.
+        let blob = "deadbeef".repeat(1 << 14);
+        let (f, root) = new_file(&blob);
+
+        let text = format!(
+            r#"
+                push1 1
+                %include_hex("{}")
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+
+        assert_eq!(output[..2], hex!("6001"));
+        assert_eq!(output[2..], hex::decode(&blob).unwrap()[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_file_large() -> Result<(), Error> {
+        let text = "push1 1\n".repeat(1 << 14);
+        let (f, _) = new_file(&text);
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest_file(f.path())?;
+
+        assert_eq!(output, hex!("6001").repeat(1 << 14));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_file_invalid_utf8() {
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(&[0x70, 0x63, 0xff]).unwrap();
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let err = ingest.ingest_file(f.path()).unwrap_err();
+
+        assert_matches!(err, Error::Io { source, .. } if source.kind() == io::ErrorKind::InvalidData);
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn ingest_include_hex_label() -> Result<(), Error> {
+        let (f, root) = new_file("deadbeef0102f6");
+
+        let text = format!(
+            r#"
+                push1 1
+                %include_hex("{}")
+                a:
+                jumpdest
+                push1 a
+                push1 0xff
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+        assert_eq!(output, hex!("6001deadbeef0102f65b600960ff"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_hex_chunks() {
+        let data: Vec<u8> = (0..3 * HEX_CHUNK / 2 + 5).map(|i| i as u8).collect();
+        let text = hex::encode(&data);
+
+        assert_eq!(decode_hex(text.as_bytes()).unwrap(), data);
+    }
+
+    #[test]
+    fn decode_hex_normalizes() {
+        assert_eq!(
+            decode_hex(b"\n  0XDEADbeef\n  0102 f6\r\n").unwrap(),
+            hex!("deadbeef0102f6")
+        );
+        assert_eq!(decode_hex(b"c0de").unwrap(), hex!("c0de"));
+        assert!(decode_hex(b"0x").unwrap().is_empty());
+        assert_eq!(
+            decode_hex(b"0x 0 0 0").unwrap_err(),
+            hex::FromHexError::OddLength
+        );
+    }
+
+    #[test]
+    fn decode_hex_reports_first_error() {
+        let mut text = vec![b'a'; 3 * HEX_CHUNK];
+        text[HEX_CHUNK + 3] = b'x';
+        text[2 * HEX_CHUNK + 1] = b'y';
+
+        let err = decode_hex(&text).unwrap_err();
+        assert_eq!(
+            err,
+            hex::FromHexError::InvalidHexCharacter {
+                c: 'x',
+                index: HEX_CHUNK + 3,
+            }
+        );
+
+        let err = decode_hex(b"abc").unwrap_err();
+        assert_eq!(err, hex::FromHexError::OddLength);
+    }
+
+    #[test]
+    fn cache_reuses_include_hex() -> Result<(), Error> {
+        let (f, root) = new_file("deadbeef");
+
+        let text = format!(
+            r#"
+                %include_hex("{}")
+            "#,
+            f.path().display(),
+        );
+
+        let (output, mut cache) = build(Cache::new(), &root, &text)?;
+        assert_eq!(output, hex!("deadbeef"));
+
+        // Tamper with the cached bytes to prove they're used instead of decoding
+        // the file again.
+        let decoded = cache.hex.values_mut().next().unwrap();
+        *decoded = vec![0x5b];
+
+        let (output, cache) = build(cache, &root, &text)?;
+        assert_eq!(output, hex!("5b"));
+
+        // Changing the file misses the cache.
+        std::fs::write(f.path(), "58").unwrap();
+
+        let (output, cache) = build(cache, &root, &text)?;
+        assert_eq!(output, hex!("58"));
+        assert_eq!(cache.hex.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_pending_then_raw() -> Result<(), Error> {
+        let (f, root) = new_file("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+
+        let text = format!(
+            r#"
+                push2 lbl
+                %include_hex("{}")
+                lbl:
+                jumpdest
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+
+        let expected = hex!("61001caaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa5b");
+        assert_eq!(output, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_import_in_import() -> Result<(), Error> {
+        let (end, _) = new_file(
+            r#"
+                end:
+                jumpdest
+                push1 start
+                push1 middle
+            "#,
+        );
+
+        let (middle, root) = new_file(format!(
+            r#"
+                %import("{}")
+                middle:
+                jumpdest
+                push2 start
+                push2 end
+            "#,
+            end.path().display(),
+        ));
+
+        let text = format!(
+            r#"
+                push3 end
+                push3 middle
+                start:
+                jumpdest
+                %import("{}")
+            "#,
+            middle.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+
+        let expected = hex!("620000096200000e5b5b6008600e5b610008610009");
+        assert_eq!(output, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_import_in_include() -> Result<(), Error> {
+        let (end, _) = new_file(
+            r#"
+                included:
+                jumpdest
+                push2 backward
+                push2 forward
+            "#,
+        );
+
+        let (middle, root) = new_file(format!(
+            r#"
+                pc
+                push1 backward
+                forward:
+                jumpdest
+                %import("{}")
+                backward:
+                jumpdest
+                push1 forward
+                push1 included
+            "#,
+            end.path().display(),
+        ));
+
+        let text = format!(
+            r#"
+                push3 backward
+                forward:
+                jumpdest
+                %include("{}")
+                backward:
+                jumpdest
+                push3 forward
+            "#,
+            middle.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text)?;
+
+        let expected = hex!("620000155b58600b5b5b61000b6100035b600360045b62000004");
+        assert_eq!(output, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_files() -> Result<(), Error> {
+        let (hex, _) = new_file("5b");
+        let (end, _) = new_file("pc");
+
+        let (middle, root) = new_file(format!(
+            r#"
+                %import("{0}")
+                %include_hex("{1}")
+                %import("{0}")
+            "#,
+            end.path().display(),
+            hex.path().display(),
+        ));
+
+        let text = format!(
+            r#"
+                %include("{}")
+            "#,
+            middle.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(&root, &text)?;
+
+        let expected = [
+            root,
+            middle.path().to_owned(),
+            end.path().to_owned(),
+            hex.path().to_owned(),
+        ];
+        assert_eq!(ingest.files(), expected);
+
+        Ok(())
+    }
+
+    fn build(cache: Cache, root: &Path, text: &str) -> Result<(Vec<u8>, Cache), Error> {
+        let mut output = Vec::new();
+        let mut ingest = Ingest::with_cache(&mut output, cache);
+        ingest.ingest(root, text)?;
+        let cache = ingest.into_cache();
+        Ok((output, cache))
+    }
+
+    #[test]
+    fn cache_reuses_include() -> Result<(), Error> {
+        let (f, root) = new_file("pc");
+
+        let text = format!(
+            r#"
+                %include("{}")
+            "#,
+            f.path().display(),
+        );
+
+        let (output, mut cache) = build(Cache::new(), &root, &text)?;
+        assert_eq!(output, hex!("58"));
+
+        // Tamper with the cached output to prove it's used instead of the file.
+        let path = root.parent().unwrap().join(f.path());
+        cache.included.get_mut(&path).unwrap().raw = vec![0x5b];
+
+        let (output, _) = build(cache, &root, &text)?;
+        assert_eq!(output, hex!("5b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_reports_include_warnings() -> Result<(), Error> {
+        let (f, root) = new_file(
+            r#"
+                unused:
+                callcode
+                push4 selector("gasprice_bit_ether(int128)")
+            "#,
+        );
+
+        let text = format!(
+            r#"
+                %include("{}")
+                push4 selector("transferFrom(address,address,uint256)")
+            "#,
+            f.path().display(),
+        );
+
+        let lints = |ingest: &Ingest<_>| -> Vec<_> {
+            let mut lints: Vec<_> = ingest
+                .warnings()
+                .iter()
+                .map(|w| (w.lint, w.span.map(|s| s.start)))
+                .collect();
+            lints.sort();
+            lints
+        };
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::with_cache(&mut output, Cache::new());
+        ingest.ingest(&root, &text)?;
+        let first = lints(&ingest);
+        let cache = ingest.into_cache();
+
+        assert_eq!(
+            first,
+            [
+                (Lint::UnusedLabel, Some((2, 17))),
+                (Lint::DeprecatedInstruction, Some((3, 17))),
+                (Lint::StackUnderflow, Some((3, 17))),
+                (Lint::SelectorCollision, Some((4, 17))),
+            ]
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::with_cache(&mut output, cache);
+        ingest.ingest(&root, &text)?;
+        assert_eq!(lints(&ingest), first);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_keeps_runtime_symbols() -> Result<(), Error> {
+        let (f, root) = new_file("push1 start\nstart:\njumpdest");
+        let text = format!("push1 1\n%include(\"{}\")", f.path().display());
+
+        let context = AssemblerContext::new().runtime(true);
+        let mut cache = Cache::new();
+
+        for _ in 0..2 {
+            let mut output = Vec::new();
+            let mut ingest = context.ingest_with_cache(&mut output, cache);
+            ingest.ingest(&root, &text)?;
+
+            let listed: Vec<_> = ingest
+                .symbols()
+                .iter()
+                .map(|s| (s.name.as_str(), s.offset))
+                .collect();
+            assert_eq!(listed, [("start", 2)]);
+
+            cache = ingest.into_cache();
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_invalidates_through_imports() -> Result<(), Error> {
+        let (end, _) = new_file("pc");
+
+        let (middle, root) = new_file(format!(
+            r#"
+                %import("{}")
+            "#,
+            end.path().display(),
+        ));
+
+        let text = format!(
+            r#"
+                %include("{}")
+            "#,
+            middle.path().display(),
+        );
+
+        let (output, cache) = build(Cache::new(), &root, &text)?;
+        assert_eq!(output, hex!("58"));
+
+        std::fs::write(end.path(), "caller").unwrap();
+
+        let (output, _) = build(cache, &root, &text)?;
+        assert_eq!(output, hex!("33"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cache_keeps_entries_after_failure() -> Result<(), Error> {
+        let (_, root) = new_file("");
+
+        let (_, cache) = build(Cache::new(), &root, "pc")?;
+        assert_eq!(cache.parsed.len(), 1);
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::with_cache(&mut output, cache);
+        ingest
+            .ingest(&root, "%import(\"missing.etk\")")
+            .unwrap_err();
+        let cache = ingest.into_cache();
+        assert_eq!(cache.parsed.len(), 2);
+
+        let (_, cache) = build(cache, &root, "caller")?;
+        assert_eq!(cache.parsed.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_define() -> Result<(), Error> {
+        let (f, root) = new_file("push1 ONE");
+
+        let text = format!(
+            r#"
+                push2 FEE
+                %push(FEE)
+                %push(ZERO)
+                %include("{}")
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("FEE", vec![0x00, 0x00, 0x03, 0xe8])
+            .define("ZERO", vec![])
+            .define("ONE", vec![0x01])
+            .ingest(&mut output);
+        ingest.ingest(root, &text)?;
+
+        assert_eq!(output, hex!("6103e86103e860006001"));
+
+        Ok(())
     }
-}
 
-impl<W> Ingest<W>
-where
-    W: Write,
-{
-    /// Assemble instructions from the file located at `path`.
-    pub fn ingest_file<P>(&mut self, path: P) -> Result<(), Error>
-    where
-        P: Into<PathBuf>,
-    {
-        let path = path.into();
+    #[test]
+    fn ingest_define_too_large() {
+        let (_, root) = new_file("");
 
-        let mut file = File::open(&path).with_context(|| error::Io {
-            message: "opening source",
-            path: path.clone(),
-        })?;
-        let mut text = String::new();
-        file.read_to_string(&mut text).with_context(|| error::Io {
-            message: "reading source",
-            path: path.clone(),
-        })?;
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("FEE", vec![0x03, 0xe8])
+            .ingest(&mut output);
+        let err = ingest.ingest(root, "push1 FEE").unwrap_err();
 
-        self.ingest(path, &text)
+        assert_matches!(err, Error::DefineTooLarge { name, .. } if name == "FEE");
     }
 
-    /// Assemble instructions from `src` as if they were read from a file located
-    /// at `path`.
-    pub fn ingest<P>(&mut self, path: P, src: &str) -> Result<(), Error>
-    where
-        P: Into<PathBuf>,
-    {
-        let nodes = parse_asm(src)?;
-        let partial = self.sources.resolve(path.into(), Scope::independent())?;
-        partial.push(nodes);
+    #[test]
+    fn ingest_define_conflict() {
+        let (_, root) = new_file("");
 
-        while let Some(source) = self.sources.peek() {
-            let node = match source.nodes.next() {
-                Some(n) => n,
-                None => {
-                    self.sources.pop()?;
-                    continue;
-                }
-            };
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("a", vec![0x01])
+            .ingest(&mut output);
+        let err = ingest.ingest(root, "a:\njumpdest").unwrap_err();
 
-            match node {
-                Node::Op(op) => {
-                    self.sources.write(RawOp::Op(op))?;
-                }
-                Node::Raw(raw) => {
-                    self.sources.write(RawOp::Raw(raw))?;
-                }
-                Node::Import(path) => {
-                    let partial = self.sources.resolve(path, Scope::same())?;
-                    let parsed = parse_file(partial.path())?;
-                    partial.push(parsed);
-                }
-                Node::Include(path) => {
-                    let partial = self.sources.resolve(path, Scope::independent())?;
-                    let parsed = parse_file(partial.path())?;
-                    partial.push(parsed);
-                }
-                Node::IncludeHex(path) => {
-                    let partial = self.sources.resolve(path, Scope::same())?;
+        assert_matches!(err, Error::DefineConflict { name, .. } if name == "a");
+    }
 
-                    let file =
-                        std::fs::read_to_string(partial.path()).with_context(|| error::Io {
-                            message: "reading hex include",
-                            path: partial.path().to_owned(),
-                        })?;
+    #[test]
+    fn ingest_unsupported_instruction() {
+        let (_, root) = new_file("");
 
-                    let raw = hex::decode(file)
-                        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-                        .context(error::InvalidHex {
-                            path: partial.path().to_owned(),
-                        })?;
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .fork(Fork::Berlin)
+            .ingest(&mut output);
+        let err = ingest.ingest(root, "chainid\nbasefee").unwrap_err();
 
-                    partial.push(vec![Node::Raw(raw)]);
-                }
+        assert_matches!(
+            err,
+            Error::UnsupportedInstruction {
+                op: Specifier::BaseFee,
+                fork: Fork::Berlin,
+                ..
             }
-        }
+        );
+    }
 
-        if !self.sources.sources.is_empty() {
-            panic!("extra sources?");
-        }
+    #[test]
+    fn cache_invalidates_on_define() -> Result<(), Error> {
+        let (f, root) = new_file("push1 VALUE");
+
+        let text = format!(
+            r#"
+                %include("{}")
+            "#,
+            f.path().display(),
+        );
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("VALUE", vec![0x01])
+            .ingest(&mut output);
+        ingest.ingest(&root, &text)?;
+        let cache = ingest.into_cache();
+        assert_eq!(output, hex!("6001"));
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .define("VALUE", vec![0x02])
+            .ingest_with_cache(&mut output, cache);
+        ingest.ingest(&root, &text)?;
+        assert_eq!(output, hex!("6002"));
 
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use assert_matches::assert_matches;
+    #[test]
+    fn ingest_directory_traversal() {
+        let (f, _) = new_file("pc");
 
-    use crate::asm::Error as AsmError;
+        let text = format!(
+            r#"
+                %include("{}")
+            "#,
+            f.path().display(),
+        );
 
-    use hex_literal::hex;
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let root = std::env::current_exe().unwrap();
+        let err = ingest.ingest(root, &text).unwrap_err();
 
-    use std::fmt::Display;
-    use std::io::Write;
+        assert_matches!(err, Error::DirectoryTraversal { .. });
+    }
 
-    use super::*;
+    #[test]
+    fn ingest_recursive() {
+        let (mut f, root) = new_file("");
+        let path = f.path().display().to_string();
+        write!(f, r#"%import("{}")"#, path).unwrap();
 
-    use tempfile::NamedTempFile;
+        let text = format!(
+            r#"
+                %import("{}")
+            "#,
+            path,
+        );
 
-    fn new_file<S: Display>(s: S) -> (NamedTempFile, PathBuf) {
-        let mut f = NamedTempFile::new().unwrap();
-        let root = f.path().parent().unwrap().join("root.asm");
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let err = ingest.ingest(root, &text).unwrap_err();
 
-        write!(f, "{}", s).unwrap();
-        (f, root)
+        assert_matches!(err, Error::RecursionLimit { .. });
     }
 
     #[test]
-    fn ingest_import() -> Result<(), Error> {
-        let (f, root) = new_file("push1 42");
+    #[cfg(feature = "parallel")]
+    fn prefetch_imports() -> Result<(), Error> {
+        let (a, root) = new_file("pc");
+        let (b, _) = new_file("caller");
 
         let text = format!(
             r#"
-            push1 1
-            %import("{}")
-            push1 2
-        "#,
-            f.path().display()
+                %import("{}")
+                %include("{}")
+            "#,
+            a.path().display(),
+            b.path().display(),
         );
 
-        let mut output = Vec::new();
-        let mut ingest = Ingest::new(&mut output);
-        ingest.ingest(root, &text)?;
-        assert_eq!(output, hex!("6001602a6002"));
+        let (output, cache) = build(Cache::new(), &root, &text)?;
+        assert_eq!(output, hex!("5833"));
+        assert_eq!(cache.parsed.len(), 3);
 
         Ok(())
     }
 
     #[test]
-    fn ingest_include() -> Result<(), Error> {
-        let (f, root) = new_file(
+    fn cache_keyed_on_parse_options() -> Result<(), Error> {
+        let text = "%alias(two, \"push1 2 push1 2\")\ntwo\ntwo";
+
+        let mut ingest = Ingest::new(Vec::new());
+        ingest.ingest("./main.etk", text)?;
+        let cache = ingest.into_cache();
+
+        let mut ingest = AssemblerContext::new()
+            .macro_limits(2, 1)
+            .ingest_with_cache(Vec::new(), cache);
+        let err = ingest.ingest("./main.etk", text).unwrap_err();
+        assert_eq!(err.code(), codes::EXPANSION_LIMIT);
+
+        let mut ingest = AssemblerContext::new()
+            .experimental(Experimental::proposals())
+            .ingest_with_cache(Vec::new(), ingest.into_cache());
+        ingest.ingest("./main.etk", "jumpsub")?;
+
+        let mut ingest = Ingest::with_cache(Vec::new(), ingest.into_cache());
+        let err = ingest.ingest("./main.etk", "jumpsub").unwrap_err();
+        assert_eq!(err.code(), codes::EXPERIMENTAL_DISABLED);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn prefetch_reports_errors_in_order() {
+        let (a, root) = new_file("pusj1 1");
+        let (b, _) = new_file("push1 nowhere");
+
+        let text = format!(
             r#"
-                a:
-                jumpdest
-                pc
-                push1 a
-                jump
+                %import("{}")
+                %include("{}")
             "#,
+            a.path().display(),
+            b.path().display(),
         );
 
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        let err = ingest.ingest(root, &text).unwrap_err();
+
+        assert_matches!(err, Error::Parse { .. });
+        assert_eq!(ingest.failed_file(), Some(a.path()));
+    }
+
+    #[test]
+    fn streaming_matches_buffered() -> Result<(), Error> {
+        let (hex_file, root) = new_file("deadbeef");
+        let (include, _) = new_file("a:\npush1 a\njumpdest");
+
         let text = format!(
             r#"
-            push1 1
-            %include("{}")
-            push1 2
-        "#,
-            f.path().display()
+                push1 1
+                %push(later)
+                %include_hex("{}")
+                %include("{}")
+                later:
+                jumpdest
+                %push(later)
+            "#,
+            hex_file.path().display(),
+            include.path().display(),
         );
 
+        let mut buffered = Vec::new();
+        Ingest::new(&mut buffered).ingest(&root, &text)?;
+
+        let mut streamed = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .streaming(true)
+            .ingest(&mut streamed);
+        ingest.ingest(&root, &text)?;
+
+        assert_eq!(streamed, buffered);
+        assert_eq!(streamed, hex!("6001600bdeadbeef60005b5b600b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn streaming_writes_before_error() {
+        let (_, root) = new_file("");
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new().streaming(true).ingest(&mut output);
+        ingest
+            .ingest(&root, "push1 1\npush1 nowhere\npush1 2")
+            .unwrap_err();
+
+        assert_eq!(output, hex!("6001"));
+    }
+
+    #[test]
+    fn failed_file_in_root() {
+        let (_, root) = new_file("");
+
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
-        ingest.ingest(root, &text)?;
-        assert_eq!(output, hex!("60015b586000566002"));
+        ingest.ingest(&root, "push1 nowhere").unwrap_err();
 
-        Ok(())
+        assert_eq!(ingest.failed_file(), Some(root.as_path()));
     }
 
     #[test]
-    fn ingest_import_twice() {
-        let (f, root) = new_file(
+    fn failed_file_in_include() {
+        let (f, root) = new_file("push1 nowhere");
+
+        let text = format!(
             r#"
-                a:
-                jumpdest
-                push1 a
+                %include("{}")
             "#,
+            f.path().display(),
         );
 
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(root, &text).unwrap_err();
+
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn failed_file_parse_import() {
+        let (f, root) = new_file("pusj1 1");
+
         let text = format!(
             r#"
-                push1 1
-                %import("{0}")
-                %import("{0}")
-                push1 2
+                %import("{}")
             "#,
-            f.path().display()
+            f.path().display(),
         );
 
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
         let err = ingest.ingest(root, &text).unwrap_err();
 
-        assert_matches!(
-            err,
-            Error::Assemble {
-                source: AsmError::DuplicateLabel { label, ..}
-            } if label == "a"
-        );
+        assert_matches!(err, Error::Parse { .. });
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn failed_file_cleared_on_success() {
+        let (_, root) = new_file("");
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(&root, "pusj1 1").unwrap_err();
+        ingest.ingest(&root, "push1 1").unwrap();
+
+        assert_eq!(ingest.failed_file(), None);
+    }
+
+    #[derive(Debug)]
+    struct Remap(PathBuf);
+
+    impl Resolver for Remap {
+        fn resolve(&self, _: &Path, path: &Path) -> Option<PathBuf> {
+            if path == Path::new("@lib") {
+                Some(self.0.clone())
+            } else {
+                None
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct Collect(Arc<std::sync::Mutex<Vec<Warning>>>);
+
+    impl Sink for Collect {
+        fn warning(&self, warning: &Warning) {
+            self.0.lock().unwrap().push(warning.clone());
+        }
+    }
+
+    #[test]
+    fn context_resolver() -> Result<(), Error> {
+        let (f, root) = new_file("push1 42");
+
+        let context = AssemblerContext::new().resolver(Remap(f.path().to_owned()));
+
+        let mut output = Vec::new();
+        let mut ingest = context.ingest(&mut output);
+        ingest.ingest(root, r#"%import("@lib")"#)?;
+        assert_eq!(ingest.files()[1], f.path());
+        assert_eq!(output, hex!("602a"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn context_sink() -> Result<(), Error> {
+        let sink = Collect::default();
+        let context = AssemblerContext::new()
+            .fork(Fork::Byzantium)
+            .sink(sink.clone());
+
+        let mut output = Vec::new();
+        let mut ingest = context.ingest(&mut output);
+        ingest.ingest("./root.etk", "a:\nreturndatasize")?;
+
+        let warnings = sink.0.lock().unwrap();
+        assert_eq!(*warnings, ingest.warnings());
+        assert_eq!(warnings[0].lint, Lint::UnusedLabel);
+
+        // The context's fork applies.
+        let mut ingest = context.ingest(Vec::new());
+        let err = ingest.ingest("./root.etk", "chainid").unwrap_err();
+        assert_matches!(err, Error::UnsupportedInstruction { .. });
+
+        Ok(())
     }
 
     #[test]
-    fn ingest_include_hex() -> Result<(), Error> {
-        let (f, root) = new_file("deadbeef0102f6");
+    fn warnings_unused_label_in_include() -> Result<(), Error> {
+        let (f, root) = new_file("a:\njumpdest");
 
         let text = format!(
             r#"
-                push1 1
-                %include_hex("{}")
-                push1 2
+                push1 a
+                %include("{}")
+                a:
+                jumpdest
             "#,
             f.path().display(),
         );
@@ -590,23 +5319,23 @@ mod tests {
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
         ingest.ingest(root, &text)?;
-        assert_eq!(output, hex!("6001deadbeef0102f66002"));
+
+        let warnings = ingest.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::UnusedLabel);
+        assert_eq!(warnings[0].file.as_deref(), Some(f.path()));
 
         Ok(())
     }
 
     #[test]
-    fn ingest_include_hex_label() -> Result<(), Error> {
-        let (f, root) = new_file("deadbeef0102f6");
+    fn warnings_stack_underflow_through_import() -> Result<(), Error> {
+        let (f, root) = new_file("add");
 
         let text = format!(
             r#"
                 push1 1
-                %include_hex("{}")
-                a:
-                jumpdest
-                push1 a
-                push1 0xff
+                %import("{}")
             "#,
             f.path().display(),
         );
@@ -614,21 +5343,23 @@ mod tests {
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
         ingest.ingest(root, &text)?;
-        assert_eq!(output, hex!("6001deadbeef0102f65b600960ff"));
+
+        let warnings = ingest.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::StackUnderflow);
+        assert_eq!(warnings[0].file.as_deref(), Some(f.path()));
 
         Ok(())
     }
 
     #[test]
-    fn ingest_pending_then_raw() -> Result<(), Error> {
-        let (f, root) = new_file("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    fn warnings_selector_collision_across_includes() -> Result<(), Error> {
+        let (f, root) = new_file(r#"push4 selector("gasprice_bit_ether(int128)")"#);
 
         let text = format!(
             r#"
-                push2 lbl
-                %include_hex("{}")
-                lbl:
-                jumpdest
+                push4 selector("transferFrom(address,address,uint256)")
+                %include("{}")
             "#,
             f.path().display(),
         );
@@ -637,140 +5368,475 @@ mod tests {
         let mut ingest = Ingest::new(&mut output);
         ingest.ingest(root, &text)?;
 
-        let expected = hex!("61001caaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa5b");
-        assert_eq!(output, expected);
+        let warnings = ingest.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::SelectorCollision);
+        assert_eq!(warnings[0].file.as_deref(), Some(f.path()));
+        assert_eq!(warnings[0].span.map(|s| s.start), Some((1, 1)));
 
         Ok(())
     }
 
     #[test]
-    fn ingest_import_in_import() -> Result<(), Error> {
-        let (end, _) = new_file(
-            r#"
-                end:
-                jumpdest
-                push1 start
-                push1 middle
-            "#,
-        );
+    fn warnings_lint_levels() -> Result<(), Error> {
+        let (_, root) = new_file("");
 
-        let (middle, root) = new_file(format!(
-            r#"
-                %import("{}")
-                middle:
-                jumpdest
-                push2 start
-                push2 end
-            "#,
-            end.path().display(),
-        ));
+        let mut levels = Levels::new();
+        levels
+            .set(Lint::StackUnderflow, Level::Allow)
+            .set(Lint::DeprecatedInstruction, Level::Deny);
 
-        let text = format!(
-            r#"
-                push3 end
-                push3 middle
-                start:
-                jumpdest
-                %import("{}")
-            "#,
-            middle.path().display(),
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new().lints(levels).ingest(&mut output);
+        ingest.ingest(&root, "callcode")?;
+
+        let warnings = ingest.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::DeprecatedInstruction);
+        assert_eq!(warnings[0].level, Level::Deny);
+
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_spans() -> Result<(), Error> {
+        let (_, root) = new_file("");
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(&root, "push1 1\n  callcode\nunused:\n")?;
+
+        let spans: Vec<_> = ingest
+            .warnings()
+            .iter()
+            .map(|w| (w.lint, w.span.map(|s| s.start)))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (Lint::DeprecatedInstruction, Some((2, 3))),
+                (Lint::StackUnderflow, Some((2, 3))),
+                (Lint::UnusedLabel, Some((3, 1))),
+            ]
         );
 
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_cleared_between_ingests() -> Result<(), Error> {
+        let (_, root) = new_file("");
+
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
-        ingest.ingest(root, &text)?;
 
-        let expected = hex!("620000096200000e5b5b6008600e5b610008610009");
-        assert_eq!(output, expected);
+        ingest.ingest(&root, "callcode")?;
+        assert_eq!(ingest.warnings().len(), 2);
+
+        ingest.ingest(&root, "push1 1")?;
+        assert!(ingest.warnings().is_empty());
 
         Ok(())
     }
 
     #[test]
-    fn ingest_import_in_include() -> Result<(), Error> {
-        let (end, _) = new_file(
-            r#"
-                included:
-                jumpdest
-                push2 backward
-                push2 forward
-            "#,
-        );
+    fn warnings_oversized_push() -> Result<(), Error> {
+        let (_, root) = new_file("");
 
-        let (middle, root) = new_file(format!(
-            r#"
-                pc
-                push1 backward
-                forward:
-                jumpdest
-                %import("{}")
-                backward:
-                jumpdest
-                push1 forward
-                push1 included
-            "#,
-            end.path().display(),
-        ));
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(&root, "push32 1\npush32 keccak(a, a)\na:")?;
 
-        let text = format!(
-            r#"
-                push3 backward
-                forward:
-                jumpdest
-                %include("{}")
-                backward:
-                jumpdest
-                push3 forward
-            "#,
-            middle.path().display(),
-        );
+        let lints: Vec<_> = ingest.warnings().iter().map(|w| w.lint).collect();
+        assert_eq!(lints, [Lint::OversizedPush]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_label_used_in_expression() -> Result<(), Error> {
+        let (_, root) = new_file("");
 
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
-        ingest.ingest(root, &text)?;
+        ingest.ingest(
+            &root,
+            "push1 len(a, b)
+%assert(c > 0)
+a:
+b:
+c:
+d:",
+        )?;
 
-        let expected = hex!("620000155b58600b5b5b61000b6100035b600360045b62000004");
-        assert_eq!(output, expected);
+        let messages: Vec<_> = ingest.warnings().iter().map(|w| &w.message).collect();
+        assert_eq!(messages, ["label `d` is never used"]);
 
         Ok(())
     }
 
     #[test]
-    fn ingest_directory_traversal() {
-        let (f, _) = new_file("pc");
+    fn warnings_memory_layout() -> Result<(), Error> {
+        let (_, root) = new_file("");
+        let text = r#"
+            %import(std, "memory")
+            push1 0x00
+            push1 0x60
+            mstore
+            %pragma(solidity_memory)
+            init_free_ptr
+            push1 0x40
+            alloc
+            push1 0x00
+            push1 0x20
+            mstore
+            push1 0x00
+            push1 0x7f
+            mstore8
+            push1 0x00
+            push1 0x3f
+            mstore
+        "#;
+
+        let mut output = Vec::new();
+        let mut ingest = Ingest::new(&mut output);
+        ingest.ingest(&root, text)?;
+
+        let messages: Vec<_> = ingest.warnings().iter().map(|w| &w.message).collect();
+        assert_eq!(
+            messages,
+            [
+                "`mstore8` to 0x7f overwrites the zero slot (0x60 to 0x7f), which Solidity \
+                expects to always be zero",
+                "`mstore` to 0x3f overwrites part of the free memory pointer (0x40 to 0x5f)",
+            ]
+        );
+
+        Ok(())
+    }
 
+    #[test]
+    fn ingest_optimize_size() -> Result<(), Error> {
+        let (f, root) = new_file("push1 end - start\nstart:\npush4 1\nend:");
         let text = format!(
             r#"
+                push8 0x42
+                push2 a
+                a:
                 %include("{}")
             "#,
             f.path().display(),
         );
 
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .optimize_size(true)
+            .ingest(&mut output);
+        ingest.ingest(&root, &text)?;
+        drop(ingest);
+
+        // The include does math on labels, so its push is left alone.
+        assert_eq!(output, hex!("6042 610005 6005 6300000001"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_buildinfo() -> Result<(), Error> {
+        let (_, root) = new_file("");
+        let text = "push1 1\n%buildinfo()\npush1 2\n";
+
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
-        let root = std::env::current_exe().unwrap();
-        let err = ingest.ingest(root, &text).unwrap_err();
+        ingest.ingest(&root, text)?;
 
-        assert_matches!(err, Error::DirectoryTraversal { .. });
+        // The trailer goes at the end, wherever the directive is.
+        assert_eq!(output[..4], hex!("60016002"));
+        assert_eq!(output.len(), 4 + crate::buildinfo::TRAILER_LEN);
+
+        let info = BuildInfo::from_code(&output).unwrap();
+        assert_eq!(info, BuildInfo::new(hash(hash(text))));
+
+        Ok(())
     }
 
     #[test]
-    fn ingest_recursive() {
-        let (mut f, root) = new_file("");
-        let path = f.path().display().to_string();
-        write!(f, r#"%import("{}")"#, path).unwrap();
+    fn estimate_matches_ingest() -> Result<(), Error> {
+        let (f, root) = new_file("push1 len(a, b)\na:\npc\nb:");
 
         let text = format!(
             r#"
-                %import("{}")
-            "#,
-            path,
+            %include("{}")
+            start:
+            %push(end)
+            jump
+            end:
+            jumpdest
+        "#,
+            f.path().display(),
+        );
+
+        let mut expected = Vec::new();
+        let mut ingest = Ingest::new(&mut expected);
+        ingest.ingest(&root, &text)?;
+        let symbols = ingest.symbols().to_vec();
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new().stamp(true).ingest(&mut output);
+
+        let estimate = ingest.estimate(&root, &text)?;
+        assert_eq!(
+            estimate.size,
+            expected.len() + crate::buildinfo::TRAILER_LEN
         );
+        assert_eq!(estimate.symbols, symbols);
+
+        // Values are left out, so an estimate isn't held up by them.
+        let estimate =
+            ingest.estimate(&root, &format!("{}\n%assert(0)\npush1 end + 0xff", text))?;
+        assert_eq!(
+            estimate.size,
+            expected.len() + crate::buildinfo::TRAILER_LEN + 2
+        );
+        assert!(ingest.warnings().is_empty());
+
+        // Later estimates reuse the included file, and can still fail.
+        let err = ingest
+            .estimate(&root, &format!("{}\npush1 nowhere", text))
+            .unwrap_err();
+        assert_matches!(err, Error::UndeclaredLabels { .. });
+
+        drop(ingest);
+        assert!(output.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn estimate_then_ingest_warns() -> Result<(), Error> {
+        let (f, root) = new_file("push32 1");
+        let text = format!(r#"%include("{}")"#, f.path().display());
 
         let mut output = Vec::new();
         let mut ingest = Ingest::new(&mut output);
-        let err = ingest.ingest(root, &text).unwrap_err();
 
-        assert_matches!(err, Error::RecursionLimit { .. });
+        ingest.estimate(&root, &text)?;
+        assert!(ingest.warnings().is_empty());
+
+        // The estimate didn't lint the include, so it isn't reused here.
+        ingest.ingest(&root, &text)?;
+        assert_eq!(ingest.warnings().len(), 1);
+        assert_eq!(ingest.warnings()[0].lint, Lint::OversizedPush);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_stamp_covers_includes() -> Result<(), Error> {
+        let (f, root) = new_file("pc");
+        let text = format!(r#"%include("{}")"#, f.path().display());
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new().stamp(true).ingest(&mut output);
+        ingest.ingest(&root, &text)?;
+
+        let mut hasher = Keccak256::new();
+        hasher.update(hash(&text));
+        hasher.update(hash("pc"));
+        let expected = BuildInfo::new(hasher.finalize().into());
+
+        assert_eq!(output[0], 0x58);
+        assert_eq!(BuildInfo::from_code(&output), Some(expected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn buildinfo_in_cached_include() -> Result<(), Error> {
+        let (f, root) = new_file("pc\n%buildinfo()");
+        let text = format!(r#"%include("{}")"#, f.path().display());
+
+        let (first, cache) = build(Cache::new(), &root, &text)?;
+        let (second, _) = build(cache, &root, &text)?;
+
+        assert!(BuildInfo::from_code(&first).is_some());
+        assert_eq!(first, second);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stamp_ignores_location() -> Result<(), Error> {
+        let mut outputs = Vec::new();
+
+        for _ in 0..2 {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("lib.etk"), "lbl:\njumpdest\n").unwrap();
+            std::fs::write(dir.path().join("data.hex"), "c0ffee").unwrap();
+            std::fs::write(
+                dir.path().join("main.etk"),
+                "%import(\"lib.etk\")\n%include_hex(\"data.hex\")\n%push(lbl)\n%buildinfo()\n",
+            )
+            .unwrap();
+
+            let mut output = Vec::new();
+            let mut ingest = Ingest::new(&mut output);
+            ingest.ingest_file(dir.path().join("main.etk"))?;
+            outputs.push(output);
+        }
+
+        assert_eq!(outputs[0], outputs[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_huff_includes() -> Result<(), Error> {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+
+        std::fs::write(
+            dir.path().join("lib").join("math.huff"),
+            "#define constant SLOT = FREE_STORAGE_POINTER()\n\
+             #define macro ADD_ONE() = takes(1) returns(1) { 0x01 add }\n",
+        )
+        .unwrap();
+
+        // Included twice, directly and through `math.huff`, but only merged once.
+        std::fs::write(
+            dir.path().join("lib").join("both.huff"),
+            "#include \"./math.huff\"\n",
+        )
+        .unwrap();
+
+        let main = dir.path().join("main.huff");
+        std::fs::write(
+            &main,
+            "#include \"./lib/math.huff\"\n\
+             #include \"./lib/both.huff\"\n\
+             #define macro MAIN() = takes(0) returns(0) { [SLOT] ADD_ONE() }\n",
+        )
+        .unwrap();
+
+        let mut output = Vec::new();
+        let mut ingest = AssemblerContext::new()
+            .dialect(Dialect::Huff)
+            .ingest(&mut output);
+        ingest.ingest_file(&main)?;
+        let files = ingest.files().len();
+
+        assert_eq!(output, hex!("6000600101"));
+        assert_eq!(files, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ingest_huff_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.huff");
+        std::fs::write(
+            dir.path().join("lib.huff"),
+            "#define macro MAIN() = { 0x01 }",
+        )
+        .unwrap();
+        std::fs::write(
+            &main,
+            "#include \"lib.huff\"\n#define macro MAIN() = { 0x02 }\n",
+        )
+        .unwrap();
+
+        let mut ingest = AssemblerContext::new()
+            .dialect(Dialect::Huff)
+            .ingest(Vec::new());
+        let err = ingest.ingest_file(&main).unwrap_err();
+
+        assert_matches!(
+            err,
+            Error::Parse {
+                source: ParseError::DuplicateDefinition { .. },
+                ..
+            }
+        );
+        assert_eq!(ingest.failed_file(), Some(main.as_path()));
+
+        let (f, root) = new_file("#define macro MAIN() = { nowhere jump }");
+        let text = format!("#include \"{}\"", f.path().display());
+        std::fs::remove_file(f.path()).unwrap();
+
+        let err = ingest.ingest(&root, &text).unwrap_err();
+        assert_matches!(err, Error::Io { .. });
+        assert_eq!(ingest.failed_file(), Some(f.path()));
+    }
+
+    #[test]
+    fn ingest_macro_limits() {
+        let text = "
+            #define macro TWO() = { 0x02 0x02 }
+            #define macro MAIN() = { TWO() TWO() }
+        ";
+
+        let mut ingest = AssemblerContext::new()
+            .dialect(Dialect::Huff)
+            .macro_limits(1, 1 << 20)
+            .ingest(Vec::new());
+        let err = ingest.ingest("./main.huff", text).unwrap_err();
+        assert_matches!(
+            err,
+            Error::Parse {
+                source: ParseError::RecursionLimit { limit: 1, .. },
+                ..
+            }
+        );
+
+        let context = AssemblerContext::new().macro_limits(2, 3);
+        let mut ingest = context.clone().dialect(Dialect::Huff).ingest(Vec::new());
+        let err = ingest.ingest("./main.huff", text).unwrap_err();
+        assert_matches!(
+            err,
+            Error::Parse {
+                source: ParseError::ExpansionLimit { limit: 3, .. },
+                ..
+            }
+        );
+
+        let mut ingest = context.ingest(Vec::new());
+        let err = ingest
+            .ingest(
+                "./main.etk",
+                "%alias(two, \"push1 2 push1 2\")\n%alias(four, \"two two\")",
+            )
+            .unwrap_err();
+        assert_eq!(err.span().map(|s| s.start), Some((2, 1)));
+        assert_matches!(
+            err,
+            Error::Parse {
+                source: ParseError::Located { error, .. },
+                ..
+            } if matches!(*error, ParseError::ExpansionLimit { limit: 3, .. })
+        );
+    }
+
+    #[test]
+    fn dialect_from_str() {
+        assert_eq!("huff".parse::<Dialect>().unwrap(), Dialect::Huff);
+        assert_eq!(Dialect::Etk.to_string(), "etk");
+        "hufff".parse::<Dialect>().unwrap_err();
+    }
+
+    #[test]
+    fn cache_keeps_include_written_early() -> Result<(), Error> {
+        // `pc` is ready as soon as it's assembled, so it's passed on to the
+        // root before the include finishes.
+        let (f, root) = new_file("pc\npush1 later\nlater:");
+        let text = format!(r#"%include("{}")"#, f.path().display());
+
+        let (first, cache) = build(Cache::new(), &root, &text)?;
+        let (second, _) = build(cache, &root, &text)?;
+
+        assert_eq!(first, hex!("586003"));
+        assert_eq!(second, first);
+
+        Ok(())
     }
 }
@@ -6,20 +6,42 @@
 //! The [`ingest`] module is high-level and similar to the command-line interface.
 //!
 //! The [`mod@asm`] module provides low-level access to the internals of the assembler.
+//! Code generators can skip the text front-end entirely, and hand their
+//! instructions to [`asm::assemble`]. The [`visit`] module has traits for
+//! inspecting and rewriting those instructions.
 //!
 //! All of the instructions are defined in the [`mod@ops`] module, and simple
 //! disassembly functionality is available in the [`disasm`] module.
+//!
+//! Warnings produced while assembling are described in the [`lint`] module,
+//! and every kind of error has a stable code, listed in the [`codes`] module.
+//!
+//! The [`solc`] module translates the assembly printed by `solc --asm` into
+//! etk sources.
 #![recursion_limit = "512"]
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 #![deny(unreachable_pub)]
 #![deny(missing_debug_implementations)]
 
+mod artifact;
 pub mod asm;
 mod ast;
+pub mod buildinfo;
+pub mod codes;
 pub mod disasm;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
 pub mod ingest;
+pub mod lint;
 pub mod ops;
 mod parse;
+mod script;
+pub mod solc;
+mod symbol;
+#[cfg(feature = "template")]
+mod template;
+pub mod visit;
 
-pub use self::parse::error::ParseError;
+pub use self::parse::error::{ParseError, Span};
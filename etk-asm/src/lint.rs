@@ -0,0 +1,895 @@
+//! Warnings about code that assembles, but probably doesn't behave as intended.
+//!
+//! Warnings are collected while assembling with [`Ingest`], and are available
+//! from [`Ingest::warnings`] afterwards.
+//!
+//! [`Ingest`]: crate::ingest::Ingest
+//! [`Ingest::warnings`]: crate::ingest::Ingest::warnings
+
+mod error {
+    use snafu::{Backtrace, Snafu};
+
+    /// The error that can arise while parsing a [`super::Lint`] from a string.
+    #[derive(Debug, Snafu)]
+    #[snafu(display("unknown lint: {}", text))]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub struct UnknownLintError {
+        text: String,
+        backtrace: Backtrace,
+    }
+
+    /// The error that can arise while parsing a [`super::Level`] from a string.
+    #[derive(Debug, Snafu)]
+    #[snafu(display("unknown lint level: {}", text))]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub struct UnknownLevelError {
+        text: String,
+        backtrace: Backtrace,
+    }
+}
+
+pub use self::error::{UnknownLevelError, UnknownLintError};
+
+use crate::ops::{AbstractOp, Experimental, Metadata, Op, Profile, Specifier, Support};
+use crate::symbol::{Interner, Symbol, SymbolHasher};
+use crate::Span;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::OptionExt;
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::BuildHasherDefault;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The largest contract that can be deployed, in bytes (see EIP-170.)
+pub const MAX_CODE_SIZE: usize = 24576;
+
+/// How many bytes wider than its constant a push must be before it's reported
+/// as [`Lint::OversizedPush`].
+pub const OVERSIZED_PUSH: u32 = 4;
+
+/// A kind of warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Lint {
+    /// A label is declared, but never used.
+    ///
+    /// Labels starting with an underscore (ex. `_loop`) are never reported.
+    UnusedLabel,
+
+    /// An instruction that is discouraged, like `callcode`, is used.
+    DeprecatedInstruction,
+
+    /// The assembled code is larger than [`MAX_CODE_SIZE`].
+    CodeSize,
+
+    /// Straight-line code at the start of the program pops more items than it
+    /// pushes.
+    StackUnderflow,
+
+    /// Two different function signatures given to `selector(...)` have the
+    /// same selector, so a dispatcher can't tell them apart.
+    SelectorCollision,
+
+    /// A push of a constant is at least [`OVERSIZED_PUSH`] bytes wider than
+    /// the constant needs, like `push32 1`.
+    OversizedPush,
+
+    /// After `%pragma(solidity_memory)`, a store to a constant offset
+    /// overwrites the zero slot, or writes the free memory pointer with
+    /// anything other than a whole `mstore`.
+    MemoryLayout,
+
+    /// An instruction that no fork has adopted, enabled with
+    /// [`AssemblerContext::experimental`], is used.
+    ///
+    /// [`AssemblerContext::experimental`]: crate::ingest::AssemblerContext::experimental
+    ExperimentalInstruction,
+
+    /// An instruction is used that doesn't work, or doesn't work the same
+    /// way, on the chain chosen with [`AssemblerContext::profile`].
+    ///
+    /// [`AssemblerContext::profile`]: crate::ingest::AssemblerContext::profile
+    UnsupportedInstruction,
+}
+
+impl Lint {
+    /// Every kind of warning.
+    pub const ALL: [Lint; 9] = [
+        Lint::UnusedLabel,
+        Lint::DeprecatedInstruction,
+        Lint::CodeSize,
+        Lint::StackUnderflow,
+        Lint::SelectorCollision,
+        Lint::OversizedPush,
+        Lint::MemoryLayout,
+        Lint::ExperimentalInstruction,
+        Lint::UnsupportedInstruction,
+    ];
+
+    /// The kebab-case name of the lint (ex. `"unused-label"`.)
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedLabel => "unused-label",
+            Lint::DeprecatedInstruction => "deprecated-instruction",
+            Lint::CodeSize => "code-size",
+            Lint::StackUnderflow => "stack-underflow",
+            Lint::SelectorCollision => "selector-collision",
+            Lint::OversizedPush => "oversized-push",
+            Lint::MemoryLayout => "memory-layout",
+            Lint::ExperimentalInstruction => "experimental-instruction",
+            Lint::UnsupportedInstruction => "unsupported-instruction",
+        }
+    }
+
+    /// A one sentence description of what the lint looks for.
+    pub fn description(self) -> &'static str {
+        match self {
+            Lint::UnusedLabel => "A label is declared, but never used.",
+            Lint::DeprecatedInstruction => "A deprecated instruction is used.",
+            Lint::CodeSize => "The assembled code is too large to deploy.",
+            Lint::StackUnderflow => {
+                "An instruction at the start of the program pops from an empty stack."
+            }
+            Lint::SelectorCollision => "Two function signatures have the same selector.",
+            Lint::OversizedPush => "A constant is pushed with a needlessly wide push.",
+            Lint::MemoryLayout => "A store breaks the memory layout Solidity uses.",
+            Lint::ExperimentalInstruction => "An instruction no fork has adopted is used.",
+            Lint::UnsupportedInstruction => {
+                "An instruction doesn't work the same way on the deployment target."
+            }
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = UnknownLintError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .iter()
+            .copied()
+            .find(|l| l.name() == text)
+            .context(error::UnknownLintContext { text })
+    }
+}
+
+/// How a kind of warning is treated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// The warning isn't reported at all.
+    Allow,
+
+    /// The warning is reported, but the build still succeeds.
+    #[default]
+    Warn,
+
+    /// The warning is reported, and should fail the build.
+    Deny,
+}
+
+impl Level {
+    /// Every level, from most to least lenient.
+    pub const ALL: [Level; 3] = [Level::Allow, Level::Warn, Level::Deny];
+
+    /// The lower case name of the level (ex. `"deny"`.)
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Level {
+    type Err = UnknownLevelError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Level::ALL
+            .iter()
+            .copied()
+            .find(|l| l.name() == text)
+            .context(error::UnknownLevelContext { text })
+    }
+}
+
+/// The [`Level`] of each kind of warning.
+///
+/// Lints that haven't been given a level are [`Level::Warn`].
+///
+/// Set them with [`AssemblerContext::lints`].
+///
+/// [`AssemblerContext::lints`]: crate::ingest::AssemblerContext::lints
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Levels {
+    levels: BTreeMap<Lint, Level>,
+}
+
+impl Levels {
+    /// Make a new `Levels` where every lint is [`Level::Warn`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat warnings of the kind `lint` according to `level`.
+    pub fn set(&mut self, lint: Lint, level: Level) -> &mut Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    /// How warnings of the kind `lint` are treated.
+    pub fn get(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or_default()
+    }
+}
+
+/// A problem found while assembling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Warning {
+    /// What kind of problem was found.
+    pub lint: Lint,
+
+    /// How this kind of problem is treated. Allowed warnings are never
+    /// reported, so this is either [`Level::Warn`] or [`Level::Deny`].
+    pub level: Level,
+
+    /// A human readable explanation of the problem.
+    pub message: String,
+
+    /// The file containing the problem, if it can be attributed to one.
+    pub file: Option<PathBuf>,
+
+    /// The location of the problem in `file`, if known.
+    pub span: Option<Span>,
+
+    /// Source that could replace the problem, if there's an obvious fix.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Receives the warnings from each successful build, as they're reported.
+///
+/// Set one with [`AssemblerContext::sink`].
+///
+/// [`AssemblerContext::sink`]: crate::ingest::AssemblerContext::sink
+pub trait Sink: fmt::Debug + Send + Sync {
+    /// Called once for each warning, in the order they're reported.
+    fn warning(&self, warning: &Warning);
+}
+
+/// Why an instruction is discouraged, if it is.
+fn deprecation(spec: Specifier) -> Option<&'static str> {
+    match spec {
+        Op::CallCode => Some("`callcode` is deprecated, use `delegatecall` instead"),
+        Op::SelfDestruct => Some("`selfdestruct` is deprecated (see EIP-6049)"),
+        _ => None,
+    }
+}
+
+/// The constant `op` pushes, if it's a push of a constant that fits in a
+/// `u64`.
+fn constant(op: &AbstractOp) -> Option<u64> {
+    let mut encoded = Vec::new();
+    op.shrunk()?.concretize()?.assemble(&mut encoded);
+
+    let imm = &encoded[1..];
+    if imm.len() > 8 {
+        return None;
+    }
+
+    Some(imm.iter().fold(0, |acc, b| acc << 8 | u64::from(*b)))
+}
+
+/// A label declaration, checked for uses once every instruction is seen.
+#[derive(Debug)]
+struct Declared {
+    unit: usize,
+    symbol: Symbol,
+    label: Arc<str>,
+    file: PathBuf,
+    span: Option<Span>,
+}
+
+/// How far a [`Linter`] had got, to collect what it finds after that point
+/// with [`Linter::since`].
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Mark {
+    warnings: usize,
+    signatures: usize,
+}
+
+/// What a [`Linter`] found in part of a program, like an include, to be
+/// reported again when that part's code is reused instead of linted again.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Replay {
+    warnings: Vec<Warning>,
+
+    /// Collisions depend on the signatures seen elsewhere, so the signatures
+    /// are checked again instead of keeping their warnings.
+    signatures: Vec<(String, PathBuf, Option<Span>)>,
+}
+
+/// Collects warnings from a stream of instructions.
+///
+/// `unit` identifies the label namespace an instruction belongs to, since
+/// includes get their own labels.
+#[derive(Debug)]
+pub(crate) struct Linter {
+    declared: Vec<Declared>,
+    used: HashSet<(usize, Symbol), BuildHasherDefault<SymbolHasher>>,
+
+    /// Stack depth of the straight-line code at the start of the program, or
+    /// `None` once it can't be known.
+    depth: Option<usize>,
+
+    /// The first signature hashed to each selector, and the file it was in.
+    selectors: HashMap<[u8; 4], (String, PathBuf)>,
+
+    /// Every signature checked, in order, for [`Replay`].
+    signatures: Vec<(String, PathBuf, Option<Span>)>,
+
+    /// Whether `%pragma(solidity_memory)` opted into [`Lint::MemoryLayout`].
+    solidity_memory: bool,
+
+    /// The constant pushed by the previous instruction, if it was a push of
+    /// one that fits in a `u64`.
+    constant: Option<u64>,
+
+    /// The instructions reported by [`Lint::ExperimentalInstruction`].
+    experimental: Experimental,
+
+    /// The chain reported on by [`Lint::UnsupportedInstruction`].
+    profile: Profile,
+
+    /// Whether the next raw bytes are the immediate argument of an
+    /// experimental instruction, instead of code that isn't inspected.
+    immediate: bool,
+
+    warnings: Vec<Warning>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self {
+            declared: Default::default(),
+            used: Default::default(),
+            depth: Some(0),
+            selectors: Default::default(),
+            signatures: Default::default(),
+            solidity_memory: false,
+            constant: None,
+            experimental: Default::default(),
+            profile: Default::default(),
+            immediate: false,
+            warnings: Default::default(),
+        }
+    }
+}
+
+impl Linter {
+    fn warn(&mut self, lint: Lint, message: String, file: Option<&Path>, span: Option<Span>) {
+        self.warnings.push(Warning {
+            lint,
+            level: Level::Warn,
+            message,
+            file: file.map(Path::to_owned),
+            span,
+            suggestion: None,
+        });
+    }
+
+    /// Check `op`, found at `span` in `file`.
+    pub(crate) fn op(
+        &mut self,
+        symbols: &mut Interner,
+        unit: usize,
+        file: &Path,
+        span: Option<Span>,
+        op: &AbstractOp,
+    ) {
+        let previous = std::mem::replace(&mut self.constant, constant(op));
+
+        if let AbstractOp::Label(ref label) = op {
+            let symbol = symbols.intern(label);
+            self.declared.push(Declared {
+                unit,
+                symbol,
+                label: label.clone(),
+                file: file.to_owned(),
+                span,
+            });
+        }
+
+        if let Some(label) = op.shared_label() {
+            self.refer(symbols, unit, label);
+        }
+
+        let spec = match op.specifier() {
+            Some(s) => s,
+            None => {
+                // A push sized to fit a label still pushes.
+                self.step(file, span, op);
+                return;
+            }
+        };
+
+        if let Some(reason) = deprecation(spec) {
+            self.warn(
+                Lint::DeprecatedInstruction,
+                reason.to_owned(),
+                Some(file),
+                span,
+            );
+        }
+
+        match self.profile.support(spec) {
+            Support::Full => (),
+            Support::Differs(reason) => {
+                let message = format!(
+                    "`{}` behaves differently on {}: {}",
+                    spec,
+                    self.profile.title(),
+                    reason
+                );
+                self.warn(Lint::UnsupportedInstruction, message, Some(file), span);
+            }
+            Support::Unsupported(reason) => {
+                let message = format!(
+                    "`{}` doesn't work on {}: {}",
+                    spec,
+                    self.profile.title(),
+                    reason
+                );
+                self.warn(Lint::UnsupportedInstruction, message, Some(file), span);
+            }
+        }
+
+        self.immediate = false;
+        if let Some(e) = self.experimental.by_opcode(u8::from(spec)) {
+            self.immediate = e.immediate > 0;
+
+            let message = format!(
+                "`{}` is an experimental instruction from {}, which no fork has adopted",
+                e.mnemonic, e.origin
+            );
+            self.warn(Lint::ExperimentalInstruction, message, Some(file), span);
+        }
+
+        if let Some(shrunk) = op.shrunk() {
+            self.oversized(file, span, spec, &shrunk);
+        }
+
+        if self.solidity_memory {
+            self.store(file, span, spec, previous);
+        }
+
+        self.step(file, span, op);
+    }
+
+    /// Record a use of `label`, like in a push or an expression.
+    pub(crate) fn refer(&mut self, symbols: &mut Interner, unit: usize, label: &Arc<str>) {
+        self.used.insert((unit, symbols.intern(label)));
+    }
+
+    fn step(&mut self, file: &Path, span: Option<Span>, op: &AbstractOp) {
+        let depth = match self.depth {
+            Some(d) => d,
+            None => return,
+        };
+
+        // Anything could have jumped here, so the depth isn't known anymore.
+        if op.is_jump_target() {
+            self.depth = None;
+            return;
+        }
+
+        // Experimental instructions are unassigned opcodes, which would
+        // otherwise look like they halt.
+        let experimental = op
+            .specifier()
+            .and_then(|s| self.experimental.by_opcode(u8::from(s)));
+
+        let (name, pops, pushes, ends) = match experimental {
+            Some(e) => (e.mnemonic.clone(), e.pops, e.pushes, false),
+            None => (
+                op.to_string(),
+                op.pops(),
+                op.pushes(),
+                op.is_jump() || op.is_exit(),
+            ),
+        };
+
+        if pops > depth {
+            self.depth = None;
+
+            let message = format!(
+                "`{}` pops {} stack item(s), but only {} are available when \
+                execution reaches it",
+                name, pops, depth,
+            );
+            self.warn(Lint::StackUnderflow, message, Some(file), span);
+            return;
+        }
+
+        self.depth = if ends {
+            None
+        } else {
+            Some(depth - pops + pushes)
+        };
+    }
+
+    /// Report `spec` if it's much wider than `shrunk`, the narrowest push of
+    /// the same constant.
+    fn oversized(&mut self, file: &Path, span: Option<Span>, spec: Specifier, shrunk: &AbstractOp) {
+        let width = spec.size() - 1;
+        let needed = shrunk.size().unwrap() - 1;
+
+        if width < needed + OVERSIZED_PUSH {
+            return;
+        }
+
+        let message = format!(
+            "`{}` pushes a constant that fits in {} byte(s), wasting {} bytes",
+            spec,
+            needed,
+            width - needed,
+        );
+        self.warn(Lint::OversizedPush, message, Some(file), span);
+        self.warnings.last_mut().unwrap().suggestion = Some(shrunk.to_string());
+    }
+
+    /// Report uses of the instructions in `experimental`.
+    pub(crate) fn experimental(&mut self, experimental: &Experimental) {
+        self.experimental = experimental.clone();
+    }
+
+    /// Report instructions that don't work the same way on `profile`.
+    pub(crate) fn profile(&mut self, profile: Profile) {
+        self.profile = profile;
+    }
+
+    /// Check the rest of the program against the memory layout Solidity uses.
+    pub(crate) fn solidity_memory(&mut self) {
+        self.solidity_memory = true;
+    }
+
+    /// Report `spec` if it's a store to the constant `offset` that breaks the
+    /// memory layout Solidity uses.
+    fn store(&mut self, file: &Path, span: Option<Span>, spec: Specifier, offset: Option<u64>) {
+        let width = match spec {
+            Op::MStore => 32,
+            Op::MStore8 => 1,
+            _ => return,
+        };
+
+        let offset = match offset {
+            Some(o) => o,
+            None => return,
+        };
+        let end = offset.saturating_add(width);
+
+        let overwritten = if offset < 0x80 && end > 0x60 {
+            "the zero slot (0x60 to 0x7f), which Solidity expects to always be zero"
+        } else if offset < 0x60 && end > 0x40 && (width, offset) != (32, 0x40) {
+            "part of the free memory pointer (0x40 to 0x5f)"
+        } else {
+            return;
+        };
+
+        let message = format!("`{}` to 0x{:02x} overwrites {}", spec, offset, overwritten);
+        self.warn(Lint::MemoryLayout, message, Some(file), span);
+    }
+
+    /// Check the selector of `signature`, hashed at `span` in `file`, against
+    /// the other signatures used.
+    pub(crate) fn signature(&mut self, signature: &str, file: &Path, span: Option<Span>) {
+        self.signatures
+            .push((signature.to_owned(), file.to_owned(), span));
+
+        let hash = Keccak256::digest(signature.as_bytes());
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(&hash[..4]);
+
+        let (first, first_file) = match self.selectors.entry(selector) {
+            Entry::Vacant(v) => {
+                v.insert((signature.to_owned(), file.to_owned()));
+                return;
+            }
+            Entry::Occupied(o) => o.into_mut().clone(),
+        };
+
+        if first == signature {
+            return;
+        }
+
+        let message = format!(
+            "`{}` has the same selector as `{}` (in `{}`), 0x{}, so a dispatcher \
+            can't tell them apart",
+            signature,
+            first,
+            first_file.display(),
+            hex::encode(selector),
+        );
+        self.warn(Lint::SelectorCollision, message, Some(file), span);
+    }
+
+    /// Raw bytes (ex. `%include_hex`) aren't inspected, so stop tracking the
+    /// stack.
+    pub(crate) fn raw(&mut self) {
+        if std::mem::take(&mut self.immediate) {
+            return;
+        }
+
+        self.depth = None;
+        self.constant = None;
+    }
+
+    /// How far the linter has got.
+    pub(crate) fn mark(&self) -> Mark {
+        Mark {
+            warnings: self.warnings.len(),
+            signatures: self.signatures.len(),
+        }
+    }
+
+    /// What the linter found after `mark`.
+    pub(crate) fn since(&self, mark: Mark) -> Replay {
+        let warnings = self.warnings[mark.warnings..]
+            .iter()
+            .filter(|w| w.lint != Lint::SelectorCollision)
+            .cloned()
+            .collect();
+
+        Replay {
+            warnings,
+            signatures: self.signatures[mark.signatures..].to_vec(),
+        }
+    }
+
+    /// Report what was found in `replay` again.
+    pub(crate) fn replay(&mut self, replay: &Replay) {
+        self.warnings.extend(replay.warnings.iter().cloned());
+
+        for (signature, file, span) in &replay.signatures {
+            self.signature(signature, file, *span);
+        }
+    }
+
+    /// Report the unused labels of `unit`, which can't be used once its
+    /// source has been assembled.
+    pub(crate) fn close(&mut self, unit: usize) {
+        let (closed, open) = std::mem::take(&mut self.declared)
+            .into_iter()
+            .partition(|d| d.unit == unit);
+        self.declared = open;
+        self.unused(closed);
+    }
+
+    fn unused(&mut self, declared: Vec<Declared>) {
+        for d in declared {
+            if d.label.starts_with('_') || self.used.contains(&(d.unit, d.symbol)) {
+                continue;
+            }
+
+            let message = format!("label `{}` is never used", d.label);
+            self.warn(Lint::UnusedLabel, message, Some(&d.file), d.span);
+        }
+    }
+
+    /// Report anything that can only be checked at the end, given the number of
+    /// bytes that were assembled.
+    pub(crate) fn finish(mut self, size: usize) -> Vec<Warning> {
+        let declared = std::mem::take(&mut self.declared);
+        self.unused(declared);
+
+        if size > MAX_CODE_SIZE {
+            let message = format!(
+                "assembled code is {} bytes, which is more than the {} byte limit for \
+                deployed contracts",
+                size, MAX_CODE_SIZE,
+            );
+            self.warn(Lint::CodeSize, message, None, None);
+        }
+
+        self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(ops: &[AbstractOp]) -> Vec<Warning> {
+        let mut symbols = Interner::default();
+        let mut linter = Linter::default();
+        for op in ops {
+            linter.op(&mut symbols, 0, Path::new("main.etk"), None, op);
+        }
+        linter.finish(0)
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let names: HashSet<_> = Lint::ALL.iter().map(|l| l.name()).collect();
+        assert_eq!(names.len(), Lint::ALL.len());
+    }
+
+    #[test]
+    fn parse_names() {
+        for lint in &Lint::ALL {
+            assert_eq!(lint.name().parse::<Lint>().unwrap(), *lint);
+        }
+
+        assert_eq!("deny".parse::<Level>().unwrap(), Level::Deny);
+        assert!("unused_label".parse::<Lint>().is_err());
+        assert!("forbid".parse::<Level>().is_err());
+    }
+
+    #[test]
+    fn levels() {
+        let mut levels = Levels::new();
+        levels
+            .set(Lint::CodeSize, Level::Deny)
+            .set(Lint::UnusedLabel, Level::Allow);
+
+        assert_eq!(levels.get(Lint::CodeSize), Level::Deny);
+        assert_eq!(levels.get(Lint::UnusedLabel), Level::Allow);
+        assert_eq!(levels.get(Lint::StackUnderflow), Level::Warn);
+    }
+
+    #[test]
+    fn stack_underflow() {
+        let warnings = lint(&[
+            AbstractOp::new(Op::Caller).unwrap(),
+            AbstractOp::new(Op::Add).unwrap(),
+        ]);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::StackUnderflow);
+        assert_eq!(warnings[0].file.as_deref(), Some(Path::new("main.etk")));
+    }
+
+    #[test]
+    fn stack_variable_push() {
+        let warnings = lint(&[
+            AbstractOp::Push("a".into()),
+            AbstractOp::new(Op::Caller).unwrap(),
+            AbstractOp::new(Op::Add).unwrap(),
+            AbstractOp::Label("a".into()),
+        ]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn stack_unknown_after_jumpdest() {
+        let warnings = lint(&[
+            AbstractOp::new(Op::JumpDest).unwrap(),
+            AbstractOp::new(Op::Add).unwrap(),
+        ]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn unsupported_on_profile() {
+        let mut symbols = Interner::default();
+        let mut linter = Linter::default();
+        linter.profile(Profile::ZkSyncEra);
+
+        let file = Path::new("main.etk");
+        for op in &[Op::GetPc, Op::Difficulty, Op::Pop, Op::Stop] {
+            linter.op(&mut symbols, 0, file, None, &AbstractOp::new(*op).unwrap());
+        }
+
+        let warnings = linter.finish(0);
+        let messages: Vec<_> = warnings
+            .iter()
+            .filter(|w| w.lint == Lint::UnsupportedInstruction)
+            .map(|w| w.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "`pc` doesn't work on zkSync Era: it's rejected by the compiler",
+                "`difficulty` behaves differently on zkSync Era: it returns a constant",
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_label_per_unit() {
+        let mut symbols = Interner::default();
+        let mut linter = Linter::default();
+        let file = Path::new("main.etk");
+
+        linter.op(&mut symbols, 0, file, None, &AbstractOp::Label("a".into()));
+        linter.op(&mut symbols, 1, file, None, &AbstractOp::Label("b".into()));
+        linter.op(&mut symbols, 1, file, None, &AbstractOp::Label("_c".into()));
+        linter.op(
+            &mut symbols,
+            1,
+            file,
+            None,
+            &AbstractOp::with_label(Op::Push1(()), "a"),
+        );
+
+        let warnings = linter.finish(0);
+        let messages: Vec<_> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["label `a` is never used", "label `b` is never used"]
+        );
+    }
+
+    #[test]
+    fn selector_collision() {
+        let mut linter = Linter::default();
+        let file = Path::new("main.etk");
+
+        linter.signature("transferFrom(address,address,uint256)", file, None);
+        linter.signature("transferFrom(address,address,uint256)", file, None);
+        linter.signature("transfer(address,uint256)", file, None);
+        assert!(linter.warnings.is_empty());
+
+        linter.signature("gasprice_bit_ether(int128)", Path::new("other.etk"), None);
+
+        let warnings = linter.finish(0);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::SelectorCollision);
+        assert_eq!(warnings[0].file.as_deref(), Some(Path::new("other.etk")));
+        assert!(warnings[0].message.contains("0x23b872dd"));
+    }
+
+    #[test]
+    fn oversized_push() {
+        let warnings = lint(&[
+            AbstractOp::with_immediate(Op::Push32(()), &[1]).unwrap(),
+            AbstractOp::with_immediate(Op::Push5(()), &[1]).unwrap(),
+            AbstractOp::with_immediate(Op::Push4(()), &[1]).unwrap(),
+            AbstractOp::with_label(Op::Push32(()), "a"),
+            AbstractOp::Label("a".into()),
+        ]);
+
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].lint, Lint::OversizedPush);
+        assert_eq!(
+            warnings[0].message,
+            "`push32` pushes a constant that fits in 1 byte(s), wasting 31 bytes"
+        );
+        assert_eq!(warnings[0].suggestion.as_deref(), Some("push1 0x01"));
+        assert_eq!(warnings[1].suggestion.as_deref(), Some("push1 0x01"));
+    }
+
+    #[test]
+    fn code_size() {
+        let warnings = Linter::default().finish(MAX_CODE_SIZE + 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::CodeSize);
+        assert!(Linter::default().finish(MAX_CODE_SIZE).is_empty());
+    }
+}
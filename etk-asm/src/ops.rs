@@ -12,13 +12,126 @@ mod error {
         text: String,
         backtrace: Backtrace,
     }
+
+    /// The error that can arise while parsing a [`super::Fork`] from a string.
+    #[derive(Debug, Snafu)]
+    #[snafu(display("unknown fork: {}", text))]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub struct UnknownForkError {
+        text: String,
+        backtrace: Backtrace,
+    }
+
+    /// The error that can arise while parsing a [`super::Profile`] from a
+    /// string.
+    #[derive(Debug, Snafu)]
+    #[snafu(display("unknown profile: {}", text))]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub struct UnknownProfileError {
+        text: String,
+        backtrace: Backtrace,
+    }
+
+    /// The error that can arise while adding an instruction to a
+    /// [`super::Experimental`] table.
+    #[derive(Debug, Snafu)]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub enum OpcodeTableError {
+        /// The opcode is already assigned to an instruction by a fork.
+        #[snafu(display("opcode {:#04x} is already assigned to an instruction", opcode))]
+        #[non_exhaustive]
+        Assigned {
+            /// The opcode.
+            opcode: u8,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The mnemonic isn't a plain name, or is already an instruction.
+        #[snafu(display("`{}` can't be used as the name of an instruction", mnemonic))]
+        #[non_exhaustive]
+        Mnemonic {
+            /// The mnemonic.
+            mnemonic: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// Another instruction in the table has the same opcode.
+        #[snafu(display("opcode {:#04x} is in the table more than once", opcode))]
+        #[non_exhaustive]
+        DuplicateOpcode {
+            /// The opcode.
+            opcode: u8,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// Another instruction in the table has the same mnemonic.
+        #[snafu(display("`{}` is in the table more than once", mnemonic))]
+        #[non_exhaustive]
+        DuplicateMnemonic {
+            /// The mnemonic.
+            mnemonic: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The immediate argument is wider than a word.
+        #[snafu(display(
+            "`{}` has a {} byte immediate, but immediates are at most 32 bytes",
+            mnemonic,
+            width
+        ))]
+        #[non_exhaustive]
+        ImmediateWidth {
+            /// The mnemonic.
+            mnemonic: String,
+
+            /// The width of the immediate, in bytes.
+            width: usize,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The table isn't valid TOML, or doesn't have the expected layout.
+        #[cfg(feature = "opcodes")]
+        #[snafu(display("the opcode table couldn't be parsed"))]
+        #[non_exhaustive]
+        Toml {
+            /// The underlying source of this error.
+            source: toml::de::Error,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+    }
 }
 
+mod experimental;
+mod expression;
+mod fork;
+mod gas;
 mod imm;
+mod profile;
 mod types;
 
-pub use self::error::UnknownSpecifierError;
+pub use self::error::{
+    OpcodeTableError, UnknownForkError, UnknownProfileError, UnknownSpecifierError,
+};
+pub use self::experimental::{Experimental, ExperimentalOp};
+pub use self::expression::{Expression, ExpressionError, Lookup, Operator};
+pub use self::fork::Fork;
 pub use self::imm::{Imm, Immediate, TryFromIntError, TryFromSliceError};
+pub use self::profile::{Profile, Support};
 use self::types::ImmediateTypes;
 pub use self::types::{Abstract, Concrete, Spec};
 
@@ -28,6 +141,7 @@ use std::cmp::{Eq, PartialEq};
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// The access mode (read, write, both) of an instruction.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -453,14 +567,20 @@ macro_rules! ops {
             }
         }
 
-        impl fmt::Display for Op<Spec> {
-            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                let txt = match self {
+        impl Op<Spec> {
+            /// The name of this instruction, as written in assembly.
+            pub fn mnemonic(self) -> &'static str {
+                match self {
                     $(
                         pat!($op$(, $arg)?) => $mnemonic,
                     )*
-                };
-                write!(f, "{}", txt)
+                }
+            }
+        }
+
+        impl fmt::Display for Op<Spec> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.mnemonic())
             }
         }
 
@@ -471,8 +591,8 @@ macro_rules! ops {
             ///
             /// This function panics if the instruction described by the specifier
             /// does not accept immediate arguments.
-            pub fn with_label<S: Into<String>>(spec: Op<Spec>, lbl: S) -> Self {
-                let lbl = lbl.into();
+            pub fn with_label<S: Into<Arc<str>>>(spec: Op<Spec>, lbl: S) -> Self {
+                let lbl: Arc<str> = lbl.into();
 
                 match spec {
                     $(
@@ -501,7 +621,7 @@ macro_rules! ops {
             }
 
             /// The label to be pushed on the stack. Only relevant for push instructions.
-            pub(crate) fn immediate_label(&self) -> Option<&str> {
+            pub(crate) fn shared_label(&self) -> Option<&Arc<str>> {
                 match self {
                     $(
                         pat_label!(a, $op$(, $arg)?) => ret_label!(a$(, $arg)?),
@@ -646,6 +766,13 @@ impl Op<Spec> {
         Self::push(bytes)
     }
 
+    /// Returns false for bytes that aren't assigned to an instruction (like
+    /// `invalid_0c`.) These can be disassembled, but are only written in
+    /// assembly with `%raw`.
+    pub fn is_defined(self) -> bool {
+        !self.mnemonic().starts_with("invalid_")
+    }
+
     /// Converts a push instruction to the next larger push size.
     ///
     /// For example, a `push2` will become a `push3`.
@@ -702,6 +829,58 @@ impl Op<Spec> {
 
         Some(spec)
     }
+
+    /// Create a new `dup` instruction that copies the `n`th stack element,
+    /// counting the top as one.
+    pub fn dup(n: u32) -> Option<Self> {
+        let spec = match n {
+            1 => Self::Dup1,
+            2 => Self::Dup2,
+            3 => Self::Dup3,
+            4 => Self::Dup4,
+            5 => Self::Dup5,
+            6 => Self::Dup6,
+            7 => Self::Dup7,
+            8 => Self::Dup8,
+            9 => Self::Dup9,
+            10 => Self::Dup10,
+            11 => Self::Dup11,
+            12 => Self::Dup12,
+            13 => Self::Dup13,
+            14 => Self::Dup14,
+            15 => Self::Dup15,
+            16 => Self::Dup16,
+            _ => return None,
+        };
+
+        Some(spec)
+    }
+
+    /// Create a new `swap` instruction that exchanges the top of the stack
+    /// with the element `n` below it.
+    pub fn swap(n: u32) -> Option<Self> {
+        let spec = match n {
+            1 => Self::Swap1,
+            2 => Self::Swap2,
+            3 => Self::Swap3,
+            4 => Self::Swap4,
+            5 => Self::Swap5,
+            6 => Self::Swap6,
+            7 => Self::Swap7,
+            8 => Self::Swap8,
+            9 => Self::Swap9,
+            10 => Self::Swap10,
+            11 => Self::Swap11,
+            12 => Self::Swap12,
+            13 => Self::Swap13,
+            14 => Self::Swap14,
+            15 => Self::Swap15,
+            16 => Self::Swap16,
+            _ => return None,
+        };
+
+        Some(spec)
+    }
 }
 
 ops! {
@@ -990,7 +1169,7 @@ pub enum AbstractOp {
     Op(Op<Abstract>),
 
     /// A label, which is a virtual instruction.
-    Label(String),
+    Label(Arc<str>),
 
     /// A variable sized push, which is a virtual instruction.
     Push(Imm<Vec<u8>>),
@@ -1011,7 +1190,7 @@ impl AbstractOp {
     ///
     /// This function panics if the instruction described by the specifier
     /// does not accept immediate arguments.
-    pub fn with_label<S: Into<String>>(spec: Op<Spec>, lbl: S) -> Self {
+    pub fn with_label<S: Into<Arc<str>>>(spec: Op<Spec>, lbl: S) -> Self {
         Self::Op(Op::with_label(spec, lbl))
     }
 
@@ -1029,8 +1208,12 @@ impl AbstractOp {
     }
 
     pub(crate) fn immediate_label(&self) -> Option<&str> {
+        self.shared_label().map(AsRef::as_ref)
+    }
+
+    pub(crate) fn shared_label(&self) -> Option<&Arc<str>> {
         match self {
-            Self::Op(op) => op.immediate_label(),
+            Self::Op(op) => op.shared_label(),
             Self::Push(Imm::Label(lbl)) => Some(lbl),
             Self::Push(_) => None,
             Self::Label(_) => None,
@@ -1074,6 +1257,32 @@ impl AbstractOp {
         Some(res)
     }
 
+    /// The narrowest push of the same constant, if this is a push of a
+    /// constant (and not of a label.)
+    ///
+    /// Zero is pushed with `push1`, so the result is available in every fork.
+    pub(crate) fn shrunk(&self) -> Option<Self> {
+        let op = match self {
+            Self::Op(op) if op.shared_label().is_none() => op,
+            _ => return None,
+        };
+
+        let spec = op.specifier();
+        if spec.extra_len() == 0 || Specifier::push(spec.extra_len()) != Some(spec) {
+            return None;
+        }
+
+        let mut encoded = Vec::new();
+        op.clone().concretize().assemble(&mut encoded);
+
+        let imm = &encoded[1..];
+        let start = imm.iter().position(|b| *b != 0).unwrap_or(imm.len() - 1);
+        let trimmed = &imm[start..];
+
+        let spec = Specifier::push(trimmed.len() as u32)?;
+        Some(Self::with_immediate(spec, trimmed).unwrap())
+    }
+
     /// Return the total encoded size for this instruction, including the
     /// immediate if one is required.
     ///
@@ -1107,7 +1316,7 @@ impl fmt::Display for AbstractOp {
         match self {
             Self::Op(op) => write!(f, "{}", op),
             Self::Push(txt) => write!(f, r#"%push({})"#, txt),
-            Self::Label(lbl) => write!(f, r#".{}:"#, lbl),
+            Self::Label(lbl) => write!(f, "{}:", lbl),
         }
     }
 }
@@ -1268,6 +1477,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn abstract_op_display_labels() {
+        assert_eq!(AbstractOp::Label("a".into()).to_string(), "a:");
+        assert_eq!(
+            AbstractOp::with_label(Op::Push2(()), "a").to_string(),
+            "push2 a"
+        );
+        assert_eq!(AbstractOp::Push(Imm::from("a")).to_string(), "%push(a)");
+    }
+
     #[test]
     fn specifier_push_for_zero() {
         let spec = Specifier::push_for(0);
@@ -1322,9 +1541,24 @@ mod tests {
         assert_eq!(spec, Some(Specifier::Push4(())));
     }
 
+    #[test]
+    fn abstract_op_shrunk() {
+        let push32 = AbstractOp::with_immediate(Op::Push32(()), &[1]).unwrap();
+        let push1 = AbstractOp::with_immediate(Op::Push1(()), &[1]).unwrap();
+        assert_eq!(push32.shrunk(), Some(push1.clone()));
+        assert_eq!(push1.shrunk(), Some(push1));
+
+        let zero = AbstractOp::with_immediate(Op::Push4(()), &[0, 0, 0, 0]).unwrap();
+        let expected = AbstractOp::with_immediate(Op::Push1(()), &[0]).unwrap();
+        assert_eq!(zero.shrunk(), Some(expected));
+
+        assert_eq!(AbstractOp::with_label(Op::Push32(()), "a").shrunk(), None);
+        assert_eq!(AbstractOp::new(Op::Caller).unwrap().shrunk(), None);
+    }
+
     #[test]
     fn specifier_to_u8_selfdestruct() {
         let spec = Specifier::SelfDestruct;
-        assert_eq!(0xffu8, spec.into());
+        assert_eq!(0xffu8, u8::from(spec));
     }
 }
@@ -0,0 +1,382 @@
+use super::error::{self, OpcodeTableError};
+use super::Specifier;
+
+use snafu::ensure;
+#[cfg(feature = "opcodes")]
+use snafu::ResultExt;
+
+/// An instruction that isn't part of any fork, like one from an EIP that
+/// hasn't been adopted yet, or one a private chain added.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ExperimentalOp {
+    /// The name the instruction is written as (ex. `"jumpsub"`.)
+    pub mnemonic: String,
+
+    /// The byte the instruction is encoded as.
+    pub opcode: u8,
+
+    /// How many bytes of immediate argument follow the opcode.
+    pub immediate: usize,
+
+    /// How many items the instruction pops off the stack.
+    pub pops: usize,
+
+    /// How many items the instruction pushes onto the stack.
+    pub pushes: usize,
+
+    /// Where the instruction comes from (ex. `"EIP-2315"`.)
+    pub origin: String,
+}
+
+impl ExperimentalOp {
+    /// Make a new instruction named `mnemonic`, encoded as `opcode`, without
+    /// an immediate argument or any effect on the stack.
+    pub fn new<M>(mnemonic: M, opcode: u8) -> Self
+    where
+        M: Into<String>,
+    {
+        Self {
+            mnemonic: mnemonic.into(),
+            opcode,
+            immediate: 0,
+            pops: 0,
+            pushes: 0,
+            origin: "custom".to_owned(),
+        }
+    }
+
+    /// Follow the opcode with an immediate argument `width` bytes wide.
+    pub fn immediate(mut self, width: usize) -> Self {
+        self.immediate = width;
+        self
+    }
+
+    /// Pop `pops` items off the stack, then push `pushes` items onto it.
+    pub fn stack(mut self, pops: usize, pushes: usize) -> Self {
+        self.pops = pops;
+        self.pushes = pushes;
+        self
+    }
+
+    /// Describe where the instruction comes from, like the proposal or chain
+    /// that added it.
+    pub fn origin<O>(mut self, origin: O) -> Self
+    where
+        O: Into<String>,
+    {
+        self.origin = origin.into();
+        self
+    }
+
+    /// The unassigned opcode this instruction is encoded as.
+    pub fn specifier(&self) -> Specifier {
+        Specifier::from(self.opcode)
+    }
+
+    /// The number of bytes the instruction is encoded as, including its
+    /// immediate argument.
+    pub fn size(&self) -> usize {
+        1 + self.immediate
+    }
+}
+
+/// Instructions that can be assembled and disassembled even though no fork
+/// has them, so proposals can be tried out, and chains with instructions of
+/// their own can be targeted, without changing the assembler.
+///
+/// Each instruction is encoded as one of the opcodes no fork has assigned
+/// yet, followed by its immediate argument, if it has one.
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_asm::ops::Experimental;
+///
+/// let experimental = Experimental::proposals();
+/// let jumpsub = experimental.by_mnemonic("jumpsub").unwrap();
+///
+/// assert_eq!(jumpsub.opcode, 0x5e);
+/// assert_eq!(jumpsub.origin, "EIP-2315");
+/// assert!(Experimental::new().by_mnemonic("jumpsub").is_none());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Experimental {
+    ops: Vec<ExperimentalOp>,
+}
+
+impl Experimental {
+    /// Make a new `Experimental` without any instructions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The instructions from proposals the assembler knows about:
+    ///
+    ///  - [EIP-2315](https://eips.ethereum.org/EIPS/eip-2315): `beginsub`
+    ///    (`0x5c`), `returnsub` (`0x5d`), and `jumpsub` (`0x5e`), which takes
+    ///    its destination from the stack.
+    pub fn proposals() -> Self {
+        Self {
+            ops: vec![
+                ExperimentalOp::new("beginsub", 0x5c).origin("EIP-2315"),
+                ExperimentalOp::new("returnsub", 0x5d).origin("EIP-2315"),
+                ExperimentalOp::new("jumpsub", 0x5e)
+                    .stack(1, 0)
+                    .origin("EIP-2315"),
+            ],
+        }
+    }
+
+    /// Read a table of instructions from `text`, in TOML, like:
+    ///
+    /// ```toml
+    /// [[opcode]]
+    /// mnemonic = "l1block"
+    /// opcode = 0xe0
+    /// immediate = 2
+    /// pops = 1
+    /// pushes = 1
+    /// origin = "my-rollup"
+    /// ```
+    ///
+    /// Only `mnemonic` and `opcode` are required. Each instruction is checked
+    /// like in [`Experimental::insert`].
+    #[cfg(feature = "opcodes")]
+    pub fn from_toml(text: &str) -> Result<Self, OpcodeTableError> {
+        let table: toml::Table = toml::from_str(text).context(error::Toml)?;
+
+        let mut experimental = Self::new();
+        for entry in table.opcode {
+            let mut op = ExperimentalOp::new(entry.mnemonic, entry.opcode)
+                .immediate(entry.immediate)
+                .stack(entry.pops, entry.pushes);
+
+            if let Some(origin) = entry.origin {
+                op = op.origin(origin);
+            }
+
+            experimental.insert(op)?;
+        }
+
+        Ok(experimental)
+    }
+
+    /// Add `op` to the table.
+    ///
+    /// Fails if the opcode is assigned to an instruction by a fork, if the
+    /// mnemonic isn't a plain name or is already an instruction, if either is
+    /// already in the table, or if the immediate is wider than 32 bytes.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ops::{Experimental, ExperimentalOp};
+    ///
+    /// let mut experimental = Experimental::new();
+    /// experimental
+    ///     .insert(ExperimentalOp::new("l1block", 0xe0).immediate(2).stack(1, 1))
+    ///     .unwrap();
+    ///
+    /// // `0x01` is already `add`.
+    /// experimental.insert(ExperimentalOp::new("fee", 0x01)).unwrap_err();
+    /// ```
+    pub fn insert(&mut self, op: ExperimentalOp) -> Result<(), OpcodeTableError> {
+        let opcode = op.opcode;
+        let mnemonic = op.mnemonic.as_str();
+
+        ensure!(!op.specifier().is_defined(), error::Assigned { opcode });
+
+        ensure!(
+            is_name(mnemonic) && mnemonic.parse::<Specifier>().is_err(),
+            error::Mnemonic { mnemonic }
+        );
+
+        ensure!(
+            self.by_opcode(opcode).is_none(),
+            error::DuplicateOpcode { opcode }
+        );
+
+        ensure!(
+            self.by_mnemonic(mnemonic).is_none(),
+            error::DuplicateMnemonic { mnemonic }
+        );
+
+        ensure!(
+            op.immediate <= 32,
+            error::ImmediateWidth {
+                mnemonic,
+                width: op.immediate,
+            }
+        );
+
+        self.ops.push(op);
+        Ok(())
+    }
+
+    /// Whether there are no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The instruction written as `mnemonic`, if there is one.
+    pub fn by_mnemonic(&self, mnemonic: &str) -> Option<&ExperimentalOp> {
+        self.ops.iter().find(|op| op.mnemonic == mnemonic)
+    }
+
+    /// The instruction encoded as `opcode`, if there is one.
+    pub fn by_opcode(&self, opcode: u8) -> Option<&ExperimentalOp> {
+        self.ops.iter().find(|op| op.opcode == opcode)
+    }
+
+    /// Every instruction, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &ExperimentalOp> {
+        self.ops.iter()
+    }
+}
+
+/// Whether `text` could be written as an instruction, starting with a letter
+/// and made up of letters, digits, and `_`.
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => (),
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(feature = "opcodes")]
+mod toml {
+    use serde::Deserialize;
+
+    pub(super) use ::toml::from_str;
+
+    /// The layout of an opcode table file.
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct Table {
+        #[serde(default)]
+        pub(super) opcode: Vec<Entry>,
+    }
+
+    /// One `[[opcode]]` in an opcode table file.
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct Entry {
+        pub(super) mnemonic: String,
+        pub(super) opcode: u8,
+        #[serde(default)]
+        pub(super) immediate: usize,
+        #[serde(default)]
+        pub(super) pops: usize,
+        #[serde(default)]
+        pub(super) pushes: usize,
+        pub(super) origin: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn proposals_are_unassigned() {
+        for op in Experimental::proposals().iter() {
+            assert!(!op.specifier().is_defined(), "{}", op.mnemonic);
+            assert!(op.mnemonic.parse::<Specifier>().is_err(), "{}", op.mnemonic);
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let experimental = Experimental::proposals();
+
+        assert_eq!(experimental.by_opcode(0x5c).unwrap().mnemonic, "beginsub");
+        assert_eq!(experimental.by_mnemonic("returnsub").unwrap().opcode, 0x5d);
+        assert!(experimental.by_opcode(0x00).is_none());
+        assert!(experimental.by_mnemonic("stop").is_none());
+    }
+
+    #[test]
+    fn insert_errors() {
+        let mut experimental = Experimental::proposals();
+
+        assert_matches!(
+            experimental.insert(ExperimentalOp::new("fee", 0x01)),
+            Err(OpcodeTableError::Assigned { opcode: 0x01, .. })
+        );
+        assert_matches!(
+            experimental.insert(ExperimentalOp::new("add", 0xe0)),
+            Err(OpcodeTableError::Mnemonic { .. })
+        );
+        assert_matches!(
+            experimental.insert(ExperimentalOp::new("1st", 0xe0)),
+            Err(OpcodeTableError::Mnemonic { .. })
+        );
+        assert_matches!(
+            experimental.insert(ExperimentalOp::new("sub2", 0x5c)),
+            Err(OpcodeTableError::DuplicateOpcode { opcode: 0x5c, .. })
+        );
+        assert_matches!(
+            experimental.insert(ExperimentalOp::new("jumpsub", 0xe0)),
+            Err(OpcodeTableError::DuplicateMnemonic { .. })
+        );
+        assert_matches!(
+            experimental.insert(ExperimentalOp::new("wide", 0xe0).immediate(33)),
+            Err(OpcodeTableError::ImmediateWidth { width: 33, .. })
+        );
+
+        experimental
+            .insert(ExperimentalOp::new("wide", 0xe0).immediate(32))
+            .unwrap();
+        assert_eq!(experimental.by_opcode(0xe0).unwrap().size(), 33);
+    }
+
+    #[cfg(feature = "opcodes")]
+    #[test]
+    fn from_toml() {
+        let text = r#"
+            [[opcode]]
+            mnemonic = "l1block"
+            opcode = 0xe0
+            immediate = 2
+            pops = 1
+            pushes = 1
+            origin = "my-rollup"
+
+            [[opcode]]
+            mnemonic = "sequencer"
+            opcode = 0xe1
+        "#;
+
+        let experimental = Experimental::from_toml(text).unwrap();
+
+        let l1block = experimental.by_mnemonic("l1block").unwrap();
+        assert_eq!(
+            *l1block,
+            ExperimentalOp::new("l1block", 0xe0)
+                .immediate(2)
+                .stack(1, 1)
+                .origin("my-rollup")
+        );
+        assert_eq!(experimental.by_opcode(0xe1).unwrap().origin, "custom");
+
+        assert_matches!(
+            Experimental::from_toml("[[opcode]]\nmnemonic = \"x\"\nopcode = 0x100"),
+            Err(OpcodeTableError::Toml { .. })
+        );
+        assert_matches!(
+            Experimental::from_toml("[[opcode]]\nmnemonic = \"x\"\nopcode = 0\nsize = 1"),
+            Err(OpcodeTableError::Toml { .. })
+        );
+        assert_matches!(
+            Experimental::from_toml("[[opcode]]\nmnemonic = \"stop\"\nopcode = 0xe0"),
+            Err(OpcodeTableError::Mnemonic { .. })
+        );
+    }
+}
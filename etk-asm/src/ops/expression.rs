@@ -0,0 +1,510 @@
+//! Arithmetic on numbers and labels, evaluated once every label has an
+//! address.
+
+mod error {
+    use snafu::{Backtrace, Snafu};
+
+    /// Errors that can arise while evaluating an [`super::Expression`].
+    #[derive(Debug, Snafu)]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub enum ExpressionError {
+        /// A label in the expression has no value.
+        #[snafu(display("label `{}` has no value", label))]
+        #[non_exhaustive]
+        UnknownLabel {
+            /// The name of the label.
+            label: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The expression divided by zero.
+        #[snafu(display("division by zero"))]
+        #[non_exhaustive]
+        DivisionByZero {
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// A range of code ended before it started, or was out of bounds.
+        #[snafu(display("{} to {} isn't a valid range of code", start, end))]
+        #[non_exhaustive]
+        InvalidRange {
+            /// The start of the range.
+            start: String,
+
+            /// The end of the range.
+            end: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The bytes in a range of code weren't available to hash.
+        #[snafu(display("the code from {:#x} to {:#x} can't be hashed", start, end))]
+        #[non_exhaustive]
+        CodeUnavailable {
+            /// The start of the range.
+            start: usize,
+
+            /// The end of the range.
+            end: usize,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+    }
+}
+
+pub use self::error::ExpressionError;
+
+use num_bigint::{BigInt, Sign};
+
+use num_traits::{ToPrimitive, Zero};
+
+use sha3::{Digest, Keccak256};
+
+use snafu::OptionExt;
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Supplies the values that an [`Expression`] refers to.
+///
+/// Any `Fn(&str) -> Option<BigInt>` is a lookup that only knows labels.
+pub trait Lookup {
+    /// The value of `label`, if it has one.
+    fn label(&self, label: &str) -> Option<BigInt>;
+
+    /// The assembled code from `start` up to (but not including) `end`, if
+    /// it's available.
+    fn code(&self, start: usize, end: usize) -> Option<&[u8]> {
+        let _ = (start, end);
+        None
+    }
+}
+
+impl<F> Lookup for F
+where
+    F: Fn(&str) -> Option<BigInt>,
+{
+    fn label(&self, label: &str) -> Option<BigInt> {
+        self(label)
+    }
+}
+
+/// An operator joining two [`Expression`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// `||`, which is `1` if either side is non-zero.
+    Or,
+
+    /// `&&`, which is `1` if both sides are non-zero.
+    And,
+
+    /// `==`
+    Equal,
+
+    /// `!=`
+    NotEqual,
+
+    /// `<`
+    Less,
+
+    /// `<=`
+    LessEqual,
+
+    /// `>`
+    Greater,
+
+    /// `>=`
+    GreaterEqual,
+
+    /// `+`
+    Plus,
+
+    /// `-`
+    Minus,
+
+    /// `*`
+    Times,
+
+    /// `/`, rounding towards zero.
+    Divide,
+
+    /// `%`, with the sign of the left side.
+    Modulo,
+}
+
+impl Operator {
+    /// How tightly the operator binds. Operators with a higher precedence are
+    /// applied first.
+    pub fn precedence(self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal | Operator::NotEqual => 3,
+            Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => 4,
+            Operator::Plus | Operator::Minus => 5,
+            Operator::Times | Operator::Divide | Operator::Modulo => 6,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Or => "||",
+            Operator::And => "&&",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Times => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+        }
+    }
+
+    fn apply(self, lhs: BigInt, rhs: BigInt) -> Result<BigInt, ExpressionError> {
+        let truth = |b: bool| BigInt::from(u8::from(b));
+
+        let value = match self {
+            Operator::Or => truth(!lhs.is_zero() || !rhs.is_zero()),
+            Operator::And => truth(!lhs.is_zero() && !rhs.is_zero()),
+            Operator::Equal => truth(lhs == rhs),
+            Operator::NotEqual => truth(lhs != rhs),
+            Operator::Less => truth(lhs < rhs),
+            Operator::LessEqual => truth(lhs <= rhs),
+            Operator::Greater => truth(lhs > rhs),
+            Operator::GreaterEqual => truth(lhs >= rhs),
+            Operator::Plus => lhs + rhs,
+            Operator::Minus => lhs - rhs,
+            Operator::Times => lhs * rhs,
+            Operator::Divide | Operator::Modulo if rhs.is_zero() => {
+                return error::DivisionByZero.fail();
+            }
+            Operator::Divide => lhs / rhs,
+            Operator::Modulo => lhs % rhs,
+        };
+
+        Ok(value)
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// A value computed from numbers and the addresses of labels, like
+/// `end - start + 1`.
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_asm::ops::{Expression, Operator};
+/// # use etk_asm::ops::ExpressionError;
+///
+/// let size = Expression::binary(
+///     Operator::Minus,
+///     Expression::Label("end".into()),
+///     Expression::Label("start".into()),
+/// );
+///
+/// let value = size.eval(&|label: &str| match label {
+///     "start" => Some(3.into()),
+///     "end" => Some(10.into()),
+///     _ => None,
+/// })?;
+///
+/// assert_eq!(value, 7.into());
+/// assert_eq!(size.to_string(), "end - start");
+/// # Result::<(), ExpressionError>::Ok(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// A literal number.
+    Number(BigInt),
+
+    /// The address of a label (or the value of a define.)
+    Label(Arc<str>),
+
+    /// Two expressions joined by an operator.
+    Binary(Operator, Box<Expression>, Box<Expression>),
+
+    /// The keccak-256 hash of the code from the first expression up to (but
+    /// not including) the second, written `keccak(start, end)`.
+    Keccak(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Join `lhs` and `rhs` with `op`.
+    pub fn binary(op: Operator, lhs: Expression, rhs: Expression) -> Self {
+        Expression::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    /// Every label the expression refers to, in order of appearance.
+    pub fn labels(&self) -> Vec<&Arc<str>> {
+        let mut labels = Vec::new();
+        self.collect_labels(&mut labels);
+        labels
+    }
+
+    fn collect_labels<'a>(&'a self, labels: &mut Vec<&'a Arc<str>>) {
+        match self {
+            Expression::Number(_) => (),
+            Expression::Label(label) => labels.push(label),
+            Expression::Binary(_, lhs, rhs) | Expression::Keccak(lhs, rhs) => {
+                lhs.collect_labels(labels);
+                rhs.collect_labels(labels);
+            }
+        }
+    }
+
+    /// Rename each label that `rename` returns a new name for.
+    pub(crate) fn relabel<F>(&mut self, rename: &F)
+    where
+        F: Fn(&Arc<str>) -> Option<Arc<str>>,
+    {
+        match self {
+            Expression::Number(_) => (),
+            Expression::Label(label) => {
+                if let Some(new) = rename(label) {
+                    *label = new;
+                }
+            }
+            Expression::Binary(_, lhs, rhs) | Expression::Keccak(lhs, rhs) => {
+                lhs.relabel(rename);
+                rhs.relabel(rename);
+            }
+        }
+    }
+
+    /// Compute the value of the expression, looking up labels and code with
+    /// `lookup`.
+    ///
+    /// Comparisons and logical operators evaluate to `1` when true, and `0`
+    /// when false.
+    pub fn eval<L>(&self, lookup: &L) -> Result<BigInt, ExpressionError>
+    where
+        L: Lookup + ?Sized,
+    {
+        match self {
+            Expression::Number(n) => Ok(n.clone()),
+            Expression::Label(label) => lookup.label(label).context(error::UnknownLabel {
+                label: label.as_ref(),
+            }),
+            Expression::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(lookup)?;
+                let rhs = rhs.eval(lookup)?;
+                op.apply(lhs, rhs)
+            }
+            Expression::Keccak(start, end) => {
+                let start = start.eval(lookup)?;
+                let end = end.eval(lookup)?;
+
+                let range = match (start.to_usize(), end.to_usize()) {
+                    (Some(s), Some(e)) if s <= e => (s, e),
+                    _ => {
+                        return error::InvalidRange {
+                            start: start.to_string(),
+                            end: end.to_string(),
+                        }
+                        .fail()
+                    }
+                };
+
+                let code = lookup
+                    .code(range.0, range.1)
+                    .context(error::CodeUnavailable {
+                        start: range.0,
+                        end: range.1,
+                    })?;
+
+                let hash = Keccak256::digest(code);
+                Ok(BigInt::from_bytes_be(Sign::Plus, &hash))
+            }
+        }
+    }
+
+    /// The most the expression could evaluate to, if no label is more than
+    /// `label` returns for it.
+    ///
+    /// Subtraction and division are assumed to leave their left side no
+    /// larger, and a hash can be anything that fits in 32 bytes.
+    pub(crate) fn bound<F>(&self, label: &F) -> BigInt
+    where
+        F: Fn(&str) -> BigInt,
+    {
+        match self {
+            Expression::Number(n) => n.clone(),
+            Expression::Label(name) => label(name),
+            Expression::Keccak(_, _) => (BigInt::from(1) << 256) - 1,
+            Expression::Binary(op, lhs, rhs) => {
+                let lhs = lhs.bound(label);
+                let rhs = rhs.bound(label);
+
+                match op {
+                    Operator::Plus => lhs + rhs,
+                    Operator::Times => lhs * rhs,
+                    Operator::Minus | Operator::Divide => lhs,
+                    Operator::Modulo => lhs.min(rhs),
+                    _ => BigInt::from(1),
+                }
+            }
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary(op, _, _) => op.precedence(),
+            _ => u8::MAX,
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (op, lhs, rhs) = match self {
+            Expression::Number(n) => return write!(f, "{}", n),
+            Expression::Label(label) => return write!(f, "{}", label),
+            Expression::Keccak(start, end) => return write!(f, "keccak({}, {})", start, end),
+            Expression::Binary(op, lhs, rhs) => (op, lhs, rhs),
+        };
+
+        // Operators are left associative, so only the right side needs
+        // parentheses when the precedence is the same.
+        if lhs.precedence() < op.precedence() {
+            write!(f, "({})", lhs)?;
+        } else {
+            write!(f, "{}", lhs)?;
+        }
+
+        write!(f, " {} ", op)?;
+
+        if rhs.precedence() <= op.precedence() {
+            write!(f, "({})", rhs)
+        } else {
+            write!(f, "{}", rhs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    fn label(name: &str) -> Expression {
+        Expression::Label(name.into())
+    }
+
+    fn number(n: u32) -> Expression {
+        Expression::Number(n.into())
+    }
+
+    #[test]
+    fn eval_comparison() -> Result<(), ExpressionError> {
+        let expr = Expression::binary(
+            Operator::LessEqual,
+            Expression::binary(Operator::Minus, label("end"), label("start")),
+            number(0x6000),
+        );
+
+        let lookup = |start: u32| {
+            move |l: &str| match l {
+                "start" => Some(BigInt::from(start)),
+                "end" => Some(BigInt::from(0x6010)),
+                _ => None,
+            }
+        };
+
+        assert_eq!(expr.eval(&lookup(0x10))?, 1.into());
+        assert_eq!(expr.eval(&lookup(0x0f))?, 0.into());
+        assert_eq!(expr.to_string(), "end - start <= 24576");
+        Ok(())
+    }
+
+    #[test]
+    fn eval_errors() {
+        let expr = Expression::binary(Operator::Divide, number(1), number(0));
+        assert_matches!(
+            expr.eval(&|_: &str| None),
+            Err(ExpressionError::DivisionByZero { .. })
+        );
+
+        let expr = Expression::binary(Operator::Plus, number(1), label("missing"));
+        assert_matches!(
+            expr.eval(&|_: &str| None),
+            Err(ExpressionError::UnknownLabel { label, .. }) if label == "missing"
+        );
+    }
+
+    struct Code(&'static [u8]);
+
+    impl Lookup for Code {
+        fn label(&self, label: &str) -> Option<BigInt> {
+            match label {
+                "end" => Some(self.0.len().into()),
+                _ => None,
+            }
+        }
+
+        fn code(&self, start: usize, end: usize) -> Option<&[u8]> {
+            self.0.get(start..end)
+        }
+    }
+
+    #[test]
+    fn eval_keccak() -> Result<(), ExpressionError> {
+        let expr = Expression::Keccak(Box::new(number(0)), Box::new(label("end")));
+        let hash =
+            hex_literal::hex!("bc36789e7a1e281436464229828f817d6612f7b477d66591ff96a9e064bcc98a");
+        assert_eq!(
+            expr.eval(&Code(&[0x00]))?,
+            BigInt::from_bytes_be(Sign::Plus, &hash)
+        );
+        assert_eq!(expr.to_string(), "keccak(0, end)");
+
+        let expr = Expression::Keccak(Box::new(number(2)), Box::new(number(1)));
+        assert_matches!(
+            expr.eval(&Code(&[0x00])),
+            Err(ExpressionError::InvalidRange { .. })
+        );
+
+        let expr = Expression::Keccak(Box::new(number(0)), Box::new(number(4)));
+        assert_matches!(
+            expr.eval(&Code(&[0x00])),
+            Err(ExpressionError::CodeUnavailable {
+                start: 0,
+                end: 4,
+                ..
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn display_parentheses() {
+        let expr = Expression::binary(
+            Operator::Times,
+            Expression::binary(Operator::Plus, number(1), number(2)),
+            Expression::binary(Operator::Minus, number(3), number(4)),
+        );
+        assert_eq!(expr.to_string(), "(1 + 2) * (3 - 4)");
+
+        let expr = Expression::binary(
+            Operator::Minus,
+            Expression::binary(Operator::Minus, number(1), number(2)),
+            Expression::binary(Operator::Minus, number(3), number(4)),
+        );
+        assert_eq!(expr.to_string(), "1 - 2 - (3 - 4)");
+    }
+}
@@ -0,0 +1,219 @@
+use super::error::{self, UnknownForkError};
+use super::{Op, Spec};
+
+use snafu::OptionExt;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A revision of the EVM, named after the network upgrade that introduced it.
+///
+/// Forks are ordered chronologically, so `Fork::Byzantium < Fork::London`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Fork {
+    /// The original EVM, launched with the Ethereum mainnet.
+    Frontier,
+
+    /// Introduced `delegatecall`.
+    Homestead,
+
+    /// Introduced `returndatasize`, `returndatacopy`, `staticcall`, and `revert`.
+    Byzantium,
+
+    /// Introduced `shl`, `shr`, `sar`, `extcodehash`, and `create2`.
+    Constantinople,
+
+    /// Removed net gas metering from Constantinople.
+    Petersburg,
+
+    /// Introduced `chainid` and `selfbalance`.
+    Istanbul,
+
+    /// Introduced access lists.
+    Berlin,
+
+    /// Introduced `basefee`.
+    London,
+}
+
+/// The block each fork activated at, for chains with a known schedule.
+///
+/// Chains that launched after London start with it, and forks newer than
+/// London are treated as London, since the assembler doesn't know them yet.
+const SCHEDULES: &[(u64, &[(u64, Fork)])] = &[
+    // Mainnet.
+    (
+        1,
+        &[
+            (0, Fork::Frontier),
+            (1_150_000, Fork::Homestead),
+            (4_370_000, Fork::Byzantium),
+            (7_280_000, Fork::Petersburg),
+            (9_069_000, Fork::Istanbul),
+            (12_244_000, Fork::Berlin),
+            (12_965_000, Fork::London),
+        ],
+    ),
+    // Goerli.
+    (
+        5,
+        &[
+            (0, Fork::Petersburg),
+            (1_561_651, Fork::Istanbul),
+            (4_460_644, Fork::Berlin),
+            (5_062_605, Fork::London),
+        ],
+    ),
+    // Holesky.
+    (17_000, &[(0, Fork::London)]),
+    // Sepolia.
+    (11_155_111, &[(0, Fork::London)]),
+];
+
+impl Fork {
+    /// Every fork, from oldest to newest.
+    pub const ALL: [Fork; 8] = [
+        Fork::Frontier,
+        Fork::Homestead,
+        Fork::Byzantium,
+        Fork::Constantinople,
+        Fork::Petersburg,
+        Fork::Istanbul,
+        Fork::Berlin,
+        Fork::London,
+    ];
+
+    /// The newest fork known to the assembler.
+    pub const fn latest() -> Self {
+        Fork::London
+    }
+
+    /// The lowercase name of the fork (ex. `"london"`.)
+    pub fn name(self) -> &'static str {
+        match self {
+            Fork::Frontier => "frontier",
+            Fork::Homestead => "homestead",
+            Fork::Byzantium => "byzantium",
+            Fork::Constantinople => "constantinople",
+            Fork::Petersburg => "petersburg",
+            Fork::Istanbul => "istanbul",
+            Fork::Berlin => "berlin",
+            Fork::London => "london",
+        }
+    }
+
+    /// The fork active at `block` on the chain with id `chain_id`, or `None`
+    /// if the chain's upgrade schedule isn't known.
+    ///
+    /// Only mainnet, Goerli, Holesky, and Sepolia are known.
+    pub fn at_block(chain_id: u64, block: u64) -> Option<Self> {
+        let (_, schedule) = SCHEDULES.iter().find(|(id, _)| *id == chain_id)?;
+
+        schedule
+            .iter()
+            .rev()
+            .find(|(activation, _)| *activation <= block)
+            .map(|(_, fork)| *fork)
+    }
+}
+
+impl Default for Fork {
+    fn default() -> Self {
+        Self::latest()
+    }
+}
+
+impl fmt::Display for Fork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Fork {
+    type Err = UnknownForkError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lower = text.to_ascii_lowercase();
+
+        Fork::ALL
+            .iter()
+            .copied()
+            .find(|f| f.name() == lower)
+            .context(error::UnknownForkContext { text })
+    }
+}
+
+impl Op<Spec> {
+    /// The fork that introduced this instruction.
+    ///
+    /// Undefined instructions (like `invalid_0c`) have always been valid to
+    /// encode, so they report [`Fork::Frontier`].
+    pub fn introduced_in(self) -> Fork {
+        match self {
+            Op::DelegateCall => Fork::Homestead,
+
+            Op::ReturnDataSize | Op::ReturnDataCopy | Op::StaticCall | Op::Revert => {
+                Fork::Byzantium
+            }
+
+            Op::Shl | Op::Shr | Op::Sar | Op::ExtCodeHash | Op::Create2 => Fork::Constantinople,
+
+            Op::ChainId | Op::SelfBalance => Fork::Istanbul,
+
+            Op::BaseFee => Fork::London,
+
+            _ => Fork::Frontier,
+        }
+    }
+
+    /// Returns true if this instruction is available in `fork`.
+    pub fn is_available_in(self, fork: Fork) -> bool {
+        self.introduced_in() <= fork
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_case_insensitive() {
+        assert_eq!(
+            "Constantinople".parse::<Fork>().unwrap(),
+            Fork::Constantinople
+        );
+    }
+
+    #[test]
+    fn parse_unknown() {
+        assert!("paris".parse::<Fork>().is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for fork in Fork::ALL.iter() {
+            assert_eq!(fork.to_string().parse::<Fork>().unwrap(), *fork);
+        }
+    }
+
+    #[test]
+    fn availability() {
+        assert!(Op::Add.is_available_in(Fork::Frontier));
+        assert!(!Op::Shl.is_available_in(Fork::Byzantium));
+        assert!(Op::Shl.is_available_in(Fork::Constantinople));
+        assert!(!Op::BaseFee.is_available_in(Fork::Berlin));
+        assert!(Op::BaseFee.is_available_in(Fork::latest()));
+    }
+
+    #[test]
+    fn fork_at_block() {
+        assert_eq!(Fork::at_block(1, 0), Some(Fork::Frontier));
+        assert_eq!(Fork::at_block(1, 4_369_999), Some(Fork::Homestead));
+        assert_eq!(Fork::at_block(1, 4_370_000), Some(Fork::Byzantium));
+        assert_eq!(Fork::at_block(1, 7_280_000), Some(Fork::Petersburg));
+        assert_eq!(Fork::at_block(1, 18_000_000), Some(Fork::London));
+        assert_eq!(Fork::at_block(5, 4_460_644), Some(Fork::Berlin));
+        assert_eq!(Fork::at_block(11_155_111, 0), Some(Fork::London));
+        assert_eq!(Fork::at_block(1337, 0), None);
+    }
+}
@@ -0,0 +1,164 @@
+use super::{Op, Spec};
+
+impl Op<Spec> {
+    /// The minimum amount of gas this instruction consumes, as of
+    /// [`Fork::London`](super::Fork::London).
+    ///
+    /// Dynamic costs, like memory expansion, cold account and storage access,
+    /// and copying, are not included. Undefined instructions report zero.
+    pub fn base_gas(self) -> u64 {
+        match self {
+            Op::Stop | Op::Return | Op::Revert | Op::Invalid => 0,
+
+            Op::JumpDest => 1,
+
+            Op::Address
+            | Op::Origin
+            | Op::Caller
+            | Op::CallValue
+            | Op::CallDataSize
+            | Op::CodeSize
+            | Op::GasPrice
+            | Op::ReturnDataSize
+            | Op::Coinbase
+            | Op::Timestamp
+            | Op::Number
+            | Op::Difficulty
+            | Op::GasLimit
+            | Op::ChainId
+            | Op::BaseFee
+            | Op::Pop
+            | Op::GetPc
+            | Op::MSize
+            | Op::Gas => 2,
+
+            Op::Add
+            | Op::Sub
+            | Op::Lt
+            | Op::Gt
+            | Op::SLt
+            | Op::SGt
+            | Op::Eq
+            | Op::IsZero
+            | Op::And
+            | Op::Or
+            | Op::Xor
+            | Op::Not
+            | Op::Byte
+            | Op::Shl
+            | Op::Shr
+            | Op::Sar
+            | Op::CallDataLoad
+            | Op::CallDataCopy
+            | Op::CodeCopy
+            | Op::ReturnDataCopy
+            | Op::MLoad
+            | Op::MStore
+            | Op::MStore8 => 3,
+
+            Op::Mul
+            | Op::Div
+            | Op::SDiv
+            | Op::Mod
+            | Op::SMod
+            | Op::SignExtend
+            | Op::SelfBalance => 5,
+
+            Op::AddMod | Op::MulMod | Op::Jump => 8,
+
+            Op::Exp | Op::JumpI => 10,
+
+            Op::BlockHash => 20,
+
+            Op::Keccak256 => 30,
+
+            Op::Balance
+            | Op::ExtCodeSize
+            | Op::ExtCodeCopy
+            | Op::ExtCodeHash
+            | Op::SLoad
+            | Op::SStore
+            | Op::Call
+            | Op::CallCode
+            | Op::DelegateCall
+            | Op::StaticCall => 100,
+
+            Op::Log0 => 375,
+            Op::Log1 => 750,
+            Op::Log2 => 1125,
+            Op::Log3 => 1500,
+            Op::Log4 => 1875,
+
+            Op::SelfDestruct => 5000,
+
+            Op::Create | Op::Create2 => 32000,
+
+            // Every push, dup, and swap.
+            _ if (0x60..=0x9f).contains(&u8::from(self)) => 3,
+
+            // Undefined instructions.
+            _ => 0,
+        }
+    }
+
+    /// Returns true if this instruction can consume more than its
+    /// [`base_gas`](Self::base_gas), depending on its arguments or the state
+    /// it touches.
+    pub fn has_dynamic_gas(self) -> bool {
+        matches!(
+            self,
+            Op::Exp
+                | Op::Keccak256
+                | Op::Balance
+                | Op::CallDataCopy
+                | Op::CodeCopy
+                | Op::ExtCodeSize
+                | Op::ExtCodeCopy
+                | Op::ReturnDataCopy
+                | Op::ExtCodeHash
+                | Op::MLoad
+                | Op::MStore
+                | Op::MStore8
+                | Op::SLoad
+                | Op::SStore
+                | Op::Log0
+                | Op::Log1
+                | Op::Log2
+                | Op::Log3
+                | Op::Log4
+                | Op::Create
+                | Op::Call
+                | Op::CallCode
+                | Op::Return
+                | Op::DelegateCall
+                | Op::Create2
+                | Op::StaticCall
+                | Op::Revert
+                | Op::SelfDestruct
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_gas() {
+        assert_eq!(Op::Add.base_gas(), 3);
+        assert_eq!(Op::Push32(()).base_gas(), 3);
+        assert_eq!(Op::Swap16.base_gas(), 3);
+        assert_eq!(Op::JumpDest.base_gas(), 1);
+        assert_eq!(Op::Log2.base_gas(), 1125);
+        assert_eq!(Op::Invalid0c.base_gas(), 0);
+    }
+
+    #[test]
+    fn dynamic_gas() {
+        assert!(Op::SStore.has_dynamic_gas());
+        assert!(Op::MStore.has_dynamic_gas());
+        assert!(Op::Return.has_dynamic_gas());
+        assert!(!Op::Add.has_dynamic_gas());
+        assert!(!Op::JumpI.has_dynamic_gas());
+    }
+}
@@ -4,6 +4,7 @@ use snafu::{Backtrace, Snafu};
 
 use std::convert::TryFrom;
 use std::fmt::{self, Debug};
+use std::sync::Arc;
 
 /// An error that arises when converting an integer into an immediate.
 #[derive(Snafu, Debug)]
@@ -28,7 +29,7 @@ impl From<std::convert::Infallible> for TryFromIntError {
 #[derive(Clone, Eq, PartialEq)]
 pub enum Imm<T> {
     /// A label argument.
-    Label(String),
+    Label(Arc<str>),
 
     /// A constant argument.
     Constant(T),
@@ -36,12 +37,18 @@ pub enum Imm<T> {
 
 impl<T> From<&str> for Imm<T> {
     fn from(label: &str) -> Self {
-        Imm::Label(label.to_owned())
+        Imm::Label(label.into())
     }
 }
 
 impl<T> From<String> for Imm<T> {
     fn from(label: String) -> Self {
+        Imm::Label(label.into())
+    }
+}
+
+impl<T> From<Arc<str>> for Imm<T> {
+    fn from(label: Arc<str>) -> Self {
         Imm::Label(label)
     }
 }
@@ -107,8 +114,9 @@ macro_rules! impl_try_from_slice {
                     return TryFromSliceContext.fail();
                 }
 
+                // Shorter slices are padded on the left, like integers.
                 let mut output = [0u8; $ii];
-                output.copy_from_slice(x);
+                output[$ii - x.len()..].copy_from_slice(x);
                 Ok(Imm::Constant(output))
             }
         }
@@ -215,7 +223,7 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Imm::Label(s) => write!(f, ":{}", s),
+            Imm::Label(s) => write!(f, "{}", s),
             Imm::Constant(c) => write!(f, "0x{}", c.encode_hex::<String>()),
         }
     }
@@ -245,4 +253,16 @@ mod tests {
         let imm = Imm::from(hex!("95ea7b30"));
         assert_matches!(imm, Imm::Constant([0x95, 0xea, 0x7b, 0x30]));
     }
+
+    #[test]
+    fn imm4_try_from_short_slice() {
+        let imm = Imm::<[u8; 4]>::try_from(&hex!("7b30")[..]).unwrap();
+        assert_matches!(imm, Imm::Constant([0x00, 0x00, 0x7b, 0x30]));
+    }
+
+    #[test]
+    fn imm4_try_from_long_slice() {
+        let imm = Imm::<[u8; 4]>::try_from(&hex!("0095ea7b30")[..]);
+        assert_matches!(imm, Err(TryFromSliceError { .. }));
+    }
 }
@@ -0,0 +1,202 @@
+use super::error::{self, UnknownProfileError};
+use super::{Op, Spec};
+
+use snafu::OptionExt;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The chain assembled code is meant to be deployed to.
+///
+/// Some layer two chains, especially zkEVMs, don't run every instruction the
+/// way Ethereum does. A profile describes which instructions are missing or
+/// behave differently, so they can be pointed out before deploying.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Ethereum, and chains that run every instruction the same way.
+    #[default]
+    Ethereum,
+
+    /// zkSync Era, which compiles EVM code for a VM of its own.
+    ZkSyncEra,
+
+    /// Polygon zkEVM.
+    PolygonZkEvm,
+
+    /// Scroll.
+    Scroll,
+
+    /// Linea.
+    Linea,
+}
+
+/// How a [`Profile`] runs an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Support {
+    /// The instruction works like it does on Ethereum.
+    Full,
+
+    /// The instruction runs, but doesn't do the same thing it does on
+    /// Ethereum, for the given reason.
+    Differs(&'static str),
+
+    /// The instruction can't be used, for the given reason.
+    Unsupported(&'static str),
+}
+
+impl Profile {
+    /// Every profile.
+    pub const ALL: [Profile; 5] = [
+        Profile::Ethereum,
+        Profile::ZkSyncEra,
+        Profile::PolygonZkEvm,
+        Profile::Scroll,
+        Profile::Linea,
+    ];
+
+    /// The kebab-case name of the profile (ex. `"polygon-zkevm"`.)
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Ethereum => "ethereum",
+            Profile::ZkSyncEra => "zksync-era",
+            Profile::PolygonZkEvm => "polygon-zkevm",
+            Profile::Scroll => "scroll",
+            Profile::Linea => "linea",
+        }
+    }
+
+    /// The name of the chain, as written in prose (ex. `"Polygon zkEVM"`.)
+    pub fn title(self) -> &'static str {
+        match self {
+            Profile::Ethereum => "Ethereum",
+            Profile::ZkSyncEra => "zkSync Era",
+            Profile::PolygonZkEvm => "Polygon zkEVM",
+            Profile::Scroll => "Scroll",
+            Profile::Linea => "Linea",
+        }
+    }
+
+    /// How this profile runs `spec`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use etk_asm::ops::{Op, Profile, Support};
+    ///
+    /// assert_eq!(Profile::Ethereum.support(Op::SelfDestruct), Support::Full);
+    /// assert!(matches!(
+    ///     Profile::Scroll.support(Op::SelfDestruct),
+    ///     Support::Unsupported(_)
+    /// ));
+    /// ```
+    pub fn support(self, spec: Op<Spec>) -> Support {
+        match (self, spec) {
+            (Profile::Ethereum, _) => Support::Full,
+
+            (Profile::ZkSyncEra, Op::SelfDestruct)
+            | (Profile::ZkSyncEra, Op::CallCode)
+            | (Profile::ZkSyncEra, Op::GetPc)
+            | (Profile::ZkSyncEra, Op::ExtCodeCopy) => {
+                Support::Unsupported("it's rejected by the compiler")
+            }
+            (Profile::ZkSyncEra, Op::CodeCopy) | (Profile::ZkSyncEra, Op::CodeSize) => {
+                Support::Differs("code isn't stored as EVM bytecode")
+            }
+            (Profile::ZkSyncEra, Op::Create) | (Profile::ZkSyncEra, Op::Create2) => {
+                Support::Differs("only contracts whose code is known ahead of time can be deployed")
+            }
+            (Profile::ZkSyncEra, Op::Difficulty) => Support::Differs("it returns a constant"),
+
+            (Profile::PolygonZkEvm, Op::SelfDestruct) => {
+                Support::Differs("it sends the balance, but doesn't destroy the account")
+            }
+            (Profile::PolygonZkEvm, Op::BlockHash) => {
+                Support::Differs("it returns the state root of the block")
+            }
+            (Profile::PolygonZkEvm, Op::Difficulty) => Support::Differs("it returns zero"),
+
+            (Profile::Scroll, Op::SelfDestruct) => {
+                Support::Unsupported("it's disabled, and reverts")
+            }
+            (Profile::Scroll, Op::BlockHash) => {
+                Support::Differs("it returns a hash of the chain id and block number")
+            }
+            (Profile::Scroll, Op::Coinbase) => {
+                Support::Differs("it returns the address of the fee vault")
+            }
+            (Profile::Scroll, Op::Difficulty) => Support::Differs("it returns zero"),
+
+            (Profile::Linea, Op::Difficulty) => Support::Differs("it returns a constant"),
+
+            _ => Support::Full,
+        }
+    }
+
+    /// The minimum amount of gas `spec` consumes on this profile, or `None` if
+    /// it can't be used at all.
+    ///
+    /// Other than unsupported instructions, this is the same as
+    /// [`Op::base_gas`]. What rollups charge for proving and for publishing
+    /// data isn't included.
+    pub fn base_gas(self, spec: Op<Spec>) -> Option<u64> {
+        match self.support(spec) {
+            Support::Unsupported(_) => None,
+            _ => Some(spec.base_gas()),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = UnknownProfileError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lower = text.to_ascii_lowercase();
+
+        Profile::ALL
+            .iter()
+            .copied()
+            .find(|p| p.name() == lower)
+            .context(error::UnknownProfileContext { text })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_round_trip() {
+        for profile in Profile::ALL.iter() {
+            assert_eq!(profile.to_string().parse::<Profile>().unwrap(), *profile);
+        }
+
+        assert_eq!("Scroll".parse::<Profile>().unwrap(), Profile::Scroll);
+        assert!("optimism".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn ethereum_runs_everything() {
+        for byte in 0..=u8::MAX {
+            let spec = Op::from(byte);
+            assert_eq!(Profile::Ethereum.support(spec), Support::Full);
+            assert_eq!(Profile::Ethereum.base_gas(spec), Some(spec.base_gas()));
+        }
+    }
+
+    #[test]
+    fn base_gas() {
+        assert_eq!(Profile::ZkSyncEra.base_gas(Op::GetPc), None);
+        assert_eq!(Profile::Scroll.base_gas(Op::SelfDestruct), None);
+        assert_eq!(
+            Profile::PolygonZkEvm.base_gas(Op::SelfDestruct),
+            Some(Op::SelfDestruct.base_gas())
+        );
+        assert_eq!(Profile::Linea.base_gas(Op::Add), Some(3));
+    }
+}
@@ -6,43 +6,117 @@ use std::path::PathBuf;
 
 use super::{error, ParseError, Rule};
 
-pub(super) trait FromPair: Sized {
-    fn from_pair(pair: Pair<Rule>) -> Result<Self, ParseError>;
+pub(super) trait FromPair<'i>: Sized {
+    fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError>;
 }
 
-impl FromPair for PathBuf {
-    fn from_pair(pair: Pair<Rule>) -> Result<Self, ParseError> {
+impl<'i> FromPair<'i> for PathBuf {
+    fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError> {
+        String::from_pair(pair).map(Into::into)
+    }
+}
+
+impl<'i> FromPair<'i> for String {
+    fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError> {
         ensure!(pair.as_rule() == Rule::string, error::ArgumentType);
 
         let txt = pair.as_str();
-        if txt.contains('\\') {
-            // TODO
-            panic!("backslashes in paths aren't implemented yet.");
+        let txt = &txt[1..txt.len() - 1];
+
+        // The grammar only allows `\\` and `\"` as escapes.
+        let mut text = String::with_capacity(txt.len());
+        let mut chars = txt.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                text.extend(chars.next());
+            } else {
+                text.push(c);
+            }
         }
 
-        Ok(txt[1..txt.len() - 1].into())
+        Ok(text)
+    }
+}
+
+macro_rules! impl_from_pair_int {
+    ($($ty:ty),*) => {
+        $(
+            impl<'i> FromPair<'i> for $ty {
+                fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError> {
+                    let txt = pair.as_str();
+                    let (digits, radix) = match pair.as_rule() {
+                        Rule::binary => (&txt[2..], 2),
+                        Rule::octal => (&txt[2..], 8),
+                        Rule::decimal => (txt, 10),
+                        Rule::hex => (&txt[2..], 16),
+                        _ => return error::ArgumentType.fail(),
+                    };
+
+                    <$ty>::from_str_radix(digits, radix)
+                        .ok()
+                        .context(error::ArgumentTooLarge {
+                            text: txt,
+                            size: std::mem::size_of::<$ty>(),
+                        })
+                }
+            }
+        )*
+    };
+}
+
+impl_from_pair_int!(u8, u32);
+
+impl<'i> FromPair<'i> for Pair<'i, Rule> {
+    fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError> {
+        Ok(pair)
+    }
+}
+
+/// The bytes given to `%raw`, either as a number that fits in one byte, or as
+/// a string of hex digits.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(super) struct Bytes(pub(super) Vec<u8>);
+
+impl<'i> FromPair<'i> for Bytes {
+    fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError> {
+        if pair.as_rule() != Rule::string {
+            return u8::from_pair(pair).map(|b| Self(vec![b]));
+        }
+
+        let text = String::from_pair(pair)?;
+        let digits = text.strip_prefix("0x").unwrap_or(&text);
+
+        let bytes = hex::decode(digits)
+            .ok()
+            .filter(|b| !b.is_empty())
+            .with_context(|| error::InvalidHex { text: text.clone() })?;
+
+        Ok(Self(bytes))
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub(super) struct Label(pub(super) String);
+pub(super) struct Label<'i>(pub(super) &'i str);
 
-impl FromPair for Label {
-    fn from_pair(pair: Pair<Rule>) -> Result<Self, ParseError> {
+impl<'i> FromPair<'i> for Label<'i> {
+    fn from_pair(pair: Pair<'i, Rule>) -> Result<Self, ParseError> {
         ensure!(pair.as_rule() == Rule::label, error::ArgumentType);
-        let txt = pair.as_str();
-        Ok(Self(txt.into()))
+        Ok(Self(pair.as_str()))
     }
 }
 
-pub(super) trait Signature {
+pub(super) trait Signature<'i> {
     type Output;
-    fn parse_arguments(pairs: Pairs<Rule>) -> Result<Self::Output, ParseError>;
+    fn parse_arguments(pairs: Pairs<'i, Rule>) -> Result<Self::Output, ParseError>;
 }
 
-fn arg<T>(pairs: &mut Pairs<Rule>, expected: usize, got: &mut usize) -> Result<T, ParseError>
+fn arg<'i, T>(
+    pairs: &mut Pairs<'i, Rule>,
+    expected: usize,
+    got: &mut usize,
+) -> Result<T, ParseError>
 where
-    T: FromPair,
+    T: FromPair<'i>,
 {
     let pair = pairs.next().context(error::MissingArgument {
         got: *got,
@@ -52,10 +126,10 @@ where
     T::from_pair(pair)
 }
 
-impl Signature for () {
+impl<'i> Signature<'i> for () {
     type Output = Self;
 
-    fn parse_arguments(mut pairs: Pairs<Rule>) -> Result<Self, ParseError> {
+    fn parse_arguments(mut pairs: Pairs<'i, Rule>) -> Result<Self, ParseError> {
         match pairs.next() {
             Some(_) => error::ExtraArgument { expected: 0usize }.fail(),
             None => Ok(()),
@@ -63,13 +137,13 @@ impl Signature for () {
     }
 }
 
-impl<T> Signature for (T,)
+impl<'i, T> Signature<'i> for (T,)
 where
-    T: FromPair,
+    T: FromPair<'i>,
 {
     type Output = Self;
 
-    fn parse_arguments(mut pairs: Pairs<Rule>) -> Result<Self, ParseError> {
+    fn parse_arguments(mut pairs: Pairs<'i, Rule>) -> Result<Self, ParseError> {
         let expected = 1;
         let mut got = 0;
 
@@ -81,3 +155,92 @@ where
         }
     }
 }
+
+impl<'i, T, U> Signature<'i> for (T, U)
+where
+    T: FromPair<'i>,
+    U: FromPair<'i>,
+{
+    type Output = Self;
+
+    fn parse_arguments(mut pairs: Pairs<'i, Rule>) -> Result<Self, ParseError> {
+        let expected = 2;
+        let mut got = 0;
+
+        let first = arg::<T>(&mut pairs, expected, &mut got)?;
+        let second = arg::<U>(&mut pairs, expected, &mut got)?;
+
+        match pairs.next() {
+            Some(_) => error::ExtraArgument { expected }.fail(),
+            None => Ok((first, second)),
+        }
+    }
+}
+
+impl<'i, T, U, V> Signature<'i> for (T, U, V)
+where
+    T: FromPair<'i>,
+    U: FromPair<'i>,
+    V: FromPair<'i>,
+{
+    type Output = Self;
+
+    fn parse_arguments(mut pairs: Pairs<'i, Rule>) -> Result<Self, ParseError> {
+        let expected = 3;
+        let mut got = 0;
+
+        let first = arg::<T>(&mut pairs, expected, &mut got)?;
+        let second = arg::<U>(&mut pairs, expected, &mut got)?;
+        let third = arg::<V>(&mut pairs, expected, &mut got)?;
+
+        match pairs.next() {
+            Some(_) => error::ExtraArgument { expected }.fail(),
+            None => Ok((first, second, third)),
+        }
+    }
+}
+
+impl<'i, T, U> Signature<'i> for (T, Option<U>)
+where
+    T: FromPair<'i>,
+    U: FromPair<'i>,
+{
+    type Output = Self;
+
+    fn parse_arguments(mut pairs: Pairs<'i, Rule>) -> Result<Self, ParseError> {
+        let expected = 2;
+        let mut got = 0;
+
+        let first = arg::<T>(&mut pairs, expected, &mut got)?;
+        let second = match pairs.peek() {
+            Some(_) => Some(arg::<U>(&mut pairs, expected, &mut got)?),
+            None => None,
+        };
+
+        match pairs.next() {
+            Some(_) => error::ExtraArgument { expected }.fail(),
+            None => Ok((first, second)),
+        }
+    }
+}
+
+impl<'i, T> Signature<'i> for Vec<T>
+where
+    T: FromPair<'i>,
+{
+    type Output = Self;
+
+    fn parse_arguments(pairs: Pairs<'i, Rule>) -> Result<Self, ParseError> {
+        let result = pairs.map(T::from_pair).collect::<Result<Self, _>>()?;
+
+        ensure!(
+            !result.is_empty(),
+            error::MissingArgument {
+                got: 0usize,
+                expected: 1usize,
+            }
+        );
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,255 @@
+use pest::iterators::Pair;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{ensure, OptionExt};
+
+use crate::ast::Node;
+use crate::ops::{AbstractOp, Op, Specifier};
+
+use super::args::{FromPair, Signature};
+use super::locals::Locals;
+use super::{error, parse_narrow_push, span, Labels, ParseError, Rule};
+
+/// An event signature, like `Transfer(address indexed,address indexed,uint256)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    /// The signature without parameter names or `indexed`, which is what
+    /// gets hashed into the first topic.
+    canonical: String,
+
+    /// How many parameters are marked `indexed`, and so are topics.
+    indexed: usize,
+}
+
+impl Event {
+    /// Parse `text`, returning `None` if it isn't an event signature with at
+    /// most three indexed parameters.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let open = text.find('(')?;
+        let name = &text[..open];
+        let params = text[open + 1..].strip_suffix(')')?;
+
+        let mut chars = name.chars();
+        let first = chars.next()?;
+        if !(first.is_ascii_alphabetic() || first == '_')
+            || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return None;
+        }
+
+        let mut types = Vec::new();
+        let mut indexed = 0;
+
+        if !params.trim().is_empty() {
+            for param in split_top_level(params)? {
+                let (ty, rest) = split_type(param.trim())?;
+
+                let mut words: Vec<_> = rest.split_whitespace().collect();
+                if words.first() == Some(&"indexed") {
+                    indexed += 1;
+                    words.remove(0);
+                }
+
+                // Anything left is the name of the parameter.
+                if words.len() > 1 {
+                    return None;
+                }
+
+                types.push(ty);
+            }
+        }
+
+        if indexed > 3 {
+            return None;
+        }
+
+        Some(Self {
+            canonical: format!("{}({})", name, types.join(",")),
+            indexed,
+        })
+    }
+
+    /// The first topic of the event, the hash of its canonical signature.
+    fn topic(&self) -> [u8; 32] {
+        Keccak256::digest(self.canonical.as_bytes()).into()
+    }
+}
+
+/// Split `text` at the commas that aren't inside parentheses, or return `None`
+/// if the parentheses don't balance.
+fn split_top_level(text: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (idx, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(&text[start..idx]);
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+
+    if depth != 0 {
+        return None;
+    }
+
+    parts.push(&text[start..]);
+    Some(parts)
+}
+
+/// Split a parameter into its type, with whitespace removed, and whatever
+/// follows the type.
+fn split_type(param: &str) -> Option<(String, &str)> {
+    let mut depth = 0usize;
+    let end = param
+        .char_indices()
+        .find(|(_, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+            depth == 0 && c.is_whitespace()
+        })
+        .map(|(idx, _)| idx)
+        .unwrap_or(param.len());
+
+    let ty: String = param[..end].split_whitespace().collect();
+    if ty.is_empty() {
+        return None;
+    }
+
+    Some((ty, &param[end..]))
+}
+
+/// Expand `%emit("Event(...)", topics..., offset, size)` into pushes of the
+/// topics and the data, and the `log` that emits them.
+///
+/// A topic, offset, or size named with `%locals` is copied from the stack.
+/// Anything else is pushed as a constant or the address of a label.
+pub(super) fn parse_emit<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+    locals: &Locals,
+) -> Result<Vec<Node>, ParseError> {
+    let mut args = <Vec<Pair<Rule>>>::parse_arguments(pair.into_inner())?;
+    ensure!(
+        !args.is_empty(),
+        error::MissingArgument {
+            expected: 1usize,
+            got: 0usize,
+        }
+    );
+
+    let signature = String::from_pair(args.remove(0))?;
+    let event = Event::parse(&signature).context(error::InvalidEvent {
+        signature: &signature,
+    })?;
+
+    let expected = event.indexed + 3;
+    let got = args.len() + 1;
+    ensure!(got >= expected, error::MissingArgument { expected, got });
+    ensure!(got <= expected, error::ExtraArgument { expected });
+
+    let size = args.pop().unwrap();
+    let offset = args.pop().unwrap();
+
+    let mut nodes: Vec<Node> = Vec::new();
+
+    for topic in args.into_iter().rev() {
+        let node = argument(topic, nodes.len(), labels, locals)?;
+        nodes.push(node);
+    }
+
+    let spec = Specifier::push(32).unwrap();
+    nodes.push(
+        AbstractOp::with_immediate(spec, &event.topic())
+            .unwrap()
+            .into(),
+    );
+
+    let node = argument(size, nodes.len(), labels, locals)?;
+    nodes.push(node);
+    let node = argument(offset, nodes.len(), labels, locals)?;
+    nodes.push(node);
+
+    let log = match event.indexed {
+        0 => Op::Log1,
+        1 => Op::Log2,
+        2 => Op::Log3,
+        _ => Op::Log4,
+    };
+    nodes.push(log.into());
+
+    Ok(nodes)
+}
+
+/// The instruction that puts `arg` on the stack, after `pushed` other values
+/// were pushed since the last statement.
+fn argument<'a>(
+    arg: Pair<'a, Rule>,
+    pushed: usize,
+    labels: &mut Labels<'a>,
+    locals: &Locals,
+) -> Result<Node, ParseError> {
+    let depth = match arg.as_rule() {
+        Rule::label => locals.depth(arg.as_str()),
+        _ => None,
+    };
+
+    let depth = match depth {
+        Some(d) => d + pushed,
+        None => return Ok(parse_narrow_push(arg, labels)?.into()),
+    };
+
+    let spec = Specifier::dup(depth as u32 + 1).with_context(|| error::LocalOutOfReach {
+        name: arg.as_str(),
+        depth,
+        instruction: "dup",
+        above: locals.above(depth - pushed),
+        span: span(&arg),
+    })?;
+
+    Ok(AbstractOp::new(spec).unwrap().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_parse() {
+        let event = Event::parse("Transfer(address indexed from, address indexed, uint256 value)");
+        assert_eq!(
+            event,
+            Some(Event {
+                canonical: "Transfer(address,address,uint256)".into(),
+                indexed: 2,
+            })
+        );
+
+        let event = Event::parse("Swap((uint256, bytes32)[] indexed, bool)").unwrap();
+        assert_eq!(event.canonical, "Swap((uint256,bytes32)[],bool)");
+        assert_eq!(event.indexed, 1);
+
+        let event = Event::parse("Ping()").unwrap();
+        assert_eq!(event.canonical, "Ping()");
+        assert_eq!(event.indexed, 0);
+
+        assert_eq!(Event::parse("Transfer"), None);
+        assert_eq!(Event::parse("(uint256)"), None);
+        assert_eq!(Event::parse("A(uint256 indexed a b)"), None);
+        assert_eq!(Event::parse("A((uint256)"), None);
+        assert_eq!(
+            Event::parse("A(uint8 indexed, uint8 indexed, uint8 indexed, uint8 indexed)"),
+            None
+        );
+    }
+}
@@ -1,7 +1,10 @@
-use pest::error::Error;
+use pest::error::{Error, LineColLocation};
 
 use snafu::{Backtrace, IntoError, Snafu};
 
+use crate::codes::{self, Code};
+
+use super::huff::Rule as HuffRule;
 use super::Rule;
 
 /// Type for errors that may arise while parsing assembly source code.
@@ -22,12 +25,23 @@ pub enum ParseError {
     #[non_exhaustive]
     Lexer {
         /// The underlying source of this error.
-        source: Box<dyn std::error::Error>,
+        source: Box<dyn std::error::Error + Send + Sync>,
 
         /// The location of this error.
         backtrace: Backtrace,
     },
 
+    /// A string given to `%raw` wasn't a non-empty string of hex digits.
+    #[snafu(display("`{}` isn't a valid hex string", text))]
+    #[non_exhaustive]
+    InvalidHex {
+        /// The string, as written.
+        text: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
     /// A required argument for a macro was missing.
     #[snafu(display("expected {} argument(s) but only got {}", expected, got))]
     #[non_exhaustive]
@@ -60,6 +74,504 @@ pub enum ParseError {
         /// The location of the error.
         backtrace: Backtrace,
     },
+
+    /// A `%pragma` directive named a pragma that doesn't exist.
+    #[snafu(display("unknown pragma `{}`", name))]
+    #[non_exhaustive]
+    UnknownPragma {
+        /// The name of the pragma.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A macro was invoked, but never defined.
+    #[snafu(display("macro `{}` isn't defined", name))]
+    #[non_exhaustive]
+    UndefinedMacro {
+        /// The name of the macro.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A constant was used, but never defined.
+    #[snafu(display("constant `{}` isn't defined", name))]
+    #[non_exhaustive]
+    UndefinedConstant {
+        /// The name of the constant.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A macro referred to an argument it doesn't have.
+    #[snafu(display("`{}` isn't an argument of the macro", name))]
+    #[non_exhaustive]
+    UndefinedArgument {
+        /// The name of the argument.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A Huff builtin referred to a function, event, or error that wasn't
+    /// declared.
+    #[snafu(display("`{}` isn't declared", name))]
+    #[non_exhaustive]
+    UndefinedSignature {
+        /// The name of the function, event, or error.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// Something in a Huff file was defined more than once.
+    #[snafu(display("`{}` is defined more than once", name))]
+    #[non_exhaustive]
+    DuplicateDefinition {
+        /// The name of the definition.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An anonymous label reference, like `1f`, had no matching anonymous
+    /// label in the direction it points.
+    #[snafu(display(
+        "no `{}:` label {} `{}`",
+        number,
+        if *forward { "after" } else { "before" },
+        label
+    ))]
+    #[non_exhaustive]
+    UnmatchedAnonymousLabel {
+        /// The reference, as written.
+        label: String,
+
+        /// The number of the anonymous label.
+        number: String,
+
+        /// Whether the reference points forward (`f`) or backward (`b`).
+        forward: bool,
+
+        /// Where the reference is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A word that isn't an instruction was used as one, and no `%alias` by
+    /// that name had been declared.
+    #[snafu(display("`{}` isn't an instruction or alias", name))]
+    #[non_exhaustive]
+    UnknownInstruction {
+        /// The word, as written.
+        name: String,
+
+        /// Where the word is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A `dup` or `swap` named a stack slot that wasn't declared with
+    /// `%locals`, or that has since been popped.
+    #[snafu(display("`{}` isn't a local on the stack", name))]
+    #[non_exhaustive]
+    UnknownLocal {
+        /// The name, as written.
+        name: String,
+
+        /// Where the name is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A `dup` or `swap` named a stack slot too deep (or, for `swap`, too
+    /// shallow) for any instruction to reach.
+    #[snafu(display(
+        "`{}` is at depth {}, which `{}` can't reach",
+        name,
+        depth,
+        instruction
+    ))]
+    #[non_exhaustive]
+    LocalOutOfReach {
+        /// The name of the local.
+        name: String,
+
+        /// How many stack elements are above the local.
+        depth: usize,
+
+        /// The instruction, either `dup` or `swap`.
+        instruction: String,
+
+        /// The names of the named locals above this one, from the top of
+        /// the stack down.
+        above: Vec<String>,
+
+        /// Where the name is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A `dup` or `swap` deeper than any instruction can reach, like
+    /// `dup17`, was written or generated by a macro.
+    #[snafu(display("`{}` reaches deeper into the stack than any instruction can", name))]
+    #[non_exhaustive]
+    StackTooDeep {
+        /// The instruction, as written.
+        name: String,
+
+        /// Where the instruction is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An instruction from a proposal was used, but experimental
+    /// instructions weren't asked for.
+    #[snafu(display(
+        "`{}` is an experimental instruction from {}, which isn't enabled",
+        name,
+        origin
+    ))]
+    #[non_exhaustive]
+    ExperimentalDisabled {
+        /// The instruction, as written.
+        name: String,
+
+        /// Where the instruction comes from (ex. `EIP-2315`.)
+        origin: String,
+
+        /// Where the instruction is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An `%import(std, "...")` named a module the standard library doesn't
+    /// have.
+    #[snafu(display("`{}` isn't a module of the standard library", name))]
+    #[non_exhaustive]
+    UnknownModule {
+        /// The name of the module.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An `%emit` was given something that isn't an event signature, or an
+    /// event with more than three indexed parameters.
+    #[snafu(display(
+        "`{}` isn't an event signature with at most three indexed parameters",
+        signature
+    ))]
+    #[non_exhaustive]
+    InvalidEvent {
+        /// The signature, as written.
+        signature: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An array was indexed, but never defined with `%def`.
+    #[snafu(display("`{}` isn't an array defined with `%def`", name))]
+    #[non_exhaustive]
+    UndefinedArray {
+        /// The name of the array.
+        name: String,
+
+        /// Where the name is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An array was indexed with something other than a constant less than
+    /// its length.
+    #[snafu(display(
+        "`{}` isn't a constant index into `{}`, which has {} element(s)",
+        index,
+        name,
+        len
+    ))]
+    #[non_exhaustive]
+    ArrayIndex {
+        /// The name of the array.
+        name: String,
+
+        /// The index, as written.
+        index: String,
+
+        /// How many elements the array has.
+        len: usize,
+
+        /// Where the index is in the source.
+        span: Span,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// The body of a `%macro` has a `$(...)*` that isn't closed, is inside
+    /// another, or is in a macro without a variadic argument.
+    #[snafu(display("`%{}` has a `$(...)*` that can't be repeated", name))]
+    #[non_exhaustive]
+    InvalidRepetition {
+        /// The name of the macro.
+        name: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// The code a `%macro` expanded to had a mistake in it.
+    #[snafu(display("in the expansion of `%{}`: {}", name, error.summary()))]
+    #[non_exhaustive]
+    MacroExpansion {
+        /// The name of the macro.
+        name: String,
+
+        /// Where the macro was used.
+        span: Span,
+
+        /// The mistake in the expanded code.
+        error: Box<ParseError>,
+    },
+
+    /// A `substr(...)` reached outside its string, or split a character.
+    #[snafu(display(
+        "can't take {} byte(s) from offset {} of a string of {} byte(s)",
+        len,
+        start,
+        size
+    ))]
+    #[non_exhaustive]
+    StringRange {
+        /// The offset the substring starts at.
+        start: usize,
+
+        /// How many bytes the substring has.
+        len: usize,
+
+        /// How many bytes the string has.
+        size: usize,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A number given to a directive, like `%raw(0x100)`, was too large for
+    /// the argument it was given as.
+    #[snafu(display("`{}` doesn't fit in {} byte(s)", text, size))]
+    #[non_exhaustive]
+    ArgumentTooLarge {
+        /// The number, as written.
+        text: String,
+
+        /// How many bytes the argument can hold.
+        size: usize,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A `selector(...)` was given a string that isn't a function signature.
+    #[snafu(display("`{}` isn't a function signature", signature))]
+    #[non_exhaustive]
+    InvalidSelector {
+        /// The string.
+        signature: String,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// An error that doesn't know where it is, in the statement at `span`.
+    #[snafu(display("{}", error))]
+    #[non_exhaustive]
+    Located {
+        /// Where the statement is in the source.
+        span: Span,
+
+        /// The error.
+        error: Box<ParseError>,
+    },
+
+    /// Macros were nested more deeply than the limit allows.
+    #[snafu(display("macros are nested more than {} deep", limit))]
+    #[non_exhaustive]
+    RecursionLimit {
+        /// The deepest nesting allowed.
+        limit: usize,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// A macro invoked itself, directly or through other macros.
+    #[snafu(display("macro `{}` invokes itself: {}", name, chain.join(" -> ")))]
+    #[non_exhaustive]
+    RecursiveMacro {
+        /// The macro that invoked itself.
+        name: String,
+
+        /// The invocations from the first use of `name` to the one that
+        /// repeats it.
+        chain: Vec<String>,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+
+    /// Expanding macros or aliases produced more instructions than the limit
+    /// allows.
+    #[snafu(display("macros expanded to more than {} instructions", limit))]
+    #[non_exhaustive]
+    ExpansionLimit {
+        /// The most instructions allowed.
+        limit: usize,
+
+        /// The location of the error.
+        backtrace: Backtrace,
+    },
+}
+
+/// A region of source code, as one-based line and column numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The line and column where the region begins.
+    pub start: (usize, usize),
+
+    /// The line and column where the region ends.
+    pub end: (usize, usize),
+}
+
+impl ParseError {
+    /// The stable code identifying this kind of error.
+    pub fn code(&self) -> Code {
+        match self {
+            ParseError::ImmediateTooLarge { .. } => codes::IMMEDIATE_TOO_LARGE,
+            ParseError::Lexer { .. } => codes::SYNTAX,
+            ParseError::MissingArgument { .. } => codes::MISSING_ARGUMENT,
+            ParseError::ExtraArgument { .. } => codes::EXTRA_ARGUMENT,
+            ParseError::ArgumentType { .. } => codes::ARGUMENT_TYPE,
+            ParseError::UnknownPragma { .. } => codes::UNKNOWN_PRAGMA,
+            ParseError::UndefinedMacro { .. } => codes::UNDEFINED_MACRO,
+            ParseError::UndefinedConstant { .. } => codes::UNDEFINED_CONSTANT,
+            ParseError::UndefinedArgument { .. } => codes::UNDEFINED_ARGUMENT,
+            ParseError::UndefinedSignature { .. } => codes::UNDEFINED_SIGNATURE,
+            ParseError::DuplicateDefinition { .. } => codes::DUPLICATE_DEFINITION,
+            ParseError::RecursionLimit { .. } => codes::MACRO_RECURSION_LIMIT,
+            ParseError::RecursiveMacro { .. } => codes::RECURSIVE_MACRO,
+            ParseError::ExpansionLimit { .. } => codes::EXPANSION_LIMIT,
+            ParseError::UnmatchedAnonymousLabel { .. } => codes::UNMATCHED_ANONYMOUS_LABEL,
+            ParseError::UnknownInstruction { .. } => codes::UNKNOWN_INSTRUCTION,
+            ParseError::InvalidHex { .. } => codes::INVALID_HEX,
+            ParseError::UnknownLocal { .. } => codes::UNKNOWN_LOCAL,
+            ParseError::LocalOutOfReach { .. } => codes::LOCAL_OUT_OF_REACH,
+            ParseError::StackTooDeep { .. } => codes::STACK_TOO_DEEP,
+            ParseError::ExperimentalDisabled { .. } => codes::EXPERIMENTAL_DISABLED,
+            ParseError::UnknownModule { .. } => codes::UNKNOWN_MODULE,
+            ParseError::InvalidEvent { .. } => codes::INVALID_EVENT,
+            ParseError::UndefinedArray { .. } => codes::UNDEFINED_ARRAY,
+            ParseError::ArrayIndex { .. } => codes::ARRAY_INDEX,
+            ParseError::InvalidRepetition { .. } => codes::INVALID_REPETITION,
+            ParseError::StringRange { .. } => codes::STRING_RANGE,
+            ParseError::InvalidSelector { .. } => codes::INVALID_SELECTOR,
+            ParseError::ArgumentTooLarge { .. } => codes::ARGUMENT_TOO_LARGE,
+            ParseError::MacroExpansion { error, .. } | ParseError::Located { error, .. } => {
+                error.code()
+            }
+        }
+    }
+
+    /// This error, pointed at the statement at `span` if it doesn't already
+    /// know where it is.
+    pub(super) fn located(self, span: Span) -> Self {
+        match self.span() {
+            Some(_) => self,
+            None => ParseError::Located {
+                span,
+                error: Box::new(self),
+            },
+        }
+    }
+
+    /// A one line description of the error.
+    ///
+    /// Unlike the [`std::error::Error::source`] of a lexer error, this leaves
+    /// out the excerpt of the source code.
+    pub fn summary(&self) -> String {
+        let source = match self {
+            ParseError::Lexer { source, .. } => source.to_string(),
+            ParseError::Located { error, .. } => return error.summary(),
+            _ => return self.to_string(),
+        };
+
+        // The excerpt ends with a line like `  = expected ...`.
+        let message = source
+            .lines()
+            .rev()
+            .find_map(|line| line.trim_start().strip_prefix("= "));
+
+        match message {
+            Some(m) => format!("{}: {}", self, m),
+            None => self.to_string(),
+        }
+    }
+
+    /// The location of the error in the source, if known.
+    pub fn span(&self) -> Option<Span> {
+        let source = match self {
+            ParseError::Lexer { source, .. } => source,
+            ParseError::UnmatchedAnonymousLabel { span, .. }
+            | ParseError::UnknownInstruction { span, .. }
+            | ParseError::UnknownLocal { span, .. }
+            | ParseError::LocalOutOfReach { span, .. }
+            | ParseError::StackTooDeep { span, .. }
+            | ParseError::ExperimentalDisabled { span, .. }
+            | ParseError::UndefinedArray { span, .. }
+            | ParseError::ArrayIndex { span, .. }
+            | ParseError::MacroExpansion { span, .. }
+            | ParseError::Located { span, .. } => return Some(*span),
+            _ => return None,
+        };
+
+        let line_col = match source.downcast_ref::<Error<Rule>>() {
+            Some(pest) => &pest.line_col,
+            None => &source.downcast_ref::<Error<HuffRule>>()?.line_col,
+        };
+
+        let span = match *line_col {
+            LineColLocation::Pos(pos) => Span {
+                start: pos,
+                end: pos,
+            },
+            LineColLocation::Span(start, end) => Span { start, end },
+        };
+
+        Some(span)
+    }
 }
 
 impl From<Error<Rule>> for ParseError {
@@ -67,3 +579,9 @@ impl From<Error<Rule>> for ParseError {
         Lexer {}.into_error(Box::new(err))
     }
 }
+
+impl From<Error<HuffRule>> for ParseError {
+    fn from(err: Error<HuffRule>) -> Self {
+        Lexer {}.into_error(Box::new(err))
+    }
+}
@@ -0,0 +1,830 @@
+//! A front-end for the core of [Huff](https://huff.sh/): macros, constants,
+//! and jump labels.
+//!
+//! Each file is parsed into a [`HuffFile`], holding its definitions and the
+//! files it includes. Once every included file has been merged into one set of
+//! [`Definitions`], the `MAIN` macro is expanded into ordinary nodes.
+//!
+//! Labels belong to the macro invocation that declares them, so a macro can be
+//! invoked more than once. Labels from enclosing invocations are visible too,
+//! and can also be passed as arguments.
+mod parser {
+    #![allow(clippy::upper_case_acronyms)]
+
+    use pest_derive::Parser;
+
+    #[derive(Parser)]
+    #[grammar = "parse/huff.pest"]
+    pub(super) struct HuffParser;
+}
+
+use crate::ast::Node;
+use crate::ops::{AbstractOp, Op, Specifier};
+
+use pest::iterators::Pair;
+use pest::Parser;
+
+use sha3::{Digest, Keccak256};
+
+use snafu::{ensure, OptionExt};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::error::{self, ParseError};
+use super::Limits;
+
+use self::parser::HuffParser;
+pub(crate) use self::parser::Rule;
+
+/// Name of the macro expanded to produce the code.
+const MAIN: &str = "MAIN";
+
+/// A value passed to, or referenced from, a macro.
+#[derive(Debug, Clone)]
+enum Argument {
+    Literal(Vec<u8>),
+    Constant(String),
+    Param(String),
+    Ident(String),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Label(String),
+    Invoke(String, Vec<Argument>),
+    Push(Specifier, Vec<u8>),
+    Builtin(Builtin, Signature),
+    Value(Argument),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    FuncSig,
+    EventHash,
+    Error,
+}
+
+/// The argument of a builtin: either a signature, or the name of a declared
+/// function, event, or error.
+#[derive(Debug, Clone)]
+enum Signature {
+    Text(String),
+    Name(String),
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone)]
+enum Constant {
+    Literal(Vec<u8>),
+    FreeStoragePointer,
+}
+
+/// The definitions in a single Huff file.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HuffFile {
+    /// Paths from `#include` directives, relative to the file.
+    pub(crate) includes: Vec<PathBuf>,
+
+    constants: Vec<(String, Constant)>,
+    macros: Vec<(String, Macro)>,
+    functions: Vec<(String, String)>,
+    events: Vec<(String, String)>,
+    errors: Vec<(String, String)>,
+}
+
+/// Everything defined by a Huff file and the files it includes.
+#[derive(Debug, Default)]
+pub(crate) struct Definitions {
+    constants: HashMap<String, Vec<u8>>,
+    macros: HashMap<String, Macro>,
+    functions: HashMap<String, String>,
+    events: HashMap<String, String>,
+    errors: HashMap<String, String>,
+    free_slots: usize,
+}
+
+fn insert<V>(map: &mut HashMap<String, V>, name: String, value: V) -> Result<(), ParseError> {
+    ensure!(
+        !map.contains_key(&name),
+        error::DuplicateDefinition { name }
+    );
+    map.insert(name, value);
+    Ok(())
+}
+
+impl Definitions {
+    /// Add the definitions from `file`.
+    ///
+    /// `FREE_STORAGE_POINTER()` constants are given slots in the order they're
+    /// merged, starting from zero.
+    pub(crate) fn merge(&mut self, file: HuffFile) -> Result<(), ParseError> {
+        for (name, constant) in file.constants {
+            let value = match constant {
+                Constant::Literal(v) => v,
+                Constant::FreeStoragePointer => {
+                    let slot = self.free_slots;
+                    self.free_slots += 1;
+                    minimal(&slot.to_be_bytes())
+                }
+            };
+            insert(&mut self.constants, name, value)?;
+        }
+
+        for (name, m) in file.macros {
+            insert(&mut self.macros, name, m)?;
+        }
+
+        for (name, sig) in file.functions {
+            insert(&mut self.functions, name, sig)?;
+        }
+
+        for (name, sig) in file.events {
+            insert(&mut self.events, name, sig)?;
+        }
+
+        for (name, sig) in file.errors {
+            insert(&mut self.errors, name, sig)?;
+        }
+
+        Ok(())
+    }
+
+    /// Expand the `MAIN` macro, within `limits`.
+    pub(crate) fn expand(&self, limits: Limits) -> Result<Vec<Node>, ParseError> {
+        let main = self
+            .macros
+            .get(MAIN)
+            .context(error::UndefinedMacro { name: MAIN })?;
+
+        ensure!(
+            main.params.is_empty(),
+            error::ExtraArgument {
+                expected: main.params.len()
+            }
+        );
+
+        let mut expander = Expander {
+            defs: self,
+            limits,
+            nodes: Vec::new(),
+            scopes: Vec::new(),
+            chain: Vec::new(),
+            invocations: 0,
+        };
+
+        expander.invoke(MAIN, main, Vec::new())?;
+        Ok(expander.nodes)
+    }
+}
+
+/// A macro argument, after resolving it in the invoking macro.
+#[derive(Debug, Clone)]
+enum Value {
+    Literal(Vec<u8>),
+    Label(Arc<str>),
+    Op(Specifier),
+}
+
+#[derive(Debug)]
+struct Scope {
+    labels: HashMap<String, Arc<str>>,
+    args: HashMap<String, Value>,
+}
+
+#[derive(Debug)]
+struct Expander<'a> {
+    defs: &'a Definitions,
+    limits: Limits,
+    nodes: Vec<Node>,
+    scopes: Vec<Scope>,
+
+    /// Names of the macros being expanded, outermost first.
+    chain: Vec<&'a str>,
+
+    invocations: usize,
+}
+
+impl<'a> Expander<'a> {
+    fn invoke(&mut self, name: &'a str, m: &'a Macro, args: Vec<Value>) -> Result<(), ParseError> {
+        if let Some(first) = self.chain.iter().position(|n| *n == name) {
+            let mut chain: Vec<_> = self.chain[first..].iter().map(|n| n.to_string()).collect();
+            chain.push(name.to_owned());
+
+            return error::RecursiveMacro { name, chain }.fail();
+        }
+
+        ensure!(
+            self.chain.len() < self.limits.depth,
+            error::RecursionLimit {
+                limit: self.limits.depth
+            }
+        );
+
+        // Only labels in `MAIN` keep their names as written.
+        let id = self.invocations;
+        self.invocations += 1;
+
+        let labels = m
+            .body
+            .iter()
+            .filter_map(|s| match s {
+                Statement::Label(l) if id == 0 => Some((l.clone(), l.as_str().into())),
+                Statement::Label(l) => Some((l.clone(), format!("{}.{}.{}", name, id, l).into())),
+                _ => None,
+            })
+            .collect();
+
+        let args = m.params.iter().cloned().zip(args).collect();
+
+        self.scopes.push(Scope { labels, args });
+        self.chain.push(name);
+        let result = m.body.iter().try_for_each(|s| self.statement(s));
+        self.chain.pop();
+        self.scopes.pop();
+
+        result
+    }
+
+    fn push(&mut self, node: Node) -> Result<(), ParseError> {
+        ensure!(
+            self.nodes.len() < self.limits.size,
+            error::ExpansionLimit {
+                limit: self.limits.size
+            }
+        );
+
+        self.nodes.push(node);
+        Ok(())
+    }
+
+    fn scope(&self) -> &Scope {
+        self.scopes.last().unwrap()
+    }
+
+    /// Find the label `name` in the current invocation, or one enclosing it.
+    fn label(&self, name: &str) -> Option<Arc<str>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.labels.get(name))
+            .cloned()
+    }
+
+    fn resolve(&self, arg: &Argument) -> Result<Value, ParseError> {
+        let value = match arg {
+            Argument::Literal(v) => Value::Literal(v.clone()),
+            Argument::Constant(name) => {
+                let value = self
+                    .defs
+                    .constants
+                    .get(name)
+                    .context(error::UndefinedConstant { name })?;
+                Value::Literal(value.clone())
+            }
+            Argument::Param(name) => self
+                .scope()
+                .args
+                .get(name)
+                .cloned()
+                .context(error::UndefinedArgument { name })?,
+            Argument::Ident(name) => match self.label(name) {
+                Some(label) => Value::Label(label),
+                None => match opcode(name) {
+                    Some(spec) => Value::Op(spec),
+                    // Leave undeclared labels for the assembler to report.
+                    None => Value::Label(name.as_str().into()),
+                },
+            },
+        };
+
+        Ok(value)
+    }
+
+    fn emit(&mut self, value: Value) -> Result<(), ParseError> {
+        let op = match value {
+            Value::Literal(v) => {
+                let spec = Specifier::push(v.len() as u32).unwrap();
+                AbstractOp::with_immediate(spec, &v).unwrap()
+            }
+            Value::Label(label) => AbstractOp::with_label(Specifier::push(2).unwrap(), label),
+            Value::Op(spec) => AbstractOp::new(spec).context(error::MissingArgument {
+                expected: 1usize,
+                got: 0usize,
+            })?,
+        };
+
+        self.push(op.into())
+    }
+
+    fn statement(&mut self, statement: &'a Statement) -> Result<(), ParseError> {
+        match statement {
+            Statement::Label(name) => {
+                let label = self.scope().labels[name].clone();
+                self.push(AbstractOp::Label(label).into())?;
+                self.push(Op::JumpDest.into())?;
+            }
+            Statement::Push(spec, imm) => {
+                let size = spec.size() as usize - 1;
+                ensure!(imm.len() <= size, error::ImmediateTooLarge);
+
+                let mut padded = vec![0u8; size - imm.len()];
+                padded.extend_from_slice(imm);
+
+                let op = AbstractOp::with_immediate(*spec, &padded).unwrap();
+                self.push(op.into())?;
+            }
+            Statement::Builtin(builtin, sig) => {
+                let op = self.builtin(*builtin, sig)?;
+                self.push(op.into())?;
+            }
+            Statement::Value(arg) => {
+                let value = self.resolve(arg)?;
+                self.emit(value)?;
+            }
+            Statement::Invoke(name, args) => {
+                let m = self
+                    .defs
+                    .macros
+                    .get(name)
+                    .context(error::UndefinedMacro { name })?;
+
+                ensure!(
+                    args.len() >= m.params.len(),
+                    error::MissingArgument {
+                        expected: m.params.len(),
+                        got: args.len(),
+                    }
+                );
+
+                ensure!(
+                    args.len() <= m.params.len(),
+                    error::ExtraArgument {
+                        expected: m.params.len(),
+                    }
+                );
+
+                let values = args
+                    .iter()
+                    .map(|a| self.resolve(a))
+                    .collect::<Result<_, _>>()?;
+
+                self.invoke(name, m, values)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn builtin(&self, builtin: Builtin, sig: &Signature) -> Result<AbstractOp, ParseError> {
+        let declared = match builtin {
+            Builtin::FuncSig => &self.defs.functions,
+            Builtin::EventHash => &self.defs.events,
+            Builtin::Error => &self.defs.errors,
+        };
+
+        let text = match sig {
+            Signature::Text(t) => t,
+            Signature::Name(n) => declared
+                .get(n)
+                .context(error::UndefinedSignature { name: n })?,
+        };
+
+        let hash = Keccak256::digest(text.as_bytes());
+
+        let op = match builtin {
+            Builtin::FuncSig => AbstractOp::with_immediate(Specifier::push(4).unwrap(), &hash[..4]),
+            Builtin::EventHash => AbstractOp::with_immediate(Specifier::push(32).unwrap(), &hash),
+            Builtin::Error => {
+                // Left aligned, ready to be stored at the start of the revert
+                // data.
+                let mut word = [0u8; 32];
+                word[..4].copy_from_slice(&hash[..4]);
+                AbstractOp::with_immediate(Specifier::push(32).unwrap(), &word)
+            }
+        };
+
+        Ok(op.unwrap())
+    }
+}
+
+/// Look up an instruction without an immediate argument by its Huff name.
+fn opcode(name: &str) -> Option<Specifier> {
+    let name = match name {
+        "sha3" => "keccak256",
+        n => n,
+    };
+
+    name.parse().ok()
+}
+
+/// Strip leading zeros, keeping at least one byte.
+fn minimal(bytes: &[u8]) -> Vec<u8> {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    let start = std::cmp::min(zeros, bytes.len() - 1);
+    bytes[start..].to_vec()
+}
+
+fn literal(pair: Pair<Rule>) -> Result<Vec<u8>, ParseError> {
+    let digits = pair.as_str()[2..].trim_start_matches('0');
+    ensure!(digits.len() <= 64, error::ImmediateTooLarge);
+
+    let padded = match digits.len() {
+        0 => "00".to_owned(),
+        n if n % 2 == 1 => format!("0{}", digits),
+        _ => digits.to_owned(),
+    };
+
+    Ok(hex::decode(padded).expect("grammar only allows hex digits"))
+}
+
+fn string(pair: Pair<Rule>) -> String {
+    let text = pair.as_str();
+    text[1..text.len() - 1].to_owned()
+}
+
+/// The canonical form of an ABI signature: `name(type,type)`, without
+/// parameter names, `indexed`, or whitespace.
+fn canonical(name: &str, params: &str) -> String {
+    let mut types = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (idx, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(&params[start..idx]);
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    types.push(&params[start..]);
+
+    let types: Vec<_> = types
+        .into_iter()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| t.split_whitespace().next().unwrap())
+        .collect();
+
+    format!("{}({})", name, types.join(","))
+}
+
+fn argument(pair: Pair<Rule>) -> Result<Argument, ParseError> {
+    let arg = match pair.as_rule() {
+        Rule::literal => Argument::Literal(literal(pair)?),
+        Rule::constant_ref => Argument::Constant(inner(pair)),
+        Rule::arg_ref => Argument::Param(inner(pair)),
+        Rule::ident => Argument::Ident(pair.as_str().to_owned()),
+        r => unreachable!("{:?}", r),
+    };
+
+    Ok(arg)
+}
+
+/// The text of the first child of `pair`.
+fn inner(pair: Pair<Rule>) -> String {
+    pair.into_inner().next().unwrap().as_str().to_owned()
+}
+
+fn statement(pair: Pair<Rule>) -> Result<Statement, ParseError> {
+    let statement = match pair.as_rule() {
+        Rule::label_defn => Statement::Label(inner(pair)),
+        Rule::push => {
+            let mut pairs = pair.into_inner();
+            let size: u32 = pairs.next().unwrap().as_str().parse().unwrap();
+            let imm = literal(pairs.next().unwrap())?;
+            Statement::Push(Specifier::push(size).unwrap(), imm)
+        }
+        Rule::builtin => {
+            let mut pairs = pair.into_inner();
+            let builtin = match pairs.next().unwrap().as_str() {
+                "__FUNC_SIG" => Builtin::FuncSig,
+                "__EVENT_HASH" => Builtin::EventHash,
+                "__ERROR" => Builtin::Error,
+                b => unreachable!("{}", b),
+            };
+            let arg = pairs.next().unwrap();
+            let sig = match arg.as_rule() {
+                Rule::string => Signature::Text(string(arg)),
+                _ => Signature::Name(arg.as_str().to_owned()),
+            };
+            Statement::Builtin(builtin, sig)
+        }
+        Rule::invocation => {
+            let mut pairs = pair.into_inner();
+            let name = pairs.next().unwrap().as_str().to_owned();
+            let args = pairs.map(argument).collect::<Result<_, _>>()?;
+            Statement::Invoke(name, args)
+        }
+        _ => Statement::Value(argument(pair)?),
+    };
+
+    Ok(statement)
+}
+
+fn signature(pair: Pair<Rule>) -> (String, String) {
+    let mut pairs = pair.into_inner();
+    let name = pairs.next().unwrap().as_str();
+    let params = pairs.next().unwrap().as_str();
+    (name.to_owned(), canonical(name, params))
+}
+
+pub(crate) fn parse_huff(src: &str) -> Result<HuffFile, ParseError> {
+    let mut file = HuffFile::default();
+
+    for pair in HuffParser::parse(Rule::file, src)? {
+        match pair.as_rule() {
+            Rule::include => {
+                let path = string(pair.into_inner().next().unwrap());
+                file.includes.push(path.into());
+            }
+            Rule::constant => {
+                let mut pairs = pair.into_inner();
+                let name = pairs.next().unwrap().as_str().to_owned();
+                let value = pairs.next().unwrap();
+                let constant = match value.as_rule() {
+                    Rule::free_storage_pointer => Constant::FreeStoragePointer,
+                    _ => Constant::Literal(literal(value)?),
+                };
+                file.constants.push((name, constant));
+            }
+            Rule::macro_defn => {
+                let mut pairs = pair.into_inner();
+                let name = pairs.next().unwrap().as_str().to_owned();
+                let params = pairs
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .map(|p| p.as_str().to_owned())
+                    .collect();
+                let body = pairs
+                    .find(|p| p.as_rule() == Rule::body)
+                    .unwrap()
+                    .into_inner()
+                    .map(statement)
+                    .collect::<Result<_, _>>()?;
+                file.macros.push((name, Macro { params, body }));
+            }
+            Rule::function => file.functions.push(signature(pair)),
+            Rule::event => file.events.push(signature(pair)),
+            Rule::error_defn => file.errors.push(signature(pair)),
+            _ => continue,
+        }
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use crate::ops::Imm;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn expand(src: &str) -> Result<Vec<Node>, ParseError> {
+        let mut defs = Definitions::default();
+        defs.merge(parse_huff(src)?)?;
+        defs.expand(Limits::default())
+    }
+
+    #[test]
+    fn huff_literals_and_ops() {
+        let src = r#"
+            // A comment.
+            #define macro MAIN() = takes(0) returns(0) {
+                0x00 0x0100 /* another */ add
+                push2 0x01
+                sha3
+            }
+        "#;
+
+        let expected = vec![
+            Node::from(Op::Push1(Imm::from([0]))),
+            Node::from(Op::Push2(Imm::from([1, 0]))),
+            Node::from(Op::Add),
+            Node::from(Op::Push2(Imm::from([0, 1]))),
+            Node::from(Op::Keccak256),
+        ];
+
+        assert_eq!(expand(src).unwrap(), expected);
+    }
+
+    #[test]
+    fn huff_constants() {
+        let src = r#"
+            #define constant FIRST = FREE_STORAGE_POINTER()
+            #define constant SECOND = FREE_STORAGE_POINTER()
+            #define constant OWNER = 0x00000000000000000000000000000000000000ff
+
+            #define macro MAIN() = {
+                [SECOND] [OWNER]
+            }
+        "#;
+
+        let expected = vec![
+            Node::from(Op::Push1(Imm::from([1]))),
+            Node::from(Op::Push1(Imm::from([0xff]))),
+        ];
+
+        assert_eq!(expand(src).unwrap(), expected);
+    }
+
+    #[test]
+    fn huff_labels_per_invocation() {
+        let src = r#"
+            #define macro SKIP() = takes(0) returns(0) {
+                done jump
+                done:
+            }
+
+            #define macro MAIN() = takes(0) returns(0) {
+                SKIP()
+                SKIP()
+                end jump
+                end:
+            }
+        "#;
+
+        let nodes = expand(src).unwrap();
+
+        let labels: Vec<_> = nodes
+            .iter()
+            .filter_map(|n| match n {
+                Node::Op(AbstractOp::Label(l)) => Some(l.to_string()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(labels, ["SKIP.1.done", "SKIP.2.done", "end"]);
+        assert_eq!(
+            nodes[0],
+            AbstractOp::with_label(Specifier::push(2).unwrap(), "SKIP.1.done").into()
+        );
+        assert_eq!(nodes[3], Op::JumpDest.into());
+    }
+
+    #[test]
+    fn huff_arguments() {
+        let src = r#"
+            #define constant VALUE = 0x2a
+
+            #define macro BRANCH(dest, value, op) = takes(0) returns(0) {
+                <value> <op> <dest> jumpi
+            }
+
+            #define macro MAIN() = takes(0) returns(0) {
+                BRANCH(out, [VALUE], iszero)
+                out:
+            }
+        "#;
+
+        let expected = vec![
+            Node::from(Op::Push1(Imm::from([0x2a]))),
+            Node::from(Op::IsZero),
+            Node::from(AbstractOp::with_label(Specifier::push(2).unwrap(), "out")),
+            Node::from(Op::JumpI),
+            Node::from(AbstractOp::Label("out".into())),
+            Node::from(Op::JumpDest),
+        ];
+
+        assert_eq!(expand(src).unwrap(), expected);
+    }
+
+    #[test]
+    fn huff_builtins() {
+        let src = r#"
+            #define function transfer(address to, uint256 amount) nonpayable returns (bool)
+            #define event Transfer(address indexed from, address indexed to, uint256)
+            #define error Unauthorized()
+
+            #define macro MAIN() = takes(0) returns(0) {
+                __FUNC_SIG(transfer)
+                __FUNC_SIG("transfer(address,uint256)")
+                __EVENT_HASH(Transfer)
+                __ERROR(Unauthorized)
+            }
+        "#;
+
+        let expected = vec![
+            Node::from(Op::Push4(Imm::from(hex!("a9059cbb")))),
+            Node::from(Op::Push4(Imm::from(hex!("a9059cbb")))),
+            Node::from(Op::Push32(Imm::from(hex!(
+                "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+            )))),
+            Node::from(Op::Push32(Imm::from(hex!(
+                "82b4290000000000000000000000000000000000000000000000000000000000"
+            )))),
+        ];
+
+        assert_eq!(expand(src).unwrap(), expected);
+    }
+
+    #[test]
+    fn huff_includes() {
+        let file = parse_huff("#include \"./lib.huff\"\n#include \"other.huff\"").unwrap();
+        assert_eq!(
+            file.includes,
+            [PathBuf::from("./lib.huff"), PathBuf::from("other.huff")]
+        );
+    }
+
+    #[test]
+    fn huff_errors() {
+        assert_matches!(
+            expand("#define macro NOT_MAIN() = {}"),
+            Err(ParseError::UndefinedMacro { name, .. }) if name == "MAIN"
+        );
+
+        assert_matches!(
+            expand("#define macro MAIN() = { [NOPE] }"),
+            Err(ParseError::UndefinedConstant { name, .. }) if name == "NOPE"
+        );
+
+        assert_matches!(
+            expand("#define macro MAIN() = { <nope> }"),
+            Err(ParseError::UndefinedArgument { name, .. }) if name == "nope"
+        );
+
+        assert_matches!(
+            expand("#define macro A(x) = {} #define macro MAIN() = { A() }"),
+            Err(ParseError::MissingArgument {
+                expected: 1,
+                got: 0,
+                ..
+            })
+        );
+
+        assert_matches!(
+            expand("#define macro MAIN() = { MAIN() }"),
+            Err(ParseError::RecursiveMacro { name, chain, .. })
+                if name == "MAIN" && chain == ["MAIN", "MAIN"]
+        );
+
+        assert_matches!(
+            expand("#define constant A = 0x01 #define constant A = 0x02"),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "A"
+        );
+
+        assert_matches!(
+            expand("#define macro MAIN() = { push1 0x0100 }"),
+            Err(ParseError::ImmediateTooLarge { .. })
+        );
+
+        assert_matches!(
+            expand("#define macro MAIN() = { 0x01 add"),
+            Err(ParseError::Lexer { .. })
+        );
+    }
+
+    #[test]
+    fn huff_limits() {
+        let src = "
+            #define macro A() = { B() }
+            #define macro B() = { C() }
+            #define macro C() = { A() }
+            #define macro MAIN() = { A() }
+        ";
+
+        let err = expand(src).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "macro `A` invokes itself: A -> B -> C -> A"
+        );
+
+        let src = "
+            #define macro ONE() = { 0x01 0x01 }
+            #define macro TWO() = { ONE() ONE() }
+            #define macro MAIN() = { TWO() TWO() }
+        ";
+
+        let mut defs = Definitions::default();
+        defs.merge(parse_huff(src).unwrap()).unwrap();
+
+        let nodes = defs.expand(Limits::default()).unwrap();
+        assert_eq!(nodes.len(), 8);
+
+        assert_matches!(
+            defs.expand(Limits { depth: 2, size: 8 }),
+            Err(ParseError::RecursionLimit { limit: 2, .. })
+        );
+
+        assert_matches!(
+            defs.expand(Limits { depth: 3, size: 7 }),
+            Err(ParseError::ExpansionLimit { limit: 7, .. })
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use pest::iterators::Pair;
+
+use snafu::{ensure, OptionExt};
+
+use crate::ast::Node;
+use crate::ops::{AbstractOp, Metadata, Specifier};
+
+use super::args::Label;
+use super::{error, span, ParseError, Rule};
+
+/// The stack slots named with `%locals`, followed through the instructions
+/// after them so `dup a` and `swap a` can find the slot named `a`.
+///
+/// Instructions are followed in the order they're written, without regard
+/// for jumps.
+#[derive(Debug, Default)]
+pub(super) struct Locals {
+    /// The name of each slot, from the bottom of the stack to the top, or
+    /// `None` for slots without a name.
+    slots: Vec<Option<String>>,
+}
+
+impl Locals {
+    /// Name the slots at the top of the stack, listed from the top down,
+    /// forgetting any names given before.
+    pub(super) fn declare(&mut self, names: Vec<Label>) -> Result<(), ParseError> {
+        self.slots.clear();
+
+        for Label(name) in names.into_iter().rev() {
+            ensure!(
+                !self.slots.iter().flatten().any(|n| n == name),
+                error::DuplicateDefinition { name }
+            );
+            self.slots.push(Some(name.to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// The `dup` or `swap` that reaches the slot named in `pair`, a
+    /// `local_op`.
+    pub(super) fn resolve(&self, pair: Pair<Rule>) -> Result<AbstractOp, ParseError> {
+        let mut pairs = pair.into_inner();
+        let instruction = pairs.next().unwrap().as_str();
+        let name = pairs.next().unwrap();
+
+        let depth = self
+            .depth(name.as_str())
+            .with_context(|| error::UnknownLocal {
+                name: name.as_str(),
+                span: span(&name),
+            })?;
+
+        let spec = match instruction {
+            "dup" => Specifier::dup(depth as u32 + 1),
+            _ => Specifier::swap(depth as u32),
+        };
+
+        let spec = spec.with_context(|| error::LocalOutOfReach {
+            name: name.as_str(),
+            depth,
+            instruction,
+            above: self.above(depth),
+            span: span(&name),
+        })?;
+
+        Ok(AbstractOp::new(spec).unwrap())
+    }
+
+    /// How many slots are above the one named `name`, if there is one.
+    pub(super) fn depth(&self, name: &str) -> Option<usize> {
+        self.slots
+            .iter()
+            .rev()
+            .position(|n| n.as_deref() == Some(name))
+    }
+
+    /// The names of the named slots among the `depth` slots at the top of the
+    /// stack, from the top down.
+    pub(super) fn above(&self, depth: usize) -> Vec<String> {
+        self.slots
+            .iter()
+            .rev()
+            .take(depth)
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Follow the slots through `nodes`, which come next in the program.
+    pub(super) fn step(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Op(op) => self.op(op),
+                Node::ComputedPush { .. } | Node::PushExpression(_) => self.slots.push(None),
+                Node::Location(_)
+                | Node::Expanded { .. }
+                | Node::Assert { .. }
+                | Node::Export(_)
+                | Node::PragmaOnce
+                | Node::PragmaSolidityMemory => (),
+
+                // Code from elsewhere could do anything to the stack.
+                _ => self.slots.clear(),
+            }
+        }
+    }
+
+    fn op(&mut self, op: &AbstractOp) {
+        if let AbstractOp::Op(op) = op {
+            let spec = op.specifier();
+
+            if let Some(n) = (1..=16).find(|n| Specifier::dup(*n) == Some(spec)) {
+                self.reach(n as usize);
+                self.slots.push(None);
+                return;
+            }
+
+            if let Some(n) = (1..=16).find(|n| Specifier::swap(*n) == Some(spec)) {
+                self.reach(n as usize + 1);
+                let top = self.slots.len() - 1;
+                self.slots.swap(top, top - n as usize);
+                return;
+            }
+        }
+
+        self.reach(op.pops());
+        let remaining = self.slots.len() - op.pops();
+        self.slots.truncate(remaining);
+        self.slots.extend((0..op.pushes()).map(|_| None));
+    }
+
+    /// Make sure at least `len` slots are followed, adding unnamed ones to
+    /// the bottom of the stack.
+    fn reach(&mut self, len: usize) {
+        if let Some(missing) = len.checked_sub(self.slots.len()) {
+            self.slots.splice(0..0, (0..missing).map(|_| None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ops::Op;
+
+    use super::*;
+
+    fn locals(names: &[&'static str]) -> Locals {
+        let mut locals = Locals::default();
+        locals
+            .declare(names.iter().copied().map(Label).collect())
+            .unwrap();
+        locals
+    }
+
+    #[test]
+    fn step_follows_values() {
+        let mut locals = locals(&["a", "b", "c"]);
+        assert_eq!(locals.depth("a"), Some(0));
+        assert_eq!(locals.depth("c"), Some(2));
+
+        // Copy `c` to the top, then swap the copy with `c`.
+        locals.step(&[Node::from(Op::Dup3), Node::from(Op::Swap3)]);
+        assert_eq!(locals.depth("c"), Some(0));
+        assert_eq!(locals.depth("a"), Some(1));
+        assert_eq!(locals.depth("b"), Some(2));
+
+        locals.step(&[Node::from(Op::Add)]);
+        assert_eq!(locals.depth("c"), None);
+        assert_eq!(locals.depth("a"), None);
+        assert_eq!(locals.depth("b"), Some(1));
+
+        locals.step(&[Node::IncludeHex("a.hex".into())]);
+        assert_eq!(locals.depth("b"), None);
+    }
+
+    #[test]
+    fn step_swap_below_names() {
+        let mut locals = locals(&["a"]);
+        locals.step(&[Node::from(Op::Swap2)]);
+        assert_eq!(locals.depth("a"), Some(2));
+        assert_eq!(locals.slots.len(), 3);
+    }
+}
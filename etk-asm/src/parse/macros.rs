@@ -0,0 +1,383 @@
+use pest::iterators::Pair;
+use pest::Parser;
+
+use snafu::{ensure, OptionExt};
+
+use std::collections::HashMap;
+
+use super::error::{self, ParseError, Span};
+use super::parser::{AsmParser, Rule};
+use super::{span, Limits};
+
+/// An instruction macro declared with `%macro`.
+#[derive(Debug)]
+struct Macro {
+    /// The names of the arguments, in order.
+    params: Vec<String>,
+
+    /// Whether the last argument (written `name...`) collects any number of
+    /// arguments.
+    variadic: bool,
+
+    /// The text between the parameters and `%end`.
+    body: String,
+
+    /// Where the macro's name is declared.
+    span: Span,
+}
+
+impl Macro {
+    /// The name of the variadic argument, if there is one.
+    fn variadic(&self) -> Option<&str> {
+        match self.variadic {
+            true => self.params.last().map(String::as_str),
+            false => None,
+        }
+    }
+
+    /// Match `args` up with the parameters.
+    fn args<'a>(&'a self, args: &'a [&'a str]) -> Args<'a> {
+        let fixed = self.params.len() - usize::from(self.variadic);
+
+        Args {
+            fixed: self
+                .params
+                .iter()
+                .map(String::as_str)
+                .zip(args.iter().copied())
+                .take(fixed)
+                .collect(),
+            rest: args.get(fixed..).unwrap_or_default(),
+        }
+    }
+}
+
+/// The arguments of one use of a macro.
+struct Args<'a> {
+    /// The arguments for each parameter, except the variadic one.
+    fixed: HashMap<&'a str, &'a str>,
+
+    /// The arguments collected by the variadic parameter.
+    rest: &'a [&'a str],
+}
+
+/// Instruction macros declared with `%macro`, by name.
+///
+/// A macro's body is kept as text, and each use pastes its arguments into a
+/// copy of the text, which is then parsed like the rest of the file. In the
+/// body:
+///
+///  - `$name` and `${name}` are replaced by the argument `name`, so
+///    `handler_$name:` or `${name}_end:` declares a label named after an
+///    argument.
+///  - `$(...)*` is repeated once for each argument collected by a variadic
+///    parameter, with the parameter standing for one argument each time.
+///    Elsewhere, the variadic parameter stands for all of them, separated by
+///    commas.
+#[derive(Debug, Default)]
+pub(super) struct Macros {
+    declared: HashMap<String, Macro>,
+
+    /// Names of the macros being expanded, outermost first.
+    chain: Vec<String>,
+}
+
+impl Macros {
+    /// Declare the macro in `pair`, a `macro_defn`.
+    pub(super) fn declare(&mut self, pair: Pair<Rule>) -> Result<(), ParseError> {
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap();
+        let location = span(&name);
+        let name = name.as_str();
+
+        ensure!(
+            !self.declared.contains_key(name) && !is_builtin(name),
+            error::DuplicateDefinition { name }
+        );
+
+        let mut params = Vec::new();
+        let mut variadic = false;
+        let mut body = String::new();
+
+        for pair in pairs {
+            let param = match pair.as_rule() {
+                Rule::macro_param => pair.as_str(),
+                Rule::macro_varargs => {
+                    variadic = true;
+                    pair.into_inner().next().unwrap().as_str()
+                }
+                _ => {
+                    body = pair.as_str().to_owned();
+                    continue;
+                }
+            };
+
+            ensure!(
+                !params.iter().any(|p| p == param),
+                error::DuplicateDefinition { name: param }
+            );
+            params.push(param.to_owned());
+        }
+
+        let m = Macro {
+            params,
+            variadic,
+            body,
+            span: location,
+        };
+
+        // Paste placeholders into the body, so mistakes in it are reported
+        // where the macro is declared instead of where it's used.
+        let empty = vec![""; m.params.len()];
+        paste(name, &m, &m.body, &m.args(&empty), None)?;
+
+        self.declared.insert(name.to_owned(), m);
+        Ok(())
+    }
+
+    /// The text that the macro `name` expands to with `args`, and where the
+    /// macro was declared.
+    pub(super) fn substitute(
+        &self,
+        name: &str,
+        args: &[&str],
+    ) -> Result<(String, Span), ParseError> {
+        let m = self
+            .declared
+            .get(name)
+            .context(error::UndefinedMacro { name })?;
+
+        let required = m.params.len() - usize::from(m.variadic);
+
+        ensure!(
+            args.len() >= required,
+            error::MissingArgument {
+                expected: required,
+                got: args.len(),
+            }
+        );
+
+        ensure!(
+            m.variadic || args.len() <= m.params.len(),
+            error::ExtraArgument {
+                expected: m.params.len(),
+            }
+        );
+
+        let text = paste(name, m, &m.body, &m.args(args), None)?;
+        Ok((text, m.span))
+    }
+
+    /// Note that the macro `name` is being expanded, until [`Macros::leave`]
+    /// is called.
+    pub(super) fn enter(&mut self, name: &str, limits: Limits) -> Result<(), ParseError> {
+        if let Some(first) = self.chain.iter().position(|n| n == name) {
+            let mut chain = self.chain[first..].to_vec();
+            chain.push(name.to_owned());
+
+            return error::RecursiveMacro { name, chain }.fail();
+        }
+
+        ensure!(
+            self.chain.len() < limits.depth,
+            error::RecursionLimit {
+                limit: limits.depth
+            }
+        );
+
+        self.chain.push(name.to_owned());
+        Ok(())
+    }
+
+    /// Note that the innermost macro being expanded is done.
+    pub(super) fn leave(&mut self) {
+        self.chain.pop();
+    }
+}
+
+/// Whether `%name(...)` already means one of the built-in macros.
+fn is_builtin(name: &str) -> bool {
+    AsmParser::parse(Rule::builtin_macro, name).is_ok()
+}
+
+/// Paste `args` into `text`, part of the body of the macro `m` named `name`.
+/// Inside a repetition, `each` is the argument the variadic parameter stands
+/// for.
+fn paste(
+    name: &str,
+    m: &Macro,
+    text: &str,
+    args: &Args,
+    each: Option<&str>,
+) -> Result<String, ParseError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(at) = rest.find('$') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + 1..];
+
+        if let Some(group) = rest.strip_prefix('(') {
+            let end = closing(group)
+                .filter(|end| group[end + 1..].starts_with('*'))
+                .context(error::InvalidRepetition { name })?;
+
+            ensure!(
+                each.is_none() && m.variadic().is_some(),
+                error::InvalidRepetition { name }
+            );
+
+            let inner = &group[..end];
+            for arg in args.rest {
+                out.push_str(&paste(name, m, inner, args, Some(arg))?);
+            }
+
+            // Check the group even when there's nothing to repeat it for.
+            if args.rest.is_empty() {
+                paste(name, m, inner, args, Some(""))?;
+            }
+
+            rest = &group[end + 2..];
+            continue;
+        }
+
+        let (param, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => return error::UndefinedArgument { name: braced }.fail(),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+
+        if param.is_empty() {
+            out.push('$');
+            continue;
+        }
+
+        let value = match args.fixed.get(param) {
+            Some(value) => value.to_string(),
+            None if m.variadic() == Some(param) => match each {
+                Some(each) => each.to_owned(),
+                None => args.rest.join(", "),
+            },
+            None => return error::UndefinedArgument { name: param }.fail(),
+        };
+
+        out.push_str(&value);
+        rest = after;
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The index of the `)` closing a group that starts just before `text`.
+fn closing(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (idx, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(idx),
+            ')' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use assert_matches::assert_matches;
+
+    fn declare(text: &str) -> Result<Macros, ParseError> {
+        let mut macros = Macros::default();
+        for pair in AsmParser::parse(Rule::program, text)? {
+            if pair.as_rule() == Rule::macro_defn {
+                macros.declare(pair)?;
+            }
+        }
+        Ok(macros)
+    }
+
+    #[test]
+    fn substitute_arguments() {
+        let macros =
+            declare("%macro handler(name, sel)\nhandler_$name: push4 $sel ${name}_end:\n%end")
+                .unwrap();
+
+        let (text, _) = macros
+            .substitute("handler", &["mint", "0x40c10f19"])
+            .unwrap();
+        assert_eq!(text, "\nhandler_mint: push4 0x40c10f19 mint_end:\n");
+    }
+
+    #[test]
+    fn substitute_variadic() {
+        let macros =
+            declare("%macro all(first, rest...)\n$first $(push1 $rest;)* %other($rest)\n%end")
+                .unwrap();
+
+        let (text, _) = macros.substitute("all", &["a", "1", "2"]).unwrap();
+        assert_eq!(text, "\na push1 1;push1 2; %other(1, 2)\n");
+
+        let (text, _) = macros.substitute("all", &["a"]).unwrap();
+        assert_eq!(text, "\na  %other()\n");
+
+        assert_matches!(
+            macros.substitute("all", &[]),
+            Err(ParseError::MissingArgument {
+                expected: 1,
+                got: 0,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn substitute_errors() {
+        let macros = declare("%macro two(a, b)\n$a $b\n%end").unwrap();
+
+        assert_matches!(
+            macros.substitute("two", &["1", "2", "3"]),
+            Err(ParseError::ExtraArgument { expected: 2, .. })
+        );
+        assert_matches!(
+            macros.substitute("three", &[]),
+            Err(ParseError::UndefinedMacro { name, .. }) if name == "three"
+        );
+
+        assert_matches!(
+            declare("%macro m(a)\n$b\n%end"),
+            Err(ParseError::UndefinedArgument { name, .. }) if name == "b"
+        );
+        assert_matches!(
+            declare("%macro m(a)\n$(push1 $a)*\n%end"),
+            Err(ParseError::InvalidRepetition { name, .. }) if name == "m"
+        );
+        assert_matches!(
+            declare("%macro m(a...)\n$(push1 $a\n%end"),
+            Err(ParseError::InvalidRepetition { .. })
+        );
+        assert_matches!(
+            declare("%macro m(a, a)\n%end"),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "a"
+        );
+        assert_matches!(
+            declare("%macro push(a)\n%end"),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "push"
+        );
+        assert_matches!(
+            declare("%macro assert()\n%end"),
+            Err(ParseError::DuplicateDefinition { .. })
+        );
+    }
+}
@@ -1,5 +1,9 @@
 mod args;
+mod emit;
 pub(crate) mod error;
+pub(crate) mod huff;
+mod locals;
+mod macros;
 mod parser {
     #![allow(clippy::upper_case_acronyms)]
 
@@ -11,117 +15,1319 @@ mod parser {
 }
 
 use crate::ast::Node;
-use crate::ops::{AbstractOp, Imm, Op, Specifier};
+use crate::ops::{AbstractOp, Experimental, Expression, Imm, Op, Operator, Specifier};
+use crate::visit::Fold;
 
+use pest::iterators::Pair;
 use pest::Parser;
 
-use self::args::{Label, Signature};
-use self::error::ParseError;
+use self::args::{Bytes, FromPair, Label, Signature};
+use self::error::{ParseError, Span};
+use self::locals::Locals;
+use self::macros::Macros;
 use self::parser::{AsmParser, Rule};
 
+use num_bigint::{BigInt, Sign};
+
 use sha3::{Digest, Keccak256};
 
-use snafu::OptionExt;
+use snafu::{ensure, OptionExt};
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-pub(crate) fn parse_asm(asm: &str) -> Result<Vec<Node>, ParseError> {
-    let mut program: Vec<Node> = Vec::new();
+/// How far macros and aliases may expand, so a runaway expansion fails
+/// instead of exhausting the stack or memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Limits {
+    /// Deepest allowed nesting of macro invocations.
+    pub(crate) depth: usize,
 
-    let pairs = AsmParser::parse(Rule::program, asm)?;
-    for pair in pairs {
+    /// Most instructions an expansion may produce.
+    pub(crate) size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            depth: 255,
+            size: 1 << 20,
+        }
+    }
+}
+
+/// Hands out one shared copy of each label name in a source file, so a label
+/// used many times is only allocated once.
+///
+/// Also names anonymous labels (like `1:`) after their number and how many
+/// anonymous labels with the same number came before them, so references
+/// (like `1b` and `1f`) can find the nearest one in either direction.
+#[derive(Debug, Default)]
+struct Labels<'a> {
+    names: HashMap<&'a str, Arc<str>>,
+
+    /// How many anonymous labels with each number have been declared so far.
+    anonymous: HashMap<&'a str, usize>,
+
+    /// Forward references to anonymous labels that haven't been declared
+    /// yet, with the index of the label each refers to.
+    forward: Vec<(Pair<'a, Rule>, usize)>,
+
+    /// Arrays defined with `%def`, by name.
+    arrays: HashMap<&'a str, Vec<Expression>>,
+
+    /// Strings defined with `%def`, by name.
+    strings: HashMap<&'a str, String>,
+}
+
+impl<'a> Labels<'a> {
+    fn get(&mut self, name: &'a str) -> Arc<str> {
+        self.names
+            .entry(name)
+            .or_insert_with(|| name.into())
+            .clone()
+    }
+
+    fn declare_anonymous(&mut self, number: &'a str) -> Arc<str> {
+        let count = self.anonymous.entry(number).or_insert(0);
+        *count += 1;
+        anonymous(number, *count - 1)
+    }
+
+    /// The label that `pair`, a `label` or `anonymous_label`, refers to.
+    fn target(&mut self, pair: Pair<'a, Rule>) -> Result<Arc<str>, ParseError> {
+        let text = pair.as_str();
+
+        let (number, direction) = match pair.as_rule() {
+            Rule::label => return Ok(self.get(text)),
+            Rule::anonymous_label => text.split_at(text.len() - 1),
+            _ => return error::ArgumentType.fail(),
+        };
+
+        let count = self.anonymous.get(number).copied().unwrap_or(0);
+
+        if direction == "f" {
+            self.forward.push((pair, count));
+            return Ok(anonymous(number, count));
+        }
+
+        match count.checked_sub(1) {
+            Some(index) => Ok(anonymous(number, index)),
+            None => unmatched(&pair),
+        }
+    }
+
+    /// Define the array or string in `pair`, a `def`.
+    fn define(&mut self, pair: Pair<'a, Rule>) -> Result<(), ParseError> {
+        let mut pairs = pair.into_inner().peekable();
+        let name = pairs.next().unwrap().as_str();
+
+        ensure!(
+            !self.arrays.contains_key(name) && !self.strings.contains_key(name),
+            error::DuplicateDefinition { name }
+        );
+
+        match pairs.peek().map(Pair::as_rule) {
+            Some(Rule::expression) | None => {
+                let elements = pairs
+                    .map(|p| parse_expression(p, self))
+                    .collect::<Result<_, _>>()?;
+
+                self.arrays.insert(name, elements);
+            }
+            Some(_) => {
+                let text = self.string(pairs.next().unwrap())?;
+                self.strings.insert(name, text);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The text of `pair`, a string literal, the name of a string defined
+    /// with `%def`, or a `concat(...)` or `substr(...)` of those.
+    fn string(&mut self, pair: Pair<'a, Rule>) -> Result<String, ParseError> {
         match pair.as_rule() {
-            Rule::inst_macro => {
+            Rule::string => String::from_pair(pair),
+            Rule::name => {
+                let name = pair.as_str();
+                let text = self
+                    .strings
+                    .get(name)
+                    .context(error::UndefinedConstant { name })?;
+                Ok(text.clone())
+            }
+            Rule::concat_builtin => {
+                let mut text = String::new();
+                for part in pair.into_inner() {
+                    text.push_str(&self.string(part)?);
+                }
+                Ok(text)
+            }
+            Rule::substr_builtin => {
                 let mut pairs = pair.into_inner();
-                let inst_macro = pairs.next().unwrap();
-                assert!(pairs.next().is_none());
-                let node = parse_inst_macro(inst_macro)?;
-                program.push(node);
+                let text = self.string(pairs.next().unwrap())?;
+                let start = self.constant(pairs.next().unwrap())?;
+                let len = self.constant(pairs.next().unwrap())?;
+
+                let part = start
+                    .checked_add(len)
+                    .and_then(|end| text.get(start..end))
+                    .context(error::StringRange {
+                        start,
+                        len,
+                        size: text.len(),
+                    })?;
+
+                Ok(part.to_owned())
+            }
+            r => unreachable!("{:?}", r),
+        }
+    }
+
+    /// The value of `pair`, an expression that can't refer to labels.
+    fn constant(&mut self, pair: Pair<'a, Rule>) -> Result<usize, ParseError> {
+        parse_expression(pair, self)?
+            .eval(&|_: &str| -> Option<BigInt> { None })
+            .ok()
+            .and_then(|v| usize::try_from(v).ok())
+            .context(error::ArgumentType)
+    }
+
+    /// The function signature that `pair`, a `selector`, hashes.
+    fn signature(&mut self, pair: Pair<'a, Rule>) -> Result<String, ParseError> {
+        let inner = pair.into_inner().next().unwrap();
+        if inner.as_rule() == Rule::function_declaration {
+            return Ok(inner.as_str().to_owned());
+        }
+
+        let text = self.string(inner)?;
+        let valid = AsmParser::parse(Rule::function_declaration, &text)
+            .map(|mut p| p.next().unwrap().as_str().len() == text.len())
+            .unwrap_or(false);
+
+        ensure!(valid, error::InvalidSelector { signature: text });
+        Ok(text)
+    }
+
+    /// The element of an array that `pair`, an `index`, refers to.
+    fn element(&mut self, pair: Pair<'a, Rule>) -> Result<Expression, ParseError> {
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap();
+        let index = pairs.next().unwrap();
+        let written = index.clone();
+
+        let value = parse_expression(index, self)?
+            .eval(&|_: &str| -> Option<BigInt> { None })
+            .ok();
+
+        let elements = match self.arrays.get(name.as_str()) {
+            Some(e) => e,
+            None => {
+                return error::UndefinedArray {
+                    name: name.as_str(),
+                    span: span(&name),
+                }
+                .fail()
+            }
+        };
+
+        let element = value
+            .and_then(|v| usize::try_from(v).ok())
+            .and_then(|i| elements.get(i));
+
+        match element {
+            Some(e) => Ok(e.clone()),
+            None => error::ArrayIndex {
+                name: name.as_str(),
+                index: written.as_str(),
+                len: elements.len(),
+                span: span(&written),
+            }
+            .fail(),
+        }
+    }
+
+    /// Make sure every forward reference to an anonymous label found one.
+    fn finish(&self) -> Result<(), ParseError> {
+        for (pair, index) in &self.forward {
+            let text = pair.as_str();
+            let number = &text[..text.len() - 1];
+
+            if self.anonymous.get(number).copied().unwrap_or(0) <= *index {
+                return unmatched(pair);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The name given to the anonymous label `number:` declared after `index`
+/// others with the same number, which can't be written in source.
+fn anonymous(number: &str, index: usize) -> Arc<str> {
+    format!("anon.{}.{}", number, index).into()
+}
+
+/// Whether `label` is the name of an anonymous label.
+pub(crate) fn is_anonymous(label: &str) -> bool {
+    match label.strip_prefix("anon.") {
+        Some(rest) => rest.starts_with(|c: char| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Instruction sequences declared with `%alias`, by name.
+#[derive(Debug, Default)]
+struct Aliases {
+    declared: HashMap<String, Vec<Node>>,
+
+    /// How many times an alias has been expanded, used to give the anonymous
+    /// labels in each expansion names of their own.
+    expansions: usize,
+
+    /// The modules of the standard library whose aliases have been declared.
+    modules: Vec<&'static str>,
+
+    /// Instructions that aren't part of any fork, but were asked for anyway.
+    experimental: Experimental,
+}
+
+impl Aliases {
+    /// Declare `name` as an alias for the instructions in `body`, which may
+    /// use aliases declared before it. `location` is where the alias is
+    /// declared.
+    fn declare(
+        &mut self,
+        name: &str,
+        body: &str,
+        location: Span,
+        limits: Limits,
+    ) -> Result<(), ParseError> {
+        ensure!(
+            name.parse::<Specifier>().is_err()
+                && !PSEUDO.contains(&name)
+                && !self.declared.contains_key(name),
+            error::DuplicateDefinition { name }
+        );
+
+        let mut labels = Labels::default();
+        let mut nodes = Vec::new();
+
+        // Errors in the body are reported at the declaration, since spans
+        // inside the string wouldn't match the file.
+        let expanded = Node::Expanded {
+            span: location,
+            from: Vec::new(),
+        };
+
+        for pair in AsmParser::parse(Rule::alias_body, body)? {
+            match pair.as_rule() {
+                Rule::alias_push => {
+                    let inst_macro = pair.into_inner().next().unwrap();
+                    let node = parse_inst_macro(inst_macro, &mut labels)?;
+                    push_located(&mut nodes, node, &expanded);
+                }
+                Rule::push => {
+                    let node = parse_push(pair, &mut labels)?;
+                    push_located(&mut nodes, node, &expanded);
+                }
+                Rule::pseudo => {
+                    for node in parse_pseudo(pair, &mut labels)? {
+                        push_located(&mut nodes, node, &expanded);
+                    }
+                }
+                Rule::anonymous_label_defn => {
+                    let number = pair.into_inner().next().unwrap();
+                    let label = labels.declare_anonymous(number.as_str());
+                    nodes.push(AbstractOp::Label(label).into());
+                }
+                Rule::op => nodes.push(parse_op(&pair).into()),
+                Rule::alias_ref => {
+                    let expansion = self.expand(pair.as_str(), location)?;
+                    nodes.extend(expansion);
+                }
+                _ => continue,
+            }
+
+            ensure!(
+                nodes.len() <= limits.size,
+                error::ExpansionLimit { limit: limits.size }
+            );
+        }
+
+        labels.finish()?;
+
+        self.declared.insert(name.to_owned(), nodes);
+        Ok(())
+    }
+
+    /// Declare the aliases in `module` of the standard library, unless they
+    /// already have been.
+    fn import(&mut self, module: &str, limits: Limits) -> Result<(), ParseError> {
+        let (name, source) = STD
+            .iter()
+            .find(|(n, _)| *n == module)
+            .context(error::UnknownModule { name: module })?;
+
+        if self.modules.contains(name) {
+            return Ok(());
+        }
+        self.modules.push(name);
+
+        for pair in AsmParser::parse(Rule::program, source)? {
+            if pair.as_rule() != Rule::inst_macro {
+                continue;
+            }
+
+            let location = span(&pair);
+            let inst_macro = pair.into_inner().next().unwrap();
+            let (alias, body) = <(Label, String)>::parse_arguments(inst_macro.into_inner())?;
+            self.declare(alias.0, &body, location, limits)?;
+        }
+
+        Ok(())
+    }
+
+    /// Names for the anonymous labels of one more expansion, for macros that
+    /// aren't aliases.
+    fn unique(&mut self) -> Unique {
+        self.expansions += 1;
+        Unique(self.expansions)
+    }
+
+    /// The error for `name`, used at `location`, which isn't an instruction or
+    /// an alias.
+    fn unknown(&self, name: &str, location: Span) -> Result<Vec<Node>, ParseError> {
+        ensure!(
+            !too_deep(name),
+            error::StackTooDeep {
+                name,
+                span: location,
+            }
+        );
+
+        if let Some(op) = Experimental::proposals().by_mnemonic(name) {
+            return error::ExperimentalDisabled {
+                name,
+                origin: &op.origin,
+                span: location,
+            }
+            .fail();
+        }
+
+        error::UnknownInstruction {
+            name,
+            span: location,
+        }
+        .fail()
+    }
+
+    /// The experimental instruction `name`, used at `location` with
+    /// `immediate`.
+    fn experimental(
+        &self,
+        name: &str,
+        immediate: Option<BigInt>,
+        location: Span,
+    ) -> Result<Vec<Node>, ParseError> {
+        let op = match self.experimental.by_mnemonic(name) {
+            Some(op) => op,
+            None => return self.unknown(name, location),
+        };
+
+        let spec = AbstractOp::new(op.specifier()).unwrap();
+        let mut nodes = vec![Node::Location(location), spec.into()];
+
+        match immediate {
+            None if op.immediate == 0 => (),
+            None => {
+                return error::MissingArgument {
+                    expected: 1usize,
+                    got: 0usize,
+                }
+                .fail()
+            }
+            Some(_) if op.immediate == 0 => {
+                return error::ExtraArgument { expected: 0usize }.fail()
+            }
+            Some(immediate) => {
+                let bytes = immediate.to_bytes_be().1;
+                let bytes = bytes.strip_prefix(&[0][..]).unwrap_or(&bytes);
+                ensure!(bytes.len() <= op.immediate, error::ImmediateTooLarge);
+
+                let mut padded = vec![0; op.immediate - bytes.len()];
+                padded.extend_from_slice(bytes);
+                nodes.push(Node::Raw(padded));
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// The instructions the alias `name`, used at `location`, expands to.
+    fn expand(&mut self, name: &str, location: Span) -> Result<Vec<Node>, ParseError> {
+        let nodes = match self.declared.get(name) {
+            Some(nodes) => nodes,
+            None => return self.experimental(name, None, location),
+        };
+
+        self.expansions += 1;
+        let mut unique = Unique(self.expansions);
+
+        let expansion = nodes
+            .iter()
+            .cloned()
+            .map(|node| unique.fold_node(node).expanded(name, location))
+            .collect();
+
+        Ok(expansion)
+    }
+}
+
+/// Whether `name` is a `dup` or `swap` past the deepest ones, like `dup17`.
+fn too_deep(name: &str) -> bool {
+    let number = match name.strip_prefix("dup") {
+        Some(number) => number,
+        None => match name.strip_prefix("swap") {
+            Some(number) => number,
+            None => return false,
+        },
+    };
+
+    !number.is_empty()
+        && number.bytes().all(|b| b.is_ascii_digit())
+        && number.parse::<u32>().map_or(true, |n| n > 16)
+}
+
+/// Gives the anonymous labels in one expansion of an alias names that no
+/// other expansion has, by adding the number of the expansion.
+struct Unique(usize);
+
+impl Unique {
+    fn get(&self, label: Arc<str>) -> Arc<str> {
+        match is_anonymous(&label) {
+            true => format!("{}.{}", label, self.0).into(),
+            false => label,
+        }
+    }
+
+    fn fold_node(&mut self, node: Node) -> Node {
+        match node {
+            Node::Op(op) => Node::Op(self.fold_abstract_op(op)),
+            mut node => {
+                if let Some(expression) = node.expression_mut() {
+                    expression.relabel(&|l| Some(self.get(l.clone())));
+                }
+                node
+            }
+        }
+    }
+}
+
+impl Fold for Unique {
+    fn fold_label(&mut self, label: Arc<str>) -> Arc<str> {
+        self.get(label)
+    }
+
+    fn fold_label_use(&mut self, label: Arc<str>) -> Arc<str> {
+        self.get(label)
+    }
+}
+
+fn unmatched<T>(pair: &Pair<Rule>) -> Result<T, ParseError> {
+    let text = pair.as_str();
+    let (number, direction) = text.split_at(text.len() - 1);
+
+    error::UnmatchedAnonymousLabel {
+        label: text,
+        number,
+        forward: direction == "f",
+        span: span(pair),
+    }
+    .fail()
+}
+
+/// Where `pair` is in its source.
+///
+/// pest counts the lines from the start of the source on every call, so code
+/// that runs for every statement should use [`Lines::span`] instead.
+fn span(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+
+    Span {
+        start: span.start_pos().line_col(),
+        end: span.end_pos().line_col(),
+    }
+}
+
+/// Where each line of a source starts, to find the line and column of an
+/// offset without counting the lines before it.
+struct Lines<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        Self { text, starts }
+    }
+
+    /// The line and column, counting from one like pest, of `offset`.
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+
+        let column = self.text[self.starts[line]..offset].chars().count();
+        (line + 1, column + 1)
+    }
+
+    /// Where `pair`, parsed from this source, is in it.
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        let span = pair.as_span();
+
+        Span {
+            start: self.line_col(span.start()),
+            end: self.line_col(span.end()),
+        }
+    }
+}
+
+/// What a file has declared so far, which the macros it uses can see too.
+#[derive(Debug)]
+struct Context {
+    aliases: Aliases,
+    macros: Macros,
+    limits: Limits,
+
+    /// Whether every instruction is preceded by its location, instead of only
+    /// the ones that can fail to assemble.
+    locate: bool,
+
+    /// The function signatures hashed by `selector(...)` so far, and where.
+    signatures: Vec<(String, Span)>,
+}
+
+/// A parsed source: its nodes, and what it says about itself for the linter.
+#[derive(Debug, Clone)]
+pub(crate) struct Parsed {
+    pub(crate) nodes: Vec<Node>,
+
+    /// Each function signature hashed by `selector(...)`, and the push it's
+    /// in, to be checked for collisions with the others in the build.
+    pub(crate) signatures: Vec<(String, Span)>,
+}
+
+#[cfg(any(test, feature = "fuzzing"))]
+pub(crate) fn parse_asm(
+    asm: &str,
+    limits: Limits,
+    experimental: &Experimental,
+) -> Result<Vec<Node>, ParseError> {
+    parse(asm, limits, experimental, false).map(|p| p.nodes)
+}
+
+/// Like [`parse_asm`], but every instruction is preceded by its location, so
+/// warnings about it can say where it is.
+pub(crate) fn parse_located(
+    asm: &str,
+    limits: Limits,
+    experimental: &Experimental,
+) -> Result<Parsed, ParseError> {
+    parse(asm, limits, experimental, true)
+}
+
+fn parse(
+    asm: &str,
+    limits: Limits,
+    experimental: &Experimental,
+    locate: bool,
+) -> Result<Parsed, ParseError> {
+    let mut context = Context {
+        aliases: Aliases {
+            experimental: experimental.clone(),
+            ..Default::default()
+        },
+        macros: Macros::default(),
+        limits,
+        locate,
+        signatures: Vec::new(),
+    };
+
+    let nodes = parse_program(asm, Labels::default(), &mut context)?;
+
+    Ok(Parsed {
+        nodes,
+        signatures: context.signatures,
+    })
+}
+
+fn parse_program<'a>(
+    asm: &'a str,
+    mut labels: Labels<'a>,
+    context: &mut Context,
+) -> Result<Vec<Node>, ParseError> {
+    let mut program: Vec<Node> = Vec::new();
+    let mut locals = Locals::default();
+
+    // Without experimental instructions that take immediates, a typo like
+    // `pusj1 1` is a syntax error, instead of an unknown instruction.
+    let rule = match context
+        .aliases
+        .experimental
+        .iter()
+        .any(|op| op.immediate > 0)
+    {
+        true => Rule::custom_program,
+        false => Rule::program,
+    };
+
+    let lines = Lines::new(asm);
+
+    let pairs = AsmParser::parse(rule, asm)?;
+    for pair in pairs {
+        let start = program.len();
+        let statement = lines.span(&pair);
+
+        let result = parse_statement(
+            pair,
+            &lines,
+            &mut program,
+            &mut labels,
+            &mut locals,
+            context,
+        );
+
+        if let Err(err) = result {
+            // Most errors don't know where they are, so point them at the
+            // whole statement.
+            return match context.locate {
+                true => Err(err.located(statement)),
+                false => Err(err),
+            };
+        }
+
+        if context.locate {
+            locate(&mut program, start, statement);
+        }
+
+        locals.step(&program[start..]);
+    }
+
+    labels.finish()?;
+
+    Ok(program)
+}
+
+/// Parse `pair`, a single statement from the source indexed by `lines`, onto
+/// the end of `program`.
+fn parse_statement<'a>(
+    pair: Pair<'a, Rule>,
+    lines: &Lines,
+    program: &mut Vec<Node>,
+    labels: &mut Labels<'a>,
+    locals: &mut Locals,
+    context: &mut Context,
+) -> Result<(), ParseError> {
+    let limits = context.limits;
+
+    match pair.as_rule() {
+        Rule::macro_defn => {
+            context.macros.declare(pair)?;
+            return Ok(());
+        }
+        Rule::inst_macro => {
+            let location = lines.span(&pair);
+            let mut pairs = pair.into_inner();
+            let inst_macro = pairs.next().unwrap();
+            assert!(pairs.next().is_none());
+
+            if inst_macro.as_rule() == Rule::alias {
+                let (name, body) = <(Label, String)>::parse_arguments(inst_macro.into_inner())?;
+                context.aliases.declare(name.0, &body, location, limits)?;
+                return Ok(());
+            }
+
+            if inst_macro.as_rule() == Rule::import {
+                if let Some(module) = std_module(inst_macro.clone())? {
+                    context.aliases.import(&module, limits)?;
+                    return Ok(());
+                }
+            }
+
+            if inst_macro.as_rule() == Rule::def {
+                labels.define(inst_macro)?;
+                return Ok(());
+            }
+
+            if inst_macro.as_rule() == Rule::locals {
+                locals.declare(<Vec<Label>>::parse_arguments(inst_macro.into_inner())?)?;
+                return Ok(());
+            }
+
+            match inst_macro.as_rule() {
+                Rule::minimal_proxy => {
+                    program.push(Node::Location(location));
+                    program.extend(parse_minimal_proxy(inst_macro, labels)?);
+                }
+                Rule::create_macro | Rule::create2_macro => {
+                    for node in parse_create(inst_macro, labels)? {
+                        push_node(program, node, location);
+                    }
+                }
+                Rule::sstore2_write | Rule::sstore2_read => {
+                    for node in parse_sstore2(inst_macro, labels)? {
+                        push_node(program, node, location);
+                    }
+                }
+                Rule::emit => {
+                    for node in emit::parse_emit(inst_macro, labels, locals)? {
+                        push_node(program, node, location);
+                    }
+                }
+                Rule::calldata_word | Rule::calldata_addr => {
+                    for node in parse_calldata(inst_macro)? {
+                        push_node(program, node, location);
+                    }
+                }
+                Rule::macro_call => {
+                    program.extend(expand_macro(inst_macro, location, labels, context)?);
+                }
+                _ => {
+                    let node = parse_inst_macro(inst_macro, labels)?;
+                    push_node(program, node, location);
+                }
+            }
+        }
+        Rule::label_defn => {
+            let mut pair = pair.into_inner();
+            let label = pair.next().unwrap();
+            program.push(Node::Location(lines.span(&label)));
+            program.push(AbstractOp::Label(labels.get(label.as_str())).into());
+        }
+        Rule::anonymous_label_defn => {
+            let mut pair = pair.into_inner();
+            let number = pair.next().unwrap();
+            program.push(Node::Location(lines.span(&number)));
+            program.push(AbstractOp::Label(labels.declare_anonymous(number.as_str())).into());
+        }
+        Rule::push => {
+            let location = lines.span(&pair);
+            if let Some(signature) = signature(&pair, labels)? {
+                context.signatures.push((signature, location));
+            }
+            let node = parse_push(pair, labels)?;
+            push_node(program, node, location);
+        }
+        Rule::pseudo => {
+            let location = lines.span(&pair);
+            for node in parse_pseudo(pair, labels)? {
+                push_node(program, node, location);
+            }
+        }
+        Rule::local_op => program.push(locals.resolve(pair)?.into()),
+        Rule::op => program.push(parse_op(&pair).into()),
+        Rule::alias_use => {
+            let name = pair.as_str();
+            let location = lines.span(&pair);
+            program.extend(context.aliases.expand(name, location)?);
+        }
+        Rule::custom_use => {
+            let location = lines.span(&pair);
+            let mut pairs = pair.into_inner();
+            let name = pairs.next().unwrap().as_str();
+            let immediate = match parse_operand(pairs.next().unwrap(), labels)? {
+                Expression::Number(immediate) => immediate,
+                _ => unreachable!("grammar only allows numbers"),
+            };
+
+            let nodes = context
+                .aliases
+                .experimental(name, Some(immediate), location)?;
+            program.extend(nodes);
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// The nodes that `pair`, a `macro_call` at `location`, expands to.
+///
+/// Named labels declared in the macro keep the names they're given, so a
+/// macro can declare labels for the rest of the file to use, while anonymous
+/// labels get names unique to the expansion, like in an alias.
+fn expand_macro(
+    pair: Pair<Rule>,
+    location: Span,
+    labels: &Labels,
+    context: &mut Context,
+) -> Result<Vec<Node>, ParseError> {
+    let mut pairs = pair.into_inner();
+    let name = pairs.next().unwrap().as_str();
+    let args: Vec<_> = pairs.map(|p| p.as_str().trim()).collect();
+
+    let (text, declared) = context.macros.substitute(name, &args)?;
+
+    let inner = Labels {
+        arrays: labels.arrays.clone(),
+        strings: labels.strings.clone(),
+        ..Default::default()
+    };
+
+    let signatures = context.signatures.len();
+
+    context.macros.enter(name, context.limits)?;
+    let result = parse_program(&text, inner, context);
+    context.macros.leave();
+
+    // Like errors, signatures hashed in the expansion are reported where the
+    // macro was used.
+    for (_, span) in &mut context.signatures[signatures..] {
+        *span = location;
+    }
+
+    let nodes = result.map_err(|e| {
+        error::MacroExpansion {
+            name,
+            span: location,
+            error: Box::new(e),
+        }
+        .build()
+    })?;
+
+    ensure!(
+        nodes.len() <= context.limits.size,
+        error::ExpansionLimit {
+            limit: context.limits.size
+        }
+    );
+
+    // Spans in the expansion are in the pasted text, not the file, so
+    // everything in it is reported at the declaration instead.
+    let mut unique = context.aliases.unique();
+    let expansion = nodes
+        .into_iter()
+        .map(|node| match unique.fold_node(node) {
+            Node::Location(_) => Node::Expanded {
+                span: declared,
+                from: vec![(name.to_owned(), location)],
+            },
+            Node::Expanded { span, from } => Node::Expanded {
+                span,
+                from: from
+                    .into_iter()
+                    .map(|(n, _)| (n, declared))
+                    .chain(std::iter::once((name.to_owned(), location)))
+                    .collect(),
+            },
+            node => node,
+        })
+        .collect();
+
+    Ok(expansion)
+}
+
+/// Precede each instruction in `program[start..]` that doesn't already have a
+/// location with `location`.
+fn locate(program: &mut Vec<Node>, start: usize, location: Span) {
+    let nodes = program.split_off(start);
+    let mut located = false;
+
+    for node in nodes {
+        if !located && matches!(node, Node::Op(_)) {
+            program.push(Node::Location(location));
+        }
+
+        located = matches!(node, Node::Location(_) | Node::Expanded { .. });
+        program.push(node);
+    }
+}
+
+/// Push `node` onto `program`, preceded by `location` if it refers to a label,
+/// has an expression, or brings in another file.
+fn push_node(program: &mut Vec<Node>, node: Node, location: Span) {
+    push_located(program, node, &Node::Location(location));
+}
+
+/// Push `node` onto `program`, preceded by a copy of `location` (a
+/// [`Node::Location`] or [`Node::Expanded`]) if `node` needs one.
+fn push_located(program: &mut Vec<Node>, node: Node, location: &Node) {
+    let located = match node {
+        Node::Op(ref op) => op.shared_label().is_some(),
+        Node::ComputedPush { .. }
+        | Node::PushExpression(_)
+        | Node::Assert { .. }
+        | Node::Import { .. }
+        | Node::Include(_)
+        | Node::IncludeHex(_)
+        | Node::IncludeArtifact { .. }
+        | Node::Script(_) => true,
+        _ => false,
+    };
+
+    if located {
+        program.push(location.clone());
+    }
+
+    program.push(node);
+}
+
+fn parse_op(pair: &Pair<Rule>) -> AbstractOp {
+    let spec: Specifier = pair.as_str().parse().unwrap();
+    AbstractOp::Op(Op::new(spec).unwrap())
+}
+
+/// The function signature a push hashes with `selector(...)`, if it does.
+fn signature<'a>(
+    pair: &Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Option<String>, ParseError> {
+    match pair.clone().into_inner().nth(1) {
+        Some(operand) if operand.as_rule() == Rule::selector => labels.signature(operand).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn parse_push<'a>(
+    pair: pest::iterators::Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Node, ParseError> {
+    let mut pair = pair.into_inner();
+    let size = pair.next().unwrap();
+    let size: usize = size.as_str().parse().unwrap();
+    let operand = pair.next().unwrap();
+
+    let spec = Specifier::push(size as u32).unwrap();
+
+    // An expression of a single operand is pushed as that operand, so only
+    // expressions with operators go through the general case.
+    let operand = match operand.as_rule() {
+        Rule::push_expression => {
+            let expression = operand.into_inner().next().unwrap();
+            let mut operands = expression.clone().into_inner();
+
+            match (operands.next(), operands.next()) {
+                (Some(single), None) if single.as_rule() != Rule::expression => single,
+                _ => {
+                    let expression = parse_expression(expression, labels)?;
+                    return push_value(spec, expression);
+                }
             }
-            Rule::label_defn => {
-                let mut pair = pair.into_inner();
-                let label = pair.next().unwrap();
-                let txt = label.as_str();
-                program.push(AbstractOp::Label(txt.into()).into());
+        }
+        _ => operand,
+    };
+
+    let op = match operand.as_rule() {
+        Rule::len_builtin | Rule::keccak_builtin | Rule::pc_of_builtin => {
+            return Ok(Node::ComputedPush {
+                spec,
+                expression: parse_operand(operand, labels)?,
+            });
+        }
+        Rule::index => return push_value(spec, labels.element(operand)?),
+        Rule::binary => {
+            let raw = operand.as_str();
+            let mut buf = [0u8; 32];
+            let imm = radix_str_to_slice(&raw[2..], 2, size, &mut buf)?;
+            AbstractOp::with_immediate(spec, imm)
+                .ok()
+                .context(error::ImmediateTooLarge)?
+        }
+        Rule::octal => {
+            let raw = operand.as_str();
+            let mut buf = [0u8; 32];
+            let imm = radix_str_to_slice(&raw[2..], 8, size, &mut buf)?;
+            AbstractOp::with_immediate(spec, imm)
+                .ok()
+                .context(error::ImmediateTooLarge)?
+        }
+        Rule::decimal => {
+            let raw = operand.as_str();
+            let mut buf = [0u8; 32];
+            let imm = radix_str_to_slice(raw, 10, size, &mut buf)?;
+            AbstractOp::with_immediate(spec, imm)
+                .ok()
+                .context(error::ImmediateTooLarge)?
+        }
+        Rule::hex => {
+            let digits = &operand.as_str()[2..];
+            ensure!(digits.len() <= 64, error::ImmediateTooLarge);
+
+            // Pad with zeros, which also takes care of an odd number of digits.
+            let mut padded = [b'0'; 64];
+            padded[64 - digits.len()..].copy_from_slice(digits.as_bytes());
+
+            let mut buf = [0u8; 32];
+            hex::decode_to_slice(&padded[..], &mut buf).expect("grammar only allows hex digits");
+
+            let imm = &buf[(64 - digits.len()) / 2..];
+            AbstractOp::with_immediate(spec, imm)
+                .ok()
+                .context(error::ImmediateTooLarge)?
+        }
+        Rule::selector => {
+            let raw = labels.signature(operand)?;
+            let mut hasher = Keccak256::new();
+            hasher.update(raw.as_bytes());
+            AbstractOp::with_immediate(spec, &hasher.finalize()[0..(spec.size() - 1) as usize])
+                .ok()
+                .context(error::ImmediateTooLarge)?
+        }
+        Rule::label | Rule::anonymous_label => {
+            AbstractOp::with_label(spec, labels.target(operand)?)
+        }
+        r => unreachable!("{:?}", r),
+    };
+
+    Ok(op.into())
+}
+
+/// A push of `expression` by `spec`, with the value filled in now if it's a
+/// constant, or once the labels it uses have addresses if not.
+fn push_value(spec: Specifier, expression: Expression) -> Result<Node, ParseError> {
+    let value = match expression.eval(&|_: &str| -> Option<BigInt> { None }) {
+        Ok(v) if v.sign() != Sign::Minus => v.to_bytes_be().1,
+        _ => return Ok(Node::ComputedPush { spec, expression }),
+    };
+
+    let size = spec.size() as usize - 1;
+    ensure!(value.len() <= size, error::ImmediateTooLarge);
+
+    let mut imm = vec![0u8; size - value.len()];
+    imm.extend(value);
+    Ok(AbstractOp::with_immediate(spec, &imm).unwrap().into())
+}
+
+/// The modules of the standard library, by name, which `%import(std, "...")`
+/// declares the aliases of.
+const STD: [(&str, &str); 3] = [
+    ("call", include_str!("../../std/call.etk")),
+    ("math", include_str!("../../std/math.etk")),
+    ("memory", include_str!("../../std/memory.etk")),
+];
+
+/// The module of the standard library an `import` names, or `None` if it
+/// brings in a file instead.
+fn std_module(pair: Pair<Rule>) -> Result<Option<String>, ParseError> {
+    let mut pairs = pair.into_inner();
+    let args = pairs.next().unwrap().into_inner();
+
+    if args.peek().map(|p| p.as_rule()) != Some(Rule::label) {
+        return Ok(None);
+    }
+
+    let (Label(library), module) = <(Label, String)>::parse_arguments(args)?;
+    ensure!(
+        library == "std" && pairs.next().is_none(),
+        error::ArgumentType
+    );
+
+    Ok(Some(module))
+}
+
+/// The names of the pseudo-instructions, which expand to a short sequence of
+/// real instructions.
+const PSEUDO: [&str; 3] = ["revert0", "ret0", "mstore_imm"];
+
+/// Expand the pseudo-instruction in `pair` into the instructions it stands for.
+fn parse_pseudo<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Vec<Node>, ParseError> {
+    let mut pairs = pair.into_inner();
+    let name = pairs.next().unwrap();
+
+    let nodes = match name.as_str() {
+        "revert0" | "ret0" => {
+            <()>::parse_arguments(pairs)?;
+
+            let exit = match name.as_str() {
+                "revert0" => Op::Revert,
+                _ => Op::Return,
+            };
+
+            vec![
+                Op::Push1(Imm::from([0])).into(),
+                Op::Dup1.into(),
+                exit.into(),
+            ]
+        }
+        "mstore_imm" => {
+            let (offset, value) = <(Pair<Rule>, Pair<Rule>)>::parse_arguments(pairs)?;
+            let value = parse_narrow_push(value, labels)?;
+            let offset = parse_narrow_push(offset, labels)?;
+
+            vec![value.into(), offset.into(), Op::MStore.into()]
+        }
+        r => unreachable!("{:?}", r),
+    };
+
+    Ok(nodes)
+}
+
+/// The narrowest push of the number or label in `pair`.
+fn parse_narrow_push<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<AbstractOp, ParseError> {
+    let bytes = match parse_operand(pair, labels)? {
+        Expression::Number(number) => number.to_bytes_be().1,
+        Expression::Label(label) => return Ok(AbstractOp::Push(Imm::from(label))),
+        e => unreachable!("{:?}", e),
+    };
+
+    let spec = Specifier::push(bytes.len() as u32).context(error::ImmediateTooLarge)?;
+    Ok(AbstractOp::with_immediate(spec, &bytes).unwrap())
+}
+
+/// The push for `%push(expression)`.
+///
+/// A lone label is sized once its value is known, and a constant gets the
+/// narrowest push that fits. Anything else can only be evaluated after
+/// labels are resolved, so it's sized and filled in by the assembler.
+fn parse_push_expression(expression: Expression) -> Result<Node, ParseError> {
+    let value = match expression {
+        Expression::Label(label) => return Ok(AbstractOp::Push(Imm::from(label)).into()),
+        ref e => e.eval(&|_: &str| -> Option<BigInt> { None }).ok(),
+    };
+
+    if let Some(value) = value {
+        if value.sign() != Sign::Minus {
+            let bytes = value.to_bytes_be().1;
+            if let Some(spec) = Specifier::push(bytes.len() as u32) {
+                return Ok(AbstractOp::with_immediate(spec, &bytes).unwrap().into());
             }
-            Rule::push => {
-                program.push(parse_push(pair)?.into());
+        }
+    }
+
+    // Values that don't fit, or are negative, are reported once the push is
+    // filled in.
+    Ok(Node::PushExpression(expression))
+}
+
+fn parse_expression<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Expression, ParseError> {
+    // Apply `op` to the last two operands.
+    fn reduce(operands: &mut Vec<Expression>, op: Operator) {
+        let rhs = operands.pop().unwrap();
+        let lhs = operands.pop().unwrap();
+        operands.push(Expression::binary(op, lhs, rhs));
+    }
+
+    let mut operands: Vec<Expression> = Vec::new();
+    let mut operators: Vec<Operator> = Vec::new();
+
+    for inner in pair.into_inner() {
+        let op = match inner.as_rule() {
+            Rule::logical_or => Operator::Or,
+            Rule::logical_and => Operator::And,
+            Rule::equal => Operator::Equal,
+            Rule::not_equal => Operator::NotEqual,
+            Rule::less_equal => Operator::LessEqual,
+            Rule::greater_equal => Operator::GreaterEqual,
+            Rule::less => Operator::Less,
+            Rule::greater => Operator::Greater,
+            Rule::plus => Operator::Plus,
+            Rule::minus => Operator::Minus,
+            Rule::times => Operator::Times,
+            Rule::divide => Operator::Divide,
+            Rule::modulo => Operator::Modulo,
+            _ => {
+                operands.push(parse_operand(inner, labels)?);
+                continue;
             }
-            Rule::op => {
-                let spec: Specifier = pair.as_str().parse().unwrap();
-                let op = Op::new(spec).unwrap();
-                let aop = AbstractOp::Op(op);
-                program.push(aop.into());
+        };
+
+        while let Some(top) = operators.last().copied() {
+            if top.precedence() < op.precedence() {
+                break;
             }
-            _ => continue,
+
+            operators.pop();
+            reduce(&mut operands, top);
         }
+
+        operators.push(op);
     }
 
-    Ok(program)
+    while let Some(op) = operators.pop() {
+        reduce(&mut operands, op);
+    }
+
+    Ok(operands.pop().unwrap())
 }
 
-fn parse_push(pair: pest::iterators::Pair<Rule>) -> Result<AbstractOp, ParseError> {
-    let mut pair = pair.into_inner();
-    let size = pair.next().unwrap();
-    let size: usize = size.as_str().parse().unwrap();
-    let operand = pair.next().unwrap();
+fn parse_operand<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Expression, ParseError> {
+    let txt = pair.as_str();
 
-    let spec = Specifier::push(size as u32).unwrap();
+    let (digits, radix) = match pair.as_rule() {
+        Rule::expression => return parse_expression(pair, labels),
+        Rule::len_builtin | Rule::keccak_builtin => {
+            let rule = pair.as_rule();
+            let mut pairs = pair.into_inner();
+            let start = pairs.next().unwrap();
 
-    let op = match operand.as_rule() {
-        Rule::binary => {
-            let raw = operand.as_str();
-            let imm = radix_str_to_vec(&raw[2..], 2, size)?;
-            AbstractOp::with_immediate(spec, imm.as_ref())
-                .ok()
-                .context(error::ImmediateTooLarge)?
-        }
-        Rule::octal => {
-            let raw = operand.as_str();
-            let imm = radix_str_to_vec(&raw[2..], 8, size)?;
-            AbstractOp::with_immediate(spec, imm.as_ref())
-                .ok()
-                .context(error::ImmediateTooLarge)?
-        }
-        Rule::decimal => {
-            let raw = operand.as_str();
-            let imm = radix_str_to_vec(raw, 10, size)?;
-            AbstractOp::with_immediate(spec, imm.as_ref())
-                .ok()
-                .context(error::ImmediateTooLarge)?
-        }
-        Rule::hex => {
-            let raw = operand.as_str();
-            let imm = hex::decode(&raw[2..]).unwrap();
-            AbstractOp::with_immediate(spec, imm.as_ref())
-                .ok()
-                .context(error::ImmediateTooLarge)?
+            if start.as_rule() != Rule::expression {
+                let text = labels.string(start)?;
+                return Ok(Expression::Number(text.len().into()));
+            }
+
+            let start = parse_expression(start, labels)?;
+            let end = parse_expression(pairs.next().unwrap(), labels)?;
+
+            return Ok(match rule {
+                Rule::len_builtin => Expression::binary(Operator::Minus, end, start),
+                _ => Expression::Keccak(Box::new(start), Box::new(end)),
+            });
         }
-        Rule::selector => {
-            let raw = operand.into_inner().next().unwrap().as_str();
-            let mut hasher = Keccak256::new();
-            hasher.update(raw.as_bytes());
-            AbstractOp::with_immediate(spec, &hasher.finalize()[0..(spec.size() - 1) as usize])
-                .ok()
-                .context(error::ImmediateTooLarge)?
+        Rule::label | Rule::anonymous_label => {
+            return Ok(Expression::Label(labels.target(pair)?));
         }
-        Rule::label => {
-            let label = operand.as_str().to_string();
-            AbstractOp::with_label(spec, label)
+        Rule::pc_of_builtin => {
+            let label = pair.into_inner().next().unwrap();
+            return Ok(Expression::Label(labels.target(label)?));
         }
-        r => unreachable!(format!("{:?}", r)),
+        Rule::index => return labels.element(pair),
+        Rule::binary => (&txt[2..], 2),
+        Rule::octal => (&txt[2..], 8),
+        Rule::decimal => (txt, 10),
+        Rule::hex => (&txt[2..], 16),
+        r => unreachable!("{:?}", r),
     };
 
-    Ok(op)
+    let number = BigInt::parse_bytes(digits.as_bytes(), radix).expect("grammar only allows digits");
+    Ok(Expression::Number(number))
 }
 
-fn parse_inst_macro(pair: pest::iterators::Pair<Rule>) -> Result<Node, ParseError> {
+fn parse_inst_macro<'a>(
+    pair: pest::iterators::Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Node, ParseError> {
     let rule = pair.as_rule();
 
     let node = match rule {
         Rule::import => {
-            let args = <(PathBuf,)>::parse_arguments(pair.into_inner())?;
-            Node::Import(args.0)
+            let mut pairs = pair.into_inner();
+            let args = <(PathBuf,)>::parse_arguments(pairs.next().unwrap().into_inner())?;
+            let alias = pairs.next().map(|p| p.as_str().to_owned());
+            Node::Import {
+                path: args.0,
+                alias,
+            }
         }
 
         Rule::include => {
@@ -134,11 +1340,80 @@ fn parse_inst_macro(pair: pest::iterators::Pair<Rule>) -> Result<Node, ParseErro
             Node::IncludeHex(args.0)
         }
 
+        Rule::include_artifact => {
+            let (path, code) = <(PathBuf, Option<Label>)>::parse_arguments(pair.into_inner())?;
+            let deployed = match code {
+                None => false,
+                Some(Label("deployed")) => true,
+                Some(_) => return error::ArgumentType.fail(),
+            };
+            Node::IncludeArtifact { path, deployed }
+        }
+
+        Rule::script => {
+            let args = <(PathBuf,)>::parse_arguments(pair.into_inner())?;
+            Node::Script(args.0)
+        }
+
         Rule::push_macro => {
-            // TODO: This should accept labels or literals, not just labels.
-            let args = <(Label,)>::parse_arguments(pair.into_inner())?;
-            let arg = Imm::from(args.0 .0);
-            Node::Op(AbstractOp::Push(arg))
+            let mut pairs = pair.into_inner();
+            match pairs.peek().map(|p| p.as_rule()) {
+                Some(Rule::expression) => {
+                    let expression = parse_expression(pairs.next().unwrap(), labels)?;
+                    parse_push_expression(expression)?
+                }
+                _ => {
+                    // Only reached when there isn't exactly one argument.
+                    let args = <(Pair<Rule>,)>::parse_arguments(pairs)?;
+                    let arg = Imm::from(labels.target(args.0)?);
+                    Node::Op(AbstractOp::Push(arg))
+                }
+            }
+        }
+
+        Rule::buildinfo => {
+            <()>::parse_arguments(pair.into_inner())?;
+            Node::BuildInfo
+        }
+
+        Rule::raw => {
+            let args = <(Bytes,)>::parse_arguments(pair.into_inner())?;
+            Node::Raw(args.0 .0)
+        }
+
+        Rule::pragma => match <(Label,)>::parse_arguments(pair.into_inner())?.0 {
+            Label("once") => Node::PragmaOnce,
+            Label("solidity_memory") => Node::PragmaSolidityMemory,
+            Label(name) => return error::UnknownPragma { name }.fail(),
+        },
+
+        Rule::export => {
+            let args = <Vec<Label>>::parse_arguments(pair.into_inner())?;
+            Node::Export(args.into_iter().map(|l| l.0.to_owned()).collect())
+        }
+
+        Rule::org => {
+            let (offset, fill) = <(u32, Option<u8>)>::parse_arguments(pair.into_inner())?;
+            Node::Org {
+                offset,
+                fill: fill.unwrap_or(0),
+            }
+        }
+
+        Rule::assert => {
+            let mut pairs = pair.into_inner();
+            let expression = pairs.next().unwrap();
+            let text = expression.as_str();
+
+            let message = match pairs.next() {
+                Some(m) => String::from_pair(m)?,
+                None => format!("`{}`", text),
+            };
+
+            Node::Assert {
+                expression: parse_expression(expression, labels)?,
+                message,
+            }
         }
 
         _ => unreachable!(),
@@ -146,7 +1421,246 @@ fn parse_inst_macro(pair: pest::iterators::Pair<Rule>) -> Result<Node, ParseErro
     Ok(node)
 }
 
-fn radix_str_to_vec(s: &str, radix: u32, min: usize) -> Result<Vec<u8>, ParseError> {
+/// The code that deploys an EIP-1167 minimal proxy, which comes before it.
+const MINIMAL_PROXY_DEPLOY: [u8; 10] = [0x3d, 0x60, 0x2d, 0x80, 0x60, 0x0a, 0x3d, 0x39, 0x81, 0xf3];
+
+/// The start of an EIP-1167 minimal proxy, up to the address of its
+/// implementation.
+const MINIMAL_PROXY_HEAD: [u8; 10] = [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+
+/// The rest of an EIP-1167 minimal proxy, after the address of its
+/// implementation.
+const MINIMAL_PROXY_TAIL: [u8; 15] = [
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+];
+
+/// Expand `%minimal_proxy(...)` into the code that deploys a minimal proxy.
+///
+/// The argument is either the address of the implementation, or a label to
+/// declare at a placeholder of zeros where the address can be written later.
+fn parse_minimal_proxy<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Vec<Node>, ParseError> {
+    let (arg,) = <(Pair<Rule>,)>::parse_arguments(pair.into_inner())?;
+
+    let mut head = MINIMAL_PROXY_DEPLOY.to_vec();
+    head.extend_from_slice(&MINIMAL_PROXY_HEAD);
+    let mut nodes = vec![Node::Raw(head)];
+
+    match arg.as_rule() {
+        Rule::hex => {
+            let digits = &arg.as_str()[2..];
+            ensure!(digits.len() <= 40, error::ImmediateTooLarge);
+
+            // Pad with zeros, which also takes care of an odd number of digits.
+            let mut padded = [b'0'; 40];
+            padded[40 - digits.len()..].copy_from_slice(digits.as_bytes());
+
+            let mut address = vec![0u8; 20];
+            hex::decode_to_slice(&padded[..], &mut address)
+                .expect("grammar only allows hex digits");
+            nodes.push(Node::Raw(address));
+        }
+        Rule::label => {
+            nodes.push(AbstractOp::Label(labels.get(arg.as_str())).into());
+            nodes.push(Node::Raw(vec![0; 20]));
+        }
+        _ => return error::ArgumentType.fail(),
+    }
+
+    nodes.push(Node::Raw(MINIMAL_PROXY_TAIL.to_vec()));
+    Ok(nodes)
+}
+
+/// Expand `%create(start, end)` or `%create2(start, end, salt)` into code that
+/// copies the code between the labels `start` and `end` into memory at zero,
+/// and deploys it as init code, leaving the new address on the stack.
+fn parse_create<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Vec<Node>, ParseError> {
+    let rule = pair.as_rule();
+
+    let (start, end, salt) = match rule {
+        Rule::create_macro => {
+            let (start, end) = <(Label, Label)>::parse_arguments(pair.into_inner())?;
+            (start, end, None)
+        }
+        _ => {
+            let (start, end, salt) =
+                <(Label, Label, Pair<Rule>)>::parse_arguments(pair.into_inner())?;
+            (start, end, Some(salt))
+        }
+    };
+
+    let start = labels.get(start.0);
+    let end = labels.get(end.0);
+
+    let mut nodes = Vec::new();
+
+    if let Some(salt) = salt {
+        nodes.push(parse_narrow_push(salt, labels)?.into());
+    }
+
+    let zero = AbstractOp::Op(Op::Push1(Imm::from([0])));
+    let create = match rule {
+        Rule::create_macro => Op::Create,
+        _ => Op::Create2,
+    };
+
+    nodes.extend(vec![
+        Node::ComputedPush {
+            spec: Specifier::Push2(()),
+            expression: Expression::binary(
+                Operator::Minus,
+                Expression::Label(end),
+                Expression::Label(start.clone()),
+            ),
+        },
+        Op::Dup1.into(),
+        AbstractOp::Push(Imm::from(start)).into(),
+        zero.clone().into(),
+        Op::CodeCopy.into(),
+        zero.into(),
+        Op::Dup1.into(),
+        create.into(),
+    ]);
+
+    Ok(nodes)
+}
+
+/// The most data one SSTORE2 data contract can hold: the largest contract
+/// allowed by EIP-170, less the `stop` the data comes after.
+const SSTORE2_MAX_DATA: u32 = 24_575;
+
+/// The init code of an SSTORE2 data contract, up to the data, left-aligned in
+/// a word. The size of the contract to return goes in the zeros after `0x61`.
+///
+/// The init code copies everything after itself, a `stop` and then the data,
+/// and returns it as the new contract's code.
+const SSTORE2_HEADER: [u8; 32] = [
+    0x61, 0x00, 0x00, 0x80, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Expand `%sstore2_write(start, end)` or `%sstore2_read(dest)`.
+///
+/// `%sstore2_write` deploys the code between the labels `start` and `end` as
+/// an SSTORE2 data contract, leaving its address (the pointer) on the stack.
+/// `%sstore2_read` takes a pointer off the stack, copies the data it points
+/// to into memory at `dest`, and leaves the length of the data on the stack.
+fn parse_sstore2<'a>(
+    pair: Pair<'a, Rule>,
+    labels: &mut Labels<'a>,
+) -> Result<Vec<Node>, ParseError> {
+    if pair.as_rule() == Rule::sstore2_read {
+        let (dest,) = <(Pair<Rule>,)>::parse_arguments(pair.into_inner())?;
+        ensure!(dest.as_rule() != Rule::string, error::ArgumentType);
+
+        return Ok(vec![
+            Op::Dup1.into(),
+            Op::ExtCodeSize.into(),
+            Op::Push1(Imm::from([1])).into(),
+            Op::Swap1.into(),
+            Op::Sub.into(),
+            Op::Swap1.into(),
+            Op::Dup2.into(),
+            Op::Push1(Imm::from([1])).into(),
+            parse_narrow_push(dest, labels)?.into(),
+            Op::Dup4.into(),
+            Op::ExtCodeCopy.into(),
+            Op::Pop.into(),
+        ]);
+    }
+
+    let (start, end) = <(Label, Label)>::parse_arguments(pair.into_inner())?;
+    let start = labels.get(start.0);
+    let end = labels.get(end.0);
+
+    let len = || {
+        Expression::binary(
+            Operator::Minus,
+            Expression::Label(end.clone()),
+            Expression::Label(start.clone()),
+        )
+    };
+    let plus = |n: u32| Expression::binary(Operator::Plus, len(), Expression::Number(n.into()));
+    let zero = AbstractOp::Op(Op::Push1(Imm::from([0])));
+
+    Ok(vec![
+        Node::Assert {
+            expression: Expression::binary(
+                Operator::LessEqual,
+                len(),
+                Expression::Number(SSTORE2_MAX_DATA.into()),
+            ),
+            message: format!(
+                "an SSTORE2 data contract can't hold more than {} bytes",
+                SSTORE2_MAX_DATA
+            ),
+        },
+        Node::ComputedPush {
+            spec: Specifier::Push2(()),
+            expression: plus(1),
+        },
+        Op::Push1(Imm::from([0xe8])).into(),
+        Op::Shl.into(),
+        Op::Push32(Imm::from(SSTORE2_HEADER)).into(),
+        Op::Or.into(),
+        zero.clone().into(),
+        Op::MStore.into(),
+        Node::ComputedPush {
+            spec: Specifier::Push2(()),
+            expression: len(),
+        },
+        AbstractOp::Push(Imm::from(start.clone())).into(),
+        Op::Push1(Imm::from([13])).into(),
+        Op::CodeCopy.into(),
+        Node::ComputedPush {
+            spec: Specifier::Push2(()),
+            expression: plus(13),
+        },
+        zero.clone().into(),
+        zero.into(),
+        Op::Create.into(),
+    ])
+}
+
+/// Expand `%calldata_word(n)` or `%calldata_addr(n)` into code that loads the
+/// `n`th argument of the call, counting from zero after the selector.
+///
+/// `%calldata_addr` also clears the twelve bytes above the address.
+fn parse_calldata(pair: Pair<Rule>) -> Result<Vec<Node>, ParseError> {
+    let rule = pair.as_rule();
+    let (index,) = <(u32,)>::parse_arguments(pair.into_inner())?;
+
+    let offset = 4 + 32 * u64::from(index);
+    let bytes = offset.to_be_bytes();
+    let bytes = &bytes[bytes.iter().position(|b| *b != 0).unwrap()..];
+    let spec = Specifier::push(bytes.len() as u32).unwrap();
+
+    let mut nodes = vec![
+        AbstractOp::with_immediate(spec, bytes).unwrap().into(),
+        Op::CallDataLoad.into(),
+    ];
+
+    if rule == Rule::calldata_addr {
+        nodes.push(Op::Push20(Imm::from([0xff; 20])).into());
+        nodes.push(Op::And.into());
+    }
+
+    Ok(nodes)
+}
+
+/// Decode `s` into the end of `buf`, returning the big-endian bytes of the
+/// number, padded with zeros to at least `min` bytes.
+fn radix_str_to_slice<'b>(
+    s: &str,
+    radix: u32,
+    min: usize,
+    buf: &'b mut [u8; 32],
+) -> Result<&'b [u8], ParseError> {
     let n = u128::from_str_radix(s, radix)
         .ok()
         .context(error::ImmediateTooLarge)?;
@@ -159,25 +1673,40 @@ fn radix_str_to_vec(s: &str, radix: u32, min: usize) -> Result<Vec<u8>, ParseErr
 
     len = std::cmp::max(len, min);
 
-    Ok(n.to_be_bytes()[16 - len..].to_vec())
+    buf[16..].copy_from_slice(&n.to_be_bytes());
+    Ok(&buf[32 - len..])
 }
 
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
 
+    use crate::codes;
+    use crate::ops::ExperimentalOp;
     use crate::ops::Imm;
 
     use hex_literal::hex;
 
     use super::*;
 
+    fn parse_asm(asm: &str) -> Result<Vec<Node>, ParseError> {
+        super::parse_asm(asm, Limits::default(), &Experimental::new())
+    }
+
     macro_rules! nodes {
         ($($x:expr),+ $(,)?) => (
             vec![$(Node::from($x)),+]
         );
     }
 
+    #[test]
+    fn parse_error_span() {
+        let asm = "stop\n  pusj1 1\n";
+        let err = parse_asm(asm).unwrap_err();
+        let span = err.span().unwrap();
+        assert_eq!(span.start, (2, 3));
+    }
+
     #[test]
     fn parse_ops() {
         let asm = r#"
@@ -199,6 +1728,17 @@ mod tests {
         assert_matches!(parse_asm(asm), Ok(e) if e == expected);
     }
 
+    #[test]
+    fn lines_match_pest() {
+        let asm = "push1 1\r\n\n  # \u{e9}t\u{e9}\n\tpush2 0x\u{e9}\npush1 2";
+        let lines = Lines::new(asm);
+
+        for (offset, _) in asm.char_indices().chain(Some((asm.len(), ' '))) {
+            let expected = pest::Position::new(asm, offset).unwrap().line_col();
+            assert_eq!(lines.line_col(offset), expected, "at offset {}", offset);
+        }
+    }
+
     #[test]
     fn parse_mixed_lines() {
         let asm = r#"
@@ -255,6 +1795,9 @@ mod tests {
 
             # just enough for 4 bytes
             push4 4294967295
+
+            # wider than the largest supported number
+            push32 12345
         "#;
         let expected = nodes![
             Op::Push1(Imm::from([0])),
@@ -262,6 +1805,9 @@ mod tests {
             Op::Push2(Imm::from([0, 42])),
             Op::Push2(Imm::from(hex!("0100"))),
             Op::Push4(Imm::from(hex!("ffffffff"))),
+            Op::Push32(Imm::from(hex!(
+                "0000000000000000000000000000000000000000000000000000000000003039"
+            ))),
         ];
         assert_matches!(parse_asm(asm), Ok(e) if e == expected);
 
@@ -301,6 +1847,25 @@ mod tests {
         assert_matches!(parse_asm(asm), Err(ParseError::ImmediateTooLarge { .. }));
     }
 
+    #[test]
+    fn parse_push_hex_short() {
+        let asm = r#"
+            # left-pad values too small
+            push2 0x01
+
+            # odd number of digits
+            push2 0x123
+        "#;
+        let expected = nodes![
+            Op::Push2(Imm::from(hex!("0001"))),
+            Op::Push2(Imm::from(hex!("0123"))),
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        let asm = "push1 0x123";
+        assert_matches!(parse_asm(asm), Err(ParseError::ImmediateTooLarge { .. }));
+    }
+
     #[test]
     fn parse_variable_ops() {
         let asm = r#"
@@ -336,7 +1901,14 @@ mod tests {
     #[test]
     fn parse_jumpdest_label() {
         let asm = "start:\njumpdest";
-        let expected = nodes![AbstractOp::Label("start".into()), Op::JumpDest,];
+        let expected = nodes![
+            Node::Location(Span {
+                start: (1, 1),
+                end: (1, 6),
+            }),
+            AbstractOp::Label("start".into()),
+            Op::JumpDest,
+        ];
         assert_matches!(parse_asm(asm), Ok(e) if e == expected);
     }
 
@@ -346,10 +1918,40 @@ mod tests {
             push2 snake_case
             jumpi
         "#;
-        let expected = nodes![Op::Push2(Imm::from("snake_case")), Op::JumpI];
+        let expected = nodes![
+            Node::Location(Span {
+                start: (2, 13),
+                end: (2, 29),
+            }),
+            Op::Push2(Imm::from("snake_case")),
+            Op::JumpI,
+        ];
         assert_matches!(parse_asm(asm), Ok(e) if e == expected);
     }
 
+    #[test]
+    fn parse_label_shared() {
+        let asm = r#"
+            a:
+            push1 a
+            %push(a)
+        "#;
+        let nodes = parse_asm(asm).unwrap();
+        let labels: Vec<_> = nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Op(AbstractOp::Label(lbl)) => Some(lbl),
+                Node::Op(op) => Some(op.shared_label().unwrap()),
+                Node::Location(_) => None,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(labels.len(), 3);
+        assert!(Arc::ptr_eq(labels[0], labels[1]));
+        assert!(Arc::ptr_eq(labels[0], labels[2]));
+    }
+
     #[test]
     fn parse_push_op_as_label() {
         let asm = r#"
@@ -358,13 +1960,299 @@ mod tests {
             jumpi
         "#;
         let expected = nodes![
+            Node::Location(Span {
+                start: (2, 13),
+                end: (2, 18),
+            }),
             AbstractOp::Label("push1".into()),
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 24),
+            }),
             Op::Push1(Imm::from("push1")),
             Op::JumpI
         ];
         assert_matches!(parse_asm(asm), Ok(e) if e == expected);
     }
 
+    #[test]
+    fn parse_anonymous_labels() {
+        let asm = "1:\npush1 1b\npush1 1f\n1:\n%push(1b)";
+        let at = |line, end| {
+            Node::Location(Span {
+                start: (line, 1),
+                end: (line, end),
+            })
+        };
+        let expected = nodes![
+            at(1, 2),
+            AbstractOp::Label("anon.1.0".into()),
+            at(2, 9),
+            Op::Push1(Imm::from("anon.1.0")),
+            at(3, 9),
+            Op::Push1(Imm::from("anon.1.1")),
+            at(4, 2),
+            AbstractOp::Label("anon.1.1".into()),
+            at(5, 10),
+            AbstractOp::Push("anon.1.1".into()),
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        assert_matches!(
+            parse_asm("push1 0b1"),
+            Ok(e) if e == nodes![Op::Push1(Imm::from(1))]
+        );
+    }
+
+    #[test]
+    fn parse_unmatched_anonymous_label() {
+        let err = parse_asm("1:\npush1 2b").unwrap_err();
+        assert_matches!(
+            err,
+            ParseError::UnmatchedAnonymousLabel { forward: false, .. }
+        );
+        assert_eq!(err.to_string(), "no `2:` label before `2b`");
+
+        let err = parse_asm("push1 1f\n1:\npush1 1f").unwrap_err();
+        assert_eq!(err.to_string(), "no `1:` label after `1f`");
+        assert_eq!(
+            err.span(),
+            Some(Span {
+                start: (3, 7),
+                end: (3, 9),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_alias() {
+        let asm = r#"
+            %alias(iszero_jumpi, "iszero jumpi")
+            %alias(to_end, "push2 end; iszero_jumpi")
+            push1 0
+            to_end
+            "#;
+        let expected = nodes![
+            Op::Push1(Imm::from(0)),
+            Node::Expanded {
+                span: Span {
+                    start: (3, 13),
+                    end: (3, 54),
+                },
+                from: vec![(
+                    "to_end".into(),
+                    Span {
+                        start: (5, 13),
+                        end: (5, 19),
+                    }
+                )],
+            },
+            Op::Push2(Imm::from("end")),
+            Op::IsZero,
+            Op::JumpI,
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+    }
+
+    #[test]
+    fn parse_alias_errors() {
+        assert_matches!(
+            parse_asm(r#"%alias(stop, "pop")"#),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "stop"
+        );
+        assert_matches!(
+            parse_asm("%alias(a, \"pop\")\n%alias(a, \"pop\")"),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "a"
+        );
+
+        let err = parse_asm("stop\n  stpo # typo\n").unwrap_err();
+        assert_eq!(err.to_string(), "`stpo` isn't an instruction or alias");
+        assert_eq!(
+            err.span(),
+            Some(Span {
+                start: (2, 3),
+                end: (2, 7),
+            })
+        );
+
+        // Aliases must be declared before they're used.
+        assert_matches!(
+            parse_asm("later\n%alias(later, \"pop\")"),
+            Err(ParseError::UnknownInstruction { .. })
+        );
+        assert_matches!(
+            parse_asm("jumpdestx"),
+            Err(ParseError::UnknownInstruction { .. })
+        );
+    }
+
+    #[test]
+    fn parse_stack_too_deep() {
+        let err = parse_asm("stop\ndup17").unwrap_err();
+        assert_matches!(err, ParseError::StackTooDeep { ref name, .. } if name == "dup17");
+        assert_eq!(err.code(), codes::STACK_TOO_DEEP);
+        assert_eq!(
+            err.span(),
+            Some(Span {
+                start: (2, 1),
+                end: (2, 6),
+            })
+        );
+
+        assert_matches!(
+            parse_asm("swap99999999999"),
+            Err(ParseError::StackTooDeep { .. })
+        );
+        assert_matches!(
+            parse_asm("dup01"),
+            Err(ParseError::UnknownInstruction { .. })
+        );
+        assert_matches!(
+            parse_asm("swapx"),
+            Err(ParseError::UnknownInstruction { .. })
+        );
+
+        let asm = "%macro deep(n)\nswap$n\n%end\n%deep(16)\n%deep(17)";
+        assert_matches!(
+            parse_asm(asm),
+            Err(ParseError::MacroExpansion { error, .. })
+                if matches!(*error, ParseError::StackTooDeep { ref name, .. } if name == "swap17")
+        );
+    }
+
+    #[test]
+    fn parse_experimental() {
+        let experimental = Experimental::proposals();
+        let nodes = super::parse_asm("beginsub\njumpsub", Limits::default(), &experimental);
+
+        let ops: Vec<_> = nodes
+            .unwrap()
+            .into_iter()
+            .filter_map(|n| match n {
+                Node::Op(op) => Some(op.specifier().map(u8::from)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops, [Some(0x5c), Some(0x5e)]);
+
+        let err = parse_asm("stop\nreturnsub").unwrap_err();
+        assert_matches!(
+            err,
+            ParseError::ExperimentalDisabled { ref name, ref origin, .. }
+                if name == "returnsub" && origin == "EIP-2315"
+        );
+        assert_eq!(err.code(), codes::EXPERIMENTAL_DISABLED);
+        assert_eq!(
+            err.span(),
+            Some(Span {
+                start: (2, 1),
+                end: (2, 10),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_experimental_immediate() {
+        let mut experimental = Experimental::new();
+        experimental
+            .insert(ExperimentalOp::new("l1block", 0xe0).immediate(2))
+            .unwrap();
+        let parse = |asm| super::parse_asm(asm, Limits::default(), &experimental);
+
+        let raw: Vec<_> = parse("l1block 0x102\nl1block 7 # seven")
+            .unwrap()
+            .into_iter()
+            .filter_map(|n| match n {
+                Node::Raw(raw) => Some(raw),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(raw, [vec![0x01, 0x02], vec![0x00, 0x07]]);
+
+        assert_matches!(
+            parse("l1block 0x10000"),
+            Err(ParseError::ImmediateTooLarge { .. })
+        );
+        assert_matches!(
+            parse("l1block"),
+            Err(ParseError::MissingArgument {
+                expected: 1,
+                got: 0,
+                ..
+            })
+        );
+        assert_matches!(
+            parse("other 1"),
+            Err(ParseError::UnknownInstruction { name, .. }) if name == "other"
+        );
+
+        experimental
+            .insert(ExperimentalOp::new("sequencer", 0xe1))
+            .unwrap();
+        let parse = |asm| super::parse_asm(asm, Limits::default(), &experimental);
+        assert_matches!(
+            parse("sequencer 1"),
+            Err(ParseError::ExtraArgument { expected: 0, .. })
+        );
+    }
+
+    #[test]
+    fn parse_alias_anonymous_labels() {
+        let asm = r#"
+            %alias(skip, "%push(1f); jump; 1:; jumpdest")
+            skip
+            skip
+            "#;
+        let labels: Vec<_> = parse_asm(asm)
+            .unwrap()
+            .into_iter()
+            .filter_map(|n| match n {
+                Node::Op(AbstractOp::Label(label)) => Some(label),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels.len(), 2);
+        assert_ne!(labels[0], labels[1]);
+    }
+
+    #[test]
+    fn parse_import_std() {
+        let asm = r#"
+            %import(std, "math")
+            %import(std, "math")
+            safe_sub
+            "#;
+        let nodes = parse_asm(asm).unwrap();
+        assert!(nodes.contains(&Node::from(Op::Lt)));
+        assert!(nodes.contains(&Node::from(Op::Sub)));
+
+        // Every module of the standard library can be imported.
+        for (module, _) in STD {
+            let asm = format!("%import(std, \"{}\")", module);
+            assert_matches!(parse_asm(&asm), Ok(n) if n.is_empty());
+        }
+    }
+
+    #[test]
+    fn parse_import_std_errors() {
+        assert_matches!(
+            parse_asm(r#"%import(std, "maths")"#),
+            Err(ParseError::UnknownModule { name, .. }) if name == "maths"
+        );
+        assert_matches!(
+            parse_asm(r#"%import(lib, "math")"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+        assert_matches!(
+            parse_asm(r#"%import(std, "math") as m"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+        assert_matches!(
+            parse_asm("safe_add"),
+            Err(ParseError::UnknownInstruction { .. })
+        );
+    }
+
     #[test]
     fn parse_selector() {
         let asm = r#"
@@ -386,6 +2274,28 @@ mod tests {
         assert_matches!(parse_asm(asm), Ok(e) if e == expected);
     }
 
+    #[test]
+    fn parse_selector_signatures() {
+        let asm = r#"
+            %macro sel()
+                push4 selector("name()")
+            %end
+            push4 selector("balanceOf(address)")
+            %sel()
+        "#;
+        let parsed = parse_located(asm, Limits::default(), &Experimental::default()).unwrap();
+
+        let signatures: Vec<_> = parsed
+            .signatures
+            .iter()
+            .map(|(s, span)| (s.as_str(), span.start))
+            .collect();
+        assert_eq!(
+            signatures,
+            [("balanceOf(address)", (5, 13)), ("name()", (6, 13))]
+        );
+    }
+
     #[test]
     fn parse_selector_with_spaces() {
         let asm = r#"
@@ -405,6 +2315,10 @@ mod tests {
         );
         let expected = nodes![
             Op::Push1(Imm::from(1)),
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 32),
+            }),
             Node::Include(PathBuf::from("foo.asm")),
             Op::Push1(Imm::from(2)),
         ];
@@ -422,12 +2336,62 @@ mod tests {
         );
         let expected = nodes![
             Op::Push1(Imm::from(1)),
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 36),
+            }),
             Node::IncludeHex(PathBuf::from("foo.hex")),
             Op::Push1(Imm::from(2)),
         ];
         assert_matches!(parse_asm(&asm), Ok(e) if e == expected)
     }
 
+    #[test]
+    fn parse_script() {
+        let asm = r#"%script("gen_table.rhai")"#;
+        let expected = nodes![
+            Node::Location(Span {
+                start: (1, 1),
+                end: (1, 26),
+            }),
+            Node::Script(PathBuf::from("gen_table.rhai")),
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected)
+    }
+
+    #[test]
+    fn parse_include_artifact() {
+        let asm = r#"
+            %include_artifact("out/Token.sol/Token.json")
+            %include_artifact("out/Token.sol/Token.json", deployed)
+        "#;
+        let expected = vec![
+            Node::Location(Span {
+                start: (2, 13),
+                end: (2, 58),
+            }),
+            Node::IncludeArtifact {
+                path: PathBuf::from("out/Token.sol/Token.json"),
+                deployed: false,
+            },
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 68),
+            }),
+            Node::IncludeArtifact {
+                path: PathBuf::from("out/Token.sol/Token.json"),
+                deployed: true,
+            },
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        let asm = r#"%include_artifact("a.json", runtime)"#;
+        assert_matches!(parse_asm(asm), Err(ParseError::ArgumentType { .. }));
+
+        let asm = r#"%include_artifact("a.json", deployed, 3)"#;
+        assert_matches!(parse_asm(asm), Err(ParseError::ExtraArgument { .. }));
+    }
+
     #[test]
     fn parse_import() {
         let asm = format!(
@@ -439,12 +2403,35 @@ mod tests {
         );
         let expected = nodes![
             Op::Push1(Imm::from(1)),
-            Node::Import(PathBuf::from("foo.asm")),
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 31),
+            }),
+            Node::Import {
+                path: PathBuf::from("foo.asm"),
+                alias: None,
+            },
             Op::Push1(Imm::from(2)),
         ];
         assert_matches!(parse_asm(&asm), Ok(e) if e == expected)
     }
 
+    #[test]
+    fn parse_import_escaped() {
+        let asm = r#"%import("a\\b\"c.etk")"#;
+        let expected = nodes![
+            Node::Location(Span {
+                start: (1, 1),
+                end: (1, 23),
+            }),
+            Node::Import {
+                path: PathBuf::from(r#"a\b"c.etk"#),
+                alias: None,
+            }
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+    }
+
     #[test]
     fn parse_import_extra_argument() {
         let asm = format!(
@@ -499,12 +2486,275 @@ mod tests {
         );
         let expected = nodes![
             Op::Push1(Imm::from(1)),
-            Node::Import(PathBuf::from("hello.asm")),
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 35),
+            }),
+            Node::Import {
+                path: PathBuf::from("hello.asm"),
+                alias: None,
+            },
             Op::Push1(Imm::from(2)),
         ];
         assert_matches!(parse_asm(&asm), Ok(e) if e == expected)
     }
 
+    #[test]
+    fn parse_buildinfo() {
+        let asm = "push1 1\n%buildinfo()";
+        let expected = vec![Node::from(Op::Push1(Imm::from(1))), Node::BuildInfo];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        let asm = "%buildinfo(1)";
+        assert_matches!(parse_asm(asm), Err(ParseError::ExtraArgument { .. }));
+    }
+
+    #[test]
+    fn parse_raw() {
+        let asm = "%raw(0x0c)\n%raw(255)\n%raw(0b1)";
+        let expected = vec![
+            Node::Raw(vec![0x0c]),
+            Node::Raw(vec![0xff]),
+            Node::Raw(vec![0x01]),
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        assert_matches!(
+            parse_asm("%raw(256)"),
+            Err(ParseError::ArgumentTooLarge { text, size: 1, .. }) if text == "256"
+        );
+        assert_matches!(parse_asm("%raw()"), Err(ParseError::MissingArgument { .. }));
+        assert_matches!(
+            parse_asm("%raw(label)"),
+            Err(ParseError::ArgumentType { .. })
+        );
+    }
+
+    #[test]
+    fn parse_raw_hex() {
+        let asm = r#"%raw("0x60ff5b")
+            %raw("0C")"#;
+        let expected = vec![Node::Raw(vec![0x60, 0xff, 0x5b]), Node::Raw(vec![0x0c])];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        for bad in &[r#"%raw("0x6")"#, r#"%raw("0x")"#, r#"%raw("zz")"#] {
+            assert_matches!(
+                parse_asm(bad),
+                Err(ParseError::InvalidHex { .. }),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn parse_org() {
+        let asm = "%org(0x20)\n%org(64, 0xfe)";
+        let expected = vec![
+            Node::Org {
+                offset: 0x20,
+                fill: 0,
+            },
+            Node::Org {
+                offset: 64,
+                fill: 0xfe,
+            },
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        assert_matches!(
+            parse_asm("%org(1, 256)"),
+            Err(ParseError::ArgumentTooLarge { size: 1, .. })
+        );
+        assert_matches!(
+            parse_asm("%org(start)"),
+            Err(ParseError::ArgumentType { .. })
+        );
+    }
+
+    #[test]
+    fn parse_assert() {
+        let asm = r#"%assert(end - start <= 0x6000 && 1, "too large")
+            %assert((1 + 2) * 3 == 9)"#;
+
+        let size = Expression::binary(
+            Operator::Minus,
+            Expression::Label("end".into()),
+            Expression::Label("start".into()),
+        );
+        let first = Expression::binary(
+            Operator::And,
+            Expression::binary(Operator::LessEqual, size, Expression::Number(0x6000.into())),
+            Expression::Number(1.into()),
+        );
+
+        let nodes = parse_asm(asm).unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_matches!(nodes[0], Node::Location(Span { start: (1, 1), .. }));
+        assert_matches!(
+            &nodes[1],
+            Node::Assert { expression, message } if *expression == first && message == "too large"
+        );
+        assert_matches!(
+            &nodes[3],
+            Node::Assert { message, .. } if message == "`(1 + 2) * 3 == 9`"
+        );
+
+        assert_matches!(parse_asm("%assert()"), Err(ParseError::Lexer { .. }));
+        assert_matches!(
+            parse_asm("%assert(1f)"),
+            Err(ParseError::UnmatchedAnonymousLabel { .. })
+        );
+    }
+
+    #[test]
+    fn parse_builtins() {
+        let asm = "push1 len(start, end)\npush32 keccak(data, data + 4)";
+
+        let start = Expression::Label("start".into());
+        let end = Expression::Label("end".into());
+        let data = Expression::Label("data".into());
+        let data_end =
+            Expression::binary(Operator::Plus, data.clone(), Expression::Number(4.into()));
+
+        let nodes = parse_asm(asm).unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_matches!(
+            &nodes[1],
+            Node::ComputedPush { spec: Specifier::Push1(()), expression }
+                if *expression == Expression::binary(Operator::Minus, end, start)
+        );
+        assert_matches!(
+            &nodes[3],
+            Node::ComputedPush { spec: Specifier::Push32(()), expression }
+                if *expression == Expression::Keccak(Box::new(data), Box::new(data_end))
+        );
+
+        let nodes = parse_asm("%assert(len(a, b) == 3)").unwrap();
+        assert_matches!(&nodes[1], Node::Assert { .. });
+
+        assert_matches!(
+            parse_asm("push1 len(a)"),
+            Err(ParseError::UndefinedConstant { .. })
+        );
+    }
+
+    #[test]
+    fn parse_push_constant_expression() {
+        let expected = nodes![Op::Push2(Imm::from(hex!("0102")))];
+        assert_matches!(parse_asm("push2 0x100 + 2"), Ok(e) if e == expected);
+        assert_matches!(parse_asm("push2 (0x100 + 2)"), Ok(e) if e == expected);
+
+        assert_matches!(
+            parse_asm("push1 0xff + 1"),
+            Err(ParseError::ImmediateTooLarge { .. })
+        );
+    }
+
+    #[test]
+    fn parse_pc_of() {
+        let table = Expression::Label("table".into());
+
+        let nodes = parse_asm("push2 pc_of(table)").unwrap();
+        assert_matches!(
+            &nodes[1],
+            Node::ComputedPush { spec: Specifier::Push2(()), expression }
+                if *expression == table
+        );
+
+        let nodes = parse_asm("%push(pc_of(table) + 32 * 2)").unwrap();
+        let expected = Expression::binary(
+            Operator::Plus,
+            table,
+            Expression::binary(
+                Operator::Times,
+                Expression::Number(32.into()),
+                Expression::Number(2.into()),
+            ),
+        );
+        assert_matches!(&nodes[1], Node::PushExpression(expression) if *expression == expected);
+
+        let nodes = parse_asm("push1 pc_of(table) + 32*2").unwrap();
+        assert_matches!(
+            &nodes[1],
+            Node::ComputedPush { spec: Specifier::Push1(()), expression }
+                if *expression == expected
+        );
+
+        let nodes = parse_asm("1:\n%assert(pc_of(1b) == 0)").unwrap();
+        assert_matches!(
+            nodes.last(),
+            Some(Node::Assert { expression, .. })
+                if expression.labels()[0].as_ref() == anonymous("1", 0).as_ref()
+        );
+
+        assert_matches!(parse_asm("push2 pc_of(1)"), Err(ParseError::Lexer { .. }));
+        assert_matches!(
+            parse_asm("push2 pc_of(1f)"),
+            Err(ParseError::UnmatchedAnonymousLabel { .. })
+        );
+    }
+
+    #[test]
+    fn parse_pragma() {
+        assert_matches!(parse_asm("%pragma(once)"), Ok(e) if e == [Node::PragmaOnce]);
+        assert_matches!(
+            parse_asm("%pragma(solidity_memory)"),
+            Ok(e) if e == [Node::PragmaSolidityMemory]
+        );
+        assert_matches!(
+            parse_asm("%pragma(twice)"),
+            Err(ParseError::UnknownPragma { name, .. }) if name == "twice"
+        );
+        assert_matches!(
+            parse_asm(r#"%pragma("once")"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+    }
+
+    #[test]
+    fn parse_import_alias() {
+        let asm = r#"
+            %import("math.etk") as math
+            push2 math.add
+            "#;
+        let expected = nodes![
+            Node::Location(Span {
+                start: (2, 13),
+                end: (2, 40),
+            }),
+            Node::Import {
+                path: PathBuf::from("math.etk"),
+                alias: Some("math".into()),
+            },
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 27),
+            }),
+            AbstractOp::with_label(Specifier::push(2).unwrap(), "math.add"),
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        // Qualified labels can be used, but not declared.
+        assert_matches!(parse_asm("math.add:"), Err(ParseError::Lexer { .. }));
+    }
+
+    #[test]
+    fn parse_export() {
+        let asm = "%export(add, sub_2)";
+        let expected = vec![Node::Export(vec!["add".into(), "sub_2".into()])];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        assert_matches!(
+            parse_asm("%export()"),
+            Err(ParseError::MissingArgument { .. })
+        );
+        assert_matches!(
+            parse_asm(r#"%export("add")"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+    }
+
     #[test]
     fn parse_push_macro_with_label() {
         let asm = format!(
@@ -516,9 +2766,489 @@ mod tests {
         );
         let expected = nodes![
             Op::Push1(Imm::from(1)),
+            Node::Location(Span {
+                start: (3, 13),
+                end: (3, 27),
+            }),
             AbstractOp::Push("hello".into()),
             Op::Push1(Imm::from(2)),
         ];
         assert_matches!(parse_asm(&asm), Ok(e) if e == expected)
     }
+
+    #[test]
+    fn parse_push_macro_with_expression() {
+        let table = Expression::Label("table".into());
+        let end = Expression::Label("end".into());
+
+        let nodes = parse_asm("%push(table + 3)\n%push(end - table)").unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_matches!(
+            &nodes[1],
+            Node::PushExpression(expression)
+                if *expression == Expression::binary(
+                    Operator::Plus,
+                    table.clone(),
+                    Expression::Number(3.into()),
+                )
+        );
+        assert_matches!(
+            &nodes[3],
+            Node::PushExpression(expression)
+                if *expression == Expression::binary(Operator::Minus, end, table)
+        );
+
+        let expected = nodes![Op::Push1(Imm::from(7)), Op::Push2(Imm::from([1, 0]))];
+        assert_eq!(parse_asm("%push(3 + 4)\n%push(0x100)").unwrap(), expected);
+
+        assert_matches!(
+            parse_asm("%push()"),
+            Err(ParseError::MissingArgument { .. })
+        );
+        assert_matches!(
+            parse_asm("%push(a, b)"),
+            Err(ParseError::ExtraArgument { .. })
+        );
+    }
+
+    #[test]
+    fn parse_array_index() {
+        let asm = r#"
+            %def FEES = [100, 300, 0x1f4 + 1]
+            push2 FEES[1]
+            %push(FEES[2])
+            push1 FEES[3 - 3]
+            "#;
+        let expected = nodes![
+            Op::Push2(Imm::from([0x01, 0x2c])),
+            Op::Push2(Imm::from([0x01, 0xf5])),
+            Op::Push1(Imm::from(100)),
+        ];
+        assert_eq!(parse_asm(asm).unwrap(), expected);
+
+        let nodes = parse_asm("%def JUMPS = [a, b]\npush2 JUMPS[1]").unwrap();
+        assert_matches!(
+            &nodes[1],
+            Node::ComputedPush { spec: Specifier::Push2(()), expression }
+                if *expression == Expression::Label("b".into())
+        );
+
+        assert_matches!(
+            parse_asm("%def A = [1]\npush1 B[0]"),
+            Err(ParseError::UndefinedArray { name, .. }) if name == "B"
+        );
+        assert_matches!(
+            parse_asm("%def A = [1]\npush1 A[1]"),
+            Err(ParseError::ArrayIndex { len: 1, .. })
+        );
+        assert_matches!(
+            parse_asm("%def A = [1]\npush1 A[x]"),
+            Err(ParseError::ArrayIndex { index, .. }) if index == "x"
+        );
+        assert_matches!(
+            parse_asm("%def A = [1, 0x100]\npush1 A[1]"),
+            Err(ParseError::ImmediateTooLarge { .. })
+        );
+        assert_matches!(
+            parse_asm("%def A = [1]\n%def A = [2]"),
+            Err(ParseError::DuplicateDefinition { .. })
+        );
+    }
+
+    #[test]
+    fn parse_strings() {
+        let asm = r#"
+            %def SYMBOL = "USDC"
+            %def TRANSFER = concat("transfer", SYMBOL, "(address,uint256)")
+            push4 selector(TRANSFER)
+            push4 selector(concat("mint", "(uint256)"))
+            push1 len(SYMBOL)
+            push1 len(substr(TRANSFER, 8, 4))
+            push4 selector(substr("xname()", 1, 6))
+            "#;
+        let expected = nodes![
+            Op::Push4(Imm::from(hex!("f7aaaf3f"))),
+            Op::Push4(Imm::from(hex!("a0712d68"))),
+            Node::Location(Span {
+                start: (6, 13),
+                end: (6, 30),
+            }),
+            Node::ComputedPush {
+                spec: Specifier::Push1(()),
+                expression: Expression::Number(4.into()),
+            },
+            Node::Location(Span {
+                start: (7, 13),
+                end: (7, 46),
+            }),
+            Node::ComputedPush {
+                spec: Specifier::Push1(()),
+                expression: Expression::Number(4.into()),
+            },
+            Op::Push4(Imm::from(hex!("06fdde03"))),
+        ];
+        assert_eq!(parse_asm(asm).unwrap(), expected);
+
+        assert_matches!(
+            parse_asm("push1 len(substr(\"abc\", 2, 2))"),
+            Err(ParseError::StringRange {
+                start: 2,
+                len: 2,
+                size: 3,
+                ..
+            })
+        );
+        assert_matches!(
+            parse_asm("push4 selector(concat(\"a b\", \"()\"))"),
+            Err(ParseError::InvalidSelector { signature, .. }) if signature == "a b()"
+        );
+        assert_matches!(
+            parse_asm("push4 selector(NOPE)"),
+            Err(ParseError::UndefinedConstant { name, .. }) if name == "NOPE"
+        );
+        assert_matches!(
+            parse_asm("%def A = \"a\"\n%def A = [1]"),
+            Err(ParseError::DuplicateDefinition { .. })
+        );
+    }
+
+    #[test]
+    fn parse_macro() {
+        let asm = r#"
+            %macro push_all(args...)
+                $(push1 $args
+                )*
+            %end
+
+            %macro handler(name, args...)
+                handler_$name:
+                %push_all($args)
+                1:
+                push1 1b
+            %end
+
+            %handler(mint, 1, 2)
+            %handler(burn)
+            "#;
+
+        let nodes: Vec<_> = parse_asm(asm)
+            .unwrap()
+            .into_iter()
+            .filter(|n| matches!(n, Node::Op(_)))
+            .collect();
+
+        let expected = nodes![
+            AbstractOp::Label("handler_mint".into()),
+            Op::Push1(Imm::from(1)),
+            Op::Push1(Imm::from(2)),
+            AbstractOp::Label("anon.1.0.2".into()),
+            Op::Push1(Imm::from("anon.1.0.2")),
+            AbstractOp::Label("handler_burn".into()),
+            AbstractOp::Label("anon.1.0.4".into()),
+            Op::Push1(Imm::from("anon.1.0.4")),
+        ];
+        assert_eq!(nodes, expected);
+    }
+
+    #[test]
+    fn parse_macro_errors() {
+        let err = parse_asm("%macro m(a)\npush1 $a\n%end\n\n%m(0x100)").unwrap_err();
+        assert_matches!(
+            &err,
+            ParseError::MacroExpansion { name, span, error }
+                if name == "m"
+                    && span.start == (5, 1)
+                    && matches!(**error, ParseError::ImmediateTooLarge { .. })
+        );
+        assert_eq!(err.code(), codes::IMMEDIATE_TOO_LARGE);
+
+        assert_matches!(
+            parse_asm("%macro a()\n%b()\n%end\n%macro b()\n%a()\n%end\n%a()"),
+            Err(ParseError::MacroExpansion { error, .. })
+                if matches!(
+                    *error,
+                    ParseError::MacroExpansion { ref error, .. }
+                        if matches!(**error, ParseError::RecursiveMacro { .. })
+                )
+        );
+
+        assert_matches!(
+            parse_asm("%nope(1)"),
+            Err(ParseError::UndefinedMacro { name, .. }) if name == "nope"
+        );
+        assert_matches!(
+            parse_asm("%macro m(a)\n%end\n%m()"),
+            Err(ParseError::MissingArgument {
+                expected: 1,
+                got: 0,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn parse_pseudo() {
+        let asm = r#"
+            revert0
+            ret0
+            mstore_imm 0x40, 256
+            mstore_imm 0, end
+            end:
+            "#;
+        let expected = nodes![
+            Op::Push1(Imm::from(0)),
+            Op::Dup1,
+            Op::Revert,
+            Op::Push1(Imm::from(0)),
+            Op::Dup1,
+            Op::Return,
+            Op::Push2(Imm::from(hex!("0100"))),
+            Op::Push1(Imm::from(0x40)),
+            Op::MStore,
+            Node::Location(Span {
+                start: (5, 13),
+                end: (5, 30),
+            }),
+            AbstractOp::Push("end".into()),
+            Op::Push1(Imm::from(0)),
+            Op::MStore,
+            Node::Location(Span {
+                start: (6, 13),
+                end: (6, 16),
+            }),
+            AbstractOp::Label("end".into()),
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        let asm = r#"
+            %alias(fail, "push1 1; revert0")
+            fail
+            "#;
+        let nodes = parse_asm(asm).unwrap();
+        assert_eq!(nodes.last(), Some(&Node::from(Op::Revert)));
+    }
+
+    #[test]
+    fn parse_pseudo_errors() {
+        assert_matches!(
+            parse_asm("ret0 1"),
+            Err(ParseError::ExtraArgument { expected, .. }) if expected == 0
+        );
+        assert_matches!(
+            parse_asm("mstore_imm 0"),
+            Err(ParseError::MissingArgument { expected, got, .. }) if expected == 2 && got == 1
+        );
+        assert_matches!(
+            parse_asm(r#"%alias(ret0, "pop")"#),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "ret0"
+        );
+    }
+
+    #[test]
+    fn parse_locals() {
+        let asm = r#"
+            %locals(x, y)
+            dup y
+            dup y
+            add
+            swap y
+            pop
+            push1 1
+            swap x
+            "#;
+        let expected = nodes![
+            Op::Dup2,
+            Op::Dup3,
+            Op::Add,
+            Op::Swap2,
+            Op::Pop,
+            Op::Push1(Imm::from(1)),
+            Op::Swap1,
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+    }
+
+    #[test]
+    fn parse_locals_errors() {
+        let err = parse_asm("%locals(a)\npop\ndup a").unwrap_err();
+        assert_matches!(err, ParseError::UnknownLocal { ref name, .. } if name == "a");
+        assert_eq!(
+            err.span(),
+            Some(Span {
+                start: (3, 5),
+                end: (3, 6),
+            })
+        );
+
+        assert_matches!(
+            parse_asm("%locals(a)\nswap a"),
+            Err(ParseError::LocalOutOfReach { depth: 0, .. })
+        );
+
+        let asm = format!("%locals(a)\n{}dup a", "push1 0\n".repeat(16));
+        assert_matches!(
+            parse_asm(&asm),
+            Err(ParseError::LocalOutOfReach { depth: 16, .. })
+        );
+
+        let asm = format!("%locals(a, b, c)\n{}swap c", "push1 0\n".repeat(15));
+        assert_matches!(
+            parse_asm(&asm),
+            Err(ParseError::LocalOutOfReach { depth: 17, above, .. }) if above == ["a", "b"]
+        );
+        assert_matches!(
+            parse_asm("%locals(a, b, a)"),
+            Err(ParseError::DuplicateDefinition { name, .. }) if name == "a"
+        );
+    }
+
+    #[test]
+    fn parse_minimal_proxy() {
+        let nodes = parse_asm("%minimal_proxy(0x01)").unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(
+            nodes[2],
+            Node::Raw(hex!("0000000000000000000000000000000000000001").to_vec())
+        );
+
+        let nodes = parse_asm("%minimal_proxy(impl)").unwrap();
+        assert_eq!(nodes[2], AbstractOp::Label("impl".into()).into());
+        assert_eq!(nodes[3], Node::Raw(vec![0; 20]));
+
+        let too_long = format!("%minimal_proxy(0x{})", "ab".repeat(21));
+        assert_matches!(
+            parse_asm(&too_long),
+            Err(ParseError::ImmediateTooLarge { .. })
+        );
+        assert_matches!(
+            parse_asm(r#"%minimal_proxy("0x01")"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+    }
+
+    #[test]
+    fn parse_emit() {
+        let asm = r#"
+            %locals(from, to)
+            %emit("Transfer(address indexed from, address indexed to, uint256)", from, to, 0x00, 32)
+            "#;
+        let expected = nodes![
+            Op::Dup2,
+            Op::Dup2,
+            Op::Push32(Imm::from(hex!(
+                "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+            ))),
+            Op::Push1(Imm::from(32u8)),
+            Op::Push1(Imm::from(0u8)),
+            Op::Log3,
+        ];
+        assert_matches!(parse_asm(asm), Ok(e) if e == expected);
+
+        let nodes = parse_asm(r#"%emit("Ping()", data, 0x00)"#).unwrap();
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(nodes[4], Node::from(Op::Log1));
+    }
+
+    #[test]
+    fn parse_emit_errors() {
+        assert_matches!(
+            parse_asm(r#"%emit("Transfer(address indexed, uint256)", 0x00, 0x20)"#),
+            Err(ParseError::MissingArgument {
+                expected: 4,
+                got: 3,
+                ..
+            })
+        );
+        assert_matches!(
+            parse_asm(r#"%emit("Ping()", 1, 0x00, 0x20)"#),
+            Err(ParseError::ExtraArgument { expected: 3, .. })
+        );
+        assert_matches!(
+            parse_asm(r#"%emit("Ping", 0x00, 0x20)"#),
+            Err(ParseError::InvalidEvent { signature, .. }) if signature == "Ping"
+        );
+        assert_matches!(
+            parse_asm("%emit(0x00, 0x20)"),
+            Err(ParseError::ArgumentType { .. })
+        );
+        assert_matches!(
+            parse_asm("%emit()"),
+            Err(ParseError::MissingArgument { .. })
+        );
+    }
+
+    #[test]
+    fn parse_calldata() {
+        let expected = nodes![Op::Push1(Imm::from(4u8)), Op::CallDataLoad];
+        assert_matches!(parse_asm("%calldata_word(0)"), Ok(e) if e == expected);
+
+        let expected = nodes![
+            Op::Push2(Imm::from(0x0124u16)),
+            Op::CallDataLoad,
+            Op::Push20(Imm::from([0xff; 20])),
+            Op::And,
+        ];
+        assert_matches!(parse_asm("%calldata_addr(9)"), Ok(e) if e == expected);
+
+        assert_matches!(
+            parse_asm("%calldata_word(a)"),
+            Err(ParseError::ArgumentType { .. })
+        );
+        assert_matches!(
+            parse_asm("%calldata_word(0x100000000)"),
+            Err(ParseError::ArgumentTooLarge { size: 4, .. })
+        );
+    }
+
+    #[test]
+    fn parse_sstore2_errors() {
+        assert_matches!(
+            parse_asm("%sstore2_write(a)"),
+            Err(ParseError::MissingArgument {
+                expected: 2,
+                got: 1,
+                ..
+            })
+        );
+        assert_matches!(
+            parse_asm("%sstore2_read(1, 2)"),
+            Err(ParseError::ExtraArgument { expected: 1, .. })
+        );
+        assert_matches!(
+            parse_asm(r#"%sstore2_read("a")"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+        assert_matches!(
+            parse_asm(&format!("%sstore2_read(0x{})", "ab".repeat(33))),
+            Err(ParseError::ImmediateTooLarge { .. })
+        );
+    }
+
+    #[test]
+    fn parse_create_errors() {
+        assert_matches!(
+            parse_asm("%create(a)"),
+            Err(ParseError::MissingArgument {
+                expected: 2,
+                got: 1,
+                ..
+            })
+        );
+        assert_matches!(
+            parse_asm("%create2(a, b)"),
+            Err(ParseError::MissingArgument {
+                expected: 3,
+                got: 2,
+                ..
+            })
+        );
+        assert_matches!(
+            parse_asm("%create2(a, b, 1, 2)"),
+            Err(ParseError::ExtraArgument { expected: 3, .. })
+        );
+        assert_matches!(
+            parse_asm(r#"%create("a", b)"#),
+            Err(ParseError::ArgumentType { .. })
+        );
+    }
 }
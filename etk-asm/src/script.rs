@@ -0,0 +1,146 @@
+//! Running [Rhai](https://rhai.rs) scripts for `%script`, which generate code
+//! while assembling.
+
+use crate::Span;
+
+/// Why a script couldn't be run, and where in the script.
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) message: String,
+    pub(crate) span: Option<Span>,
+}
+
+/// What a script returned.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "script"), allow(dead_code))]
+pub(crate) enum Output {
+    /// Assembly source, to be parsed and assembled in place of the `%script`.
+    Text(String),
+
+    /// Bytes to be included verbatim in place of the `%script`.
+    Bytes(Vec<u8>),
+}
+
+/// Most operations a script may perform before it's stopped, so a script that
+/// never finishes doesn't hang the build.
+#[cfg(feature = "script")]
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// Run `script`, and interpret what it returns.
+///
+/// A string is assembly source. A blob, or an array of integers from 0 to
+/// 255, is raw bytes.
+#[cfg(feature = "script")]
+pub(crate) fn run(script: &str) -> Result<Output, Failure> {
+    use rhai::{Dynamic, Engine, EvalAltResult};
+    use std::convert::TryFrom;
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let value = engine
+        .eval::<Dynamic>(script)
+        .map_err(|mut err: Box<EvalAltResult>| {
+            let position = err.take_position();
+            let span = position.line().map(|line| {
+                let column = position.position().unwrap_or(1);
+                Span {
+                    start: (line, column),
+                    end: (line, column),
+                }
+            });
+
+            Failure {
+                message: err.to_string(),
+                span,
+            }
+        })?;
+
+    let returned = |what: &str| Failure {
+        message: format!(
+            "expected a string of assembly or an array of bytes, but the script returned {}",
+            what
+        ),
+        span: None,
+    };
+
+    if value.is_string() {
+        return Ok(Output::Text(value.into_string().unwrap()));
+    }
+
+    if value.is_blob() {
+        return Ok(Output::Bytes(value.into_blob().unwrap()));
+    }
+
+    if !value.is_array() {
+        return Err(returned(value.type_name()));
+    }
+
+    value
+        .into_array()
+        .unwrap()
+        .into_iter()
+        .map(|item| match item.as_int() {
+            Ok(int) => u8::try_from(int).map_err(|_| returned(&format!("the integer {}", int))),
+            Err(what) => Err(returned(&format!("an array containing {}", what))),
+        })
+        .collect::<Result<_, _>>()
+        .map(Output::Bytes)
+}
+
+/// Without the `script` feature, scripts can't be run.
+#[cfg(not(feature = "script"))]
+pub(crate) fn run(_script: &str) -> Result<Output, Failure> {
+    Err(Failure {
+        message: "etk-asm was built without the `script` feature".into(),
+        span: None,
+    })
+}
+
+#[cfg(all(test, feature = "script"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_text() {
+        let script = r#"
+            let out = "";
+            for fee in [100, 300] {
+                out += `push2 ${fee}` + "\n";
+            }
+            out
+        "#;
+
+        assert_eq!(
+            run(script).unwrap(),
+            Output::Text("push2 100\npush2 300\n".into())
+        );
+    }
+
+    #[test]
+    fn run_bytes() {
+        assert_eq!(run("[0x60, 1]").unwrap(), Output::Bytes(vec![0x60, 0x01]));
+
+        let blob = "let b = blob(2, 0x5b); b";
+        assert_eq!(run(blob).unwrap(), Output::Bytes(vec![0x5b, 0x5b]));
+    }
+
+    #[test]
+    fn run_errors() {
+        let failure = run("[256]").unwrap_err();
+        assert!(failure.message.ends_with("the integer 256"));
+
+        let failure = run("42").unwrap_err();
+        assert!(failure.message.ends_with("returned i64"));
+
+        let failure = run("let x = 1;\nundefined_fn(x)").unwrap_err();
+        assert_eq!(failure.span.unwrap().start, (2, 1));
+
+        let failure = run("loop {}").unwrap_err();
+        assert!(
+            failure.message.contains("operations"),
+            "{}",
+            failure.message
+        );
+    }
+}
@@ -0,0 +1,678 @@
+//! Translating the textual assembly printed by `solc --asm` into etk sources.
+//!
+//! Each assembly (the contract itself, and every `sub_N: assembly { ... }`
+//! nested inside it) becomes its own [`Module`], and each sub-assembly is
+//! included at the end of the assembly that contains it, under a label with
+//! the same name. That keeps the tags of every assembly independent, just like
+//! they are in `solc`.
+//!
+//! Items are translated like so:
+//!
+//! | `solc --asm`              | etk                                   |
+//! |---------------------------|---------------------------------------|
+//! | `tag_3:`                  | `tag_3:` followed by `jumpdest`       |
+//! | `tag_3`                   | `pushN tag_3`                         |
+//! | `0x40`                    | `push1 0x40`                          |
+//! | `mstore(0x40, 0x80)`      | `push1 0x80`, `push1 0x40`, `mstore`  |
+//! | `dataOffset(sub_0)`       | `pushN sub_0`                         |
+//! | `dataSize(sub_0)`         | the size of `sub_0`, as a literal     |
+//! | `auxdata: 0xa264...`      | an `%include_hex` of a `.hex` module  |
+//!
+//! Tags and offsets are pushed with the same width `solc` would use: the
+//! fewest bytes that can hold the size of the code. Since `dataSize` becomes a
+//! literal, it has to be updated by hand when a sub-assembly is changed.
+//!
+//! Immutables, libraries, and the other linker references aren't supported.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use etk_asm::solc::translate;
+//!
+//! let asm = r#"
+//! EVM assembly:
+//!   mstore(0x40, 0x80)
+//!   tag_1
+//!   jump
+//! tag_1:
+//!   stop
+//! "#;
+//!
+//! let modules = translate(asm)?;
+//! assert_eq!(modules[0].path.to_str(), Some("main.etk"));
+//! assert!(modules[0].contents.contains("push1 tag_1\njump\ntag_1:\njumpdest\n"));
+//! # Result::<(), etk_asm::solc::Error>::Ok(())
+//! ```
+mod error {
+    use snafu::{Backtrace, Snafu};
+
+    /// Errors that can occur while translating `solc --asm` output.
+    #[derive(Debug, Snafu)]
+    #[non_exhaustive]
+    #[snafu(visibility = "pub(super)")]
+    pub enum Error {
+        /// A line couldn't be understood.
+        #[snafu(display("line {}: can't parse `{}`", line, text))]
+        #[non_exhaustive]
+        Syntax {
+            /// The one-based line number.
+            line: usize,
+
+            /// The offending text.
+            text: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// An item has no etk equivalent (yet.)
+        #[snafu(display("line {}: `{}` isn't supported", line, text))]
+        #[non_exhaustive]
+        Unsupported {
+            /// The one-based line number.
+            line: usize,
+
+            /// The offending text.
+            text: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// A `dataSize` or `dataOffset` referred to a sub-assembly that
+        /// doesn't exist.
+        #[snafu(display("`{}` isn't a sub-assembly", name))]
+        #[non_exhaustive]
+        UnknownSub {
+            /// The name of the sub-assembly.
+            name: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// An `assembly {` block was never closed, or a `}` was never opened.
+        #[snafu(display("unbalanced braces"))]
+        #[non_exhaustive]
+        Unbalanced {
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The input held the assembly of more than one contract.
+        #[snafu(display("found more than one contract, translate them one at a time"))]
+        #[non_exhaustive]
+        MultipleContracts {
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+    }
+}
+
+pub use self::error::Error;
+
+use crate::ops::Specifier;
+
+use snafu::{ensure, OptionExt};
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// A translated source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    /// Where the file should be written, relative to the root module.
+    pub path: PathBuf,
+
+    /// The contents of the file.
+    pub contents: String,
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Op(Specifier),
+    Literal(Vec<u8>),
+    Tag(String),
+    PushTag(String),
+    DataOffset(String),
+    DataSize(String),
+}
+
+#[derive(Debug, Default)]
+struct Assembly {
+    items: Vec<Item>,
+    subs: Vec<(String, Assembly)>,
+    auxdata: Vec<u8>,
+}
+
+impl Assembly {
+    fn sub(&self, name: &str) -> Result<&Assembly, Error> {
+        self.subs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, a)| a)
+            .context(error::UnknownSub { name })
+    }
+
+    /// Size of the code, including sub-assemblies and auxdata, when tags are
+    /// pushed with `width` bytes.
+    fn size_with(&self, width: usize) -> Result<usize, Error> {
+        let mut size = self.auxdata.len();
+
+        for (_, sub) in &self.subs {
+            size += sub.size()?;
+        }
+
+        for item in &self.items {
+            size += match item {
+                Item::Op(_) | Item::Tag(_) => 1,
+                Item::Literal(v) => 1 + v.len(),
+                Item::PushTag(_) | Item::DataOffset(_) => 1 + width,
+                Item::DataSize(name) => 1 + minimal(self.sub(name)?.size()?).len(),
+            };
+        }
+
+        Ok(size)
+    }
+
+    /// Bytes used to push a tag, which must be able to hold any offset into
+    /// the code.
+    fn width(&self) -> Result<usize, Error> {
+        let mut width = 1;
+
+        while self.size_with(width)? > 1 << (8 * width) {
+            width += 1;
+        }
+
+        Ok(width)
+    }
+
+    fn size(&self) -> Result<usize, Error> {
+        self.size_with(self.width()?)
+    }
+
+    /// Render this assembly, and its sub-assemblies, into `modules`.
+    fn render(&self, stem: Option<&str>, modules: &mut Vec<Module>) -> Result<(), Error> {
+        let width = self.width()?;
+        let mut out = String::from("# Translated from `solc --asm` output.\n\n");
+
+        for item in &self.items {
+            match item {
+                Item::Op(spec) => writeln!(out, "{}", spec),
+                Item::Literal(v) => writeln!(out, "push{} 0x{}", v.len(), hex::encode(v)),
+                Item::Tag(t) => writeln!(out, "{}:\njumpdest", t),
+                Item::PushTag(t) | Item::DataOffset(t) => writeln!(out, "push{} {}", width, t),
+                Item::DataSize(name) => {
+                    let v = minimal(self.sub(name)?.size()?);
+                    writeln!(out, "push{} 0x{}", v.len(), hex::encode(v))
+                }
+            }
+            .unwrap();
+        }
+
+        for (name, sub) in &self.subs {
+            let sub_stem = match stem {
+                Some(s) => format!("{}.{}", s, name),
+                None => name.clone(),
+            };
+
+            writeln!(out, "\n{}:\n%include(\"{}.etk\")", name, sub_stem).unwrap();
+            sub.render(Some(&sub_stem), modules)?;
+        }
+
+        let stem = stem.unwrap_or("main");
+
+        if !self.auxdata.is_empty() {
+            let aux = format!("{}.auxdata.hex", stem);
+            writeln!(out, "\n%include_hex(\"{}\")", aux).unwrap();
+            // No trailing newline, since `%include_hex` doesn't allow one.
+            modules.push(Module {
+                path: aux.into(),
+                contents: hex::encode(&self.auxdata),
+            });
+        }
+
+        modules.push(Module {
+            path: format!("{}.etk", stem).into(),
+            contents: out,
+        });
+
+        Ok(())
+    }
+}
+
+/// The big-endian bytes of `value`, without leading zeros (but at least one
+/// byte.)
+fn minimal(value: usize) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    bytes[std::cmp::min(zeros, bytes.len() - 1)..].to_vec()
+}
+
+fn literal(text: &str) -> Option<Vec<u8>> {
+    let digits = text.strip_prefix("0x")?.trim_start_matches('0');
+
+    if digits.len() > 64 {
+        return None;
+    }
+
+    let padded = match digits.len() {
+        0 => "00".to_owned(),
+        n if n % 2 == 1 => format!("0{}", digits),
+        _ => digits.to_owned(),
+    };
+
+    hex::decode(padded).ok()
+}
+
+fn is_tag(text: &str) -> bool {
+    match text.strip_prefix("tag_") {
+        Some(n) => !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Parses one expression, like `0x40`, `tag_2`, `callvalue`, or
+/// `mstore(0x40, 0x80)`, pushing its items onto `items`.
+struct Expr<'a> {
+    text: &'a str,
+    line: usize,
+    full: &'a str,
+}
+
+impl<'a> Expr<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        self.text = self.text.trim_start();
+        match self.text.strip_prefix(c) {
+            Some(rest) => {
+                self.text = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        self.text = self.text.trim_start();
+        let end = self
+            .text
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.text.len());
+        let (word, rest) = self.text.split_at(end);
+        self.text = rest;
+        word
+    }
+
+    fn parse(&mut self, items: &mut Vec<Item>) -> Result<(), Error> {
+        let word = self.word();
+        ensure!(
+            !word.is_empty(),
+            error::Syntax {
+                line: self.line,
+                text: self.full,
+            }
+        );
+
+        if !self.eat('(') {
+            let item = if word.starts_with("0x") {
+                Item::Literal(literal(word).context(error::Syntax {
+                    line: self.line,
+                    text: self.full,
+                })?)
+            } else if is_tag(word) {
+                Item::PushTag(word.to_owned())
+            } else {
+                Item::Op(self.op(word)?)
+            };
+
+            items.push(item);
+            return Ok(());
+        }
+
+        match word {
+            "dataSize" | "dataOffset" => {
+                let name = self.word();
+                ensure!(
+                    name.starts_with("sub_") && self.eat(')'),
+                    error::Unsupported {
+                        line: self.line,
+                        text: self.full,
+                    }
+                );
+
+                items.push(match word {
+                    "dataSize" => Item::DataSize(name.to_owned()),
+                    _ => Item::DataOffset(name.to_owned()),
+                });
+
+                return Ok(());
+            }
+            "linkerSymbol" | "immutable" | "assignImmutable" | "deployTimeAddress"
+            | "bytecodeSize" | "PUSHLIB" => {
+                return error::Unsupported {
+                    line: self.line,
+                    text: word,
+                }
+                .fail()
+            }
+            _ => (),
+        }
+
+        let op = self.op(word)?;
+
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                let mut arg = Vec::new();
+                self.parse(&mut arg)?;
+                args.push(arg);
+
+                if self.eat(')') {
+                    break;
+                }
+
+                ensure!(
+                    self.eat(','),
+                    error::Syntax {
+                        line: self.line,
+                        text: self.full,
+                    }
+                );
+            }
+        }
+
+        // The first argument ends up on the top of the stack.
+        for arg in args.into_iter().rev() {
+            items.extend(arg);
+        }
+
+        items.push(Item::Op(op));
+        Ok(())
+    }
+
+    fn op(&self, word: &str) -> Result<Specifier, Error> {
+        let name = match word {
+            "sha3" => "keccak256",
+            "suicide" => "selfdestruct",
+            w => w,
+        };
+
+        match name.parse::<Specifier>() {
+            Ok(spec) if spec.size() == 1 => Ok(spec),
+            _ => error::Unsupported {
+                line: self.line,
+                text: word,
+            }
+            .fail(),
+        }
+    }
+}
+
+/// Remove `/* ... */` comments, which `solc` uses to show the source each
+/// item came from, and `//` comments.
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start..].find("*/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+
+    out.push_str(rest);
+
+    out.lines()
+        .map(|l| match l.find("//") {
+            Some(idx) => &l[..idx],
+            None => l,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Translate the output of `solc --asm`, for a single contract, into etk
+/// modules.
+///
+/// The root module is called `main.etk`. Sub-assemblies are named after their
+/// position, like `sub_0.etk` and `sub_0.sub_1.etk`.
+pub fn translate(asm: &str) -> Result<Vec<Module>, Error> {
+    let text = strip_comments(asm);
+
+    let mut stack = vec![(String::new(), Assembly::default())];
+    let mut seen_start = false;
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with("=======") {
+            continue;
+        }
+
+        if line == "EVM assembly:" {
+            ensure!(!seen_start, error::MultipleContracts);
+            seen_start = true;
+            continue;
+        }
+
+        if let Some(name) = line.strip_suffix(": assembly {") {
+            stack.push((name.to_owned(), Assembly::default()));
+            continue;
+        }
+
+        if line == "}" {
+            ensure!(stack.len() > 1, error::Unbalanced);
+            let (name, sub) = stack.pop().unwrap();
+            stack.last_mut().unwrap().1.subs.push((name, sub));
+            continue;
+        }
+
+        let current = &mut stack.last_mut().unwrap().1;
+
+        if let Some(aux) = line.strip_prefix("auxdata:") {
+            let aux = aux.trim();
+            let aux = aux.strip_prefix("0x").unwrap_or(aux);
+            current.auxdata = hex::decode(aux).ok().context(error::Syntax {
+                line: line_no,
+                text: line,
+            })?;
+            continue;
+        }
+
+        if let Some(tag) = line.strip_suffix(':') {
+            if is_tag(tag) {
+                current.items.push(Item::Tag(tag.to_owned()));
+                continue;
+            }
+
+            return error::Unsupported {
+                line: line_no,
+                text: line,
+            }
+            .fail();
+        }
+
+        let mut expr = Expr {
+            text: line,
+            line: line_no,
+            full: line,
+        };
+
+        expr.parse(&mut current.items)?;
+        ensure!(
+            expr.text.trim().is_empty(),
+            error::Syntax {
+                line: line_no,
+                text: line,
+            }
+        );
+    }
+
+    ensure!(stack.len() == 1, error::Unbalanced);
+
+    let (_, root) = stack.pop().unwrap();
+
+    let mut modules = Vec::new();
+    root.render(None, &mut modules)?;
+
+    // Put the root module first.
+    modules.reverse();
+    Ok(modules)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use crate::ingest::Ingest;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    /// Write `modules` to a temporary directory and assemble `main.etk`.
+    fn assemble(modules: &[Module]) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+
+        for module in modules {
+            std::fs::write(dir.path().join(&module.path), &module.contents).unwrap();
+        }
+
+        let mut output = Vec::new();
+        Ingest::new(&mut output)
+            .ingest_file(dir.path().join("main.etk"))
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn translate_items() {
+        let asm = r#"
+======= c.sol:C =======
+EVM assembly:
+    /* "c.sol":0:10  contract C { ... } */
+  mstore(0x40, 0x80)
+  callvalue
+  dup1
+  iszero
+  tag_1
+  jumpi
+  0x00
+  dup1
+  revert
+tag_1:
+  pop
+  shr(0xe0, calldataload(0x00))
+  jump(tag_1)	// in
+  sha3
+"#;
+
+        let modules = translate(asm).unwrap();
+        assert_eq!(modules.len(), 1);
+
+        let expected = hex!(
+            "
+            6080604052 34 80 15 600f 57 6000 80 fd
+            5b 50 6000 35 60e0 1c 600f 56 20
+            "
+        );
+
+        assert_eq!(assemble(&modules), expected);
+    }
+
+    #[test]
+    fn translate_subs() {
+        let asm = r#"
+EVM assembly:
+  dataSize(sub_0)
+  dup1
+  dataOffset(sub_0)
+  0x00
+  codecopy
+  0x00
+  return
+stop
+
+sub_0: assembly {
+      tag_1
+      jump
+    tag_1:
+      stop
+
+    sub_0: assembly {
+          stop
+    }
+
+    auxdata: 0xa1b2
+}
+"#;
+
+        let modules = translate(asm).unwrap();
+
+        let paths: Vec<_> = modules.iter().map(|m| m.path.to_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            [
+                "main.etk",
+                "sub_0.etk",
+                "sub_0.auxdata.hex",
+                "sub_0.sub_0.etk"
+            ]
+        );
+
+        // The runtime is `push1 3; jump; jumpdest; stop`, then the nested
+        // `stop`, then the auxdata: 8 bytes.
+        let runtime = hex!("6003565b0000a1b2");
+        let constructor = hex!("6008 80 600c 6000 39 6000 f3 00");
+
+        let mut expected = constructor.to_vec();
+        expected.extend_from_slice(&runtime);
+
+        assert_eq!(assemble(&modules), expected);
+    }
+
+    #[test]
+    fn tag_width_grows() {
+        let mut asm = String::from("EVM assembly:\ntag_1\ntag_1:\n");
+        for _ in 0..300 {
+            asm.push_str("pop\n");
+        }
+
+        let modules = translate(&asm).unwrap();
+        assert!(modules[0].contents.contains("push2 tag_1"));
+        assert_eq!(assemble(&modules)[..4], hex!("6100035b"));
+    }
+
+    #[test]
+    fn translate_errors() {
+        assert_matches!(
+            translate("PUSHLIB(\"lib.sol:L\")"),
+            Err(Error::Unsupported { line: 1, .. })
+        );
+
+        assert_matches!(
+            translate("mstore(0x40,"),
+            Err(Error::Syntax { line: 1, .. })
+        );
+
+        assert_matches!(
+            translate("\n\nfoo"),
+            Err(Error::Unsupported { line: 3, .. })
+        );
+
+        assert_matches!(translate("dataSize(sub_3)"), Err(Error::UnknownSub { .. }));
+
+        assert_matches!(
+            translate("sub_0: assembly {"),
+            Err(Error::Unbalanced { .. })
+        );
+
+        assert_matches!(
+            translate("EVM assembly:\nstop\nEVM assembly:\nstop"),
+            Err(Error::MultipleContracts { .. })
+        );
+    }
+}
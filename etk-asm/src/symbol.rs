@@ -0,0 +1,106 @@
+//! Interned label names.
+//!
+//! Label names are hashed once, when an instruction enters the assembler, and
+//! compared as integers from then on.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+
+/// A label name, as interned by an [`Interner`].
+///
+/// Symbols from different interners can't be compared meaningfully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Symbol(u32);
+
+/// A `HashMap` keyed by [`Symbol`], which skips the cost of a general purpose
+/// hash function.
+pub(crate) type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+
+/// A `HashSet` of [`Symbol`]. See [`SymbolMap`].
+pub(crate) type SymbolSet = HashSet<Symbol, BuildHasherDefault<SymbolHasher>>;
+
+/// Hashes a [`Symbol`] with a single multiplication.
+///
+/// Symbols are small sequential integers, so spreading them across the
+/// high bits is all a hash table needs.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(u64::from(*byte));
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+/// Assigns each distinct label name a [`Symbol`].
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+
+    /// The name of each symbol, indexed by its number.
+    names: Vec<Arc<str>>,
+}
+
+impl Interner {
+    /// The symbol for `name`, assigning a new one if it hasn't been seen.
+    pub(crate) fn intern(&mut self, name: &Arc<str>) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.symbols.len().try_into().expect("too many symbols"));
+        self.symbols.insert(name.clone(), symbol);
+        self.names.push(name.clone());
+        symbol
+    }
+
+    /// The name that was interned as `symbol`.
+    pub(crate) fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    /// The symbol for `name`, if it has been seen.
+    pub(crate) fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_same_name() {
+        let mut symbols = Interner::default();
+
+        let a = symbols.intern(&"a".into());
+        let b = symbols.intern(&"b".into());
+
+        assert_ne!(a, b);
+        assert_eq!(a, symbols.intern(&"a".into()));
+        assert_eq!(b, symbols.intern(&"b".into()));
+
+        assert_eq!(symbols.name(a), "a");
+        assert_eq!(symbols.name(b), "b");
+    }
+}
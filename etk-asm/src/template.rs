@@ -0,0 +1,90 @@
+//! Rendering [MiniJinja](https://docs.rs/minijinja) templates into assembly
+//! source, before it's parsed.
+
+use crate::Span;
+
+use minijinja::{Environment, UndefinedBehavior, Value};
+
+/// Why a template couldn't be rendered, and where in the template.
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) message: String,
+    pub(crate) span: Option<Span>,
+}
+
+/// The one-based line and column of the byte at `offset` in `text`.
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let start = before.rfind('\n').map(|n| n + 1).unwrap_or(0);
+
+    (line, before[start..].chars().count() + 1)
+}
+
+/// Render `template`, filling it in with the fields of `vars`.
+///
+/// Using a variable that isn't in `vars` is an error, so a typo doesn't
+/// quietly become an empty string.
+pub(crate) fn render(template: &str, vars: &serde_json::Value) -> Result<String, Failure> {
+    let mut env = Environment::new();
+    env.set_debug(true);
+    env.set_keep_trailing_newline(true);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+
+    let result = env
+        .template_from_str(template)
+        .and_then(|t| t.render(Value::from_serialize(vars)));
+
+    result.map_err(|err| {
+        let span = match (err.range(), err.line()) {
+            (Some(range), _) => Some(Span {
+                start: line_col(template, range.start),
+                end: line_col(template, range.end),
+            }),
+            (None, Some(line)) => Some(Span {
+                start: (line, 1),
+                end: (line, 1),
+            }),
+            (None, None) => None,
+        };
+
+        let message = match err.detail() {
+            Some(detail) => format!("{}: {}", err.kind(), detail),
+            None => err.kind().to_string(),
+        };
+
+        Failure { message, span }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn render_table() {
+        let template = "{% for fee in fees %}push2 {{ fee }}\n{% endfor %}stop\n";
+        let text = render(template, &json!({ "fees": [100, 300, 500] })).unwrap();
+
+        assert_eq!(text, "push2 100\npush2 300\npush2 500\nstop\n");
+    }
+
+    #[test]
+    fn render_errors() {
+        let failure = render("push1 1\npush1 {{ fee }}\n", &json!({})).unwrap_err();
+        assert!(failure.message.starts_with("undefined value"));
+        assert_eq!(
+            failure.span,
+            Some(Span {
+                start: (2, 10),
+                end: (2, 13),
+            })
+        );
+
+        let failure = render("push1 1\n{% if %}\n", &json!({})).unwrap_err();
+        assert!(failure.message.starts_with("syntax error"));
+        assert_eq!(failure.span.unwrap().start.0, 2);
+    }
+}
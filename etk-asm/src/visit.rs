@@ -0,0 +1,303 @@
+//! Traits for walking over, and rewriting, sequences of instructions.
+//!
+//! [`Visit`] and [`Fold`] have a method for each kind of item that can appear
+//! in an [`AbstractOp`] or [`RawOp`], with defaults that walk into its
+//! contents. Implementations override only the methods for the items they
+//! care about, so they keep working as instructions are added.
+//!
+//! The `walk_*` and `fold_*` functions are the default behaviour of each
+//! method, for implementations that want to do something and then carry on
+//! walking.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use etk_asm::ops::{AbstractOp, Imm, Op};
+//! use etk_asm::visit::Visit;
+//!
+//! use std::sync::Arc;
+//!
+//! #[derive(Default)]
+//! struct Used(Vec<Arc<str>>);
+//!
+//! impl Visit for Used {
+//!     fn visit_label_use(&mut self, label: &Arc<str>) {
+//!         self.0.push(label.clone());
+//!     }
+//! }
+//!
+//! let ops = vec![
+//!     AbstractOp::Op(Op::Push2(Imm::from("start"))),
+//!     AbstractOp::Op(Op::Jump),
+//!     AbstractOp::Label("start".into()),
+//! ];
+//!
+//! let mut used = Used::default();
+//! for op in &ops {
+//!     used.visit_abstract_op(op);
+//! }
+//!
+//! assert_eq!(used.0, [Arc::from("start")]);
+//! ```
+
+use crate::asm::RawOp;
+use crate::ops::{AbstractOp, Imm, Op};
+
+use std::sync::Arc;
+
+/// Inspects instructions, without changing them.
+pub trait Visit {
+    /// Visit an item to be assembled.
+    fn visit_raw_op(&mut self, op: &RawOp) {
+        walk_raw_op(self, op)
+    }
+
+    /// Visit an instruction, label, or variable sized push.
+    fn visit_abstract_op(&mut self, op: &AbstractOp) {
+        walk_abstract_op(self, op)
+    }
+
+    /// Visit an instruction.
+    fn visit_op(&mut self, op: &Op) {
+        walk_op(self, op)
+    }
+
+    /// Visit a variable sized push, as written with `%push`.
+    fn visit_push(&mut self, imm: &Imm<Vec<u8>>) {
+        walk_push(self, imm)
+    }
+
+    /// Visit the declaration of a label.
+    fn visit_label(&mut self, _label: &Arc<str>) {}
+
+    /// Visit a label used as an immediate.
+    fn visit_label_use(&mut self, _label: &Arc<str>) {}
+
+    /// Visit bytes to be included verbatim.
+    fn visit_raw(&mut self, _raw: &[u8]) {}
+
+    /// Visit padding up to `offset`, as written with `%org`.
+    fn visit_org(&mut self, _offset: u32, _fill: u8) {}
+}
+
+/// Visit the contents of `op`.
+pub fn walk_raw_op<V>(visitor: &mut V, op: &RawOp)
+where
+    V: Visit + ?Sized,
+{
+    match op {
+        RawOp::Op(op) => visitor.visit_abstract_op(op),
+        RawOp::Raw(raw) => visitor.visit_raw(raw),
+        RawOp::Org { offset, fill } => visitor.visit_org(*offset, *fill),
+    }
+}
+
+/// Visit the contents of `op`.
+pub fn walk_abstract_op<V>(visitor: &mut V, op: &AbstractOp)
+where
+    V: Visit + ?Sized,
+{
+    match op {
+        AbstractOp::Op(op) => visitor.visit_op(op),
+        AbstractOp::Label(label) => visitor.visit_label(label),
+        AbstractOp::Push(imm) => visitor.visit_push(imm),
+    }
+}
+
+/// Visit the label used by `op`, if it has one.
+pub fn walk_op<V>(visitor: &mut V, op: &Op)
+where
+    V: Visit + ?Sized,
+{
+    if let Some(label) = op.shared_label() {
+        visitor.visit_label_use(label);
+    }
+}
+
+/// Visit the label pushed by `imm`, if it is one.
+pub fn walk_push<V>(visitor: &mut V, imm: &Imm<Vec<u8>>)
+where
+    V: Visit + ?Sized,
+{
+    if let Imm::Label(label) = imm {
+        visitor.visit_label_use(label);
+    }
+}
+
+/// Rewrites instructions, one at a time.
+pub trait Fold {
+    /// Rewrite an item to be assembled.
+    fn fold_raw_op(&mut self, op: RawOp) -> RawOp {
+        fold_raw_op(self, op)
+    }
+
+    /// Rewrite an instruction, label, or variable sized push.
+    fn fold_abstract_op(&mut self, op: AbstractOp) -> AbstractOp {
+        fold_abstract_op(self, op)
+    }
+
+    /// Rewrite an instruction.
+    fn fold_op(&mut self, op: Op) -> Op {
+        fold_op(self, op)
+    }
+
+    /// Rewrite a variable sized push, as written with `%push`.
+    fn fold_push(&mut self, imm: Imm<Vec<u8>>) -> Imm<Vec<u8>> {
+        fold_push(self, imm)
+    }
+
+    /// Rewrite the name of a declared label.
+    fn fold_label(&mut self, label: Arc<str>) -> Arc<str> {
+        label
+    }
+
+    /// Rewrite a label used as an immediate.
+    fn fold_label_use(&mut self, label: Arc<str>) -> Arc<str> {
+        label
+    }
+
+    /// Rewrite bytes to be included verbatim.
+    fn fold_raw(&mut self, raw: Vec<u8>) -> Vec<u8> {
+        raw
+    }
+}
+
+/// Rewrite the contents of `op`.
+pub fn fold_raw_op<F>(folder: &mut F, op: RawOp) -> RawOp
+where
+    F: Fold + ?Sized,
+{
+    match op {
+        RawOp::Op(op) => RawOp::Op(folder.fold_abstract_op(op)),
+        RawOp::Raw(raw) => RawOp::Raw(folder.fold_raw(raw)),
+        RawOp::Org { .. } => op,
+    }
+}
+
+/// Rewrite the contents of `op`.
+pub fn fold_abstract_op<F>(folder: &mut F, op: AbstractOp) -> AbstractOp
+where
+    F: Fold + ?Sized,
+{
+    match op {
+        AbstractOp::Op(op) => AbstractOp::Op(folder.fold_op(op)),
+        AbstractOp::Label(label) => AbstractOp::Label(folder.fold_label(label)),
+        AbstractOp::Push(imm) => AbstractOp::Push(folder.fold_push(imm)),
+    }
+}
+
+/// Rewrite the label used by `op`, if it has one.
+pub fn fold_op<F>(folder: &mut F, op: Op) -> Op
+where
+    F: Fold + ?Sized,
+{
+    match op.shared_label() {
+        Some(label) => {
+            let label = folder.fold_label_use(label.clone());
+            Op::with_label(op.specifier(), label)
+        }
+        None => op,
+    }
+}
+
+/// Rewrite the label pushed by `imm`, if it is one.
+pub fn fold_push<F>(folder: &mut F, imm: Imm<Vec<u8>>) -> Imm<Vec<u8>>
+where
+    F: Fold + ?Sized,
+{
+    match imm {
+        Imm::Label(label) => Imm::Label(folder.fold_label_use(label)),
+        Imm::Constant(_) => imm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Count {
+        ops: usize,
+        declared: usize,
+        used: usize,
+        raw: usize,
+    }
+
+    impl Visit for Count {
+        fn visit_op(&mut self, op: &Op) {
+            self.ops += 1;
+            walk_op(self, op);
+        }
+
+        fn visit_label(&mut self, _: &Arc<str>) {
+            self.declared += 1;
+        }
+
+        fn visit_label_use(&mut self, _: &Arc<str>) {
+            self.used += 1;
+        }
+
+        fn visit_raw(&mut self, raw: &[u8]) {
+            self.raw += raw.len();
+        }
+    }
+
+    fn ops() -> Vec<RawOp> {
+        vec![
+            RawOp::Op(AbstractOp::Push("b".into())),
+            RawOp::Op(AbstractOp::Label("a".into())),
+            RawOp::Op(AbstractOp::Op(Op::Push1(Imm::from("a")))),
+            RawOp::Op(AbstractOp::Op(Op::Jump)),
+            RawOp::Raw(vec![0xaa, 0xbb]),
+            RawOp::Org { offset: 8, fill: 0 },
+            RawOp::Op(AbstractOp::Label("b".into())),
+        ]
+    }
+
+    #[test]
+    fn visit_counts() {
+        let mut count = Count::default();
+        for op in &ops() {
+            count.visit_raw_op(op);
+        }
+
+        assert_eq!(count.ops, 2);
+        assert_eq!(count.declared, 2);
+        assert_eq!(count.used, 2);
+        assert_eq!(count.raw, 2);
+    }
+
+    struct Prefix;
+
+    impl Fold for Prefix {
+        fn fold_label(&mut self, label: Arc<str>) -> Arc<str> {
+            format!("m.{}", label).into()
+        }
+
+        fn fold_label_use(&mut self, label: Arc<str>) -> Arc<str> {
+            format!("m.{}", label).into()
+        }
+    }
+
+    #[test]
+    fn fold_renames() {
+        let folded: Vec<_> = ops()
+            .into_iter()
+            .map(|op| Prefix.fold_raw_op(op))
+            .filter_map(|op| match op {
+                RawOp::Op(op) => Some(op),
+                _ => None,
+            })
+            .collect();
+
+        let expected = [
+            AbstractOp::Push("m.b".into()),
+            AbstractOp::Label("m.a".into()),
+            AbstractOp::Op(Op::Push1(Imm::from("m.a"))),
+            AbstractOp::Op(Op::Jump),
+            AbstractOp::Label("m.b".into()),
+        ];
+
+        assert_eq!(folded, expected);
+    }
+}
@@ -0,0 +1,212 @@
+//! End-to-end tests driven by the files in `tests/golden`.
+//!
+//! Each `.etk` file directly inside `tests/golden` is assembled, and the
+//! result is compared against the file next to it with the same name and:
+//!
+//!  - a `.hex` extension, holding the expected bytecode; or
+//!  - an `.err` extension, holding the expected error's code and the first
+//!    line of its message, like `error[E0001]: parsing failed`.
+//!
+//! Only the first line of an error is compared, so changes to how causes are
+//! rendered (like the list of rules a syntax error expected) don't break
+//! unrelated cases.
+//!
+//! Run with `ETK_BLESS=1` to write the expected files from the actual results,
+//! then review the changes before committing them.
+
+use etk_asm::disasm::Disassembler;
+use etk_asm::ingest::Ingest;
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that, when set, overwrites the expected files.
+const BLESS: &str = "ETK_BLESS";
+
+/// Number of instructions to show before the first difference.
+const CONTEXT: usize = 3;
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+/// What assembling a case produced.
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Code(Vec<u8>),
+    Error(String),
+}
+
+fn assemble(path: &Path) -> Outcome {
+    let mut output = Vec::new();
+    let mut ingest = Ingest::new(&mut output);
+
+    let err = match ingest.ingest_file(path) {
+        Ok(()) => return Outcome::Code(output),
+        Err(e) => e,
+    };
+
+    let message = err.to_string();
+    let first = message.lines().next().unwrap_or_default();
+
+    // Keep absolute paths out of the expected files.
+    let root = root();
+    let first = first.replace(&*root.to_string_lossy(), ".");
+
+    let message = format!("error[{}]: {}", err.code(), first);
+
+    Outcome::Error(message)
+}
+
+fn expected(case: &Path) -> Option<Outcome> {
+    if let Ok(text) = fs::read_to_string(case.with_extension("hex")) {
+        let digits: String = text.split_whitespace().collect();
+        let code = hex::decode(&digits)
+            .unwrap_or_else(|e| panic!("{}: invalid hex: {}", case.display(), e));
+        return Some(Outcome::Code(code));
+    }
+
+    if let Ok(text) = fs::read_to_string(case.with_extension("err")) {
+        return Some(Outcome::Error(text.trim_end().to_owned()));
+    }
+
+    None
+}
+
+fn bless(case: &Path, actual: &Outcome) {
+    let hex_path = case.with_extension("hex");
+    let err_path = case.with_extension("err");
+
+    let _ = fs::remove_file(&hex_path);
+    let _ = fs::remove_file(&err_path);
+
+    match actual {
+        Outcome::Code(code) => fs::write(hex_path, format!("{}\n", hex::encode(code))),
+        Outcome::Error(msg) => fs::write(err_path, format!("{}\n", msg)),
+    }
+    .unwrap();
+}
+
+/// Disassemble `code` into one line per instruction. Any truncated
+/// instruction at the end is shown as raw bytes.
+fn listing(code: &[u8]) -> Vec<String> {
+    let mut dasm = Disassembler::new();
+    dasm.write_all(code).unwrap();
+
+    let mut lines = Vec::new();
+    let mut end = 0;
+
+    for off in dasm.ops() {
+        end = off.offset + off.item.size() as usize;
+        lines.push(off.to_string());
+    }
+
+    if end < code.len() {
+        lines.push(format!("{: >4x}:   0x{}", end, hex::encode(&code[end..])));
+    }
+
+    lines
+}
+
+/// Show where `actual` starts to differ from `expected`, as disassembly.
+fn diff_code(expected: &[u8], actual: &[u8]) -> String {
+    let expected_lines = listing(expected);
+    let actual_lines = listing(actual);
+
+    let first = expected_lines
+        .iter()
+        .zip(&actual_lines)
+        .take_while(|(e, a)| e == a)
+        .count();
+
+    let start = first.saturating_sub(CONTEXT);
+
+    let mut out = String::new();
+    writeln!(out, "expected: {}", hex::encode(expected)).unwrap();
+    writeln!(out, "actual:   {}", hex::encode(actual)).unwrap();
+
+    for line in &expected_lines[start..first] {
+        writeln!(out, "      {}", line).unwrap();
+    }
+
+    for line in &expected_lines[first..] {
+        writeln!(out, "    - {}", line).unwrap();
+    }
+
+    for line in &actual_lines[first..] {
+        writeln!(out, "    + {}", line).unwrap();
+    }
+
+    out
+}
+
+fn describe(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Code(code) => format!("bytecode {}\n", hex::encode(code)),
+        Outcome::Error(msg) => format!("error:\n{}\n", msg),
+    }
+}
+
+/// Explain how `actual` differs from `expected`.
+fn diff(expected: &Outcome, actual: &Outcome) -> String {
+    match (expected, actual) {
+        (Outcome::Code(e), Outcome::Code(a)) => diff_code(e, a),
+        (Outcome::Error(e), Outcome::Error(a)) => {
+            let mut out = String::new();
+            for line in e.lines() {
+                writeln!(out, "    - {}", line).unwrap();
+            }
+            for line in a.lines() {
+                writeln!(out, "    + {}", line).unwrap();
+            }
+            out
+        }
+        _ => format!("expected {}actual {}", describe(expected), describe(actual)),
+    }
+}
+
+fn cases() -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(root())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file() && path.extension() == Some(OsStr::new("etk")))
+        .collect();
+
+    paths.sort();
+    paths
+}
+
+#[test]
+fn golden() {
+    let bless_mode = env::var_os(BLESS).is_some();
+    let mut report = String::new();
+
+    for case in cases() {
+        let actual = assemble(&case);
+        let name = case.file_name().unwrap().to_string_lossy();
+
+        if bless_mode {
+            bless(&case, &actual);
+            continue;
+        }
+
+        match expected(&case) {
+            None => writeln!(
+                report,
+                "{}: no `.hex` or `.err` file (run with {}=1 to create one)",
+                name, BLESS
+            )
+            .unwrap(),
+            Some(e) if e == actual => (),
+            Some(e) => writeln!(report, "{}:\n{}", name, diff(&e, &actual)).unwrap(),
+        }
+    }
+
+    assert!(report.is_empty(), "golden files differ:\n\n{}", report);
+}
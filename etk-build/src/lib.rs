@@ -0,0 +1,318 @@
+//! Helpers for embedding assembled EVM bytecode in Rust crates.
+//!
+//! Call [`assemble_dir`] from a build script to assemble every `.etk` file in
+//! a directory, and generate a Rust module for each:
+//!
+//! ```no_run
+//! // In `main` of build.rs:
+//! let out_dir = std::env::var_os("OUT_DIR").unwrap();
+//! etk_build::assemble_dir("asm/", out_dir).unwrap();
+//! ```
+//!
+//! Then include the generated code in the crate:
+//!
+//! ```ignore
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/etk.rs"));
+//!
+//! // Assembled from `asm/counter.etk`.
+//! let code: &[u8] = counter::CODE;
+//! let start: usize = counter::labels::START;
+//! ```
+//!
+//! Cargo is told to run the build script again whenever the directory, or any
+//! file imported or included while assembling, changes.
+#![deny(unsafe_code)]
+#![deny(missing_docs)]
+#![deny(unreachable_pub)]
+#![deny(missing_debug_implementations)]
+
+mod error {
+    use etk_asm::ingest::Error as IngestError;
+
+    use snafu::{Backtrace, Snafu};
+
+    use std::path::PathBuf;
+
+    /// Errors that may arise while assembling a directory.
+    #[derive(Debug, Snafu)]
+    #[non_exhaustive]
+    #[snafu(visibility = "pub(super)")]
+    pub enum Error {
+        /// An i/o error.
+        #[snafu(display("an i/o error occurred on path `{}`: {}", path.display(), source))]
+        #[non_exhaustive]
+        Io {
+            /// The underlying source of this error.
+            source: std::io::Error,
+
+            /// The path where the error occurred.
+            path: PathBuf,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// A source file couldn't be assembled.
+        #[snafu(display("assembling `{}` failed: {}", path.display(), source))]
+        #[non_exhaustive]
+        Assemble {
+            /// The underlying source of this error.
+            #[snafu(backtrace, source(from(IngestError, Box::new)))]
+            source: Box<IngestError>,
+
+            /// The source file that failed.
+            path: PathBuf,
+        },
+
+        /// Two names would have become the same Rust identifier.
+        #[snafu(display(
+            "`{}` and `{}` in `{}` would both be named `{}`",
+            first,
+            second,
+            path.display(),
+            ident
+        ))]
+        #[non_exhaustive]
+        DuplicateName {
+            /// The first name.
+            first: String,
+
+            /// The second name.
+            second: String,
+
+            /// The identifier they'd share.
+            ident: String,
+
+            /// The directory or source file the names are from.
+            path: PathBuf,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+    }
+}
+
+pub use self::error::Error;
+
+use etk_asm::ingest::{AssemblerContext, SymbolKind};
+
+use snafu::ResultExt;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Name of the file, in the output directory, that the generated code is
+/// written to.
+pub const OUTPUT: &str = "etk.rs";
+
+/// Assemble every `.etk` file directly inside `dir`, and write a Rust module
+/// for each to [`OUTPUT`] in `out_dir`.
+///
+/// The module for `dir/foo-bar.etk` is named `foo_bar`, and contains:
+///
+///  - `CODE`, a `&[u8]` with the assembled bytecode; and
+///  - `labels`, a module with a `usize` constant for the offset of each label,
+///    named in upper case.
+///
+/// Files in subdirectories aren't assembled on their own, so they can hold
+/// code that's only meant to be imported or included.
+///
+/// Meant to be called from a build script: it prints `cargo:rerun-if-changed`
+/// for `dir` and for every file read while assembling, and prints assembler
+/// warnings as `cargo:warning`.
+pub fn assemble_dir<D, O>(dir: D, out_dir: O) -> Result<(), Error>
+where
+    D: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    assemble_dir_with(&AssemblerContext::new(), dir, out_dir)
+}
+
+/// Like [`assemble_dir`], but assembling with the dialect, fork, and defines
+/// in `context`.
+pub fn assemble_dir_with<D, O>(context: &AssemblerContext, dir: D, out_dir: O) -> Result<(), Error>
+where
+    D: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut modules: BTreeMap<String, (String, Module)> = BTreeMap::new();
+    for path in sources(dir)? {
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let ident = module_name(&stem);
+
+        if let Some((first, _)) = modules.get(&ident) {
+            return error::DuplicateName {
+                first: first.clone(),
+                second: stem,
+                ident,
+                path: dir,
+            }
+            .fail();
+        }
+
+        let module = assemble(context, &path)?;
+        modules.insert(ident, (stem, module));
+    }
+
+    let mut generated = String::new();
+    for (ident, (_, module)) in &modules {
+        generated.push_str(&module.render(ident));
+    }
+
+    let out = out_dir.as_ref().join(OUTPUT);
+    std::fs::write(&out, generated).context(error::Io { path: out })
+}
+
+/// The `.etk` files directly inside `dir`, in a stable order.
+fn sources(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = std::fs::read_dir(dir).context(error::Io { path: dir })?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.context(error::Io { path: dir })?.path();
+        if path.is_file() && path.extension() == Some("etk".as_ref()) {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// An assembled source file, ready to be written out as Rust.
+#[derive(Debug)]
+struct Module {
+    path: PathBuf,
+    code: Vec<u8>,
+    labels: BTreeMap<String, usize>,
+}
+
+fn assemble(context: &AssemblerContext, path: &Path) -> Result<Module, Error> {
+    let mut code = Vec::new();
+    let mut ingest = context.ingest(&mut code);
+    let result = ingest.ingest_file(path);
+
+    for file in ingest.files() {
+        println!("cargo:rerun-if-changed={}", file.display());
+    }
+
+    result.context(error::Assemble { path })?;
+
+    for warning in ingest.warnings() {
+        let file = warning.file.as_deref().unwrap_or(path);
+
+        match warning.span {
+            Some(span) => println!(
+                "cargo:warning={}:{}:{}: {}",
+                file.display(),
+                span.start.0,
+                span.start.1,
+                warning
+            ),
+            None => println!("cargo:warning={}: {}", file.display(), warning),
+        }
+    }
+
+    let mut labels: BTreeMap<String, (String, usize)> = BTreeMap::new();
+    for symbol in ingest.symbols() {
+        if symbol.kind != SymbolKind::Label {
+            continue;
+        }
+
+        let ident = const_name(&symbol.name);
+        match labels.get(&ident) {
+            Some((first, _)) if *first != symbol.name => {
+                return error::DuplicateName {
+                    first: first.clone(),
+                    second: symbol.name.clone(),
+                    ident,
+                    path,
+                }
+                .fail();
+            }
+            _ => {
+                labels.insert(ident, (symbol.name.clone(), symbol.offset));
+            }
+        }
+    }
+
+    drop(ingest);
+
+    Ok(Module {
+        path: path.to_owned(),
+        code,
+        labels: labels.into_iter().map(|(k, (_, v))| (k, v)).collect(),
+    })
+}
+
+impl Module {
+    fn render(&self, ident: &str) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "/// Assembled from `{}`.", self.path.display()).unwrap();
+        writeln!(out, "pub mod {} {{", ident).unwrap();
+        writeln!(out, "    /// The assembled bytecode.").unwrap();
+        writeln!(out, "    pub const CODE: &[u8] = &[").unwrap();
+        for chunk in self.code.chunks(16) {
+            let bytes: Vec<_> = chunk.iter().map(|b| format!("0x{:02x},", b)).collect();
+            writeln!(out, "        {}", bytes.join(" ")).unwrap();
+        }
+        writeln!(out, "    ];").unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "    /// Offsets of the labels, in bytes from the start of `CODE`."
+        )
+        .unwrap();
+        writeln!(out, "    pub mod labels {{").unwrap();
+        for (name, offset) in &self.labels {
+            writeln!(out, "        pub const {}: usize = {};", name, offset).unwrap();
+        }
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+
+        out
+    }
+}
+
+/// Replace anything that can't be in a Rust identifier with `_`.
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        ident.insert(0, '_');
+    }
+
+    ident
+}
+
+/// Rust keywords that could be a module name.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The name of the module generated for a file named `stem`.
+fn module_name(stem: &str) -> String {
+    let mut ident = identifier(stem).to_ascii_lowercase();
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+/// The name of the constant generated for a label named `label`.
+fn const_name(label: &str) -> String {
+    identifier(label).to_ascii_uppercase()
+}
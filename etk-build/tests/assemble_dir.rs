@@ -0,0 +1,54 @@
+use assert_matches::assert_matches;
+
+use etk_build::{assemble_dir, Error, OUTPUT};
+
+use std::fs;
+
+#[test]
+fn generates_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let asm = dir.path().join("asm");
+    fs::create_dir_all(asm.join("lib")).unwrap();
+
+    fs::write(asm.join("lib").join("two.etk"), "push1 2\n").unwrap();
+    fs::write(
+        asm.join("counter.etk"),
+        "push1 1\nstart:\njumpdest\n%include(\"lib/two.etk\")\n",
+    )
+    .unwrap();
+    fs::write(asm.join("my-proxy.etk"), "caller\n").unwrap();
+    fs::write(asm.join("notes.txt"), "not assembly").unwrap();
+
+    assemble_dir(&asm, dir.path()).unwrap();
+
+    let generated = fs::read_to_string(dir.path().join(OUTPUT)).unwrap();
+
+    assert!(generated.contains("pub mod counter {"));
+    assert!(generated.contains("        0x60, 0x01, 0x5b, 0x60, 0x02,\n"));
+    assert!(generated.contains("        pub const START: usize = 2;\n"));
+
+    assert!(generated.contains("pub mod my_proxy {"));
+    assert!(generated.contains("        0x33,\n"));
+
+    assert!(!generated.contains("pub mod two"));
+    assert!(!generated.contains("notes"));
+}
+
+#[test]
+fn duplicate_module() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a-b.etk"), "stop\n").unwrap();
+    fs::write(dir.path().join("a_b.etk"), "stop\n").unwrap();
+
+    let err = assemble_dir(dir.path(), dir.path()).unwrap_err();
+    assert_matches!(err, Error::DuplicateName { ident, .. } if ident == "a_b");
+}
+
+#[test]
+fn assemble_failed() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("bad.etk"), "pusj1 1\n").unwrap();
+
+    let err = assemble_dir(dir.path(), dir.path()).unwrap_err();
+    assert_matches!(err, Error::Assemble { .. });
+}
@@ -2,22 +2,109 @@
 
 use crate::parse::Hex;
 
+use std::fmt;
 use std::fs::File;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use structopt::StructOpt;
 
+/// The path that stands for standard input or standard output.
+pub const STDIO: &str = "-";
+
+/// Returns true if `path` is `-`, meaning standard input or standard output.
+pub fn is_stdio<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref() == Path::new(STDIO)
+}
+
+/// Open `path` for reading, or standard input if `path` is `-`.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn io::Read>, io::Error> {
+    let path = path.as_ref();
+    if is_stdio(path) {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// Read all of `path`, or standard input if `path` is `-`, as hexadecimal.
+pub fn read_hex<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, io::Error> {
+    let mut bytes = Vec::new();
+    HexRead::new(open_input(path)?).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Create `path` for writing, or use standard output if `path` is `-`.
+pub fn create_output<P: AsRef<Path>>(path: P) -> Result<Box<dyn io::Write>, io::Error> {
+    let path = path.as_ref();
+    if is_stdio(path) {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(File::create(path)?))
+    }
+}
+
+/// How a stream of bytes is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Hexadecimal text. When reading, a `0x` prefix and whitespace are
+    /// allowed.
+    Hex,
+
+    /// Raw binary bytes.
+    Binary,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Hex => write!(f, "hex"),
+            Format::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Error returned when parsing an unrecognized [`Format`].
+#[derive(Debug)]
+pub struct UnknownFormatError(String);
+
+impl fmt::Display for UnknownFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown format `{}` (expected `hex` or `binary`)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownFormatError {}
+
+impl FromStr for Format {
+    type Err = UnknownFormatError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "hex" => Ok(Format::Hex),
+            "binary" | "bin" => Ok(Format::Binary),
+            _ => Err(UnknownFormatError(text.to_owned())),
+        }
+    }
+}
+
 /// Command-line options describing an input source, either from a file or
 /// directly from the command line.
+///
+/// Any of the paths may be `-` to read from standard input.
 #[derive(Debug, StructOpt)]
 pub struct InputSource {
     #[structopt(
         long = "bin-file",
         short = "b",
         help = "path to input data, as raw binary data",
-        conflicts_with_all(&["hex-file", "code"]),
-        required_unless_one(&["hex-file", "code"]),
+        conflicts_with_all(&["hex-file", "code", "input"]),
+        required_unless_one(&["hex-file", "code", "input"]),
     )]
     bin_file: Option<PathBuf>,
 
@@ -25,7 +112,7 @@ pub struct InputSource {
         long = "hex-file",
         short = "x",
         help = "path to input data, encoded in hexadecimal format",
-        conflicts_with = "code"
+        conflicts_with_all(&["code", "input"])
     )]
     hex_file: Option<PathBuf>,
 
@@ -35,27 +122,46 @@ pub struct InputSource {
         help = "input data, encoded in hexadecimal format (with 0x prefix)"
     )]
     code: Option<Hex<Vec<u8>>>,
+
+    #[structopt(
+        parse(from_os_str),
+        conflicts_with = "code",
+        help = "path to input data (`-` for standard input), encoded as given by --input-format"
+    )]
+    input: Option<PathBuf>,
+
+    #[structopt(
+        long = "input-format",
+        default_value = "hex",
+        possible_values(&["hex", "binary"]),
+        help = "encoding of the positional input"
+    )]
+    input_format: Format,
 }
 
 impl InputSource {
     /// Convert `self` into something that implements `std::io::Read`.
     pub fn open(self) -> Result<impl io::Read, io::Error> {
-        let boxed: Box<dyn io::Read> = match (self.bin_file, self.hex_file, self.code) {
-            (Some(bin), None, None) => Box::new(Self::bin(bin)?),
-            (None, Some(hex), None) => Box::new(Self::hex(hex)?),
-            (None, None, Some(code)) => Box::new(Self::code(code.0)),
+        let boxed: Box<dyn io::Read> = match (self.bin_file, self.hex_file, self.code, self.input) {
+            (Some(bin), None, None, None) => Self::bin(bin)?,
+            (None, Some(hex), None, None) => Box::new(Self::hex(hex)?),
+            (None, None, Some(code), None) => Box::new(Self::code(code.0)),
+            (None, None, None, Some(input)) => match self.input_format {
+                Format::Hex => Box::new(Self::hex(input)?),
+                Format::Binary => Self::bin(input)?,
+            },
             _ => unreachable!(),
         };
 
         Ok(boxed)
     }
 
-    fn bin(path: PathBuf) -> Result<File, io::Error> {
-        File::open(path)
+    fn bin(path: PathBuf) -> Result<Box<dyn io::Read>, io::Error> {
+        open_input(path)
     }
 
-    fn hex(path: PathBuf) -> Result<HexRead<File>, io::Error> {
-        Ok(HexRead::new(File::open(path)?))
+    fn hex(path: PathBuf) -> Result<HexRead<Box<dyn io::Read>>, io::Error> {
+        Ok(HexRead::new(open_input(path)?))
     }
 
     fn code(code: Vec<u8>) -> io::Cursor<Vec<u8>> {
@@ -149,12 +255,14 @@ where
         loop {
             let read = self.file.read(&mut hexbuffer[available..])?;
             eof = 0 == read;
-            available += read;
+
+            // Hex copied from elsewhere is often wrapped, or indented.
+            available += strip_whitespace(&mut hexbuffer[available..available + read]);
 
             // Check for the 0x prefix, if we have read less than 2 bytes.
             if self.first_read && available >= 2 {
                 self.first_read = false;
-                if b"0x" == &hexbuffer[..2] {
+                if hexbuffer[..2].eq_ignore_ascii_case(b"0x") {
                     available -= 2;
 
                     if hexbuffer.len() > 2 {
@@ -169,13 +277,9 @@ where
         }
 
         if eof && 1 == available {
-            if char::from(hexbuffer[0]).is_whitespace() {
-                available = 0;
-            } else {
-                let kind = io::ErrorKind::InvalidData;
-                let src = hex::FromHexError::OddLength;
-                return Err(io::Error::new(kind, src));
-            }
+            let kind = io::ErrorKind::InvalidData;
+            let src = hex::FromHexError::OddLength;
+            return Err(io::Error::new(kind, src));
         } else if available % 2 == 0 {
             self.remainder = None;
         } else {
@@ -196,6 +300,21 @@ where
     }
 }
 
+/// Move the bytes of `buf` that aren't ASCII whitespace to its start,
+/// returning how many there are.
+fn strip_whitespace(buf: &mut [u8]) -> usize {
+    let mut len = 0;
+
+    for idx in 0..buf.len() {
+        if !buf[idx].is_ascii_whitespace() {
+            buf[len] = buf[idx];
+            len += 1;
+        }
+    }
+
+    len
+}
+
 #[cfg(test)]
 mod tests {
     use hex_literal::hex;
@@ -234,6 +353,43 @@ mod tests {
         assert_eq!(err.kind, ErrorKind::ArgumentConflict);
     }
 
+    #[test]
+    fn input_source_positional_conflicts_with_bin_file() {
+        let args = &["exe", "--bin-file", "floop", "-"];
+        let err = InputSource::from_iter_safe(args).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn input_source_positional() {
+        let args = &["exe", "-", "--input-format", "binary"];
+        let src = InputSource::from_iter_safe(args).unwrap();
+        assert_eq!(src.input.as_deref(), Some(Path::new(STDIO)));
+        assert_eq!(src.input_format, Format::Binary);
+    }
+
+    #[test]
+    fn input_source_positional_defaults_to_hex() {
+        let args = &["exe", "contract.hex"];
+        let src = InputSource::from_iter_safe(args).unwrap();
+        assert_eq!(src.input_format, Format::Hex);
+    }
+
+    #[test]
+    fn format_from_str() {
+        assert_eq!("hex".parse::<Format>().unwrap(), Format::Hex);
+        assert_eq!("binary".parse::<Format>().unwrap(), Format::Binary);
+        assert_eq!("bin".parse::<Format>().unwrap(), Format::Binary);
+        assert!("base64".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn stdio_path() {
+        assert!(is_stdio("-"));
+        assert!(!is_stdio("./-"));
+        assert!(!is_stdio("out.hex"));
+    }
+
     #[test]
     fn hex_read_with_prefix_empty() {
         let data = b"0x";
@@ -270,6 +426,27 @@ mod tests {
         assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 
+    #[test]
+    fn hex_read_whitespace_and_case() {
+        let data = b"  0XABcd\n  ef01\r\n2345 6789\n";
+        let mut decoder = HexRead::new(&data[..]);
+
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out).unwrap();
+        assert_eq!(out, hex!("abcdef0123456789"));
+
+        // Whitespace can split a byte, even across reads.
+        let data = b"a\nb c\td";
+        let mut decoder = HexRead::new(&data[..]);
+
+        let mut buf = [0u8];
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 0xab);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 0xcd);
+        assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+    }
+
     #[test]
     fn hex_read_with_prefix_big_buffer() {
         let data = b"0xabcdef0123456789";
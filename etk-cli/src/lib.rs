@@ -7,4 +7,7 @@
 
 pub mod errors;
 pub mod io;
+pub mod log;
 pub mod parse;
+pub mod rpc;
+pub mod snippet;
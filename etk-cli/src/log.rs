@@ -0,0 +1,113 @@
+//! Command-line options for controlling log output.
+
+use structopt::StructOpt;
+
+use tracing::level_filters::LevelFilter;
+
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Command-line options that choose how much is logged, and in what format.
+///
+/// Log messages are written to the standard error.
+#[derive(Debug, StructOpt)]
+pub struct LogOptions {
+    #[structopt(
+        short = "v",
+        long = "verbose",
+        global = true,
+        parse(from_occurrences),
+        help = "log more detail, including how long each phase takes (-vv for even more)"
+    )]
+    verbose: u8,
+
+    #[structopt(
+        short = "q",
+        long = "quiet",
+        global = true,
+        conflicts_with = "verbose",
+        help = "only log errors"
+    )]
+    quiet: bool,
+
+    #[structopt(
+        long = "log-json",
+        global = true,
+        help = "log as JSON, one object per line"
+    )]
+    json: bool,
+}
+
+impl LogOptions {
+    /// The most detailed level that will be logged.
+    pub fn level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::ERROR;
+        }
+
+        match self.verbose {
+            0 => LevelFilter::INFO,
+            1 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+
+    /// Install a global subscriber that logs according to these options.
+    ///
+    /// When verbose, the time spent in each span is logged as it closes.
+    pub fn init(&self) {
+        let level = self.level();
+
+        let timed = level >= LevelFilter::DEBUG;
+
+        let spans = if timed { FmtSpan::CLOSE } else { FmtSpan::NONE };
+
+        let builder = tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_max_level(level)
+            .with_span_events(spans);
+
+        if self.json {
+            builder.json().init();
+        } else if timed {
+            builder.with_target(false).init();
+        } else {
+            // Span timings are only shown alongside timestamps, and neither is
+            // interesting at the default level.
+            builder.without_time().with_target(false).init();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use structopt::clap::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn default_level() {
+        let opts = LogOptions::from_iter_safe(&["exe"]).unwrap();
+        assert_eq!(opts.level(), LevelFilter::INFO);
+    }
+
+    #[test]
+    fn verbose_levels() {
+        let opts = LogOptions::from_iter_safe(&["exe", "-v"]).unwrap();
+        assert_eq!(opts.level(), LevelFilter::DEBUG);
+
+        let opts = LogOptions::from_iter_safe(&["exe", "-vv"]).unwrap();
+        assert_eq!(opts.level(), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn quiet() {
+        let opts = LogOptions::from_iter_safe(&["exe", "-q"]).unwrap();
+        assert_eq!(opts.level(), LevelFilter::ERROR);
+    }
+
+    #[test]
+    fn quiet_conflicts_with_verbose() {
+        let err = LogOptions::from_iter_safe(&["exe", "-q", "-v"]).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ArgumentConflict);
+    }
+}
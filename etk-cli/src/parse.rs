@@ -50,8 +50,39 @@ where
     type Err = FromHexError<<T as FromHex>::Error>;
 
     fn from_str(txt: &str) -> Result<Self, Self::Err> {
-        let rest = txt.strip_prefix("0x").ok_or(FromHexError::Prefix)?;
-        let item = T::from_hex(rest).map_err(FromHexError::Hex)?;
+        let txt = txt.trim_start();
+        let rest = txt
+            .strip_prefix("0x")
+            .or_else(|| txt.strip_prefix("0X"))
+            .ok_or(FromHexError::Prefix)?;
+
+        // Allow hex that was wrapped or spaced out when it was copied.
+        let digits: String = rest.split_whitespace().collect();
+
+        let item = T::from_hex(digits).map_err(FromHexError::Hex)?;
         Ok(Self(item))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_from_str() {
+        let Hex(bytes) = "0xc0de".parse::<Hex<Vec<u8>>>().unwrap();
+        assert_eq!(bytes, [0xc0, 0xde]);
+
+        let Hex(bytes) = " 0XC0 De\n01\n".parse::<Hex<Vec<u8>>>().unwrap();
+        assert_eq!(bytes, [0xc0, 0xde, 0x01]);
+
+        assert!(matches!(
+            "c0de".parse::<Hex<Vec<u8>>>(),
+            Err(FromHexError::Prefix)
+        ));
+        assert!(matches!(
+            "0xc0d".parse::<Hex<Vec<u8>>>(),
+            Err(FromHexError::Hex(_))
+        ));
+    }
+}
@@ -0,0 +1,516 @@
+//! A minimal JSON-RPC client for talking to Ethereum nodes.
+
+mod error {
+    use snafu::{Backtrace, Snafu};
+
+    /// Errors that can occur while making a JSON-RPC request.
+    #[derive(Debug, Snafu)]
+    #[snafu(visibility = "pub(super)")]
+    #[non_exhaustive]
+    pub enum Error {
+        /// The request could not be sent, or the response could not be read.
+        #[snafu(display("request `{}` to `{}` failed", method, url))]
+        #[non_exhaustive]
+        Transport {
+            /// The endpoint the request was sent to.
+            url: String,
+
+            /// The JSON-RPC method that was called.
+            method: String,
+
+            /// The underlying source of this error.
+            source: Box<ureq::Error>,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The response could not be read or wasn't well-formed JSON-RPC.
+        #[snafu(display("malformed response to `{}`", method))]
+        #[non_exhaustive]
+        Malformed {
+            /// The JSON-RPC method that was called.
+            method: String,
+
+            /// The underlying source of this error.
+            source: Box<dyn std::error::Error>,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// The node responded with an error object.
+        #[snafu(display("`{}` failed with code {}: {}", method, code, message))]
+        #[non_exhaustive]
+        Response {
+            /// The JSON-RPC method that was called.
+            method: String,
+
+            /// The error code returned by the node.
+            code: i64,
+
+            /// The error message returned by the node.
+            message: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// A cached response could not be read or written.
+        #[snafu(display("could not use the cache at `{}`", path.display()))]
+        #[non_exhaustive]
+        Cache {
+            /// The cache file that failed.
+            path: std::path::PathBuf,
+
+            /// The underlying source of this error.
+            source: std::io::Error,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+
+        /// A quantity returned by the node wasn't valid hexadecimal.
+        #[snafu(display("invalid quantity `{}`", text))]
+        #[non_exhaustive]
+        Quantity {
+            /// The text that failed to parse.
+            text: String,
+
+            /// The location of the error.
+            backtrace: Backtrace,
+        },
+    }
+}
+
+pub use self::error::Error;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use serde_json::{json, Value};
+
+use snafu::{OptionExt, ResultExt};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Encode `n` as a JSON-RPC quantity (ex. `0x2a`.)
+pub fn quantity(n: u128) -> String {
+    format!("{:#x}", n)
+}
+
+/// Parse a JSON-RPC quantity (ex. `0x2a`) into an integer.
+pub fn parse_quantity(text: &str) -> Result<u128, Error> {
+    text.strip_prefix("0x")
+        .and_then(|digits| u128::from_str_radix(digits, 16).ok())
+        .context(error::Quantity { text })
+}
+
+/// Returns true if `block` is a block number, and not a tag like `latest`
+/// whose block changes over time.
+fn is_block_number(block: &str) -> bool {
+    parse_quantity(block).is_ok()
+}
+
+/// Returns true if `param` can be used as part of a file name.
+fn is_plain(param: &str) -> bool {
+    !param.is_empty() && param.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// A directory of responses that can't change once a block is final, like the
+/// code of an account or a storage slot at a particular block.
+///
+/// Responses are stored under the chain id, then the block number, so the
+/// same cache can be shared between endpoints. Since the chain id is needed to
+/// find a response, the chain id of each endpoint is cached too.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Create a `Cache` that keeps its files in `dir`, creating it as needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory this cache keeps its files in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn chains_path(&self) -> PathBuf {
+        self.dir.join("chains.json")
+    }
+
+    fn chains(&self) -> Result<BTreeMap<String, String>, Error> {
+        let path = self.chains_path();
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok(serde_json::from_str(&text).unwrap_or_default()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e).context(error::Cache { path }),
+        }
+    }
+
+    fn chain_id(&self, url: &str) -> Result<Option<u128>, Error> {
+        match self.chains()?.get(url) {
+            Some(id) => parse_quantity(id).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn set_chain_id(&self, url: &str, chain_id: u128) -> Result<(), Error> {
+        let mut chains = self.chains()?;
+        chains.insert(url.to_owned(), quantity(chain_id));
+        self.write(&self.chains_path(), &chains)
+    }
+
+    /// Where the response to `method` with `params` is kept, where the last
+    /// of `params` is the block number.
+    fn path(&self, chain_id: u128, method: &str, params: &[&str]) -> PathBuf {
+        let (block, rest) = params.split_last().unwrap();
+        self.dir
+            .join(chain_id.to_string())
+            .join(block)
+            .join(method)
+            .join(format!("{}.json", rest.join("-")))
+    }
+
+    fn read(&self, path: &Path) -> Result<Option<Value>, Error> {
+        match fs::read_to_string(path) {
+            // A damaged entry is treated as missing, and gets overwritten.
+            Ok(text) => Ok(serde_json::from_str(&text).ok()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(error::Cache { path }),
+        }
+    }
+
+    fn write<T: Serialize>(&self, path: &Path, value: &T) -> Result<(), Error> {
+        let text = serde_json::to_string(value).unwrap();
+        fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| fs::write(path, text))
+            .context(error::Cache { path })
+    }
+}
+
+/// A blocking client for a single JSON-RPC endpoint.
+#[derive(Debug)]
+pub struct Client {
+    url: String,
+    agent: ureq::Agent,
+    next_id: u64,
+    cache: Option<Cache>,
+    chain_id: Option<u128>,
+}
+
+impl Client {
+    /// Create a new `Client` that sends requests to `url`.
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .build();
+
+        Self {
+            url: url.into(),
+            agent,
+            next_id: 1,
+            cache: None,
+            chain_id: None,
+        }
+    }
+
+    /// Keep the responses of [`Client::request_at`] in `cache`, and answer
+    /// from it when possible.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The endpoint this client sends requests to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Call `method` with `params`, and deserialize the result as `R`.
+    pub fn request<P, R>(&mut self, method: &str, params: P) -> Result<R, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let text = self
+            .agent
+            .post(&self.url)
+            .send_json(body)
+            .map_err(Box::new)
+            .with_context(|| error::Transport {
+                url: self.url.clone(),
+                method,
+            })?
+            .into_string()
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .context(error::Malformed { method })?;
+
+        let mut response: Value = serde_json::from_str(&text)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .context(error::Malformed { method })?;
+
+        if let Some(err) = response.get("error") {
+            return error::Response {
+                method,
+                code: err["code"].as_i64().unwrap_or_default(),
+                message: err["message"].as_str().unwrap_or_default(),
+            }
+            .fail();
+        }
+
+        let result = response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or_default();
+
+        serde_json::from_value(result)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .context(error::Malformed { method })
+    }
+
+    /// Call `method` with `params`, and parse the result as a quantity.
+    pub fn request_quantity<P>(&mut self, method: &str, params: P) -> Result<u128, Error>
+    where
+        P: Serialize,
+    {
+        let text: String = self.request(method, params)?;
+        parse_quantity(&text)
+    }
+
+    /// The chain id of the endpoint, only asking the endpoint once.
+    pub fn chain_id(&mut self) -> Result<u128, Error> {
+        if let Some(id) = self.chain_id {
+            return Ok(id);
+        }
+
+        let cached = match self.cache {
+            Some(ref cache) => cache.chain_id(&self.url)?,
+            None => None,
+        };
+
+        let id = match cached {
+            Some(id) => id,
+            None => {
+                let id = self.request_quantity("eth_chainId", ())?;
+                if let Some(ref cache) = self.cache {
+                    cache.set_chain_id(&self.url, id)?;
+                }
+                id
+            }
+        };
+
+        self.chain_id = Some(id);
+        Ok(id)
+    }
+
+    /// Call `method` with `params`, where the last of `params` is a block
+    /// number or tag, like `eth_getCode` or `eth_getStorageAt`.
+    ///
+    /// With a cache, responses at a block number are kept, so asking again
+    /// for the same address or slot at that block doesn't need the endpoint.
+    /// Responses at a tag like `latest` are never cached.
+    pub fn request_at<R>(&mut self, method: &str, params: &[&str]) -> Result<R, Error>
+    where
+        R: Serialize + DeserializeOwned,
+    {
+        let cacheable = match params.last() {
+            Some(block) => is_block_number(block) && params.iter().all(|p| is_plain(p)),
+            None => false,
+        };
+
+        if self.cache.is_none() || !cacheable {
+            return self.request(method, params);
+        }
+
+        let chain_id = self.chain_id()?;
+        let cache = self.cache.clone().unwrap();
+        let path = cache.path(chain_id, method, params);
+
+        if let Some(value) = cache.read(&path)? {
+            if let Ok(response) = serde_json::from_value(value) {
+                return Ok(response);
+            }
+        }
+
+        let response: R = self.request(method, params)?;
+        cache.write(&path, &response)?;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    use super::*;
+
+    fn serve_once(body: &'static str) -> (String, JoinHandle<String>) {
+        let (url, handle) = serve(vec![body]);
+        (
+            url,
+            std::thread::spawn(move || handle.join().unwrap().remove(0)),
+        )
+    }
+
+    /// Answer one request with each of `bodies`, in order.
+    fn serve(bodies: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            bodies
+                .into_iter()
+                .map(|body| answer(&listener, body))
+                .collect()
+        });
+
+        (url, handle)
+    }
+
+    fn answer(listener: &TcpListener, body: &str) -> String {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+
+            let lower = line.to_ascii_lowercase();
+            if let Some(value) = lower.strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+
+        let mut request = vec![0u8; length];
+        reader.read_exact(&mut request).unwrap();
+
+        let mut stream = reader.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+
+        String::from_utf8(request).unwrap()
+    }
+
+    #[test]
+    fn parse_quantity_zero() {
+        assert_eq!(parse_quantity("0x0").unwrap(), 0);
+    }
+
+    #[test]
+    fn parse_quantity_no_prefix() {
+        assert_matches!(parse_quantity("2a"), Err(Error::Quantity { .. }));
+    }
+
+    #[test]
+    fn quantity_round_trip() {
+        assert_eq!(quantity(0x2a), "0x2a");
+        assert_eq!(
+            parse_quantity(&quantity(1_000_000_007)).unwrap(),
+            1_000_000_007
+        );
+    }
+
+    #[test]
+    fn request_result() {
+        let (url, handle) = serve_once(r#"{"jsonrpc":"2.0","id":1,"result":"0x539"}"#);
+
+        let mut client = Client::new(url);
+        let chain_id = client.request_quantity("eth_chainId", ()).unwrap();
+        assert_eq!(chain_id, 1337);
+
+        let request: Value = serde_json::from_str(&handle.join().unwrap()).unwrap();
+        assert_eq!(request["method"], "eth_chainId");
+        assert_eq!(request["id"], 1);
+    }
+
+    #[test]
+    fn request_error() {
+        let (url, handle) = serve_once(
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"nonce too low"}}"#,
+        );
+
+        let mut client = Client::new(url);
+        let err = client
+            .request::<_, String>("eth_sendRawTransaction", ["0x00"])
+            .unwrap_err();
+        handle.join().unwrap();
+
+        assert_matches!(
+            err,
+            Error::Response { code: -32000, message, .. } if message == "nonce too low"
+        );
+    }
+
+    #[test]
+    fn request_at_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let (url, handle) = serve(vec![
+            r#"{"jsonrpc":"2.0","id":1,"result":"0x539"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"result":"0x6001"}"#,
+        ]);
+
+        let address = "0x5fbdb2315678afecb367f032d93f642f64180aa3";
+
+        let mut client = Client::new(&url).with_cache(Cache::new(dir.path()));
+        let code: String = client
+            .request_at("eth_getCode", &[address, "0x10"])
+            .unwrap();
+        assert_eq!(code, "0x6001");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let path = dir
+            .path()
+            .join("1337")
+            .join("0x10")
+            .join("eth_getCode")
+            .join(format!("{}.json", address));
+        assert!(path.exists());
+
+        // The server is gone, so these can only come from the cache.
+        let mut client = Client::new(&url).with_cache(Cache::new(dir.path()));
+        assert_eq!(client.chain_id().unwrap(), 1337);
+        let code: String = client
+            .request_at("eth_getCode", &[address, "0x10"])
+            .unwrap();
+        assert_eq!(code, "0x6001");
+
+        let err = client
+            .request_at::<String>("eth_getCode", &[address, "latest"])
+            .unwrap_err();
+        assert_matches!(err, Error::Transport { .. });
+    }
+}
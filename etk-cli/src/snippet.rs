@@ -0,0 +1,324 @@
+//! Rendering excerpts of source code, with the interesting regions underlined
+//! and labelled.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// A labelled region of source code, as one-based line and column numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// The line and column where the region begins.
+    pub start: (usize, usize),
+
+    /// The line and column just past the end of the region.
+    pub end: (usize, usize),
+
+    /// Text printed beside the underline. May be empty.
+    pub message: String,
+
+    /// Whether this is the region the problem is about, as opposed to one
+    /// that gives context (like where a label was first declared.)
+    pub primary: bool,
+}
+
+impl Label {
+    /// A label for the region a problem is about.
+    pub fn primary<M>(start: (usize, usize), end: (usize, usize), message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self {
+            start,
+            end,
+            message: message.into(),
+            primary: true,
+        }
+    }
+
+    /// A label for a region that gives context to a problem.
+    pub fn secondary<M>(start: (usize, usize), end: (usize, usize), message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self {
+            start,
+            end,
+            message: message.into(),
+            primary: false,
+        }
+    }
+}
+
+/// When to print in color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when the standard error is a terminal, and the `NO_COLOR`
+    /// environment variable isn't set.
+    Auto,
+
+    /// Always.
+    Always,
+
+    /// Never.
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output to the standard error should be colored.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && atty::is(atty::Stream::Stderr)
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice `{}`", text)),
+        }
+    }
+}
+
+/// ANSI escape codes, or nothing when color is disabled.
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    color: bool,
+}
+
+impl Style {
+    /// Make a new `Style`, which only colors text if `color` is set.
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// Bold red, for errors.
+    pub fn error(self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    /// Bold yellow, for warnings.
+    pub fn warning(self, text: &str) -> String {
+        self.paint("1;33", text)
+    }
+
+    /// Bold blue, for line numbers and secondary labels.
+    pub fn note(self, text: &str) -> String {
+        self.paint("1;34", text)
+    }
+
+    /// Bold, for messages.
+    pub fn bold(self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
+
+/// Render the lines of `source` (the contents of the file named `origin`)
+/// that `labels` refer to, with each label's region underlined.
+///
+/// Labels that refer to lines past the end of `source` are left out.
+///
+/// ## Example
+///
+/// ```rust
+/// use etk_cli::snippet::{render, Label, Style};
+///
+/// let source = "a:\nstop\na:\n";
+/// let labels = [
+///     Label::primary((3, 1), (3, 2), "declared again here"),
+///     Label::secondary((1, 1), (1, 2), "first declared here"),
+/// ];
+///
+/// let expected = "  --> main.etk:3:1
+///   |
+/// 1 | a:
+///   | - first declared here
+/// 2 | stop
+/// 3 | a:
+///   | ^ declared again here
+///   |
+/// ";
+///
+/// assert_eq!(render("main.etk", source, &labels, Style::new(false)), expected);
+/// ```
+pub fn render(origin: &str, source: &str, labels: &[Label], style: Style) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+
+    let mut labels: Vec<&Label> = labels
+        .iter()
+        .filter(|l| 0 < l.start.0 && l.start.0 <= lines.len())
+        .collect();
+    labels.sort_by_key(|l| (l.start, !l.primary));
+
+    let mut out = String::new();
+
+    let first = labels.iter().find(|l| l.primary).or_else(|| labels.first());
+    let first = match first {
+        Some(f) => f,
+        None => return out,
+    };
+
+    let last_line = labels.iter().map(|l| l.start.0).max().unwrap();
+    let width = last_line.to_string().len();
+    let gutter = format!("{:width$} {}", "", style.note("|"), width = width);
+
+    writeln!(
+        out,
+        "{:width$}{} {}:{}:{}",
+        "",
+        style.note("-->"),
+        origin,
+        first.start.0,
+        first.start.1,
+        width = width + 1,
+    )
+    .unwrap();
+    writeln!(out, "{}", gutter).unwrap();
+
+    let mut previous: Option<usize> = None;
+
+    for (idx, label) in labels.iter().enumerate() {
+        let line = label.start.0;
+
+        if previous != Some(line) {
+            // Show a line of context between labels close together, and an
+            // ellipsis for anything further apart.
+            match previous {
+                Some(p) if line == p + 2 => {
+                    let number = style.note(&format!("{:width$} |", p + 1, width = width));
+                    writeln!(out, "{} {}", number, lines[p]).unwrap();
+                }
+                Some(p) if line > p + 2 => writeln!(out, "{}", style.note("...")).unwrap(),
+                _ => (),
+            }
+
+            let number = style.note(&format!("{:width$} |", line, width = width));
+            writeln!(out, "{} {}", number, lines[line - 1]).unwrap();
+            previous = Some(line);
+        }
+
+        write!(out, "{} ", gutter).unwrap();
+        underline(&mut out, lines[line - 1], label, style);
+
+        if idx + 1 == labels.len() {
+            writeln!(out, "{}", gutter).unwrap();
+        }
+    }
+
+    out
+}
+
+fn underline(out: &mut String, text: &str, label: &Label, style: Style) {
+    let chars: Vec<char> = text.chars().collect();
+    let start = label.start.1.max(1) - 1;
+
+    // Regions spanning several lines are underlined to the end of the first.
+    let end = if label.end.0 == label.start.0 {
+        label.end.1.saturating_sub(1)
+    } else {
+        chars.len()
+    };
+
+    let len = end.saturating_sub(start).max(1);
+
+    // Keep tabs, so the underline lines up however wide they're displayed.
+    for idx in 0..start {
+        out.push(match chars.get(idx) {
+            Some('\t') => '\t',
+            _ => ' ',
+        });
+    }
+
+    let mark = if label.primary { "^" } else { "-" };
+    let mut marks = mark.repeat(len);
+
+    if !label.message.is_empty() {
+        marks.push(' ');
+        marks.push_str(&label.message);
+    }
+
+    let marks = if label.primary {
+        style.error(&marks)
+    } else {
+        style.note(&marks)
+    };
+
+    writeln!(out, "{}", marks).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_label() {
+        let source = "stop\n  pusj1 1\n";
+        let labels = [Label::primary((2, 3), (2, 3), "expected an instruction")];
+
+        let expected = "  --> main.etk:2:3
+  |
+2 |   pusj1 1
+  |   ^ expected an instruction
+  |
+";
+
+        assert_eq!(
+            render("main.etk", source, &labels, Style::new(false)),
+            expected
+        );
+    }
+
+    #[test]
+    fn distant_labels() {
+        let source = "a:\n1\n2\n3\n4\n5\n6\n7\n8\n9\n\ta:\n";
+        let labels = [
+            Label::secondary((1, 1), (1, 3), "first"),
+            Label::primary((11, 2), (11, 4), ""),
+        ];
+
+        let expected = "   --> main.etk:11:2
+   |
+ 1 | a:
+   | -- first
+...
+11 | \ta:
+   | \t^^
+   |
+";
+
+        assert_eq!(
+            render("main.etk", source, &labels, Style::new(false)),
+            expected
+        );
+    }
+
+    #[test]
+    fn past_the_end() {
+        let labels = [Label::primary((4, 1), (4, 1), "")];
+        assert_eq!(render("main.etk", "stop\n", &labels, Style::new(false)), "");
+    }
+
+    #[test]
+    fn colors() {
+        let labels = [Label::primary((1, 1), (1, 5), "")];
+        let out = render("main.etk", "stop\n", &labels, Style::new(true));
+        assert!(out.contains("\x1b[1;31m^^^^\x1b[0m"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Properties that hold for every program.
+//!
+//! Each function returns a [`TestCaseError`] describing the first violation,
+//! so they can be used with `?` inside [`proptest::proptest!`].
+
+use etk_asm::asm::Assembler;
+use etk_asm::disasm::Disassembler;
+use etk_asm::fuzzing;
+use etk_asm::ops::{AbstractOp, ConcreteOp, Specifier};
+
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use std::io::Write;
+
+/// Assemble `ops` into bytecode.
+pub fn assemble<I>(ops: I) -> Result<Vec<u8>, TestCaseError>
+where
+    I: IntoIterator,
+    I::Item: Into<AbstractOp>,
+{
+    let mut asm = Assembler::new();
+
+    let result = asm.push_all(ops.into_iter().map(Into::into));
+    let output = asm.take();
+
+    result
+        .and_then(|_| asm.finish())
+        .map_err(|e| TestCaseError::fail(format!("assembling failed: {}", e)))?;
+
+    Ok(output)
+}
+
+/// Disassemble as much of `code` as possible.
+///
+/// Returns the instructions, and the number of bytes they cover. Only a
+/// truncated push at the end of `code` is left over.
+pub fn disassemble(code: &[u8]) -> Result<(Vec<ConcreteOp>, usize), TestCaseError> {
+    let mut dasm = Disassembler::new();
+    dasm.write_all(code).unwrap();
+
+    let mut ops = Vec::new();
+    let mut consumed = 0;
+
+    for offset in dasm.ops() {
+        prop_assert_eq!(offset.offset, consumed, "instructions aren't contiguous");
+        consumed += offset.item.size() as usize;
+        ops.push(offset.item);
+    }
+
+    let remaining = &code[consumed..];
+
+    match remaining.first() {
+        None => prop_assert!(dasm.finish().is_ok(), "disassembler didn't finish"),
+        Some(first) => {
+            let spec = Specifier::from(*first);
+            prop_assert!(
+                (remaining.len() as u32) < spec.size(),
+                "{} bytes left over after {}",
+                remaining.len(),
+                spec,
+            );
+            prop_assert!(dasm.finish().is_err(), "truncated instruction accepted");
+        }
+    }
+
+    Ok((ops, consumed))
+}
+
+/// Print `ops` as source code, one instruction per line.
+pub fn print(ops: &[AbstractOp]) -> String {
+    let mut text = String::new();
+
+    for op in ops {
+        text.push_str(&op.to_string());
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Parse `text` into instructions.
+pub fn parse(text: &str) -> Result<Vec<AbstractOp>, TestCaseError> {
+    fuzzing::parse_ops(text).map_err(|e| TestCaseError::fail(format!("parsing failed: {}", e)))
+}
+
+/// Assembling `ops`, disassembling the result, and assembling it again
+/// produces the same bytecode.
+pub fn assemble_round_trip(ops: &[AbstractOp]) -> Result<(), TestCaseError> {
+    let code = assemble(ops.iter().cloned())?;
+
+    let (disassembled, consumed) = disassemble(&code)?;
+    prop_assert_eq!(consumed, code.len(), "assembled a truncated instruction");
+
+    let reassembled = assemble(disassembled)?;
+    prop_assert_eq!(code, reassembled);
+
+    Ok(())
+}
+
+/// Disassembling `code` and assembling the instructions produces the same
+/// bytecode, except for any truncated push at the end.
+pub fn disassemble_round_trip(code: &[u8]) -> Result<(), TestCaseError> {
+    let (ops, consumed) = disassemble(code)?;
+
+    let reassembled = assemble(ops)?;
+    prop_assert_eq!(&code[..consumed], reassembled.as_slice());
+
+    Ok(())
+}
+
+/// Printing `ops` and parsing the text produces the same instructions, so
+/// printing again produces the same text.
+pub fn print_round_trip(ops: &[AbstractOp]) -> Result<(), TestCaseError> {
+    let text = print(ops);
+    let parsed = parse(&text)?;
+
+    prop_assert_eq!(ops, parsed.as_slice(), "when parsing:\n{}", text);
+    prop_assert_eq!(&text, &print(&parsed));
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Property testing helpers for the EVM Toolkit.
+//!
+//! The [`strategy`] module generates random programs and bytecode with
+//! [`proptest`], and the [`check`] module asserts the properties every
+//! program should have, like surviving a trip through the disassembler.
+//!
+//! Crates that build on `etk-asm` can use both to test their own
+//! transformations:
+//!
+//! ```no_run
+//! use etk_testing::{check, strategy};
+//!
+//! use proptest::proptest;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn my_pass_round_trips(ops in strategy::program()) {
+//!         // Transform `ops` here.
+//!         check::assemble_round_trip(&ops)?;
+//!     }
+//! }
+//! ```
+#![deny(unsafe_code)]
+#![deny(missing_docs)]
+#![deny(unreachable_pub)]
+#![deny(missing_debug_implementations)]
+
+pub mod check;
+pub mod strategy;
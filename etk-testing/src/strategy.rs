@@ -0,0 +1,113 @@
+//! [`Strategy`] implementations for instructions, programs, and bytecode.
+
+use etk_asm::ops::{AbstractOp, Concrete, ConcreteOp, Imm, Op, Specifier};
+
+use proptest::collection::{hash_set, vec};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+/// Largest number of instructions in a generated program, not counting
+/// labels.
+const MAX_OPS: usize = 64;
+
+/// Largest number of labels declared in a generated program.
+const MAX_LABELS: usize = 8;
+
+/// Largest generated bytecode, in bytes.
+const MAX_BYTECODE: usize = 256;
+
+/// Any specifier, including undefined instructions like `invalid_0c`.
+pub fn specifier() -> impl Strategy<Value = Specifier> {
+    any::<u8>().prop_map(Specifier::from)
+}
+
+/// Specifiers the assembler accepts in source code.
+///
+/// Undefined instructions (like `invalid_0c`) can be disassembled, but can
+/// only be written in assembly with `%raw`, so they're left out.
+pub fn defined_specifier() -> impl Strategy<Value = Specifier> {
+    specifier().prop_filter("undefined instruction", |spec| spec.is_defined())
+}
+
+/// Instructions with random immediates, drawn from [`defined_specifier`].
+pub fn concrete_op() -> impl Strategy<Value = ConcreteOp> {
+    defined_specifier().prop_flat_map(|spec| {
+        let len = spec.size() as usize - 1;
+        vec(any::<u8>(), len).prop_map(move |imm| {
+            if imm.is_empty() {
+                Op::new(spec).unwrap()
+            } else {
+                Op::<Concrete>::with_immediate(spec, &imm).unwrap()
+            }
+        })
+    })
+}
+
+/// Names the assembler accepts as labels.
+pub fn label() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9_]{0,7}"
+}
+
+/// An instruction in a program that hasn't had its labels picked yet.
+#[derive(Debug, Clone)]
+enum Item {
+    Op(ConcreteOp),
+
+    /// A `pushN` (or `%push` if `None`) of the label chosen by `Index`.
+    Push(Option<Specifier>, Index),
+}
+
+fn item() -> impl Strategy<Value = Item> {
+    // Programs are small enough that every address fits in two bytes.
+    let push = prop_oneof![Just(None), (2u32..=32).prop_map(Specifier::push)];
+
+    prop_oneof![
+        3 => concrete_op().prop_map(Item::Op),
+        1 => (push, any::<Index>()).prop_map(|(spec, index)| Item::Push(spec, index)),
+    ]
+}
+
+/// Programs that assemble without errors.
+///
+/// Each label is declared exactly once, and may be pushed any number of
+/// times, with sized pushes (like `push2 label`) or `%push(label)`.
+///
+/// `%push` only accepts labels in source code, so constants are always pushed
+/// with a sized push.
+pub fn program() -> impl Strategy<Value = Vec<AbstractOp>> {
+    hash_set(label(), 0..=MAX_LABELS).prop_flat_map(|labels| {
+        let labels: Vec<_> = labels.into_iter().collect();
+        let positions = vec(any::<Index>(), labels.len());
+        (Just(labels), vec(item(), 0..=MAX_OPS), positions).prop_map(
+            |(labels, items, positions)| {
+                let mut ops: Vec<AbstractOp> = items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Item::Op(op) => Some(op.into()),
+                        Item::Push(_, _) if labels.is_empty() => None,
+                        Item::Push(None, index) => {
+                            let label = index.get(&labels).as_str();
+                            Some(AbstractOp::Push(Imm::from(label)))
+                        }
+                        Item::Push(Some(spec), index) => {
+                            let label = index.get(&labels).as_str();
+                            Some(AbstractOp::with_label(spec, label))
+                        }
+                    })
+                    .collect();
+
+                for (label, position) in labels.into_iter().zip(positions) {
+                    let at = position.index(ops.len() + 1);
+                    ops.insert(at, AbstractOp::Label(label.into()));
+                }
+
+                ops
+            },
+        )
+    })
+}
+
+/// Arbitrary bytes, which may or may not disassemble cleanly.
+pub fn bytecode() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..=MAX_BYTECODE)
+}
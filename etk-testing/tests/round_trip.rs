@@ -0,0 +1,26 @@
+use etk_testing::{check, strategy};
+
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn assemble_round_trip(ops in strategy::program()) {
+        check::assemble_round_trip(&ops)?;
+    }
+
+    #[test]
+    fn disassemble_round_trip(code in strategy::bytecode()) {
+        check::disassemble_round_trip(&code)?;
+    }
+
+    #[test]
+    fn print_round_trip(ops in strategy::program()) {
+        check::print_round_trip(&ops)?;
+    }
+
+    #[test]
+    fn print_disassembled_round_trip(ops in prop::collection::vec(strategy::concrete_op(), 0..64)) {
+        let ops: Vec<_> = ops.into_iter().map(Into::into).collect();
+        check::print_round_trip(&ops)?;
+    }
+}